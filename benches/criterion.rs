@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fmt::Write as _;
+
+/// Number of functions in the generated shader, which makes for a few megabytes of SPIR-V.
+const FUNCTION_COUNT: usize = 4000;
+
+/// Builds a large SPIR-V module out of many small functions, so that reading it is
+/// dominated by the per-instruction cost of the parser rather than by any single construct.
+fn large_spirv() -> Vec<u8> {
+    let mut source = String::new();
+    for i in 0..FUNCTION_COUNT {
+        writeln!(
+            source,
+            "fn f{i}(a: vec4<f32>, b: f32) -> vec4<f32> {{
+                var v: vec4<f32> = a * b;
+                let m = mat2x2<f32>(v.xy, v.zw);
+                v.x = dot(m[0], m[1]) + {i}.0;
+                if (v.y > b) {{
+                    v = v.wzyx;
+                }}
+                return normalize(v) + vec4<f32>(v.w, v.z, v.y, v.x);
+            }}",
+            i = i
+        )
+        .unwrap();
+    }
+    source.push_str(
+        "[[stage(compute), workgroup_size(1)]]
+        fn main() {
+            let x = f0(vec4<f32>(1.0), 2.0);
+        }",
+    );
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let words =
+        naga::back::spv::write_vec(&module, &info, &naga::back::spv::Options::default()).unwrap();
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+fn spv_parse(c: &mut Criterion) {
+    let data = large_spirv();
+    let mut group = c.benchmark_group("spv-in");
+    group.throughput(criterion::Throughput::Bytes(data.len() as u64));
+    group.sample_size(20);
+    group.bench_function("large", |b| {
+        b.iter(|| {
+            let options = naga::front::spv::Options::default();
+            naga::front::spv::parse_u8_slice(&data, &options).unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, spv_parse);
+criterion_main!(benches);
@@ -28,6 +28,14 @@ struct Args {
     #[argh(option)]
     profile: Option<GlslProfileArg>,
 
+    /// the metal version to use, for example, `1.0`, `1.1`, `1.2`, `2.0`, `2.1`
+    #[argh(option)]
+    msl_version: Option<MslVersionArg>,
+
+    /// path to a RON file with the per-stage binding map, if targeting Metal
+    #[argh(option)]
+    binding_map: Option<String>,
+
     /// the shader model to use if targeting HSLS
     ///
     /// May be `50`, 51`, or `60`
@@ -84,6 +92,33 @@ impl FromStr for ShaderModelArg {
     }
 }
 
+/// Newtype so we can implement [`FromStr`] for the Metal language version.
+#[derive(Debug, Clone)]
+struct MslVersionArg((u8, u8));
+
+impl FromStr for MslVersionArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.split('.');
+
+        let check_value = |iter: &mut core::str::Split<_>| {
+            iter.next()
+                .ok_or_else(|| format!("Invalid value for --msl-version: {}", s))?
+                .parse::<u8>()
+                .map_err(|err| format!("Invalid value for --msl-version: {}: {}", s, err))
+        };
+
+        let major = check_value(&mut iter)?;
+        let minor = check_value(&mut iter)?;
+        if iter.next().is_some() {
+            return Err(format!("Invalid value for --msl-version: {}", s));
+        }
+
+        Ok(Self((major, minor)))
+    }
+}
+
 /// Newtype so we can implement [`FromStr`] for [`naga::back::glsl::Version`].
 #[derive(Clone, Debug)]
 struct GlslProfileArg(naga::back::glsl::Version);
@@ -191,6 +226,15 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     if let Some(version) = args.profile {
         params.glsl.version = version.0;
     }
+    if let Some(version) = args.msl_version {
+        params.msl.lang_version = version.0;
+    }
+    if let Some(ref path) = args.binding_map {
+        let map = fs::read_to_string(path)?;
+        params.msl.per_stage_map = ron::de::from_str(&map)?;
+        // the bindings are explicit now, so missing ones are an error
+        params.msl.fake_missing_bindings = false;
+    }
     if let Some(model) = args.shader_model {
         params.hlsl.shader_model = model.0;
     }
@@ -212,26 +256,22 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
         "wgsl" => {
             let input = fs::read_to_string(input_path)?;
-            let result = naga::front::wgsl::parse_str(&input);
+            let result = naga::front::wgsl::parse_str_recovering(&input);
             match result {
                 Ok(v) => v,
-                Err(ref e) => {
-                    e.emit_to_stderr(&input);
+                Err(ref errors) => {
+                    for e in errors {
+                        e.emit_to_stderr(&input);
+                    }
                     return Err(CliError("Could not parse WGSL").into());
                 }
             }
         }
         "vert" => {
             let input = fs::read_to_string(input_path)?;
-            let mut entry_points = naga::FastHashMap::default();
-            entry_points.insert("main".to_string(), naga::ShaderStage::Vertex);
             naga::front::glsl::parse_str(
                 &input,
-                &naga::front::glsl::Options {
-                    entry_points,
-                    defines: Default::default(),
-                    strip_unused_linkages: false,
-                },
+                &naga::front::glsl::Options::from_stage(naga::ShaderStage::Vertex),
             )
             .unwrap_or_else(|err| {
                 let filename = input_path.file_name().and_then(std::ffi::OsStr::to_str);
@@ -241,15 +281,9 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
         "frag" => {
             let input = fs::read_to_string(input_path)?;
-            let mut entry_points = naga::FastHashMap::default();
-            entry_points.insert("main".to_string(), naga::ShaderStage::Fragment);
             naga::front::glsl::parse_str(
                 &input,
-                &naga::front::glsl::Options {
-                    entry_points,
-                    defines: Default::default(),
-                    strip_unused_linkages: false,
-                },
+                &naga::front::glsl::Options::from_stage(naga::ShaderStage::Fragment),
             )
             .unwrap_or_else(|err| {
                 let filename = input_path.file_name().and_then(std::ffi::OsStr::to_str);
@@ -259,15 +293,9 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
         "comp" => {
             let input = fs::read_to_string(input_path)?;
-            let mut entry_points = naga::FastHashMap::default();
-            entry_points.insert("main".to_string(), naga::ShaderStage::Compute);
             naga::front::glsl::parse_str(
                 &input,
-                &naga::front::glsl::Options {
-                    entry_points,
-                    defines: Default::default(),
-                    strip_unused_linkages: false,
-                },
+                &naga::front::glsl::Options::from_stage(naga::ShaderStage::Compute),
             )
             .unwrap_or_else(|err| {
                 let filename = input_path.file_name().and_then(std::ffi::OsStr::to_str);
@@ -371,6 +399,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                         "comp" => naga::ShaderStage::Compute,
                         _ => unreachable!(),
                     },
+                    multiview: None,
                 };
 
                 let mut buffer = String::new();
@@ -1,3 +1,4 @@
+use crate::FastHashMap;
 use std::{cmp::Ordering, fmt, hash, marker::PhantomData, num::NonZeroU32, ops};
 
 /// An unique index in the arena array that a handle points to.
@@ -73,6 +74,12 @@ impl<T> Handle<T> {
         let index = self.index.get() - 1;
         index as usize
     }
+
+    /// Convert a zero-based index into a handle.
+    pub(crate) fn from_usize(index: usize) -> Self {
+        let index = Index::new(index as u32 + 1).expect("Handle index overflows");
+        Handle::new(index)
+    }
 }
 
 /// A strongly typed range of handles.
@@ -101,6 +108,21 @@ impl<T> fmt::Debug for Range<T> {
         write!(formatter, "[{}..{}]", self.inner.start + 1, self.inner.end)
     }
 }
+impl<T> hash::Hash for Range<T> {
+    fn hash<H: hash::Hasher>(&self, hasher: &mut H) {
+        self.inner.hash(hasher)
+    }
+}
+impl<T> Range<T> {
+    /// Return a range covering the handles from `first` to `last`, inclusive.
+    pub(crate) fn new_from_bounds(first: Handle<T>, last: Handle<T>) -> Self {
+        Range {
+            inner: first.index() as u32..last.index() as u32 + 1,
+            marker: PhantomData,
+        }
+    }
+}
+
 impl<T> Iterator for Range<T> {
     type Item = Handle<T>;
     fn next(&mut self) -> Option<Self::Item> {
@@ -139,6 +161,11 @@ impl<T> Default for Arena<T> {
         Self::new()
     }
 }
+impl<T: hash::Hash> hash::Hash for Arena<T> {
+    fn hash<H: hash::Hasher>(&self, hasher: &mut H) {
+        self.data.hash(hasher)
+    }
+}
 impl<T: fmt::Debug> fmt::Debug for Arena<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_map().entries(self.iter()).finish()
@@ -168,6 +195,9 @@ impl<T> Arena<T> {
 
     /// Returns an iterator over the items stored in this arena, returning both
     /// the item's handle and a reference to it.
+    ///
+    /// The items are visited in the order they were appended, which is also
+    /// the order of their handles.
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = (Handle<T>, &T)> {
         self.data.iter().enumerate().map(|(i, v)| {
             let position = i + 1;
@@ -240,6 +270,15 @@ impl<T> Arena<T> {
             marker: PhantomData,
         }
     }
+
+    /// Remove the items appended since the arena had `length` items.
+    ///
+    /// Together with [`len`](Self::len), this rolls the arena back to an
+    /// earlier state. The handles of the removed items must not be used
+    /// anymore: they will refer to the items appended next.
+    pub fn truncate(&mut self, length: usize) {
+        self.data.truncate(length);
+    }
 }
 
 impl<T> ops::Index<Handle<T>> for Arena<T> {
@@ -256,6 +295,205 @@ impl<T> ops::Index<Range<T>> for Arena<T> {
     }
 }
 
+/// An arena whose elements are guaranteed to be unique.
+///
+/// Inserting a value that is already present returns the handle of the
+/// existing element, so two handles from the same `UniqueArena` are equal
+/// exactly when the values they refer to are. Values are looked up by their
+/// hash, unlike [`Arena::fetch_or_append`], which scans the whole arena.
+///
+/// Elements can't be mutated in place, since that would break uniqueness.
+pub struct UniqueArena<T> {
+    /// Values of this arena.
+    data: Vec<T>,
+    /// Handles of the values, grouped by the hash of the value.
+    lookup: FastHashMap<u64, Vec<Handle<T>>>,
+}
+
+impl<T> Default for UniqueArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for UniqueArena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for UniqueArena<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<T: hash::Hash> hash::Hash for UniqueArena<T> {
+    fn hash<H: hash::Hasher>(&self, hasher: &mut H) {
+        self.data.hash(hasher)
+    }
+}
+
+impl<T> UniqueArena<T> {
+    /// Create a new arena with no initial capacity allocated.
+    pub fn new() -> Self {
+        UniqueArena {
+            data: Vec::new(),
+            lookup: FastHashMap::default(),
+        }
+    }
+
+    /// Extracts the inner vector.
+    pub fn into_inner(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Returns the current number of items stored in this arena.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the arena contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns an iterator over the items stored in this arena, returning both
+    /// the item's handle and a reference to it.
+    ///
+    /// The items are visited in the order they were inserted, which is also
+    /// the order of their handles.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (Handle<T>, &T)> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Handle::from_usize(i), v))
+    }
+
+    /// Fetch a handle to an existing element matching `fun`.
+    pub fn fetch_if<F: Fn(&T) -> bool>(&self, fun: F) -> Option<Handle<T>> {
+        self.data.iter().position(fun).map(Handle::from_usize)
+    }
+
+    pub fn try_get(&self, handle: Handle<T>) -> Option<&T> {
+        self.data.get(handle.index())
+    }
+}
+
+impl<T: Eq + hash::Hash> UniqueArena<T> {
+    fn hash_of(value: &T) -> u64 {
+        use std::hash::Hasher as _;
+        let mut hasher = fxhash::FxHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Append `value` without checking whether it's already present.
+    fn push(&mut self, value: T) -> Handle<T> {
+        let handle = Handle::from_usize(self.data.len());
+        self.lookup
+            .entry(Self::hash_of(&value))
+            .or_default()
+            .push(handle);
+        self.data.push(value);
+        handle
+    }
+
+    /// Insert a new value, returning the handle of the equal element if
+    /// there is one already.
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        match self.get(&value) {
+            Some(handle) => handle,
+            None => self.push(value),
+        }
+    }
+
+    /// Returns the handle of the element equal to `value`, if any.
+    pub fn get(&self, value: &T) -> Option<Handle<T>> {
+        let data = &self.data;
+        self.lookup
+            .get(&Self::hash_of(value))?
+            .iter()
+            .cloned()
+            .find(|&handle| data[handle.index()] == *value)
+    }
+
+    /// Remove the values inserted since the arena had `length` values.
+    ///
+    /// See [`Arena::truncate`].
+    pub fn truncate(&mut self, length: usize) {
+        if length >= self.data.len() {
+            return;
+        }
+        self.data.truncate(length);
+        self.lookup.retain(|_, handles| {
+            handles.retain(|handle| handle.index() < length);
+            !handles.is_empty()
+        });
+    }
+
+    /// Replace the element of `handle`, keeping the handle.
+    ///
+    /// This is only meant for front ends completing the types they are
+    /// building, like forward declared pointers. If `value` is already
+    /// present under another handle, the arena is left unchanged and that
+    /// handle is returned as the error, since the element would be duplicated.
+    pub(crate) fn replace(&mut self, handle: Handle<T>, value: T) -> Result<(), Handle<T>> {
+        match self.get(&value) {
+            Some(existing) if existing != handle => return Err(existing),
+            _ => {}
+        }
+        let old_hash = Self::hash_of(&self.data[handle.index()]);
+        if let Some(handles) = self.lookup.get_mut(&old_hash) {
+            handles.retain(|&h| h != handle);
+        }
+        let handles = self.lookup.entry(Self::hash_of(&value)).or_default();
+        let position = handles
+            .iter()
+            .position(|&h| h > handle)
+            .unwrap_or(handles.len());
+        handles.insert(position, handle);
+        self.data[handle.index()] = value;
+        Ok(())
+    }
+}
+
+impl<T> ops::Index<Handle<T>> for UniqueArena<T> {
+    type Output = T;
+    fn index(&self, handle: Handle<T>) -> &T {
+        &self.data[handle.index()]
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<T: serde::Serialize> serde::Serialize for UniqueArena<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "deserialize")]
+impl<'de, T> serde::Deserialize<'de> for UniqueArena<T>
+where
+    T: Eq + hash::Hash + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Vec::deserialize(deserializer)?;
+        let mut arena = Self::new();
+        for value in data {
+            // The handles have to stay as they were serialized, so duplicates
+            // can't be merged.
+            if arena.get(&value).is_some() {
+                return Err(serde::de::Error::custom(
+                    "duplicate element in a unique arena",
+                ));
+            }
+            arena.push(value);
+        }
+        Ok(arena)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,4 +533,73 @@ mod tests {
         assert!(t1 != t2);
         assert!(arena[t1] != arena[t2]);
     }
+
+    #[test]
+    fn insert_non_unique() {
+        let mut arena: UniqueArena<u8> = UniqueArena::new();
+        let t1 = arena.insert(0);
+        let t2 = arena.insert(0);
+        assert!(t1 == t2);
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    fn insert_unique() {
+        let mut arena: UniqueArena<u8> = UniqueArena::new();
+        let t1 = arena.insert(0);
+        let t2 = arena.insert(1);
+        assert!(t1 != t2);
+        assert!(arena[t1] != arena[t2]);
+        assert_eq!(arena.get(&1), Some(t2));
+    }
+
+    #[test]
+    fn truncate() {
+        let mut arena: Arena<u8> = Arena::new();
+        arena.append(0);
+        let length = arena.len();
+        arena.append(1);
+        arena.truncate(length);
+        assert_eq!(arena.len(), 1);
+        let t2 = arena.append(2);
+        assert_eq!(arena[t2], 2);
+    }
+
+    #[test]
+    fn truncate_unique() {
+        let mut arena: UniqueArena<u8> = UniqueArena::new();
+        let t1 = arena.insert(0);
+        let length = arena.len();
+        arena.insert(1);
+        arena.truncate(length);
+        assert_eq!(arena.len(), 1);
+        assert_eq!(arena.get(&0), Some(t1));
+        assert_eq!(arena.get(&1), None);
+        let t2 = arena.insert(2);
+        assert_eq!(arena[t2], 2);
+    }
+
+    #[test]
+    fn replace() {
+        let mut arena: UniqueArena<u8> = UniqueArena::new();
+        let t1 = arena.insert(0);
+        arena.replace(t1, 2).unwrap();
+        assert_eq!(arena.get(&0), None);
+        assert_eq!(arena.insert(2), t1);
+        // Replacing with the current value is fine
+        arena.replace(t1, 2).unwrap();
+
+        let t2 = arena.insert(3);
+        assert_eq!(arena.replace(t2, 2), Err(t1));
+        assert_eq!(arena[t2], 3);
+        assert_eq!(arena.get(&3), Some(t2));
+    }
+
+    #[cfg(feature = "deserialize")]
+    #[test]
+    fn deserialize_duplicates() {
+        let arena: UniqueArena<u8> = ron::de::from_str("[0, 1, 2]").unwrap();
+        assert_eq!(arena.get(&2).map(|handle| handle.index()), Some(2));
+        assert!(ron::de::from_str::<UniqueArena<u8>>("[0, 1, 0]").is_err());
+    }
 }
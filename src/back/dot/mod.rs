@@ -213,6 +213,7 @@ fn write_fun(
             E::ImageSample {
                 image,
                 sampler,
+                gather,
                 coordinate,
                 array_index,
                 offset: _,
@@ -242,7 +243,11 @@ fn write_fun(
                 if let Some(expr) = depth_ref {
                     edges.insert("depth_ref", expr);
                 }
-                ("ImageSample".into(), 5)
+                let name = match gather {
+                    Some(component) => format!("ImageGather{:?}", component).into(),
+                    None => std::borrow::Cow::from("ImageSample"),
+                };
+                (name, 5)
             }
             E::ImageLoad {
                 image,
@@ -269,6 +274,14 @@ fn write_fun(
                         }
                         std::borrow::Cow::from("ImageSize")
                     }
+                    crate::ImageQuery::Lod {
+                        sampler,
+                        coordinate,
+                    } => {
+                        edges.insert("sampler", sampler);
+                        edges.insert("coordinate", coordinate);
+                        std::borrow::Cow::from("ImageLod")
+                    }
                     _ => format!("{:?}", query).into(),
                 };
                 (args, 7)
@@ -292,9 +305,13 @@ fn write_fun(
                 edges.insert("reject", reject);
                 ("Select".into(), 3)
             }
-            E::Derivative { axis, expr } => {
+            E::Derivative { axis, ctrl, expr } => {
                 edges.insert("", expr);
-                (format!("d{:?}", axis).into(), 8)
+                let name = match ctrl {
+                    crate::DerivativeControl::None => format!("d{:?}", axis),
+                    _ => format!("d{:?}{:?}", axis, ctrl),
+                };
+                (name.into(), 8)
             }
             E::Relational { fun, argument } => {
                 edges.insert("arg", argument);
@@ -327,6 +344,10 @@ fn write_fun(
                 };
                 (string.into(), 3)
             }
+            E::PointerCast { expr, ty } => {
+                edges.insert("", expr);
+                (format!("PointerCast<{:?}>", ty).into(), 3)
+            }
             E::Call(_function) => ("Call".into(), 4),
             E::ArrayLength(expr) => {
                 edges.insert("", expr);
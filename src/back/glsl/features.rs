@@ -1,7 +1,7 @@
 use super::{BackendResult, Error, Version, Writer};
 use crate::{
-    Binding, Bytes, Handle, ImageClass, ImageDimension, Interpolation, Sampling, ScalarKind,
-    ShaderStage, StorageClass, StorageFormat, Type, TypeInner,
+    Binding, Bytes, Expression, Handle, ImageClass, ImageDimension, ImageQuery, Interpolation,
+    MathFunction, Sampling, ScalarKind, ShaderStage, StorageClass, Type, TypeInner,
 };
 use std::fmt::Write;
 
@@ -34,6 +34,21 @@ bitflags::bitflags! {
         const SAMPLE_VARIABLES = 1 << 15;
         /// Arrays with a dynamic length
         const DYNAMIC_ARRAY_SIZE = 1 << 16;
+        /// `gl_HelperInvocation`, which was added in 450 and es 310
+        const HELPER_INVOCATION = 1 << 17;
+        const MULTI_VIEW = 1 << 18;
+        /// 64 bit integers
+        const INT64 = 1 << 19;
+        /// 16 bit floats
+        const FLOAT16 = 1 << 20;
+        /// Level of detail queries
+        const TEXTURE_QUERY_LOD = 1 << 21;
+        /// Texture gathers with a component or a depth reference
+        const TEXTURE_GATHER = 1 << 22;
+        /// Packing and unpacking of 4x8 normalized vectors
+        const PACK_4X8 = 1 << 23;
+        /// Packing and unpacking of 2x16 normalized and half float vectors
+        const PACK_2X16 = 1 << 24;
     }
 }
 
@@ -85,7 +100,7 @@ impl FeaturesManager {
         check_feature!(BUFFER_STORAGE, 400, 310);
         check_feature!(DOUBLE_TYPE, 150);
         check_feature!(CUBE_TEXTURES_ARRAY, 130, 310);
-        check_feature!(MULTISAMPLED_TEXTURES, 150, 300);
+        check_feature!(MULTISAMPLED_TEXTURES, 150, 310);
         check_feature!(MULTISAMPLED_TEXTURE_ARRAYS, 150, 310);
         check_feature!(ARRAY_OF_ARRAYS, 120, 310);
         check_feature!(IMAGE_LOAD_STORE, 130, 310);
@@ -101,6 +116,14 @@ impl FeaturesManager {
         check_feature!(CULL_DISTANCE, 450, 300);
         check_feature!(SAMPLE_VARIABLES, 400, 300);
         check_feature!(DYNAMIC_ARRAY_SIZE, 430, 310);
+        check_feature!(HELPER_INVOCATION, 450, 310);
+        check_feature!(MULTI_VIEW, 140, 300);
+        check_feature!(INT64, 400);
+        check_feature!(FLOAT16, 450);
+        check_feature!(TEXTURE_QUERY_LOD, 130);
+        check_feature!(TEXTURE_GATHER, 400, 310);
+        check_feature!(PACK_4X8, 400, 310);
+        check_feature!(PACK_2X16, 420, 300);
 
         // Return an error if there are missing features
         if missing.is_empty() {
@@ -116,68 +139,73 @@ impl FeaturesManager {
     /// This won't check for feature availability so it might output extensions that aren't even
     /// supported.[`check_availability`](Self::check_availability) will check feature availability
     pub fn write(&self, version: Version, mut out: impl Write) -> BackendResult {
-        if self.0.contains(Features::COMPUTE_SHADER) && !version.is_es() {
+        for extension in self.extensions(version) {
+            writeln!(out, "#extension {} : require", extension)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the extensions enabling the required [`Features`](Features) in
+    /// the specified [`Version`](super::Version)
+    pub fn extensions(&self, version: Version) -> Vec<&'static str> {
+        let mut extensions = Vec::new();
+
+        if self.0.contains(Features::COMPUTE_SHADER) && version < Version::Desktop(430) {
             // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_compute_shader.txt
-            writeln!(out, "#extension GL_ARB_compute_shader : require")?;
+            extensions.push("GL_ARB_compute_shader");
         }
 
-        if self.0.contains(Features::BUFFER_STORAGE) && !version.is_es() {
+        if self.0.contains(Features::BUFFER_STORAGE) && version < Version::Desktop(430) {
             // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_shader_storage_buffer_object.txt
-            writeln!(
-                out,
-                "#extension GL_ARB_shader_storage_buffer_object : require"
-            )?;
+            extensions.push("GL_ARB_shader_storage_buffer_object");
         }
 
         if self.0.contains(Features::DOUBLE_TYPE) && version < Version::Desktop(400) {
             // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_gpu_shader_fp64.txt
-            writeln!(out, "#extension GL_ARB_gpu_shader_fp64 : require")?;
+            extensions.push("GL_ARB_gpu_shader_fp64");
         }
 
         if self.0.contains(Features::CUBE_TEXTURES_ARRAY) {
             if version.is_es() {
                 // https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_texture_cube_map_array.txt
-                writeln!(out, "#extension GL_EXT_texture_cube_map_array : require")?;
+                extensions.push("GL_EXT_texture_cube_map_array");
             } else if version < Version::Desktop(400) {
                 // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_texture_cube_map_array.txt
-                writeln!(out, "#extension GL_ARB_texture_cube_map_array : require")?;
+                extensions.push("GL_ARB_texture_cube_map_array");
             }
         }
 
         if self.0.contains(Features::MULTISAMPLED_TEXTURE_ARRAYS) && version.is_es() {
             // https://www.khronos.org/registry/OpenGL/extensions/OES/OES_texture_storage_multisample_2d_array.txt
-            writeln!(
-                out,
-                "#extension GL_OES_texture_storage_multisample_2d_array : require"
-            )?;
+            extensions.push("GL_OES_texture_storage_multisample_2d_array");
         }
 
         if self.0.contains(Features::ARRAY_OF_ARRAYS) && version < Version::Desktop(430) {
             // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_arrays_of_arrays.txt
-            writeln!(out, "#extension ARB_arrays_of_arrays : require")?;
+            extensions.push("GL_ARB_arrays_of_arrays");
         }
 
         if self.0.contains(Features::IMAGE_LOAD_STORE) {
             if self.0.contains(Features::FULL_IMAGE_FORMATS) && version.is_es() {
                 // https://www.khronos.org/registry/OpenGL/extensions/NV/NV_image_formats.txt
-                writeln!(out, "#extension GL_NV_image_formats : require")?;
+                extensions.push("GL_NV_image_formats");
             }
 
             if version < Version::Desktop(420) {
                 // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_shader_image_load_store.txt
-                writeln!(out, "#extension GL_ARB_shader_image_load_store : require")?;
+                extensions.push("GL_ARB_shader_image_load_store");
             }
         }
 
         if self.0.contains(Features::CONSERVATIVE_DEPTH) {
             if version.is_es() {
                 // https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_conservative_depth.txt
-                writeln!(out, "#extension GL_EXT_conservative_depth : require")?;
+                extensions.push("GL_EXT_conservative_depth");
             }
 
             if version < Version::Desktop(420) {
                 // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_conservative_depth.txt
-                writeln!(out, "#extension GL_ARB_conservative_depth : require")?;
+                extensions.push("GL_ARB_conservative_depth");
             }
         }
 
@@ -186,15 +214,35 @@ impl FeaturesManager {
         {
             // TODO: handle gl_ClipDistance and gl_CullDistance usage in better way
             // https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_clip_cull_distance.txt
-            // writeln!(out, "#extension GL_EXT_clip_cull_distance : require")?;
+            // extensions.push("GL_EXT_clip_cull_distance");
         }
 
         if self.0.contains(Features::SAMPLE_VARIABLES) && version.is_es() {
             // https://www.khronos.org/registry/OpenGL/extensions/OES/OES_sample_variables.txt
-            writeln!(out, "#extension GL_OES_sample_variables : require")?;
+            extensions.push("GL_OES_sample_variables");
         }
 
-        Ok(())
+        if self.0.contains(Features::MULTI_VIEW) {
+            // https://www.khronos.org/registry/OpenGL/extensions/OVR/OVR_multiview2.txt
+            extensions.push("GL_OVR_multiview2");
+        }
+
+        if self.0.contains(Features::INT64) {
+            // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_gpu_shader_int64.txt
+            extensions.push("GL_ARB_gpu_shader_int64");
+        }
+
+        if self.0.contains(Features::FLOAT16) {
+            // https://www.khronos.org/registry/OpenGL/extensions/AMD/AMD_gpu_shader_half_float.txt
+            extensions.push("GL_AMD_gpu_shader_half_float");
+        }
+
+        if self.0.contains(Features::TEXTURE_QUERY_LOD) && version < Version::Desktop(400) {
+            // https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_texture_query_lod.txt
+            extensions.push("GL_ARB_texture_query_lod");
+        }
+
+        extensions
     }
 }
 
@@ -224,6 +272,10 @@ impl<'a, W> Writer<'a, W> {
             self.features.request(Features::COMPUTE_SHADER)
         }
 
+        if self.multiview.is_some() && self.entry_point.stage == ShaderStage::Vertex {
+            self.features.request(Features::MULTI_VIEW);
+        }
+
         for (_, ty) in self.module.types.iter() {
             match ty.inner {
                 TypeInner::Scalar { kind, width } => self.scalar_required_features(kind, width),
@@ -231,6 +283,10 @@ impl<'a, W> Writer<'a, W> {
                 TypeInner::Matrix { width, .. } => {
                     self.scalar_required_features(ScalarKind::Float, width)
                 }
+                TypeInner::Pointer {
+                    class: StorageClass::PhysicalStorageBuffer,
+                    ..
+                } => return Err(Error::PhysicalStorageBufferNotSupported),
                 TypeInner::Array { base, size, .. } => {
                     if let TypeInner::Array { .. } = self.module.types[base].inner {
                         self.features.request(Features::ARRAY_OF_ARRAYS)
@@ -252,36 +308,30 @@ impl<'a, W> Writer<'a, W> {
                     }
 
                     match class {
-                        ImageClass::Sampled { multi: true, .. } => {
+                        ImageClass::Sampled { multi: true, .. }
+                        | ImageClass::Depth { multi: true } => {
                             self.features.request(Features::MULTISAMPLED_TEXTURES);
                             if arrayed {
                                 self.features.request(Features::MULTISAMPLED_TEXTURE_ARRAYS);
                             }
                         }
-                        ImageClass::Storage(format) => match format {
-                            StorageFormat::R8Unorm
-                            | StorageFormat::R8Snorm
-                            | StorageFormat::R8Uint
-                            | StorageFormat::R8Sint
-                            | StorageFormat::R16Uint
-                            | StorageFormat::R16Sint
-                            | StorageFormat::R16Float
-                            | StorageFormat::Rg8Unorm
-                            | StorageFormat::Rg8Snorm
-                            | StorageFormat::Rg8Uint
-                            | StorageFormat::Rg8Sint
-                            | StorageFormat::Rg16Uint
-                            | StorageFormat::Rg16Sint
-                            | StorageFormat::Rg16Float
-                            | StorageFormat::Rgb10a2Unorm
-                            | StorageFormat::Rg11b10Float
-                            | StorageFormat::Rg32Uint
-                            | StorageFormat::Rg32Sint
-                            | StorageFormat::Rg32Float => {
+                        ImageClass::Storage(format) => {
+                            self.features.request(Features::IMAGE_LOAD_STORE);
+                            if let Some(closest) = super::es_storage_format_fallback(format) {
+                                if self.options.version.is_es()
+                                    && self
+                                        .options
+                                        .writer_flags
+                                        .contains(super::WriterFlags::CORE_ES_IMAGE_FORMATS)
+                                {
+                                    return Err(Error::UnsupportedStorageFormat {
+                                        format,
+                                        closest,
+                                    });
+                                }
                                 self.features.request(Features::FULL_IMAGE_FORMATS)
                             }
-                            _ => {}
-                        },
+                        }
                         _ => {}
                     }
                 }
@@ -289,6 +339,42 @@ impl<'a, W> Writer<'a, W> {
             }
         }
 
+        let module = self.module;
+        let functions = module
+            .functions
+            .iter()
+            .map(|(_, fun)| fun)
+            .chain(std::iter::once(&self.entry_point.function));
+        for fun in functions {
+            for (_, expr) in fun.expressions.iter() {
+                match *expr {
+                    Expression::ImageQuery {
+                        query: ImageQuery::Lod { .. },
+                        ..
+                    } => self.features.request(Features::TEXTURE_QUERY_LOD),
+                    Expression::ImageSample {
+                        gather: Some(_), ..
+                    } => self.features.request(Features::TEXTURE_GATHER),
+                    Expression::Math { fun, .. } => match fun {
+                        MathFunction::Pack4x8snorm
+                        | MathFunction::Pack4x8unorm
+                        | MathFunction::Unpack4x8snorm
+                        | MathFunction::Unpack4x8unorm => self.features.request(Features::PACK_4X8),
+                        MathFunction::Pack2x16snorm
+                        | MathFunction::Pack2x16unorm
+                        | MathFunction::Pack2x16float
+                        | MathFunction::Unpack2x16snorm
+                        | MathFunction::Unpack2x16unorm
+                        | MathFunction::Unpack2x16float => {
+                            self.features.request(Features::PACK_2X16)
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+
         for (_, global) in self.module.global_variables.iter() {
             match global.class {
                 StorageClass::WorkGroup => self.features.request(Features::COMPUTE_SHADER),
@@ -303,8 +389,11 @@ impl<'a, W> Writer<'a, W> {
 
     /// Helper method that checks the [`Features`](Features) needed by a scalar
     fn scalar_required_features(&mut self, kind: ScalarKind, width: Bytes) {
-        if kind == ScalarKind::Float && width == 8 {
-            self.features.request(Features::DOUBLE_TYPE);
+        match (kind, width) {
+            (ScalarKind::Float, 8) => self.features.request(Features::DOUBLE_TYPE),
+            (ScalarKind::Float, 2) => self.features.request(Features::FLOAT16),
+            (ScalarKind::Sint, 8) | (ScalarKind::Uint, 8) => self.features.request(Features::INT64),
+            _ => {}
         }
     }
 
@@ -325,9 +414,15 @@ impl<'a, W> Writer<'a, W> {
                             crate::BuiltIn::CullDistance => {
                                 self.features.request(Features::CULL_DISTANCE)
                             }
-                            crate::BuiltIn::SampleIndex => {
+                            crate::BuiltIn::SampleIndex | crate::BuiltIn::SamplePosition => {
                                 self.features.request(Features::SAMPLE_VARIABLES)
                             }
+                            crate::BuiltIn::HelperInvocation => {
+                                self.features.request(Features::HELPER_INVOCATION)
+                            }
+                            crate::BuiltIn::ViewIndex => {
+                                self.features.request(Features::MULTI_VIEW)
+                            }
                             _ => {}
                         },
                         Binding::Location {
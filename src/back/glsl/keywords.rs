@@ -160,7 +160,8 @@ pub const RESERVED_KEYWORDS: &[&str] = &[
     "uimage2DMS",
     "image2DMSArray",
     "iimage2DMSArray",
-    "uimage2DMSArraystruct",
+    "uimage2DMSArray",
+    "struct",
     "common",
     "partition",
     "active",
@@ -201,4 +202,171 @@ pub const RESERVED_KEYWORDS: &[&str] = &[
     "namespace",
     "using",
     "main",
+    // Built-in functions, which user declarations would shadow
+    "radians",
+    "degrees",
+    "sin",
+    "cos",
+    "tan",
+    "asin",
+    "acos",
+    "atan",
+    "sinh",
+    "cosh",
+    "tanh",
+    "asinh",
+    "acosh",
+    "atanh",
+    "pow",
+    "exp",
+    "log",
+    "exp2",
+    "log2",
+    "sqrt",
+    "inversesqrt",
+    "abs",
+    "sign",
+    "floor",
+    "trunc",
+    "round",
+    "roundEven",
+    "ceil",
+    "fract",
+    "mod",
+    "modf",
+    "min",
+    "max",
+    "clamp",
+    "mix",
+    "step",
+    "smoothstep",
+    "isnan",
+    "isinf",
+    "floatBitsToInt",
+    "floatBitsToUint",
+    "intBitsToFloat",
+    "uintBitsToFloat",
+    "fma",
+    "frexp",
+    "ldexp",
+    "packUnorm2x16",
+    "packSnorm2x16",
+    "packUnorm4x8",
+    "packSnorm4x8",
+    "unpackUnorm2x16",
+    "unpackSnorm2x16",
+    "unpackUnorm4x8",
+    "unpackSnorm4x8",
+    "packHalf2x16",
+    "unpackHalf2x16",
+    "packDouble2x32",
+    "unpackDouble2x32",
+    "length",
+    "distance",
+    "dot",
+    "cross",
+    "normalize",
+    "ftransform",
+    "faceforward",
+    "reflect",
+    "refract",
+    "matrixCompMult",
+    "outerProduct",
+    "transpose",
+    "determinant",
+    "inverse",
+    "lessThan",
+    "lessThanEqual",
+    "greaterThan",
+    "greaterThanEqual",
+    "equal",
+    "notEqual",
+    "any",
+    "all",
+    "not",
+    "uaddCarry",
+    "usubBorrow",
+    "umulExtended",
+    "imulExtended",
+    "bitfieldExtract",
+    "bitfieldInsert",
+    "bitfieldReverse",
+    "bitCount",
+    "findLSB",
+    "findMSB",
+    "textureSize",
+    "textureQueryLod",
+    "textureQueryLevels",
+    "textureSamples",
+    "texture",
+    "textureProj",
+    "textureLod",
+    "textureOffset",
+    "texelFetch",
+    "texelFetchOffset",
+    "textureProjOffset",
+    "textureLodOffset",
+    "textureProjLod",
+    "textureProjLodOffset",
+    "textureGrad",
+    "textureGradOffset",
+    "textureProjGrad",
+    "textureProjGradOffset",
+    "textureGather",
+    "textureGatherOffset",
+    "textureGatherOffsets",
+    "atomicCounterIncrement",
+    "atomicCounterDecrement",
+    "atomicCounter",
+    "atomicAdd",
+    "atomicMin",
+    "atomicMax",
+    "atomicAnd",
+    "atomicOr",
+    "atomicXor",
+    "atomicExchange",
+    "atomicCompSwap",
+    "imageSize",
+    "imageSamples",
+    "imageLoad",
+    "imageStore",
+    "imageAtomicAdd",
+    "imageAtomicMin",
+    "imageAtomicMax",
+    "imageAtomicAnd",
+    "imageAtomicOr",
+    "imageAtomicXor",
+    "imageAtomicExchange",
+    "imageAtomicCompSwap",
+    "dFdx",
+    "dFdy",
+    "dFdxFine",
+    "dFdyFine",
+    "dFdxCoarse",
+    "dFdyCoarse",
+    "fwidth",
+    "fwidthFine",
+    "fwidthCoarse",
+    "interpolateAtCentroid",
+    "interpolateAtSample",
+    "interpolateAtOffset",
+    "noise1",
+    "noise2",
+    "noise3",
+    "noise4",
+    "EmitStreamVertex",
+    "EndStreamPrimitive",
+    "EmitVertex",
+    "EndPrimitive",
+    "barrier",
+    "memoryBarrier",
+    "memoryBarrierAtomicCounter",
+    "memoryBarrierBuffer",
+    "memoryBarrierShared",
+    "memoryBarrierImage",
+    "groupMemoryBarrier",
+    "subpassLoad",
+    "anyInvocation",
+    "allInvocations",
+    "allInvocationsEqual",
 ];
@@ -60,11 +60,17 @@ use thiserror::Error;
 mod features;
 /// Contains a constant with a slice of all the reserved keywords RESERVED_KEYWORDS
 mod keywords;
+#[cfg(test)]
+mod writer_tests;
 
 /// List of supported core glsl versions
 pub const SUPPORTED_CORE_VERSIONS: &[u16] = &[330, 400, 410, 420, 430, 440, 450];
 /// List of supported es glsl versions
 pub const SUPPORTED_ES_VERSIONS: &[u16] = &[300, 310, 320];
+/// Instance name of the block that loose uniforms are gathered into
+const DEFAULT_UNIFORMS: &str = "_default_uniforms";
+/// Name of the function of the entry point, GLSL requires it to be `main`.
+const ENTRY_POINT_NAME: &str = "main";
 
 pub type BindingMap = std::collections::BTreeMap<crate::ResourceBinding, u8>;
 
@@ -110,6 +116,18 @@ impl Version {
     fn supports_explicit_locations(&self) -> bool {
         *self >= Version::Embedded(310) || *self >= Version::Desktop(410)
     }
+
+    /// Checks if the version has the `Coarse` and `Fine` variants of the
+    /// derivative functions, which aren't available on es.
+    fn supports_derivative_control(&self) -> bool {
+        *self >= Version::Desktop(450)
+    }
+
+    /// Checks if the version has `textureOffset` for `sampler2DArrayShadow`,
+    /// which was added in 430 and isn't available on es.
+    fn supports_array_shadow_offset(&self) -> bool {
+        *self >= Version::Desktop(430)
+    }
 }
 
 impl PartialOrd for Version {
@@ -140,6 +158,9 @@ bitflags::bitflags! {
         /// Supports GL_EXT_texture_shadow_lod on the host, which provides
         /// additional functions on shadows and arrays of shadows.
         const TEXTURE_SHADOW_LOD = 0x2;
+        /// Only use the storage image formats of core GLSL ES instead of requiring
+        /// GL_NV_image_formats for the others, which most GLES drivers don't have.
+        const CORE_ES_IMAGE_FORMATS = 0x4;
     }
 }
 
@@ -154,6 +175,20 @@ pub struct Options {
     pub writer_flags: WriterFlags,
     /// Map of resources association to binding locations.
     pub binding_map: BindingMap,
+    /// Binding of the uniform block that loose uniforms are gathered into.
+    ///
+    /// Uniforms that aren't structs are declared as plain `uniform` globals unless this is set,
+    /// which targets like Vulkan GLSL don't allow. The block is synthesized per stage with the
+    /// `std140` layout and the uniforms become its members.
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub default_uniform_binding: Option<u8>,
+    /// Assign binding points to the resources that [`binding_map`](Self::binding_map) doesn't
+    /// list, instead of leaving them to be queried with `glGetUniformLocation`.
+    ///
+    /// Textures, storage images, uniform buffers and storage buffers have separate binding
+    /// points, and each resource gets the lowest ones of its kind that are still free.
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub allocate_bindings: bool,
 }
 
 impl Default for Options {
@@ -162,6 +197,8 @@ impl Default for Options {
             version: Version::Embedded(310),
             writer_flags: WriterFlags::ADJUST_COORDINATE_SPACE,
             binding_map: BindingMap::default(),
+            default_uniform_binding: None,
+            allocate_bindings: false,
         }
     }
 }
@@ -178,12 +215,35 @@ pub struct PipelineOptions {
     /// If no entry point that matches is found a error will be thrown while creating a new instance
     /// of [`Writer`](struct.Writer.html)
     pub entry_point: String,
+    /// How many views to render to, if doing multiview rendering.
+    pub multiview: Option<std::num::NonZeroU32>,
 }
 
 /// Structure that contains a reflection info
 pub struct ReflectionInfo {
+    /// Combined samplers, keyed by their name, and the texture and sampler they bind.
     pub texture_mapping: crate::FastHashMap<String, TextureMapping>,
+    /// Names of the uniform blocks, or of the default uniform block, of the globals.
     pub uniforms: crate::FastHashMap<Handle<crate::GlobalVariable>, String>,
+    /// Names of the input varyings of the entry point, keyed by location.
+    pub varying_inputs: crate::FastHashMap<u32, String>,
+    /// Names of the output varyings of the entry point, keyed by location.
+    pub varying_outputs: crate::FastHashMap<u32, String>,
+    /// The `gl_` built-ins that replace the built-in inputs of the entry point,
+    /// e.g. `uint(gl_VertexID)` for [`BuiltIn::VertexIndex`](crate::BuiltIn::VertexIndex).
+    pub built_in_inputs: crate::FastHashMap<crate::BuiltIn, String>,
+    /// The `gl_` built-ins that replace the built-in outputs of the entry point.
+    pub built_in_outputs: crate::FastHashMap<crate::BuiltIn, String>,
+    /// Binding points of the resources, either taken from [`Options::binding_map`] or
+    /// allocated because of [`Options::allocate_bindings`].
+    pub bindings: crate::FastHashMap<Handle<crate::GlobalVariable>, u8>,
+    /// Items whose IR name couldn't be used verbatim (because it's reserved,
+    /// contains double underscores or collides with another name) mapped to
+    /// the name they were emitted with.
+    pub renamed: crate::FastHashMap<NameKey, String>,
+    /// Name of the function of the entry point in the output, GLSL always
+    /// calls it `main`.
+    pub entry_point: String,
 }
 
 /// Structure that connects a texture to a sampler or not
@@ -195,6 +255,10 @@ pub struct ReflectionInfo {
 ///
 /// [`Storage`](crate::ImageClass::Storage) images produce `gimageN` and don't have an associated sampler
 /// so the [`sampler`](Self::sampler) field will be [`None`](std::option::Option::None)
+///
+/// [`Depth`](crate::ImageClass::Depth) images produce `samplerNShadow`, which only compare
+/// against the reference if the sampler state bound to the texture unit has a compare mode,
+/// so the user has to enable it when the [`sampler`](Self::sampler) is a comparison sampler.
 #[derive(Debug, Clone)]
 pub struct TextureMapping {
     /// Handle to the image global variable
@@ -203,6 +267,15 @@ pub struct TextureMapping {
     pub sampler: Option<Handle<crate::GlobalVariable>>,
 }
 
+/// The kinds of resources that have separate binding points
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum BindingKind {
+    Texture,
+    Image,
+    UniformBuffer,
+    StorageBuffer,
+}
+
 /// Helper structure that generates a number
 #[derive(Default)]
 struct IdGenerator(u32);
@@ -234,7 +307,8 @@ impl fmt::Display for VaryingName<'_> {
         match *self.binding {
             crate::Binding::Location { location, .. } => {
                 let prefix = match (self.stage, self.output) {
-                    (ShaderStage::Compute, _) => unreachable!(),
+                    // compute shaders have no varyings, the module isn't valid
+                    (ShaderStage::Compute, _) => return Err(fmt::Error),
                     // pipeline to vertex
                     (ShaderStage::Vertex, false) => "p2vs",
                     // vertex to fragment
@@ -269,6 +343,10 @@ pub enum Error {
     /// supported in the glsl backend
     #[error("Push constants aren't supported")]
     PushConstantNotSupported,
+    /// [`StorageClass::PhysicalStorageBuffer`](crate::StorageClass::PhysicalStorageBuffer)
+    /// was used and isn't supported in the glsl backend
+    #[error("Physical storage buffer pointers aren't supported")]
+    PhysicalStorageBufferNotSupported,
     /// The specified [`Version`](Version) isn't supported
     #[error("The specified version isn't supported")]
     VersionNotSupported,
@@ -284,10 +362,65 @@ pub enum Error {
     /// A image was used with multiple samplers, this isn't supported
     #[error("A image was used with multiple samplers")]
     ImageMultipleSamplers,
+    /// A storage image format isn't available in core GLSL ES and
+    /// [`CORE_ES_IMAGE_FORMATS`](WriterFlags::CORE_ES_IMAGE_FORMATS) was requested
+    #[error("Storage format {format:?} isn't supported by GLSL ES, the closest supported format is {closest:?}")]
+    UnsupportedStorageFormat {
+        format: crate::StorageFormat,
+        closest: crate::StorageFormat,
+    },
+    /// A storage image is both read and written in GLSL ES, this is only allowed for the
+    /// single channel 32 bit formats
+    #[error("Storage images with format {0:?} can't be both read and written in GLSL ES, only r32f, r32i and r32ui can")]
+    ReadWriteStorageImage(crate::StorageFormat),
+    /// A depth comparison sample can't be expressed for this kind of shadow sampler,
+    /// some of these become possible with
+    /// [`TEXTURE_SHADOW_LOD`](WriterFlags::TEXTURE_SHADOW_LOD)
+    #[error("Sampling a {sampler} with {operation} isn't supported")]
+    UnsupportedShadowSample {
+        sampler: &'static str,
+        operation: &'static str,
+    },
+    /// An expression doesn't have the type or form the validator guarantees,
+    /// the module most likely wasn't validated
+    #[error("Expression at {0:?} isn't valid")]
+    InvalidExpression(Box<crate::diagnostic::IrLocation>),
+    /// An array is sized by a constant that isn't an integer
+    #[error("Array size {0:?} isn't an integer constant")]
+    InvalidArraySize(Handle<crate::Constant>),
+    /// An entry point input or output, or one of its struct members, has no binding
+    #[error("Entry point input or output of type {0:?} has no binding")]
+    MissingIoBinding(Handle<crate::Type>),
+    /// The entry point returns without a value but has a result
+    #[error("Entry point {0} returns without a value")]
+    MissingReturnValue(String),
+    /// The result of a call is used, but the function has no result
+    #[error("Function {0:?} has no result")]
+    MissingCallResult(Handle<crate::Function>),
     #[error("{0}")]
     Custom(String),
 }
 
+impl Error {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic),
+    /// pointing to the item of the IR that can't be written, if known.
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        use crate::diagnostic::IrLocation;
+
+        let location = match *self {
+            Error::InvalidExpression(ref location) => Some(IrLocation::clone(location)),
+            Error::InvalidArraySize(handle) => Some(IrLocation::Constant(handle)),
+            Error::MissingIoBinding(handle) => Some(IrLocation::Type(handle)),
+            Error::MissingCallResult(handle) => Some(IrLocation::Function {
+                handle,
+                expression: None,
+            }),
+            _ => None,
+        };
+        crate::diagnostic::Diagnostic::from_error("back::glsl", self).with_location(location)
+    }
+}
+
 /// Main structure of the glsl backend responsible for all code generation
 pub struct Writer<'a, W> {
     // Inputs
@@ -313,8 +446,14 @@ pub struct Writer<'a, W> {
     entry_point: &'a crate::EntryPoint,
     /// The index of the selected entry point
     entry_point_idx: proc::EntryPointIndex,
+    /// The number of views to render to, if doing multiview rendering
+    multiview: Option<std::num::NonZeroU32>,
     /// Used to generate a unique number for blocks
     block_id: IdGenerator,
+    /// Name of the block loose uniforms were gathered into, if any
+    default_uniform_block: Option<String>,
+    /// Binding points of the resources used by the entry point
+    bindings: crate::FastHashMap<Handle<crate::GlobalVariable>, u8>,
     /// Set of expressions that have associated temporary variables
     named_expressions: crate::NamedExpressions,
 }
@@ -365,24 +504,38 @@ impl<'a, W: Write> Writer<'a, W> {
             reflection_names: crate::FastHashMap::default(),
             entry_point: &module.entry_points[ep_idx],
             entry_point_idx: ep_idx as u16,
+            multiview: pipeline_options.multiview,
 
             block_id: IdGenerator::default(),
+            default_uniform_block: None,
+            bindings: crate::FastHashMap::default(),
             named_expressions: crate::NamedExpressions::default(),
         };
 
         // Find all features required to print this module
         this.collect_required_features()?;
+        if options.version.supports_explicit_locations() {
+            this.collect_bindings();
+        }
 
         Ok(this)
     }
 
+    /// Returns the extensions needed by the entry point in the targeted version
+    ///
+    /// The features are collected when the writer is created, so this doesn't
+    /// require writing anything
+    pub fn required_extensions(&self) -> Vec<&'static str> {
+        self.features.extensions(self.options.version)
+    }
+
     /// Writes the [`Module`](crate::Module) as glsl to the output
     ///
     /// # Notes
     /// If an error occurs while writing, the output might have been written partially
     ///
-    /// # Panics
-    /// Might panic if the module is invalid
+    /// # Errors
+    /// Besides unsupported features, invalid modules are reported as errors
     pub fn write(&mut self) -> Result<ReflectionInfo, Error> {
         // We use `writeln!(self.out)` throughout the write to add newlines
         // to make the output more readable
@@ -428,6 +581,13 @@ impl<'a, W: Write> Writer<'a, W> {
             writeln!(self.out)?;
         }
 
+        if self.entry_point.stage == ShaderStage::Vertex {
+            if let Some(multiview) = self.multiview {
+                writeln!(self.out, "layout(num_views = {}) in;", multiview)?;
+                writeln!(self.out)?;
+            }
+        }
+
         // Enable early depth tests if needed
         if let Some(depth_test) = self.entry_point.early_depth_test {
             writeln!(self.out, "layout(early_fragment_tests) in;")?;
@@ -467,6 +627,10 @@ impl<'a, W: Write> Writer<'a, W> {
             }
         }
 
+        if let Some(binding) = self.options.default_uniform_binding {
+            self.write_default_uniform_block(binding)?;
+        }
+
         let ep_info = self.info.get_entry_point(self.entry_point_idx as usize);
 
         // Write the globals
@@ -475,11 +639,17 @@ impl<'a, W: Write> Writer<'a, W> {
         // interfere with each other (i.e. two globals with the same location but different with
         // different classes)
         for (handle, global) in self.module.global_variables.iter() {
-            if ep_info[handle].is_empty() {
+            if ep_info[handle].is_empty() || self.is_default_uniform(global) {
                 continue;
             }
 
-            match self.module.types[global.ty].inner {
+            // Binding arrays are declared as arrays of their base type
+            let (ty, binding_array_size) = match self.module.types[global.ty].inner {
+                TypeInner::BindingArray { base, size } => (base, Some(size)),
+                _ => (global.ty, None),
+            };
+
+            match self.module.types[ty].inner {
                 // We treat images separately because they might require
                 // writing the storage format
                 TypeInner::Image {
@@ -488,7 +658,7 @@ impl<'a, W: Write> Writer<'a, W> {
                     class,
                 } => {
                     // Gather the storage format if needed
-                    let layout_storage_format = match self.module.types[global.ty].inner {
+                    let layout_storage_format = match self.module.types[ty].inner {
                         TypeInner::Image {
                             class: crate::ImageClass::Storage(format),
                             ..
@@ -496,12 +666,7 @@ impl<'a, W: Write> Writer<'a, W> {
                         _ => None,
                     };
                     // Gether the location if needed
-                    let layout_binding = if self.options.version.supports_explicit_locations() {
-                        let br = global.binding.as_ref().unwrap();
-                        self.options.binding_map.get(br).cloned()
-                    } else {
-                        None
-                    };
+                    let layout_binding = self.bindings.get(&handle).cloned();
 
                     // Write all the layout qualifiers
                     if layout_binding.is_some() || layout_storage_format.is_some() {
@@ -519,7 +684,13 @@ impl<'a, W: Write> Writer<'a, W> {
                         write!(self.out, ") ")?;
                     }
 
-                    if let Some(storage_access) = glsl_storage_access(global.storage_access) {
+                    let storage_access = match class {
+                        crate::ImageClass::Storage(format) if self.options.version.is_es() => {
+                            self.es_storage_access(handle, global.storage_access, format)?
+                        }
+                        _ => global.storage_access,
+                    };
+                    if let Some(storage_access) = glsl_storage_access(storage_access) {
                         write!(self.out, "{} ", storage_access)?;
                     }
 
@@ -536,7 +707,11 @@ impl<'a, W: Write> Writer<'a, W> {
                     // Finally write the name and end the global with a `;`
                     // The leading space is important
                     let global_name = self.get_global_name(handle, global);
-                    writeln!(self.out, " {};", global_name)?;
+                    write!(self.out, " {}", global_name)?;
+                    if let Some(size) = binding_array_size {
+                        self.write_array_size(size)?;
+                    }
+                    writeln!(self.out, ";")?;
                     writeln!(self.out)?;
 
                     self.reflection_names.insert(global.ty, global_name);
@@ -587,7 +762,7 @@ impl<'a, W: Write> Writer<'a, W> {
             back::FunctionType::EntryPoint(self.entry_point_idx),
             &self.entry_point.function,
             ep_info,
-            "main",
+            ENTRY_POINT_NAME,
         )?;
 
         // Add newline at the end of file
@@ -602,7 +777,6 @@ impl<'a, W: Write> Writer<'a, W> {
 
         // Write the array size
         // Writes nothing if `ArraySize::Dynamic`
-        // Panics if `ArraySize::Constant` has a constant that isn't an uint
         match size {
             crate::ArraySize::Constant(const_handle) => {
                 match self.module.constants[const_handle].inner {
@@ -614,7 +788,7 @@ impl<'a, W: Write> Writer<'a, W> {
                         width: _,
                         value: crate::ScalarValue::Sint(size),
                     } => write!(self.out, "{}", size)?,
-                    _ => unreachable!(),
+                    _ => return Err(Error::InvalidArraySize(const_handle)),
                 }
             }
             crate::ArraySize::Dynamic => (),
@@ -629,10 +803,10 @@ impl<'a, W: Write> Writer<'a, W> {
     /// # Notes
     /// Adds no trailing or leading whitespace
     ///
-    /// # Panics
+    /// # Errors
     /// - If type is either a image, a sampler, a pointer, or a struct
     /// - If it's an Array with a [`ArraySize::Constant`](crate::ArraySize::Constant) with a
-    /// constant that isn't [`Uint`](crate::ConstantInner::Uint)
+    /// constant that isn't an integer
     fn write_value_type(&mut self, inner: &TypeInner) -> BackendResult {
         match *inner {
             // Scalars are simple we just get the full name from `glsl_scalar`
@@ -676,14 +850,17 @@ impl<'a, W: Write> Writer<'a, W> {
             // Current code is written arrays only as `[size]`
             // Base `type` and `name` should be written outside
             TypeInner::Array { size, .. } => self.write_array_size(size)?,
-            // Panic if either Image, Sampler, Pointer, or a Struct is being written
+            // Image, Sampler, Pointer, and Struct types are written by the callers
             //
             // Write all variants instead of `_` so that if new variants are added a
             // no exhaustiveness error is thrown
             TypeInner::Pointer { .. }
             | TypeInner::Struct { .. }
             | TypeInner::Image { .. }
-            | TypeInner::Sampler { .. } => unreachable!(),
+            | TypeInner::Sampler { .. }
+            | TypeInner::BindingArray { .. } => {
+                return Err(Error::Custom(format!("Unable to write type {:?}", inner)))
+            }
         }
 
         Ok(())
@@ -694,10 +871,10 @@ impl<'a, W: Write> Writer<'a, W> {
     /// # Notes
     /// Adds no trailing or leading whitespace
     ///
-    /// # Panics
+    /// # Errors
     /// - If type is either a image or sampler
     /// - If it's an Array with a [`ArraySize::Constant`](crate::ArraySize::Constant) with a
-    /// constant that isn't [`Uint`](crate::ConstantInner::Uint)
+    /// constant that isn't an integer
     fn write_type(&mut self, ty: Handle<crate::Type>) -> BackendResult {
         match self.module.types[ty].inner {
             // glsl has no pointer types so just write types as normal and loads are skipped
@@ -747,7 +924,9 @@ impl<'a, W: Write> Writer<'a, W> {
         let (base, kind, ms, comparison) = match class {
             Ic::Sampled { kind, multi: true } => ("sampler", kind, "MS", ""),
             Ic::Sampled { kind, multi: false } => ("sampler", kind, "", ""),
-            Ic::Depth => ("sampler", crate::ScalarKind::Float, "", "Shadow"),
+            // There are no multisampled shadow samplers, but they can only be loaded from anyway
+            Ic::Depth { multi: true } => ("sampler", crate::ScalarKind::Float, "MS", ""),
+            Ic::Depth { multi: false } => ("sampler", crate::ScalarKind::Float, "", "Shadow"),
             Ic::Storage(format) => ("image", format.into(), "", ""),
         };
 
@@ -765,6 +944,153 @@ impl<'a, W: Write> Writer<'a, W> {
         Ok(())
     }
 
+    /// Returns the kind of binding points used by a global, if it's a resource
+    fn binding_kind(&self, global: &crate::GlobalVariable) -> Option<BindingKind> {
+        let ty = match self.module.types[global.ty].inner {
+            TypeInner::BindingArray { base, .. } => base,
+            _ => global.ty,
+        };
+        match self.module.types[ty].inner {
+            TypeInner::Image {
+                class: crate::ImageClass::Storage(_),
+                ..
+            } => Some(BindingKind::Image),
+            TypeInner::Image { .. } => Some(BindingKind::Texture),
+            TypeInner::Struct { .. } => match global.class {
+                crate::StorageClass::Uniform => Some(BindingKind::UniformBuffer),
+                crate::StorageClass::Storage => Some(BindingKind::StorageBuffer),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Assigns the binding points of the resources used by the entry point
+    ///
+    /// The resources listed in [`Options::binding_map`] keep their binding, and the others are
+    /// allocated free ones if [`Options::allocate_bindings`] is set
+    fn collect_bindings(&mut self) {
+        let ep_info = self.info.get_entry_point(self.entry_point_idx as usize);
+        let mut taken = crate::FastHashMap::<_, bit_set::BitSet>::default();
+        let mut unassigned = Vec::new();
+
+        if let Some(binding) = self.options.default_uniform_binding {
+            taken
+                .entry(BindingKind::UniformBuffer)
+                .or_default()
+                .insert(binding as usize);
+        }
+
+        for (handle, global) in self.module.global_variables.iter() {
+            let br = match global.binding {
+                Some(ref br) if !ep_info[handle].is_empty() => br,
+                _ => continue,
+            };
+            let kind = self.binding_kind(global);
+            // Binding arrays take one binding point per element
+            let count = match self.module.types[global.ty].inner {
+                TypeInner::BindingArray {
+                    size: crate::ArraySize::Constant(size),
+                    ..
+                } => self.module.constants[size].to_array_length().unwrap_or(1),
+                _ => 1,
+            } as usize;
+
+            match (self.options.binding_map.get(br), kind) {
+                (Some(&binding), _) => {
+                    self.bindings.insert(handle, binding);
+                    if let Some(kind) = kind {
+                        let set = taken.entry(kind).or_default();
+                        for index in 0..count {
+                            set.insert(binding as usize + index);
+                        }
+                    }
+                }
+                (None, Some(kind)) if self.options.allocate_bindings => {
+                    unassigned.push((handle, kind, count))
+                }
+                (None, _) => {}
+            }
+        }
+
+        for (handle, kind, count) in unassigned {
+            let set = taken.entry(kind).or_default();
+            let first = (0..).find(|&first| (first..first + count).all(|i| !set.contains(i)));
+            match first {
+                Some(first) if first + count <= 0x100 => {
+                    for index in first..first + count {
+                        set.insert(index);
+                    }
+                    self.bindings.insert(handle, first as u8);
+                }
+                _ => log::warn!("no {:?} binding left for {:?}", kind, handle),
+            }
+        }
+    }
+
+    /// Checks if a global is a loose uniform that is written as a member of the default uniform
+    /// block (see [`Options::default_uniform_binding`])
+    fn is_default_uniform(&self, global: &crate::GlobalVariable) -> bool {
+        self.options.default_uniform_binding.is_some()
+            && global.class == crate::StorageClass::Uniform
+            && match self.module.types[global.ty].inner {
+                TypeInner::Scalar { .. }
+                | TypeInner::Vector { .. }
+                | TypeInner::Matrix { .. }
+                | TypeInner::Array { .. } => true,
+                _ => false,
+            }
+    }
+
+    /// Writes the block that the loose uniforms used by the entry point are gathered into
+    ///
+    /// # Notes
+    /// Nothing is written if the entry point doesn't use any loose uniforms
+    fn write_default_uniform_block(&mut self, binding: u8) -> BackendResult {
+        let ep_info = self.info.get_entry_point(self.entry_point_idx as usize);
+        let uniforms: Vec<_> = self
+            .module
+            .global_variables
+            .iter()
+            .filter(|&(handle, global)| {
+                !ep_info[handle].is_empty() && self.is_default_uniform(global)
+            })
+            .map(|(handle, _)| handle)
+            .collect();
+        if uniforms.is_empty() {
+            return Ok(());
+        }
+
+        write!(self.out, "layout(std140")?;
+        if self.options.version.supports_explicit_locations() {
+            write!(self.out, ", binding = {}", binding)?;
+        }
+        let block_name = self.block_name("DefaultUniforms");
+        writeln!(self.out, ") uniform {} {{", block_name)?;
+
+        for handle in uniforms {
+            let global = &self.module.global_variables[handle];
+            // The indentation is only for readability
+            write!(self.out, "{}", back::INDENT)?;
+            self.write_type(global.ty)?;
+            write!(
+                self.out,
+                " {}",
+                self.names[&NameKey::GlobalVariable(handle)]
+            )?;
+            if let TypeInner::Array { size, .. } = self.module.types[global.ty].inner {
+                self.write_array_size(size)?;
+            }
+            writeln!(self.out, ";")?;
+        }
+
+        writeln!(self.out, "}} {};", DEFAULT_UNIFORMS)?;
+        writeln!(self.out)?;
+
+        self.default_uniform_block = Some(block_name);
+        Ok(())
+    }
+
     /// Helper method used to write non images/sampler globals
     ///
     /// # Notes
@@ -777,12 +1103,10 @@ impl<'a, W: Write> Writer<'a, W> {
         handle: Handle<crate::GlobalVariable>,
         global: &crate::GlobalVariable,
     ) -> BackendResult {
-        if self.options.version.supports_explicit_locations() {
-            if let Some(ref br) = global.binding {
-                match self.options.binding_map.get(br) {
-                    Some(binding) => write!(self.out, "layout(binding = {}) ", binding)?,
-                    None => log::debug!("unassigned binding for {:?}", global.name),
-                }
+        if self.options.version.supports_explicit_locations() && global.binding.is_some() {
+            match self.bindings.get(&handle) {
+                Some(binding) => write!(self.out, "layout(binding = {}) ", binding)?,
+                None => log::debug!("unassigned binding for {:?}", global.name),
             }
         }
 
@@ -813,33 +1137,84 @@ impl<'a, W: Write> Writer<'a, W> {
             self.write_array_size(size)?;
         }
 
-        match self.module.types[global.ty].inner {
-            TypeInner::Scalar { .. } | TypeInner::Vector { .. } | TypeInner::Matrix { .. } => {
+        // Only the globals without a storage qualifier can be initialized, and
+        // blocks are declared along with their type, which can't be constructed
+        let is_block = match self.module.types[global.ty].inner {
+            TypeInner::Struct { top_level, .. } => top_level,
+            _ => false,
+        };
+        if global.class == crate::StorageClass::Private && !is_block {
+            if let Some(init) = global.init {
                 write!(self.out, " = ")?;
-                if let Some(init) = global.init {
-                    self.write_constant(&self.module.constants[init])?;
-                } else {
-                    self.write_zero_init_value(&self.module.types[global.ty].inner)?;
+                self.write_constant(&self.module.constants[init])?;
+            } else {
+                match self.module.types[global.ty].inner {
+                    TypeInner::Scalar { .. }
+                    | TypeInner::Vector { .. }
+                    | TypeInner::Matrix { .. } => {
+                        write!(self.out, " = ")?;
+                        self.write_zero_init_value(&self.module.types[global.ty].inner)?;
+                    }
+                    _ => {}
                 }
             }
-            _ => {}
         }
         writeln!(self.out, ";")?;
 
         Ok(())
     }
 
+    /// Returns the access a storage image needs to be declared with in es
+    ///
+    /// Only the `r32` formats can be both read and written in es, so when the declared access
+    /// allows both, narrow it down to the way the image is actually used by the entry point.
+    fn es_storage_access(
+        &self,
+        handle: Handle<crate::GlobalVariable>,
+        access: crate::StorageAccess,
+        format: crate::StorageFormat,
+    ) -> Result<crate::StorageAccess, Error> {
+        use crate::{StorageAccess as Sa, StorageFormat as Sf};
+
+        if access != Sa::all() {
+            return Ok(access);
+        }
+        match format {
+            Sf::R32Uint | Sf::R32Sint | Sf::R32Float => return Ok(access),
+            _ => {}
+        }
+
+        let usage = self.info.get_entry_point(self.entry_point_idx as usize)[handle];
+        match (
+            usage.contains(valid::GlobalUse::READ),
+            usage.contains(valid::GlobalUse::WRITE),
+        ) {
+            (true, true) => Err(Error::ReadWriteStorageImage(format)),
+            (true, false) => Ok(Sa::LOAD),
+            (false, _) => Ok(Sa::STORE),
+        }
+    }
+
     /// Helper method used to get a name for a global
     ///
     /// Globals have different naming schemes depending on their binding:
     /// - Globals without bindings use the name from the [`Namer`](crate::proc::Namer)
     /// - Globals with resource binding are named `_group_X_binding_Y` where `X`
     ///   is the group and `Y` is the binding
+    /// - Loose uniforms gathered into the default uniform block are accessed as
+    ///   members of its instance
     fn get_global_name(
         &self,
         handle: Handle<crate::GlobalVariable>,
         global: &crate::GlobalVariable,
     ) -> String {
+        if self.is_default_uniform(global) {
+            return format!(
+                "{}.{}",
+                DEFAULT_UNIFORMS,
+                self.names[&NameKey::GlobalVariable(handle)]
+            );
+        }
         match global.binding {
             Some(ref br) => {
                 format!("_group_{}_binding_{}", br.group, br.binding)
@@ -1048,7 +1423,10 @@ impl<'a, W: Write> Writer<'a, W> {
                         write!(self.out, "(")?;
                         for (index, member) in members.iter().enumerate() {
                             let varying_name = VaryingName {
-                                binding: member.binding.as_ref().unwrap(),
+                                binding: member
+                                    .binding
+                                    .as_ref()
+                                    .ok_or(Error::MissingIoBinding(member.ty))?,
                                 stage,
                                 output: false,
                             };
@@ -1061,7 +1439,10 @@ impl<'a, W: Write> Writer<'a, W> {
                     }
                     _ => {
                         let varying_name = VaryingName {
-                            binding: arg.binding.as_ref().unwrap(),
+                            binding: arg
+                                .binding
+                                .as_ref()
+                                .ok_or(Error::MissingIoBinding(arg.ty))?,
                             stage,
                             output: false,
                         };
@@ -1113,6 +1494,16 @@ impl<'a, W: Write> Writer<'a, W> {
         Ok(())
     }
 
+    /// Helper method that makes an [`Error::InvalidExpression`] for an expression of the
+    /// function being written
+    fn invalid_expression(
+        &self,
+        ctx: &back::FunctionCtx,
+        expr: Handle<crate::Expression>,
+    ) -> Error {
+        Error::InvalidExpression(Box::new(ctx.expression_location(self.module, expr)))
+    }
+
     /// Helper method that writes a list of comma separated `T` with a writer function `F`
     ///
     /// The writer function `F` receives a mutable reference to `self` that if needed won't cause
@@ -1150,10 +1541,10 @@ impl<'a, W: Write> Writer<'a, W> {
         use crate::ScalarValue as Sv;
 
         match constant.inner {
-            crate::ConstantInner::Scalar {
-                width: _,
-                ref value,
-            } => match *value {
+            crate::ConstantInner::Scalar { width, ref value } => match *value {
+                // 64 bit integers need a `l` at the end
+                Sv::Sint(int) if width == 8 => write!(self.out, "{}l", int)?,
+                Sv::Uint(int) if width == 8 => write!(self.out, "{}ul", int)?,
                 // Signed integers don't need anything special
                 Sv::Sint(int) => write!(self.out, "{}", int)?,
                 // Unsigned integers need a `u` at the end
@@ -1163,7 +1554,10 @@ impl<'a, W: Write> Writer<'a, W> {
                 Sv::Uint(int) => write!(self.out, "{}u", int)?,
                 // Floats are written using `Debug` instead of `Display` because it always appends the
                 // decimal part even it's zero which is needed for a valid glsl float constant
-                Sv::Float(float) => write!(self.out, "{:?}", float)?,
+                Sv::Float(float) => {
+                    let suffix = if width == 2 { "hf" } else { "" };
+                    write!(self.out, "{:?}{}", float, suffix)?
+                }
                 // Booleans are either `true` or `false` so nothing special needs to be done
                 Sv::Bool(boolean) => write!(self.out, "{}", boolean)?,
             },
@@ -1171,6 +1565,9 @@ impl<'a, W: Write> Writer<'a, W> {
             // `type(components)` where `components` is a comma separated list of constants
             crate::ConstantInner::Composite { ty, ref components } => {
                 self.write_type(ty)?;
+                if let TypeInner::Array { size, .. } = self.module.types[ty].inner {
+                    self.write_array_size(size)?;
+                }
                 write!(self.out, "(")?;
 
                 // Write the comma separated constants
@@ -1185,6 +1582,24 @@ impl<'a, W: Write> Writer<'a, W> {
         Ok(())
     }
 
+    /// Helper method used to generate a block name that's unique between blocks and structs
+    ///
+    /// Blocks are named `name_block_ID` with the stage appended, where `ID` is a
+    /// [`IdGenerator`](IdGenerator) generated number
+    fn block_name(&mut self, name: &str) -> String {
+        let stage_postfix = match self.entry_point.stage {
+            ShaderStage::Vertex => "Vs",
+            ShaderStage::Fragment => "Fs",
+            ShaderStage::Compute => "Cs",
+        };
+        format!(
+            "{}_block_{}{}",
+            name,
+            self.block_id.generate(),
+            stage_postfix
+        )
+    }
+
     /// Helper method used to write structs
     ///
     /// # Notes
@@ -1202,24 +1617,15 @@ impl<'a, W: Write> Writer<'a, W> {
         //  | `members` is a semicolon separated list of `type name`
         //      | `type` is the member type
         //      | `name` is the member name
-        let name = &self.names[&NameKey::Type(handle)];
+        let name = self.names[&NameKey::Type(handle)].clone();
 
         // If struct is a block we need to write `block_name { members }` where `block_name` must be
         // unique between blocks and structs so we add `_block_ID` where `ID` is a `IdGenerator`
         // generated number so it's unique and `members` are the same as in a struct
         if block {
             // Write the block name, it's just the struct name appended with `_block_ID`
-            let stage_postfix = match self.entry_point.stage {
-                ShaderStage::Vertex => "Vs",
-                ShaderStage::Fragment => "Fs",
-                ShaderStage::Compute => "Cs",
-            };
-            let block_name = format!(
-                "{}_block_{}{}",
-                name,
-                self.block_id.generate(),
-                stage_postfix
-            );
+            // Names may already end with an underscore and glsl reserves double underscores
+            let block_name = self.block_name(name.trim_end_matches('_'));
             writeln!(self.out, "{} {{", block_name)?;
 
             self.reflection_names.insert(handle, block_name);
@@ -1503,7 +1909,8 @@ impl<'a, W: Write> Writer<'a, W> {
                     back::FunctionType::EntryPoint(ep_index) => {
                         let ep = &self.module.entry_points[ep_index as usize];
                         if let Some(ref result) = ep.function.result {
-                            let value = value.unwrap();
+                            let value =
+                                value.ok_or_else(|| Error::MissingReturnValue(ep.name.clone()))?;
                             match self.module.types[result.ty].inner {
                                 crate::TypeInner::Struct { ref members, .. } => {
                                     let temp_struct_name = match ctx.expressions[value] {
@@ -1541,7 +1948,10 @@ impl<'a, W: Write> Writer<'a, W> {
                                         }
 
                                         let varying_name = VaryingName {
-                                            binding: member.binding.as_ref().unwrap(),
+                                            binding: member
+                                                .binding
+                                                .as_ref()
+                                                .ok_or(Error::MissingIoBinding(member.ty))?,
                                             stage: ep.stage,
                                             output: true,
                                         };
@@ -1562,7 +1972,10 @@ impl<'a, W: Write> Writer<'a, W> {
                                 }
                                 _ => {
                                     let name = VaryingName {
-                                        binding: result.binding.as_ref().unwrap(),
+                                        binding: result
+                                            .binding
+                                            .as_ref()
+                                            .ok_or(Error::MissingIoBinding(result.ty))?,
                                         stage: ep.stage,
                                         output: true,
                                     };
@@ -1598,12 +2011,16 @@ impl<'a, W: Write> Writer<'a, W> {
             // in spir-v that's why it's called `Statement::Kill`
             Statement::Kill => writeln!(self.out, "{}discard;", INDENT.repeat(indent))?,
             // Issue an execution or a memory barrier.
+            // `barrier` synchronizes the execution, and the memory barriers before it make
+            // the writes to buffers and shared variables visible to the whole workgroup
             Statement::Barrier(flags) => {
-                if flags.is_empty() {
-                    writeln!(self.out, "{}barrier();", INDENT.repeat(indent))?;
-                } else {
-                    writeln!(self.out, "{}groupMemoryBarrier();", INDENT.repeat(indent))?;
+                if flags.contains(crate::Barrier::STORAGE) {
+                    writeln!(self.out, "{}memoryBarrierBuffer();", INDENT.repeat(indent))?;
                 }
+                if flags.contains(crate::Barrier::WORK_GROUP) {
+                    writeln!(self.out, "{}memoryBarrierShared();", INDENT.repeat(indent))?;
+                }
+                writeln!(self.out, "{}barrier();", INDENT.repeat(indent))?;
             }
             // Stores in glsl are just variable assignments written as `pointer = value;`
             Statement::Store { pointer, value } => {
@@ -1621,10 +2038,9 @@ impl<'a, W: Write> Writer<'a, W> {
                 value,
             } => {
                 write!(self.out, "{}", INDENT.repeat(indent))?;
-                // This will only panic if the module is invalid
                 let dim = match *ctx.info[image].ty.inner_with(&self.module.types) {
                     TypeInner::Image { dim, .. } => dim,
-                    _ => unreachable!(),
+                    _ => return Err(self.invalid_expression(ctx, image)),
                 };
 
                 write!(self.out, "imageStore(")?;
@@ -1644,7 +2060,10 @@ impl<'a, W: Write> Writer<'a, W> {
                 write!(self.out, "{}", INDENT.repeat(indent))?;
                 if let Some(expr) = result {
                     let name = format!("_expr{}", expr.index());
-                    let result = self.module.functions[function].result.as_ref().unwrap();
+                    let result = self.module.functions[function]
+                        .result
+                        .as_ref()
+                        .ok_or(Error::MissingCallResult(function))?;
                     self.write_type(result.ty)?;
                     write!(self.out, " {} = ", name)?;
                     self.named_expressions.insert(expr, name);
@@ -1716,11 +2135,13 @@ impl<'a, W: Write> Writer<'a, W> {
                     }
                     TypeInner::Matrix { .. }
                     | TypeInner::Array { .. }
+                    | TypeInner::BindingArray { .. }
                     | TypeInner::ValuePointer { .. } => write!(self.out, "[{}]", index)?,
                     TypeInner::Struct { .. } => {
-                        // This will never panic in case the type is a `Struct`, this is not true
+                        // Struct types are always resolved to a handle, this is not true
                         // for other types so we can only check while inside this match arm
-                        let ty = base_ty_handle.unwrap();
+                        let ty =
+                            base_ty_handle.ok_or_else(|| self.invalid_expression(ctx, base))?;
 
                         write!(
                             self.out,
@@ -1759,6 +2180,9 @@ impl<'a, W: Write> Writer<'a, W> {
             // comma separated list of expressions
             Expression::Compose { ty, ref components } => {
                 self.write_type(ty)?;
+                if let TypeInner::Array { size, .. } = self.module.types[ty].inner {
+                    self.write_array_size(size)?;
+                }
 
                 write!(self.out, "(")?;
                 self.write_slice(components, |this, _, arg| this.write_expr(*arg, ctx))?;
@@ -1789,9 +2213,67 @@ impl<'a, W: Write> Writer<'a, W> {
             // `textureLod(image, coordinate, level)` - Zero or Exact sample level
             //
             // Furthermore if `depth_ref` is some we need to append it to the coordinate vector
+            //
+            // Gathers use `textureGather(image, coordinate, component)`, or the depth reference
+            // instead of the component, and `textureGatherOffset` with the offset before it
+            Expression::ImageSample {
+                image,
+                gather: Some(component),
+                coordinate,
+                array_index,
+                offset,
+                depth_ref,
+                ..
+            } => {
+                // Shadow samplers only gather comparisons
+                let class = match *ctx.info[image].ty.inner_with(&self.module.types) {
+                    TypeInner::Image { class, .. } => class,
+                    _ => return Err(self.invalid_expression(ctx, image)),
+                };
+                if let (crate::ImageClass::Depth { .. }, None) = (class, depth_ref) {
+                    return Err(Error::Custom(
+                        "Gathering from depth images without a reference isn't supported".into(),
+                    ));
+                }
+
+                let offset_name = match offset {
+                    Some(_) => "Offset",
+                    None => "",
+                };
+                write!(self.out, "textureGather{}(", offset_name)?;
+                self.write_expr(image, ctx)?;
+                write!(self.out, ", ")?;
+                match array_index {
+                    Some(expr) => {
+                        let size = match *ctx.info[coordinate].ty.inner_with(&self.module.types) {
+                            TypeInner::Vector { size, .. } => size as u8,
+                            _ => return Err(self.invalid_expression(ctx, coordinate)),
+                        };
+                        write!(self.out, "vec{}(", size + 1)?;
+                        self.write_expr(coordinate, ctx)?;
+                        write!(self.out, ", ")?;
+                        self.write_expr(expr, ctx)?;
+                        write!(self.out, ")")?;
+                    }
+                    None => self.write_expr(coordinate, ctx)?,
+                }
+                if let Some(expr) = depth_ref {
+                    write!(self.out, ", ")?;
+                    self.write_expr(expr, ctx)?;
+                }
+                if let Some(constant) = offset {
+                    write!(self.out, ", ")?;
+                    self.write_constant(&self.module.constants[constant])?;
+                }
+                if depth_ref.is_none() {
+                    write!(self.out, ", {}", component as u8)?;
+                }
+                write!(self.out, ")")?
+            }
             Expression::ImageSample {
                 image,
                 sampler: _, //TODO?
+                gather: None,
                 coordinate,
                 array_index,
                 offset,
@@ -1800,18 +2282,53 @@ impl<'a, W: Write> Writer<'a, W> {
             } => {
                 let dim = match *ctx.info[image].ty.inner_with(&self.module.types) {
                     TypeInner::Image { dim, .. } => dim,
-                    _ => unreachable!(),
+                    _ => return Err(self.invalid_expression(ctx, image)),
                 };
 
+                let shadow_lod = self
+                    .options
+                    .writer_flags
+                    .contains(WriterFlags::TEXTURE_SHADOW_LOD);
+                if depth_ref.is_some() && array_index.is_some() {
+                    // Reject what the array shadow samplers have no overloads for
+                    let cube = dim == crate::ImageDimension::Cube;
+                    let unsupported = match level {
+                        crate::SampleLevel::Gradient { .. } if cube => {
+                            Some(("samplerCubeArrayShadow", "explicit gradients"))
+                        }
+                        crate::SampleLevel::Zero | crate::SampleLevel::Exact(_)
+                            if cube && !shadow_lod =>
+                        {
+                            Some(("samplerCubeArrayShadow", "an explicit level"))
+                        }
+                        crate::SampleLevel::Bias(_) if !shadow_lod => Some((
+                            if cube {
+                                "samplerCubeArrayShadow"
+                            } else {
+                                "sampler2DArrayShadow"
+                            },
+                            "a level bias",
+                        )),
+                        crate::SampleLevel::Auto
+                            if offset.is_some()
+                                && !self.options.version.supports_array_shadow_offset()
+                                && !shadow_lod =>
+                        {
+                            Some(("sampler2DArrayShadow", "an offset before GLSL 430"))
+                        }
+                        _ => None,
+                    };
+                    if let Some((sampler, operation)) = unsupported {
+                        return Err(Error::UnsupportedShadowSample { sampler, operation });
+                    }
+                }
+
                 // textureLod on sampler2DArrayShadow and samplerCubeShadow does not exist in GLSL.
                 // To emulate this, we will have to use textureGrad with a constant gradient of 0.
                 let workaround_lod_array_shadow_as_grad = (array_index.is_some()
                     || dim == crate::ImageDimension::Cube)
                     && depth_ref.is_some()
-                    && !self
-                        .options
-                        .writer_flags
-                        .contains(WriterFlags::TEXTURE_SHADOW_LOD);
+                    && !shadow_lod;
 
                 //Write the function to be used depending on the sample level
                 let fun_name = match level {
@@ -1838,32 +2355,58 @@ impl<'a, W: Write> Writer<'a, W> {
                 write!(self.out, ", ")?;
 
                 // We need to get the coordinates vector size to later build a vector that's `size + 1`
-                // if `depth_ref` is some, if it isn't a vector we panic as that's not a valid expression
+                // if `depth_ref` is some, 1D images use scalar coordinates
                 let size = match *ctx.info[coordinate].ty.inner_with(&self.module.types) {
-                    TypeInner::Vector { size, .. } => size,
-                    _ => unreachable!(),
+                    TypeInner::Vector { size, .. } => size as u8,
+                    TypeInner::Scalar { .. } => 1,
+                    _ => return Err(self.invalid_expression(ctx, coordinate)),
                 };
 
-                let mut coord_dim = size as u8;
+                let mut coord_dim = size;
                 if array_index.is_some() {
                     coord_dim += 1;
                 }
-                if depth_ref.is_some() {
+                // `sampler1DShadow` still takes the reference as the third component
+                let pad_1d_shadow = depth_ref.is_some() && coord_dim == 1;
+                if pad_1d_shadow {
+                    coord_dim += 1;
+                }
+                // `samplerCubeArrayShadow` takes the reference as a separate argument
+                let separate_depth_ref = depth_ref.is_some() && coord_dim == 4;
+                if depth_ref.is_some() && !separate_depth_ref {
                     coord_dim += 1;
                 }
 
                 // Compose a new texture coordinates vector
-                write!(self.out, "vec{}(", coord_dim)?;
-                self.write_expr(coordinate, ctx)?;
-                if let Some(expr) = array_index {
-                    write!(self.out, ", ")?;
-                    self.write_expr(expr, ctx)?;
+                if coord_dim == 1 {
+                    self.write_expr(coordinate, ctx)?;
+                } else {
+                    write!(self.out, "vec{}(", coord_dim)?;
+                    self.write_expr(coordinate, ctx)?;
+                    if let Some(expr) = array_index {
+                        write!(self.out, ", ")?;
+                        self.write_expr(expr, ctx)?;
+                    }
+                    if pad_1d_shadow {
+                        write!(self.out, ", 0.0")?;
+                    }
+                    if let (Some(expr), false) = (depth_ref, separate_depth_ref) {
+                        write!(self.out, ", ")?;
+                        self.write_expr(expr, ctx)?;
+                    }
+                    write!(self.out, ")")?;
                 }
-                if let Some(expr) = depth_ref {
+                if let (Some(expr), true) = (depth_ref, separate_depth_ref) {
                     write!(self.out, ", ")?;
                     self.write_expr(expr, ctx)?;
                 }
-                write!(self.out, ")")?;
+
+                // The gradients of the workaround match the coordinates
+                let zero_gradient = if dim == crate::ImageDimension::Cube {
+                    ", vec3(0,0,0), vec3(0,0,0)"
+                } else {
+                    ", vec2(0,0), vec2(0,0)"
+                };
 
                 match level {
                     // Auto needs no more arguments
@@ -1871,7 +2414,7 @@ impl<'a, W: Write> Writer<'a, W> {
                     // Zero needs level set to 0
                     crate::SampleLevel::Zero => {
                         if workaround_lod_array_shadow_as_grad {
-                            write!(self.out, ", vec2(0,0), vec2(0,0)")?;
+                            write!(self.out, "{}", zero_gradient)?;
                         } else {
                             write!(self.out, ", 0.0")?;
                         }
@@ -1880,7 +2423,7 @@ impl<'a, W: Write> Writer<'a, W> {
                     crate::SampleLevel::Exact(expr) => {
                         if workaround_lod_array_shadow_as_grad {
                             log::warn!("Unable to `textureLod` a shadow array, ignoring the LOD");
-                            write!(self.out, ", vec2(0,0), vec2(0,0)")?;
+                            write!(self.out, "{}", zero_gradient)?;
                         } else {
                             write!(self.out, ", ")?;
                             self.write_expr(expr, ctx)?;
@@ -1920,21 +2463,25 @@ impl<'a, W: Write> Writer<'a, W> {
                 array_index,
                 index,
             } => {
-                // This will only panic if the module is invalid
                 let (dim, class) = match *ctx.info[image].ty.inner_with(&self.module.types) {
                     TypeInner::Image {
                         dim,
                         arrayed: _,
                         class,
                     } => (dim, class),
-                    _ => unreachable!(),
+                    _ => return Err(self.invalid_expression(ctx, image)),
                 };
 
                 let fun_name = match class {
-                    crate::ImageClass::Sampled { .. } => "texelFetch",
+                    crate::ImageClass::Sampled { .. }
+                    | crate::ImageClass::Depth { multi: true } => "texelFetch",
                     crate::ImageClass::Storage(_) => "imageLoad",
-                    // TODO: Is there even a function for this?
-                    crate::ImageClass::Depth => todo!(),
+                    // Shadow samplers can only be used for comparisons
+                    crate::ImageClass::Depth { multi: false } => {
+                        return Err(Error::Custom(
+                            "Loading from non-multisampled depth images isn't supported".into(),
+                        ))
+                    }
                 };
 
                 write!(self.out, "{}(", fun_name)?;
@@ -1947,6 +2494,12 @@ impl<'a, W: Write> Writer<'a, W> {
                     self.write_expr(index_expr, ctx)?;
                 }
                 write!(self.out, ")")?;
+
+                // Multisampled depth images are declared as regular samplers, which return a
+                // `vec4` instead of the depth value
+                if let crate::ImageClass::Depth { multi: true } = class {
+                    write!(self.out, ".x")?;
+                }
             }
             // Query translates into one of the:
             // - textureSize/imageSize
@@ -1955,14 +2508,13 @@ impl<'a, W: Write> Writer<'a, W> {
             Expression::ImageQuery { image, query } => {
                 use crate::ImageClass;
 
-                // This will only panic if the module is invalid
                 let (dim, class) = match *ctx.info[image].ty.inner_with(&self.module.types) {
                     TypeInner::Image {
                         dim,
                         arrayed: _,
                         class,
                     } => (dim, class),
-                    _ => unreachable!(),
+                    _ => return Err(self.invalid_expression(ctx, image)),
                 };
                 let components = match dim {
                     crate::ImageDimension::D1 => 1,
@@ -1973,7 +2525,13 @@ impl<'a, W: Write> Writer<'a, W> {
                 match query {
                     crate::ImageQuery::Size { level } => {
                         match class {
-                            ImageClass::Sampled { .. } | ImageClass::Depth => {
+                            // Multisampled images have no mipmaps, so there's no level argument
+                            ImageClass::Sampled { multi: true, .. }
+                            | ImageClass::Depth { multi: true } => {
+                                write!(self.out, "textureSize(")?;
+                                self.write_expr(image, ctx)?;
+                            }
+                            ImageClass::Sampled { .. } | ImageClass::Depth { .. } => {
                                 write!(self.out, "textureSize(")?;
                                 self.write_expr(image, ctx)?;
                                 write!(self.out, ",")?;
@@ -1996,24 +2554,41 @@ impl<'a, W: Write> Writer<'a, W> {
                         write!(self.out, ")",)?;
                     }
                     crate::ImageQuery::NumLayers => {
-                        let fun_name = match class {
-                            ImageClass::Sampled { .. } | ImageClass::Depth => "textureSize",
-                            ImageClass::Storage(_) => "imageSize",
+                        let (fun_name, level) = match class {
+                            ImageClass::Sampled { multi: true, .. }
+                            | ImageClass::Depth { multi: true } => ("textureSize", ""),
+                            ImageClass::Sampled { .. } | ImageClass::Depth { .. } => {
+                                ("textureSize", ",0")
+                            }
+                            ImageClass::Storage(_) => ("imageSize", ""),
                         };
                         write!(self.out, "{}(", fun_name)?;
                         self.write_expr(image, ctx)?;
-                        write!(self.out, ",0).{}", back::COMPONENTS[components])?;
+                        write!(self.out, "{}).{}", level, back::COMPONENTS[components])?;
                     }
                     crate::ImageQuery::NumSamples => {
                         // assumes ARB_shader_texture_image_samples
                         let fun_name = match class {
-                            ImageClass::Sampled { .. } | ImageClass::Depth => "textureSamples",
+                            ImageClass::Sampled { .. } | ImageClass::Depth { .. } => {
+                                "textureSamples"
+                            }
                             ImageClass::Storage(_) => "imageSamples",
                         };
                         write!(self.out, "{}(", fun_name)?;
                         self.write_expr(image, ctx)?;
                         write!(self.out, ")",)?;
                     }
+                    // The sampler is combined with the image
+                    crate::ImageQuery::Lod {
+                        sampler: _,
+                        coordinate,
+                    } => {
+                        write!(self.out, "textureQueryLod(")?;
+                        self.write_expr(image, ctx)?;
+                        write!(self.out, ", ")?;
+                        self.write_expr(coordinate, ctx)?;
+                        write!(self.out, ")")?;
+                    }
                 }
             }
             // `Unary` is pretty straightforward
@@ -2025,26 +2600,29 @@ impl<'a, W: Write> Writer<'a, W> {
             Expression::Unary { op, expr } => {
                 use crate::{ScalarKind as Sk, UnaryOperator as Uo};
 
-                write!(
-                    self.out,
-                    "({} ",
-                    match op {
-                        Uo::Negate => "-",
-                        Uo::Not => match *ctx.info[expr].ty.inner_with(&self.module.types) {
-                            TypeInner::Scalar { kind: Sk::Sint, .. } => "~",
-                            TypeInner::Scalar { kind: Sk::Uint, .. } => "~",
-                            TypeInner::Scalar { kind: Sk::Bool, .. } => "!",
-                            ref other =>
-                                return Err(Error::Custom(format!(
-                                    "Cannot apply not to type {:?}",
-                                    other
-                                ))),
-                        },
-                    }
-                )?;
+                // The opening of the expression, which is wrapped in parentheses
+                // to avoid precedence issues
+                let prefix = match op {
+                    Uo::Negate => "(- ",
+                    Uo::Not => match *ctx.info[expr].ty.inner_with(&self.module.types) {
+                        TypeInner::Scalar { kind: Sk::Sint, .. }
+                        | TypeInner::Scalar { kind: Sk::Uint, .. }
+                        | TypeInner::Vector { kind: Sk::Sint, .. }
+                        | TypeInner::Vector { kind: Sk::Uint, .. } => "(~ ",
+                        TypeInner::Scalar { kind: Sk::Bool, .. } => "(! ",
+                        // The logical not of a boolean vector is a builtin function
+                        TypeInner::Vector { kind: Sk::Bool, .. } => "not(",
+                        ref other => {
+                            return Err(Error::Custom(format!(
+                                "Cannot apply not to type {:?}",
+                                other
+                            )))
+                        }
+                    },
+                };
 
+                write!(self.out, "{}", prefix)?;
                 self.write_expr(expr, ctx)?;
-
                 write!(self.out, ")")?
             }
             // `Binary` we just write `left op right`, except when dealing with
@@ -2102,18 +2680,27 @@ impl<'a, W: Write> Writer<'a, W> {
                 write!(self.out, ")")?
             }
             // `Derivative` is a function call to a glsl provided function
-            Expression::Derivative { axis, expr } => {
-                use crate::DerivativeAxis as Da;
+            Expression::Derivative { axis, ctrl, expr } => {
+                use crate::{DerivativeAxis as Axis, DerivativeControl as Ctrl};
 
-                write!(
-                    self.out,
-                    "{}(",
-                    match axis {
-                        Da::X => "dFdx",
-                        Da::Y => "dFdy",
-                        Da::Width => "fwidth",
-                    }
-                )?;
+                // without support for them, the precision hints are dropped
+                let ctrl = if self.options.version.supports_derivative_control() {
+                    ctrl
+                } else {
+                    Ctrl::None
+                };
+                let fun_name = match (axis, ctrl) {
+                    (Axis::X, Ctrl::Coarse) => "dFdxCoarse",
+                    (Axis::X, Ctrl::Fine) => "dFdxFine",
+                    (Axis::X, Ctrl::None) => "dFdx",
+                    (Axis::Y, Ctrl::Coarse) => "dFdyCoarse",
+                    (Axis::Y, Ctrl::Fine) => "dFdyFine",
+                    (Axis::Y, Ctrl::None) => "dFdy",
+                    (Axis::Width, Ctrl::Coarse) => "fwidthCoarse",
+                    (Axis::Width, Ctrl::Fine) => "fwidthFine",
+                    (Axis::Width, Ctrl::None) => "fwidth",
+                };
+                write!(self.out, "{}(", fun_name)?;
                 self.write_expr(expr, ctx)?;
                 write!(self.out, ")")?
             }
@@ -2203,6 +2790,18 @@ impl<'a, W: Write> Writer<'a, W> {
                     // bits
                     Mf::CountOneBits => "bitCount",
                     Mf::ReverseBits => "bitfieldReverse",
+                    // data packing
+                    Mf::Pack4x8snorm => "packSnorm4x8",
+                    Mf::Pack4x8unorm => "packUnorm4x8",
+                    Mf::Pack2x16snorm => "packSnorm2x16",
+                    Mf::Pack2x16unorm => "packUnorm2x16",
+                    Mf::Pack2x16float => "packHalf2x16",
+                    // data unpacking
+                    Mf::Unpack4x8snorm => "unpackSnorm4x8",
+                    Mf::Unpack4x8unorm => "unpackUnorm4x8",
+                    Mf::Unpack2x16snorm => "unpackSnorm2x16",
+                    Mf::Unpack2x16unorm => "unpackUnorm2x16",
+                    Mf::Unpack2x16float => "unpackHalf2x16",
                 };
 
                 write!(self.out, "{}(", fun_name)?;
@@ -2240,7 +2839,9 @@ impl<'a, W: Write> Writer<'a, W> {
                     None => {
                         use crate::ScalarKind as Sk;
 
-                        let source_kind = inner.scalar_kind().unwrap();
+                        let source_kind = inner
+                            .scalar_kind()
+                            .ok_or_else(|| self.invalid_expression(ctx, expr))?;
                         write!(
                             self.out,
                             "{}",
@@ -2267,13 +2868,15 @@ impl<'a, W: Write> Writer<'a, W> {
                 self.write_expr(expr, ctx)?;
                 write!(self.out, ")")?
             }
-            Expression::Call(_function) => unreachable!(),
+            // Call results are baked into named expressions by the `Call` statement
+            Expression::Call(_function) => return Err(self.invalid_expression(ctx, expr)),
             // `ArrayLength` is written as `expr.length()` and we convert it to a uint
             Expression::ArrayLength(expr) => {
                 write!(self.out, "uint(")?;
                 self.write_expr(expr, ctx)?;
                 write!(self.out, ".length())")?
             }
+            Expression::PointerCast { .. } => return Err(Error::PhysicalStorageBufferNotSupported),
         }
 
         Ok(())
@@ -2439,14 +3042,96 @@ impl<'a, W: Write> Writer<'a, W> {
                     }
                     _ => (),
                 },
+                _ if self.is_default_uniform(var) => {
+                    if let Some(ref name) = self.default_uniform_block {
+                        uniforms.insert(handle, name.clone());
+                    }
+                }
                 _ => continue,
             }
         }
 
-        Ok(ReflectionInfo {
+        let ep_idx = self.entry_point_idx;
+        let mut renamed = proc::renamed_items(self.module, &self.names);
+        renamed.retain(|key, _| match *key {
+            // The entry point is always written as `main`
+            NameKey::EntryPoint(_) => false,
+            NameKey::EntryPointLocal(idx, _) | NameKey::EntryPointArgument(idx, _) => {
+                idx == ep_idx
+            }
+            _ => true,
+        });
+
+        let mut reflection = ReflectionInfo {
             texture_mapping: mappings,
             uniforms,
-        })
+            varying_inputs: crate::FastHashMap::default(),
+            varying_outputs: crate::FastHashMap::default(),
+            built_in_inputs: crate::FastHashMap::default(),
+            built_in_outputs: crate::FastHashMap::default(),
+            bindings: self.bindings.clone(),
+            renamed,
+            entry_point: ENTRY_POINT_NAME.to_string(),
+        };
+        for arg in self.entry_point.function.arguments.iter() {
+            self.collect_varying_names(arg.binding.as_ref(), arg.ty, false, &mut reflection);
+        }
+        if let Some(ref result) = self.entry_point.function.result {
+            self.collect_varying_names(result.binding.as_ref(), result.ty, true, &mut reflection);
+        }
+
+        Ok(reflection)
+    }
+
+    /// Helper method used to collect the names of the varyings and built-ins
+    /// of an entry point argument or result, like [`write_varying`](Self::write_varying)
+    fn collect_varying_names(
+        &self,
+        binding: Option<&crate::Binding>,
+        ty: Handle<crate::Type>,
+        output: bool,
+        reflection: &mut ReflectionInfo,
+    ) {
+        match self.module.types[ty].inner {
+            crate::TypeInner::Struct { ref members, .. } => {
+                for member in members {
+                    self.collect_varying_names(
+                        member.binding.as_ref(),
+                        member.ty,
+                        output,
+                        reflection,
+                    );
+                }
+            }
+            _ => match binding {
+                Some(&crate::Binding::Location { location, .. }) => {
+                    let name = VaryingName {
+                        binding: &crate::Binding::Location {
+                            location,
+                            interpolation: None,
+                            sampling: None,
+                        },
+                        stage: self.entry_point.stage,
+                        output,
+                    };
+                    let varyings = if output {
+                        &mut reflection.varying_outputs
+                    } else {
+                        &mut reflection.varying_inputs
+                    };
+                    varyings.insert(location, name.to_string());
+                }
+                Some(&crate::Binding::BuiltIn(built_in)) => {
+                    let built_ins = if output {
+                        &mut reflection.built_in_outputs
+                    } else {
+                        &mut reflection.built_in_inputs
+                    };
+                    built_ins.insert(built_in, glsl_built_in(built_in, output).to_string());
+                }
+                None => {}
+            },
+        }
     }
 }
 
@@ -2473,15 +3158,33 @@ fn glsl_scalar(
     use crate::ScalarKind as Sk;
 
     Ok(match kind {
-        Sk::Sint => ScalarString {
-            prefix: "i",
-            full: "int",
+        Sk::Sint => match width {
+            4 => ScalarString {
+                prefix: "i",
+                full: "int",
+            },
+            8 => ScalarString {
+                prefix: "i64",
+                full: "int64_t",
+            },
+            _ => return Err(Error::UnsupportedScalar(kind, width)),
         },
-        Sk::Uint => ScalarString {
-            prefix: "u",
-            full: "uint",
+        Sk::Uint => match width {
+            4 => ScalarString {
+                prefix: "u",
+                full: "uint",
+            },
+            8 => ScalarString {
+                prefix: "u64",
+                full: "uint64_t",
+            },
+            _ => return Err(Error::UnsupportedScalar(kind, width)),
         },
         Sk::Float => match width {
+            2 => ScalarString {
+                prefix: "f16",
+                full: "float16_t",
+            },
             4 => ScalarString {
                 prefix: "",
                 full: "float",
@@ -2519,9 +3222,11 @@ fn glsl_built_in(built_in: crate::BuiltIn, output: bool) -> &'static str {
         Bi::InstanceIndex => "uint(gl_InstanceID)",
         Bi::PointSize => "gl_PointSize",
         Bi::VertexIndex => "uint(gl_VertexID)",
+        Bi::ViewIndex => "gl_ViewID_OVR",
         // fragment
         Bi::FragDepth => "gl_FragDepth",
         Bi::FrontFacing => "gl_FrontFacing",
+        Bi::HelperInvocation => "gl_HelperInvocation",
         Bi::PrimitiveIndex => "uint(gl_PrimitiveID)",
         Bi::SampleIndex => "gl_SampleID",
         Bi::SampleMask => {
@@ -2531,10 +3236,12 @@ fn glsl_built_in(built_in: crate::BuiltIn, output: bool) -> &'static str {
                 "gl_SampleMaskIn"
             }
         }
+        Bi::SamplePosition => "gl_SamplePosition",
         // compute
         Bi::GlobalInvocationId => "gl_GlobalInvocationID",
         Bi::LocalInvocationId => "gl_LocalInvocationID",
         Bi::LocalInvocationIndex => "gl_LocalInvocationIndex",
+        Bi::NumWorkGroups => "gl_NumWorkGroups",
         Bi::WorkGroupId => "gl_WorkGroupID",
         Bi::WorkGroupSize => "gl_WorkGroupSize",
     }
@@ -2551,7 +3258,7 @@ fn glsl_storage_class(class: crate::StorageClass) -> Option<&'static str> {
         Sc::Uniform => Some("uniform"),
         Sc::Handle => Some("uniform"),
         Sc::WorkGroup => Some("shared"),
-        Sc::PushConstant => None,
+        Sc::PushConstant | Sc::PhysicalStorageBuffer => None,
     }
 }
 
@@ -2611,11 +3318,11 @@ fn glsl_storage_format(format: crate::StorageFormat) -> &'static str {
         Sf::Rg16Uint => "rg16ui",
         Sf::Rg16Sint => "rg16i",
         Sf::Rg16Float => "rg16f",
-        Sf::Rgba8Unorm => "rgba8ui",
+        Sf::Rgba8Unorm => "rgba8",
         Sf::Rgba8Snorm => "rgba8_snorm",
         Sf::Rgba8Uint => "rgba8ui",
         Sf::Rgba8Sint => "rgba8i",
-        Sf::Rgb10a2Unorm => "rgb10_a2ui",
+        Sf::Rgb10a2Unorm => "rgb10_a2",
         Sf::Rg11b10Float => "r11f_g11f_b10f",
         Sf::Rg32Uint => "rg32ui",
         Sf::Rg32Sint => "rg32i",
@@ -2629,6 +3336,38 @@ fn glsl_storage_format(format: crate::StorageFormat) -> &'static str {
     }
 }
 
+/// Helper function that returns the closest format supported by core glsl es for the
+/// [`StorageFormat`](crate::StorageFormat)s that require `GL_NV_image_formats` there
+fn es_storage_format_fallback(format: crate::StorageFormat) -> Option<crate::StorageFormat> {
+    use crate::StorageFormat as Sf;
+
+    Some(match format {
+        Sf::R8Unorm | Sf::Rg8Unorm => Sf::Rgba8Unorm,
+        Sf::R8Snorm | Sf::Rg8Snorm => Sf::Rgba8Snorm,
+        Sf::R8Uint | Sf::Rg8Uint => Sf::Rgba8Uint,
+        Sf::R8Sint | Sf::Rg8Sint => Sf::Rgba8Sint,
+        Sf::R16Uint | Sf::Rg16Uint => Sf::Rgba16Uint,
+        Sf::R16Sint | Sf::Rg16Sint => Sf::Rgba16Sint,
+        Sf::R16Float | Sf::Rg16Float | Sf::Rgb10a2Unorm | Sf::Rg11b10Float => Sf::Rgba16Float,
+        Sf::Rg32Uint => Sf::Rgba32Uint,
+        Sf::Rg32Sint => Sf::Rgba32Sint,
+        Sf::Rg32Float => Sf::Rgba32Float,
+        Sf::R32Uint
+        | Sf::R32Sint
+        | Sf::R32Float
+        | Sf::Rgba8Unorm
+        | Sf::Rgba8Snorm
+        | Sf::Rgba8Uint
+        | Sf::Rgba8Sint
+        | Sf::Rgba16Uint
+        | Sf::Rgba16Sint
+        | Sf::Rgba16Float
+        | Sf::Rgba32Uint
+        | Sf::Rgba32Sint
+        | Sf::Rgba32Float => return None,
+    })
+}
+
 /// Helper function that return the glsl storage access string of [`StorageAccess`](crate::StorageAccess)
 ///
 /// glsl allows adding both `readonly` and `writeonly` but this means that
@@ -0,0 +1,807 @@
+//! Tests of the GLSL writer that don't need a GLSL compiler.
+//!
+//! The modules are written with [`write`], which takes care of the pipeline
+//! options: the entry point is always called `main`.
+
+use super::{
+    Error, Features, Options, PipelineOptions, ReflectionInfo, Version, Writer, WriterFlags,
+};
+use crate::{proc::NameKey, valid, ShaderStage};
+
+/// Validate `module`, allowing `capabilities`.
+fn validate(module: &crate::Module, capabilities: valid::Capabilities) -> valid::ModuleInfo {
+    valid::Validator::new(valid::ValidationFlags::all(), capabilities)
+        .validate(module)
+        .unwrap()
+}
+
+/// Parse and validate WGSL source.
+#[cfg(feature = "wgsl-in")]
+fn parse_wgsl(
+    source: &str,
+    capabilities: valid::Capabilities,
+) -> (crate::Module, valid::ModuleInfo) {
+    let module = crate::front::wgsl::parse_str(source).unwrap();
+    let info = validate(&module, capabilities);
+    (module, info)
+}
+
+/// Parse and validate GLSL source of the given stage.
+#[cfg(feature = "glsl-in")]
+fn parse_glsl(source: &str, stage: ShaderStage) -> (crate::Module, valid::ModuleInfo) {
+    let options = crate::front::glsl::Options::from_stage(stage);
+    let module = crate::front::glsl::parse_str(source, &options).unwrap();
+    let info = validate(&module, valid::Capabilities::empty());
+    (module, info)
+}
+
+/// The pipeline options selecting the `main` entry point of `stage`.
+fn pipeline(stage: ShaderStage) -> PipelineOptions {
+    PipelineOptions {
+        shader_stage: stage,
+        entry_point: "main".to_string(),
+        multiview: None,
+    }
+}
+
+/// Write the `main` entry point of `stage`.
+fn write(
+    module: &crate::Module,
+    info: &valid::ModuleInfo,
+    options: &Options,
+    stage: ShaderStage,
+) -> Result<(String, ReflectionInfo), Error> {
+    write_pipeline(module, info, options, &pipeline(stage))
+}
+
+/// Write the entry point selected by `pipeline_options`.
+fn write_pipeline(
+    module: &crate::Module,
+    info: &valid::ModuleInfo,
+    options: &Options,
+    pipeline_options: &PipelineOptions,
+) -> Result<(String, ReflectionInfo), Error> {
+    let mut out = String::new();
+    let reflection = Writer::new(&mut out, module, info, options, pipeline_options)?.write()?;
+    Ok((out, reflection))
+}
+
+/// Options targeting `version`.
+fn version_options(version: Version) -> Options {
+    Options {
+        version,
+        ..Options::default()
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn reserved_identifiers() {
+    let (module, info) = parse_wgsl(
+        "
+        struct gl_Data { a__b: f32; };
+        fn __helper(gl_arg: gl_Data) -> f32 { return gl_arg.a__b; }
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] f32 {
+            var data: gl_Data;
+            return __helper(data);
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let (out, reflection) =
+        write(&module, &info, &Options::default(), ShaderStage::Fragment).unwrap();
+
+    assert!(!out.contains("__"));
+    assert!(!out.contains(" gl_Data") && !out.contains(" gl_arg"));
+    let ty = module.types.find_by_name("gl_Data").unwrap();
+    let fun = module.functions.find_by_name("__helper").unwrap();
+    assert_eq!(reflection.renamed[&NameKey::Type(ty)], "gen_gl_Data");
+    assert_eq!(reflection.renamed[&NameKey::StructMember(ty, 0)], "a_b");
+    assert_eq!(reflection.renamed[&NameKey::Function(fun)], "_helper");
+    assert_eq!(
+        reflection.renamed[&NameKey::FunctionArgument(fun, 0)],
+        "gen_gl_arg"
+    );
+    assert!(!reflection.renamed.contains_key(&NameKey::EntryPoint(0)));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn reflection_info() {
+    let (module, info) = parse_wgsl(
+        "
+        [[block]]
+        struct Globals { scale: f32; };
+        struct FragmentInput {
+            [[builtin(position)]] position: vec4<f32>;
+            [[location(0)]] uv: vec2<f32>;
+            [[location(2)]] tint: f32;
+        };
+        struct FragmentOutput {
+            [[location(0)]] color: vec4<f32>;
+            [[builtin(frag_depth)]] depth: f32;
+        };
+        [[group(0), binding(0)]] var<uniform> globals: Globals;
+        [[group(0), binding(1)]] var tex: texture_2d<f32>;
+        [[group(0), binding(2)]] var samp: sampler;
+        [[stage(fragment)]]
+        fn main(input: FragmentInput) -> FragmentOutput {
+            let color = textureSample(tex, samp, input.uv) * input.tint * globals.scale;
+            return FragmentOutput(color, input.position.z);
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let (out, reflection) =
+        write(&module, &info, &Options::default(), ShaderStage::Fragment).unwrap();
+
+    let global = |name: &str| {
+        module
+            .global_variables
+            .iter()
+            .find(|&(_, var)| var.name.as_deref() == Some(name))
+            .unwrap()
+            .0
+    };
+    let (sampler_name, mapping) = reflection.texture_mapping.iter().next().unwrap();
+    assert_eq!(reflection.texture_mapping.len(), 1);
+    assert_eq!(mapping.texture, global("tex"));
+    assert_eq!(mapping.sampler, Some(global("samp")));
+    assert!(out.contains(&format!("uniform highp sampler2D {};", sampler_name)));
+    let block_name = &reflection.uniforms[&global("globals")];
+    assert!(out.contains(&format!("uniform {} {{", block_name)));
+
+    assert_eq!(reflection.varying_inputs.len(), 2);
+    assert_eq!(reflection.varying_inputs[&0], "_vs2fs_location0");
+    assert_eq!(reflection.varying_inputs[&2], "_vs2fs_location2");
+    assert_eq!(reflection.varying_outputs.len(), 1);
+    assert_eq!(reflection.varying_outputs[&0], "_fs2p_location0");
+    assert_eq!(reflection.built_in_inputs.len(), 1);
+    assert_eq!(
+        reflection.built_in_inputs[&crate::BuiltIn::Position],
+        "gl_FragCoord"
+    );
+    assert_eq!(reflection.built_in_outputs.len(), 1);
+    assert_eq!(
+        reflection.built_in_outputs[&crate::BuiltIn::FragDepth],
+        "gl_FragDepth"
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn binding_allocation() {
+    let (module, info) = parse_wgsl(
+        "
+        [[block]]
+        struct Globals { scale: f32; };
+        [[block]]
+        struct Data { values: array<f32>; };
+        [[group(0), binding(0)]] var<uniform> globals: Globals;
+        [[group(0), binding(1)]] var<storage> data: [[access(read_write)]] Data;
+        [[group(0), binding(2)]] var tex1: texture_2d<f32>;
+        [[group(0), binding(3)]] var tex2: texture_2d<f32>;
+        [[group(1), binding(0)]] var image: [[access(write)]] texture_storage_2d<rgba8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            let color = textureLoad(tex1, vec2<i32>(0), 0) + textureLoad(tex2, vec2<i32>(0), 0);
+            textureStore(image, vec2<i32>(0), color * globals.scale);
+            data.values[0] = color.x;
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let global = |name: &str| {
+        module
+            .global_variables
+            .iter()
+            .find(|&(_, var)| var.name.as_deref() == Some(name))
+            .unwrap()
+            .0
+    };
+
+    let mut options = version_options(Version::Desktop(450));
+    let mapped = |group, binding| crate::ResourceBinding { group, binding };
+    options.binding_map.insert(mapped(0, 1), 3);
+    options.binding_map.insert(mapped(0, 3), 0);
+
+    // Without allocation, only the mapped resources have a binding
+    let (_, reflection) = write(&module, &info, &options, ShaderStage::Compute).unwrap();
+    let bindings = reflection.bindings;
+    assert_eq!(bindings.len(), 2);
+    assert_eq!(bindings[&global("data")], 3);
+    assert_eq!(bindings[&global("tex2")], 0);
+
+    // Each kind of resource has its own binding points
+    options.allocate_bindings = true;
+    let (out, reflection) = write(&module, &info, &options, ShaderStage::Compute).unwrap();
+    let bindings = reflection.bindings;
+    assert_eq!(bindings.len(), 5);
+    assert_eq!(bindings[&global("globals")], 0);
+    assert_eq!(bindings[&global("data")], 3);
+    assert_eq!(bindings[&global("tex1")], 1);
+    assert_eq!(bindings[&global("tex2")], 0);
+    assert_eq!(bindings[&global("image")], 0);
+    assert!(out.contains("layout(binding = 1) uniform highp sampler2D"));
+    assert!(out.contains("layout(binding = 0,rgba8) writeonly uniform highp image2D"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn names_across_scopes() {
+    let (module, info) = parse_wgsl(
+        "
+        var<private> sample: f32;
+        fn active(input: f32) -> f32 {
+            let sample = input * 2.0;
+            var active: f32 = sample;
+            return active;
+        }
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            var _expr3: f32 = active(sample);
+            return vec4<f32>(_expr3);
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let (out, _) = write(&module, &info, &Options::default(), ShaderStage::Fragment).unwrap();
+
+    // Keywords are renamed, and locals never shadow globals or functions
+    assert!(out.contains("float sample1 = 0.0;"));
+    assert!(out.contains("float active1(float input1)"));
+    assert!(out.contains("float active2;"));
+    assert!(out.contains("float sample2 = "));
+    // User names can't collide with the names of baked expressions
+    assert!(out.contains("float _expr3_;"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn builtin_function_names() {
+    let (module, info) = parse_wgsl(
+        "
+        var<private> filter: f32;
+        fn texture(mod: f32) -> f32 {
+            return max(mod, 0.5);
+        }
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(texture(filter));
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let (out, _) = write(&module, &info, &Options::default(), ShaderStage::Fragment).unwrap();
+
+    // User declarations don't shadow the built-in functions
+    assert!(out.contains("float filter1 = 0.0;"));
+    assert!(out.contains("float texture1(float mod1)"));
+    assert!(out.contains("max(mod1, 0.5)"));
+    assert!(out.contains("texture1(_expr"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn required_extensions() {
+    let (module, info) = parse_wgsl(
+        "
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {}
+        ",
+        valid::Capabilities::empty(),
+    );
+    let extensions = |version| {
+        Writer::new(
+            String::new(),
+            &module,
+            &info,
+            &version_options(version),
+            &pipeline(ShaderStage::Compute),
+        )
+        .map(|writer| writer.required_extensions())
+    };
+
+    assert_eq!(
+        extensions(Version::Desktop(420)).unwrap(),
+        ["GL_ARB_compute_shader"]
+    );
+    // Compute shaders are core since GLSL 4.30 and GLSL ES 3.10
+    assert!(extensions(Version::Desktop(430)).unwrap().is_empty());
+    assert!(extensions(Version::Embedded(310)).unwrap().is_empty());
+    // Compute shaders aren't available at all before GLSL 4.20
+    match extensions(Version::Desktop(330)) {
+        Err(Error::MissingFeatures(features)) => assert_eq!(features, Features::COMPUTE_SHADER),
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn extension_directives() {
+    let (module, info) = parse_wgsl(
+        "
+        [[group(0), binding(0)]] var tex: texture_cube_array<f32>;
+        [[group(0), binding(1)]] var samp: sampler;
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return textureSample(tex, samp, vec3<f32>(1.0), 0);
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let write = |version| {
+        let options = version_options(version);
+        write(&module, &info, &options, ShaderStage::Fragment)
+            .unwrap()
+            .0
+    };
+
+    // The extensions directly follow the version
+    assert!(write(Version::Embedded(310))
+        .starts_with("#version 310 es\n#extension GL_EXT_texture_cube_map_array : require\n"));
+    assert!(write(Version::Desktop(330))
+        .starts_with("#version 330 core\n#extension GL_ARB_texture_cube_map_array : require\n"));
+    // Cube map arrays are core since GLSL 4.00
+    assert!(!write(Version::Desktop(400)).contains("#extension"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn texture_gathers() {
+    let (module, info) = parse_wgsl(
+        "
+        [[group(0), binding(0)]] var tex: texture_2d<f32>;
+        [[group(0), binding(1)]] var samp: sampler;
+        [[group(0), binding(2)]] var depth: texture_depth_2d;
+        [[group(0), binding(3)]] var samp_cmp: sampler_comparison;
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            let tc = vec2<f32>(0.5);
+            return textureGather(2, tex, samp, tc, vec2<i32>(1, 0))
+                + textureGatherCompare(depth, samp_cmp, tc, 0.5);
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let write = |version| {
+        write(
+            &module,
+            &info,
+            &version_options(version),
+            ShaderStage::Fragment,
+        )
+    };
+
+    let (out, _) = write(Version::Desktop(400)).unwrap();
+    assert!(out.contains("textureGatherOffset(_group_0_binding_0, tc, ivec2(1, 0), 2)"));
+    assert!(out.contains("textureGather(_group_0_binding_2, tc, 0.5)"));
+    match write(Version::Desktop(330)) {
+        Err(Error::MissingFeatures(features)) => {
+            assert_eq!(features, Features::TEXTURE_GATHER)
+        }
+        _ => panic!("gathers need GLSL 4.00"),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn es_storage_images() {
+    let write = |source: &str, writer_flags| {
+        let (module, info) = parse_wgsl(source, valid::Capabilities::empty());
+        let options = Options {
+            version: Version::Embedded(310),
+            writer_flags,
+            ..Options::default()
+        };
+        write(&module, &info, &options, ShaderStage::Compute).map(|(out, _)| out)
+    };
+
+    // Read-write images that are only written are declared `writeonly`
+    let output = write(
+        "
+        [[group(0), binding(0)]]
+        var image: [[access(read_write)]] texture_storage_2d<rgba8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            textureStore(image, vec2<i32>(0, 0), vec4<f32>(1.0));
+        }
+        ",
+        WriterFlags::empty(),
+    )
+    .unwrap();
+    assert!(output.contains("layout(rgba8) writeonly uniform highp image2D"));
+
+    let read_write = "
+        [[group(0), binding(0)]]
+        var image: [[access(read_write)]] texture_storage_2d<rgba8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            textureStore(image, vec2<i32>(0, 0), textureLoad(image, vec2<i32>(1, 1)));
+        }
+        ";
+    match write(read_write, WriterFlags::empty()) {
+        Err(Error::ReadWriteStorageImage(crate::StorageFormat::Rgba8Unorm)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    let rg8 = "
+        [[group(0), binding(0)]]
+        var image: [[access(write)]] texture_storage_2d<rg8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            textureStore(image, vec2<i32>(0, 0), vec4<f32>(1.0));
+        }
+        ";
+    assert!(write(rg8, WriterFlags::empty())
+        .unwrap()
+        .contains("GL_NV_image_formats"));
+    match write(rg8, WriterFlags::CORE_ES_IMAGE_FORMATS) {
+        Err(Error::UnsupportedStorageFormat {
+            format: crate::StorageFormat::Rg8Unorm,
+            closest: crate::StorageFormat::Rgba8Unorm,
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn multiview() {
+    let (module, info) = parse_wgsl(
+        "
+        [[stage(vertex)]]
+        fn main([[builtin(view_index)]] view: u32) -> [[builtin(position)]] vec4<f32> {
+            return vec4<f32>(f32(view));
+        }
+        ",
+        valid::Capabilities::MULTIVIEW,
+    );
+    let pipeline_options = PipelineOptions {
+        multiview: std::num::NonZeroU32::new(2),
+        ..pipeline(ShaderStage::Vertex)
+    };
+    let (out, _) = write_pipeline(
+        &module,
+        &info,
+        &version_options(Version::Desktop(330)),
+        &pipeline_options,
+    )
+    .unwrap();
+
+    assert!(out.contains("#extension GL_OVR_multiview2 : require"));
+    assert!(out.contains("layout(num_views = 2) in;"));
+    assert!(out.contains("gl_ViewID_OVR"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn shadow_sampling() {
+    let write = |sample: &str, version, writer_flags| {
+        let source = format!(
+            "
+            [[group(0), binding(0)]]
+            var shadow_2d_array: texture_depth_2d_array;
+            [[group(0), binding(1)]]
+            var shadow_cube: texture_depth_cube;
+            [[group(0), binding(2)]]
+            var shadow_cube_array: texture_depth_cube_array;
+            [[group(0), binding(3)]]
+            var shadow_sampler: sampler_comparison;
+            [[stage(fragment)]]
+            fn main([[location(0)]] dir: vec3<f32>) -> [[location(0)]] f32 {{
+                return {};
+            }}
+            ",
+            sample
+        );
+        let (module, info) = parse_wgsl(&source, valid::Capabilities::empty());
+        let options = Options {
+            version,
+            writer_flags,
+            ..Options::default()
+        };
+        write(&module, &info, &options, ShaderStage::Fragment).map(|(out, _)| out)
+    };
+    let desktop = Version::Desktop(450);
+
+    // the reference of a cube array is a separate argument
+    let output = write(
+        "textureSampleCompare(shadow_cube_array, shadow_sampler, dir, 2, 0.5)",
+        desktop,
+        WriterFlags::empty(),
+    )
+    .unwrap();
+    assert!(output.contains("texture(_group_0_binding_2, vec4(dir, 2), 0.5)"));
+
+    // cube shadows emulate the level with three dimensional gradients
+    let output = write(
+        "textureSampleCompareLevel(shadow_cube, shadow_sampler, dir, 0.5)",
+        desktop,
+        WriterFlags::empty(),
+    )
+    .unwrap();
+    assert!(output
+        .contains("textureGrad(_group_0_binding_1, vec4(dir, 0.5), vec3(0,0,0), vec3(0,0,0))"));
+
+    // offsets on 2D array shadows need 430 or the extension
+    let offset =
+        "textureSampleCompare(shadow_2d_array, shadow_sampler, dir.xy, 1, 0.5, vec2<i32>(1, 1))";
+    let output = write(offset, desktop, WriterFlags::empty()).unwrap();
+    assert!(output.contains("textureOffset(_group_0_binding_0, vec4(dir.xy, 1, 0.5), ivec2(1, 1))"));
+    for &version in &[Version::Desktop(420), Version::Embedded(310)] {
+        match write(offset, version, WriterFlags::empty()) {
+            Err(Error::UnsupportedShadowSample {
+                sampler: "sampler2DArrayShadow",
+                ..
+            }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+    let output = write(
+        offset,
+        Version::Desktop(420),
+        WriterFlags::TEXTURE_SHADOW_LOD,
+    )
+    .unwrap();
+    assert!(output.contains("textureOffset(_group_0_binding_0"));
+
+    // cube array shadows only have explicit levels with the extension
+    let level = "textureSampleCompareLevel(shadow_cube_array, shadow_sampler, dir, 2, 0.5)";
+    match write(level, desktop, WriterFlags::empty()) {
+        Err(Error::UnsupportedShadowSample {
+            sampler: "samplerCubeArrayShadow",
+            operation: "an explicit level",
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    let output = write(level, desktop, WriterFlags::TEXTURE_SHADOW_LOD).unwrap();
+    assert!(output.contains("textureLod(_group_0_binding_2, vec4(dir, 2), 0.5, 0.0)"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn default_uniform_block() {
+    let (module, info) = parse_wgsl(
+        "
+        [[group(0), binding(0)]]
+        var<uniform> scale: f32;
+        [[group(0), binding(1)]]
+        var<uniform> tint: vec4<f32>;
+        [[group(0), binding(2)]]
+        var<uniform> unused: vec2<f32>;
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return tint * scale;
+        }
+        ",
+        valid::Capabilities::LOOSE_UNIFORMS,
+    );
+    let options = Options {
+        version: Version::Desktop(450),
+        default_uniform_binding: Some(3),
+        ..Options::default()
+    };
+    let (out, reflection) = write(&module, &info, &options, ShaderStage::Fragment).unwrap();
+
+    assert!(out.contains(
+        "layout(std140, binding = 3) uniform DefaultUniforms_block_0Fs {\n    float scale;\n    vec4 tint;\n} _default_uniforms;"
+    ));
+    assert!(!out.contains("unused"));
+    assert!(out.contains("_default_uniforms.tint"));
+    assert!(out.contains("_default_uniforms.scale"));
+    assert_eq!(reflection.uniforms.len(), 2);
+    assert!(reflection
+        .uniforms
+        .values()
+        .all(|name| name == "DefaultUniforms_block_0Fs"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn multisampled_load() {
+    let color = "
+        [[group(0), binding(0)]]
+        var image: texture_multisampled_2d<f32>;
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return textureLoad(image, vec2<i32>(1, 2), 3);
+        }
+        ";
+    let depth = "
+        [[group(0), binding(0)]]
+        var image: texture_depth_multisampled_2d;
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(textureLoad(image, vec2<i32>(1, 2), 3));
+        }
+        ";
+
+    for &source in [color, depth].iter() {
+        let (module, info) = parse_wgsl(source, valid::Capabilities::empty());
+        let write = |version| {
+            write(
+                &module,
+                &info,
+                &version_options(version),
+                ShaderStage::Fragment,
+            )
+        };
+
+        let (out, _) = write(Version::Embedded(310)).unwrap();
+        assert!(out.contains("uniform highp sampler2DMS _group_0_binding_0;"));
+        assert!(out.contains("texelFetch(_group_0_binding_0, ivec2(1, 2), 3)"));
+        // Multisampled textures were only added in GLSL ES 3.10
+        match write(Version::Embedded(300)) {
+            Err(Error::MissingFeatures(features)) => {
+                assert!(features.contains(Features::MULTISAMPLED_TEXTURES))
+            }
+            other => panic!("Unexpected result {:?}", other.map(|(out, _)| out)),
+        }
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn pack_version() {
+    let pack_4x8 = "
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] u32 {
+            return pack4x8unorm(vec4<f32>(0.0, 0.5, 1.0, 0.5));
+        }
+        ";
+    let pack_2x16 = "
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] u32 {
+            return pack2x16float(vec2<f32>(0.0, 0.5));
+        }
+        ";
+
+    let cases = [
+        (
+            pack_4x8,
+            Features::PACK_4X8,
+            Version::Desktop(400),
+            Version::Desktop(330),
+        ),
+        (
+            pack_4x8,
+            Features::PACK_4X8,
+            Version::Embedded(310),
+            Version::Embedded(300),
+        ),
+        (
+            pack_2x16,
+            Features::PACK_2X16,
+            Version::Desktop(420),
+            Version::Desktop(400),
+        ),
+    ];
+    for &(source, feature, supported, unsupported) in cases.iter() {
+        let (module, info) = parse_wgsl(source, valid::Capabilities::empty());
+        let write = |version| {
+            write(
+                &module,
+                &info,
+                &version_options(version),
+                ShaderStage::Fragment,
+            )
+        };
+
+        assert!(write(supported).is_ok());
+        match write(unsupported) {
+            Err(Error::MissingFeatures(features)) => assert!(features.contains(feature)),
+            other => panic!("Unexpected result {:?}", other.map(|(out, _)| out)),
+        }
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn compute_shaders() {
+    let (module, info) = parse_wgsl(
+        "
+        var<workgroup> partial: array<u32, 64>;
+
+        fn total() -> u32 {
+            return partial[0] + partial[63];
+        }
+
+        [[stage(compute), workgroup_size(64, 2)]]
+        fn main(
+            [[builtin(local_invocation_index)]] index: u32,
+            [[builtin(num_workgroups)]] groups: vec3<u32>,
+            [[builtin(workgroup_size)]] size: vec3<u32>,
+        ) {
+            partial[index] = groups.x * size.y;
+            workgroupBarrier();
+            partial[0] = total();
+            storageBarrier();
+        }
+        ",
+        valid::Capabilities::empty(),
+    );
+    let (out, _) = write(&module, &info, &Options::default(), ShaderStage::Compute).unwrap();
+
+    assert!(out.contains("layout(local_size_x = 64, local_size_y = 2, local_size_z = 1) in;"));
+    assert!(out.contains("shared uint partial[64];"));
+    assert!(out.contains("uvec3 groups = gl_NumWorkGroups;"));
+    assert!(out.contains("uvec3 size = gl_WorkGroupSize;"));
+    assert!(out.contains("memoryBarrierShared();\n    barrier();"));
+    assert!(out.contains("memoryBarrierBuffer();\n    barrier();"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn unvalidated_modules() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]]
+        var image: [[access(write)]] texture_storage_2d<rgba8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            textureStore(image, vec2<i32>(0, 0), vec4<f32>(1.0));
+        }
+        ",
+    )
+    .unwrap();
+
+    // Store to a plain number instead of the image, which the validator would reject
+    let function = &mut module.entry_points[0].function;
+    let image = function
+        .body
+        .iter()
+        .find_map(|statement| match *statement {
+            crate::Statement::ImageStore { image, .. } => Some(image),
+            _ => None,
+        })
+        .unwrap();
+    let constant = module.constants.append(crate::Constant {
+        name: None,
+        specialization: None,
+        inner: crate::ConstantInner::Scalar {
+            width: 4,
+            value: crate::ScalarValue::Float(1.0),
+        },
+    });
+    *function.expressions.get_mut(image) = crate::Expression::Constant(constant);
+
+    let info = valid::Validator::new(
+        valid::ValidationFlags::empty(),
+        valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    match write(&module, &info, &Options::default(), ShaderStage::Compute) {
+        Err(Error::InvalidExpression(location)) => match *location {
+            crate::diagnostic::IrLocation::EntryPoint {
+                stage: ShaderStage::Compute,
+                ref name,
+                expression: Some(handle),
+            } if name == "main" && handle == image => {}
+            ref other => panic!("Unexpected location {:?}", other),
+        },
+        other => panic!("Unexpected result {:?}", other.map(|(out, _)| out)),
+    }
+}
+
+#[cfg(feature = "glsl-in")]
+#[test]
+fn logical_not_of_vectors() {
+    let (module, info) = parse_glsl(
+        "
+        #version 450
+        layout(location = 0) in vec4 a;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            bvec4 positive = greaterThan(a, vec4(0.0));
+            o_color = any(not(positive)) ? vec4(1.0) : a;
+        }
+        ",
+        ShaderStage::Fragment,
+    );
+    let (out, _) = write(&module, &info, &Options::default(), ShaderStage::Fragment).unwrap();
+    // `!` only applies to scalars in GLSL
+    assert!(out.contains("any(not("), "{}", out);
+}
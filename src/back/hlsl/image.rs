@@ -21,9 +21,12 @@
 // int dim_1d = NagaDimensions1D(image_1d);
 // ```
 
-use super::{super::FunctionCtx, writer::BackendResult};
+use super::{super::FunctionCtx, writer::BackendResult, Error};
 use crate::arena::Handle;
-use std::fmt::Write;
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::Write,
+};
 
 #[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
 pub(super) struct WrappedImageQuery {
@@ -73,16 +76,24 @@ pub(super) enum ImageQuery {
     NumSamples,
 }
 
-impl From<crate::ImageQuery> for ImageQuery {
-    fn from(q: crate::ImageQuery) -> Self {
+impl TryFrom<crate::ImageQuery> for ImageQuery {
+    type Error = Error;
+
+    fn try_from(q: crate::ImageQuery) -> Result<Self, Error> {
         use crate::ImageQuery as Iq;
-        match q {
+        Ok(match q {
             Iq::Size { level: Some(_) } => ImageQuery::SizeLevel,
             Iq::Size { level: None } => ImageQuery::Size,
             Iq::NumLevels => ImageQuery::NumLevels,
             Iq::NumLayers => ImageQuery::NumLayers,
             Iq::NumSamples => ImageQuery::NumSamples,
-        }
+            // written inline with `CalculateLevelOfDetail`
+            Iq::Lod { .. } => {
+                return Err(Error::Custom(
+                    "level of detail queries don't have a wrapped function".to_string(),
+                ))
+            }
+        })
     }
 }
 
@@ -101,7 +112,8 @@ impl<'a, W: Write> super::Writer<'a, W> {
         let dim_str = query.dim.to_hlsl_str();
         let class_str = match query.class {
             crate::ImageClass::Sampled { multi: true, .. } => "MS",
-            crate::ImageClass::Depth => "Depth",
+            crate::ImageClass::Depth { multi: true } => "DepthMS",
+            crate::ImageClass::Depth { multi: false } => "Depth",
             _ => "",
         };
         let arrayed_str = if query.arrayed { "Array" } else { "" };
@@ -131,6 +143,10 @@ impl<'a, W: Write> super::Writer<'a, W> {
     ) -> BackendResult {
         for (handle, _) in func_ctx.expressions.iter() {
             if let crate::Expression::ImageQuery { image, query } = func_ctx.expressions[handle] {
+                // written inline with `CalculateLevelOfDetail`
+                if let crate::ImageQuery::Lod { .. } = query {
+                    continue;
+                }
                 let image_ty = func_ctx.info[image].ty.inner_with(&module.types);
                 match *image_ty {
                     crate::TypeInner::Image {
@@ -146,7 +162,7 @@ impl<'a, W: Write> super::Writer<'a, W> {
                             dim,
                             arrayed,
                             class,
-                            query: query.into(),
+                            query: query.try_into()?,
                         };
 
                         if !self.wrapped_image_queries.contains(&wrapped_image_query) {
@@ -176,14 +192,16 @@ impl<'a, W: Write> super::Writer<'a, W> {
                             let array_coords = if arrayed { 1 } else { 0 };
                             // GetDimensions Overloaded Methods
                             // https://docs.microsoft.com/en-us/windows/win32/direct3dhlsl/dx-graphics-hlsl-to-getdimensions#overloaded-methods
-                            let (ret_swizzle, number_of_params) = match query {
-                                Iq::Size { .. } => match dim {
+                            let (ret_swizzle, number_of_params) = match wrapped_image_query.query {
+                                ImageQuery::Size | ImageQuery::SizeLevel => match dim {
                                     IDim::D1 => ("x", 1 + array_coords),
                                     IDim::D2 => ("xy", 3 + array_coords),
                                     IDim::D3 => ("xyz", 4),
                                     IDim::Cube => ("xy", 3 + array_coords),
                                 },
-                                Iq::NumLevels | Iq::NumSamples | Iq::NumLayers => {
+                                ImageQuery::NumLevels
+                                | ImageQuery::NumSamples
+                                | ImageQuery::NumLayers => {
                                     if arrayed || dim == IDim::D3 {
                                         ("w", 4)
                                     } else {
@@ -204,7 +222,9 @@ impl<'a, W: Write> super::Writer<'a, W> {
                                 _ =>
                                 // Write zero mipmap level for supported types
                                 {
-                                    if let crate::ImageClass::Sampled { multi: true, .. } = class {
+                                    if let crate::ImageClass::Sampled { multi: true, .. }
+                                    | crate::ImageClass::Depth { multi: true } = class
+                                    {
                                     } else {
                                         match dim {
                                             IDim::D2 | IDim::D3 | IDim::Cube => {
@@ -87,6 +87,10 @@ pub struct Options {
     pub binding_map: BindingMap,
     /// Don't panic on missing bindings, instead generate any HLSL.
     pub fake_missing_bindings: bool,
+    /// Names to give to some of the entry points instead of their IR names,
+    /// which may be renamed otherwise if they are reserved.
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub entry_point_names: super::EntryPointNames,
 }
 
 impl Default for Options {
@@ -95,6 +99,7 @@ impl Default for Options {
             shader_model: ShaderModel::V5_0,
             binding_map: BindingMap::default(),
             fake_missing_bindings: true,
+            entry_point_names: super::EntryPointNames::new(),
         }
     }
 }
@@ -124,6 +129,10 @@ pub struct ReflectionInfo {
     ///
     ///Note: Some entry points may fail translation because of missing bindings.
     pub entry_point_names: Vec<Result<String, EntryPointError>>,
+    /// Items whose IR name couldn't be used verbatim (because it's reserved,
+    /// contains double underscores or collides with another name) mapped to
+    /// the name they were emitted with.
+    pub renamed: crate::FastHashMap<crate::proc::NameKey, String>,
 }
 
 #[derive(Error, Debug)]
@@ -134,6 +143,15 @@ pub enum Error {
     UnsupportedScalar(crate::ScalarKind, crate::Bytes),
     #[error("{0}")]
     Unimplemented(String), // TODO: Error used only during development
+    #[error(transparent)]
+    EntryPointName(#[from] super::EntryPointNameError),
     #[error("{0}")]
     Custom(String),
 }
+
+impl Error {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic).
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        crate::diagnostic::Diagnostic::from_error("back::hlsl", self)
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
     proc::{self, NameKey},
     valid, Handle, Module, ShaderStage, TypeInner,
 };
-use std::fmt::Write;
+use std::{convert::TryInto, fmt::Write};
 
 const LOCATION_SEMANTIC: &str = "LOC";
 
@@ -55,13 +55,19 @@ impl<'a, W: Write> Writer<'a, W> {
         }
     }
 
-    fn reset(&mut self, module: &Module) {
+    fn reset(&mut self, module: &Module) -> BackendResult {
         self.names.clear();
-        self.namer
-            .reset(module, super::keywords::RESERVED, &[], &mut self.names);
+        back::reset_names(
+            &mut self.namer,
+            module,
+            super::keywords::RESERVED,
+            &self.options.entry_point_names,
+            &mut self.names,
+        )?;
         self.named_expressions.clear();
         self.ep_inputs.clear();
         self.wrapped_image_queries.clear();
+        Ok(())
     }
 
     pub fn write(
@@ -69,7 +75,7 @@ impl<'a, W: Write> Writer<'a, W> {
         module: &Module,
         module_info: &valid::ModuleInfo,
     ) -> Result<super::ReflectionInfo, Error> {
-        self.reset(module);
+        self.reset(module)?;
 
         // Write all constants
         // For example, input wgsl shader:
@@ -243,7 +249,10 @@ impl<'a, W: Write> Writer<'a, W> {
             entry_point_names.push(Ok(name));
         }
 
-        Ok(super::ReflectionInfo { entry_point_names })
+        Ok(super::ReflectionInfo {
+            entry_point_names,
+            renamed: proc::renamed_items(module, &self.names),
+        })
     }
 
     fn write_semantic(
@@ -254,7 +263,7 @@ impl<'a, W: Write> Writer<'a, W> {
     ) -> BackendResult {
         match *binding {
             crate::Binding::BuiltIn(builtin) => {
-                write!(self.out, " : {}", builtin_str(builtin))?;
+                write!(self.out, " : {}", builtin_str(builtin)?)?;
             }
             crate::Binding::Location { location, .. } => {
                 if stage == Some(crate::ShaderStage::Fragment) && output == Some(true) {
@@ -367,6 +376,11 @@ impl<'a, W: Write> Writer<'a, W> {
                 }
             }
             crate::StorageClass::PushConstant => unimplemented!("Push constants"),
+            crate::StorageClass::PhysicalStorageBuffer => {
+                return Err(Error::Unimplemented(
+                    "PhysicalStorageBuffer storage class".to_string(),
+                ))
+            }
         };
 
         write!(self.out, "{}", storage)?;
@@ -422,31 +436,17 @@ impl<'a, W: Write> Writer<'a, W> {
     ) -> BackendResult {
         write!(self.out, "static const ")?;
         match *inner {
-            crate::ConstantInner::Scalar {
-                width: _,
-                ref value,
-            } => {
+            crate::ConstantInner::Scalar { width, ref value } => {
                 // Write type
-                let ty_str = match *value {
-                    crate::ScalarValue::Sint(_) => "int",
-                    crate::ScalarValue::Uint(_) => "uint",
-                    crate::ScalarValue::Float(_) => "float",
-                    crate::ScalarValue::Bool(_) => "bool",
+                let kind = match *value {
+                    crate::ScalarValue::Sint(_) => crate::ScalarKind::Sint,
+                    crate::ScalarValue::Uint(_) => crate::ScalarKind::Uint,
+                    crate::ScalarValue::Float(_) => crate::ScalarKind::Float,
+                    crate::ScalarValue::Bool(_) => crate::ScalarKind::Bool,
                 };
                 let name = &self.names[&NameKey::Constant(handle)];
-                write!(self.out, "{} {} = ", ty_str, name)?;
-
-                // Second match required to avoid heap allocation by `format!()`
-                match *value {
-                    crate::ScalarValue::Sint(value) => write!(self.out, "{}", value)?,
-                    crate::ScalarValue::Uint(value) => write!(self.out, "{}", value)?,
-                    crate::ScalarValue::Float(value) => {
-                        // Floats are written using `Debug` instead of `Display` because it always appends the
-                        // decimal part even it's zero
-                        write!(self.out, "{:?}", value)?
-                    }
-                    crate::ScalarValue::Bool(value) => write!(self.out, "{}", value)?,
-                };
+                write!(self.out, "{} {} = ", scalar_kind_str(kind, width)?, name)?;
+                self.write_scalar_value(*value, width)?;
             }
             crate::ConstantInner::Composite { ty, ref components } => {
                 self.write_type(module, ty)?;
@@ -627,13 +627,19 @@ impl<'a, W: Write> Writer<'a, W> {
 
                 let dim_str = dim.to_hlsl_str();
                 let arrayed_str = if arrayed { "Array" } else { "" };
-                write!(self.out, "Texture{}{}", dim_str, arrayed_str)?;
+                let multi = match class {
+                    Ic::Sampled { multi, .. } | Ic::Depth { multi } => multi,
+                    Ic::Storage(_) => false,
+                };
+                let multi_str = if multi { "MS" } else { "" };
+                write!(self.out, "Texture{}{}{}", dim_str, multi_str, arrayed_str)?;
                 match class {
-                    Ic::Depth => {}
-                    Ic::Sampled { kind, multi } => {
-                        let multi_str = if multi { "MS" } else { "" };
+                    // Multi-sampled textures need the element type spelled out
+                    Ic::Depth { multi: true } => write!(self.out, "<float>")?,
+                    Ic::Depth { multi: false } => {}
+                    Ic::Sampled { kind, multi: _ } => {
                         let scalar_kind_str = scalar_kind_str(kind, 4)?;
-                        write!(self.out, "{}<{}4>", multi_str, scalar_kind_str)?
+                        write!(self.out, "<{}4>", scalar_kind_str)?
                     }
                     Ic::Storage(format) => {
                         let storage_format_str = storage_format_to_texture_type(format);
@@ -959,6 +965,72 @@ impl<'a, W: Write> Writer<'a, W> {
 
                 writeln!(self.out, "{}}}", INDENT.repeat(indent))?
             }
+            Statement::Switch {
+                selector,
+                ref cases,
+                ref default,
+            } => {
+                write!(self.out, "{}", INDENT.repeat(indent))?;
+                write!(self.out, "switch(")?;
+                self.write_expr(module, selector, func_ctx)?;
+                writeln!(self.out, ") {{")?;
+
+                for (index, case) in cases.iter().enumerate() {
+                    // Empty cases that fall through share the label of the next one
+                    if case.fall_through && case.body.is_empty() {
+                        writeln!(
+                            self.out,
+                            "{}case {}:",
+                            INDENT.repeat(indent + 1),
+                            case.value
+                        )?;
+                        continue;
+                    }
+
+                    writeln!(
+                        self.out,
+                        "{}case {}: {{",
+                        INDENT.repeat(indent + 1),
+                        case.value
+                    )?;
+
+                    // HLSL rejects falling through from a non-empty case, so
+                    // the bodies of the cases it falls through to are repeated,
+                    // declaring their expressions again in this scope
+                    let named_expressions = self.named_expressions.clone();
+                    let mut falls_to_default = true;
+                    for next in cases[index..].iter() {
+                        for sta in next.body.iter() {
+                            self.write_stmt(module, sta, func_ctx, indent + 2)?;
+                        }
+                        if !next.fall_through {
+                            falls_to_default = false;
+                            break;
+                        }
+                    }
+                    if falls_to_default {
+                        for sta in default.iter() {
+                            self.write_stmt(module, sta, func_ctx, indent + 2)?;
+                        }
+                    }
+
+                    writeln!(self.out, "{}break;", INDENT.repeat(indent + 2))?;
+                    writeln!(self.out, "{}}}", INDENT.repeat(indent + 1))?;
+                    self.named_expressions = named_expressions;
+                }
+
+                if !default.is_empty() {
+                    writeln!(self.out, "{}default: {{", INDENT.repeat(indent + 1))?;
+
+                    for sta in default {
+                        self.write_stmt(module, sta, func_ctx, indent + 2)?;
+                    }
+
+                    writeln!(self.out, "{}}}", INDENT.repeat(indent + 1))?;
+                }
+
+                writeln!(self.out, "{}}}", INDENT.repeat(indent))?
+            }
             Statement::Break => {
                 write!(self.out, "{}", INDENT.repeat(indent))?;
                 writeln!(self.out, "break;")?
@@ -1010,7 +1082,6 @@ impl<'a, W: Write> Writer<'a, W> {
                 self.write_expr(module, value, func_ctx)?;
                 writeln!(self.out, ";")?;
             }
-            _ => return Err(Error::Unimplemented(format!("write_stmt {:?}", stmt))),
         }
 
         Ok(())
@@ -1143,6 +1214,7 @@ impl<'a, W: Write> Writer<'a, W> {
             Expression::ImageSample {
                 image,
                 sampler,
+                gather,
                 coordinate,
                 array_index,
                 offset,
@@ -1166,7 +1238,14 @@ impl<'a, W: Write> Writer<'a, W> {
                 };
 
                 self.write_expr(module, image, func_ctx)?;
-                write!(self.out, ".{}(", texture_func)?;
+                match gather {
+                    Some(component) => {
+                        let cmp = if depth_ref.is_some() { "Cmp" } else { "" };
+                        let channel = ["Red", "Green", "Blue", "Alpha"][component as usize];
+                        write!(self.out, ".Gather{}{}(", cmp, channel)?;
+                    }
+                    None => write!(self.out, ".{}(", texture_func)?,
+                }
                 self.write_expr(module, sampler, func_ctx)?;
                 write!(self.out, ", ")?;
                 self.write_texture_coordinates(
@@ -1208,6 +1287,31 @@ impl<'a, W: Write> Writer<'a, W> {
 
                 write!(self.out, ")")?;
             }
+            Expression::ImageQuery {
+                image,
+                query:
+                    crate::ImageQuery::Lod {
+                        sampler,
+                        coordinate,
+                    },
+            } => {
+                write!(self.out, "float2(")?;
+                for (i, method) in ["CalculateLevelOfDetail", "CalculateLevelOfDetailUnclamped"]
+                    .iter()
+                    .enumerate()
+                {
+                    if i != 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    self.write_expr(module, image, func_ctx)?;
+                    write!(self.out, ".{}(", method)?;
+                    self.write_expr(module, sampler, func_ctx)?;
+                    write!(self.out, ", ")?;
+                    self.write_expr(module, coordinate, func_ctx)?;
+                    write!(self.out, ")")?;
+                }
+                write!(self.out, ")")?;
+            }
             Expression::ImageQuery { image, query } => {
                 // use wrapped image query function
                 if let TypeInner::Image {
@@ -1220,7 +1324,7 @@ impl<'a, W: Write> Writer<'a, W> {
                         dim,
                         arrayed,
                         class,
-                        query: query.into(),
+                        query: query.try_into()?,
                     };
 
                     self.write_wrapped_image_query_function_name(wrapped_image_query)?;
@@ -1245,6 +1349,10 @@ impl<'a, W: Write> Writer<'a, W> {
                     crate::TypeInner::Image {
                         class: crate::ImageClass::Sampled { multi, .. },
                         ..
+                    }
+                    | crate::TypeInner::Image {
+                        class: crate::ImageClass::Depth { multi },
+                        ..
                     } => multi,
                     _ => false,
                 };
@@ -1272,7 +1380,10 @@ impl<'a, W: Write> Writer<'a, W> {
 
                 if ms {
                     write!(self.out, ", ")?;
-                    self.write_expr(module, index.unwrap(), func_ctx)?;
+                    let sample = index.ok_or_else(|| {
+                        Error::Custom("multisampled image load without a sample index".to_string())
+                    })?;
+                    self.write_expr(module, sample, func_ctx)?;
                 }
 
                 // close bracket for Load function
@@ -1330,10 +1441,15 @@ impl<'a, W: Write> Writer<'a, W> {
 
                 write!(self.out, ")")?
             }
-            Expression::As { expr, kind, .. } => {
+            Expression::As {
+                expr,
+                kind,
+                convert,
+            } => {
                 let inner = func_ctx.info[expr].ty.inner_with(&module.types);
                 match *inner {
                     TypeInner::Vector { size, width, .. } => {
+                        let width = convert.unwrap_or(width);
                         write!(
                             self.out,
                             "{}{}",
@@ -1342,6 +1458,7 @@ impl<'a, W: Write> Writer<'a, W> {
                         )?;
                     }
                     TypeInner::Scalar { width, .. } => {
+                        let width = convert.unwrap_or(width);
                         write!(self.out, "{}", scalar_kind_str(kind, width)?)?
                     }
                     _ => {
@@ -1363,6 +1480,22 @@ impl<'a, W: Write> Writer<'a, W> {
             } => {
                 use crate::MathFunction as Mf;
 
+                match fun {
+                    Mf::Pack4x8snorm
+                    | Mf::Pack4x8unorm
+                    | Mf::Pack2x16snorm
+                    | Mf::Pack2x16unorm
+                    | Mf::Pack2x16float
+                    | Mf::Unpack4x8snorm
+                    | Mf::Unpack4x8unorm
+                    | Mf::Unpack2x16snorm
+                    | Mf::Unpack2x16unorm
+                    | Mf::Unpack2x16float => {
+                        return self.write_data_packing(module, fun, arg, func_ctx);
+                    }
+                    _ => {}
+                }
+
                 let fun_name = match fun {
                     // comparison
                     Mf::Abs => "abs",
@@ -1450,20 +1583,35 @@ impl<'a, W: Write> Writer<'a, W> {
                 self.write_expr(module, expr, func_ctx)?;
                 write!(self.out, ".length()")?
             }
-            Expression::Derivative { axis, expr } => {
-                use crate::DerivativeAxis as Da;
+            Expression::Derivative { axis, ctrl, expr } => {
+                use crate::{DerivativeAxis as Axis, DerivativeControl as Ctrl};
 
-                write!(
-                    self.out,
-                    "{}(",
-                    match axis {
-                        Da::X => "ddx",
-                        Da::Y => "ddy",
-                        Da::Width => "fwidth",
-                    }
-                )?;
-                self.write_expr(module, expr, func_ctx)?;
-                write!(self.out, ")")?
+                // `fwidth` has no variants, so it's written as the sum of
+                // the absolute derivatives it's defined as
+                if axis == Axis::Width && ctrl != Ctrl::None {
+                    let suffix = match ctrl {
+                        Ctrl::Coarse => "coarse",
+                        _ => "fine",
+                    };
+                    write!(self.out, "(abs(ddx_{}(", suffix)?;
+                    self.write_expr(module, expr, func_ctx)?;
+                    write!(self.out, ")) + abs(ddy_{}(", suffix)?;
+                    self.write_expr(module, expr, func_ctx)?;
+                    write!(self.out, ")))")?
+                } else {
+                    let fun_name = match (axis, ctrl) {
+                        (Axis::X, Ctrl::Coarse) => "ddx_coarse",
+                        (Axis::X, Ctrl::Fine) => "ddx_fine",
+                        (Axis::X, Ctrl::None) => "ddx",
+                        (Axis::Y, Ctrl::Coarse) => "ddy_coarse",
+                        (Axis::Y, Ctrl::Fine) => "ddy_fine",
+                        (Axis::Y, Ctrl::None) => "ddy",
+                        (Axis::Width, _) => "fwidth",
+                    };
+                    write!(self.out, "{}(", fun_name)?;
+                    self.write_expr(module, expr, func_ctx)?;
+                    write!(self.out, ")")?
+                }
             }
             Expression::Splat { size, value } => {
                 // hlsl is not supported one value constructor
@@ -1512,14 +1660,11 @@ impl<'a, W: Write> Writer<'a, W> {
     ) -> BackendResult {
         let constant = &module.constants[handle];
         match constant.inner {
-            crate::ConstantInner::Scalar {
-                width: _,
-                ref value,
-            } => {
+            crate::ConstantInner::Scalar { width, ref value } => {
                 if constant.name.is_some() {
                     write!(self.out, "{}", &self.names[&NameKey::Constant(handle)])?;
                 } else {
-                    self.write_scalar_value(*value)?;
+                    self.write_scalar_value(*value, width)?;
                 }
             }
             crate::ConstantInner::Composite { ty, ref components } => {
@@ -1537,9 +1682,9 @@ impl<'a, W: Write> Writer<'a, W> {
         components: &[Handle<crate::Constant>],
     ) -> BackendResult {
         let (open_b, close_b) = match module.types[ty].inner {
-            TypeInner::Struct { .. } => ("{ ", " }"),
+            TypeInner::Struct { .. } | TypeInner::Array { .. } => ("{ ", " }"),
             _ => {
-                // We should write type only for non struct constants
+                // We should write type only for non struct and array constants
                 self.write_type(module, ty)?;
                 ("(", ")")
             }
@@ -1562,21 +1707,141 @@ impl<'a, W: Write> Writer<'a, W> {
     ///
     /// # Notes
     /// Adds no trailing or leading whitespace
-    fn write_scalar_value(&mut self, value: crate::ScalarValue) -> BackendResult {
+    fn write_scalar_value(
+        &mut self,
+        value: crate::ScalarValue,
+        width: crate::Bytes,
+    ) -> BackendResult {
         use crate::ScalarValue as Sv;
 
         match value {
+            // 64 bit integers need a `L` at the end
+            Sv::Sint(value) if width == 8 => write!(self.out, "{}L", value)?,
+            Sv::Uint(value) if width == 8 => write!(self.out, "{}uL", value)?,
             Sv::Sint(value) => write!(self.out, "{}", value)?,
             Sv::Uint(value) => write!(self.out, "{}u", value)?,
             // Floats are written using `Debug` instead of `Display` because it always appends the
             // decimal part even it's zero
-            Sv::Float(value) => write!(self.out, "{:?}", value)?,
+            Sv::Float(value) => {
+                let suffix = match width {
+                    2 => "h",
+                    8 => "L",
+                    _ => "",
+                };
+                write!(self.out, "{:?}{}", value, suffix)?
+            }
             Sv::Bool(value) => write!(self.out, "{}", value)?,
         }
 
         Ok(())
     }
 
+    /// Helper method used to write the data packing and unpacking functions,
+    /// which HLSL only has for halves, with bit operations
+    fn write_data_packing(
+        &mut self,
+        module: &Module,
+        fun: crate::MathFunction,
+        arg: Handle<crate::Expression>,
+        func_ctx: &back::FunctionCtx<'_>,
+    ) -> BackendResult {
+        use crate::MathFunction as Mf;
+
+        // (components, bits of each component, normalized range of the values)
+        let (components, bits, signed) = match fun {
+            Mf::Pack4x8snorm | Mf::Unpack4x8snorm => (4, 8, Some(true)),
+            Mf::Pack4x8unorm | Mf::Unpack4x8unorm => (4, 8, Some(false)),
+            Mf::Pack2x16snorm | Mf::Unpack2x16snorm => (2, 16, Some(true)),
+            Mf::Pack2x16unorm | Mf::Unpack2x16unorm => (2, 16, Some(false)),
+            Mf::Pack2x16float | Mf::Unpack2x16float => (2, 16, None),
+            _ => unreachable!(),
+        };
+        let mask = (1u32 << bits) - 1;
+        let pack = match fun {
+            Mf::Pack4x8snorm
+            | Mf::Pack4x8unorm
+            | Mf::Pack2x16snorm
+            | Mf::Pack2x16unorm
+            | Mf::Pack2x16float => true,
+            _ => false,
+        };
+
+        if pack {
+            // Each component is converted and shifted to its place
+            write!(self.out, "(")?;
+            for index in 0..components {
+                if index != 0 {
+                    write!(self.out, " | (")?;
+                }
+                match signed {
+                    Some(true) => write!(self.out, "(uint(int(round(clamp(")?,
+                    Some(false) => write!(self.out, "uint(round(clamp(")?,
+                    None => write!(self.out, "f32tof16(")?,
+                }
+                self.write_expr(module, arg, func_ctx)?;
+                match signed {
+                    Some(true) => write!(
+                        self.out,
+                        "[{}], -1.0, 1.0) * {}.0))) & {})",
+                        index,
+                        mask >> 1,
+                        mask
+                    )?,
+                    Some(false) => write!(self.out, "[{}], 0.0, 1.0) * {}.0))", index, mask)?,
+                    None => write!(self.out, "[{}])", index)?,
+                }
+                if index != 0 {
+                    write!(self.out, " << {})", index * bits)?;
+                }
+            }
+            write!(self.out, ")")?;
+        } else {
+            match signed {
+                // The components are moved to the top of signed integers, and
+                // shifted back down to extend their sign
+                Some(true) => {
+                    write!(self.out, "max(float{}(int{}(", components, components)?;
+                    for index in 0..components {
+                        if index != 0 {
+                            write!(self.out, ", ")?;
+                        }
+                        self.write_expr(module, arg, func_ctx)?;
+                        let shift = 32 - bits * (index + 1);
+                        if shift != 0 {
+                            write!(self.out, " << {}", shift)?;
+                        }
+                    }
+                    write!(self.out, ") >> {}) / {}.0, -1.0)", 32 - bits, mask >> 1)?;
+                }
+                Some(false) => {
+                    write!(self.out, "(float{}(", components)?;
+                    for index in 0..components {
+                        if index != 0 {
+                            write!(self.out, ", ")?;
+                        }
+                        self.write_expr(module, arg, func_ctx)?;
+                        if index != 0 {
+                            write!(self.out, " >> {}", index * bits)?;
+                        }
+                        if index != components - 1 {
+                            write!(self.out, " & {}", mask)?;
+                        }
+                    }
+                    write!(self.out, ") / {}.0)", mask)?;
+                }
+                None => {
+                    write!(self.out, "float2(f16tof32(")?;
+                    self.write_expr(module, arg, func_ctx)?;
+                    write!(self.out, "), f16tof32(")?;
+                    self.write_expr(module, arg, func_ctx)?;
+                    write!(self.out, " >> 16))")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn write_named_expr(
         &mut self,
         module: &Module,
@@ -1628,10 +1893,10 @@ impl<'a, W: Write> Writer<'a, W> {
     }
 }
 
-fn builtin_str(built_in: crate::BuiltIn) -> &'static str {
+fn builtin_str(built_in: crate::BuiltIn) -> Result<&'static str, Error> {
     use crate::BuiltIn as Bi;
 
-    match built_in {
+    Ok(match built_in {
         Bi::Position => "SV_Position",
         // vertex
         Bi::ClipDistance => "SV_ClipDistance",
@@ -1641,6 +1906,7 @@ fn builtin_str(built_in: crate::BuiltIn) -> &'static str {
         // No meaning unless you target Direct3D 9
         Bi::PointSize => "PSIZE",
         Bi::VertexIndex => "SV_VertexID",
+        Bi::ViewIndex => "SV_ViewID",
         // fragment
         Bi::FragDepth => "SV_Depth",
         Bi::FrontFacing => "SV_IsFrontFace",
@@ -1652,8 +1918,18 @@ fn builtin_str(built_in: crate::BuiltIn) -> &'static str {
         Bi::LocalInvocationId => "SV_GroupThreadID",
         Bi::LocalInvocationIndex => "SV_GroupIndex",
         Bi::WorkGroupId => "SV_GroupID",
-        _ => todo!("builtin_str {:?}", built_in),
-    }
+        // these are only available through functions like `EvaluateAttributeAtSample`
+        // and `IsHelperLane`, and not as semantics
+        Bi::SamplePosition | Bi::HelperInvocation => {
+            return Err(Error::Unimplemented(format!("built-in {:?}", built_in)))
+        }
+        // HLSL has no system value for it, the size of the dispatch has to be
+        // passed by the application in a constant buffer
+        Bi::NumWorkGroups => return Err(Error::Unimplemented(format!("built-in {:?}", built_in))),
+        Bi::BaseInstance | Bi::BaseVertex | Bi::WorkGroupSize => {
+            return Err(Error::Unimplemented(format!("built-in {:?}", built_in)))
+        }
+    })
 }
 
 /// Helper function that returns scalar related strings
@@ -1662,8 +1938,16 @@ fn scalar_kind_str(kind: crate::ScalarKind, width: crate::Bytes) -> Result<&'sta
     use crate::ScalarKind as Sk;
 
     match kind {
-        Sk::Sint => Ok("int"),
-        Sk::Uint => Ok("uint"),
+        Sk::Sint => match width {
+            4 => Ok("int"),
+            8 => Ok("int64_t"),
+            _ => Err(Error::UnsupportedScalar(kind, width)),
+        },
+        Sk::Uint => match width {
+            4 => Ok("uint"),
+            8 => Ok("uint64_t"),
+            _ => Err(Error::UnsupportedScalar(kind, width)),
+        },
         Sk::Float => match width {
             2 => Ok("half"),
             4 => Ok("float"),
@@ -1723,3 +2007,145 @@ fn storage_format_to_texture_type(format: crate::StorageFormat) -> &'static str
         Sf::Rgba8Sint | Sf::Rgba16Sint | Sf::R32Sint | Sf::Rg32Sint | Sf::Rgba32Sint => "int4",
     }
 }
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn scalar_widths() {
+    use valid::{Capabilities, ValidationFlags};
+
+    let module = crate::front::wgsl::parse_str(
+        "
+        enable f16;
+
+        let big: i64 = -5000000000i64;
+
+        fn wide(a: i64, b: u64) -> u32 {
+            return u32(u64(a - big) + b * 2u64);
+        }
+
+        fn narrow(a: f16) -> f32 {
+            return f32(a * 0.5f16);
+        }
+        ",
+    )
+    .unwrap();
+    let capabilities = Capabilities::INT64 | Capabilities::FLOAT16;
+    let info = valid::Validator::new(ValidationFlags::all(), capabilities)
+        .validate(&module)
+        .unwrap();
+    let mut output = String::new();
+    Writer::new(&mut output, &Options::default())
+        .write(&module, &info)
+        .unwrap();
+
+    assert!(output.contains("static const int64_t big = -5000000000L;"));
+    assert!(output.contains("(b * 2uL)"));
+    assert!(output.contains("uint((uint64_t((a - big))"));
+    assert!(output.contains("float((a1 * 0.5h))"));
+}
+
+#[cfg(feature = "glsl-in")]
+#[test]
+fn unsupported_built_ins() {
+    use valid::{Capabilities, ValidationFlags};
+
+    let write = |source: &str| {
+        let options = crate::front::glsl::Options::from_stage(ShaderStage::Fragment);
+        let module = crate::front::glsl::parse_str(source, &options).unwrap();
+        let info = valid::Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+        let mut output = String::new();
+        Writer::new(&mut output, &Options::default()).write(&module, &info)
+    };
+
+    for built_in in [
+        "vec4(gl_SamplePosition, 0.0, 1.0)",
+        "vec4(float(gl_HelperInvocation))",
+    ]
+    .iter()
+    {
+        let source = format!(
+            "#version 450\nlayout(location = 0) out vec4 color;\nvoid main() {{ color = {}; }}",
+            built_in
+        );
+        match write(&source) {
+            Err(Error::Unimplemented(_)) => {}
+            Err(other) => panic!("Unexpected error {:?}", other),
+            Ok(_) => panic!("{} was written", built_in),
+        }
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn view_index_and_num_workgroups() {
+    use valid::{Capabilities, ValidationFlags};
+
+    let write = |source: &str| {
+        let module = crate::front::wgsl::parse_str(source).unwrap();
+        let info = valid::Validator::new(ValidationFlags::all(), Capabilities::MULTIVIEW)
+            .validate(&module)
+            .unwrap();
+        let mut output = String::new();
+        Writer::new(&mut output, &Options::default())
+            .write(&module, &info)
+            .map(|_| output)
+    };
+
+    let output = write(
+        "
+        [[stage(fragment)]]
+        fn main([[builtin(view_index)]] view: u32) -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(f32(view));
+        }
+        ",
+    )
+    .unwrap();
+    assert!(output.contains(": SV_ViewID"));
+
+    match write(
+        "
+        [[stage(compute), workgroup_size(1)]]
+        fn main([[builtin(num_workgroups)]] count: vec3<u32>) {}
+        ",
+    ) {
+        Err(Error::Unimplemented(_)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn unvalidated_modules() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]]
+        var image: texture_multisampled_2d<f32>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            let texel = textureLoad(image, vec2<i32>(0, 0), 0);
+        }
+        ",
+    )
+    .unwrap();
+
+    // Drop the sample index of the load, which the validator would reject
+    for (_, expr) in module.entry_points[0].function.expressions.iter_mut() {
+        if let crate::Expression::ImageLoad { ref mut index, .. } = *expr {
+            *index = None;
+        }
+    }
+
+    let info = valid::Validator::new(
+        valid::ValidationFlags::empty(),
+        valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let mut output = String::new();
+    match Writer::new(&mut output, &Options::default()).write(&module, &info) {
+        Err(Error::Custom(_)) => {}
+        other => panic!("Unexpected result {:?}", other.map(|_| output)),
+    }
+}
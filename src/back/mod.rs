@@ -20,6 +20,154 @@ const INDENT: &str = "    ";
 #[allow(dead_code)]
 const BAKE_PREFIX: &str = "_e";
 
+/// Output names forced on entry points, keyed by their stage and IR name.
+///
+/// Using `BTreeMap` instead of `HashMap` so that we can hash itself.
+pub type EntryPointNames = std::collections::BTreeMap<(crate::ShaderStage, String), String>;
+
+/// Problem with a name of [`EntryPointNames`].
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum EntryPointNameError {
+    #[error("forced entry point name {0:?} is not an identifier")]
+    InvalidIdentifier(String),
+    #[error("forced entry point name {0:?} is a reserved keyword")]
+    ReservedKeyword(String),
+    #[error("forced entry point name {0:?} is used by another entry point")]
+    Collision(String),
+}
+
+/// Checks that the names in `forced` can be used verbatim.
+///
+/// They have to be identifiers other than `reserved_keywords`, and distinct
+/// from each other and from the IR names of the entry points of the same
+/// stage that keep them.
+#[cfg(any(feature = "hlsl-out", feature = "msl-out", feature = "spv-out"))]
+fn validate_entry_point_names(
+    module: &crate::Module,
+    reserved_keywords: &[&str],
+    forced: &EntryPointNames,
+) -> Result<(), EntryPointNameError> {
+    let mut seen = crate::FastHashSet::default();
+    for (&(stage, _), name) in forced.iter() {
+        let mut chars = name.chars();
+        let is_identifier = match chars.next() {
+            Some(first) => {
+                (first.is_ascii_alphabetic() || first == '_')
+                    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            None => false,
+        };
+        if !is_identifier {
+            return Err(EntryPointNameError::InvalidIdentifier(name.clone()));
+        }
+        if reserved_keywords.contains(&name.as_str()) {
+            return Err(EntryPointNameError::ReservedKeyword(name.clone()));
+        }
+        let taken = module.entry_points.iter().any(|ep| {
+            ep.stage == stage
+                && ep.name == *name
+                && !forced.contains_key(&(ep.stage, ep.name.clone()))
+        });
+        if taken || !seen.insert(name.as_str()) {
+            return Err(EntryPointNameError::Collision(name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Resets the namer, making sure that no other item is named like one of
+/// the entry points in `forced`, which are then given their forced names.
+///
+/// The forced names are used verbatim, so they are validated first.
+#[cfg(any(feature = "hlsl-out", feature = "msl-out"))]
+fn reset_names(
+    namer: &mut crate::proc::Namer,
+    module: &crate::Module,
+    reserved_keywords: &[&str],
+    forced: &EntryPointNames,
+    names: &mut crate::FastHashMap<crate::proc::NameKey, String>,
+) -> Result<(), EntryPointNameError> {
+    validate_entry_point_names(module, reserved_keywords, forced)?;
+    let mut reserved = reserved_keywords.to_vec();
+    reserved.extend(forced.values().map(String::as_str));
+    namer.reset(module, &reserved, &[], names);
+    for (index, ep) in module.entry_points.iter().enumerate() {
+        if let Some(name) = forced.get(&(ep.stage, ep.name.clone())) {
+            names.insert(crate::proc::NameKey::EntryPoint(index as _), name.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the functions that `function` calls, directly or indirectly.
+#[cfg(any(feature = "msl-out", feature = "spv-out"))]
+fn reachable_functions(
+    module: &crate::Module,
+    function: &crate::Function,
+) -> crate::FastHashSet<crate::Handle<crate::Function>> {
+    fn collect_callees(
+        block: &[crate::Statement],
+        callees: &mut Vec<crate::Handle<crate::Function>>,
+    ) {
+        use crate::Statement as S;
+        for statement in block {
+            match *statement {
+                S::Block(ref block) => collect_callees(block, callees),
+                S::If {
+                    ref accept,
+                    ref reject,
+                    ..
+                } => {
+                    collect_callees(accept, callees);
+                    collect_callees(reject, callees);
+                }
+                S::Switch {
+                    ref cases,
+                    ref default,
+                    ..
+                } => {
+                    for case in cases {
+                        collect_callees(&case.body, callees);
+                    }
+                    collect_callees(default, callees);
+                }
+                S::Loop {
+                    ref body,
+                    ref continuing,
+                } => {
+                    collect_callees(body, callees);
+                    collect_callees(continuing, callees);
+                }
+                S::Call { function, .. } => callees.push(function),
+                _ => {}
+            }
+        }
+    }
+
+    let mut reachable = crate::FastHashSet::default();
+    let mut pending = Vec::new();
+    collect_callees(&function.body, &mut pending);
+    while let Some(handle) = pending.pop() {
+        if reachable.insert(handle) {
+            collect_callees(&module.functions[handle].body, &mut pending);
+        }
+    }
+    reachable
+}
+
+/// Returns the index of the entry point of `stage` named `name`.
+#[cfg(any(feature = "msl-out", feature = "spv-out"))]
+fn find_entry_point(
+    module: &crate::Module,
+    stage: crate::ShaderStage,
+    name: &str,
+) -> Option<usize> {
+    module
+        .entry_points
+        .iter()
+        .position(|ep| ep.stage == stage && ep.name == name)
+}
+
 /// Stores the current function type (either a regular function or an entry point)
 ///
 /// Also stores data needed to identify it (handle for a regular function or index for an entry point)
@@ -66,6 +214,28 @@ impl<'a> FunctionCtx<'_> {
             }
         }
     }
+
+    /// Helper method that locates an expression of the current function, for errors.
+    fn expression_location(
+        &self,
+        module: &crate::Module,
+        expression: crate::Handle<crate::Expression>,
+    ) -> crate::diagnostic::IrLocation {
+        match self.ty {
+            FunctionType::Function(handle) => crate::diagnostic::IrLocation::Function {
+                handle,
+                expression: Some(expression),
+            },
+            FunctionType::EntryPoint(ep_index) => {
+                let ep = &module.entry_points[ep_index as usize];
+                crate::diagnostic::IrLocation::EntryPoint {
+                    stage: ep.stage,
+                    name: ep.name.clone(),
+                    expression: Some(expression),
+                }
+            }
+        }
+    }
 }
 
 /// How should code generated by Naga do indexing bounds checks?
@@ -86,6 +256,8 @@ impl<'a> FunctionCtx<'_> {
 /// -   Naga's own default is `UndefinedBehavior`, so that shader translations
 ///     are as faithful to the original as possible.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum IndexBoundsCheckPolicy {
     /// Replace out-of-bounds indexes with some arbitrary in-bounds index.
     ///
@@ -32,6 +32,8 @@ use std::{
 mod keywords;
 pub mod sampler;
 mod writer;
+#[cfg(test)]
+mod writer_tests;
 
 pub use writer::Writer;
 
@@ -76,6 +78,9 @@ pub struct PerStageResources {
     /// The slot of a buffer that contains an array of `u32`,
     /// one for the size of each bound buffer that contains a runtime array,
     /// in order of [`crate::GlobalVariable`] declarations.
+    ///
+    /// It's only needed by the entry points using such buffers, see
+    /// [`TranslationInfo::sized_buffers`].
     #[cfg_attr(feature = "deserialize", serde(default))]
     pub sizes_buffer: Option<Slot>,
 }
@@ -105,6 +110,9 @@ impl ops::Index<crate::ShaderStage> for PerStageMap {
 
 enum ResolvedBinding {
     BuiltIn(crate::BuiltIn),
+    /// The view index, taken from the vertex amplification ID in the vertex
+    /// stage, and from the render target array index in the fragment stage.
+    ViewIndex(crate::ShaderStage),
     Attribute(u32),
     Color(u32),
     User {
@@ -144,10 +152,56 @@ pub enum Error {
     FeatureNotImplemented(String),
     #[error("module is not valid")]
     Validation,
+    #[error("expression at {0:?} is not valid")]
+    InvalidExpression(Box<crate::diagnostic::IrLocation>),
+    #[error("array size {0:?} is not a valid constant")]
+    InvalidArraySize(Handle<crate::Constant>),
+    #[error("storage image {0:?} has neither load nor store access")]
+    InvalidStorageAccess(Handle<crate::Type>),
     #[error("BuiltIn {0:?} is not supported")]
     UnsupportedBuiltIn(crate::BuiltIn),
     #[error("capability {0:?} is not supported")]
     CapabilityNotSupported(crate::valid::Capabilities),
+    #[error("read-write access to storage image of format {format:?} requires Metal 1.2, but {lang_version:?} is targeted")]
+    UnsupportedReadWriteStorageImage {
+        format: crate::StorageFormat,
+        lang_version: (u8, u8),
+    },
+    #[error("storage image format {0:?} can't be accessed as read-write by Metal")]
+    UnsupportedReadWriteStorageFormat(crate::StorageFormat),
+    #[error("view index in the vertex stage requires vertex amplification of Metal 2.3, but {0:?} is targeted")]
+    UnsupportedVertexAmplification((u8, u8)),
+    #[error("level of detail queries require Metal 2.2, but {0:?} is targeted")]
+    UnsupportedLodQuery((u8, u8)),
+    #[error("arrays of textures and samplers require Metal 2.0, but {0:?} is targeted")]
+    UnsupportedBindingArray((u8, u8)),
+    #[error("entry point {1:?} of the {0:?} stage is not found")]
+    EntryPointNotFound(crate::ShaderStage, String),
+    #[error(transparent)]
+    EntryPointName(Box<super::EntryPointNameError>),
+}
+
+impl From<super::EntryPointNameError> for Error {
+    fn from(error: super::EntryPointNameError) -> Self {
+        // boxed to keep the size of `Error` down
+        Error::EntryPointName(Box::new(error))
+    }
+}
+
+impl Error {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic),
+    /// pointing to the item of the IR that can't be written, if known.
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        use crate::diagnostic::IrLocation;
+
+        let location = match *self {
+            Error::UnsupportedCompose(handle) => Some(IrLocation::Type(handle)),
+            Error::InvalidExpression(ref location) => Some(IrLocation::clone(location)),
+            Error::InvalidArraySize(handle) => Some(IrLocation::Constant(handle)),
+            _ => None,
+        };
+        crate::diagnostic::Diagnostic::from_error("back::msl", self).with_location(location)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
@@ -160,6 +214,8 @@ pub enum EntryPointError {
     MissingPushConstants,
     #[error("mapping for sizes buffer is missing")]
     MissingSizesBuffer,
+    #[error("entry point is not selected by the pipeline options")]
+    NotSelected,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -184,6 +240,10 @@ pub struct Options {
     pub spirv_cross_compatibility: bool,
     /// Don't panic on missing bindings, instead generate invalid MSL.
     pub fake_missing_bindings: bool,
+    /// Names to give to some of the entry points instead of their IR names,
+    /// which may be renamed otherwise (e.g. `main` is reserved in MSL).
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub entry_point_names: super::EntryPointNames,
 }
 
 impl Default for Options {
@@ -194,6 +254,7 @@ impl Default for Options {
             inline_samplers: Vec::new(),
             spirv_cross_compatibility: false,
             fake_missing_bindings: true,
+            entry_point_names: super::EntryPointNames::new(),
         }
     }
 }
@@ -206,12 +267,17 @@ pub struct PipelineOptions {
     /// Allow `BuiltIn::PointSize` in the vertex shader.
     /// Metal doesn't like this for non-point primitive topologies.
     pub allow_point_size: bool,
+    /// Only translate the entry point of this stage and name, together with
+    /// the functions it calls. If `None`, all the entry points are translated.
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub entry_point: Option<(crate::ShaderStage, String)>,
 }
 
 impl Default for PipelineOptions {
     fn default() -> Self {
         PipelineOptions {
             allow_point_size: true,
+            entry_point: None,
         }
     }
 }
@@ -223,6 +289,17 @@ impl Options {
         mode: LocationMode,
     ) -> Result<ResolvedBinding, Error> {
         match *binding {
+            crate::Binding::BuiltIn(crate::BuiltIn::ViewIndex) => match mode {
+                LocationMode::VertexInput
+                    if self.lang_version < writer::VERTEX_AMPLIFICATION_VERSION =>
+                {
+                    Err(Error::UnsupportedVertexAmplification(self.lang_version))
+                }
+                LocationMode::VertexInput => {
+                    Ok(ResolvedBinding::ViewIndex(crate::ShaderStage::Vertex))
+                }
+                _ => Ok(ResolvedBinding::ViewIndex(crate::ShaderStage::Fragment)),
+            },
             crate::Binding::BuiltIn(built_in) => Ok(ResolvedBinding::BuiltIn(built_in)),
             crate::Binding::Location {
                 location,
@@ -239,10 +316,10 @@ impl Options {
                     },
                     index: location,
                     interpolation: {
-                        // unwrap: The verifier ensures that vertex shader outputs and fragment
+                        // The verifier ensures that vertex shader outputs and fragment
                         // shader inputs always have fully specified interpolation, and that
                         // sampling is `None` only for Flat interpolation.
-                        let interpolation = interpolation.unwrap();
+                        let interpolation = interpolation.ok_or(Error::Validation)?;
                         let sampling = sampling.unwrap_or(crate::Sampling::Center);
                         Some(ResolvedInterpolation::from_binding(interpolation, sampling))
                     },
@@ -355,12 +432,15 @@ impl ResolvedBinding {
                     Bi::GlobalInvocationId => "thread_position_in_grid",
                     Bi::LocalInvocationId => "thread_position_in_threadgroup",
                     Bi::LocalInvocationIndex => "thread_index_in_threadgroup",
+                    Bi::NumWorkGroups => "threadgroups_per_grid",
                     Bi::WorkGroupId => "threadgroup_position_in_grid",
                     Bi::WorkGroupSize => "dispatch_threads_per_threadgroup",
                     _ => return Err(Error::UnsupportedBuiltIn(built_in)),
                 };
                 write!(out, "{}", name)?;
             }
+            Self::ViewIndex(crate::ShaderStage::Vertex) => write!(out, "amplification_id")?,
+            Self::ViewIndex(_) => write!(out, "render_target_array_index")?,
             Self::Attribute(index) => write!(out, "attribute({})", index)?,
             Self::Color(index) => write!(out, "color({})", index)?,
             Self::User {
@@ -437,6 +517,19 @@ pub struct TranslationInfo {
     ///
     ///Note: Some entry points may fail translation because of missing bindings.
     pub entry_point_names: Vec<Result<String, EntryPointError>>,
+    /// Items whose IR name couldn't be used verbatim (because it's reserved,
+    /// contains double underscores or collides with another name) mapped to
+    /// the name they were emitted with.
+    pub renamed: crate::FastHashMap<crate::proc::NameKey, String>,
+    /// Global variables ending with a runtime-sized array.
+    ///
+    /// Metal has no way to query the size of a buffer, so the entry points
+    /// using any of these take an extra argument, bound at the
+    /// [`sizes_buffer`](PerStageResources::sizes_buffer) slot of their stage.
+    /// It has to contain a `u32` per global of this list, in this order, with
+    /// the size in bytes of the buffer bound to it. The array lengths are
+    /// derived from these sizes.
+    pub sized_buffers: Vec<Handle<crate::GlobalVariable>>,
 }
 
 pub fn write_string(
@@ -450,6 +543,127 @@ pub fn write_string(
     Ok((w.finish(), info))
 }
 
+/// Return the lowest Metal Shading Language version `module` can be
+/// translated to.
+///
+/// The version is found by inspecting the module and its `ModuleInfo`, so the
+/// module isn't translated. Errors that don't depend on the version, like
+/// unsupported double precision, are only reported when writing.
+pub fn required_lang_version(
+    module: &crate::Module,
+    info: &ModuleInfo,
+    pipeline_options: &PipelineOptions,
+) -> Result<(u8, u8), Error> {
+    writer::required_lang_version(module, info, pipeline_options)
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn view_index() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[stage(vertex)]]
+        fn vs_main([[builtin(view_index)]] view: u32) -> [[builtin(position)]] vec4<f32> {
+            return vec4<f32>(f32(view));
+        }
+        [[stage(fragment)]]
+        fn fs_main([[builtin(view_index)]] view: u32) -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(f32(view));
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::MULTIVIEW,
+    )
+    .validate(&module)
+    .unwrap();
+
+    match write_string(&module, &info, &Options::default(), &Default::default()) {
+        Err(Error::UnsupportedVertexAmplification((1, 1))) => {}
+        other => panic!("Unexpected result {:?}", other.map(|(source, _)| source)),
+    }
+
+    let options = Options {
+        lang_version: (2, 3),
+        ..Options::default()
+    };
+    let (source, _) = write_string(&module, &info, &options, &Default::default()).unwrap();
+    assert!(source.contains("uint view [[amplification_id]]"));
+    assert!(source.contains("uint view_index [[render_target_array_index]];"));
+    assert!(source.contains("float>(view)), view };"));
+    assert!(source.contains("uint view1 [[render_target_array_index]]"));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn required_lang_versions() {
+    let version = |source: &str| {
+        let module = crate::front::wgsl::parse_str(source).unwrap();
+        let info = crate::valid::Validator::new(
+            crate::valid::ValidationFlags::all(),
+            crate::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .unwrap();
+        let version = required_lang_version(&module, &info, &Default::default()).unwrap();
+
+        // The writer must accept that version, and no earlier one
+        let options = |lang_version| Options {
+            lang_version,
+            ..Options::default()
+        };
+        write_string(&module, &info, &options(version), &Default::default()).unwrap();
+        if version > (1, 0) {
+            let earlier = match version {
+                (2, 0) => (1, 2),
+                (major, minor) => (major, minor - 1),
+            };
+            assert!(write_string(&module, &info, &options(earlier), &Default::default()).is_err());
+        }
+        version
+    };
+
+    let fragment = "
+        [[stage(fragment)]]
+        fn fs_main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+        ";
+    assert_eq!(version(fragment), (1, 0));
+    // Read-write storage images need Metal 1.2
+    let read_write = "
+        [[group(0), binding(0)]]
+        var image: [[access(read_write)]] texture_storage_2d<r32float>;
+
+        [[stage(compute), workgroup_size(1)]]
+        fn cs_main() {
+            textureStore(image, vec2<i32>(0), textureLoad(image, vec2<i32>(1)));
+        }
+        ";
+    assert_eq!(version(read_write), (1, 2));
+    // Binding arrays need Metal 2.0
+    let binding_arrays = "
+        [[group(0), binding(0)]]
+        var textures: binding_array<texture_2d<f32>, 4>;
+
+        [[stage(fragment)]]
+        fn fs_main() -> [[location(0)]] vec4<f32> {
+            return textureLoad(textures[1], vec2<i32>(0), 0);
+        }
+        ";
+    assert_eq!(version(binding_arrays), (2, 0));
+    // Vertex amplification needs Metal 2.3
+    let amplified = "
+        [[stage(vertex)]]
+        fn vs_main([[builtin(view_index)]] view: u32) -> [[builtin(position)]] vec4<f32> {
+            return vec4<f32>(f32(view));
+        }
+        ";
+    assert_eq!(version(amplified), (2, 3));
+}
+
 #[test]
 fn test_error_size() {
     use std::mem::size_of;
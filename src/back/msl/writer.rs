@@ -1,4 +1,6 @@
-use super::{sampler as sm, Error, LocationMode, Options, PipelineOptions, TranslationInfo};
+use super::{
+    sampler as sm, Error, LocationMode, Options, PipelineOptions, ResolvedBinding, TranslationInfo,
+};
 use crate::{
     arena::Handle,
     back,
@@ -31,49 +33,55 @@ impl Display for Level {
 
 struct TypeContext<'a> {
     handle: Handle<crate::Type>,
-    arena: &'a crate::Arena<crate::Type>,
+    arena: &'a crate::UniqueArena<crate::Type>,
+    constants: &'a crate::Arena<crate::Constant>,
     names: &'a FastHashMap<NameKey, String>,
     access: crate::StorageAccess,
     first_time: bool,
 }
 
-impl<'a> Display for TypeContext<'a> {
-    fn fmt(&self, out: &mut Formatter<'_>) -> Result<(), FmtError> {
+impl<'a> TypeContext<'a> {
+    fn try_fmt<W: Write>(&self, out: &mut W) -> BackendResult {
         let ty = &self.arena[self.handle];
         if ty.needs_alias() && !self.first_time {
             let name = &self.names[&NameKey::Type(self.handle)];
-            return write!(out, "{}", name);
+            write!(out, "{}", name)?;
+            return Ok(());
         }
 
         match ty.inner {
             // work around Metal toolchain bug with `uint` typedef
             crate::TypeInner::Scalar {
                 kind: crate::ScalarKind::Uint,
-                ..
+                width: 4,
             } => {
-                write!(out, "metal::uint")
+                write!(out, "metal::uint")?;
             }
-            crate::TypeInner::Scalar { kind, .. } => {
-                write!(out, "{}", scalar_kind_string(kind))
+            crate::TypeInner::Scalar { kind, width } => {
+                write!(out, "{}", scalar_kind_string(kind, width))?;
             }
-            crate::TypeInner::Vector { size, kind, .. } => {
+            crate::TypeInner::Vector { size, kind, width } => {
                 write!(
                     out,
                     "{}::{}{}",
                     NAMESPACE,
-                    scalar_kind_string(kind),
+                    scalar_kind_string(kind, width),
                     back::vector_size_str(size),
-                )
+                )?;
             }
-            crate::TypeInner::Matrix { columns, rows, .. } => {
+            crate::TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            } => {
                 write!(
                     out,
                     "{}::{}{}x{}",
                     NAMESPACE,
-                    scalar_kind_string(crate::ScalarKind::Float),
+                    scalar_kind_string(crate::ScalarKind::Float, width),
                     back::vector_size_str(columns),
                     back::vector_size_str(rows),
-                )
+                )?;
             }
             crate::TypeInner::Pointer { base, class } => {
                 let sub = Self {
@@ -85,24 +93,26 @@ impl<'a> Display for TypeContext<'a> {
                     Some(name) => name,
                     None => return Ok(()),
                 };
-                write!(out, "{} {}&", class_name, sub)
+                write!(out, "{} ", class_name)?;
+                sub.try_fmt(out)?;
+                write!(out, "&")?;
             }
             crate::TypeInner::ValuePointer {
                 size: None,
                 kind,
-                width: _,
+                width,
                 class,
             } => {
                 let class_name = match class.get_name(self.access) {
                     Some(name) => name,
                     None => return Ok(()),
                 };
-                write!(out, "{} {}&", class_name, scalar_kind_string(kind),)
+                write!(out, "{} {}&", class_name, scalar_kind_string(kind, width),)?;
             }
             crate::TypeInner::ValuePointer {
                 size: Some(size),
                 kind,
-                width: _,
+                width,
                 class,
             } => {
                 let class_name = match class.get_name(self.access) {
@@ -114,9 +124,9 @@ impl<'a> Display for TypeContext<'a> {
                     "{} {}::{}{}&",
                     class_name,
                     NAMESPACE,
-                    scalar_kind_string(kind),
+                    scalar_kind_string(kind, width),
                     back::vector_size_str(size),
-                )
+                )?;
             }
             crate::TypeInner::Array { base, .. } => {
                 let sub = Self {
@@ -126,9 +136,12 @@ impl<'a> Display for TypeContext<'a> {
                 };
                 // Array lengths go at the end of the type definition,
                 // so just print the element type here.
-                write!(out, "{}", sub)
+                sub.try_fmt(out)?;
+            }
+            // structs are always aliased, their definitions are written by `write_type_defs`
+            crate::TypeInner::Struct { .. } => {
+                write!(out, "{}", self.names[&NameKey::Type(self.handle)])?;
             }
-            crate::TypeInner::Struct { .. } => unreachable!(),
             crate::TypeInner::Image {
                 dim,
                 arrayed,
@@ -149,7 +162,14 @@ impl<'a> Display for TypeContext<'a> {
                         };
                         ("texture", msaa_str, kind, access)
                     }
-                    crate::ImageClass::Depth => ("depth", "", crate::ScalarKind::Float, "sample"),
+                    crate::ImageClass::Depth { multi } => {
+                        let (msaa_str, access) = if multi {
+                            ("_ms", "read")
+                        } else {
+                            ("", "sample")
+                        };
+                        ("depth", msaa_str, crate::ScalarKind::Float, access)
+                    }
                     crate::ImageClass::Storage(format) => {
                         let access = if self
                             .access
@@ -161,12 +181,12 @@ impl<'a> Display for TypeContext<'a> {
                         } else if self.access.contains(crate::StorageAccess::LOAD) {
                             "read"
                         } else {
-                            unreachable!("module is not valid")
+                            return Err(Error::InvalidStorageAccess(self.handle));
                         };
                         ("texture", "", format.into(), access)
                     }
                 };
-                let base_name = scalar_kind_string(kind);
+                let base_name = scalar_kind_string(kind, 4);
                 let array_str = if arrayed { "_array" } else { "" };
                 write!(
                     out,
@@ -179,12 +199,38 @@ impl<'a> Display for TypeContext<'a> {
                     base_name,
                     NAMESPACE,
                     access,
-                )
+                )?;
             }
             crate::TypeInner::Sampler { comparison: _ } => {
-                write!(out, "{}::sampler", NAMESPACE)
+                write!(out, "{}::sampler", NAMESPACE)?;
+            }
+            crate::TypeInner::BindingArray { base, size } => {
+                let sub = Self {
+                    handle: base,
+                    first_time: false,
+                    ..*self
+                };
+                match size {
+                    crate::ArraySize::Constant(const_handle) => {
+                        let coco = ConstantContext {
+                            handle: const_handle,
+                            arena: self.constants,
+                            names: self.names,
+                            first_time: false,
+                        };
+                        write!(out, "{}::array<", NAMESPACE)?;
+                        sub.try_fmt(out)?;
+                        write!(out, ", {}>", coco)?;
+                    }
+                    crate::ArraySize::Dynamic => {
+                        return Err(Error::FeatureNotImplemented(
+                            "runtime-sized binding array".to_string(),
+                        ))
+                    }
+                }
             }
         }
+        Ok(())
     }
 }
 
@@ -193,6 +239,7 @@ struct TypedGlobalVariable<'a> {
     names: &'a FastHashMap<NameKey, String>,
     handle: Handle<crate::GlobalVariable>,
     usage: valid::GlobalUse,
+    access: crate::StorageAccess,
     reference: bool,
 }
 
@@ -203,8 +250,9 @@ impl<'a> TypedGlobalVariable<'a> {
         let ty_name = TypeContext {
             handle: var.ty,
             arena: &self.module.types,
+            constants: &self.module.constants,
             names: self.names,
-            access: var.storage_access,
+            access: self.access,
             first_time: false,
         };
 
@@ -223,12 +271,13 @@ impl<'a> TypedGlobalVariable<'a> {
             _ => ("", "", ""),
         };
 
+        if !space.is_empty() {
+            write!(out, "{} ", space)?;
+        }
+        ty_name.try_fmt(out)?;
         Ok(write!(
             out,
-            "{}{}{}{}{}{} {}",
-            space,
-            if space.is_empty() { "" } else { " " },
-            ty_name,
+            "{}{}{} {}",
             if access.is_empty() { "" } else { " " },
             access,
             reference,
@@ -253,12 +302,14 @@ impl<'a> Display for ConstantContext<'a> {
         }
 
         match con.inner {
-            crate::ConstantInner::Scalar { value, width: _ } => match value {
+            crate::ConstantInner::Scalar { value, width } => match value {
                 crate::ScalarValue::Sint(value) => {
-                    write!(out, "{}", value)
+                    let suffix = if width == 8 { "L" } else { "" };
+                    write!(out, "{}{}", value, suffix)
                 }
                 crate::ScalarValue::Uint(value) => {
-                    write!(out, "{}u", value)
+                    let suffix = if width == 8 { "uL" } else { "u" };
+                    write!(out, "{}{}", value, suffix)
                 }
                 crate::ScalarValue::Float(value) => {
                     if value.is_infinite() {
@@ -268,17 +319,200 @@ impl<'a> Display for ConstantContext<'a> {
                         write!(out, "NAN")
                     } else {
                         let suffix = if value.fract() == 0.0 { ".0" } else { "" };
+                        let half = if width == 2 { "h" } else { "" };
 
-                        write!(out, "{}{}", value, suffix)
+                        write!(out, "{}{}{}", value, suffix, half)
                     }
                 }
                 crate::ScalarValue::Bool(value) => {
                     write!(out, "{}", value)
                 }
             },
-            crate::ConstantInner::Composite { .. } => unreachable!("should be aliased"),
+            // composite constants are always aliased
+            crate::ConstantInner::Composite { .. } => {
+                write!(out, "{}", self.names[&NameKey::Constant(self.handle)])
+            }
+        }
+    }
+}
+
+/// The entry point selected by [`PipelineOptions::entry_point`].
+struct Selection {
+    entry_point: usize,
+    /// Functions called by the entry point, directly or indirectly.
+    functions: crate::FastHashSet<Handle<crate::Function>>,
+}
+
+impl Selection {
+    /// Select the entry point requested by `pipeline_options`, if any.
+    fn new(
+        module: &crate::Module,
+        pipeline_options: &PipelineOptions,
+    ) -> Result<Option<Self>, Error> {
+        Ok(match pipeline_options.entry_point {
+            Some((stage, ref name)) => {
+                let entry_point = back::find_entry_point(module, stage, name)
+                    .ok_or_else(|| Error::EntryPointNotFound(stage, name.clone()))?;
+                let functions =
+                    back::reachable_functions(module, &module.entry_points[entry_point].function);
+                Some(Selection {
+                    entry_point,
+                    functions,
+                })
+            }
+            None => None,
+        })
+    }
+
+    fn includes_entry_point(selection: Option<&Self>, index: usize) -> bool {
+        match selection {
+            Some(sel) => sel.entry_point == index,
+            None => true,
+        }
+    }
+
+    fn includes_function(selection: Option<&Self>, handle: Handle<crate::Function>) -> bool {
+        match selection {
+            Some(sel) => sel.functions.contains(&handle),
+            None => true,
+        }
+    }
+}
+
+/// Narrow the declared access of the storage image `handle` down to its
+/// usage in the selected entry points.
+fn narrowed_access(
+    module: &crate::Module,
+    info: &valid::ModuleInfo,
+    handle: Handle<crate::GlobalVariable>,
+    var: &crate::GlobalVariable,
+    selection: Option<&Selection>,
+) -> crate::StorageAccess {
+    let usage = (0..module.entry_points.len())
+        .filter(|&index| Selection::includes_entry_point(selection, index))
+        .fold(valid::GlobalUse::empty(), |usage, index| {
+            usage | info.get_entry_point(index)[handle]
+        });
+    let mut used = crate::StorageAccess::empty();
+    if usage.contains(valid::GlobalUse::READ) {
+        used |= crate::StorageAccess::LOAD;
+    }
+    if usage.contains(valid::GlobalUse::WRITE) {
+        used |= crate::StorageAccess::STORE;
+    }
+    match var.storage_access & used {
+        // unused or only queried, keep the declared access
+        narrowed if narrowed.is_empty() => var.storage_access,
+        narrowed => narrowed,
+    }
+}
+
+/// The lowest Metal version supporting reads and writes of the same storage image.
+const READ_WRITE_STORAGE_VERSION: (u8, u8) = (1, 2);
+/// The lowest Metal version supporting vertex amplification, which gives the
+/// view index to the vertex stage.
+pub(super) const VERTEX_AMPLIFICATION_VERSION: (u8, u8) = (2, 3);
+
+/// The lowest Metal version having a scalar type of `kind` and `width`, apart
+/// from doubles, which no version has.
+fn scalar_lang_version(kind: crate::ScalarKind, width: crate::Bytes) -> (u8, u8) {
+    match (kind, width) {
+        (crate::ScalarKind::Sint, 8) | (crate::ScalarKind::Uint, 8) => (2, 2),
+        _ => (1, 0),
+    }
+}
+
+/// The lowest Metal version supporting the type `inner`.
+fn type_lang_version(inner: &crate::TypeInner) -> (u8, u8) {
+    match *inner {
+        crate::TypeInner::Scalar { kind, width }
+        | crate::TypeInner::Vector { kind, width, .. }
+        | crate::TypeInner::ValuePointer { kind, width, .. } => scalar_lang_version(kind, width),
+        crate::TypeInner::BindingArray { .. } => (2, 0),
+        _ => (1, 0),
+    }
+}
+
+/// The lowest Metal version supporting `expression`.
+fn expression_lang_version(expression: &crate::Expression) -> (u8, u8) {
+    match *expression {
+        crate::Expression::As {
+            kind,
+            convert: Some(width),
+            ..
+        } => scalar_lang_version(kind, width),
+        crate::Expression::ImageQuery {
+            query: crate::ImageQuery::Lod { .. },
+            ..
+        } => (2, 2),
+        _ => (1, 0),
+    }
+}
+
+/// Return the lowest Metal version the selected parts of `module` can be
+/// translated to, looking up the same versions as the checks of the writer.
+pub(super) fn required_lang_version(
+    module: &crate::Module,
+    info: &valid::ModuleInfo,
+    pipeline_options: &PipelineOptions,
+) -> Result<(u8, u8), Error> {
+    let selection = Selection::new(module, pipeline_options)?;
+    let mut version = (1, 0);
+
+    for (_, ty) in module.types.iter() {
+        version = version.max(type_lang_version(&ty.inner));
+    }
+
+    for (handle, var) in module.global_variables.iter() {
+        if let crate::TypeInner::Image {
+            class: crate::ImageClass::Storage(_),
+            ..
+        } = module.types[var.ty].inner
+        {
+            let access = narrowed_access(module, info, handle, var, selection.as_ref());
+            if access.contains(crate::StorageAccess::LOAD | crate::StorageAccess::STORE) {
+                version = version.max(READ_WRITE_STORAGE_VERSION);
+            }
         }
     }
+
+    let functions = module
+        .functions
+        .iter()
+        .filter(|&(handle, _)| Selection::includes_function(selection.as_ref(), handle))
+        .map(|(_, function)| function);
+    let entry_points = module
+        .entry_points
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| Selection::includes_entry_point(selection.as_ref(), index))
+        .map(|(_, ep)| ep);
+    for function in functions.chain(entry_points.clone().map(|ep| &ep.function)) {
+        for (_, expression) in function.expressions.iter() {
+            version = version.max(expression_lang_version(expression));
+        }
+    }
+
+    // vertex amplification
+    let is_view_index = |binding: Option<&crate::Binding>| {
+        binding == Some(&crate::Binding::BuiltIn(crate::BuiltIn::ViewIndex))
+    };
+    for ep in entry_points.filter(|ep| ep.stage == crate::ShaderStage::Vertex) {
+        let amplified = ep.function.arguments.iter().any(|arg| {
+            is_view_index(arg.binding.as_ref())
+                || match module.types[arg.ty].inner {
+                    crate::TypeInner::Struct { ref members, .. } => members
+                        .iter()
+                        .any(|member| is_view_index(member.binding.as_ref())),
+                    _ => false,
+                }
+        });
+        if amplified {
+            version = version.max(VERTEX_AMPLIFICATION_VERSION);
+        }
+    }
+
+    Ok(version)
 }
 
 pub struct Writer<W> {
@@ -287,18 +521,128 @@ pub struct Writer<W> {
     named_expressions: crate::NamedExpressions,
     namer: proc::Namer,
     runtime_sized_buffers: FastHashMap<Handle<crate::GlobalVariable>, usize>,
+    /// Effective access of the storage images, narrowed down to their usage.
+    storage_image_access: FastHashMap<Handle<crate::GlobalVariable>, crate::StorageAccess>,
+    /// Access of the storage images passed to functions, threaded from the
+    /// images given at their call sites.
+    argument_access: FastHashMap<(Handle<crate::Function>, u32), crate::StorageAccess>,
+    /// Names of the functions computing the inverse of square matrices, by
+    /// their size and width, since MSL has no builtin for it.
+    inverse_functions: FastHashMap<(crate::VectorSize, crate::Bytes), String>,
     #[cfg(test)]
     put_expression_stack_pointers: crate::FastHashSet<*const ()>,
     #[cfg(test)]
     put_block_stack_pointers: crate::FastHashSet<*const ()>,
 }
 
-fn scalar_kind_string(kind: crate::ScalarKind) -> &'static str {
-    match kind {
-        crate::ScalarKind::Float => "float",
-        crate::ScalarKind::Sint => "int",
-        crate::ScalarKind::Uint => "uint",
-        crate::ScalarKind::Bool => "bool",
+/// Calls `f` with the callee and the arguments of every call in `block`.
+fn collect_calls<'a>(
+    block: &'a [crate::Statement],
+    f: &mut impl FnMut(Handle<crate::Function>, &'a [Handle<crate::Expression>]),
+) {
+    use crate::Statement as S;
+    for statement in block {
+        match *statement {
+            S::Block(ref block) => collect_calls(block, f),
+            S::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                collect_calls(accept, f);
+                collect_calls(reject, f);
+            }
+            S::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases {
+                    collect_calls(&case.body, f);
+                }
+                collect_calls(default, f);
+            }
+            S::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                collect_calls(body, f);
+                collect_calls(continuing, f);
+            }
+            S::Call {
+                function,
+                ref arguments,
+                ..
+            } => f(function, arguments),
+            _ => {}
+        }
+    }
+}
+
+/// Returns true if the storage format can be accessed as `read_write`
+/// by the Metal read-write texture tier 2.
+///
+/// Tier 1 only supports `R32Float`, `R32Uint`, and `R32Sint`.
+fn supports_read_write(format: crate::StorageFormat) -> bool {
+    use crate::StorageFormat as Sf;
+    matches!(
+        format,
+        Sf::R8Unorm
+            | Sf::R8Uint
+            | Sf::R8Sint
+            | Sf::R16Uint
+            | Sf::R16Sint
+            | Sf::R16Float
+            | Sf::R32Uint
+            | Sf::R32Sint
+            | Sf::R32Float
+            | Sf::Rgba8Unorm
+            | Sf::Rgba8Uint
+            | Sf::Rgba8Sint
+            | Sf::Rgba16Uint
+            | Sf::Rgba16Sint
+            | Sf::Rgba16Float
+            | Sf::Rgba32Uint
+            | Sf::Rgba32Sint
+            | Sf::Rgba32Float
+    )
+}
+
+fn scalar_kind_string(kind: crate::ScalarKind, width: crate::Bytes) -> &'static str {
+    use crate::ScalarKind as Sk;
+
+    // unsupported widths are rejected by `check_scalar_width`
+    match (kind, width) {
+        (Sk::Float, 2) => "half",
+        (Sk::Float, _) => "float",
+        (Sk::Sint, 1) => "char",
+        (Sk::Sint, 2) => "short",
+        (Sk::Sint, 8) => "long",
+        (Sk::Sint, _) => "int",
+        (Sk::Uint, 1) => "uchar",
+        (Sk::Uint, 2) => "ushort",
+        (Sk::Uint, 8) => "ulong",
+        (Sk::Uint, _) => "uint",
+        (Sk::Bool, _) => "bool",
+    }
+}
+
+/// Check that Metal has a type for the scalar of the given width.
+///
+/// Metal has no doubles, and 64-bit integers are only available since Metal 2.2.
+fn check_scalar_width(
+    kind: crate::ScalarKind,
+    width: crate::Bytes,
+    lang_version: (u8, u8),
+) -> Result<(), Error> {
+    match (kind, width) {
+        (crate::ScalarKind::Float, 8) => {
+            Err(Error::CapabilityNotSupported(valid::Capabilities::FLOAT64))
+        }
+        _ if lang_version < scalar_lang_version(kind, width) => {
+            Err(Error::CapabilityNotSupported(valid::Capabilities::INT64))
+        }
+        _ => Ok(()),
     }
 }
 
@@ -336,7 +680,7 @@ fn should_pack_struct_member(
     }
 }
 
-fn needs_array_length(ty: Handle<crate::Type>, arena: &crate::Arena<crate::Type>) -> bool {
+fn needs_array_length(ty: Handle<crate::Type>, arena: &crate::UniqueArena<crate::Type>) -> bool {
     if let crate::TypeInner::Struct { ref members, .. } = arena[ty].inner {
         if let Some(member) = members.last() {
             if let crate::TypeInner::Array {
@@ -361,6 +705,7 @@ impl crate::StorageClass {
             crate::StorageClass::Uniform
             | crate::StorageClass::Storage
             | crate::StorageClass::Private
+            | crate::StorageClass::WorkGroup
             | crate::StorageClass::PushConstant
             | crate::StorageClass::Handle => true,
             _ => false,
@@ -377,6 +722,7 @@ impl crate::StorageClass {
             } else {
                 "constant"
             }),
+            Self::PhysicalStorageBuffer => Some("device"),
             Self::Private | Self::Function => Some("thread"),
             Self::WorkGroup => Some("threadgroup"),
         }
@@ -398,7 +744,7 @@ impl crate::Type {
             // composite types are better to be aliased, regardless of the name
             Ti::Struct { .. } | Ti::Array { .. } => true,
             // handle types may be different, depending on the global var access, so we always inline them
-            Ti::Image { .. } | Ti::Sampler { .. } => false,
+            Ti::Image { .. } | Ti::Sampler { .. } | Ti::BindingArray { .. } => false,
         }
     }
 }
@@ -424,18 +770,41 @@ struct ExpressionContext<'a> {
     info: &'a valid::FunctionInfo,
     module: &'a crate::Module,
     pipeline_options: &'a PipelineOptions,
+    lang_version: (u8, u8),
 }
 
 impl<'a> ExpressionContext<'a> {
     fn resolve_type(&self, handle: Handle<crate::Expression>) -> &'a crate::TypeInner {
         self.info[handle].ty.inner_with(&self.module.types)
     }
+
+    fn invalid_expression(&self, expression: Handle<crate::Expression>) -> Error {
+        use crate::diagnostic::IrLocation;
+
+        Error::InvalidExpression(Box::new(match self.origin {
+            FunctionOrigin::Handle(handle) => IrLocation::Function {
+                handle,
+                expression: Some(expression),
+            },
+            FunctionOrigin::EntryPoint(ep_index) => {
+                let ep = &self.module.entry_points[ep_index as usize];
+                IrLocation::EntryPoint {
+                    stage: ep.stage,
+                    name: ep.name.clone(),
+                    expression: Some(expression),
+                }
+            }
+        }))
+    }
 }
 
 struct StatementContext<'a> {
     expression: ExpressionContext<'a>,
     mod_info: &'a valid::ModuleInfo,
     result_struct: Option<&'a str>,
+    /// View index to pass on as the render target array index, after the
+    /// members of the result.
+    result_view_index: Option<&'a str>,
 }
 
 impl<W: Write> Writer<W> {
@@ -447,6 +816,9 @@ impl<W: Write> Writer<W> {
             named_expressions: crate::NamedExpressions::default(),
             namer: proc::Namer::default(),
             runtime_sized_buffers: FastHashMap::default(),
+            storage_image_access: FastHashMap::default(),
+            argument_access: FastHashMap::default(),
+            inverse_functions: FastHashMap::default(),
             #[cfg(test)]
             put_expression_stack_pointers: Default::default(),
             #[cfg(test)]
@@ -501,7 +873,7 @@ impl<W: Write> Writer<W> {
         // so compose the result of them.
         let dim = match *context.resolve_type(image) {
             crate::TypeInner::Image { dim, .. } => dim,
-            ref other => unreachable!("Unexpected type {:?}", other),
+            _ => return Err(context.invalid_expression(image)),
         };
         match dim {
             crate::ImageDimension::D1 => {
@@ -557,21 +929,18 @@ impl<W: Write> Writer<W> {
         level: crate::SampleLevel,
         context: &ExpressionContext,
     ) -> BackendResult {
-        let has_levels = match *context.resolve_type(image) {
-            crate::TypeInner::Image {
-                dim: crate::ImageDimension::D1,
-                ..
-            } => false,
-            _ => true,
+        let dim = match *context.resolve_type(image) {
+            crate::TypeInner::Image { dim, .. } => dim,
+            _ => return Err(context.invalid_expression(image)),
         };
         match level {
             crate::SampleLevel::Auto => {}
-            crate::SampleLevel::Zero => {
-                //TODO: do we support Zero on `Sampled` image classes?
-            }
-            _ if !has_levels => {
+            _ if dim == crate::ImageDimension::D1 => {
                 log::warn!("1D image can't be sampled with level {:?}", level);
             }
+            crate::SampleLevel::Zero => {
+                write!(self.out, ", {}::level(0.0)", NAMESPACE)?;
+            }
             crate::SampleLevel::Exact(h) => {
                 write!(self.out, ", {}::level(", NAMESPACE)?;
                 self.put_expression(h, context, true)?;
@@ -583,7 +952,12 @@ impl<W: Write> Writer<W> {
                 write!(self.out, ")")?;
             }
             crate::SampleLevel::Gradient { x, y } => {
-                write!(self.out, ", {}::gradient(", NAMESPACE)?;
+                let dim_str = match dim {
+                    crate::ImageDimension::D3 => "3d",
+                    crate::ImageDimension::Cube => "cube",
+                    _ => "2d",
+                };
+                write!(self.out, ", {}::gradient{}(", NAMESPACE, dim_str)?;
                 self.put_expression(x, context, true)?;
                 write!(self.out, ", ")?;
                 self.put_expression(y, context, true)?;
@@ -600,27 +974,31 @@ impl<W: Write> Writer<W> {
         context: &ExpressionContext,
     ) -> BackendResult {
         match context.module.types[ty].inner {
-            crate::TypeInner::Scalar { width: 4, kind } if components.len() == 1 => {
-                write!(self.out, "{}", scalar_kind_string(kind))?;
+            crate::TypeInner::Scalar { width, kind } if components.len() == 1 => {
+                write!(self.out, "{}", scalar_kind_string(kind, width))?;
                 self.put_call_parameters(components.iter().cloned(), context)?;
             }
-            crate::TypeInner::Vector { size, kind, .. } => {
+            crate::TypeInner::Vector { size, kind, width } => {
                 write!(
                     self.out,
                     "{}::{}{}",
                     NAMESPACE,
-                    scalar_kind_string(kind),
+                    scalar_kind_string(kind, width),
                     back::vector_size_str(size)
                 )?;
                 self.put_call_parameters(components.iter().cloned(), context)?;
             }
-            crate::TypeInner::Matrix { columns, rows, .. } => {
+            crate::TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            } => {
                 let kind = crate::ScalarKind::Float;
                 write!(
                     self.out,
                     "{}::{}{}x{}",
                     NAMESPACE,
-                    scalar_kind_string(kind),
+                    scalar_kind_string(kind, width),
                     back::vector_size_str(columns),
                     back::vector_size_str(rows)
                 )?;
@@ -750,7 +1128,8 @@ impl<W: Write> Writer<W> {
                 };
                 match *resolved {
                     crate::TypeInner::Struct { .. } => {
-                        let base_ty = base_ty_handle.unwrap();
+                        let base_ty =
+                            base_ty_handle.ok_or_else(|| context.invalid_expression(base))?;
                         let name = &self.names[&NameKey::StructMember(base_ty, index)];
                         write!(self.out, ".{}", name)?;
                     }
@@ -763,6 +1142,9 @@ impl<W: Write> Writer<W> {
                     crate::TypeInner::Array { .. } => {
                         write!(self.out, ".{}[{}]", WRAPPED_ARRAY_FIELD, index)?;
                     }
+                    crate::TypeInner::BindingArray { .. } => {
+                        write!(self.out, "[{}]", index)?;
+                    }
                     _ => {
                         // unexpected indexing, should fail validation
                     }
@@ -778,11 +1160,10 @@ impl<W: Write> Writer<W> {
                 write!(self.out, "{}", coco)?;
             }
             crate::Expression::Splat { size, value } => {
-                let scalar_kind = match *context.resolve_type(value) {
-                    crate::TypeInner::Scalar { kind, .. } => kind,
+                let scalar = match *context.resolve_type(value) {
+                    crate::TypeInner::Scalar { kind, width } => scalar_kind_string(kind, width),
                     _ => return Err(Error::Validation),
                 };
-                let scalar = scalar_kind_string(scalar_kind);
                 let size = back::vector_size_str(size);
 
                 write!(self.out, "{}::{}{}(", NAMESPACE, scalar, size)?;
@@ -862,7 +1243,7 @@ impl<W: Write> Writer<W> {
                         self.out,
                         "{}::{}3(",
                         NAMESPACE,
-                        scalar_kind_string(scalar_kind)
+                        scalar_kind_string(scalar_kind, 4)
                     )?;
                     self.put_expression(pointer, context, true)?;
                     write!(self.out, ")")?;
@@ -876,15 +1257,18 @@ impl<W: Write> Writer<W> {
             crate::Expression::ImageSample {
                 image,
                 sampler,
+                gather,
                 coordinate,
                 array_index,
                 offset,
                 level,
                 depth_ref,
             } => {
-                let op = match depth_ref {
-                    Some(_) => "sample_compare",
-                    None => "sample",
+                let op = match (gather, depth_ref) {
+                    (None, Some(_)) => "sample_compare",
+                    (None, None) => "sample",
+                    (Some(_), Some(_)) => "gather_compare",
+                    (Some(_), None) => "gather",
                 };
                 self.put_expression(image, context, false)?;
                 write!(self.out, ".{}(", op)?;
@@ -900,7 +1284,9 @@ impl<W: Write> Writer<W> {
                     self.put_expression(dref, context, true)?;
                 }
 
-                self.put_image_sample_level(image, level, context)?;
+                if gather.is_none() {
+                    self.put_image_sample_level(image, level, context)?;
+                }
 
                 if let Some(constant) = offset {
                     let coco = ConstantContext {
@@ -911,6 +1297,28 @@ impl<W: Write> Writer<W> {
                     };
                     write!(self.out, ", {}", coco)?;
                 }
+                match gather {
+                    None | Some(crate::SwizzleComponent::X) => {}
+                    Some(component) => {
+                        // the offset argument precedes the component, except on cube maps
+                        let is_cube_map = match *context.resolve_type(image) {
+                            crate::TypeInner::Image {
+                                dim: crate::ImageDimension::Cube,
+                                ..
+                            } => true,
+                            _ => false,
+                        };
+                        if offset.is_none() && !is_cube_map {
+                            write!(self.out, ", int2(0)")?;
+                        }
+                        write!(
+                            self.out,
+                            ", {}::component::{}",
+                            NAMESPACE,
+                            back::COMPONENTS[component as usize]
+                        )?;
+                    }
+                }
                 write!(self.out, ")")?;
             }
             crate::Expression::ImageLoad {
@@ -953,6 +1361,31 @@ impl<W: Write> Writer<W> {
                     self.put_expression(image, context, false)?;
                     write!(self.out, ".get_num_samples())")?;
                 }
+                crate::ImageQuery::Lod {
+                    sampler,
+                    coordinate,
+                } => {
+                    let expression = &context.function.expressions[expr_handle];
+                    if context.lang_version < expression_lang_version(expression) {
+                        return Err(Error::UnsupportedLodQuery(context.lang_version));
+                    }
+                    write!(self.out, "{}::float2(", NAMESPACE)?;
+                    for (i, method) in ["calculate_clamped_lod", "calculate_unclamped_lod"]
+                        .iter()
+                        .enumerate()
+                    {
+                        if i != 0 {
+                            write!(self.out, ", ")?;
+                        }
+                        self.put_expression(image, context, false)?;
+                        write!(self.out, ".{}(", method)?;
+                        self.put_expression(sampler, context, true)?;
+                        write!(self.out, ", ")?;
+                        self.put_expression(coordinate, context, true)?;
+                        write!(self.out, ")")?;
+                    }
+                    write!(self.out, ")")?;
+                }
             },
             crate::Expression::Unary { op, expr } => {
                 let op_str = match op {
@@ -1021,7 +1454,8 @@ impl<W: Write> Writer<W> {
                 }
                 _ => return Err(Error::Validation),
             },
-            crate::Expression::Derivative { axis, expr } => {
+            // Metal has no control over the precision of derivatives
+            crate::Expression::Derivative { axis, expr, .. } => {
                 let op = match axis {
                     crate::DerivativeAxis::X => "dfdx",
                     crate::DerivativeAxis::Y => "dfdy",
@@ -1089,7 +1523,8 @@ impl<W: Write> Writer<W> {
                     Mf::Pow => "pow",
                     // geometry
                     Mf::Dot => "dot",
-                    Mf::Outer => return Err(Error::UnsupportedCall(format!("{:?}", fun))),
+                    // written below
+                    Mf::Outer => "",
                     Mf::Cross => "cross",
                     Mf::Distance => "distance",
                     Mf::Length if scalar_argument => "abs",
@@ -1106,19 +1541,87 @@ impl<W: Write> Writer<W> {
                     Mf::SmoothStep => "smoothstep",
                     Mf::Sqrt => "sqrt",
                     Mf::InverseSqrt => "rsqrt",
-                    Mf::Inverse => return Err(Error::UnsupportedCall(format!("{:?}", fun))),
+                    // calls one of the functions written by `write_inverse_functions`
+                    Mf::Inverse => "",
                     Mf::Transpose => "transpose",
                     Mf::Determinant => "determinant",
                     // bits
                     Mf::CountOneBits => "popcount",
                     Mf::ReverseBits => "reverse_bits",
+                    // data packing
+                    Mf::Pack4x8snorm => "pack_float_to_snorm4x8",
+                    Mf::Pack4x8unorm => "pack_float_to_unorm4x8",
+                    Mf::Pack2x16snorm => "pack_float_to_snorm2x16",
+                    Mf::Pack2x16unorm => "pack_float_to_unorm2x16",
+                    // data unpacking
+                    Mf::Unpack4x8snorm => "unpack_snorm4x8_to_float",
+                    Mf::Unpack4x8unorm => "unpack_unorm4x8_to_float",
+                    Mf::Unpack2x16snorm => "unpack_snorm2x16_to_float",
+                    Mf::Unpack2x16unorm => "unpack_unorm2x16_to_float",
+                    // halves are reinterpreted below
+                    Mf::Pack2x16float | Mf::Unpack2x16float => "",
                 };
 
-                if fun == Mf::Distance && scalar_argument {
+                if fun == Mf::Outer {
+                    // build the matrix from its columns, each being the first
+                    // vector scaled by a component of the second one
+                    let arg1 = arg1.ok_or_else(|| context.invalid_expression(expr_handle))?;
+                    let (columns, rows, width) = match *context.resolve_type(expr_handle) {
+                        crate::TypeInner::Matrix {
+                            columns,
+                            rows,
+                            width,
+                        } => (columns, rows, width),
+                        _ => return Err(Error::Validation),
+                    };
+                    write!(
+                        self.out,
+                        "{}::{}{}x{}(",
+                        NAMESPACE,
+                        scalar_kind_string(crate::ScalarKind::Float, width),
+                        back::vector_size_str(columns),
+                        back::vector_size_str(rows),
+                    )?;
+                    for (index, component) in
+                        back::COMPONENTS[..columns as usize].iter().enumerate()
+                    {
+                        if index != 0 {
+                            write!(self.out, ", ")?;
+                        }
+                        self.put_expression(arg, context, false)?;
+                        write!(self.out, " * ")?;
+                        self.put_expression(arg1, context, false)?;
+                        write!(self.out, ".{}", component)?;
+                    }
+                    write!(self.out, ")")?;
+                } else if fun == Mf::Inverse {
+                    let name = match *context.resolve_type(arg) {
+                        crate::TypeInner::Matrix { columns, width, .. } => {
+                            self.inverse_functions.get(&(columns, width))
+                        }
+                        _ => None,
+                    }
+                    .ok_or(Error::Validation)?;
+                    write!(self.out, "{}", name)?;
+                    self.put_call_parameters(iter::once(arg), context)?;
+                } else if fun == Mf::Pack2x16float {
+                    write!(self.out, "as_type<uint>({}::half2", NAMESPACE)?;
+                    self.put_call_parameters(iter::once(arg), context)?;
+                    write!(self.out, ")")?;
+                } else if fun == Mf::Unpack2x16float {
+                    write!(
+                        self.out,
+                        "{}::float2(as_type<{}::half2>",
+                        NAMESPACE, NAMESPACE
+                    )?;
+                    self.put_call_parameters(iter::once(arg), context)?;
+                    write!(self.out, ")")?;
+                } else if fun == Mf::Distance && scalar_argument {
                     write!(self.out, "{}::abs(", NAMESPACE)?;
                     self.put_expression(arg, context, false)?;
                     write!(self.out, " - ")?;
-                    self.put_expression(arg1.unwrap(), context, false)?;
+                    let arg1 = arg1.ok_or_else(|| context.invalid_expression(expr_handle))?;
+                    self.put_expression(arg1, context, false)?;
                     write!(self.out, ")")?;
                 } else {
                     write!(self.out, "{}::{}", NAMESPACE, fun_name)?;
@@ -1130,7 +1633,6 @@ impl<W: Write> Writer<W> {
                 kind,
                 convert,
             } => {
-                let scalar = scalar_kind_string(kind);
                 let (size, width) = match *context.resolve_type(expr) {
                     crate::TypeInner::Scalar { width, .. } => ("", width),
                     crate::TypeInner::Vector { size, width, .. } => {
@@ -1139,22 +1641,27 @@ impl<W: Write> Writer<W> {
                     _ => return Err(Error::Validation),
                 };
                 let op = match convert {
-                    Some(w) if w == width => "static_cast",
-                    Some(8) if kind == crate::ScalarKind::Float => {
-                        return Err(Error::CapabilityNotSupported(valid::Capabilities::FLOAT64))
+                    Some(width) => {
+                        check_scalar_width(kind, width, context.lang_version)?;
+                        "static_cast"
                     }
-                    Some(_) => return Err(Error::Validation),
                     None => "as_type",
                 };
+                let scalar = scalar_kind_string(kind, convert.unwrap_or(width));
                 write!(self.out, "{}<{}{}>(", op, scalar, size)?;
                 self.put_expression(expr, context, true)?;
                 write!(self.out, ")")?;
             }
             // has to be a named expression
-            crate::Expression::Call(_) => unreachable!(),
+            crate::Expression::Call(_) => return Err(context.invalid_expression(expr_handle)),
             crate::Expression::ArrayLength(expr) => {
                 self.put_array_length(expr, context)?;
             }
+            crate::Expression::PointerCast { .. } => {
+                return Err(Error::FeatureNotImplemented(
+                    "physical storage buffer pointers".to_string(),
+                ));
+            }
         }
         Ok(())
     }
@@ -1164,11 +1671,17 @@ impl<W: Write> Writer<W> {
         level: Level,
         expr_handle: Handle<crate::Expression>,
         result_struct: Option<&str>,
+        result_view_index: Option<&str>,
         context: &ExpressionContext,
     ) -> BackendResult {
         match result_struct {
             Some(struct_name) => {
-                let result_ty = context.function.result.as_ref().unwrap().ty;
+                let result_ty = context
+                    .function
+                    .result
+                    .as_ref()
+                    .ok_or_else(|| context.invalid_expression(expr_handle))?
+                    .ty;
                 match context.module.types[result_ty].inner {
                     crate::TypeInner::Struct { ref members, .. } => {
                         let tmp = "_tmp";
@@ -1203,7 +1716,7 @@ impl<W: Write> Writer<W> {
                             {
                                 let size = context.module.constants[const_handle]
                                     .to_array_length()
-                                    .unwrap();
+                                    .ok_or(Error::InvalidArraySize(const_handle))?;
                                 write!(self.out, "{} {{", comma)?;
                                 for j in 0..size {
                                     if j != 0 {
@@ -1220,10 +1733,17 @@ impl<W: Write> Writer<W> {
                                 write!(self.out, "{} {}.{}", comma, tmp, name)?;
                             }
                         }
+                        if let Some(view_index) = result_view_index {
+                            let comma = if is_first { "" } else { "," };
+                            write!(self.out, "{} {}", comma, view_index)?;
+                        }
                     }
                     _ => {
                         write!(self.out, "{}return {} {{ ", level, struct_name)?;
                         self.put_expression(expr_handle, context, true)?;
+                        if let Some(view_index) = result_view_index {
+                            write!(self.out, ", {}", view_index)?;
+                        }
                     }
                 }
                 write!(self.out, " }}")?;
@@ -1248,30 +1768,35 @@ impl<W: Write> Writer<W> {
                 let ty_name = TypeContext {
                     handle: ty_handle,
                     arena: &context.module.types,
+                    constants: &context.module.constants,
                     names: &self.names,
                     access: crate::StorageAccess::empty(),
                     first_time: false,
                 };
-                write!(self.out, "{}", ty_name)?;
+                ty_name.try_fmt(&mut self.out)?;
             }
-            TypeResolution::Value(crate::TypeInner::Scalar { kind, .. }) => {
-                write!(self.out, "{}", scalar_kind_string(kind))?;
+            TypeResolution::Value(crate::TypeInner::Scalar { kind, width }) => {
+                write!(self.out, "{}", scalar_kind_string(kind, width))?;
             }
-            TypeResolution::Value(crate::TypeInner::Vector { size, kind, .. }) => {
+            TypeResolution::Value(crate::TypeInner::Vector { size, kind, width }) => {
                 write!(
                     self.out,
                     "{}::{}{}",
                     NAMESPACE,
-                    scalar_kind_string(kind),
+                    scalar_kind_string(kind, width),
                     back::vector_size_str(size)
                 )?;
             }
-            TypeResolution::Value(crate::TypeInner::Matrix { columns, rows, .. }) => {
+            TypeResolution::Value(crate::TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            }) => {
                 write!(
                     self.out,
                     "{}::{}{}x{}",
                     NAMESPACE,
-                    scalar_kind_string(crate::ScalarKind::Float),
+                    scalar_kind_string(crate::ScalarKind::Float, width),
                     back::vector_size_str(columns),
                     back::vector_size_str(rows),
                 )?;
@@ -1408,6 +1933,7 @@ impl<W: Write> Writer<W> {
                         level.clone(),
                         expr_handle,
                         context.result_struct,
+                        context.result_view_index,
                         &context.expression,
                     )?;
                 }
@@ -1462,7 +1988,7 @@ impl<W: Write> Writer<W> {
                         Some(const_handle) => {
                             let size = context.expression.module.constants[const_handle]
                                 .to_array_length()
-                                .unwrap();
+                                .ok_or(Error::InvalidArraySize(const_handle))?;
                             write!(self.out, "{}for(int _i=0; _i<{}; ++_i) ", level, size)?;
                             self.put_expression(pointer, &context.expression, true)?;
                             write!(self.out, ".{}[_i] = ", WRAPPED_ARRAY_FIELD)?;
@@ -1569,9 +2095,16 @@ impl<W: Write> Writer<W> {
         pipeline_options: &PipelineOptions,
     ) -> Result<TranslationInfo, Error> {
         self.names.clear();
-        self.namer
-            .reset(module, super::keywords::RESERVED, &[], &mut self.names);
+        back::reset_names(
+            &mut self.namer,
+            module,
+            super::keywords::RESERVED,
+            &options.entry_point_names,
+            &mut self.names,
+        )?;
         self.runtime_sized_buffers.clear();
+        let selection = Selection::new(module, pipeline_options)?;
+        self.collect_storage_image_access(module, info, options, selection.as_ref())?;
 
         writeln!(
             self.out,
@@ -1605,9 +2138,307 @@ impl<W: Write> Writer<W> {
         };
 
         self.write_scalar_constants(module)?;
+        for (_, ty) in module.types.iter() {
+            match ty.inner {
+                crate::TypeInner::Scalar { kind, width }
+                | crate::TypeInner::Vector { kind, width, .. }
+                | crate::TypeInner::ValuePointer { kind, width, .. } => {
+                    check_scalar_width(kind, width, options.lang_version)?
+                }
+                crate::TypeInner::Matrix { width, .. } => {
+                    check_scalar_width(crate::ScalarKind::Float, width, options.lang_version)?
+                }
+                crate::TypeInner::BindingArray { .. }
+                    if options.lang_version < type_lang_version(&ty.inner) =>
+                {
+                    return Err(Error::UnsupportedBindingArray(options.lang_version))
+                }
+                _ => {}
+            }
+        }
         self.write_type_defs(module)?;
         self.write_composite_constants(module)?;
-        self.write_functions(module, info, options, pipeline_options)
+        self.write_inverse_functions(module, info)?;
+        self.write_functions(module, info, options, pipeline_options, selection.as_ref())
+    }
+
+    /// Write the functions computing the inverse of the matrices that
+    /// [`MathFunction::Inverse`](crate::MathFunction::Inverse) is applied to.
+    ///
+    /// The inverse is the transposed matrix of cofactors, divided by the
+    /// determinant, with the cofactors of the larger matrices computed from
+    /// cross products of the columns.
+    fn write_inverse_functions(
+        &mut self,
+        module: &crate::Module,
+        info: &valid::ModuleInfo,
+    ) -> BackendResult {
+        self.inverse_functions.clear();
+        let functions = module
+            .functions
+            .iter()
+            .map(|(handle, function)| (function, &info[handle]))
+            .chain(
+                module
+                    .entry_points
+                    .iter()
+                    .enumerate()
+                    .map(|(index, ep)| (&ep.function, info.get_entry_point(index))),
+            );
+        let mut matrices = Vec::new();
+        for (function, fun_info) in functions {
+            for (_, expression) in function.expressions.iter() {
+                if let crate::Expression::Math {
+                    fun: crate::MathFunction::Inverse,
+                    arg,
+                    ..
+                } = *expression
+                {
+                    if let crate::TypeInner::Matrix { columns, width, .. } =
+                        *fun_info[arg].ty.inner_with(&module.types)
+                    {
+                        if !matrices.contains(&(columns, width)) {
+                            matrices.push((columns, width));
+                        }
+                    }
+                }
+            }
+        }
+
+        for (size, width) in matrices {
+            let scalar = scalar_kind_string(crate::ScalarKind::Float, width);
+            let size_str = back::vector_size_str(size);
+            let matrix = format!("{}::{}{}x{}", NAMESPACE, scalar, size_str, size_str);
+            let vec3 = format!("{}::{}3", NAMESPACE, scalar);
+            let name = self
+                .namer
+                .call(&format!("inverse_{}x{}", size_str, size_str));
+            let indent = back::INDENT;
+
+            writeln!(self.out)?;
+            writeln!(self.out, "{} {}({} m) {{", matrix, name, matrix)?;
+            match size {
+                crate::VectorSize::Bi => {
+                    let column = format!("{}::{}2", NAMESPACE, scalar);
+                    writeln!(
+                        self.out,
+                        "{}{} inv_det = 1 / {}::determinant(m);",
+                        indent, scalar, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}return {}({}(m[1][1], -m[0][1]), {}(-m[1][0], m[0][0])) * inv_det;",
+                        indent, matrix, column, column
+                    )?;
+                }
+                crate::VectorSize::Tri => {
+                    writeln!(
+                        self.out,
+                        "{}{} r0 = {}::cross(m[1], m[2]);",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} r1 = {}::cross(m[2], m[0]);",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} r2 = {}::cross(m[0], m[1]);",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}return {}::transpose({}(r0, r1, r2)) * (1 / {}::dot(m[0], r0));",
+                        indent, NAMESPACE, matrix, NAMESPACE
+                    )?;
+                }
+                crate::VectorSize::Quad => {
+                    for (column, name) in ["a", "b", "c", "d"].iter().enumerate() {
+                        writeln!(self.out, "{}{} {} = m[{}].xyz;", indent, vec3, name, column)?;
+                    }
+                    for (column, name) in ["x", "y", "z", "w"].iter().enumerate() {
+                        writeln!(self.out, "{}{} {} = m[{}].w;", indent, scalar, name, column)?;
+                    }
+                    writeln!(
+                        self.out,
+                        "{}{} s = {}::cross(a, b);",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} t = {}::cross(c, d);",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(self.out, "{}{} u = a * y - b * x;", indent, vec3)?;
+                    writeln!(self.out, "{}{} v = c * w - d * z;", indent, vec3)?;
+                    writeln!(
+                        self.out,
+                        "{}{} inv_det = 1 / ({}::dot(s, v) + {}::dot(t, u));",
+                        indent, scalar, NAMESPACE, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}s *= inv_det; t *= inv_det; u *= inv_det; v *= inv_det;",
+                        indent
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} r0 = {}::cross(b, v) + t * y;",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} r1 = {}::cross(v, a) - t * x;",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} r2 = {}::cross(d, u) + s * w;",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{} r3 = {}::cross(u, c) - s * z;",
+                        indent, vec3, NAMESPACE
+                    )?;
+                    let row = format!("{}::{}4", NAMESPACE, scalar);
+                    writeln!(
+                        self.out,
+                        "{}return {}::transpose({}(",
+                        indent, NAMESPACE, matrix
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{}{}(r0, -{}::dot(b, t)), {}(r1, {}::dot(a, t)),",
+                        indent, indent, row, NAMESPACE, row, NAMESPACE
+                    )?;
+                    writeln!(
+                        self.out,
+                        "{}{}{}(r2, -{}::dot(d, s)), {}(r3, {}::dot(c, s))));",
+                        indent, indent, row, NAMESPACE, row, NAMESPACE
+                    )?;
+                }
+            }
+            writeln!(self.out, "}}")?;
+            self.inverse_functions.insert((size, width), name);
+        }
+        Ok(())
+    }
+
+    /// Figure out the access qualifiers of the storage images.
+    ///
+    /// The declared access is narrowed down to the union of the usages
+    /// across all the selected entry points, so that an image that is declared
+    /// `read_write` but only ever loaded from is emitted as `access::read`.
+    /// Functions are emitted once for all the entry points, so the access
+    /// has to be the same everywhere.
+    fn collect_storage_image_access(
+        &mut self,
+        module: &crate::Module,
+        info: &valid::ModuleInfo,
+        options: &Options,
+        selection: Option<&Selection>,
+    ) -> Result<(), Error> {
+        self.storage_image_access.clear();
+        for (handle, var) in module.global_variables.iter() {
+            let format = match module.types[var.ty].inner {
+                crate::TypeInner::Image {
+                    class: crate::ImageClass::Storage(format),
+                    ..
+                } => format,
+                _ => continue,
+            };
+
+            let access = narrowed_access(module, info, handle, var, selection);
+
+            if access.contains(crate::StorageAccess::LOAD | crate::StorageAccess::STORE) {
+                if options.lang_version < READ_WRITE_STORAGE_VERSION {
+                    return Err(Error::UnsupportedReadWriteStorageImage {
+                        format,
+                        lang_version: options.lang_version,
+                    });
+                }
+                if !supports_read_write(format) {
+                    return Err(Error::UnsupportedReadWriteStorageFormat(format));
+                }
+            }
+            self.storage_image_access.insert(handle, access);
+        }
+        self.collect_argument_access(module, selection);
+        Ok(())
+    }
+
+    /// Derives the access of the storage image arguments from the images
+    /// passed to them, since MSL needs it in the function signatures.
+    fn collect_argument_access(&mut self, module: &crate::Module, selection: Option<&Selection>) {
+        self.argument_access.clear();
+        let callers = module
+            .entry_points
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| Selection::includes_entry_point(selection, index))
+            .map(|(_, ep)| (None, &ep.function))
+            .chain(
+                module
+                    .functions
+                    .iter()
+                    .filter(|&(handle, _)| Selection::includes_function(selection, handle))
+                    .map(|(handle, fun)| (Some(handle), fun)),
+            );
+        let mut calls = Vec::new();
+        for (caller, fun) in callers {
+            collect_calls(&fun.body, &mut |function, arguments| {
+                for (index, &argument) in arguments.iter().enumerate() {
+                    match fun.expressions[argument] {
+                        crate::Expression::GlobalVariable(_)
+                        | crate::Expression::FunctionArgument(_) => {
+                            calls.push((caller, (function, index as u32), argument, fun))
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        // a function can call functions declared after it, so repeat until
+        // nothing changes, access sets only ever grow
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(caller, key, argument, fun) in calls.iter() {
+                let access = match fun.expressions[argument] {
+                    crate::Expression::GlobalVariable(handle) => {
+                        self.storage_image_access.get(&handle).cloned()
+                    }
+                    crate::Expression::FunctionArgument(index) => caller
+                        .and_then(|caller| self.argument_access.get(&(caller, index)))
+                        .cloned(),
+                    _ => None,
+                };
+                if let Some(access) = access {
+                    let current = self
+                        .argument_access
+                        .entry(key)
+                        .or_insert_with(crate::StorageAccess::empty);
+                    if !current.contains(access) {
+                        *current |= access;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn global_access(
+        &self,
+        module: &crate::Module,
+        handle: Handle<crate::GlobalVariable>,
+    ) -> crate::StorageAccess {
+        match self.storage_image_access.get(&handle) {
+            Some(&access) => access,
+            None => module.global_variables[handle].storage_access,
+        }
     }
 
     fn write_type_defs(&mut self, module: &crate::Module) -> BackendResult {
@@ -1625,6 +2456,7 @@ impl<W: Write> Writer<W> {
                     let base_name = TypeContext {
                         handle: base,
                         arena: &module.types,
+                        constants: &module.constants,
                         names: &self.names,
                         access: crate::StorageAccess::empty(),
                         first_time: false,
@@ -1640,18 +2472,15 @@ impl<W: Write> Writer<W> {
                             };
 
                             writeln!(self.out, "struct {} {{", name)?;
-                            writeln!(
-                                self.out,
-                                "{}{} {}[{}];",
-                                back::INDENT,
-                                base_name,
-                                WRAPPED_ARRAY_FIELD,
-                                coco
-                            )?;
+                            write!(self.out, "{}", back::INDENT)?;
+                            base_name.try_fmt(&mut self.out)?;
+                            writeln!(self.out, " {}[{}];", WRAPPED_ARRAY_FIELD, coco)?;
                             writeln!(self.out, "}};")?;
                         }
                         crate::ArraySize::Dynamic => {
-                            writeln!(self.out, "typedef {} {}[1];", base_name, name)?;
+                            write!(self.out, "typedef ")?;
+                            base_name.try_fmt(&mut self.out)?;
+                            writeln!(self.out, " {}[1];", name)?;
                         }
                     }
                 }
@@ -1679,7 +2508,7 @@ impl<W: Write> Writer<W> {
                                     self.out,
                                     "{}packed_{}3 {};",
                                     back::INDENT,
-                                    scalar_kind_string(kind),
+                                    scalar_kind_string(kind, 4),
                                     member_name
                                 )?;
                             }
@@ -1687,17 +2516,14 @@ impl<W: Write> Writer<W> {
                                 let base_name = TypeContext {
                                     handle: member.ty,
                                     arena: &module.types,
+                                    constants: &module.constants,
                                     names: &self.names,
                                     access: crate::StorageAccess::empty(),
                                     first_time: false,
                                 };
-                                writeln!(
-                                    self.out,
-                                    "{}{} {};",
-                                    back::INDENT,
-                                    base_name,
-                                    member_name
-                                )?;
+                                write!(self.out, "{}", back::INDENT)?;
+                                base_name.try_fmt(&mut self.out)?;
+                                writeln!(self.out, " {};", member_name)?;
 
                                 // for 3-component vectors, add one component
                                 if let crate::TypeInner::Vector {
@@ -1717,11 +2543,14 @@ impl<W: Write> Writer<W> {
                     let ty_name = TypeContext {
                         handle,
                         arena: &module.types,
+                        constants: &module.constants,
                         names: &self.names,
                         access: crate::StorageAccess::empty(),
                         first_time: true,
                     };
-                    writeln!(self.out, "typedef {} {};", ty_name, name)?;
+                    write!(self.out, "typedef ")?;
+                    ty_name.try_fmt(&mut self.out)?;
+                    writeln!(self.out, " {};", name)?;
                 }
             }
         }
@@ -1731,24 +2560,15 @@ impl<W: Write> Writer<W> {
     fn write_scalar_constants(&mut self, module: &crate::Module) -> BackendResult {
         for (handle, constant) in module.constants.iter() {
             match constant.inner {
-                crate::ConstantInner::Scalar {
-                    width: _,
-                    ref value,
-                } if constant.name.is_some() => {
+                crate::ConstantInner::Scalar { width, ref value } if constant.name.is_some() => {
                     debug_assert!(constant.needs_alias());
                     write!(self.out, "constexpr constant ")?;
-                    match *value {
-                        crate::ScalarValue::Sint(_) => {
-                            write!(self.out, "int")?;
-                        }
-                        crate::ScalarValue::Uint(_) => {
+                    match (value.scalar_kind(), width) {
+                        (crate::ScalarKind::Uint, 4) => {
                             write!(self.out, "unsigned")?;
                         }
-                        crate::ScalarValue::Float(_) => {
-                            write!(self.out, "float")?;
-                        }
-                        crate::ScalarValue::Bool(_) => {
-                            write!(self.out, "bool")?;
+                        (kind, width) => {
+                            write!(self.out, "{}", scalar_kind_string(kind, width))?;
                         }
                     }
                     let name = &self.names[&NameKey::Constant(handle)];
@@ -1776,11 +2596,14 @@ impl<W: Write> Writer<W> {
                     let ty_name = TypeContext {
                         handle: ty,
                         arena: &module.types,
+                        constants: &module.constants,
                         names: &self.names,
                         access: crate::StorageAccess::empty(),
                         first_time: false,
                     };
-                    write!(self.out, "constant {} {} = {{", ty_name, name,)?;
+                    write!(self.out, "constant ")?;
+                    ty_name.try_fmt(&mut self.out)?;
+                    write!(self.out, " {} = {{", name)?;
                     for (i, &sub_handle) in components.iter().enumerate() {
                         let separator = if i != 0 { ", " } else { "" };
                         let coco = ConstantContext {
@@ -1883,9 +2706,13 @@ impl<W: Write> Writer<W> {
         mod_info: &valid::ModuleInfo,
         options: &Options,
         pipeline_options: &PipelineOptions,
+        selection: Option<&Selection>,
     ) -> Result<TranslationInfo, Error> {
         let mut pass_through_globals = Vec::new();
         for (fun_handle, fun) in module.functions.iter() {
+            if !Selection::includes_function(selection, fun_handle) {
+                continue;
+            }
             let fun_info = &mod_info[fun_handle];
             pass_through_globals.clear();
             let mut supports_array_length = false;
@@ -1905,11 +2732,12 @@ impl<W: Write> Writer<W> {
                     let ty_name = TypeContext {
                         handle: result.ty,
                         arena: &module.types,
+                        constants: &module.constants,
                         names: &self.names,
                         access: crate::StorageAccess::empty(),
                         first_time: false,
                     };
-                    write!(self.out, "{}", ty_name)?;
+                    ty_name.try_fmt(&mut self.out)?;
                 }
                 None => {
                     write!(self.out, "void")?;
@@ -1922,8 +2750,14 @@ impl<W: Write> Writer<W> {
                 let param_type_name = TypeContext {
                     handle: arg.ty,
                     arena: &module.types,
+                    constants: &module.constants,
                     names: &self.names,
-                    access: crate::StorageAccess::empty(),
+                    // storage images of uncalled functions can only be loaded
+                    access: self
+                        .argument_access
+                        .get(&(fun_handle, index as u32))
+                        .cloned()
+                        .unwrap_or(crate::StorageAccess::LOAD),
                     first_time: false,
                 };
                 let separator = separate(
@@ -1931,14 +2765,9 @@ impl<W: Write> Writer<W> {
                         || index + 1 != fun.arguments.len()
                         || supports_array_length,
                 );
-                writeln!(
-                    self.out,
-                    "{}{} {}{}",
-                    back::INDENT,
-                    param_type_name,
-                    name,
-                    separator
-                )?;
+                write!(self.out, "{}", back::INDENT)?;
+                param_type_name.try_fmt(&mut self.out)?;
+                writeln!(self.out, " {}{}", name, separator)?;
             }
             for (index, &handle) in pass_through_globals.iter().enumerate() {
                 let tyvar = TypedGlobalVariable {
@@ -1946,6 +2775,7 @@ impl<W: Write> Writer<W> {
                     names: &self.names,
                     handle,
                     usage: fun_info[handle],
+                    access: self.global_access(module, handle),
                     reference: true,
                 };
                 let separator =
@@ -1969,12 +2799,15 @@ impl<W: Write> Writer<W> {
                 let ty_name = TypeContext {
                     handle: local.ty,
                     arena: &module.types,
+                    constants: &module.constants,
                     names: &self.names,
                     access: crate::StorageAccess::empty(),
                     first_time: false,
                 };
                 let local_name = &self.names[&NameKey::FunctionLocal(fun_handle, local_handle)];
-                write!(self.out, "{}{} {}", back::INDENT, ty_name, local_name)?;
+                write!(self.out, "{}", back::INDENT)?;
+                ty_name.try_fmt(&mut self.out)?;
+                write!(self.out, " {}", local_name)?;
                 if let Some(value) = local.init {
                     let coco = ConstantContext {
                         handle: value,
@@ -1994,9 +2827,11 @@ impl<W: Write> Writer<W> {
                     info: fun_info,
                     module,
                     pipeline_options,
+                    lang_version: options.lang_version,
                 },
                 mod_info,
                 result_struct: None,
+                result_view_index: None,
             };
             self.named_expressions.clear();
             self.put_block(Level(1), &fun.body, &context)?;
@@ -2005,12 +2840,26 @@ impl<W: Write> Writer<W> {
 
         let mut info = TranslationInfo {
             entry_point_names: Vec::with_capacity(module.entry_points.len()),
+            renamed: proc::renamed_items(module, &self.names),
+            sized_buffers: module
+                .global_variables
+                .iter()
+                .filter(|&(handle, _)| self.runtime_sized_buffers.contains_key(&handle))
+                .map(|(handle, _)| handle)
+                .collect(),
         };
         for (ep_index, ep) in module.entry_points.iter().enumerate() {
+            if !Selection::includes_entry_point(selection, ep_index) {
+                info.entry_point_names
+                    .push(Err(super::EntryPointError::NotSelected));
+                continue;
+            }
             let fun = &ep.function;
             let fun_info = mod_info.get_entry_point(ep_index);
             let mut ep_error = None;
-            let mut supports_array_length = false;
+            let supports_array_length = module.global_variables.iter().any(|(handle, var)| {
+                !fun_info[handle].is_empty() && needs_array_length(var.ty, &module.types)
+            });
 
             // skip this entry point if any global bindings are missing,
             // or their types are incompatible.
@@ -2042,7 +2891,6 @@ impl<W: Write> Writer<W> {
                             break;
                         }
                     }
-                    supports_array_length |= needs_array_length(var.ty, &module.types);
                 }
                 if supports_array_length {
                     if let Err(err) = options.resolve_sizes_buffer(ep.stage) {
@@ -2112,19 +2960,39 @@ impl<W: Write> Writer<W> {
                     let ty_name = TypeContext {
                         handle: ty,
                         arena: &module.types,
+                        constants: &module.constants,
                         names: &self.names,
                         access: crate::StorageAccess::empty(),
                         first_time: false,
                     };
                     let resolved = options.resolve_local_binding(binding, in_mode)?;
-                    write!(self.out, "{}{} {}", back::INDENT, ty_name, name)?;
+                    write!(self.out, "{}", back::INDENT)?;
+                    ty_name.try_fmt(&mut self.out)?;
+                    write!(self.out, " {}", name)?;
                     resolved.try_fmt_decorated(&mut self.out, "")?;
                     writeln!(self.out, ";")?;
                 }
                 writeln!(self.out, "}};")?;
             }
 
+            // With vertex amplification, the fragment stage reads the view
+            // index from the render target array index, which the vertex
+            // stage has to write.
+            let view_index_name =
+                match ep.stage {
+                    crate::ShaderStage::Vertex => argument_members.iter().find_map(
+                        |&(ref name_key, _, binding)| match binding {
+                            Some(&crate::Binding::BuiltIn(crate::BuiltIn::ViewIndex)) => {
+                                Some(self.names[name_key].clone())
+                            }
+                            _ => None,
+                        },
+                    ),
+                    _ => None,
+                };
             let result_member_name = self.namer.call("member");
+            let namer = &mut self.namer;
+            let view_index_member_name = view_index_name.as_ref().map(|_| namer.call("view_index"));
             let result_type_name = match fun.result {
                 Some(ref result) => {
                     let mut result_members = Vec::new();
@@ -2151,6 +3019,7 @@ impl<W: Write> Writer<W> {
                         let ty_name = TypeContext {
                             handle: ty,
                             arena: &module.types,
+                            constants: &module.constants,
                             names: &self.names,
                             access: crate::StorageAccess::empty(),
                             first_time: true,
@@ -2177,13 +3046,27 @@ impl<W: Write> Writer<W> {
                             _ => None,
                         };
                         let resolved = options.resolve_local_binding(binding, out_mode)?;
-                        write!(self.out, "{}{} {}", back::INDENT, ty_name, name)?;
+                        write!(self.out, "{}", back::INDENT)?;
+                        ty_name.try_fmt(&mut self.out)?;
+                        write!(self.out, " {}", name)?;
                         resolved.try_fmt_decorated(&mut self.out, "")?;
                         if let Some(array_len) = array_len {
                             write!(self.out, " [{}]", array_len)?;
                         }
                         writeln!(self.out, ";")?;
                     }
+                    if let Some(ref member_name) = view_index_member_name {
+                        write!(
+                            self.out,
+                            "{}{}::uint {}",
+                            back::INDENT,
+                            NAMESPACE,
+                            member_name
+                        )?;
+                        ResolvedBinding::ViewIndex(crate::ShaderStage::Fragment)
+                            .try_fmt_decorated(&mut self.out, "")?;
+                        writeln!(self.out, ";")?;
+                    }
                     writeln!(self.out, "}};")?;
                     &stage_out_name
                 }
@@ -2209,6 +3092,7 @@ impl<W: Write> Writer<W> {
                 let ty_name = TypeContext {
                     handle: ty,
                     arena: &module.types,
+                    constants: &module.constants,
                     names: &self.names,
                     access: crate::StorageAccess::empty(),
                     first_time: false,
@@ -2220,13 +3104,17 @@ impl<W: Write> Writer<W> {
                 } else {
                     ','
                 };
-                write!(self.out, "{} {} {}", separator, ty_name, name)?;
+                write!(self.out, "{} ", separator)?;
+                ty_name.try_fmt(&mut self.out)?;
+                write!(self.out, " {}", name)?;
                 resolved.try_fmt_decorated(&mut self.out, "\n")?;
             }
             for (handle, var) in module.global_variables.iter() {
                 let usage = fun_info[handle];
-                if usage.is_empty() || var.class == crate::StorageClass::Private {
-                    continue;
+                match var.class {
+                    _ if usage.is_empty() => continue,
+                    crate::StorageClass::Private | crate::StorageClass::WorkGroup => continue,
+                    _ => {}
                 }
                 // the resolves have already been checked for `!fake_missing_bindings` case
                 let resolved = match var.class {
@@ -2234,9 +3122,9 @@ impl<W: Write> Writer<W> {
                         options.resolve_push_constants(ep.stage).ok()
                     }
                     crate::StorageClass::WorkGroup => None,
-                    _ => options
-                        .resolve_resource_binding(ep.stage, var.binding.as_ref().unwrap())
-                        .ok(),
+                    _ => var.binding.as_ref().and_then(|binding| {
+                        options.resolve_resource_binding(ep.stage, binding).ok()
+                    }),
                 };
                 if let Some(ref resolved) = resolved {
                     // Inline samplers are be defined in the EP body
@@ -2244,12 +3132,22 @@ impl<W: Write> Writer<W> {
                         continue;
                     }
                 }
+                if let crate::TypeInner::BindingArray {
+                    size: crate::ArraySize::Dynamic,
+                    ..
+                } = module.types[var.ty].inner
+                {
+                    return Err(Error::FeatureNotImplemented(
+                        "runtime-sized binding array".to_string(),
+                    ));
+                }
 
                 let tyvar = TypedGlobalVariable {
                     module,
                     names: &self.names,
                     handle,
                     usage,
+                    access: self.global_access(module, handle),
                     reference: true,
                 };
                 let separator = if is_first_argument {
@@ -2277,7 +3175,9 @@ impl<W: Write> Writer<W> {
 
             if supports_array_length {
                 // this is checked earlier
-                let resolved = options.resolve_sizes_buffer(ep.stage).unwrap();
+                let resolved = options
+                    .resolve_sizes_buffer(ep.stage)
+                    .map_err(|_| Error::Validation)?;
                 let separator = if module.global_variables.is_empty() {
                     ' '
                 } else {
@@ -2307,6 +3207,7 @@ impl<W: Write> Writer<W> {
                         names: &self.names,
                         handle,
                         usage,
+                        access: self.global_access(module, handle),
                         reference: false,
                     };
                     write!(self.out, "{}", back::INDENT)?;
@@ -2325,9 +3226,27 @@ impl<W: Write> Writer<W> {
                             writeln!(self.out, " = {{}};")?;
                         }
                     };
+                } else if var.class == crate::StorageClass::WorkGroup {
+                    // Threadgroup memory can't be an entry point argument without
+                    // its length being set by the API, so it's declared here as well,
+                    // and passed by reference to the functions using it.
+                    let tyvar = TypedGlobalVariable {
+                        module,
+                        names: &self.names,
+                        handle,
+                        usage,
+                        access: self.global_access(module, handle),
+                        reference: false,
+                    };
+                    write!(self.out, "{}threadgroup ", back::INDENT)?;
+                    tyvar.try_fmt(&mut self.out)?;
+                    writeln!(self.out, ";")?;
                 } else if let Some(ref binding) = var.binding {
                     // write an inline sampler
-                    let resolved = options.resolve_resource_binding(ep.stage, binding).unwrap();
+                    // this is checked earlier
+                    let resolved = options
+                        .resolve_resource_binding(ep.stage, binding)
+                        .map_err(|_| Error::Validation)?;
                     if let Some(sampler) = resolved.as_inline_sampler(options) {
                         let name = &self.names[&NameKey::GlobalVariable(handle)];
                         writeln!(
@@ -2392,11 +3311,14 @@ impl<W: Write> Writer<W> {
                 let ty_name = TypeContext {
                     handle: local.ty,
                     arena: &module.types,
+                    constants: &module.constants,
                     names: &self.names,
                     access: crate::StorageAccess::empty(),
                     first_time: false,
                 };
-                write!(self.out, "{}{} {}", back::INDENT, ty_name, name)?;
+                write!(self.out, "{}", back::INDENT)?;
+                ty_name.try_fmt(&mut self.out)?;
+                write!(self.out, " {}", name)?;
                 if let Some(value) = local.init {
                     let coco = ConstantContext {
                         handle: value,
@@ -2416,9 +3338,11 @@ impl<W: Write> Writer<W> {
                     info: fun_info,
                     module,
                     pipeline_options,
+                    lang_version: options.lang_version,
                 },
                 mod_info,
                 result_struct: Some(&stage_out_name),
+                result_view_index: view_index_name.as_deref(),
             };
             self.named_expressions.clear();
             self.put_block(Level(1), &fun.body, &context)?;
@@ -2501,3 +3425,44 @@ fn test_stack_size() {
         }
     }
 }
+
+#[cfg(feature = "glsl-in")]
+#[test]
+fn matrix_functions() {
+    use crate::valid::{Capabilities, ValidationFlags};
+
+    let module = crate::front::glsl::parse_str(
+        "
+        #version 450
+        layout(location = 0) in vec4 a;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            mat2 m2 = inverse(mat2(a.xy, a.zw));
+            mat3 m3 = inverse(mat3(a.xyz, a.yzx, a.zxy));
+            mat4 m4 = inverse(outerProduct(a, a.wzyx)) * inverse(mat4(1.0));
+            o_color = m4 * a + vec4(m2[0], m3[1].xy);
+        }
+        ",
+        &crate::front::glsl::Options::from_stage(crate::ShaderStage::Fragment),
+    )
+    .unwrap();
+    let info = valid::Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .unwrap();
+    let mut writer = Writer::new(String::new());
+    writer
+        .write(&module, &info, &Default::default(), &Default::default())
+        .unwrap();
+    let output = writer.finish();
+
+    // one function for each size, even if it's used several times
+    for size in ["2x2", "3x3", "4x4"].iter() {
+        let signature = format!(
+            "metal::float{} inverse_{}_(metal::float{} m) {{",
+            size, size, size
+        );
+        assert_eq!(output.matches(&signature).count(), 1, "{}", output);
+    }
+    // the columns of the outer product are scaled copies of the first vector
+    assert!(output.contains("inverse_4x4_(metal::float4x4(_e18 * _e19.wzyx.x, _e18 * _e19.wzyx.y,"));
+}
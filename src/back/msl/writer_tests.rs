@@ -0,0 +1,811 @@
+//! Tests of the MSL writer that don't need a Metal compiler.
+//!
+//! The output is compared after [`normalize`], which makes the comparisons
+//! insensitive to formatting, comments and the numbering of the temporaries
+//! baked by the writer.
+
+#[cfg(feature = "wgsl-in")]
+use super::{write_string, Error, Options, PipelineOptions};
+
+/// Normalize MSL source for comparisons.
+///
+/// Comments are stripped, whitespace is only kept where it separates two
+/// words, and the `_eN` temporaries are renumbered in order of appearance.
+fn normalize(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut rest = source;
+    while !rest.is_empty() {
+        if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else if rest.starts_with("/*") {
+            rest = rest[2..].find("*/").map_or("", |end| &rest[end + 4..]);
+            stripped.push(' ');
+        } else {
+            let ch = rest.chars().next().unwrap();
+            stripped.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+    }
+
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let mut temporaries = crate::FastHashMap::default();
+    let mut normalized = String::with_capacity(stripped.len());
+    let mut pending_space = false;
+    let mut chars = stripped.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if !is_word(ch) {
+            normalized.push(ch);
+            pending_space = false;
+            continue;
+        }
+
+        let mut word = ch.to_string();
+        while let Some(&next) = chars.peek() {
+            if !is_word(next) {
+                break;
+            }
+            word.push(next);
+            chars.next();
+        }
+        if pending_space && normalized.ends_with(is_word) {
+            normalized.push(' ');
+        }
+        pending_space = false;
+
+        let is_temporary = word.len() > 2
+            && word.starts_with("_e")
+            && word[2..].chars().all(|ch| ch.is_ascii_digit());
+        if is_temporary {
+            let count = temporaries.len();
+            let index = *temporaries.entry(word).or_insert(count);
+            normalized.push_str(&format!("_e{}", index));
+        } else {
+            normalized.push_str(&word);
+        }
+    }
+    normalized
+}
+
+#[test]
+fn normalization() {
+    assert_eq!(
+        normalize(
+            "
+            // a comment
+            float _e12 = a  +  b; /* another
+            comment */ float _e5 = _e12 * 2.0;
+            return _e5;
+            "
+        ),
+        "float _e0=a+b;float _e1=_e0*2.0;return _e1;",
+    );
+    assert_eq!(normalize("metal::float4\n  (1.0)"), "metal::float4(1.0)");
+}
+
+/// Translate WGSL source into normalized MSL.
+#[cfg(feature = "wgsl-in")]
+fn translate(source: &str) -> String {
+    let module = crate::front::wgsl::parse_str(source).unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    let (msl, _) = write_string(&module, &info, &Options::default(), &pipeline_options).unwrap();
+    normalize(&msl)
+}
+
+/// Check that the MSL generated for `source` contains `expected`.
+#[cfg(feature = "wgsl-in")]
+fn check(source: &str, expected: &[&str]) {
+    let msl = translate(source);
+    for snippet in expected {
+        let snippet = normalize(snippet);
+        assert!(
+            msl.contains(&snippet),
+            "`{}` not found in:\n{}",
+            snippet,
+            msl
+        );
+    }
+}
+
+/// Compare the MSL generated for an input of `tests/in` against its snapshot.
+#[cfg(feature = "wgsl-in")]
+fn check_golden(name: &str) {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let source = std::fs::read_to_string(format!("{}/tests/in/{}.wgsl", root, name)).unwrap();
+    let golden = std::fs::read_to_string(format!("{}/tests/out/msl/{}.msl", root, name)).unwrap();
+    assert_eq!(translate(&source), normalize(&golden));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn golden() {
+    for name in &["quad", "collatz", "control-flow", "operators", "globals"] {
+        check_golden(name);
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn expressions() {
+    check(
+        "
+        fn f(a: vec4<f32>, b: f32, c: bool, i: i32) -> f32 {
+            let sum = a.x + b * 2.0;
+            let swizzled = a.zyx;
+            let negated = -a;
+            let picked = select(b, sum, c);
+            let converted = f32(i);
+            let len = length(swizzled);
+            let splat = vec2<f32>(b);
+            return sum + picked + converted + len + splat.y + negated.w;
+        }
+        ",
+        &[
+            "float sum = a.x + (b * 2.0);",
+            "metal::float3 swizzled = a.zyx;",
+            "metal::float4 negated = -a;",
+            "float picked = c ? sum : b;",
+            "float converted = static_cast<float>(i);",
+            "float len = metal::length(swizzled);",
+            "metal::float2 splat = metal::float2(b);",
+        ],
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn statements() {
+    check(
+        "
+        var<workgroup> shared_value: u32;
+
+        [[stage(compute), workgroup_size(1)]]
+        fn main([[builtin(local_invocation_index)]] index: u32) {
+            var total: u32 = 0u;
+            loop {
+                if (total >= 10u) {
+                    break;
+                } else {
+                    continue;
+                }
+                continuing {
+                    total = total + 1u;
+                }
+            }
+            switch (i32(index)) {
+                case 0: { total = 1u; }
+                default: { total = 2u; }
+            }
+            workgroupBarrier();
+            shared_value = total;
+        }
+        ",
+        &[
+            "bool loop_init=true;while(true){if(!loop_init){",
+            ">=10u){break;}else{continue;}",
+            "switch(static_cast<int>(index)){case 0:{total=1u;break;}default:{total=2u;}}",
+            "metal::threadgroup_barrier(metal::mem_flags::mem_threadgroup);",
+            "shared_value=",
+        ],
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn compute() {
+    check(
+        "
+        var<workgroup> partial: array<u32, 64>;
+
+        fn total() -> u32 {
+            return partial[0] + partial[63];
+        }
+
+        [[stage(compute), workgroup_size(64)]]
+        fn main(
+            [[builtin(global_invocation_id)]] global_id: vec3<u32>,
+            [[builtin(local_invocation_index)]] index: u32,
+            [[builtin(workgroup_id)]] group_id: vec3<u32>,
+            [[builtin(num_workgroups)]] groups: vec3<u32>,
+        ) {
+            partial[index] = global_id.x + group_id.y * groups.z;
+            workgroupBarrier();
+            if (index == 0u) {
+                partial[0] = total();
+            }
+        }
+        ",
+        &[
+            "metal::uint total(threadgroup type1 const& partial)",
+            "kernel void main1(metal::uint3 global_id[[thread_position_in_grid]],\
+             metal::uint index[[thread_index_in_threadgroup]],\
+             metal::uint3 group_id[[threadgroup_position_in_grid]],\
+             metal::uint3 groups[[threadgroups_per_grid]]){threadgroup type1 partial;",
+            "total(partial)",
+        ],
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn images() {
+    check(
+        "
+        [[group(0), binding(0)]]
+        var image: texture_2d<f32>;
+        [[group(0), binding(1)]]
+        var image_sampler: sampler;
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] uv: vec2<f32>) -> [[location(0)]] vec4<f32> {
+            let sampled = textureSample(image, image_sampler, uv);
+            let loaded = textureLoad(image, vec2<i32>(0), 0);
+            let size = textureDimensions(image);
+            return sampled + loaded + f32(size.x);
+        }
+        ",
+        &[
+            "image.sample(image_sampler,uv)",
+            "image.read(metal::uint2(metal::int2(0)),0)",
+            "image.get_width()",
+        ],
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn depth_images() {
+    check(
+        "
+        [[group(0), binding(0)]]
+        var shadow_array: texture_depth_2d_array;
+        [[group(0), binding(1)]]
+        var shadow_cube: texture_depth_cube;
+        [[group(0), binding(2)]]
+        var shadow_cube_array: texture_depth_cube_array;
+        [[group(0), binding(3)]]
+        var shadow_sampler: sampler_comparison;
+        [[group(0), binding(4)]]
+        var env: texture_cube<f32>;
+        [[group(0), binding(5)]]
+        var env_sampler: sampler;
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] dir: vec3<f32>) -> [[location(0)]] vec4<f32> {
+            let a = textureSampleCompare(shadow_array, shadow_sampler, dir.xy, 1, dir.z);
+            let b = textureSampleCompareLevel(shadow_cube, shadow_sampler, dir, 0.5);
+            let c = textureSampleCompare(shadow_cube_array, shadow_sampler, dir, 2, 0.5);
+            let d = textureSampleGrad(env, env_sampler, dir, dir, dir);
+            return d * (a + b + c);
+        }
+        ",
+        &[
+            "metal::depth2d_array<float, metal::access::sample> shadow_array",
+            "metal::depthcube<float, metal::access::sample> shadow_cube",
+            "metal::depthcube_array<float, metal::access::sample> shadow_cube_array",
+            "shadow_array.sample_compare(shadow_sampler, dir.xy, 1, dir.z)",
+            "shadow_cube.sample_compare(shadow_sampler, dir, 0.5, metal::level(0.0))",
+            "shadow_cube_array.sample_compare(shadow_sampler, dir, 2, 0.5)",
+            "env.sample(env_sampler, dir, metal::gradientcube(dir, dir))",
+        ],
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn image_arguments() {
+    check(
+        "
+        [[group(0), binding(0)]]
+        var src: [[access(read)]] texture_storage_2d<rgba8uint>;
+        [[group(0), binding(1)]]
+        var depth: texture_depth_2d;
+        [[group(0), binding(2)]]
+        var cmp: sampler_comparison;
+
+        fn load_inner(t: [[access(read)]] texture_storage_2d<rgba8uint>) -> vec4<u32> {
+            return textureLoad(t, vec2<i32>(0));
+        }
+        fn load_outer(t: [[access(read)]] texture_storage_2d<rgba8uint>) -> vec4<u32> {
+            return load_inner(t);
+        }
+        fn shadow(t: texture_depth_2d, s: sampler_comparison) -> f32 {
+            return textureSampleCompare(t, s, vec2<f32>(0.5), 0.5);
+        }
+
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(load_outer(src)) * shadow(depth, cmp);
+        }
+        ",
+        &[
+            "load_inner(metal::texture2d<uint, metal::access::read> t)",
+            "load_outer(metal::texture2d<uint, metal::access::read> t1)",
+            "shadow(metal::depth2d<float, metal::access::sample> t2, metal::sampler s)",
+        ],
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn doubles() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        fn scale(x: f64) -> f64 {
+            return x * 2.0f64;
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::FLOAT64,
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    match write_string(&module, &info, &Options::default(), &pipeline_options) {
+        Err(super::Error::CapabilityNotSupported(crate::valid::Capabilities::FLOAT64)) => {}
+        other => panic!("Unexpected result {:?}", other.map(|(msl, _)| msl)),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn struct_member_names() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        struct Vertex {
+            uv0: vec2<f32>;
+            Color: vec4<f32>;
+            color: vec4<f32>;
+            device: f32;
+        };
+        fn area(v: Vertex) -> f32 {
+            return v.uv0.x * v.device;
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    let (msl, info) = write_string(&module, &info, &Options::default(), &pipeline_options).unwrap();
+
+    // valid names are kept, even if they only differ by case or end with a digit
+    assert!(msl.contains("metal::float2 uv0;"));
+    assert!(msl.contains("metal::float4 Color;"));
+    assert!(msl.contains("metal::float4 color;"));
+    assert!(msl.contains("float device1;"));
+    let ty = module.types.find_by_name("Vertex").unwrap();
+    let key = crate::proc::NameKey::StructMember(ty, 3);
+    assert_eq!(info.renamed[&key], "device1");
+    assert_eq!(info.renamed.len(), 1);
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn forced_entry_point_names() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        fn fs_main() -> f32 {
+            return 1.0;
+        }
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(fs_main());
+        }
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {}
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    let mut options = Options::default();
+    options.entry_point_names.insert(
+        (crate::ShaderStage::Fragment, "main".to_string()),
+        "fs_main".to_string(),
+    );
+    let (msl, info) = write_string(&module, &info, &options, &pipeline_options).unwrap();
+
+    // the forced name wins over the function that had it
+    assert!(msl.contains("fragment fs_mainOutput fs_main("));
+    assert!(msl.contains("float fs_main1("));
+    assert_eq!(info.entry_point_names[0].as_ref().unwrap(), "fs_main");
+    // entry points that aren't listed keep being renamed if they're reserved
+    assert_ne!(info.entry_point_names[1].as_ref().unwrap(), "main");
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn invalid_entry_point_names() {
+    use crate::back::EntryPointNameError;
+
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[stage(fragment)]]
+        fn fs_main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+        [[stage(fragment)]]
+        fn other() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(0.0);
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    let check = |forced: &[(&str, &str)], expected: EntryPointNameError| {
+        let mut options = Options::default();
+        for &(name, forced_name) in forced {
+            options.entry_point_names.insert(
+                (crate::ShaderStage::Fragment, name.to_string()),
+                forced_name.to_string(),
+            );
+        }
+        match write_string(&module, &info, &options, &pipeline_options) {
+            Err(Error::EntryPointName(ref error)) if **error == expected => {}
+            other => panic!("unexpected result: {:?}", other.map(|(msl, _)| msl)),
+        }
+    };
+
+    check(
+        &[("fs_main", "1st")],
+        EntryPointNameError::InvalidIdentifier("1st".to_string()),
+    );
+    check(
+        &[("fs_main", "fs main")],
+        EntryPointNameError::InvalidIdentifier("fs main".to_string()),
+    );
+    check(
+        &[("fs_main", "kernel")],
+        EntryPointNameError::ReservedKeyword("kernel".to_string()),
+    );
+    // another entry point of the stage keeps its name
+    check(
+        &[("fs_main", "other")],
+        EntryPointNameError::Collision("other".to_string()),
+    );
+    check(
+        &[("fs_main", "frag"), ("other", "frag")],
+        EntryPointNameError::Collision("frag".to_string()),
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn unvalidated_modules() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            let d = distance(1.0, 2.0);
+        }
+        ",
+    )
+    .unwrap();
+
+    // Drop the second argument of `distance`, which the validator would reject
+    let function = &mut module.entry_points[0].function;
+    let distance = function
+        .expressions
+        .iter()
+        .find_map(|(handle, expr)| match *expr {
+            crate::Expression::Math { .. } => Some(handle),
+            _ => None,
+        })
+        .unwrap();
+    if let crate::Expression::Math { ref mut arg1, .. } = *function.expressions.get_mut(distance) {
+        *arg1 = None;
+    }
+
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::empty(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    match write_string(&module, &info, &Options::default(), &pipeline_options) {
+        Err(Error::InvalidExpression(location)) => match *location {
+            crate::diagnostic::IrLocation::EntryPoint {
+                stage: crate::ShaderStage::Compute,
+                ref name,
+                expression: Some(handle),
+            } if name == "main" && handle == distance => {}
+            ref other => panic!("Unexpected location {:?}", other),
+        },
+        other => panic!("Unexpected result {:?}", other.map(|(source, _)| source)),
+    }
+
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]]
+        var image: [[access(write)]] texture_storage_2d<rgba8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            let size = textureDimensions(image);
+        }
+        ",
+    )
+    .unwrap();
+
+    // Drop the access of the image, which the validator would reject
+    let (_, image) = module.global_variables.iter_mut().next().unwrap();
+    image.storage_access = crate::StorageAccess::empty();
+    let image_ty = image.ty;
+
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::empty(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    match write_string(&module, &info, &Options::default(), &pipeline_options) {
+        Err(Error::InvalidStorageAccess(ty)) if ty == image_ty => {}
+        other => panic!("Unexpected result {:?}", other.map(|(source, _)| source)),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn selected_entry_point() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]]
+        var tex: texture_2d<f32>;
+        [[group(0), binding(1)]]
+        var samp: sampler;
+        fn shade(uv: vec2<f32>) -> vec4<f32> {
+            return textureSample(tex, samp, uv);
+        }
+        fn scale(position: vec4<f32>) -> vec4<f32> {
+            return position * 2.0;
+        }
+        [[stage(vertex)]]
+        fn vs_main([[location(0)]] position: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return scale(position);
+        }
+        [[stage(fragment)]]
+        fn fs_main([[location(0)]] uv: vec2<f32>) -> [[location(0)]] vec4<f32> {
+            return shade(uv);
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let mut pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: Some((crate::ShaderStage::Vertex, "vs_main".to_string())),
+    };
+    // the resources of the fragment stage aren't mapped
+    let options = Options {
+        fake_missing_bindings: false,
+        ..Options::default()
+    };
+    let (msl, translation) = write_string(&module, &info, &options, &pipeline_options).unwrap();
+
+    assert!(msl.contains("vertex vs_mainOutput vs_main("));
+    assert!(msl.contains("scale("));
+    assert!(!msl.contains("fs_main"));
+    assert!(!msl.contains("shade("));
+    assert_eq!(
+        translation.entry_point_names[0].as_ref().unwrap(),
+        "vs_main"
+    );
+    assert_eq!(
+        translation.entry_point_names[1],
+        Err(super::EntryPointError::NotSelected)
+    );
+
+    pipeline_options.entry_point = Some((crate::ShaderStage::Fragment, "vs_main".to_string()));
+    match write_string(&module, &info, &options, &pipeline_options) {
+        Err(Error::EntryPointNotFound(crate::ShaderStage::Fragment, ref name))
+            if name == "vs_main" => {}
+        other => panic!("Unexpected result {:?}", other.map(|(source, _)| source)),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn sized_buffers() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[block]]
+        struct Params {
+            count: u32;
+        };
+        [[block]]
+        struct Data {
+            values: array<f32>;
+        };
+        [[group(0), binding(0)]]
+        var<uniform> params: Params;
+        [[group(0), binding(1)]]
+        var<storage> data: [[access(read_write)]] Data;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            data.values[0] = f32(arrayLength(&data.values) + params.count);
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let pipeline_options = PipelineOptions {
+        allow_point_size: true,
+        entry_point: None,
+    };
+    let mut options = Options::default();
+    options.per_stage_map.cs.sizes_buffer = Some(7);
+    let (msl, translation) = write_string(&module, &info, &options, &pipeline_options).unwrap();
+
+    // Only the runtime-sized buffer has a size, taken from the extra argument
+    let data = module
+        .global_variables
+        .iter()
+        .find(|&(_, var)| var.name.as_deref() == Some("data"))
+        .unwrap()
+        .0;
+    assert_eq!(translation.sized_buffers, vec![data]);
+    assert!(msl.contains("constant _mslBufferSizes& _buffer_sizes [[buffer(7)]]"));
+    assert!(msl.contains(&format!("_buffer_sizes.size{}", data.index())));
+
+    // The entry point can't be translated without the slot
+    for binding in 0..2 {
+        options.per_stage_map.cs.resources.insert(
+            crate::ResourceBinding { group: 0, binding },
+            super::BindTarget {
+                buffer: Some(binding as u8),
+                ..super::BindTarget::default()
+            },
+        );
+    }
+    options.per_stage_map.cs.sizes_buffer = None;
+    options.fake_missing_bindings = false;
+    let (_, translation) = write_string(&module, &info, &options, &pipeline_options).unwrap();
+    match translation.entry_point_names[0] {
+        Err(super::EntryPointError::MissingSizesBuffer) => {}
+        ref other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn storage_image_access() {
+    let write = |source: &str, entry_point: &str, lang_version| {
+        let module = crate::front::wgsl::parse_str(source).unwrap();
+        let info = crate::valid::Validator::new(
+            crate::valid::ValidationFlags::all(),
+            crate::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+        let options = Options {
+            lang_version,
+            ..Options::default()
+        };
+        let pipeline_options = PipelineOptions {
+            allow_point_size: true,
+            entry_point: Some((crate::ShaderStage::Compute, entry_point.to_string())),
+        };
+        write_string(&module, &info, &options, &pipeline_options).map(|(msl, _)| msl)
+    };
+    let source = "
+        [[group(0), binding(0)]]
+        var loaded: [[access(read_write)]] texture_storage_2d<r32float>;
+        [[group(0), binding(1)]]
+        var stored: [[access(read_write)]] texture_storage_2d<r32float>;
+
+        fn load(t: [[access(read_write)]] texture_storage_2d<r32float>) -> vec4<f32> {
+            return textureLoad(t, vec2<i32>(0));
+        }
+
+        [[stage(compute), workgroup_size(1)]]
+        fn reader() {
+            textureStore(stored, vec2<i32>(0), load(loaded));
+        }
+        [[stage(compute), workgroup_size(1)]]
+        fn writer() {
+            textureStore(stored, vec2<i32>(1), textureLoad(stored, vec2<i32>(0)));
+        }
+    ";
+
+    // only the usages of the selected entry point count, and the access of
+    // the arguments follows the images passed to them
+    let msl = normalize(&write(source, "reader", (1, 1)).unwrap());
+    for snippet in &[
+        "load(metal::texture2d<float, metal::access::read> t)",
+        "metal::texture2d<float, metal::access::read> loaded",
+        "metal::texture2d<float, metal::access::write> stored",
+    ] {
+        let snippet = normalize(snippet);
+        assert!(
+            msl.contains(&snippet),
+            "`{}` not found in:\n{}",
+            snippet,
+            msl
+        );
+    }
+    let msl = normalize(&write(source, "writer", (1, 2)).unwrap());
+    let snippet = normalize("metal::texture2d<float, metal::access::read_write> stored");
+    assert!(
+        msl.contains(&snippet),
+        "`{}` not found in:\n{}",
+        snippet,
+        msl
+    );
+
+    match write(source, "writer", (1, 1)) {
+        Err(Error::UnsupportedReadWriteStorageImage {
+            format: crate::StorageFormat::R32Float,
+            lang_version: (1, 1),
+        }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let source = "
+        [[group(0), binding(0)]]
+        var image: [[access(read_write)]] texture_storage_2d<rgba8snorm>;
+
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            textureStore(image, vec2<i32>(1), textureLoad(image, vec2<i32>(0)));
+        }
+    ";
+    match write(source, "main", (1, 2)) {
+        Err(Error::UnsupportedReadWriteStorageFormat(crate::StorageFormat::Rgba8Snorm)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -1,6 +1,8 @@
 //! Implementations for `BlockContext` methods.
 
 use super::{
+    capabilities,
+    helpers::{buffer_alignment, is_dynamically_uniform},
     index::{BoundsCheckResult, ExpressionPointer},
     make_local, Block, BlockContext, Dimension, Error, Instruction, LocalType, LookupType,
     LoopContext, ResultMember, Writer, WriterFlags,
@@ -197,6 +199,17 @@ impl<'w> BlockContext<'w> {
     fn is_intermediate(&self, expr_handle: Handle<crate::Expression>) -> bool {
         match self.ir_function.expressions[expr_handle] {
             crate::Expression::GlobalVariable(_) | crate::Expression::LocalVariable(_) => true,
+            // Physical pointers are values, but accessing through them
+            // still needs an access chain.
+            crate::Expression::PointerCast { .. } | crate::Expression::Load { .. } => {
+                match *self.fun_info[expr_handle]
+                    .ty
+                    .inner_with(&self.ir_module.types)
+                {
+                    crate::TypeInner::Pointer { .. } => true,
+                    _ => false,
+                }
+            }
             crate::Expression::FunctionArgument(index) => {
                 let arg = &self.ir_function.arguments[index as usize];
                 match self.ir_module.types[arg.ty].inner {
@@ -213,6 +226,31 @@ impl<'w> BlockContext<'w> {
         }
     }
 
+    /// Returns the alignment that loads and stores through `pointer` must
+    /// declare, if it's a pointer in the `PhysicalStorageBuffer` class.
+    fn physical_alignment(&self, pointer: Handle<crate::Expression>) -> Option<Word> {
+        let types = &self.ir_module.types;
+        match *self.fun_info[pointer].ty.inner_with(types) {
+            crate::TypeInner::Pointer {
+                base,
+                class: crate::StorageClass::PhysicalStorageBuffer,
+            } => Some(buffer_alignment(&types[base].inner, types)),
+            crate::TypeInner::ValuePointer {
+                size,
+                kind,
+                width,
+                class: crate::StorageClass::PhysicalStorageBuffer,
+            } => {
+                let inner = match size {
+                    Some(size) => crate::TypeInner::Vector { size, kind, width },
+                    None => crate::TypeInner::Scalar { kind, width },
+                };
+                Some(buffer_alignment(&inner, types))
+            }
+            _ => None,
+        }
+    }
+
     /// Cache an expression for a value.
     pub(super) fn cache_expression_value(
         &mut self,
@@ -220,8 +258,37 @@ impl<'w> BlockContext<'w> {
         block: &mut Block,
     ) -> Result<(), Error> {
         let result_type_id = self.get_expression_type_id(&self.fun_info[expr_handle].ty)?;
+        self.writer.check(capabilities::expression(
+            expr_handle,
+            self.ir_function,
+            self.fun_info,
+            self.ir_module,
+        ))?;
 
         let id = match self.ir_function.expressions[expr_handle] {
+            crate::Expression::Access { base, index } if self.is_binding_array(base) => {
+                let index_id = self.cached[index];
+                let non_uniform = !is_dynamically_uniform(self.ir_module, self.ir_function, index);
+                self.write_binding_array_access(
+                    result_type_id,
+                    expr_handle,
+                    base,
+                    index_id,
+                    non_uniform,
+                    block,
+                )?
+            }
+            crate::Expression::AccessIndex { base, index } if self.is_binding_array(base) => {
+                let index_id = self.get_index_constant(index)?;
+                self.write_binding_array_access(
+                    result_type_id,
+                    expr_handle,
+                    base,
+                    index_id,
+                    false,
+                    block,
+                )?
+            }
             crate::Expression::Access { base, index: _ } if self.is_intermediate(base) => {
                 // See `is_intermediate`; we'll handle this later in
                 // `write_expression_pointer`.
@@ -614,6 +681,18 @@ impl<'w> BlockContext<'w> {
                         log::error!("unimplemented math function {:?}", fun);
                         return Err(Error::FeatureNotImplemented("math function"));
                     }
+                    // data packing
+                    Mf::Pack4x8snorm => MathOp::Ext(spirv::GLOp::PackSnorm4x8),
+                    Mf::Pack4x8unorm => MathOp::Ext(spirv::GLOp::PackUnorm4x8),
+                    Mf::Pack2x16snorm => MathOp::Ext(spirv::GLOp::PackSnorm2x16),
+                    Mf::Pack2x16unorm => MathOp::Ext(spirv::GLOp::PackUnorm2x16),
+                    Mf::Pack2x16float => MathOp::Ext(spirv::GLOp::PackHalf2x16),
+                    // data unpacking
+                    Mf::Unpack4x8snorm => MathOp::Ext(spirv::GLOp::UnpackSnorm4x8),
+                    Mf::Unpack4x8unorm => MathOp::Ext(spirv::GLOp::UnpackUnorm4x8),
+                    Mf::Unpack2x16snorm => MathOp::Ext(spirv::GLOp::UnpackSnorm2x16),
+                    Mf::Unpack2x16unorm => MathOp::Ext(spirv::GLOp::UnpackUnorm2x16),
+                    Mf::Unpack2x16float => MathOp::Ext(spirv::GLOp::UnpackHalf2x16),
                 };
 
                 block.body.push(match math_op {
@@ -630,12 +709,14 @@ impl<'w> BlockContext<'w> {
             }
             crate::Expression::LocalVariable(variable) => self.function.variables[&variable].id,
             crate::Expression::Load { pointer } => {
+                let alignment = self.physical_alignment(pointer);
                 match self.write_expression_pointer(pointer, block)? {
                     ExpressionPointer::Ready { pointer_id } => {
                         let id = self.gen_id();
-                        block
-                            .body
-                            .push(Instruction::load(result_type_id, id, pointer_id, None));
+                        block.body.push(
+                            Instruction::load(result_type_id, id, pointer_id, None)
+                                .with_alignment(alignment),
+                        );
                         id
                     }
                     ExpressionPointer::Conditional { condition, access } => {
@@ -648,12 +729,10 @@ impl<'w> BlockContext<'w> {
                                 let pointer_id = access.result_id.unwrap();
                                 let value_id = id_gen.next();
                                 block.body.push(access);
-                                block.body.push(Instruction::load(
-                                    result_type_id,
-                                    value_id,
-                                    pointer_id,
-                                    None,
-                                ));
+                                block.body.push(
+                                    Instruction::load(result_type_id, value_id, pointer_id, None)
+                                        .with_alignment(alignment),
+                                );
                                 value_id
                             },
                         )
@@ -661,6 +740,29 @@ impl<'w> BlockContext<'w> {
                 }
             }
             crate::Expression::FunctionArgument(index) => self.function.parameter_id(index),
+            crate::Expression::PointerCast { expr, ty } => {
+                let op = match (
+                    self.fun_info[expr].ty.inner_with(&self.ir_module.types),
+                    &self.ir_module.types[ty].inner,
+                ) {
+                    (&crate::TypeInner::Scalar { .. }, &crate::TypeInner::Pointer { .. }) => {
+                        spirv::Op::ConvertUToPtr
+                    }
+                    (&crate::TypeInner::Pointer { .. }, &crate::TypeInner::Scalar { .. }) => {
+                        spirv::Op::ConvertPtrToU
+                    }
+                    // Vector addresses are reinterpreted.
+                    _ => spirv::Op::Bitcast,
+                };
+                let id = self.gen_id();
+                block.body.push(Instruction::unary(
+                    op,
+                    result_type_id,
+                    id,
+                    self.cached[expr],
+                ));
+                id
+            }
             crate::Expression::Call(_function) => self.writer.lookup_function_call[&expr_handle],
             crate::Expression::As {
                 expr,
@@ -715,7 +817,7 @@ impl<'w> BlockContext<'w> {
                 array_index,
                 index,
             } => {
-                let image_id = self.get_image_id(image);
+                let image_id = self.get_image_id(image)?;
                 let coordinate_id =
                     self.write_texture_coordinates(coordinate, array_index, block)?;
 
@@ -728,7 +830,7 @@ impl<'w> BlockContext<'w> {
                         ..
                     } => Instruction::image_read(result_type_id, id, image_id, coordinate_id),
                     crate::TypeInner::Image {
-                        class: crate::ImageClass::Depth,
+                        class: crate::ImageClass::Depth { .. },
                         ..
                     } => {
                         // Vulkan doesn't know about our `Depth` class, and it returns `vec4<f32>`,
@@ -752,6 +854,10 @@ impl<'w> BlockContext<'w> {
                         crate::TypeInner::Image {
                             class: crate::ImageClass::Sampled { multi: true, .. },
                             ..
+                        }
+                        | crate::TypeInner::Image {
+                            class: crate::ImageClass::Depth { multi: true },
+                            ..
                         } => spirv::ImageOperands::SAMPLE,
                         _ => spirv::ImageOperands::LOD,
                     };
@@ -777,6 +883,7 @@ impl<'w> BlockContext<'w> {
             crate::Expression::ImageSample {
                 image,
                 sampler,
+                gather,
                 coordinate,
                 array_index,
                 offset,
@@ -785,15 +892,18 @@ impl<'w> BlockContext<'w> {
             } => {
                 use super::instructions::SampleLod;
                 // image
-                let image_id = self.get_image_id(image);
-                let image_type = self.fun_info[image].ty.handle().unwrap();
+                let image_id = self.get_image_id(image)?;
+                let image_type = self.fun_info[image]
+                    .ty
+                    .handle()
+                    .ok_or(Error::Validation("image type"))?;
                 // Vulkan doesn't know about our `Depth` class, and it returns `vec4<f32>`,
                 // so we need to grab the first component out of it.
                 let needs_sub_access = match self.ir_module.types[image_type].inner {
                     crate::TypeInner::Image {
-                        class: crate::ImageClass::Depth,
+                        class: crate::ImageClass::Depth { .. },
                         ..
-                    } => depth_ref.is_none(),
+                    } => depth_ref.is_none() && gather.is_none(),
                     _ => false,
                 };
                 let sample_result_type_id = if needs_sub_access {
@@ -812,7 +922,7 @@ impl<'w> BlockContext<'w> {
                 let sampled_image_type_id =
                     self.get_type_id(LookupType::Local(LocalType::SampledImage { image_type_id }))?;
 
-                let sampler_id = self.get_image_id(sampler);
+                let sampler_id = self.get_image_id(sampler)?;
                 let coordinate_id =
                     self.write_texture_coordinates(coordinate, array_index, block)?;
 
@@ -829,8 +939,25 @@ impl<'w> BlockContext<'w> {
                 let mut mask = spirv::ImageOperands::empty();
                 mask.set(spirv::ImageOperands::CONST_OFFSET, offset.is_some());
 
-                let mut main_instruction = match level {
-                    crate::SampleLevel::Zero => {
+                let mut main_instruction = match (level, gather) {
+                    (_, Some(component)) => {
+                        let component_id = self
+                            .writer
+                            .get_constant_scalar(crate::ScalarValue::Uint(component as u64), 4)?;
+                        let mut inst = Instruction::image_gather(
+                            sample_result_type_id,
+                            id,
+                            sampled_image_id,
+                            coordinate_id,
+                            component_id,
+                            depth_id,
+                        );
+                        if !mask.is_empty() {
+                            inst.add_operand(mask.bits());
+                        }
+                        inst
+                    }
+                    (crate::SampleLevel::Zero, None) => {
                         let mut inst = Instruction::image_sample(
                             sample_result_type_id,
                             id,
@@ -850,7 +977,7 @@ impl<'w> BlockContext<'w> {
 
                         inst
                     }
-                    crate::SampleLevel::Auto => {
+                    (crate::SampleLevel::Auto, None) => {
                         let mut inst = Instruction::image_sample(
                             sample_result_type_id,
                             id,
@@ -864,7 +991,7 @@ impl<'w> BlockContext<'w> {
                         }
                         inst
                     }
-                    crate::SampleLevel::Exact(lod_handle) => {
+                    (crate::SampleLevel::Exact(lod_handle), None) => {
                         let mut inst = Instruction::image_sample(
                             sample_result_type_id,
                             id,
@@ -881,7 +1008,7 @@ impl<'w> BlockContext<'w> {
 
                         inst
                     }
-                    crate::SampleLevel::Bias(bias_handle) => {
+                    (crate::SampleLevel::Bias(bias_handle), None) => {
                         let mut inst = Instruction::image_sample(
                             sample_result_type_id,
                             id,
@@ -898,7 +1025,7 @@ impl<'w> BlockContext<'w> {
 
                         inst
                     }
-                    crate::SampleLevel::Gradient { x, y } => {
+                    (crate::SampleLevel::Gradient { x, y }, None) => {
                         let mut inst = Instruction::image_sample(
                             sample_result_type_id,
                             id,
@@ -987,15 +1114,21 @@ impl<'w> BlockContext<'w> {
                 block.body.push(instruction);
                 id
             }
-            crate::Expression::Derivative { axis, expr } => {
-                use crate::DerivativeAxis as Da;
+            crate::Expression::Derivative { axis, ctrl, expr } => {
+                use crate::{DerivativeAxis as Axis, DerivativeControl as Ctrl};
 
                 let id = self.gen_id();
                 let expr_id = self.cached[expr];
-                let op = match axis {
-                    Da::X => spirv::Op::DPdx,
-                    Da::Y => spirv::Op::DPdy,
-                    Da::Width => spirv::Op::Fwidth,
+                let op = match (axis, ctrl) {
+                    (Axis::X, Ctrl::Coarse) => spirv::Op::DPdxCoarse,
+                    (Axis::X, Ctrl::Fine) => spirv::Op::DPdxFine,
+                    (Axis::X, Ctrl::None) => spirv::Op::DPdx,
+                    (Axis::Y, Ctrl::Coarse) => spirv::Op::DPdyCoarse,
+                    (Axis::Y, Ctrl::Fine) => spirv::Op::DPdyFine,
+                    (Axis::Y, Ctrl::None) => spirv::Op::DPdy,
+                    (Axis::Width, Ctrl::Coarse) => spirv::Op::FwidthCoarse,
+                    (Axis::Width, Ctrl::Fine) => spirv::Op::FwidthFine,
+                    (Axis::Width, Ctrl::None) => spirv::Op::Fwidth,
                 };
                 block
                     .body
@@ -1005,8 +1138,11 @@ impl<'w> BlockContext<'w> {
             crate::Expression::ImageQuery { image, query } => {
                 use crate::{ImageClass as Ic, ImageDimension as Id, ImageQuery as Iq};
 
-                let image_id = self.get_image_id(image);
-                let image_type = self.fun_info[image].ty.handle().unwrap();
+                let image_id = self.get_image_id(image)?;
+                let image_type = self.fun_info[image]
+                    .ty
+                    .handle()
+                    .ok_or(Error::Validation("image type"))?;
                 let (dim, arrayed, class) = match self.ir_module.types[image_type].inner {
                     crate::TypeInner::Image {
                         dim,
@@ -1018,8 +1154,6 @@ impl<'w> BlockContext<'w> {
                     }
                 };
 
-                self.writer.check(&[spirv::Capability::ImageQuery])?;
-
                 match query {
                     Iq::Size { level } => {
                         let dim_coords = match dim {
@@ -1138,6 +1272,35 @@ impl<'w> BlockContext<'w> {
                         ));
                         id
                     }
+                    Iq::Lod {
+                        sampler,
+                        coordinate,
+                    } => {
+                        let image_type_id = self.get_type_id(LookupType::Handle(image_type))?;
+                        let sampled_image_type_id =
+                            self.get_type_id(LookupType::Local(LocalType::SampledImage {
+                                image_type_id,
+                            }))?;
+                        let sampler_id = self.get_image_id(sampler)?;
+                        let sampled_image_id = self.gen_id();
+                        block.body.push(Instruction::sampled_image(
+                            sampled_image_type_id,
+                            sampled_image_id,
+                            image_id,
+                            sampler_id,
+                        ));
+
+                        let id = self.gen_id();
+                        let mut inst = Instruction::image_query(
+                            spirv::Op::ImageQueryLod,
+                            result_type_id,
+                            id,
+                            sampled_image_id,
+                        );
+                        inst.add_operand(self.cached[coordinate]);
+                        block.body.push(inst);
+                        id
+                    }
                 }
             }
             crate::Expression::Relational { fun, argument } => {
@@ -1247,6 +1410,9 @@ impl<'w> BlockContext<'w> {
                 crate::Expression::FunctionArgument(index) => {
                     break self.function.parameter_id(index);
                 }
+                crate::Expression::PointerCast { .. } | crate::Expression::Load { .. } => {
+                    break self.cached[expr_handle];
+                }
                 ref other => unimplemented!("Unexpected pointer expression {:?}", other),
             }
         };
@@ -1277,25 +1443,99 @@ impl<'w> BlockContext<'w> {
         Ok(pointer)
     }
 
-    fn get_image_id(&mut self, expr_handle: Handle<crate::Expression>) -> Word {
+    /// Return `true` if `expr_handle` evaluates to a [`BindingArray`].
+    ///
+    /// [`BindingArray`]: crate::TypeInner::BindingArray
+    fn is_binding_array(&self, expr_handle: Handle<crate::Expression>) -> bool {
+        match *self.fun_info[expr_handle]
+            .ty
+            .inner_with(&self.ir_module.types)
+        {
+            crate::TypeInner::BindingArray { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Load a single image or sampler out of a binding array global.
+    ///
+    /// Binding arrays are never loaded as a whole, so the element is reached
+    /// with an `OpAccessChain` into the global, followed by an `OpLoad`. When
+    /// the index isn't dynamically uniform, both are decorated with `NonUniform`.
+    fn write_binding_array_access(
+        &mut self,
+        result_type_id: Word,
+        expr_handle: Handle<crate::Expression>,
+        base: Handle<crate::Expression>,
+        index_id: Word,
+        non_uniform: bool,
+        block: &mut Block,
+    ) -> Result<Word, Error> {
+        let global_id = match self.ir_function.expressions[base] {
+            crate::Expression::GlobalVariable(handle) => {
+                self.writer.global_variables[handle.index()].id
+            }
+            _ => {
+                return Err(Error::Validation(
+                    "binding arrays may only be accessed through globals",
+                ))
+            }
+        };
+        let element_ty = match self.fun_info[expr_handle].ty {
+            TypeResolution::Handle(ty) => ty,
+            TypeResolution::Value(_) => {
+                return Err(Error::Validation("binding array elements must be types"))
+            }
+        };
+        let pointer_type_id = self.writer.get_pointer_id(
+            &self.ir_module.types,
+            element_ty,
+            spirv::StorageClass::UniformConstant,
+        )?;
+
+        let pointer_id = self.gen_id();
+        block.body.push(Instruction::access_chain(
+            pointer_type_id,
+            pointer_id,
+            global_id,
+            &[index_id],
+        ));
+        let id = self.gen_id();
+        block
+            .body
+            .push(Instruction::load(result_type_id, id, pointer_id, None));
+
+        if non_uniform {
+            for &decorated_id in [pointer_id, id].iter() {
+                self.writer.annotations.push(Instruction::decorate(
+                    decorated_id,
+                    spirv::Decoration::NonUniform,
+                    &[],
+                ));
+            }
+        }
+
+        Ok(id)
+    }
+
+    fn get_image_id(&mut self, expr_handle: Handle<crate::Expression>) -> Result<Word, Error> {
         let id = match self.ir_function.expressions[expr_handle] {
             crate::Expression::GlobalVariable(handle) => {
                 self.writer.global_variables[handle.index()].handle_id
             }
+            crate::Expression::Access { .. } | crate::Expression::AccessIndex { .. } => {
+                self.cached[expr_handle]
+            }
             crate::Expression::FunctionArgument(i) => {
                 self.function.parameters[i as usize].handle_id
             }
-            ref other => unreachable!("Unexpected image expression {:?}", other),
+            _ => return Err(Error::Validation("image expression")),
         };
 
         if id == 0 {
-            unreachable!(
-                "Image expression {:?} doesn't have a handle ID",
-                expr_handle
-            );
+            return Err(Error::Validation("image expression without a handle"));
         }
 
-        id
+        Ok(id)
     }
 
     pub(super) fn write_block(
@@ -1392,6 +1632,13 @@ impl<'w> BlockContext<'w> {
                         Instruction::switch(selector_id, default_id, &raw_cases),
                     );
 
+                    // `break` inside the switch exits the switch, while `continue`
+                    // still refers to the enclosing loop, if any.
+                    let inner_context = LoopContext {
+                        break_id: Some(merge_id),
+                        ..loop_context
+                    };
+
                     for (i, (case, raw_case)) in cases.iter().zip(raw_cases.iter()).enumerate() {
                         let case_finish_id = if case.fall_through {
                             match raw_cases.get(i + 1) {
@@ -1405,11 +1652,11 @@ impl<'w> BlockContext<'w> {
                             raw_case.label_id,
                             &case.body,
                             Some(case_finish_id),
-                            LoopContext::default(),
+                            inner_context,
                         )?;
                     }
 
-                    self.write_block(default_id, default, Some(merge_id), LoopContext::default())?;
+                    self.write_block(default_id, default, Some(merge_id), inner_context)?;
 
                     block = Block::new(merge_id);
                 }
@@ -1431,7 +1678,7 @@ impl<'w> BlockContext<'w> {
                     block.body.push(Instruction::loop_merge(
                         merge_id,
                         continuing_id,
-                        spirv::SelectionControl::NONE,
+                        spirv::LoopControl::NONE,
                     ));
                     self.function.consume(block, Instruction::branch(body_id));
 
@@ -1449,10 +1696,8 @@ impl<'w> BlockContext<'w> {
                         continuing_id,
                         continuing,
                         Some(preamble_id),
-                        LoopContext {
-                            continuing_id: None,
-                            break_id: Some(merge_id),
-                        },
+                        // The continue construct can only exit through the back-edge.
+                        LoopContext::default(),
                     )?;
 
                     block = Block::new(merge_id);
@@ -1522,11 +1767,13 @@ impl<'w> BlockContext<'w> {
                 }
                 crate::Statement::Store { pointer, value } => {
                     let value_id = self.cached[value];
+                    let alignment = self.physical_alignment(pointer);
                     match self.write_expression_pointer(pointer, &mut block)? {
                         ExpressionPointer::Ready { pointer_id } => {
-                            block
-                                .body
-                                .push(Instruction::store(pointer_id, value_id, None));
+                            block.body.push(
+                                Instruction::store(pointer_id, value_id, None)
+                                    .with_alignment(alignment),
+                            );
                         }
                         ExpressionPointer::Conditional { condition, access } => {
                             let merge_block = self.gen_id();
@@ -1549,9 +1796,10 @@ impl<'w> BlockContext<'w> {
                             // The in-bounds path. Perform the access and the store.
                             let pointer_id = access.result_id.unwrap();
                             block.body.push(access);
-                            block
-                                .body
-                                .push(Instruction::store(pointer_id, value_id, None));
+                            block.body.push(
+                                Instruction::store(pointer_id, value_id, None)
+                                    .with_alignment(alignment),
+                            );
 
                             // Finish the in-bounds block and start the merge block. This
                             // is the block we'll leave current on return.
@@ -1568,7 +1816,7 @@ impl<'w> BlockContext<'w> {
                     array_index,
                     value,
                 } => {
-                    let image_id = self.get_image_id(image);
+                    let image_id = self.get_image_id(image)?;
                     let coordinate_id =
                         self.write_texture_coordinates(coordinate, array_index, &mut block)?;
                     let value_id = self.cached[value];
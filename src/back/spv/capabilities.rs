@@ -0,0 +1,266 @@
+//! Capabilities needed by the parts of a module, and their inference without
+//! translating it.
+//!
+//! The writer checks the capabilities it needs with the tables below, which
+//! [`collect`] looks up for the whole module, so both always agree.
+
+use super::{
+    helpers::{is_dynamically_uniform, map_storage_class},
+    Capability, Error, Options,
+};
+use crate::{
+    arena::Handle,
+    valid::{FunctionInfo, ModuleInfo},
+    FastHashSet,
+};
+
+/// The capabilities that aren't picked when the writer may declare any capability.
+pub(super) const FORBIDDEN_BY_DEFAULT: &[Capability] = &[Capability::Kernel];
+
+/// Pick the capability to declare for needing one of `capabilities`, if none
+/// of them is `declared` yet.
+///
+/// If `forbidden` is given, the first capability not in it is picked.
+/// Otherwise, only the `declared` capabilities are allowed.
+pub(super) fn pick(
+    capabilities: &[Capability],
+    declared: &FastHashSet<Capability>,
+    forbidden: Option<&[Capability]>,
+) -> Result<Option<Capability>, Error> {
+    if capabilities.is_empty() || capabilities.iter().any(|cap| declared.contains(cap)) {
+        return Ok(None);
+    }
+    if let Some(forbidden) = forbidden {
+        // take the first allowed capability, blindly
+        if let Some(&cap) = capabilities.iter().find(|cap| !forbidden.contains(cap)) {
+            return Ok(Some(cap));
+        }
+    }
+    Err(Error::MissingCapabilities(capabilities.to_vec()))
+}
+
+/// The capability declared for a scalar type, if it needs one.
+pub(super) fn scalar(kind: crate::ScalarKind, width: crate::Bytes) -> Option<Capability> {
+    match (kind, width) {
+        (crate::ScalarKind::Sint, 1) | (crate::ScalarKind::Uint, 1) => Some(Capability::Int8),
+        (crate::ScalarKind::Sint, 2) | (crate::ScalarKind::Uint, 2) => Some(Capability::Int16),
+        (crate::ScalarKind::Sint, 8) | (crate::ScalarKind::Uint, 8) => Some(Capability::Int64),
+        (crate::ScalarKind::Float, 2) => Some(Capability::Float16),
+        (crate::ScalarKind::Float, 8) => Some(Capability::Float64),
+        _ => None,
+    }
+}
+
+/// The capabilities of an image type, one of which is needed.
+pub(super) fn image(
+    dim: crate::ImageDimension,
+    arrayed: bool,
+    class: crate::ImageClass,
+) -> &'static [Capability] {
+    let storage = match class {
+        crate::ImageClass::Storage(_) => true,
+        _ => false,
+    };
+    match (dim, arrayed, storage) {
+        (crate::ImageDimension::D1, _, false) => &[Capability::Sampled1D],
+        (crate::ImageDimension::D1, _, true) => &[Capability::Image1D],
+        (crate::ImageDimension::Cube, true, false) => &[Capability::SampledCubeArray],
+        (crate::ImageDimension::Cube, true, true) => &[Capability::ImageCubeArray],
+        _ => &[],
+    }
+}
+
+/// The capabilities of a built-in, one of which is needed.
+pub(super) fn built_in(built_in: crate::BuiltIn) -> &'static [Capability] {
+    match built_in {
+        crate::BuiltIn::ViewIndex => &[Capability::MultiView],
+        crate::BuiltIn::PrimitiveIndex => &[Capability::Geometry],
+        crate::BuiltIn::SampleIndex | crate::BuiltIn::SamplePosition => {
+            &[Capability::SampleRateShading]
+        }
+        _ => &[],
+    }
+}
+
+/// The capabilities of the instructions of an expression, one of which is needed.
+pub(super) fn expression(
+    handle: Handle<crate::Expression>,
+    function: &crate::Function,
+    info: &FunctionInfo,
+    module: &crate::Module,
+) -> &'static [Capability] {
+    use crate::Expression as E;
+
+    match function.expressions[handle] {
+        E::Derivative { ctrl, .. } if ctrl != crate::DerivativeControl::None => {
+            &[Capability::DerivativeControl]
+        }
+        E::ImageQuery { .. } => &[Capability::ImageQuery],
+        E::Access { base, index } => {
+            let is_binding_array = match *info[base].ty.inner_with(&module.types) {
+                crate::TypeInner::BindingArray { .. } => true,
+                _ => false,
+            };
+            if is_binding_array && !is_dynamically_uniform(module, function, index) {
+                &[Capability::ShaderNonUniform]
+            } else {
+                &[]
+            }
+        }
+        _ => &[],
+    }
+}
+
+struct Requirements {
+    capabilities: FastHashSet<Capability>,
+}
+
+impl Requirements {
+    /// Require one of `capabilities`, like the writer does when the
+    /// capabilities aren't restricted.
+    fn require(&mut self, capabilities: &[Capability]) {
+        if let Ok(Some(cap)) = pick(capabilities, &self.capabilities, Some(FORBIDDEN_BY_DEFAULT)) {
+            self.capabilities.insert(cap);
+        }
+    }
+
+    fn type_inner(&mut self, inner: &crate::TypeInner) {
+        use crate::TypeInner as Ti;
+
+        match *inner {
+            Ti::Scalar { kind, width }
+            | Ti::Vector { kind, width, .. }
+            | Ti::ValuePointer { kind, width, .. } => {
+                self.capabilities.extend(scalar(kind, width));
+            }
+            Ti::Matrix { width, .. } => {
+                self.capabilities
+                    .extend(scalar(crate::ScalarKind::Float, width));
+            }
+            Ti::Pointer {
+                class: crate::StorageClass::PhysicalStorageBuffer,
+                ..
+            } => self.require(
+                map_storage_class(crate::StorageClass::PhysicalStorageBuffer)
+                    .required_capabilities(),
+            ),
+            Ti::Image {
+                dim,
+                arrayed,
+                class,
+            } => self.require(image(dim, arrayed, class)),
+            Ti::BindingArray {
+                size: crate::ArraySize::Dynamic,
+                ..
+            } => self.require(&[Capability::RuntimeDescriptorArray]),
+            _ => {}
+        }
+    }
+
+    fn binding(
+        &mut self,
+        binding: Option<&crate::Binding>,
+        ty: Handle<crate::Type>,
+        module: &crate::Module,
+    ) {
+        match binding {
+            Some(&crate::Binding::BuiltIn(bi)) => self.require(built_in(bi)),
+            Some(&crate::Binding::Location { .. }) => {}
+            None => {
+                if let crate::TypeInner::Struct { ref members, .. } = module.types[ty].inner {
+                    for member in members {
+                        self.binding(member.binding.as_ref(), member.ty, module);
+                    }
+                }
+            }
+        }
+    }
+
+    fn function(
+        &mut self,
+        function: &crate::Function,
+        info: &FunctionInfo,
+        module: &crate::Module,
+    ) {
+        for (handle, _) in function.expressions.iter() {
+            self.type_inner(info[handle].ty.inner_with(&module.types));
+            self.require(expression(handle, function, info, module));
+        }
+    }
+}
+
+/// Collect the capabilities the writer would declare for `module`, if it were
+/// allowed to use any of them.
+pub(super) fn collect(
+    module: &crate::Module,
+    info: &ModuleInfo,
+    options: &Options,
+) -> Result<FastHashSet<Capability>, Error> {
+    let mut requirements = Requirements {
+        capabilities: FastHashSet::default(),
+    };
+    requirements.capabilities.insert(Capability::Shader);
+
+    // the selected entry point, and the functions it calls
+    let selection = match options.entry_point {
+        Some((stage, ref name)) => {
+            let index = crate::back::find_entry_point(module, stage, name)
+                .ok_or_else(|| Error::EntryPointNotFound(stage, name.clone()))?;
+            let functions =
+                crate::back::reachable_functions(module, &module.entry_points[index].function);
+            Some((index, functions))
+        }
+        None => None,
+    };
+
+    for (_, ty) in module.types.iter() {
+        requirements.type_inner(&ty.inner);
+    }
+    for (_, constant) in module.constants.iter() {
+        if let crate::ConstantInner::Scalar { width, ref value } = constant.inner {
+            let kind = match *value {
+                crate::ScalarValue::Sint(_) => crate::ScalarKind::Sint,
+                crate::ScalarValue::Uint(_) => crate::ScalarKind::Uint,
+                crate::ScalarValue::Float(_) => crate::ScalarKind::Float,
+                crate::ScalarValue::Bool(_) => crate::ScalarKind::Bool,
+            };
+            requirements.capabilities.extend(scalar(kind, width));
+        }
+    }
+
+    for (handle, var) in module.global_variables.iter() {
+        if let Some((index, _)) = selection {
+            if info.get_entry_point(index)[handle].is_empty() {
+                continue;
+            }
+        }
+        requirements.require(map_storage_class(var.class).required_capabilities());
+    }
+
+    for (handle, function) in module.functions.iter() {
+        if let Some((_, ref functions)) = selection {
+            if !functions.contains(&handle) {
+                continue;
+            }
+        }
+        requirements.function(function, &info[handle], module);
+    }
+
+    for (index, entry_point) in module.entry_points.iter().enumerate() {
+        if let Some((selected, _)) = selection {
+            if index != selected {
+                continue;
+            }
+        }
+        let function = &entry_point.function;
+        requirements.function(function, info.get_entry_point(index), module);
+        for argument in function.arguments.iter() {
+            requirements.binding(argument.binding.as_ref(), argument.ty, module);
+        }
+        if let Some(ref result) = function.result {
+            requirements.binding(result.binding.as_ref(), result.ty, module);
+        }
+    }
+
+    Ok(requirements.capabilities)
+}
@@ -1,4 +1,4 @@
-use crate::{Arena, Handle};
+use crate::{Handle, UniqueArena};
 use spirv::Word;
 
 pub(super) fn bytes_to_words(bytes: &[u8]) -> Vec<Word> {
@@ -20,6 +20,40 @@ pub(super) fn string_to_words(input: &str) -> Vec<Word> {
     words
 }
 
+/// Returns the bits of the half-precision float nearest to `value`.
+///
+/// Rounds to nearest, ties to even, like the hardware conversions do.
+pub(super) fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // infinities stay infinite, NaNs stay quiet NaNs
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // too small for a normal half, produce a subnormal or zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half_ulp = 1 << (shift - 1);
+        let rounded = (mantissa + half_ulp - 1 + ((mantissa >> shift) & 1)) >> shift;
+        return sign | rounded as u16;
+    }
+    // a carry out of the mantissa correctly bumps the exponent
+    let rounded = mantissa + 0xfff + ((mantissa >> 13) & 1);
+    sign | (((exponent as u32) << 10) + (rounded >> 13)) as u16
+}
+
 pub(super) fn map_storage_class(class: crate::StorageClass) -> spirv::StorageClass {
     match class {
         crate::StorageClass::Handle => spirv::StorageClass::UniformConstant,
@@ -29,13 +63,50 @@ pub(super) fn map_storage_class(class: crate::StorageClass) -> spirv::StorageCla
         crate::StorageClass::Uniform => spirv::StorageClass::Uniform,
         crate::StorageClass::WorkGroup => spirv::StorageClass::Workgroup,
         crate::StorageClass::PushConstant => spirv::StorageClass::PushConstant,
+        crate::StorageClass::PhysicalStorageBuffer => spirv::StorageClass::PhysicalStorageBuffer,
+    }
+}
+
+pub(super) fn is_physical_pointer(inner: &crate::TypeInner) -> bool {
+    match *inner {
+        crate::TypeInner::Pointer {
+            class: crate::StorageClass::PhysicalStorageBuffer,
+            ..
+        } => true,
+        _ => false,
+    }
+}
+
+/// Returns the alignment of a value of the given type in buffer memory.
+///
+/// This is what accesses through `PhysicalStorageBuffer` pointers declare
+/// with their `Aligned` memory operand.
+pub(super) fn buffer_alignment(inner: &crate::TypeInner, arena: &UniqueArena<crate::Type>) -> Word {
+    match *inner {
+        crate::TypeInner::Scalar { width, .. } => width as Word,
+        crate::TypeInner::Vector { size, width, .. }
+        | crate::TypeInner::Matrix {
+            rows: size, width, ..
+        } => {
+            let count = if size >= crate::VectorSize::Tri { 4 } else { 2 };
+            count * width as Word
+        }
+        crate::TypeInner::Array { base, .. } => buffer_alignment(&arena[base].inner, arena),
+        crate::TypeInner::Struct { ref members, .. } => members
+            .iter()
+            .map(|member| buffer_alignment(&arena[member.ty].inner, arena))
+            .max()
+            .unwrap_or(1),
+        // Only physical pointers can be stored in buffers.
+        crate::TypeInner::Pointer { .. } => crate::proc::PHYSICAL_POINTER_SPAN,
+        _ => 1,
     }
 }
 
 pub(super) fn contains_builtin(
     binding: Option<&crate::Binding>,
     ty: Handle<crate::Type>,
-    arena: &Arena<crate::Type>,
+    arena: &UniqueArena<crate::Type>,
     built_in: crate::BuiltIn,
 ) -> bool {
     if let Some(&crate::Binding::BuiltIn(bi)) = binding {
@@ -48,3 +119,72 @@ pub(super) fn contains_builtin(
         false // unreachable
     }
 }
+
+#[test]
+fn test_f16_bits() {
+    assert_eq!(f16_bits(0.0), 0x0000);
+    assert_eq!(f16_bits(-0.0), 0x8000);
+    assert_eq!(f16_bits(1.0), 0x3c00);
+    assert_eq!(f16_bits(-2.0), 0xc000);
+    assert_eq!(f16_bits(0.1), 0x2e66);
+    assert_eq!(f16_bits(65504.0), 0x7bff);
+    assert_eq!(f16_bits(1.0e6), 0x7c00);
+    assert_eq!(f16_bits(f32::NEG_INFINITY), 0xfc00);
+    assert_eq!(f16_bits(f32::NAN) & 0x7e00, 0x7e00);
+    // smallest subnormal, and a value rounding to it
+    assert_eq!(f16_bits(5.960_464_5e-8), 0x0001);
+    assert_eq!(f16_bits(4.0e-8), 0x0001);
+    assert_eq!(f16_bits(1.0e-9), 0x0000);
+}
+
+/// Return true if `expr_handle` is known to have the same value in all the
+/// invocations of a draw or a work group.
+///
+/// This is stricter than the uniformity found by the validator, which
+/// considers flat inputs uniform although they differ between primitives.
+pub(super) fn is_dynamically_uniform(
+    module: &crate::Module,
+    function: &crate::Function,
+    expr_handle: Handle<crate::Expression>,
+) -> bool {
+    use crate::Expression as E;
+
+    let uniform = |handle| is_dynamically_uniform(module, function, handle);
+    match function.expressions[expr_handle] {
+        E::Constant(_) => true,
+        // only the compute stage has built-ins shared by the whole group
+        E::FunctionArgument(index) => match function.arguments[index as usize].binding {
+            Some(crate::Binding::BuiltIn(built_in)) => match built_in {
+                crate::BuiltIn::NumWorkGroups
+                | crate::BuiltIn::WorkGroupId
+                | crate::BuiltIn::WorkGroupSize => true,
+                _ => false,
+            },
+            _ => false,
+        },
+        // the contents of other buffers may be written by other invocations
+        E::GlobalVariable(handle) => match module.global_variables[handle].class {
+            crate::StorageClass::Uniform | crate::StorageClass::PushConstant => true,
+            _ => false,
+        },
+        E::Load { pointer: expr }
+        | E::AccessIndex { base: expr, .. }
+        | E::Splat { value: expr, .. }
+        | E::Swizzle { vector: expr, .. }
+        | E::Unary { expr, .. }
+        | E::As { expr, .. }
+        | E::Relational { argument: expr, .. } => uniform(expr),
+        E::Access { base, index } => uniform(base) && uniform(index),
+        E::Binary { left, right, .. } => uniform(left) && uniform(right),
+        E::Select {
+            condition,
+            accept,
+            reject,
+        } => uniform(condition) && uniform(accept) && uniform(reject),
+        E::Compose { ref components, .. } => components.iter().all(|&c| uniform(c)),
+        E::Math {
+            arg, arg1, arg2, ..
+        } => uniform(arg) && arg1.map_or(true, uniform) && arg2.map_or(true, uniform),
+        _ => false,
+    }
+}
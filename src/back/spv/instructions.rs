@@ -233,7 +233,7 @@ impl super::Instruction {
 
         let (depth, multi, sampled) = match image_class {
             crate::ImageClass::Sampled { kind: _, multi } => (false, multi, true),
-            crate::ImageClass::Depth => (true, false, true),
+            crate::ImageClass::Depth { multi } => (true, multi, true),
             crate::ImageClass::Storage(_) => (false, false, false),
         };
         instruction.add_operand(depth as u32);
@@ -334,6 +334,16 @@ impl super::Instruction {
         instruction
     }
 
+    pub(super) fn type_forward_pointer(
+        pointer_id: Word,
+        storage_class: spirv::StorageClass,
+    ) -> Self {
+        let mut instruction = Self::new(Op::TypeForwardPointer);
+        instruction.add_operand(pointer_id);
+        instruction.add_operand(storage_class as u32);
+        instruction
+    }
+
     pub(super) fn type_function(id: Word, return_type_id: Word, parameter_ids: &[Word]) -> Self {
         let mut instruction = Self::new(Op::TypeFunction);
         instruction.set_result(id);
@@ -455,6 +465,17 @@ impl super::Instruction {
         instruction
     }
 
+    /// Adds an `Aligned` memory operand to an `OpLoad` or `OpStore`.
+    ///
+    /// The instruction must not have any other memory operands.
+    pub(super) fn with_alignment(mut self, alignment: Option<Word>) -> Self {
+        if let Some(alignment) = alignment {
+            self.add_operand(spirv::MemoryAccess::ALIGNED.bits());
+            self.add_operand(alignment);
+        }
+        self
+    }
+
     pub(super) fn access_chain(
         result_type_id: Word,
         id: Word,
@@ -579,6 +600,32 @@ impl super::Instruction {
         instruction
     }
 
+    pub(super) fn image_gather(
+        result_type_id: Word,
+        id: Word,
+        sampled_image: Word,
+        coordinates: Word,
+        component: Word,
+        depth_ref: Option<Word>,
+    ) -> Self {
+        let op = match depth_ref {
+            None => Op::ImageGather,
+            Some(_) => Op::ImageDrefGather,
+        };
+
+        let mut instruction = Self::new(op);
+        instruction.set_type(result_type_id);
+        instruction.set_result(id);
+        instruction.add_operand(sampled_image);
+        instruction.add_operand(coordinates);
+        match depth_ref {
+            None => instruction.add_operand(component),
+            Some(dref) => instruction.add_operand(dref),
+        }
+
+        instruction
+    }
+
     pub(super) fn image_fetch(
         result_type_id: Word,
         id: Word,
@@ -786,12 +833,12 @@ impl super::Instruction {
     pub(super) fn loop_merge(
         merge_id: Word,
         continuing_id: Word,
-        selection_control: spirv::SelectionControl,
+        loop_control: spirv::LoopControl,
     ) -> Self {
         let mut instruction = Self::new(Op::LoopMerge);
         instruction.add_operand(merge_id);
         instruction.add_operand(continuing_id);
-        instruction.add_operand(selection_control.bits());
+        instruction.add_operand(loop_control.bits());
         instruction
     }
 
@@ -2,6 +2,7 @@
 !*/
 
 mod block;
+mod capabilities;
 mod helpers;
 mod index;
 mod instructions;
@@ -63,6 +64,29 @@ pub enum Error {
     Validation(&'static str),
     #[error(transparent)]
     Proc(#[from] crate::proc::ProcError),
+    #[error("composite type {0:?} contains a pointer, which is not allowed by logical addressing")]
+    PointerInComposite(Handle<crate::Type>),
+    #[error("entry point {1:?} of the {0:?} stage is not found")]
+    EntryPointNotFound(crate::ShaderStage, String),
+    #[error(transparent)]
+    EntryPointName(#[from] super::EntryPointNameError),
+    #[cfg(feature = "rspirv")]
+    #[error("rspirv is unable to load the module: {0}")]
+    Rspirv(String),
+}
+
+impl Error {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic),
+    /// pointing to the item of the IR that can't be written, if known.
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        use crate::diagnostic::IrLocation;
+
+        let location = match *self {
+            Error::PointerInComposite(handle) => Some(IrLocation::Type(handle)),
+            _ => None,
+        };
+        crate::diagnostic::Diagnostic::from_error("back::spv", self).with_location(location)
+    }
 }
 
 #[derive(Default)]
@@ -122,6 +146,12 @@ struct EntryPointContext {
     results: Vec<ResultMember>,
 }
 
+/// The interface of an entry point being written.
+struct FunctionInterface<'a> {
+    varying_ids: &'a mut Vec<Word>,
+    stage: crate::ShaderStage,
+}
+
 #[derive(Default)]
 struct Function {
     signature: Option<Instruction>,
@@ -384,6 +414,8 @@ pub struct Writer {
     annotations: Vec<Instruction>,
     flags: WriterFlags,
     index_bounds_check_policy: IndexBoundsCheckPolicy,
+    entry_point_names: super::EntryPointNames,
+    entry_point: Option<(crate::ShaderStage, String)>,
     void_type: Word,
     //TODO: convert most of these into vectors, addressable by handle indices
     lookup_type: crate::FastHashMap<LookupType, Word>,
@@ -393,6 +425,9 @@ pub struct Writer {
     constant_ids: Vec<Word>,
     cached_constants: crate::FastHashMap<(crate::ScalarValue, crate::Bytes), Word>,
     global_variables: Vec<GlobalVariable>,
+    /// Physical pointer types declared with `OpTypeForwardPointer`, whose
+    /// `OpTypePointer` has to wait until their base type is written.
+    forward_pointers: Vec<(Handle<crate::Type>, Word, spirv::StorageClass)>,
 
     // Cached expressions are only meaningful within a BlockContext, but we
     // retain the table here between functions to save heap allocations.
@@ -404,15 +439,24 @@ pub struct Writer {
 }
 
 bitflags::bitflags! {
+    #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+    #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
     pub struct WriterFlags: u32 {
         /// Include debug labels for everything.
         const DEBUG = 0x1;
         /// Flip Y coordinate of `BuiltIn::Position` output.
         const ADJUST_COORDINATE_SPACE = 0x2;
+        /// Has no effect: pointers to storage buffers inside of composite
+        /// types are rejected, and composites have to hold
+        /// `StorageClass::PhysicalStorageBuffer` pointers instead.
+        #[deprecated(note = "use `StorageClass::PhysicalStorageBuffer` pointers in composites")]
+        const PHYSICAL_STORAGE_BUFFER = 0x4;
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct Options {
     /// (Major, Minor) target version of the SPIR-V.
     pub lang_version: (u8, u8),
@@ -421,10 +465,64 @@ pub struct Options {
     /// Set of SPIR-V allowed capabilities, if provided.
     // Note: there is a major bug currently associated with deriving the capabilities.
     // We are calling `required_capabilities`, but the semantics of this is broken.
+    #[cfg_attr(
+        feature = "serialize",
+        serde(serialize_with = "serialize_capabilities")
+    )]
+    #[cfg_attr(
+        feature = "deserialize",
+        serde(deserialize_with = "deserialize_capabilities")
+    )]
     pub capabilities: Option<crate::FastHashSet<Capability>>,
     /// How should the generated code handle array, vector, or matrix indices
     /// that are out of range?
     pub index_bounds_check_policy: IndexBoundsCheckPolicy,
+    /// Names to give to some of the entry points instead of their IR names.
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub entry_point_names: super::EntryPointNames,
+    /// Only translate the entry point of this stage and name, together with
+    /// the functions it calls and the globals it uses. If `None`, all the
+    /// entry points are translated.
+    #[cfg_attr(feature = "deserialize", serde(default))]
+    pub entry_point: Option<(crate::ShaderStage, String)>,
+}
+
+/// Capabilities are serialized as their numbers, `spirv` only implements
+/// serde when it's asked to.
+#[cfg(feature = "serialize")]
+fn serialize_capabilities<S: serde::Serializer>(
+    capabilities: &Option<crate::FastHashSet<Capability>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::Serialize as _;
+
+    let words = capabilities.as_ref().map(|capabilities| {
+        let mut words: Vec<u32> = capabilities.iter().map(|&cap| cap as u32).collect();
+        words.sort_unstable();
+        words
+    });
+    words.serialize(serializer)
+}
+
+#[cfg(feature = "deserialize")]
+fn deserialize_capabilities<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<crate::FastHashSet<Capability>>, D::Error> {
+    use num_traits::cast::FromPrimitive as _;
+    use serde::{de::Error as _, Deserialize as _};
+
+    let words = Option::<Vec<u32>>::deserialize(deserializer)?;
+    words
+        .map(|words| {
+            words
+                .into_iter()
+                .map(|word| {
+                    Capability::from_u32(word)
+                        .ok_or_else(|| D::Error::custom(format!("unknown capability {}", word)))
+                })
+                .collect()
+        })
+        .transpose()
 }
 
 impl Default for Options {
@@ -438,6 +536,8 @@ impl Default for Options {
             flags,
             capabilities: None,
             index_bounds_check_policy: super::IndexBoundsCheckPolicy::default(),
+            entry_point_names: super::EntryPointNames::new(),
+            entry_point: None,
         }
     }
 }
@@ -452,3 +552,432 @@ pub fn write_vec(
     w.write(module, info, &mut words)?;
     Ok(words)
 }
+
+/// Return the capabilities needed by `module`, without translating it.
+///
+/// The capabilities of `options` are ignored, so that any of them can be
+/// picked. This tells the build systems which capabilities a target must have
+/// to run the shader.
+pub fn required_capabilities(
+    module: &crate::Module,
+    info: &crate::valid::ModuleInfo,
+    options: &Options,
+) -> Result<Vec<Capability>, Error> {
+    let mut capabilities: Vec<_> = capabilities::collect(module, info, options)?
+        .into_iter()
+        .collect();
+    if module.entry_points.is_empty() {
+        capabilities.push(Capability::Linkage);
+    }
+    capabilities.sort_by_key(|&capability| capability as u32);
+    Ok(capabilities)
+}
+
+/// Return the names that the entry points of `module` get in the SPIR-V
+/// written with `options`, or `None` for the entry points left out because of
+/// [`Options::entry_point`].
+pub fn entry_point_names(module: &crate::Module, options: &Options) -> Vec<Option<String>> {
+    module
+        .entry_points
+        .iter()
+        .map(|ep| {
+            if let Some((stage, ref name)) = options.entry_point {
+                if ep.stage != stage || ep.name != *name {
+                    return None;
+                }
+            }
+            let name = options
+                .entry_point_names
+                .get(&(ep.stage, ep.name.clone()))
+                .unwrap_or(&ep.name);
+            Some(name.clone())
+        })
+        .collect()
+}
+
+/// Write the module as an `rspirv` module, for further processing with it.
+///
+/// This is a convenience wrapper: the module is written into SPIR-V words
+/// with [`write_vec`], which `rspirv` then loads. It costs parsing the whole
+/// binary again on top of writing it.
+#[cfg(feature = "rspirv")]
+pub fn to_rspirv(
+    module: &crate::Module,
+    info: &crate::valid::ModuleInfo,
+    options: &Options,
+) -> Result<rspirv::dr::Module, Error> {
+    let words = write_vec(module, info, options)?;
+    rspirv::dr::load_words(words).map_err(|state| Error::Rspirv(state.to_string()))
+}
+
+#[cfg(all(feature = "rspirv", feature = "spv-in", feature = "wgsl-in"))]
+#[test]
+fn rspirv_round_trip() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let rspirv_module = to_rspirv(&module, &info, &Options::default()).unwrap();
+    assert_eq!(rspirv_module.entry_points.len(), 1);
+
+    let options = crate::front::spv::Options::default();
+    let round_trip = crate::front::spv::from_rspirv(&rspirv_module, &options).unwrap();
+    assert_eq!(round_trip.entry_points.len(), 1);
+    assert_eq!(round_trip.entry_points[0].name, "main");
+}
+
+#[cfg(all(feature = "rspirv", feature = "wgsl-in"))]
+#[test]
+fn entry_point_interface_versions() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[block]]
+        struct Data {
+            values: array<u32>;
+        };
+
+        [[group(0), binding(0)]] var<storage> data: [[access(read_write)]] Data;
+        var<private> unused: u32;
+
+        [[stage(compute), workgroup_size(1)]]
+        fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+            data.values[id.x] = id.x;
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    for minor in 0..=5 {
+        let options = Options {
+            lang_version: (1, minor),
+            ..Options::default()
+        };
+        let rspirv_module = to_rspirv(&module, &info, &options).unwrap();
+        assert_eq!(rspirv_module.header.unwrap().version(), (1, minor));
+
+        // The storage buffer class needs an extension before SPIR-V 1.3
+        let extensions = rspirv_module.extensions.len();
+        assert_eq!(
+            extensions,
+            if minor < 3 { 1 } else { 0 },
+            "SPIR-V 1.{}",
+            minor
+        );
+
+        // `OpEntryPoint` operands: execution model, function, name, then the
+        // interface, which includes the buffer since SPIR-V 1.4
+        let interface = rspirv_module.entry_points[0].operands.len() - 3;
+        assert_eq!(
+            interface,
+            if minor < 4 { 1 } else { 2 },
+            "SPIR-V 1.{}",
+            minor
+        );
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn required_capabilities_inference() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[stage(fragment)]]
+        fn main([[builtin(sample_index)]] sample: u32) -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(f32(sample));
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // The capabilities of the options don't restrict the inference
+    let mut capabilities = crate::FastHashSet::default();
+    capabilities.insert(Capability::Shader);
+    let options = Options {
+        capabilities: Some(capabilities),
+        ..Options::default()
+    };
+    assert_eq!(
+        required_capabilities(&module, &info, &options).unwrap(),
+        [Capability::Shader, Capability::SampleRateShading]
+    );
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn unvalidated_modules() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]]
+        var image: [[access(write)]] texture_storage_2d<rgba8unorm>;
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            textureStore(image, vec2<i32>(0, 0), vec4<f32>(1.0));
+        }
+        ",
+    )
+    .unwrap();
+
+    // Store to a plain number instead of the image, which the validator would reject
+    let constant = module.constants.append(crate::Constant {
+        name: None,
+        specialization: None,
+        inner: crate::ConstantInner::Scalar {
+            width: 4,
+            value: crate::ScalarValue::Float(1.0),
+        },
+    });
+    for (_, expr) in module.entry_points[0].function.expressions.iter_mut() {
+        if let crate::Expression::GlobalVariable(_) = *expr {
+            *expr = crate::Expression::Constant(constant);
+        }
+    }
+
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::empty(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    match write_vec(&module, &info, &Options::default()) {
+        Err(Error::Validation(_)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn scalar_widths() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        enable f16;
+
+        fn wide(a: i64, b: u64) -> u64 {
+            return u64(a - -5000000000i64) + b;
+        }
+
+        fn narrow(a: f16) -> vec2<f16> {
+            return vec2<f16>(a, a * 0.5f16);
+        }
+
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            let w = wide(-1i64, 5u64);
+            let h = narrow(1.5f16);
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::INT64 | crate::valid::Capabilities::FLOAT16,
+    )
+    .validate(&module)
+    .unwrap();
+
+    // rspirv can't load 16-bit literals, so the instructions are walked here
+    let words = write_vec(&module, &info, &Options::default()).unwrap();
+    let mut instructions = Vec::new();
+    let mut rest = &words[5..];
+    while !rest.is_empty() {
+        let count = (rest[0] >> 16) as usize;
+        let opcode = rest[0] & 0xFFFF;
+        instructions.push((opcode, &rest[1..count]));
+        rest = &rest[count..];
+    }
+    let find = |op: spirv::Op| {
+        instructions
+            .iter()
+            .filter(move |&&(opcode, _)| opcode == op as u32)
+            .map(|&(_, operands)| operands)
+    };
+
+    let capabilities: Vec<_> = find(spirv::Op::Capability).map(|ops| ops[0]).collect();
+    assert!(capabilities.contains(&(Capability::Int64 as u32)));
+    assert!(capabilities.contains(&(Capability::Float16 as u32)));
+
+    // `OpTypeInt` operands: result, width, signedness
+    let ints: Vec<_> = find(spirv::Op::TypeInt)
+        .map(|ops| (ops[1], ops[2]))
+        .collect();
+    assert!(ints.contains(&(64, 1)));
+    assert!(ints.contains(&(64, 0)));
+    let half = find(spirv::Op::TypeFloat)
+        .find(|ops| ops[1] == 16)
+        .map(|ops| ops[0])
+        .unwrap();
+
+    // 64-bit literals take two words, low-order first, and 16-bit ones take
+    // one word holding the bits of the half
+    let constants: Vec<_> = find(spirv::Op::Constant).collect();
+    let big = -5000000000i64 as u64;
+    assert!(constants
+        .iter()
+        .any(|ops| ops[2..] == [big as u32, (big >> 32) as u32]));
+    assert!(constants
+        .iter()
+        .any(|ops| ops[0] == half && ops[2..] == [0x3E00]));
+}
+
+#[cfg(all(feature = "rspirv", feature = "wgsl-in"))]
+#[test]
+fn selected_entry_point() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[block]]
+        struct Data {
+            values: array<u32>;
+        };
+
+        [[group(0), binding(0)]] var<storage> data: [[access(read_write)]] Data;
+
+        fn scale(position: vec4<f32>) -> vec4<f32> {
+            return position * 2.0;
+        }
+        fn store(index: u32) {
+            data.values[index] = index;
+        }
+
+        [[stage(vertex)]]
+        fn vs_main([[location(0)]] position: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return scale(position);
+        }
+        [[stage(compute), workgroup_size(1)]]
+        fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+            store(id.x);
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let count_variables = |rspirv_module: &rspirv::dr::Module| {
+        rspirv_module
+            .types_global_values
+            .iter()
+            .filter(|inst| inst.class.opcode == spirv::Op::Variable)
+            .count()
+    };
+
+    let rspirv_module = to_rspirv(&module, &info, &Options::default()).unwrap();
+    assert_eq!(rspirv_module.entry_points.len(), 2);
+    assert_eq!(rspirv_module.functions.len(), 4);
+    assert_eq!(count_variables(&rspirv_module), 4);
+
+    // Only the vertex shader and the function it calls are left, and so are
+    // its input and output: the storage buffer and the invocation id of the
+    // compute shader are gone
+    let options = Options {
+        entry_point: Some((crate::ShaderStage::Vertex, "vs_main".to_string())),
+        ..Options::default()
+    };
+    let rspirv_module = to_rspirv(&module, &info, &options).unwrap();
+    assert_eq!(rspirv_module.entry_points.len(), 1);
+    assert_eq!(rspirv_module.functions.len(), 2);
+    assert_eq!(count_variables(&rspirv_module), 2);
+    assert!(rspirv_module.extensions.is_empty());
+
+    let options = Options {
+        entry_point: Some((crate::ShaderStage::Compute, "vs_main".to_string())),
+        ..Options::default()
+    };
+    match to_rspirv(&module, &info, &options) {
+        Err(Error::EntryPointNotFound(crate::ShaderStage::Compute, ref name))
+            if name == "vs_main" => {}
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+#[cfg(all(feature = "rspirv", feature = "wgsl-in"))]
+#[test]
+fn access_decorations_follow_usage() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[block]]
+        struct Data {
+            values: array<u32>;
+        };
+
+        [[group(0), binding(0)]] var<storage> src: [[access(read_write)]] Data;
+        [[group(0), binding(1)]] var<storage> dst: [[access(read_write)]] Data;
+
+        [[stage(compute), workgroup_size(1)]]
+        fn copy([[builtin(global_invocation_id)]] id: vec3<u32>) {
+            dst.values[id.x] = src.values[id.x];
+        }
+        [[stage(compute), workgroup_size(1)]]
+        fn increment([[builtin(global_invocation_id)]] id: vec3<u32>) {
+            dst.values[id.x] = dst.values[id.x] + 1u;
+        }
+        ",
+    )
+    .unwrap();
+    let info = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+    let access_decorations = |options: &Options| {
+        let rspirv_module = to_rspirv(&module, &info, options).unwrap();
+        rspirv_module
+            .annotations
+            .iter()
+            .filter_map(|inst| match inst.operands[1] {
+                rspirv::dr::Operand::Decoration(spirv::Decoration::NonWritable) => {
+                    Some(spirv::Decoration::NonWritable)
+                }
+                rspirv::dr::Operand::Decoration(spirv::Decoration::NonReadable) => {
+                    Some(spirv::Decoration::NonReadable)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // `dst` is read by `increment`, so only `src` is known to be read-only
+    assert_eq!(
+        access_decorations(&Options::default()),
+        [spirv::Decoration::NonWritable]
+    );
+
+    // `copy` only reads `src` and only writes `dst`
+    let options = Options {
+        entry_point: Some((crate::ShaderStage::Compute, "copy".to_string())),
+        ..Options::default()
+    };
+    assert_eq!(
+        access_decorations(&options),
+        [
+            spirv::Decoration::NonWritable,
+            spirv::Decoration::NonReadable
+        ]
+    );
+}
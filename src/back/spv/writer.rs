@@ -1,14 +1,15 @@
 use super::{
-    helpers::{contains_builtin, map_storage_class},
+    capabilities,
+    helpers::{contains_builtin, f16_bits, is_physical_pointer, map_storage_class},
     make_local, Block, BlockContext, CachedExpressions, EntryPointContext, Error, Function,
-    FunctionArgument, GlobalVariable, IdGenerator, Instruction, LocalType, LocalVariable,
-    LogicalLayout, LookupFunctionType, LookupType, LoopContext, Options, PhysicalLayout,
-    ResultMember, Writer, WriterFlags, BITS_PER_BYTE,
+    FunctionArgument, FunctionInterface, GlobalVariable, IdGenerator, Instruction, LocalType,
+    LocalVariable, LogicalLayout, LookupFunctionType, LookupType, LoopContext, Options,
+    PhysicalLayout, ResultMember, Writer, WriterFlags, BITS_PER_BYTE,
 };
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Handle, UniqueArena},
     proc::TypeResolution,
-    valid::{FunctionInfo, ModuleInfo},
+    valid::{FunctionInfo, GlobalUse, ModuleInfo},
 };
 use spirv::Word;
 use std::collections::hash_map::Entry;
@@ -55,8 +56,7 @@ impl Writer {
             None => {
                 let mut caps = crate::FastHashSet::default();
                 caps.insert(spirv::Capability::Shader);
-                let forbidden: &[_] = &[spirv::Capability::Kernel];
-                (caps, Some(forbidden))
+                (caps, Some(capabilities::FORBIDDEN_BY_DEFAULT))
             }
         };
 
@@ -74,6 +74,8 @@ impl Writer {
             annotations: vec![],
             flags: options.flags,
             index_bounds_check_policy: options.index_bounds_check_policy,
+            entry_point_names: options.entry_point_names.clone(),
+            entry_point: options.entry_point.clone(),
             void_type,
             lookup_type: crate::FastHashMap::default(),
             lookup_function: crate::FastHashMap::default(),
@@ -82,6 +84,7 @@ impl Writer {
             constant_ids: Vec::new(),
             cached_constants: crate::FastHashMap::default(),
             global_variables: Vec::new(),
+            forward_pointers: Vec::new(),
             saved_cached: CachedExpressions::default(),
             gl450_ext_inst_id,
             temp_list: Vec::new(),
@@ -111,6 +114,8 @@ impl Writer {
             // Copied from the old Writer:
             flags: self.flags,
             index_bounds_check_policy: self.index_bounds_check_policy,
+            entry_point_names: take(&mut self.entry_point_names),
+            entry_point: take(&mut self.entry_point),
             capabilities: take(&mut self.capabilities),
             forbidden_caps: take(&mut self.forbidden_caps),
 
@@ -131,6 +136,7 @@ impl Writer {
             constant_ids: take(&mut self.constant_ids).recycle(),
             cached_constants: take(&mut self.cached_constants).recycle(),
             global_variables: take(&mut self.global_variables).recycle(),
+            forward_pointers: take(&mut self.forward_pointers).recycle(),
             saved_cached: take(&mut self.saved_cached).recycle(),
             temp_list: take(&mut self.temp_list).recycle(),
         };
@@ -139,21 +145,12 @@ impl Writer {
     }
 
     pub(super) fn check(&mut self, capabilities: &[spirv::Capability]) -> Result<(), Error> {
-        if capabilities.is_empty()
-            || capabilities
-                .iter()
-                .any(|cap| self.capabilities.contains(cap))
+        if let Some(cap) =
+            capabilities::pick(capabilities, &self.capabilities, self.forbidden_caps)?
         {
-            return Ok(());
-        }
-        if let Some(forbidden) = self.forbidden_caps {
-            // take the first allowed capability, blindly
-            if let Some(&cap) = capabilities.iter().find(|cap| !forbidden.contains(cap)) {
-                self.capabilities.insert(cap);
-                return Ok(());
-            }
+            self.capabilities.insert(cap);
         }
-        Err(Error::MissingCapabilities(capabilities.to_vec()))
+        Ok(())
     }
 
     pub(super) fn get_type_id(&mut self, lookup_ty: LookupType) -> Result<Word, Error> {
@@ -177,13 +174,19 @@ impl Writer {
 
     pub(super) fn get_pointer_id(
         &mut self,
-        arena: &Arena<crate::Type>,
+        arena: &UniqueArena<crate::Type>,
         handle: Handle<crate::Type>,
         class: spirv::StorageClass,
     ) -> Result<Word, Error> {
         let ty_id = self.get_type_id(LookupType::Handle(handle))?;
-        if let crate::TypeInner::Pointer { .. } = arena[handle].inner {
-            return Ok(ty_id);
+        match arena[handle].inner {
+            // Physical pointers are values, which can be stored in variables.
+            crate::TypeInner::Pointer {
+                class: crate::StorageClass::PhysicalStorageBuffer,
+                ..
+            } => {}
+            crate::TypeInner::Pointer { .. } => return Ok(ty_id),
+            _ => {}
         }
         let lookup_type = LookupType::Local(LocalType::Pointer {
             base: handle,
@@ -230,7 +233,7 @@ impl Writer {
         ir_function: &crate::Function,
         info: &FunctionInfo,
         ir_module: &crate::Module,
-        mut varying_ids: Option<&mut Vec<Word>>,
+        mut interface: Option<FunctionInterface>,
     ) -> Result<Word, Error> {
         let mut function = Function::default();
 
@@ -254,6 +257,9 @@ impl Writer {
                 spirv::StorageClass::Function,
                 init_word,
             );
+            if is_physical_pointer(&ir_module.types[variable.ty].inner) {
+                self.decorate(id, spirv::Decoration::AliasedPointer, &[]);
+            }
             function
                 .variables
                 .insert(handle, LocalVariable { id, instruction });
@@ -278,12 +284,18 @@ impl Writer {
                 )?,
                 false => self.get_type_id(LookupType::Handle(argument.ty))?,
             };
-            if let Some(ref mut list) = varying_ids {
+            if let Some(ref mut iface) = interface {
                 let id = if let Some(ref binding) = argument.binding {
                     let name = argument.name.as_ref().map(AsRef::as_ref);
-                    let varying_id =
-                        self.write_varying(ir_module, class, name, argument.ty, binding)?;
-                    list.push(varying_id);
+                    let varying_id = self.write_varying(
+                        ir_module,
+                        iface.stage,
+                        class,
+                        name,
+                        argument.ty,
+                        binding,
+                    )?;
+                    iface.varying_ids.push(varying_id);
                     let id = self.id_gen.next();
                     prelude
                         .body
@@ -298,9 +310,15 @@ impl Writer {
                         let type_id = self.get_type_id(LookupType::Handle(member.ty))?;
                         let name = member.name.as_ref().map(AsRef::as_ref);
                         let binding = member.binding.as_ref().unwrap();
-                        let varying_id =
-                            self.write_varying(ir_module, class, name, member.ty, binding)?;
-                        list.push(varying_id);
+                        let varying_id = self.write_varying(
+                            ir_module,
+                            iface.stage,
+                            class,
+                            name,
+                            member.ty,
+                            binding,
+                        )?;
+                        iface.varying_ids.push(varying_id);
                         let id = self.id_gen.next();
                         prelude
                             .body
@@ -320,6 +338,9 @@ impl Writer {
             } else {
                 let argument_id = self.id_gen.next();
                 let instruction = Instruction::function_parameter(argument_type_id, argument_id);
+                if is_physical_pointer(&ir_module.types[argument.ty].inner) {
+                    self.decorate(argument_id, spirv::Decoration::Aliased, &[]);
+                }
                 function.parameters.push(FunctionArgument {
                     instruction,
                     handle_id: if handle_ty {
@@ -341,13 +362,19 @@ impl Writer {
 
         let return_type_id = match ir_function.result {
             Some(ref result) => {
-                if let Some(ref mut list) = varying_ids {
+                if let Some(ref mut iface) = interface {
                     let class = spirv::StorageClass::Output;
                     if let Some(ref binding) = result.binding {
                         let type_id = self.get_type_id(LookupType::Handle(result.ty))?;
-                        let varying_id =
-                            self.write_varying(ir_module, class, None, result.ty, binding)?;
-                        list.push(varying_id);
+                        let varying_id = self.write_varying(
+                            ir_module,
+                            iface.stage,
+                            class,
+                            None,
+                            result.ty,
+                            binding,
+                        )?;
+                        iface.varying_ids.push(varying_id);
                         ep_context.results.push(ResultMember {
                             id: varying_id,
                             type_id,
@@ -360,9 +387,15 @@ impl Writer {
                             let type_id = self.get_type_id(LookupType::Handle(member.ty))?;
                             let name = member.name.as_ref().map(AsRef::as_ref);
                             let binding = member.binding.as_ref().unwrap();
-                            let varying_id =
-                                self.write_varying(ir_module, class, name, member.ty, binding)?;
-                            list.push(varying_id);
+                            let varying_id = self.write_varying(
+                                ir_module,
+                                iface.stage,
+                                class,
+                                name,
+                                member.ty,
+                                binding,
+                            )?;
+                            iface.varying_ids.push(varying_id);
                             ep_context.results.push(ResultMember {
                                 id: varying_id,
                                 type_id,
@@ -400,7 +433,7 @@ impl Writer {
             function_type,
         ));
 
-        if varying_ids.is_some() {
+        if interface.is_some() {
             function.entry_point_context = Some(ep_context);
         }
 
@@ -413,6 +446,10 @@ impl Writer {
             if info[handle].is_empty() || var.class != crate::StorageClass::Handle {
                 continue;
             }
+            // Binding arrays are only loaded one element at a time.
+            if let crate::TypeInner::BindingArray { .. } = ir_module.types[var.ty].inner {
+                continue;
+            }
             let id = self.id_gen.next();
             let result_type_id = self.get_type_id(LookupType::Handle(var.ty))?;
             let gv = &mut self.global_variables[handle.index()];
@@ -488,9 +525,21 @@ impl Writer {
             &entry_point.function,
             info,
             ir_module,
-            Some(&mut interface_ids),
+            Some(FunctionInterface {
+                varying_ids: &mut interface_ids,
+                stage: entry_point.stage,
+            }),
         )?;
 
+        // Since SPIR-V 1.4, the interface lists every global used, not only the varyings
+        if self.physical_layout.version >= 0x10400 {
+            for (handle, _) in ir_module.global_variables.iter() {
+                if !info[handle].is_empty() {
+                    interface_ids.push(self.global_variables[handle.index()].id);
+                }
+            }
+        }
+
         let exec_model = match entry_point.stage {
             crate::ShaderStage::Vertex => spirv::ExecutionModel::Vertex,
             crate::ShaderStage::Fragment => {
@@ -524,10 +573,14 @@ impl Writer {
         };
         self.check(exec_model.required_capabilities())?;
 
+        let name = self
+            .entry_point_names
+            .get(&(entry_point.stage, entry_point.name.clone()))
+            .unwrap_or(&entry_point.name);
         Ok(Instruction::entry_point(
             exec_model,
             function_id,
-            &entry_point.name,
+            name,
             interface_ids.as_slice(),
         ))
     }
@@ -548,21 +601,11 @@ impl Writer {
                 } else {
                     super::instructions::Signedness::Unsigned
                 };
-                let cap = match bits {
-                    8 => Some(spirv::Capability::Int8),
-                    16 => Some(spirv::Capability::Int16),
-                    64 => Some(spirv::Capability::Int64),
-                    _ => None,
-                };
-                if let Some(cap) = cap {
-                    self.capabilities.insert(cap);
-                }
+                self.capabilities.extend(capabilities::scalar(kind, width));
                 Instruction::type_int(id, bits, signedness)
             }
             Sk::Float => {
-                if bits == 64 {
-                    self.capabilities.insert(spirv::Capability::Float64);
-                }
+                self.capabilities.extend(capabilities::scalar(kind, width));
                 Instruction::type_float(id, bits)
             }
             Sk::Bool => Instruction::type_bool(id),
@@ -606,6 +649,9 @@ impl Writer {
                 Instruction::type_matrix(id, vector_id, columns)
             }
             LocalType::Pointer { base, class } => {
+                if class == spirv::StorageClass::PhysicalStorageBuffer {
+                    self.check(class.required_capabilities())?;
+                }
                 let type_id = self.get_type_id(LookupType::Handle(base))?;
                 Instruction::type_pointer(id, class, type_id)
             }
@@ -637,7 +683,7 @@ impl Writer {
 
     fn write_type_declaration_arena(
         &mut self,
-        arena: &Arena<crate::Type>,
+        arena: &UniqueArena<crate::Type>,
         handle: Handle<crate::Type>,
     ) -> Result<Word, Error> {
         let ty = &arena[handle];
@@ -649,6 +695,7 @@ impl Writer {
                 Entry::Occupied(e) => {
                     let id = *e.into_mut();
                     self.lookup_type.insert(LookupType::Handle(handle), id);
+                    self.write_forward_pointers(handle, id);
                     return Ok(id);
                 }
                 // also register the type as "local", to avoid duplication
@@ -701,17 +748,10 @@ impl Writer {
             } => {
                 let kind = match class {
                     crate::ImageClass::Sampled { kind, multi: _ } => kind,
-                    crate::ImageClass::Depth => crate::ScalarKind::Float,
-                    crate::ImageClass::Storage(format) => {
-                        let required_caps: &[_] = match dim {
-                            crate::ImageDimension::D1 => &[spirv::Capability::Image1D],
-                            crate::ImageDimension::Cube => &[spirv::Capability::ImageCubeArray],
-                            _ => &[],
-                        };
-                        self.check(required_caps)?;
-                        format.into()
-                    }
+                    crate::ImageClass::Depth { multi: _ } => crate::ScalarKind::Float,
+                    crate::ImageClass::Storage(format) => format.into(),
                 };
+                self.check(capabilities::image(dim, arrayed, class))?;
                 let local_type = LocalType::Value {
                     vector_size: None,
                     kind,
@@ -719,7 +759,6 @@ impl Writer {
                     pointer_class: None,
                 };
                 let dim = map_dim(dim);
-                self.check(dim.required_capabilities())?;
                 let type_id = self.get_type_id(LookupType::Local(local_type))?;
                 Instruction::type_image(id, type_id, dim, arrayed, class)
             }
@@ -729,7 +768,7 @@ impl Writer {
                     self.decorate(id, Decoration::ArrayStride, &[stride]);
                 }
 
-                let type_id = self.get_type_id(LookupType::Handle(base))?;
+                let type_id = self.get_member_type_id(arena, base, handle)?;
                 match size {
                     crate::ArraySize::Constant(const_handle) => {
                         let length_id = self.constant_ids[const_handle.index()];
@@ -738,6 +777,19 @@ impl Writer {
                     crate::ArraySize::Dynamic => Instruction::type_runtime_array(id, type_id),
                 }
             }
+            crate::TypeInner::BindingArray { base, size } => {
+                let type_id = self.get_type_id(LookupType::Handle(base))?;
+                match size {
+                    crate::ArraySize::Constant(const_handle) => {
+                        let length_id = self.constant_ids[const_handle.index()];
+                        Instruction::type_array(id, type_id, length_id)
+                    }
+                    crate::ArraySize::Dynamic => {
+                        self.check(&[spirv::Capability::RuntimeDescriptorArray])?;
+                        Instruction::type_runtime_array(id, type_id)
+                    }
+                }
+            }
             crate::TypeInner::Struct {
                 top_level,
                 ref members,
@@ -772,12 +824,14 @@ impl Writer {
                         ref other => other,
                     };
                     if let crate::TypeInner::Matrix {
-                        columns,
-                        rows: _,
+                        columns: _,
+                        rows,
                         width,
                     } = *member_array_subty_inner
                     {
-                        let byte_stride = match columns {
+                        // The stride goes from a column to the next one, which are
+                        // vectors of `rows` components, aligned like them.
+                        let byte_stride = match rows {
                             crate::VectorSize::Bi => 2 * width,
                             crate::VectorSize::Tri | crate::VectorSize::Quad => 4 * width,
                         };
@@ -795,15 +849,25 @@ impl Writer {
                         ));
                     }
 
-                    let member_id = self.get_type_id(LookupType::Handle(member.ty))?;
+                    let member_id = self.get_member_type_id(arena, member.ty, handle)?;
                     member_ids.push(member_id);
                 }
                 Instruction::type_struct(id, member_ids.as_slice())
             }
             crate::TypeInner::Pointer { base, class } => {
-                let type_id = self.get_type_id(LookupType::Handle(base))?;
                 let raw_class = map_storage_class(class);
-                Instruction::type_pointer(id, raw_class, type_id)
+                if class == crate::StorageClass::PhysicalStorageBuffer {
+                    self.check(raw_class.required_capabilities())?;
+                }
+                // Physical pointers may refer to types that come later in
+                // the arena, including the structs containing them.
+                if base >= handle {
+                    self.forward_pointers.push((base, id, raw_class));
+                    Instruction::type_forward_pointer(id, raw_class)
+                } else {
+                    let type_id = self.get_type_id(LookupType::Handle(base))?;
+                    Instruction::type_pointer(id, raw_class, type_id)
+                }
             }
             crate::TypeInner::ValuePointer {
                 size,
@@ -823,9 +887,47 @@ impl Writer {
         };
 
         instruction.to_words(&mut self.logical_layout.declarations);
+        self.write_forward_pointers(handle, id);
         Ok(id)
     }
 
+    /// Completes the forward-declared pointers to `base`, now that it's written.
+    fn write_forward_pointers(&mut self, base: Handle<crate::Type>, base_id: Word) {
+        let mut index = 0;
+        while index < self.forward_pointers.len() {
+            let (pointer_base, pointer_id, class) = self.forward_pointers[index];
+            if pointer_base == base {
+                self.forward_pointers.swap_remove(index);
+                Instruction::type_pointer(pointer_id, class, base_id)
+                    .to_words(&mut self.logical_layout.declarations);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Returns the type ID of a member (or element) of a composite type.
+    ///
+    /// Logical addressing doesn't allow pointers to be stored in composites,
+    /// only `PhysicalStorageBuffer` pointers can be.
+    fn get_member_type_id(
+        &mut self,
+        arena: &UniqueArena<crate::Type>,
+        member_ty: Handle<crate::Type>,
+        composite: Handle<crate::Type>,
+    ) -> Result<Word, Error> {
+        match arena[member_ty].inner {
+            crate::TypeInner::Pointer {
+                class: crate::StorageClass::PhysicalStorageBuffer,
+                ..
+            } => self.get_type_id(LookupType::Handle(member_ty)),
+            crate::TypeInner::Pointer { .. } | crate::TypeInner::ValuePointer { .. } => {
+                Err(Error::PointerInComposite(composite))
+            }
+            _ => self.get_type_id(LookupType::Handle(member_ty)),
+        }
+    }
+
     pub(super) fn get_index_constant(&mut self, index: Word) -> Result<Word, Error> {
         self.get_constant_scalar(crate::ScalarValue::Uint(index as _), 4)
     }
@@ -866,12 +968,14 @@ impl Writer {
         let instruction = match *value {
             crate::ScalarValue::Sint(val) => {
                 let words = match width {
-                    4 => {
+                    // narrow literals are sign-extended to a full word
+                    1 | 2 | 4 => {
                         solo = [val as u32];
                         &solo[..]
                     }
                     8 => {
-                        pair = [(val >> 32) as u32, val as u32];
+                        // multi-word literals are stored low-order word first
+                        pair = [val as u32, (val >> 32) as u32];
                         &pair
                     }
                     _ => unreachable!(),
@@ -880,12 +984,12 @@ impl Writer {
             }
             crate::ScalarValue::Uint(val) => {
                 let words = match width {
-                    4 => {
+                    1 | 2 | 4 => {
                         solo = [val as u32];
                         &solo[..]
                     }
                     8 => {
-                        pair = [(val >> 32) as u32, val as u32];
+                        pair = [val as u32, (val >> 32) as u32];
                         &pair
                     }
                     _ => unreachable!(),
@@ -894,13 +998,17 @@ impl Writer {
             }
             crate::ScalarValue::Float(val) => {
                 let words = match width {
+                    2 => {
+                        solo = [f16_bits(val as f32) as u32];
+                        &solo[..]
+                    }
                     4 => {
                         solo = [(val as f32).to_bits()];
                         &solo[..]
                     }
                     8 => {
                         let bits = f64::to_bits(val);
-                        pair = [(bits >> 32) as u32, bits as u32];
+                        pair = [bits as u32, (bits >> 32) as u32];
                         &pair
                     }
                     _ => unreachable!(),
@@ -943,6 +1051,7 @@ impl Writer {
     fn write_varying(
         &mut self,
         ir_module: &crate::Module,
+        stage: crate::ShaderStage,
         class: spirv::StorageClass,
         debug_name: Option<&str>,
         ty: Handle<crate::Type>,
@@ -969,6 +1078,19 @@ impl Writer {
             } => {
                 self.decorate(id, Decoration::Location, &[location]);
 
+                // Interpolation and sampling decorations are only allowed on
+                // vertex shader outputs and fragment shader inputs.
+                let interpolated = match stage {
+                    crate::ShaderStage::Vertex => class == spirv::StorageClass::Output,
+                    crate::ShaderStage::Fragment => class == spirv::StorageClass::Input,
+                    crate::ShaderStage::Compute => false,
+                };
+                let (interpolation, sampling) = if interpolated {
+                    (interpolation, sampling)
+                } else {
+                    (None, None)
+                };
+
                 match interpolation {
                     // Perspective-correct interpolation is the default in SPIR-V.
                     None | Some(crate::Interpolation::Perspective) => (),
@@ -993,6 +1115,7 @@ impl Writer {
             }
             crate::Binding::BuiltIn(built_in) => {
                 use crate::BuiltIn as Bi;
+                self.check(capabilities::built_in(built_in))?;
                 let built_in = match built_in {
                     Bi::Position => {
                         if class == spirv::StorageClass::Output {
@@ -1009,19 +1132,20 @@ impl Writer {
                     Bi::InstanceIndex => BuiltIn::InstanceIndex,
                     Bi::PointSize => BuiltIn::PointSize,
                     Bi::VertexIndex => BuiltIn::VertexIndex,
+                    Bi::ViewIndex => BuiltIn::ViewIndex,
                     // fragment
                     Bi::FragDepth => BuiltIn::FragDepth,
                     Bi::FrontFacing => BuiltIn::FrontFacing,
-                    Bi::PrimitiveIndex => {
-                        self.capabilities.insert(spirv::Capability::Geometry);
-                        BuiltIn::PrimitiveId
-                    }
+                    Bi::HelperInvocation => BuiltIn::HelperInvocation,
+                    Bi::PrimitiveIndex => BuiltIn::PrimitiveId,
                     Bi::SampleIndex => BuiltIn::SampleId,
                     Bi::SampleMask => BuiltIn::SampleMask,
+                    Bi::SamplePosition => BuiltIn::SamplePosition,
                     // compute
                     Bi::GlobalInvocationId => BuiltIn::GlobalInvocationId,
                     Bi::LocalInvocationId => BuiltIn::LocalInvocationId,
                     Bi::LocalInvocationIndex => BuiltIn::LocalInvocationIndex,
+                    Bi::NumWorkGroups => BuiltIn::NumWorkgroups,
                     Bi::WorkGroupId => BuiltIn::WorkgroupId,
                     Bi::WorkGroupSize => BuiltIn::WorkgroupSize,
                 };
@@ -1037,9 +1161,17 @@ impl Writer {
         &mut self,
         ir_module: &crate::Module,
         global_variable: &crate::GlobalVariable,
+        usage: GlobalUse,
     ) -> Result<(Instruction, Word), Error> {
         let id = self.id_gen.next();
 
+        if global_variable.class == crate::StorageClass::Uniform {
+            match ir_module.types[global_variable.ty].inner {
+                crate::TypeInner::Struct { .. } => {}
+                _ => return Err(Error::FeatureNotImplemented("loose uniforms")),
+            }
+        }
+
         let class = map_storage_class(global_variable.class);
         self.check(class.required_capabilities())?;
 
@@ -1057,7 +1189,19 @@ impl Writer {
 
         use spirv::Decoration;
 
-        let access_decoration = match global_variable.storage_access {
+        let mut used = crate::StorageAccess::empty();
+        if usage.contains(GlobalUse::READ) {
+            used |= crate::StorageAccess::LOAD;
+        }
+        if usage.contains(GlobalUse::WRITE) {
+            used |= crate::StorageAccess::STORE;
+        }
+        let access = match global_variable.storage_access & used {
+            // unused or only queried, keep the declared access
+            narrowed if narrowed.is_empty() => global_variable.storage_access,
+            narrowed => narrowed,
+        };
+        let access_decoration = match access {
             crate::StorageAccess::LOAD => Some(Decoration::NonWritable),
             crate::StorageAccess::STORE => Some(Decoration::NonReadable),
             _ => None,
@@ -1104,15 +1248,60 @@ impl Writer {
         ir_module: &crate::Module,
         mod_info: &ModuleInfo,
     ) -> Result<(), Error> {
-        let has_storage_buffers = ir_module
-            .global_variables
-            .iter()
-            .any(|(_, var)| var.class == crate::StorageClass::Storage);
+        // the selected entry point, and the functions it calls
+        let selection = match self.entry_point {
+            Some((stage, ref name)) => {
+                let index = crate::back::find_entry_point(ir_module, stage, name)
+                    .ok_or_else(|| Error::EntryPointNotFound(stage, name.clone()))?;
+                let functions = crate::back::reachable_functions(
+                    ir_module,
+                    &ir_module.entry_points[index].function,
+                );
+                Some((index, functions))
+            }
+            None => None,
+        };
+        let is_global_used = |handle: Handle<crate::GlobalVariable>| match selection {
+            Some((index, _)) => !mod_info.get_entry_point(index)[handle].is_empty(),
+            None => true,
+        };
+
+        let has_storage_buffers = ir_module.global_variables.iter().any(|(handle, var)| {
+            var.class == crate::StorageClass::Storage && is_global_used(handle)
+        });
         if self.physical_layout.version < 0x10300 && has_storage_buffers {
             // enable the storage buffer class on < SPV-1.3
             Instruction::extension("SPV_KHR_storage_buffer_storage_class")
                 .to_words(&mut self.logical_layout.extensions);
         }
+        let uses_view_index = ir_module.entry_points.iter().any(|ep| {
+            let is_view_index = |binding: Option<&crate::Binding>| {
+                binding == Some(&crate::Binding::BuiltIn(crate::BuiltIn::ViewIndex))
+            };
+            ep.function
+                .arguments
+                .iter()
+                .any(|arg| match ir_module.types[arg.ty].inner {
+                    crate::TypeInner::Struct { ref members, .. } => members
+                        .iter()
+                        .any(|member| is_view_index(member.binding.as_ref())),
+                    _ => is_view_index(arg.binding.as_ref()),
+                })
+        });
+        if self.physical_layout.version < 0x10300 && uses_view_index {
+            // the multiview extension is core since SPV-1.3
+            Instruction::extension("SPV_KHR_multiview")
+                .to_words(&mut self.logical_layout.extensions);
+        }
+        let has_binding_arrays = ir_module.types.iter().any(|(_, ty)| match ty.inner {
+            crate::TypeInner::BindingArray { .. } => true,
+            _ => false,
+        });
+        if self.physical_layout.version < 0x10500 && has_binding_arrays {
+            // descriptor indexing is core since SPV-1.5
+            Instruction::extension("SPV_EXT_descriptor_indexing")
+                .to_words(&mut self.logical_layout.extensions);
+        }
         Instruction::type_void(self.void_type).to_words(&mut self.logical_layout.declarations);
         Instruction::ext_inst_import(self.gl450_ext_inst_id, "GLSL.std.450")
             .to_words(&mut self.logical_layout.ext_inst_imports);
@@ -1164,8 +1353,28 @@ impl Writer {
         debug_assert_eq!(self.constant_ids.iter().position(|&id| id == 0), None);
 
         // now write all globals
-        for (_, var) in ir_module.global_variables.iter() {
-            let (instruction, id) = self.write_global_variable(ir_module, var)?;
+        for (handle, var) in ir_module.global_variables.iter() {
+            if !is_global_used(handle) {
+                // keep the indices aligned with the handles
+                self.global_variables.push(GlobalVariable {
+                    id: 0,
+                    handle_id: 0,
+                });
+                continue;
+            }
+            // the usage across the entry points that are written
+            let usage = ir_module
+                .entry_points
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| match selection {
+                    Some((selected, _)) => index == selected,
+                    None => true,
+                })
+                .fold(GlobalUse::empty(), |usage, (index, _)| {
+                    usage | mod_info.get_entry_point(index)[handle]
+                });
+            let (instruction, id) = self.write_global_variable(ir_module, var, usage)?;
             instruction.to_words(&mut self.logical_layout.declarations);
             self.global_variables
                 .push(GlobalVariable { id, handle_id: 0 });
@@ -1173,6 +1382,11 @@ impl Writer {
 
         // all functions
         for (handle, ir_function) in ir_module.functions.iter() {
+            if let Some((_, ref functions)) = selection {
+                if !functions.contains(&handle) {
+                    continue;
+                }
+            }
             let info = &mod_info[handle];
             let id = self.write_function(ir_function, info, ir_module, None)?;
             self.lookup_function.insert(handle, id);
@@ -1180,6 +1394,11 @@ impl Writer {
 
         // and entry points
         for (ep_index, ir_ep) in ir_module.entry_points.iter().enumerate() {
+            if let Some((selected, _)) = selection {
+                if ep_index != selected {
+                    continue;
+                }
+            }
             let info = mod_info.get_entry_point(ep_index);
             let ep_instruction = self.write_entry_point(ir_ep, info, ir_module)?;
             ep_instruction.to_words(&mut self.logical_layout.entry_points);
@@ -1194,7 +1413,24 @@ impl Writer {
                 .to_words(&mut self.logical_layout.capabilities);
         }
 
-        let addressing_model = spirv::AddressingModel::Logical;
+        let uses_physical_pointers =
+            self.lookup_type
+                .keys()
+                .any(|lookup_type| match *lookup_type {
+                    LookupType::Local(LocalType::Pointer { class, .. }) => {
+                        class == spirv::StorageClass::PhysicalStorageBuffer
+                    }
+                    _ => false,
+                });
+        let addressing_model = if uses_physical_pointers {
+            if self.physical_layout.version < 0x10500 {
+                Instruction::extension("SPV_KHR_physical_storage_buffer")
+                    .to_words(&mut self.logical_layout.extensions);
+            }
+            spirv::AddressingModel::PhysicalStorageBuffer64
+        } else {
+            spirv::AddressingModel::Logical
+        };
         let memory_model = spirv::MemoryModel::GLSL450;
         self.check(addressing_model.required_capabilities())?;
         self.check(memory_model.required_capabilities())?;
@@ -1222,6 +1458,8 @@ impl Writer {
         words: &mut Vec<Word>,
     ) -> Result<(), Error> {
         self.reset();
+        // SPIR-V has no reserved words, the names are plain strings
+        super::super::validate_entry_point_names(ir_module, &[], &self.entry_point_names)?;
 
         self.write_logical_layout(ir_module, info)?;
         self.write_physical_layout();
@@ -1239,3 +1477,130 @@ fn test_write_physical_layout() {
     writer.write_physical_layout();
     assert_eq!(writer.physical_layout.bound, 3);
 }
+
+#[test]
+fn test_pointer_in_composite() {
+    let mut types = crate::UniqueArena::new();
+    let float = types.insert(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let pointer = types.insert(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Pointer {
+            base: float,
+            class: crate::StorageClass::Storage,
+        },
+    });
+    let composite = types.insert(crate::Type {
+        name: Some("Node".to_string()),
+        inner: crate::TypeInner::Struct {
+            top_level: false,
+            members: vec![crate::StructMember {
+                name: Some("next".to_string()),
+                ty: pointer,
+                binding: None,
+                offset: 0,
+            }],
+            span: 8,
+        },
+    });
+
+    // the deprecated flag doesn't allow them anymore
+    #[allow(deprecated)]
+    let flag_options = Options {
+        flags: WriterFlags::PHYSICAL_STORAGE_BUFFER,
+        ..Options::default()
+    };
+    for options in &[Options::default(), flag_options] {
+        let mut writer = Writer::new(options).unwrap();
+        let result = types.iter().try_for_each(|(handle, _)| {
+            writer
+                .write_type_declaration_arena(&types, handle)
+                .map(drop)
+        });
+        match result {
+            Err(Error::PointerInComposite(handle)) => assert_eq!(handle, composite),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    // only `PhysicalStorageBuffer` pointers can be stored in composites
+    let physical_pointer = types.insert(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Pointer {
+            base: float,
+            class: crate::StorageClass::PhysicalStorageBuffer,
+        },
+    });
+    let physical_composite = types.insert(crate::Type {
+        name: Some("PhysicalNode".to_string()),
+        inner: crate::TypeInner::Struct {
+            top_level: false,
+            members: vec![crate::StructMember {
+                name: Some("next".to_string()),
+                ty: physical_pointer,
+                binding: None,
+                offset: 0,
+            }],
+            span: 8,
+        },
+    });
+    let mut writer = Writer::new(&Options::default()).unwrap();
+    for &handle in &[float, physical_pointer, physical_composite] {
+        writer.write_type_declaration_arena(&types, handle).unwrap();
+    }
+    assert!(writer
+        .capabilities
+        .contains(&spirv::Capability::PhysicalStorageBufferAddresses));
+}
+
+#[test]
+fn test_matrix_stride() {
+    let mut types = crate::UniqueArena::new();
+    let mut matrix = |columns, rows| {
+        types.insert(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Matrix {
+                columns,
+                rows,
+                width: 4,
+            },
+        })
+    };
+    // `mat2x4` has columns of 4 components, and `mat4x2` of 2
+    let mat2x4 = matrix(crate::VectorSize::Bi, crate::VectorSize::Quad);
+    let mat4x2 = matrix(crate::VectorSize::Quad, crate::VectorSize::Bi);
+    let member = |ty, offset| crate::StructMember {
+        name: None,
+        ty,
+        binding: None,
+        offset,
+    };
+    types.insert(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Struct {
+            top_level: true,
+            members: vec![member(mat2x4, 0), member(mat4x2, 32)],
+            span: 64,
+        },
+    });
+
+    let mut writer = Writer::new(&Options::default()).unwrap();
+    for (handle, _) in types.iter() {
+        writer.write_type_declaration_arena(&types, handle).unwrap();
+    }
+    let strides: Vec<_> = writer
+        .annotations
+        .iter()
+        .filter(|instruction| {
+            instruction.op == spirv::Op::MemberDecorate
+                && instruction.operands[2] == spirv::Decoration::MatrixStride as u32
+        })
+        .map(|instruction| (instruction.operands[1], instruction.operands[3]))
+        .collect();
+    assert_eq!(strides, [(0, 16), (1, 8)]);
+}
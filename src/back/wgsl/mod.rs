@@ -17,6 +17,13 @@ pub enum Error {
     UnsupportedMathFunction(crate::MathFunction),
 }
 
+impl Error {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic).
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        crate::diagnostic::Diagnostic::from_error("back::wgsl", self)
+    }
+}
+
 pub fn write_string(
     module: &crate::Module,
     info: &crate::valid::ModuleInfo,
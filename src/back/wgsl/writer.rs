@@ -61,6 +61,25 @@ impl<W: Write> Writer<W> {
             }
         }
 
+        // Half precision floats have to be enabled explicitly
+        let uses_f16 = module.types.iter().any(|(_, ty)| match ty.inner {
+            TypeInner::Scalar {
+                kind: crate::ScalarKind::Float,
+                width: 2,
+            }
+            | TypeInner::Vector {
+                kind: crate::ScalarKind::Float,
+                width: 2,
+                ..
+            }
+            | TypeInner::Matrix { width: 2, .. } => true,
+            _ => false,
+        });
+        if uses_f16 {
+            writeln!(self.out, "enable f16;")?;
+            writeln!(self.out)?;
+        }
+
         // Write all structs
         for (handle, ty) in module.types.iter() {
             if let TypeInner::Struct {
@@ -142,14 +161,23 @@ impl<W: Write> Writer<W> {
     ///
     /// # Notes
     /// Adds no trailing or leading whitespace
-    fn write_scalar_value(&mut self, value: crate::ScalarValue) -> BackendResult {
+    fn write_scalar_value(
+        &mut self,
+        value: crate::ScalarValue,
+        width: crate::Bytes,
+    ) -> BackendResult {
         use crate::ScalarValue as Sv;
 
+        // Non-default widths are spelled out with the bit count, like `1i64`
+        let bits = (width as u32) * 8;
         match value {
+            Sv::Sint(value) if width != 4 => write!(self.out, "{}i{}", value, bits)?,
             Sv::Sint(value) => write!(self.out, "{}", value)?,
+            Sv::Uint(value) if width != 4 => write!(self.out, "{}u{}", value, bits)?,
             Sv::Uint(value) => write!(self.out, "{}u", value)?,
             // Floats are written using `Debug` instead of `Display` because it always appends the
             // decimal part even it's zero
+            Sv::Float(value) if width != 4 => write!(self.out, "{:?}f{}", value, bits)?,
             Sv::Float(value) => write!(self.out, "{:?}", value)?,
             Sv::Bool(value) => write!(self.out, "{}", value)?,
         }
@@ -455,13 +483,13 @@ impl<W: Write> Writer<W> {
     /// Adds no trailing or leading whitespace
     fn write_value_type(&mut self, module: &Module, inner: &TypeInner) -> BackendResult {
         match *inner {
-            TypeInner::Vector { size, kind, .. } => write!(
+            TypeInner::Vector { size, kind, width } => write!(
                 self.out,
                 "{}",
                 format!(
                     "vec{}<{}>",
                     back::vector_size_str(size),
-                    scalar_kind_str(kind),
+                    scalar_kind_str(kind, width),
                 )
             )?,
             TypeInner::Sampler { comparison: false } => {
@@ -484,9 +512,13 @@ impl<W: Write> Writer<W> {
                     Ic::Sampled { kind, multi } => (
                         "",
                         if multi { "multisampled_" } else { "" },
-                        format!("<{}>", scalar_kind_str(kind)),
+                        format!("<{}>", scalar_kind_str(kind, 4)),
+                    ),
+                    Ic::Depth { multi } => (
+                        "depth_",
+                        if multi { "multisampled_" } else { "" },
+                        String::from(""),
                     ),
-                    Ic::Depth => ("depth_", "", String::from("")),
                     Ic::Storage(storage_format) => (
                         "storage_",
                         "",
@@ -499,8 +531,8 @@ impl<W: Write> Writer<W> {
                 );
                 write!(self.out, "{}", ty_str)?;
             }
-            TypeInner::Scalar { kind, .. } => {
-                write!(self.out, "{}", scalar_kind_str(kind))?;
+            TypeInner::Scalar { kind, width } => {
+                write!(self.out, "{}", scalar_kind_str(kind, width))?;
             }
             TypeInner::Array { base, size, .. } => {
                 // More info https://gpuweb.github.io/gpuweb/wgsl/#array-types
@@ -519,17 +551,28 @@ impl<W: Write> Writer<W> {
                 }
                 write!(self.out, ">")?;
             }
+            TypeInner::BindingArray { base, size } => {
+                // binding_array<A, 3> -- Constant array
+                // binding_array<A> -- Dynamic array
+                write!(self.out, "binding_array<")?;
+                self.write_type(module, base)?;
+                if let crate::ArraySize::Constant(handle) = size {
+                    write!(self.out, ",")?;
+                    self.write_constant(module, handle)?;
+                }
+                write!(self.out, ">")?;
+            }
             TypeInner::Matrix {
                 columns,
                 rows,
-                width: _,
+                width,
             } => {
                 write!(
                     self.out,
-                    //TODO: Can matrix be other than f32?
-                    "mat{}x{}<f32>",
+                    "mat{}x{}<{}>",
                     back::vector_size_str(columns),
                     back::vector_size_str(rows),
+                    scalar_kind_str(crate::ScalarKind::Float, width),
                 )?;
             }
             TypeInner::Pointer { base, class } => {
@@ -763,9 +806,9 @@ impl<W: Write> Writer<W> {
                         if case.fall_through {
                             writeln!(self.out, "{}fallthrough;", INDENT.repeat(indent + 2))?;
                         }
-                    }
 
-                    writeln!(self.out, "{}}}", INDENT.repeat(indent + 1))?;
+                        writeln!(self.out, "{}}}", INDENT.repeat(indent + 1))?;
+                    }
                 }
 
                 if !default.is_empty() {
@@ -951,6 +994,7 @@ impl<W: Write> Writer<W> {
                     }
                     TypeInner::Matrix { .. }
                     | TypeInner::Array { .. }
+                    | TypeInner::BindingArray { .. }
                     | TypeInner::ValuePointer { .. } => write!(self.out, "[{}]", index)?,
                     TypeInner::Struct { .. } => {
                         // This will never panic in case the type is a `Struct`, this is not true
@@ -969,6 +1013,54 @@ impl<W: Write> Writer<W> {
             Expression::ImageSample {
                 image,
                 sampler,
+                gather: Some(component),
+                coordinate,
+                array_index,
+                offset,
+                level: _,
+                depth_ref,
+            } => {
+                let suffix_cmp = match depth_ref {
+                    Some(_) => "Compare",
+                    None => "",
+                };
+
+                write!(self.out, "textureGather{}(", suffix_cmp)?;
+                // Depth images only have one component to gather
+                match *func_ctx.info[image].ty.inner_with(&module.types) {
+                    TypeInner::Image {
+                        class: crate::ImageClass::Depth { .. },
+                        ..
+                    } => {}
+                    _ => write!(self.out, "{}, ", component as u8)?,
+                }
+                self.write_expr(module, image, func_ctx)?;
+                write!(self.out, ", ")?;
+                self.write_expr(module, sampler, func_ctx)?;
+                write!(self.out, ", ")?;
+                self.write_expr(module, coordinate, func_ctx)?;
+
+                if let Some(array_index) = array_index {
+                    write!(self.out, ", ")?;
+                    self.write_expr(module, array_index, func_ctx)?;
+                }
+
+                if let Some(depth_ref) = depth_ref {
+                    write!(self.out, ", ")?;
+                    self.write_expr(module, depth_ref, func_ctx)?;
+                }
+
+                if let Some(offset) = offset {
+                    write!(self.out, ", ")?;
+                    self.write_constant(module, offset)?;
+                }
+
+                write!(self.out, ")")?;
+            }
+            Expression::ImageSample {
+                image,
+                sampler,
+                gather: None,
                 coordinate,
                 array_index,
                 offset,
@@ -1044,6 +1136,11 @@ impl<W: Write> Writer<W> {
                     Iq::NumLevels => "textureNumLevels",
                     Iq::NumLayers => "textureNumLayers",
                     Iq::NumSamples => "textureNumSamples",
+                    Iq::Lod { .. } => {
+                        return Err(Error::Custom(
+                            "level of detail queries are not supported by WGSL".to_string(),
+                        ))
+                    }
                 };
 
                 write!(self.out, "{}(", texture_function)?;
@@ -1078,26 +1175,39 @@ impl<W: Write> Writer<W> {
                 let name = &self.names[&NameKey::GlobalVariable(handle)];
                 write!(self.out, "{}", name)?;
             }
-            Expression::As { expr, kind, .. } => {
+            Expression::As {
+                expr,
+                kind,
+                convert,
+            } => {
                 let inner = func_ctx.info[expr].ty.inner_with(&module.types);
                 match *inner {
-                    TypeInner::Matrix { columns, rows, .. } => {
+                    TypeInner::Matrix {
+                        columns,
+                        rows,
+                        width,
+                    } => {
                         write!(
                             self.out,
-                            "mat{}x{}<f32>",
+                            "mat{}x{}<{}>",
                             back::vector_size_str(columns),
-                            back::vector_size_str(rows)
+                            back::vector_size_str(rows),
+                            scalar_kind_str(kind, convert.unwrap_or(width)),
                         )?;
                     }
-                    TypeInner::Vector { size, .. } => {
+                    TypeInner::Vector { size, width, .. } => {
                         write!(
                             self.out,
                             "vec{}<{}>",
                             back::vector_size_str(size),
-                            scalar_kind_str(kind)
+                            scalar_kind_str(kind, convert.unwrap_or(width))
                         )?;
                     }
-                    TypeInner::Scalar { .. } => write!(self.out, "{}", scalar_kind_str(kind))?,
+                    TypeInner::Scalar { width, .. } => write!(
+                        self.out,
+                        "{}",
+                        scalar_kind_str(kind, convert.unwrap_or(width))
+                    )?,
                     _ => {
                         return Err(Error::Unimplemented(format!(
                             "write_expr expression::as {:?}",
@@ -1111,8 +1221,8 @@ impl<W: Write> Writer<W> {
             }
             Expression::Splat { size, value } => {
                 let inner = func_ctx.info[value].ty.inner_with(&module.types);
-                let scalar_kind = match *inner {
-                    crate::TypeInner::Scalar { kind, .. } => kind,
+                let scalar = match *inner {
+                    crate::TypeInner::Scalar { kind, width } => scalar_kind_str(kind, width),
                     _ => {
                         return Err(Error::Unimplemented(format!(
                             "write_expr expression::splat {:?}",
@@ -1120,7 +1230,6 @@ impl<W: Write> Writer<W> {
                         )));
                     }
                 };
-                let scalar = scalar_kind_str(scalar_kind);
                 let size = back::vector_size_str(size);
 
                 write!(self.out, "vec{}<{}>(", size, scalar)?;
@@ -1139,6 +1248,11 @@ impl<W: Write> Writer<W> {
                 self.write_expr(module, expr, func_ctx)?;
                 write!(self.out, ")")?;
             }
+            Expression::PointerCast { .. } => {
+                return Err(Error::Unimplemented(
+                    "physical storage buffer pointers".to_string(),
+                ));
+            }
             Expression::Math {
                 fun,
                 arg,
@@ -1187,6 +1301,7 @@ impl<W: Write> Writer<W> {
                     Mf::Normalize => "normalize",
                     Mf::FaceForward => "faceForward",
                     Mf::Reflect => "reflect",
+                    Mf::Refract => "refract",
                     // computational
                     Mf::Sign => "sign",
                     Mf::Fma => "fma",
@@ -1200,6 +1315,18 @@ impl<W: Write> Writer<W> {
                     // bits
                     Mf::CountOneBits => "countOneBits",
                     Mf::ReverseBits => "reverseBits",
+                    // data packing
+                    Mf::Pack4x8snorm => "pack4x8snorm",
+                    Mf::Pack4x8unorm => "pack4x8unorm",
+                    Mf::Pack2x16snorm => "pack2x16snorm",
+                    Mf::Pack2x16unorm => "pack2x16unorm",
+                    Mf::Pack2x16float => "pack2x16float",
+                    // data unpacking
+                    Mf::Unpack4x8snorm => "unpack4x8snorm",
+                    Mf::Unpack4x8unorm => "unpack4x8unorm",
+                    Mf::Unpack2x16snorm => "unpack2x16snorm",
+                    Mf::Unpack2x16unorm => "unpack2x16unorm",
+                    Mf::Unpack2x16float => "unpack2x16float",
                     _ => {
                         return Err(Error::UnsupportedMathFunction(fun));
                     }
@@ -1261,13 +1388,19 @@ impl<W: Write> Writer<W> {
                 self.write_expr(module, condition, func_ctx)?;
                 write!(self.out, ")")?
             }
-            Expression::Derivative { axis, expr } => {
-                use crate::DerivativeAxis as Da;
-
-                let op = match axis {
-                    Da::X => "dpdx",
-                    Da::Y => "dpdy",
-                    Da::Width => "fwidth",
+            Expression::Derivative { axis, ctrl, expr } => {
+                use crate::{DerivativeAxis as Axis, DerivativeControl as Ctrl};
+
+                let op = match (axis, ctrl) {
+                    (Axis::X, Ctrl::Coarse) => "dpdxCoarse",
+                    (Axis::X, Ctrl::Fine) => "dpdxFine",
+                    (Axis::X, Ctrl::None) => "dpdx",
+                    (Axis::Y, Ctrl::Coarse) => "dpdyCoarse",
+                    (Axis::Y, Ctrl::Fine) => "dpdyFine",
+                    (Axis::Y, Ctrl::None) => "dpdy",
+                    (Axis::Width, Ctrl::Coarse) => "fwidthCoarse",
+                    (Axis::Width, Ctrl::Fine) => "fwidthFine",
+                    (Axis::Width, Ctrl::None) => "fwidth",
                 };
                 write!(self.out, "{}(", op)?;
                 self.write_expr(module, expr, func_ctx)?;
@@ -1355,14 +1488,11 @@ impl<W: Write> Writer<W> {
     ) -> BackendResult {
         let constant = &module.constants[handle];
         match constant.inner {
-            crate::ConstantInner::Scalar {
-                width: _,
-                ref value,
-            } => {
+            crate::ConstantInner::Scalar { width, ref value } => {
                 if constant.name.is_some() {
                     write!(self.out, "{}", self.names[&NameKey::Constant(handle)])?;
                 } else {
-                    self.write_scalar_value(*value)?;
+                    self.write_scalar_value(*value, width)?;
                 }
             }
             crate::ConstantInner::Composite { ty, ref components } => {
@@ -1413,30 +1543,17 @@ impl<W: Write> Writer<W> {
         handle: Handle<crate::Constant>,
     ) -> BackendResult {
         match *inner {
-            crate::ConstantInner::Scalar {
-                width: _,
-                ref value,
-            } => {
+            crate::ConstantInner::Scalar { width, ref value } => {
                 let name = self.names[&NameKey::Constant(handle)].clone();
                 // First write only constant name
                 write!(self.out, "let {}: ", name)?;
                 // Next write constant type and value
-                match *value {
-                    crate::ScalarValue::Sint(value) => {
-                        write!(self.out, "i32 = {}", value)?;
-                    }
-                    crate::ScalarValue::Uint(value) => {
-                        write!(self.out, "u32 = {}u", value)?;
-                    }
-                    crate::ScalarValue::Float(value) => {
-                        // Floats are written using `Debug` instead of `Display` because it always appends the
-                        // decimal part even it's zero
-                        write!(self.out, "f32 = {:?}", value)?;
-                    }
-                    crate::ScalarValue::Bool(value) => {
-                        write!(self.out, "bool = {}", value)?;
-                    }
-                };
+                write!(
+                    self.out,
+                    "{} = ",
+                    scalar_kind_str(value.scalar_kind(), width)
+                )?;
+                self.write_scalar_value(*value, width)?;
                 // End with semicolon
                 writeln!(self.out, ";")?;
             }
@@ -1478,11 +1595,13 @@ fn builtin_str(built_in: crate::BuiltIn) -> Option<&'static str> {
     match built_in {
         Bi::VertexIndex => Some("vertex_index"),
         Bi::InstanceIndex => Some("instance_index"),
+        Bi::ViewIndex => Some("view_index"),
         Bi::Position => Some("position"),
         Bi::FrontFacing => Some("front_facing"),
         Bi::FragDepth => Some("frag_depth"),
         Bi::LocalInvocationId => Some("local_invocation_id"),
         Bi::LocalInvocationIndex => Some("local_invocation_index"),
+        Bi::NumWorkGroups => Some("num_workgroups"),
         Bi::GlobalInvocationId => Some("global_invocation_id"),
         Bi::WorkGroupId => Some("workgroup_id"),
         Bi::WorkGroupSize => Some("workgroup_size"),
@@ -1504,14 +1623,22 @@ fn image_dimension_str(dim: crate::ImageDimension) -> &'static str {
     }
 }
 
-fn scalar_kind_str(kind: crate::ScalarKind) -> &'static str {
+fn scalar_kind_str(kind: crate::ScalarKind, width: crate::Bytes) -> &'static str {
     use crate::ScalarKind as Sk;
 
-    match kind {
-        Sk::Float => "f32",
-        Sk::Sint => "i32",
-        Sk::Uint => "u32",
-        Sk::Bool => "bool",
+    match (kind, width) {
+        (Sk::Float, 2) => "f16",
+        (Sk::Float, 8) => "f64",
+        (Sk::Float, _) => "f32",
+        (Sk::Sint, 1) => "i8",
+        (Sk::Sint, 2) => "i16",
+        (Sk::Sint, 8) => "i64",
+        (Sk::Sint, _) => "i32",
+        (Sk::Uint, 1) => "u8",
+        (Sk::Uint, 2) => "u16",
+        (Sk::Uint, 8) => "u64",
+        (Sk::Uint, _) => "u32",
+        (Sk::Bool, _) => "bool",
     }
 }
 
@@ -1585,7 +1712,7 @@ fn storage_class_str(storage_class: crate::StorageClass) -> Option<&'static str>
         Sc::Storage => Some("storage"),
         Sc::PushConstant => Some("push_constant"),
         Sc::WorkGroup => Some("workgroup"),
-        Sc::Function | Sc::Handle => None,
+        Sc::Function | Sc::Handle | Sc::PhysicalStorageBuffer => None,
     }
 }
 
@@ -0,0 +1,257 @@
+//! Compressed binary representation of the IR.
+//!
+//! The module is encoded with `bincode` and compressed with DEFLATE, which
+//! keeps the size down for engines shipping a lot of precompiled shaders.
+//!
+//! The compressed data is preceded by a header: the four bytes `NAGA`,
+//! then the format version as a little-endian `u32`. The version changes
+//! whenever the encoding of the IR does. Data of every version since
+//! [`OLDEST_FORMAT_VERSION`] can still be read, the fields an older version
+//! lacks getting their default value. Data of any other version is rejected
+//! with [`CompressionError::UnsupportedVersion`], so stale assets are
+//! detected instead of being decoded into a wrong module.
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::{
+    cell::Cell,
+    io::{Read, Write},
+};
+
+/// Bytes at the start of every compressed module.
+const MAGIC: [u8; 4] = *b"NAGA";
+
+/// Version of the binary format produced by [`crate::Module::write_compressed`].
+///
+/// - 1: first versioned format.
+/// - 2: `Expression::Derivative` gained its `ctrl` field, which is
+///   `DerivativeControl::None` when reading version 1.
+pub const COMPRESSED_FORMAT_VERSION: u32 = 2;
+
+/// Oldest version of the binary format that can still be read.
+pub const OLDEST_FORMAT_VERSION: u32 = 1;
+
+thread_local! {
+    /// Version of the module being read, for the fields older versions lack.
+    #[allow(clippy::missing_const_for_thread_local)]
+    static READ_VERSION: Cell<u32> = Cell::new(COMPRESSED_FORMAT_VERSION);
+}
+
+/// Deserialize the `ctrl` of `Expression::Derivative`, which isn't encoded
+/// before version 2.
+pub(crate) fn deserialize_derivative_control<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<crate::DerivativeControl, D::Error> {
+    if READ_VERSION.with(Cell::get) < 2 {
+        return Ok(crate::DerivativeControl::None);
+    }
+    serde::Deserialize::deserialize(deserializer)
+}
+
+/// Error produced when encoding or decoding a compressed module.
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("the data is not a compressed module")]
+    InvalidHeader,
+    #[error(
+        "the module has format version {0}, but only versions {} to {} are supported",
+        OLDEST_FORMAT_VERSION,
+        COMPRESSED_FORMAT_VERSION
+    )]
+    UnsupportedVersion(u32),
+    #[error("unable to encode or decode the module: {0}")]
+    Encoding(#[from] bincode::Error),
+}
+
+impl crate::Module {
+    /// Serialize and compress the module into a byte vector.
+    pub fn to_bytes_compressed(&self) -> Result<Vec<u8>, CompressionError> {
+        let mut bytes = Vec::new();
+        self.write_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decompress and deserialize a module produced by `to_bytes_compressed`.
+    pub fn from_bytes_compressed(bytes: &[u8]) -> Result<Self, CompressionError> {
+        Self::read_compressed(bytes)
+    }
+
+    /// Serialize and compress the module into a writer.
+    ///
+    /// The data is streamed through the encoder, without serializing the
+    /// whole module into memory first.
+    pub fn write_compressed<W: Write>(&self, mut writer: W) -> Result<(), CompressionError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&COMPRESSED_FORMAT_VERSION.to_le_bytes())?;
+        let mut encoder = DeflateEncoder::new(writer, Compression::default());
+        bincode::serialize_into(&mut encoder, self)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Decompress and deserialize a module from a reader.
+    pub fn read_compressed<R: Read>(mut reader: R) -> Result<Self, CompressionError> {
+        let mut header = [0; 8];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| CompressionError::InvalidHeader)?;
+        if header[..4] != MAGIC {
+            return Err(CompressionError::InvalidHeader);
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&header[4..]);
+        let version = u32::from_le_bytes(version);
+        if !(OLDEST_FORMAT_VERSION..=COMPRESSED_FORMAT_VERSION).contains(&version) {
+            return Err(CompressionError::UnsupportedVersion(version));
+        }
+
+        let decoder = DeflateDecoder::new(reader);
+        READ_VERSION.with(|read_version| read_version.set(version));
+        let module = bincode::deserialize_from(decoder);
+        READ_VERSION.with(|read_version| read_version.set(COMPRESSED_FORMAT_VERSION));
+        Ok(module?)
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn round_trip() {
+    let source = "
+        [[stage(vertex)]]
+        fn main([[location(0)]] pos: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return pos * 2.0;
+        }
+    ";
+    let module = crate::front::wgsl::parse_str(source).unwrap();
+
+    let bytes = module.to_bytes_compressed().unwrap();
+    let decoded = crate::Module::from_bytes_compressed(&bytes).unwrap();
+    assert_eq!(
+        bincode::serialize(&module).unwrap(),
+        bincode::serialize(&decoded).unwrap(),
+    );
+
+    assert!(crate::Module::from_bytes_compressed(&bytes[..bytes.len() / 2]).is_err());
+
+    match crate::Module::from_bytes_compressed(&bytes[4..]) {
+        Err(CompressionError::InvalidHeader) => {}
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+    let mut newer = bytes;
+    newer[4..8].copy_from_slice(&(COMPRESSED_FORMAT_VERSION + 1).to_le_bytes());
+    match crate::Module::from_bytes_compressed(&newer) {
+        Err(CompressionError::UnsupportedVersion(version)) => {
+            assert_eq!(version, COMPRESSED_FORMAT_VERSION + 1)
+        }
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+/// A fragment shader returning the derivative of its input.
+#[cfg(test)]
+fn derivative_module(ctrl: crate::DerivativeControl) -> crate::Module {
+    let mut module = crate::Module::default();
+    let ty = module.types.insert(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let mut function = crate::Function {
+        arguments: vec![crate::FunctionArgument {
+            name: Some("value".to_string()),
+            ty,
+            binding: Some(crate::Binding::Location {
+                location: 0,
+                interpolation: Some(crate::Interpolation::Perspective),
+                sampling: None,
+            }),
+        }],
+        result: Some(crate::FunctionResult {
+            ty,
+            binding: Some(crate::Binding::Location {
+                location: 0,
+                interpolation: None,
+                sampling: None,
+            }),
+        }),
+        ..Default::default()
+    };
+    let value = function
+        .expressions
+        .append(crate::Expression::FunctionArgument(0));
+    let derivative = function.expressions.append(crate::Expression::Derivative {
+        axis: crate::DerivativeAxis::X,
+        ctrl,
+        expr: value,
+    });
+    function
+        .body
+        .push(crate::Statement::Emit(function.expressions.range_from(1)));
+    function.body.push(crate::Statement::Return {
+        value: Some(derivative),
+    });
+    module.entry_points.push(crate::EntryPoint {
+        name: "main".to_string(),
+        stage: crate::ShaderStage::Fragment,
+        early_depth_test: None,
+        workgroup_size: [0; 3],
+        function,
+    });
+    module
+}
+
+/// `derivative_module`, as written by the current format version.
+///
+/// If the IR encoding changes, this has to be kept, together with a golden
+/// blob of the new version and a bump of [`COMPRESSED_FORMAT_VERSION`].
+#[cfg(test)]
+const GOLDEN_BLOB: [u8; 76] = [
+    78, 65, 71, 65, 2, 0, 0, 0, 117, 142, 49, 10, 64, 33, 12, 67, 205, 231, 171, 171, 87, 116, 112,
+    16, 212, 77, 207, 175, 129, 10, 22, 52, 80, 154, 166, 175, 80, 152, 67, 223, 170, 223, 60, 4,
+    233, 27, 168, 49, 55, 60, 41, 88, 49, 35, 150, 158, 152, 2, 10, 129, 202, 244, 15, 148, 147,
+    30, 142, 43, 92, 184, 189, 227, 236, 233, 105, 38,
+];
+
+/// `derivative_module`, as written by version 1, which has no derivative
+/// control.
+#[cfg(test)]
+const GOLDEN_BLOB_V1: [u8; 75] = [
+    78, 65, 71, 65, 1, 0, 0, 0, 117, 142, 49, 10, 64, 33, 12, 67, 205, 231, 171, 171, 87, 116, 112,
+    16, 212, 77, 207, 175, 129, 10, 86, 48, 80, 146, 134, 71, 41, 204, 161, 111, 205, 111, 30, 130,
+    248, 6, 106, 204, 13, 79, 10, 86, 194, 136, 165, 39, 182, 128, 66, 160, 58, 253, 3, 229, 196,
+    195, 117, 248, 100, 118, 207, 221, 51, 51, 76,
+];
+
+#[test]
+fn golden_blob() {
+    let module = derivative_module(crate::DerivativeControl::Fine);
+    let encoded = bincode::serialize(&module).unwrap();
+
+    let decoded = crate::Module::from_bytes_compressed(&GOLDEN_BLOB).unwrap();
+    assert_eq!(bincode::serialize(&decoded).unwrap(), encoded);
+
+    let bytes = module.to_bytes_compressed().unwrap();
+    assert_eq!(bytes[..8], GOLDEN_BLOB[..8]);
+    let decoded = crate::Module::from_bytes_compressed(&bytes).unwrap();
+    assert_eq!(bincode::serialize(&decoded).unwrap(), encoded);
+}
+
+#[test]
+fn golden_blob_v1() {
+    let module = derivative_module(crate::DerivativeControl::None);
+    let decoded = crate::Module::from_bytes_compressed(&GOLDEN_BLOB_V1).unwrap();
+    assert_eq!(
+        bincode::serialize(&decoded).unwrap(),
+        bincode::serialize(&module).unwrap()
+    );
+
+    // Reading an old version doesn't affect the next reads
+    let decoded = crate::Module::from_bytes_compressed(&GOLDEN_BLOB).unwrap();
+    assert_eq!(
+        bincode::serialize(&decoded).unwrap(),
+        bincode::serialize(&derivative_module(crate::DerivativeControl::Fine)).unwrap()
+    );
+}
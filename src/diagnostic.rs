@@ -0,0 +1,680 @@
+//! Errors of the front ends, the validator and the back ends in a common form.
+//!
+//! Each error type has a `to_diagnostic` method, returning a [`Diagnostic`]
+//! that tools like editors can display without knowing where it came from.
+//! Errors in source text point to it with byte [spans](Label::span), and
+//! errors about a module point to the IR with an [`IrLocation`].
+//!
+//! With the `codespan-reporting` feature, diagnostics can be rendered along
+//! with the source they refer to.
+
+use crate::arena::Handle;
+use std::{error::Error, ops::Range};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Part of the source that a [`Diagnostic`] refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    /// Range of bytes in the source.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// Item of the IR that a [`Diagnostic`] refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IrLocation {
+    Type(Handle<crate::Type>),
+    Constant(Handle<crate::Constant>),
+    GlobalVariable(Handle<crate::GlobalVariable>),
+    Function {
+        handle: Handle<crate::Function>,
+        expression: Option<Handle<crate::Expression>>,
+    },
+    EntryPoint {
+        stage: crate::ShaderStage,
+        name: String,
+        expression: Option<Handle<crate::Expression>>,
+    },
+}
+
+/// An error or warning, with everything known about where it comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Identifier of the kind of problem, which doesn't depend on the details
+    /// of the message, like `front::wgsl::UnknownIdent`.
+    pub code: Option<String>,
+    pub message: String,
+    /// Part of the source where the problem is.
+    pub primary: Option<Label>,
+    /// Other parts of the source related to the problem.
+    pub secondary: Vec<Label>,
+    /// Additional explanations, like the underlying causes of the problem.
+    pub notes: Vec<String>,
+    /// Item of the IR where the problem is, for errors detected in a module.
+    pub location: Option<IrLocation>,
+}
+
+impl Diagnostic {
+    /// Make an error diagnostic out of `error`, which comes from `component`.
+    ///
+    /// The code is `component::` followed by the [code](ErrorCode) of
+    /// `error`, and the messages of its sources become notes.
+    pub(crate) fn from_error<E: Error + ErrorCode + 'static>(component: &str, error: &E) -> Self {
+        let mut notes = Vec::new();
+        let mut source = error.source();
+        while let Some(error) = source {
+            notes.push(error.to_string());
+            source = error.source();
+        }
+        Diagnostic {
+            severity: Severity::Error,
+            code: Some(format!("{}::{}", component, error.code())),
+            message: error.to_string(),
+            primary: None,
+            secondary: Vec::new(),
+            notes,
+            location: None,
+        }
+    }
+
+    pub(crate) fn with_location(self, location: Option<IrLocation>) -> Self {
+        Diagnostic { location, ..self }
+    }
+
+    /// Render the diagnostic in the style of `rustc`, showing the labeled
+    /// parts of `source`.
+    #[cfg(feature = "codespan-reporting")]
+    pub fn emit_to_string(&self, source: &str) -> String {
+        use codespan_reporting::{files::SimpleFile, term};
+
+        let files = SimpleFile::new("shader", source);
+        let config = term::Config::default();
+        let mut writer = term::termcolor::NoColor::new(Vec::new());
+        term::emit(&mut writer, &config, &files, &self.to_codespan())
+            .expect("cannot write diagnostic");
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    #[cfg(feature = "codespan-reporting")]
+    fn to_codespan(&self) -> codespan_reporting::diagnostic::Diagnostic<()> {
+        use codespan_reporting::diagnostic::{Diagnostic as Output, Label as OutputLabel};
+
+        let output = match self.severity {
+            Severity::Warning => Output::warning(),
+            Severity::Error => Output::error(),
+        };
+        let mut labels = Vec::new();
+        if let Some(ref label) = self.primary {
+            labels.push(OutputLabel::primary((), label.span.clone()).with_message(&label.message));
+        }
+        for label in self.secondary.iter() {
+            labels
+                .push(OutputLabel::secondary((), label.span.clone()).with_message(&label.message));
+        }
+        let mut notes = self.notes.clone();
+        if let Some(ref location) = self.location {
+            notes.push(format!("in {:?}", location));
+        }
+        let output = output
+            .with_message(&self.message)
+            .with_labels(labels)
+            .with_notes(notes);
+        match self.code {
+            Some(ref code) => output.with_code(code),
+            None => output,
+        }
+    }
+}
+
+/// Errors that can tell which kind of problem they describe.
+pub(crate) trait ErrorCode {
+    /// Name of the variant of the error, or the code of the error it wraps
+    /// when that one is more precise.
+    fn code(&self) -> &'static str;
+}
+
+/// Implements [`ErrorCode`] for an enum, listing the variants named by their
+/// own name, followed by the patterns of the variants wrapping another error.
+macro_rules! error_codes {
+    ($error:ty {
+        $($(#[$meta:meta])* $variant:ident,)*
+    } $($pattern:pat => $inner:expr,)*) => {
+        impl ErrorCode for $error {
+            fn code(&self) -> &'static str {
+                match *self {
+                    $($(#[$meta])* Self::$variant { .. } => stringify!($variant),)*
+                    $($pattern => $inner.code(),)*
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "wgsl-in")]
+error_codes!(crate::front::wgsl::Error<'_> {
+    Unexpected,
+    BadU32,
+    BadI32,
+    BadFloat,
+    BadU32Constant,
+    BadScalarWidth,
+    BadAccessor,
+    BadTexture,
+    BadGatherComponent,
+    BadTypeCast,
+    BadTextureSampleType,
+    BadMatrixScalarType,
+    InvalidResolve,
+    InvalidForInitializer,
+    UnknownStorageClass,
+    UnknownAttribute,
+    UnknownBuiltin,
+    UnknownAccess,
+    UnknownShaderStage,
+    UnknownIdent,
+    UnknownScalarType,
+    UnknownType,
+    UnknownStorageFormat,
+    UnknownConservativeDepth,
+    UnknownExtension,
+    ExtensionNotEnabled,
+    ZeroStride,
+    ZeroSizeOrAlign,
+    InconsistentBinding,
+    UnknownLocalFunction,
+    InitializationTypeMismatch,
+    MissingType,
+    ChainedComparison,
+    AssignmentInExpression,
+    Other,
+});
+
+#[cfg(feature = "glsl-in")]
+error_codes!(crate::front::glsl::ErrorKind {
+    EndOfFile,
+    InvalidProfile,
+    InvalidVersion,
+    InvalidToken,
+    NotImplemented,
+    UnknownVariable,
+    UnknownType,
+    UnknownField,
+    UnknownLayoutQualifier,
+    #[cfg(feature = "glsl-validate")]
+    VariableAlreadyDeclared,
+    ErrorDirective,
+    IntegerMatrix,
+    SemanticError,
+});
+
+#[cfg(feature = "spv-in")]
+error_codes!(crate::front::spv::Error {
+    InvalidHeader,
+    InvalidWordCount,
+    UnknownInstruction,
+    UnknownCapability,
+    UnsupportedInstruction,
+    UnsupportedCapability,
+    UnsupportedExtension,
+    UnsupportedExtSet,
+    UnsupportedExtInstSet,
+    UnsupportedExtInst,
+    UnsupportedType,
+    UnsupportedExecutionModel,
+    UnsupportedExecutionMode,
+    UnsupportedStorageClass,
+    UnsupportedImageDim,
+    UnsupportedImageFormat,
+    UnsupportedBuiltIn,
+    UnsupportedControlFlow,
+    UnsupportedBinaryOperator,
+    UnknownBinaryOperator,
+    UnknownRelationalFunction,
+    InvalidParameter,
+    InvalidOperandCount,
+    InvalidOperand,
+    InvalidId,
+    InvalidDecoration,
+    InvalidTypeWidth,
+    InvalidSign,
+    InvalidInnerType,
+    InvalidVectorSize,
+    InvalidAccessType,
+    InvalidAccess,
+    InvalidAccessIndex,
+    InvalidBinding,
+    InvalidGlobalVar,
+    InvalidImageExpression,
+    InvalidImageBaseType,
+    InvalidImage,
+    InvalidAsType,
+    InvalidVectorType,
+    InconsistentComparisonSampling,
+    WrongFunctionResultType,
+    WrongFunctionArgumentType,
+    MissingDecoration,
+    BadString,
+    IncompleteData,
+    InvalidTerminator,
+    InvalidEdgeClassification,
+    ControlFlowGraphCycle,
+    FunctionCallCycle,
+    InvalidArraySize,
+    InvalidBarrierScope,
+    InvalidBarrierMemorySemantics,
+    InvalidGatherComponent,
+    DuplicateForwardPointer,
+    IncompleteForwardPointer,
+});
+
+error_codes!(crate::proc::ProcError {
+    TypeNotIndexable,
+    InvalidArraySizeConstant,
+});
+
+error_codes!(crate::proc::ResolveError {
+    OutOfBoundsIndex,
+    InvalidAccess,
+    InvalidSubAccess,
+    InvalidScalar,
+    InvalidVector,
+    InvalidPointer,
+    InvalidImage,
+    FunctionNotDefined,
+    FunctionReturnsVoid,
+    TypeNotFound,
+    IncompatibleOperands,
+});
+
+error_codes!(crate::valid::ValidationError {
+    Layouter,
+    UnexpectedFunction,
+    Corrupted,
+}
+    Self::Type { ref error, .. } => error,
+    Self::Constant { ref error, .. } => error,
+    Self::GlobalVariable { ref error, .. } => error,
+    Self::Function { ref error, .. } => error,
+    Self::EntryPoint { ref error, .. } => error,
+);
+
+error_codes!(crate::valid::TypeError {
+    InvalidWidth,
+    UnresolvedBase,
+    InvalidPointerBase,
+    InvalidData,
+    InvalidBlockType,
+    InvalidArrayBaseType,
+    InvalidBindingArrayBaseType,
+    InvalidArraySizeConstant,
+    NonPositiveArrayLength,
+    InsufficientArrayStride,
+    InvalidDynamicArray,
+    MemberOverlap,
+    MemberOutOfBounds,
+    NestedTopLevel,
+    UnsupportedCapability,
+    UnsupportedImageType,
+});
+
+error_codes!(crate::valid::Disalignment {
+    ArrayStride,
+    StructSpan,
+    MemberOffset,
+    UnsizedMember,
+});
+
+error_codes!(crate::valid::ComposeError {
+    TypeDoesntExist,
+    Type,
+    ComponentCount,
+    ComponentType,
+});
+
+error_codes!(crate::valid::ConstantError {
+    InvalidType,
+    UnresolvedComponent,
+    UnresolvedSize,
+}
+    Self::Compose(ref error) => error,
+);
+
+error_codes!(crate::valid::GlobalVariableError {
+    InvalidUsage,
+    InvalidType,
+    InvalidStorageAccess,
+    MissingTypeFlags,
+    UnsupportedCapability,
+    InvalidBinding,
+    InitializerNotAllowed,
+    InitializerType,
+    BindGroupOutOfRange,
+    BindingOutOfRange,
+}
+    Self::Alignment(_, ref error) => error,
+);
+
+error_codes!(crate::valid::LocalVariableError {
+    InvalidType,
+    InitializerType,
+});
+
+error_codes!(crate::valid::CallError {
+    InvalidFunction,
+    ForwardDeclaredFunction,
+    ResultAlreadyInScope,
+    ArgumentCount,
+    ArgumentType,
+    ExpressionMismatch,
+}
+    Self::Argument { ref error, .. } => error,
+    Self::ResultValue(ref error) => error,
+);
+
+error_codes!(crate::valid::ExpressionError {
+    DoesntExist,
+    NotInScope,
+    ForwardDependency,
+    InvalidBaseType,
+    InvalidIndexType,
+    IndexOutOfBounds,
+    IndexMustBeConstant,
+    FunctionArgumentDoesntExist,
+    ConstantDoesntExist,
+    GlobalVarDoesntExist,
+    LocalVarDoesntExist,
+    InvalidPointerType,
+    InvalidArrayType,
+    InvalidSplatType,
+    InvalidVectorType,
+    InvalidSwizzleComponent,
+    PointerOperand,
+    InvalidUnaryOperandType,
+    InvalidBinaryOperandTypes,
+    InvalidSelectTypes,
+    InvalidBooleanVector,
+    InvalidFloatArgument,
+    ExpectedGlobalVariable,
+    ExpectedGlobalOrArgument,
+    CallToUndeclaredFunction,
+    ExpectedImageType,
+    ExpectedSamplerType,
+    InvalidImageClass,
+    InvalidDerivative,
+    InvalidImageArrayIndex,
+    InvalidImageOtherIndex,
+    InvalidImageArrayIndexType,
+    InvalidImageOtherIndexType,
+    InvalidImageCoordinateType,
+    ComparisonSamplingMismatch,
+    InvalidSampleOffset,
+    InvalidDepthReference,
+    InvalidSampleLevel,
+    InvalidGatherDimension,
+    InvalidGatherComponent,
+    InvalidGatherLevel,
+    InvalidSampleLevelExactType,
+    InvalidSampleLevelBiasType,
+    InvalidSampleLevelGradientType,
+    InvalidCastArgument,
+    InvalidPointerCast,
+    WrongArgumentCount,
+    InvalidArgumentType,
+}
+    Self::Compose(ref error) => error,
+    Self::Proc(ref error) => error,
+    Self::Type(ref error) => error,
+);
+
+error_codes!(crate::valid::FunctionError {
+    ExpressionAlreadyInScope,
+    InvalidArgumentType,
+    UnexpectedArgumentBinding,
+    UnexpectedResultBinding,
+    InstructionsAfterReturn,
+    BreakOutsideOfLoopOrSwitch,
+    ContinueOutsideOfLoop,
+    InvalidReturnSpot,
+    InvalidReturnType,
+    InvalidIfType,
+    InvalidSwitchType,
+    ConflictingSwitchCase,
+    InvalidStorePointer,
+    InvalidStoreValue,
+    InvalidStoreTypes,
+    InvalidExpression,
+    Recursion,
+    NonUniformControlFlow,
+}
+    Self::Expression { ref error, .. } => error,
+    Self::LocalVariable { ref error, .. } => error,
+    Self::InvalidImageStore(ref error) => error,
+    Self::InvalidCall { ref error, .. } => error,
+);
+
+error_codes!(crate::valid::VaryingError {
+    InvalidType,
+    InvalidInterpolation,
+    MissingInterpolation,
+    UnexpectedInterpolation,
+    InvalidBuiltInStage,
+    InvalidBuiltInType,
+    MissingBinding,
+    MemberMissingBinding,
+    BindingCollision,
+    LocationOutOfRange,
+    DuplicateBuiltIn,
+    UnsupportedCapability,
+}
+    Self::Member { ref error, .. } => error,
+);
+
+error_codes!(crate::valid::EntryPointError {
+    Conflict,
+    UnexpectedEarlyDepthTest,
+    UnexpectedWorkgroupSize,
+    OutOfRangeWorkgroupSize,
+    ForbiddenStageOperations,
+    InvalidGlobalUsage,
+    BindingCollision,
+    InvalidIntegerInterpolation,
+}
+    Self::Argument(_, ref error) => error,
+    Self::Result(ref error) => error,
+    Self::Function(ref error) => error,
+);
+
+#[cfg(feature = "glsl-out")]
+error_codes!(crate::back::glsl::Error {
+    FmtError,
+    MissingFeatures,
+    PushConstantNotSupported,
+    PhysicalStorageBufferNotSupported,
+    VersionNotSupported,
+    EntryPointNotFound,
+    UnsupportedExternal,
+    UnsupportedScalar,
+    ImageMultipleSamplers,
+    UnsupportedStorageFormat,
+    ReadWriteStorageImage,
+    UnsupportedShadowSample,
+    InvalidExpression,
+    InvalidArraySize,
+    MissingIoBinding,
+    MissingReturnValue,
+    MissingCallResult,
+    Custom,
+});
+
+#[cfg(feature = "hlsl-out")]
+error_codes!(crate::back::hlsl::Error {
+    IoError,
+    UnsupportedScalar,
+    Unimplemented,
+    EntryPointName,
+    Custom,
+});
+
+#[cfg(feature = "msl-out")]
+error_codes!(crate::back::msl::Error {
+    Format,
+    UnimplementedBindTarget,
+    UnsupportedCompose,
+    UnsupportedBinaryOp,
+    UnsupportedCall,
+    FeatureNotImplemented,
+    Validation,
+    InvalidExpression,
+    InvalidArraySize,
+    InvalidStorageAccess,
+    UnsupportedBuiltIn,
+    CapabilityNotSupported,
+    UnsupportedReadWriteStorageImage,
+    UnsupportedReadWriteStorageFormat,
+    UnsupportedVertexAmplification,
+    UnsupportedLodQuery,
+    UnsupportedBindingArray,
+    EntryPointNotFound,
+    EntryPointName,
+});
+
+#[cfg(feature = "spv-out")]
+error_codes!(crate::back::spv::Error {
+    UnsupportedVersion,
+    MissingCapabilities,
+    FeatureNotImplemented,
+    Validation,
+    PointerInComposite,
+    EntryPointNotFound,
+    EntryPointName,
+    #[cfg(feature = "rspirv")]
+    Rspirv,
+}
+    Self::Proc(ref error) => error,
+);
+
+#[cfg(feature = "wgsl-out")]
+error_codes!(crate::back::wgsl::Error {
+    FmtError,
+    Custom,
+    Unimplemented,
+    UnsupportedMathFunction,
+});
+
+impl crate::valid::ValidationError {
+    /// Describe the error as a [`Diagnostic`] pointing to the invalid item.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        use crate::valid::{EntryPointError, FunctionError, ValidationError as Ve};
+
+        fn expression(error: &FunctionError) -> Option<Handle<crate::Expression>> {
+            match *error {
+                FunctionError::Expression { handle, .. } => Some(handle),
+                _ => None,
+            }
+        }
+
+        let location = match *self {
+            Ve::Layouter(_) | Ve::Corrupted => None,
+            Ve::Type { handle, .. } => Some(IrLocation::Type(handle)),
+            Ve::Constant { handle, .. } => Some(IrLocation::Constant(handle)),
+            Ve::GlobalVariable { handle, .. } => Some(IrLocation::GlobalVariable(handle)),
+            Ve::Function {
+                handle, ref error, ..
+            } => Some(IrLocation::Function {
+                handle,
+                expression: expression(error),
+            }),
+            Ve::UnexpectedFunction(handle) => Some(IrLocation::Function {
+                handle,
+                expression: None,
+            }),
+            Ve::EntryPoint {
+                stage,
+                ref name,
+                ref error,
+            } => Some(IrLocation::EntryPoint {
+                stage,
+                name: name.clone(),
+                expression: match *error {
+                    EntryPointError::Function(ref error) => expression(error),
+                    _ => None,
+                },
+            }),
+        };
+        Diagnostic::from_error("valid", self).with_location(location)
+    }
+}
+
+#[cfg(all(feature = "wgsl-in", feature = "glsl-in"))]
+#[test]
+fn front_end_diagnostics() {
+    let source = "
+        fn main() {
+            let x = y;
+        }
+    ";
+    let error = crate::front::wgsl::parse_str(source).unwrap_err();
+    let diagnostic = error.to_diagnostic();
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(
+        diagnostic.code.as_deref(),
+        Some("front::wgsl::UnknownIdent")
+    );
+    let span = diagnostic.primary.unwrap().span;
+    assert_eq!(&source[span], "y");
+
+    let source = "#version 450\nvoid main() { float x = y; }";
+    let options = crate::front::glsl::Options::from_stage(crate::ShaderStage::Vertex);
+    let error = crate::front::glsl::parse_str(source, &options).unwrap_err();
+    let diagnostic = error.to_diagnostic();
+    assert_eq!(
+        diagnostic.code.as_deref(),
+        Some("front::glsl::UnknownVariable")
+    );
+    let span = diagnostic.primary.unwrap().span;
+    assert_eq!(&source[span], "y");
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn validation_diagnostics() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        fn f() -> f32 {
+            return 1.0 + 1;
+        }
+        ",
+    )
+    .unwrap();
+    let error = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap_err();
+    let diagnostic = error.to_diagnostic();
+    assert_eq!(
+        diagnostic.code.as_deref(),
+        Some("valid::InvalidBinaryOperandTypes")
+    );
+    assert_eq!(diagnostic.notes.len(), 2);
+    match diagnostic.location {
+        Some(IrLocation::Function {
+            expression: Some(_),
+            ..
+        }) => {}
+        ref other => panic!("Unexpected location {:?}", other),
+    }
+
+    #[cfg(feature = "codespan-reporting")]
+    assert!(diagnostic
+        .emit_to_string("")
+        .starts_with("error[valid::InvalidBinaryOperandTypes]: Function [1] 'f' is invalid"));
+}
@@ -0,0 +1,570 @@
+//! Construction of modules from code.
+//!
+//! [`ModuleBuilder`] adds types, constants and global variables to a
+//! [`Module`], and [`FunctionBuilder`] writes the functions and entry points.
+//! The expressions get their types resolved as they are added, so that
+//! generators can inspect them, and the `Emit` statements they need are
+//! inserted automatically.
+//!
+//! ```
+//! use naga::{front::builder::ModuleBuilder, BinaryOperator, ScalarKind, ScalarValue};
+//!
+//! let mut builder = ModuleBuilder::new();
+//! let float = builder.scalar(ScalarKind::Float, 4);
+//! let two = builder.constant(4, ScalarValue::Float(2.0));
+//!
+//! let mut function = builder.function("double");
+//! let x = function.argument("x", float, None);
+//! function.result(float, None);
+//! let two = function.constant_expr(two);
+//! let product = function.binary(BinaryOperator::Multiply, x, two)?;
+//! function.ret(Some(product));
+//! function.finish();
+//!
+//! let module = builder.finish();
+//! # Ok::<(), naga::proc::ResolveError>(())
+//! ```
+//!
+//! The usage of the global variables and the other information the back ends
+//! need are then obtained by [validating](crate::valid::Validator) the module.
+
+use super::{Emitter, Typifier};
+use crate::{
+    arena::Handle,
+    proc::{ResolveContext, ResolveError},
+    BinaryOperator, Binding, Block, Constant, ConstantInner, Expression, Function,
+    FunctionArgument, FunctionResult, GlobalVariable, LocalVariable, Module, ResourceBinding,
+    ScalarKind, ScalarValue, ShaderStage, Statement, StorageAccess, StorageClass, Type, TypeInner,
+    UnaryOperator, VectorSize,
+};
+
+/// Builds a [`Module`] piece by piece.
+#[derive(Debug, Default)]
+pub struct ModuleBuilder {
+    module: Module,
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the module built so far.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    pub fn finish(self) -> Module {
+        self.module
+    }
+
+    /// Returns an anonymous type, which is shared with the identical ones.
+    pub fn ty(&mut self, inner: TypeInner) -> Handle<Type> {
+        anonymous_type(&mut self.module, inner)
+    }
+
+    /// Returns a type called `name`, like a structure.
+    pub fn named_ty(&mut self, name: &str, inner: TypeInner) -> Handle<Type> {
+        self.module.types.insert(Type {
+            name: Some(name.to_string()),
+            inner,
+        })
+    }
+
+    pub fn scalar(&mut self, kind: ScalarKind, width: crate::Bytes) -> Handle<Type> {
+        self.ty(TypeInner::Scalar { kind, width })
+    }
+
+    pub fn vector(
+        &mut self,
+        size: VectorSize,
+        kind: ScalarKind,
+        width: crate::Bytes,
+    ) -> Handle<Type> {
+        self.ty(TypeInner::Vector { size, kind, width })
+    }
+
+    /// Returns an anonymous scalar constant, which is shared with the
+    /// identical ones.
+    pub fn constant(&mut self, width: crate::Bytes, value: ScalarValue) -> Handle<Constant> {
+        scalar_constant(&mut self.module, width, value)
+    }
+
+    /// Returns an anonymous constant of type `ty` made of `components`.
+    pub fn composite(
+        &mut self,
+        ty: Handle<Type>,
+        components: Vec<Handle<Constant>>,
+    ) -> Handle<Constant> {
+        self.module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite { ty, components },
+        })
+    }
+
+    /// Adds a constant called `name`.
+    pub fn named_constant(&mut self, name: &str, inner: ConstantInner) -> Handle<Constant> {
+        self.module.constants.append(Constant {
+            name: Some(name.to_string()),
+            specialization: None,
+            inner,
+        })
+    }
+
+    /// Adds a variable of the `Private` or `WorkGroup` class.
+    pub fn global(
+        &mut self,
+        name: &str,
+        class: StorageClass,
+        ty: Handle<Type>,
+        init: Option<Handle<Constant>>,
+    ) -> Handle<GlobalVariable> {
+        self.module.global_variables.append(GlobalVariable {
+            name: Some(name.to_string()),
+            class,
+            binding: None,
+            ty,
+            init,
+            storage_access: StorageAccess::empty(),
+        })
+    }
+
+    /// Adds a resource bound to `binding`, like a buffer or a texture.
+    ///
+    /// Storage buffers and images are readable and writable.
+    pub fn resource(
+        &mut self,
+        name: &str,
+        class: StorageClass,
+        ty: Handle<Type>,
+        binding: ResourceBinding,
+    ) -> Handle<GlobalVariable> {
+        let is_storage = match self.module.types[ty].inner {
+            TypeInner::Image {
+                class: crate::ImageClass::Storage(_),
+                ..
+            } => true,
+            _ => class == StorageClass::Storage,
+        };
+        self.module.global_variables.append(GlobalVariable {
+            name: Some(name.to_string()),
+            class,
+            binding: Some(binding),
+            ty,
+            init: None,
+            storage_access: if is_storage {
+                StorageAccess::all()
+            } else {
+                StorageAccess::empty()
+            },
+        })
+    }
+
+    /// Starts writing a function called `name`.
+    ///
+    /// It's added to the module by [`FunctionBuilder::finish`], or made an
+    /// entry point by [`FunctionBuilder::finish_entry_point`].
+    pub fn function(&mut self, name: &str) -> FunctionBuilder<'_> {
+        let function = Function {
+            name: Some(name.to_string()),
+            ..Function::default()
+        };
+        let mut emitter = Emitter::default();
+        emitter.start(&function.expressions);
+        FunctionBuilder {
+            module: &mut self.module,
+            function,
+            typifier: Typifier::new(),
+            emitter,
+            blocks: vec![Block::new()],
+        }
+    }
+}
+
+/// Writes the body of a function, see [`ModuleBuilder::function`].
+///
+/// The statements are added to the innermost block being written, which is
+/// the body unless a closure given to [`if_else`](Self::if_else) or
+/// [`looping`](Self::looping) is running.
+#[derive(Debug)]
+pub struct FunctionBuilder<'a> {
+    module: &'a mut Module,
+    function: Function,
+    typifier: Typifier,
+    emitter: Emitter,
+    blocks: Vec<Block>,
+}
+
+impl<'a> FunctionBuilder<'a> {
+    /// Adds an argument and returns its value.
+    pub fn argument(
+        &mut self,
+        name: &str,
+        ty: Handle<Type>,
+        binding: Option<Binding>,
+    ) -> Handle<Expression> {
+        let index = self.function.arguments.len() as u32;
+        self.function.arguments.push(FunctionArgument {
+            name: Some(name.to_string()),
+            ty,
+            binding,
+        });
+        self.append_pre_emitted(Expression::FunctionArgument(index))
+    }
+
+    pub fn result(&mut self, ty: Handle<Type>, binding: Option<Binding>) {
+        self.function.result = Some(FunctionResult { ty, binding });
+    }
+
+    /// Adds a local variable and returns a pointer to it.
+    pub fn local(
+        &mut self,
+        name: &str,
+        ty: Handle<Type>,
+        init: Option<Handle<Constant>>,
+    ) -> Handle<Expression> {
+        let variable = self.function.local_variables.append(LocalVariable {
+            name: Some(name.to_string()),
+            ty,
+            init,
+        });
+        self.append_pre_emitted(Expression::LocalVariable(variable))
+    }
+
+    /// Returns a pointer to `variable`, or its handle for images and samplers.
+    pub fn global(&mut self, variable: Handle<GlobalVariable>) -> Handle<Expression> {
+        self.append_pre_emitted(Expression::GlobalVariable(variable))
+    }
+
+    /// Returns an anonymous type, like [`ModuleBuilder::ty`], for the types
+    /// that are only needed once the function is being written.
+    pub fn ty(&mut self, inner: TypeInner) -> Handle<Type> {
+        anonymous_type(self.module, inner)
+    }
+
+    /// Returns an anonymous scalar constant, like [`ModuleBuilder::constant`].
+    pub fn constant(&mut self, width: crate::Bytes, value: ScalarValue) -> Handle<Constant> {
+        scalar_constant(self.module, width, value)
+    }
+
+    /// Returns the value of `constant`.
+    pub fn constant_expr(&mut self, constant: Handle<Constant>) -> Handle<Expression> {
+        self.append_pre_emitted(Expression::Constant(constant))
+    }
+
+    /// Adds `expression` and resolves its type.
+    ///
+    /// On error, the expression isn't added.
+    pub fn expr(&mut self, expression: Expression) -> Result<Handle<Expression>, ResolveError> {
+        let emitted = !expression.needs_pre_emit();
+        self.append_resolved(expression, emitted)
+    }
+
+    /// Returns the type of `expression`.
+    pub fn type_of(&mut self, expression: Handle<Expression>) -> &TypeInner {
+        self.typifier.get(expression, &self.module.types)
+    }
+
+    pub fn load(
+        &mut self,
+        pointer: Handle<Expression>,
+    ) -> Result<Handle<Expression>, ResolveError> {
+        self.expr(Expression::Load { pointer })
+    }
+
+    pub fn unary(
+        &mut self,
+        op: UnaryOperator,
+        expr: Handle<Expression>,
+    ) -> Result<Handle<Expression>, ResolveError> {
+        self.expr(Expression::Unary { op, expr })
+    }
+
+    pub fn binary(
+        &mut self,
+        op: BinaryOperator,
+        left: Handle<Expression>,
+        right: Handle<Expression>,
+    ) -> Result<Handle<Expression>, ResolveError> {
+        self.expr(Expression::Binary { op, left, right })
+    }
+
+    /// Returns the member or component `index` of `base`.
+    pub fn access_index(
+        &mut self,
+        base: Handle<Expression>,
+        index: u32,
+    ) -> Result<Handle<Expression>, ResolveError> {
+        self.expr(Expression::AccessIndex { base, index })
+    }
+
+    pub fn compose(
+        &mut self,
+        ty: Handle<Type>,
+        components: Vec<Handle<Expression>>,
+    ) -> Result<Handle<Expression>, ResolveError> {
+        self.expr(Expression::Compose { ty, components })
+    }
+
+    /// Adds a statement to the current block.
+    pub fn statement(&mut self, statement: Statement) {
+        self.flush();
+        self.current_block().push(statement);
+    }
+
+    pub fn store(&mut self, pointer: Handle<Expression>, value: Handle<Expression>) {
+        self.statement(Statement::Store { pointer, value });
+    }
+
+    pub fn ret(&mut self, value: Option<Handle<Expression>>) {
+        self.statement(Statement::Return { value });
+    }
+
+    /// Calls `function`, and returns its result if it has one.
+    pub fn call(
+        &mut self,
+        function: Handle<Function>,
+        arguments: Vec<Handle<Expression>>,
+    ) -> Result<Option<Handle<Expression>>, ResolveError> {
+        let result = match self.module.functions[function].result {
+            // the result is produced by the call, so it isn't emitted
+            Some(_) => Some(self.append_resolved(Expression::Call(function), false)?),
+            None => None,
+        };
+        self.statement(Statement::Call {
+            function,
+            arguments,
+            result,
+        });
+        Ok(result)
+    }
+
+    /// Adds an `If` statement, whose branches are written by `accept` and
+    /// `reject`.
+    pub fn if_else<E>(
+        &mut self,
+        condition: Handle<Expression>,
+        accept: impl FnOnce(&mut Self) -> Result<(), E>,
+        reject: impl FnOnce(&mut Self) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let accept = self.nested_block(accept)?;
+        let reject = self.nested_block(reject)?;
+        self.statement(Statement::If {
+            condition,
+            accept,
+            reject,
+        });
+        Ok(())
+    }
+
+    /// Adds a `Loop` statement, whose blocks are written by `body` and
+    /// `continuing`.
+    pub fn looping<E>(
+        &mut self,
+        body: impl FnOnce(&mut Self) -> Result<(), E>,
+        continuing: impl FnOnce(&mut Self) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let body = self.nested_block(body)?;
+        let continuing = self.nested_block(continuing)?;
+        self.statement(Statement::Loop { body, continuing });
+        Ok(())
+    }
+
+    /// Adds the function to the module.
+    pub fn finish(self) -> Handle<Function> {
+        let (module, function) = self.into_function();
+        module.functions.append(function)
+    }
+
+    /// Adds the function to the module as an entry point of `stage`, named
+    /// like the function.
+    pub fn finish_entry_point(self, stage: ShaderStage, workgroup_size: [u32; 3]) {
+        let (module, function) = self.into_function();
+        module.entry_points.push(crate::EntryPoint {
+            name: function.name.clone().unwrap_or_default(),
+            stage,
+            early_depth_test: None,
+            workgroup_size,
+            function,
+        });
+    }
+
+    fn into_function(mut self) -> (&'a mut Module, Function) {
+        self.flush();
+        let mut body = self.blocks.pop().unwrap();
+        if self.function.result.is_none() {
+            crate::proc::ensure_block_returns(&mut body);
+        }
+        self.function.body = body;
+        (self.module, self.function)
+    }
+
+    fn current_block(&mut self) -> &mut Block {
+        self.blocks.last_mut().unwrap()
+    }
+
+    /// Emit the expressions added since the last statement.
+    fn flush(&mut self) {
+        self.pause();
+        self.emitter.start(&self.function.expressions);
+    }
+
+    fn pause(&mut self) {
+        if let Some(emit) = self.emitter.finish(&self.function.expressions) {
+            self.blocks.last_mut().unwrap().push(emit);
+        }
+    }
+
+    /// Append an expression that must not be covered by an `Emit`.
+    fn append_pre_emitted(&mut self, expression: Expression) -> Handle<Expression> {
+        self.pause();
+        let handle = self.function.expressions.append(expression);
+        self.emitter.start(&self.function.expressions);
+        handle
+    }
+
+    /// Append `expression` and resolve its type, or roll it back on error.
+    fn append_resolved(
+        &mut self,
+        expression: Expression,
+        emitted: bool,
+    ) -> Result<Handle<Expression>, ResolveError> {
+        if !emitted {
+            self.pause();
+        }
+        let handle = self.function.expressions.append(expression);
+        let result = self.resolve(handle);
+        if result.is_err() {
+            self.function.expressions.truncate(handle.index());
+        }
+        if !emitted {
+            self.emitter.start(&self.function.expressions);
+        }
+        result.map(|()| handle)
+    }
+
+    fn nested_block<E>(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> Result<(), E>,
+    ) -> Result<Block, E> {
+        self.flush();
+        self.blocks.push(Block::new());
+        let result = write(self);
+        self.flush();
+        let block = self.blocks.pop().unwrap();
+        result.map(|()| block)
+    }
+
+    fn resolve(&mut self, handle: Handle<Expression>) -> Result<(), ResolveError> {
+        let resolve_ctx = ResolveContext {
+            constants: &self.module.constants,
+            types: &self.module.types,
+            global_vars: &self.module.global_variables,
+            local_vars: &self.function.local_variables,
+            functions: &self.module.functions,
+            arguments: &self.function.arguments,
+        };
+        self.typifier
+            .grow(handle, &self.function.expressions, &resolve_ctx)
+    }
+}
+
+fn anonymous_type(module: &mut Module, inner: TypeInner) -> Handle<Type> {
+    module.types.insert(Type { name: None, inner })
+}
+
+fn scalar_constant(
+    module: &mut Module,
+    width: crate::Bytes,
+    value: ScalarValue,
+) -> Handle<Constant> {
+    module.constants.fetch_or_append(Constant {
+        name: None,
+        specialization: None,
+        inner: ConstantInner::Scalar { width, value },
+    })
+}
+
+#[test]
+fn build_fragment_shader() {
+    use crate::BuiltIn;
+
+    let mut builder = ModuleBuilder::new();
+    let vec4 = builder.vector(VectorSize::Quad, ScalarKind::Float, 4);
+    let half = builder.constant(4, ScalarValue::Float(0.5));
+    let tint = builder.global("tint", StorageClass::Private, vec4, None);
+
+    let mut function = builder.function("brighten");
+    let color = function.argument("color", vec4, None);
+    function.result(vec4, None);
+    let half_expr = function.constant_expr(half);
+    let scaled = function
+        .binary(BinaryOperator::Multiply, color, half_expr)
+        .unwrap();
+    assert_eq!(
+        *function.type_of(scaled),
+        TypeInner::Vector {
+            size: VectorSize::Quad,
+            kind: ScalarKind::Float,
+            width: 4,
+        }
+    );
+    let sum = function.binary(BinaryOperator::Add, color, scaled).unwrap();
+    function.ret(Some(sum));
+    let brighten = function.finish();
+
+    let mut main = builder.function("main");
+    let position = main.argument("position", vec4, Some(Binding::BuiltIn(BuiltIn::Position)));
+    main.result(
+        vec4,
+        Some(Binding::Location {
+            location: 0,
+            interpolation: None,
+            sampling: None,
+        }),
+    );
+    let x = main.access_index(position, 0).unwrap();
+    // types and constants can still be added while writing a function
+    let float = main.ty(TypeInner::Scalar {
+        kind: ScalarKind::Float,
+        width: 4,
+    });
+    let same_half = main.constant(4, ScalarValue::Float(0.5));
+    assert_eq!(same_half, half);
+    let threshold = main.local("threshold", float, Some(same_half));
+    let limit = main.load(threshold).unwrap();
+    let is_right = main.binary(BinaryOperator::Greater, x, limit).unwrap();
+    let tint_pointer = main.global(tint);
+    main.if_else(
+        is_right,
+        |accept| {
+            let color = accept.load(tint_pointer)?;
+            let brighter = accept.call(brighten, vec![color])?.unwrap();
+            accept.store(tint_pointer, brighter);
+            Ok::<_, ResolveError>(())
+        },
+        |_| Ok(()),
+    )
+    .unwrap();
+    let color = main.load(tint_pointer).unwrap();
+    main.ret(Some(color));
+    main.finish_entry_point(ShaderStage::Fragment, [0; 3]);
+
+    // invalid expressions are rejected without being added
+    let mut broken = builder.function("broken");
+    let length = broken.constant_expr(half);
+    assert!(broken.access_index(length, 0).is_err());
+    assert_eq!(broken.function.expressions.len(), 1);
+    drop(broken);
+
+    let module = builder.finish();
+    assert_eq!(module.functions.len(), 1);
+    assert_eq!(module.entry_points.len(), 1);
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
@@ -70,6 +70,8 @@ pub struct EntryArg {
     pub handle: Handle<GlobalVariable>,
     pub prologue: PrologueStage,
     pub storage: StorageQualifier,
+    /// Declaration of the variable, or its first use for built-ins
+    pub meta: SourceMetadata,
 }
 
 #[derive(Debug)]
@@ -77,6 +79,10 @@ pub struct Program<'a> {
     pub version: u16,
     pub profile: Profile,
     pub entry_points: &'a FastHashMap<String, ShaderStage>,
+    /// Entry points declared with `#pragma entry(name, stage)`.
+    pub pragma_entry_points: FastHashMap<String, ShaderStage>,
+    /// Stage of the `main` function, if it isn't listed as an entry point.
+    pub main_stage: Option<ShaderStage>,
     pub strip_unused_linkages: bool,
 
     pub workgroup_size: [u32; 3],
@@ -104,6 +110,8 @@ impl<'a> Program<'a> {
             version: 0,
             profile: Profile::Core,
             entry_points,
+            pragma_entry_points: FastHashMap::default(),
+            main_stage: None,
             strip_unused_linkages,
 
             workgroup_size: [1; 3],
@@ -363,7 +371,7 @@ impl<'function> Context<'function> {
         };
 
         if qualifier.is_lhs() {
-            arg.ty = program.module.types.fetch_or_append(Type {
+            arg.ty = program.module.types.insert(Type {
                 name: None,
                 inner: TypeInner::Pointer {
                     base: arg.ty,
@@ -462,7 +470,18 @@ impl<'function> Context<'function> {
 
         let handle = match kind {
             HirExprKind::Access { base, index } => {
-                let base = self.lower_expect(program, base, true, body)?.0;
+                let base = match self.hir_exprs[base].kind {
+                    // Immutable variables (like stage inputs) can still be
+                    // indexed as long as the result is only read
+                    HirExprKind::Variable(ref var) if !lhs && !var.mutable => {
+                        if let Some(idx) = var.entry_arg {
+                            self.arg_use[idx] |= EntryArgUse::READ
+                        }
+
+                        var.expr
+                    }
+                    _ => self.lower_expect(program, base, true, body)?.0,
+                };
                 let (index, index_meta) = self.lower_expect(program, index, false, body)?;
 
                 let pointer = program
@@ -538,6 +557,13 @@ impl<'function> Context<'function> {
                 let right_inner = self.typifier.get(right, &program.module.types);
 
                 match (left_inner, right_inner) {
+                    (&TypeInner::Struct { .. }, &TypeInner::Struct { .. })
+                    | (&TypeInner::Array { .. }, &TypeInner::Array { .. })
+                        if op == BinaryOperator::Equal || op == BinaryOperator::NotEqual =>
+                    {
+                        let equals = op == BinaryOperator::Equal;
+                        self.equality_expr(program, equals, left, right, meta, body)?
+                    }
                     (&TypeInner::Vector { .. }, &TypeInner::Vector { .. })
                     | (&TypeInner::Matrix { .. }, &TypeInner::Matrix { .. }) => match op {
                         BinaryOperator::Equal | BinaryOperator::NotEqual => {
@@ -560,6 +586,10 @@ impl<'function> Context<'function> {
                         BinaryOperator::Add
                         | BinaryOperator::Subtract
                         | BinaryOperator::Divide
+                        | BinaryOperator::Modulo
+                        | BinaryOperator::And
+                        | BinaryOperator::ExclusiveOr
+                        | BinaryOperator::InclusiveOr
                         | BinaryOperator::ShiftLeft
                         | BinaryOperator::ShiftRight => {
                             let scalar_vector =
@@ -577,7 +607,14 @@ impl<'function> Context<'function> {
                         _ => self.add_expression(Expression::Binary { left, op, right }, body),
                     },
                     (&TypeInner::Scalar { .. }, &TypeInner::Vector { size, .. }) => match op {
-                        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
+                        // The shifted operand has to be a vector already
+                        BinaryOperator::Add
+                        | BinaryOperator::Subtract
+                        | BinaryOperator::Divide
+                        | BinaryOperator::Modulo
+                        | BinaryOperator::And
+                        | BinaryOperator::ExclusiveOr
+                        | BinaryOperator::InclusiveOr => {
                             let scalar_vector =
                                 self.add_expression(Expression::Splat { size, value: left }, body);
 
@@ -768,7 +805,7 @@ impl<'function> Context<'function> {
                 if postfix {
                     let local = self.locals.append(LocalVariable {
                         name: None,
-                        ty: program.module.types.fetch_or_append(Type {
+                        ty: program.module.types.insert(Type {
                             name: None,
                             inner: TypeInner::Scalar {
                                 kind: match uint {
@@ -818,6 +855,90 @@ impl<'function> Context<'function> {
         Ok((Some(handle), meta))
     }
 
+    /// Lowers `==` (or `!=`, if `equals` is false) of two values of the same
+    /// type, comparing composites component-wise and combining the results.
+    fn equality_expr(
+        &mut self,
+        program: &mut Program,
+        equals: bool,
+        left: Handle<Expression>,
+        right: Handle<Expression>,
+        meta: SourceMetadata,
+        body: &mut Block,
+    ) -> Result<Handle<Expression>, ErrorKind> {
+        let (op, combine, fun) = if equals {
+            (
+                BinaryOperator::Equal,
+                BinaryOperator::LogicalAnd,
+                RelationalFunction::All,
+            )
+        } else {
+            (
+                BinaryOperator::NotEqual,
+                BinaryOperator::LogicalOr,
+                RelationalFunction::Any,
+            )
+        };
+
+        let count = match *program.resolve_type(self, left, meta)? {
+            TypeInner::Scalar { .. } | TypeInner::Vector { .. } => None,
+            TypeInner::Matrix { columns, .. } => Some(columns as u32),
+            TypeInner::Array {
+                size: crate::ArraySize::Constant(size),
+                ..
+            } => program.module.constants[size].to_array_length(),
+            TypeInner::Struct { ref members, .. } => Some(members.len() as u32),
+            _ => None,
+        };
+
+        let count = match count {
+            // there are no components to compare, so the values are equal
+            Some(0) => {
+                let constant = program.module.constants.append(Constant {
+                    name: None,
+                    specialization: None,
+                    inner: crate::ConstantInner::Scalar {
+                        width: crate::BOOL_WIDTH,
+                        value: crate::ScalarValue::Bool(equals),
+                    },
+                });
+                return Ok(self.add_expression(Expression::Constant(constant), body));
+            }
+            Some(count) => count,
+            None => {
+                let argument = self.add_expression(Expression::Binary { op, left, right }, body);
+
+                return Ok(match *program.resolve_type(self, argument, meta)? {
+                    TypeInner::Vector { .. } => {
+                        self.add_expression(Expression::Relational { fun, argument }, body)
+                    }
+                    _ => argument,
+                });
+            }
+        };
+
+        let mut result = None;
+        for index in 0..count {
+            let left = self.add_expression(Expression::AccessIndex { base: left, index }, body);
+            let right = self.add_expression(Expression::AccessIndex { base: right, index }, body);
+            let component = self.equality_expr(program, equals, left, right, meta, body)?;
+
+            result = Some(match result {
+                Some(result) => self.add_expression(
+                    Expression::Binary {
+                        op: combine,
+                        left: result,
+                        right: component,
+                    },
+                    body,
+                ),
+                None => component,
+            });
+        }
+
+        Ok(result.unwrap())
+    }
+
     pub fn expr_scalar_components(
         &mut self,
         program: &mut Program,
@@ -836,7 +957,7 @@ impl<'function> Context<'function> {
     ) -> Result<Option<u32>, ErrorKind> {
         Ok(self
             .expr_scalar_components(program, expr, meta)?
-            .and_then(|(kind, _)| type_power(kind)))
+            .and_then(|(kind, width)| type_power(kind, width)))
     }
 
     pub fn get_expression(&self, expr: Handle<Expression>) -> &Expression {
@@ -851,9 +972,10 @@ impl<'function> Context<'function> {
         kind: ScalarKind,
         width: crate::Bytes,
     ) -> Result<(), ErrorKind> {
-        if let (Some(tgt_power), Some(expr_power)) =
-            (type_power(kind), self.expr_power(program, *expr, meta)?)
-        {
+        if let (Some(tgt_power), Some(expr_power)) = (
+            type_power(kind, width),
+            self.expr_power(program, *expr, meta)?,
+        ) {
             if tgt_power > expr_power {
                 *expr = self.expressions.append(Expression::As {
                     expr: *expr,
@@ -881,8 +1003,9 @@ impl<'function> Context<'function> {
             Some((left_power, left_width, left_kind)),
             Some((right_power, right_width, right_kind)),
         ) = (
-            left_components.and_then(|(kind, width)| Some((type_power(kind)?, width, kind))),
-            right_components.and_then(|(kind, width)| Some((type_power(kind)?, width, kind))),
+            left_components.and_then(|(kind, width)| Some((type_power(kind, width)?, width, kind))),
+            right_components
+                .and_then(|(kind, width)| Some((type_power(kind, width)?, width, kind))),
         ) {
             match left_power.cmp(&right_power) {
                 std::cmp::Ordering::Less => {
@@ -935,10 +1058,13 @@ pub fn scalar_components(ty: &TypeInner) -> Option<(ScalarKind, crate::Bytes)> {
     }
 }
 
-pub fn type_power(kind: ScalarKind) -> Option<u32> {
+/// Rank of the scalar types for the implicit conversions, which only go
+/// from a lower to a higher rank: `int` -> `uint` -> `float` -> `double`.
+pub fn type_power(kind: ScalarKind, width: crate::Bytes) -> Option<u32> {
     Some(match kind {
         ScalarKind::Sint => 0,
         ScalarKind::Uint => 1,
+        ScalarKind::Float if width == 8 => 3,
         ScalarKind::Float => 2,
         ScalarKind::Bool => return None,
     })
@@ -1003,9 +1129,11 @@ pub enum TypeQualifier {
     ResourceBinding(ResourceBinding),
     Location(u32),
     WorkGroupSize(usize, u32),
+    SpecializationConstant(u32),
     Sampling(Sampling),
     Layout(StructLayout),
     Precision(Precision),
+    StorageAccess(crate::StorageAccess),
     EarlyFragmentTests,
 }
 
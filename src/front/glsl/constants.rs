@@ -1,12 +1,12 @@
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
     BinaryOperator, Constant, ConstantInner, Expression, ScalarKind, ScalarValue, Type, TypeInner,
     UnaryOperator,
 };
 
 #[derive(Debug)]
 pub struct ConstantSolver<'a> {
-    pub types: &'a Arena<Type>,
+    pub types: &'a UniqueArena<Type>,
     pub expressions: &'a Arena<Expression>,
     pub constants: &'a mut Arena<Constant>,
 }
@@ -35,6 +35,8 @@ pub enum ConstantSolvingError {
     Load,
     #[error("Constants don't support image expressions")]
     ImageExpression,
+    #[error("Constants don't support pointer casts")]
+    PointerCast,
     #[error("Cannot access the type")]
     InvalidAccessBase,
     #[error("Cannot access at the index")]
@@ -196,6 +198,7 @@ impl<'a> ConstantSolver<'a> {
             Expression::Call { .. } => Err(ConstantSolvingError::Call),
             Expression::FunctionArgument(_) => Err(ConstantSolvingError::FunctionArg),
             Expression::GlobalVariable(_) => Err(ConstantSolvingError::GlobalVariable),
+            Expression::PointerCast { .. } => Err(ConstantSolvingError::PointerCast),
             Expression::ImageSample { .. }
             | Expression::ImageLoad { .. }
             | Expression::ImageQuery { .. } => Err(ConstantSolvingError::ImageExpression),
@@ -447,18 +450,18 @@ mod tests {
 
     use crate::{
         Arena, Constant, ConstantInner, Expression, ScalarKind, ScalarValue, Type, TypeInner,
-        UnaryOperator, VectorSize,
+        UnaryOperator, UniqueArena, VectorSize,
     };
 
     use super::ConstantSolver;
 
     #[test]
     fn unary_op() {
-        let mut types = Arena::new();
+        let mut types = UniqueArena::new();
         let mut expressions = Arena::new();
         let mut constants = Arena::new();
 
-        let vec_ty = types.append(Type {
+        let vec_ty = types.insert(Type {
             name: None,
             inner: TypeInner::Vector {
                 size: VectorSize::Bi,
@@ -587,7 +590,7 @@ mod tests {
         });
 
         let mut solver = ConstantSolver {
-            types: &Arena::new(),
+            types: &UniqueArena::new(),
             expressions: &expressions,
             constants: &mut constants,
         };
@@ -605,11 +608,11 @@ mod tests {
 
     #[test]
     fn access() {
-        let mut types = Arena::new();
+        let mut types = UniqueArena::new();
         let mut expressions = Arena::new();
         let mut constants = Arena::new();
 
-        let matrix_ty = types.append(Type {
+        let matrix_ty = types.insert(Type {
             name: None,
             inner: TypeInner::Matrix {
                 columns: VectorSize::Bi,
@@ -618,7 +621,7 @@ mod tests {
             },
         });
 
-        let vec_ty = types.append(Type {
+        let vec_ty = types.insert(Type {
             name: None,
             inner: TypeInner::Vector {
                 size: VectorSize::Tri,
@@ -26,6 +26,7 @@ pub enum ExpectedToken {
     IntLiteral,
     FloatLiteral,
     BoolLiteral,
+    Statement,
     Eof,
 }
 impl From<TokenValue> for ExpectedToken {
@@ -42,6 +43,7 @@ impl std::fmt::Display for ExpectedToken {
             ExpectedToken::IntLiteral => write!(f, "integer literal"),
             ExpectedToken::FloatLiteral => write!(f, "float literal"),
             ExpectedToken::BoolLiteral => write!(f, "bool literal"),
+            ExpectedToken::Statement => write!(f, "a statement"),
             ExpectedToken::Eof => write!(f, "end of file"),
         }
     }
@@ -71,6 +73,10 @@ pub enum ErrorKind {
     #[cfg(feature = "glsl-validate")]
     #[error("Variable already declared: {1}")]
     VariableAlreadyDeclared(SourceMetadata, String),
+    #[error("#error {1}")]
+    ErrorDirective(SourceMetadata, String),
+    #[error("Matrices of integers are not supported: {1}")]
+    IntegerMatrix(SourceMetadata, String),
     #[error("{1}")]
     SemanticError(SourceMetadata, Cow<'static, str>),
 }
@@ -84,6 +90,8 @@ impl ErrorKind {
             | ErrorKind::InvalidVersion(metadata, _)
             | ErrorKind::NotImplemented(metadata, _)
             | ErrorKind::UnknownLayoutQualifier(metadata, _)
+            | ErrorKind::ErrorDirective(metadata, _)
+            | ErrorKind::IntegerMatrix(metadata, _)
             | ErrorKind::SemanticError(metadata, _)
             | ErrorKind::UnknownField(metadata, _) => Some(metadata),
             #[cfg(feature = "glsl-validate")]
@@ -120,6 +128,18 @@ pub struct ParseError {
     pub kind: ErrorKind,
 }
 
+impl ParseError {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic).
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        let mut diagnostic = crate::diagnostic::Diagnostic::from_error("front::glsl", &self.kind);
+        diagnostic.primary = self.kind.metadata().map(|meta| crate::diagnostic::Label {
+            span: meta.into(),
+            message: String::new(),
+        });
+        diagnostic
+    }
+}
+
 impl From<ErrorKind> for ParseError {
     fn from(kind: ErrorKind) -> Self {
         ParseError { kind }
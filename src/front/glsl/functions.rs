@@ -1,8 +1,9 @@
 use crate::{
-    proc::ensure_block_returns, Arena, BinaryOperator, Block, Constant, ConstantInner, EntryPoint,
-    Expression, Function, FunctionArgument, FunctionResult, Handle, ImageQuery, LocalVariable,
-    MathFunction, RelationalFunction, SampleLevel, ScalarKind, ScalarValue, ShaderStage, Statement,
-    StructMember, SwizzleComponent, Type, TypeInner, VectorSize,
+    proc::ensure_block_returns, Arena, ArraySize, BinaryOperator, Binding, Block, Constant,
+    ConstantInner, DerivativeAxis, DerivativeControl, EntryPoint, Expression, Function,
+    FunctionArgument, FunctionResult, Handle, ImageQuery, LocalVariable, MathFunction,
+    RelationalFunction, SampleLevel, ScalarKind, ScalarValue, ShaderStage, Statement, StructMember,
+    SwizzleComponent, Type, TypeInner, UnaryOperator, VectorSize,
 };
 
 use super::{ast::*, error::ErrorKind, SourceMetadata};
@@ -23,6 +24,83 @@ impl Program<'_> {
         })
     }
 
+    /// Splits the coordinate passed to a texture function on an arrayed image
+    /// into the actual coordinate and the array layer, which GLSL packs as the
+    /// component following the coordinate. Floating point layers are rounded
+    /// to the nearest integer.
+    fn split_array_coordinate(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        (image, image_meta): (Handle<Expression>, SourceMetadata),
+        (coordinate, coordinate_meta): (Handle<Expression>, SourceMetadata),
+    ) -> Result<(Handle<Expression>, Option<Handle<Expression>>), ErrorKind> {
+        let dim = match *self.resolve_type(ctx, image, image_meta)? {
+            TypeInner::Image {
+                dim, arrayed: true, ..
+            } => dim,
+            _ => return Ok((coordinate, None)),
+        };
+
+        let (expr, layer) = match dim {
+            crate::ImageDimension::D1 => (
+                Expression::AccessIndex {
+                    base: coordinate,
+                    index: 0,
+                },
+                1,
+            ),
+            crate::ImageDimension::D2 => (
+                Expression::Swizzle {
+                    size: VectorSize::Bi,
+                    vector: coordinate,
+                    pattern: SwizzleComponent::XYZW,
+                },
+                2,
+            ),
+            crate::ImageDimension::D3 | crate::ImageDimension::Cube => (
+                Expression::Swizzle {
+                    size: VectorSize::Tri,
+                    vector: coordinate,
+                    pattern: SwizzleComponent::XYZW,
+                },
+                3,
+            ),
+        };
+        let split = ctx.add_expression(expr, body);
+        let mut array_index = ctx.add_expression(
+            Expression::AccessIndex {
+                base: coordinate,
+                index: layer,
+            },
+            body,
+        );
+        if let Some(ScalarKind::Float) = self
+            .resolve_type(ctx, coordinate, coordinate_meta)?
+            .scalar_kind()
+        {
+            let rounded = ctx.add_expression(
+                Expression::Math {
+                    fun: MathFunction::Round,
+                    arg: array_index,
+                    arg1: None,
+                    arg2: None,
+                },
+                body,
+            );
+            array_index = ctx.add_expression(
+                Expression::As {
+                    kind: ScalarKind::Sint,
+                    expr: rounded,
+                    convert: Some(4),
+                },
+                body,
+            );
+        }
+
+        Ok((split, Some(array_index)))
+    }
+
     pub fn function_call(
         &mut self,
         ctx: &mut Context,
@@ -226,13 +304,16 @@ impl Program<'_> {
                             return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
                         }
                         if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, array_index) =
+                                self.split_array_coordinate(ctx, body, args[0], args[1])?;
                             Ok(Some(ctx.add_expression(
                                 Expression::ImageSample {
                                     image: args[0].0,
                                     sampler,
-                                    coordinate: args[1].0,
-                                    array_index: None, //TODO
-                                    offset: None,      //TODO
+                                    gather: None,
+                                    coordinate,
+                                    array_index,
+                                    offset: None, //TODO
                                     level: args.get(2).map_or(SampleLevel::Auto, |&(expr, _)| {
                                         SampleLevel::Bias(expr)
                                     }),
@@ -257,13 +338,16 @@ impl Program<'_> {
                             body,
                         );
                         if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, array_index) =
+                                self.split_array_coordinate(ctx, body, args[0], args[1])?;
                             Ok(Some(ctx.add_expression(
                                 Expression::ImageSample {
                                     image: args[0].0,
                                     sampler,
-                                    coordinate: args[1].0,
-                                    array_index: None, //TODO
-                                    offset: None,      //TODO
+                                    gather: None,
+                                    coordinate,
+                                    array_index,
+                                    offset: None, //TODO
                                     level: SampleLevel::Exact(exact),
                                     depth_ref: None,
                                 },
@@ -276,6 +360,72 @@ impl Program<'_> {
                             ))
                         }
                     }
+                    "textureOffset" => {
+                        if !(3..=4).contains(&args.len()) {
+                            return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
+                        }
+                        let offset = self.solve_constant(ctx, args[2].0, args[2].1)?;
+                        if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, array_index) =
+                                self.split_array_coordinate(ctx, body, args[0], args[1])?;
+                            Ok(Some(ctx.add_expression(
+                                Expression::ImageSample {
+                                    image: args[0].0,
+                                    sampler,
+                                    gather: None,
+                                    coordinate,
+                                    array_index,
+                                    offset: Some(offset),
+                                    level: args.get(3).map_or(SampleLevel::Auto, |&(expr, _)| {
+                                        SampleLevel::Bias(expr)
+                                    }),
+                                    depth_ref: None,
+                                },
+                                body,
+                            )))
+                        } else {
+                            Err(ErrorKind::SemanticError(
+                                meta,
+                                "Bad call to textureOffset".into(),
+                            ))
+                        }
+                    }
+                    "textureLodOffset" => {
+                        if args.len() != 4 {
+                            return Err(ErrorKind::wrong_function_args(name, 4, args.len(), meta));
+                        }
+                        let exact = ctx.add_expression(
+                            Expression::As {
+                                kind: crate::ScalarKind::Float,
+                                expr: args[2].0,
+                                convert: Some(4),
+                            },
+                            body,
+                        );
+                        let offset = self.solve_constant(ctx, args[3].0, args[3].1)?;
+                        if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, array_index) =
+                                self.split_array_coordinate(ctx, body, args[0], args[1])?;
+                            Ok(Some(ctx.add_expression(
+                                Expression::ImageSample {
+                                    image: args[0].0,
+                                    sampler,
+                                    gather: None,
+                                    coordinate,
+                                    array_index,
+                                    offset: Some(offset),
+                                    level: SampleLevel::Exact(exact),
+                                    depth_ref: None,
+                                },
+                                body,
+                            )))
+                        } else {
+                            Err(ErrorKind::SemanticError(
+                                meta,
+                                "Bad call to textureLodOffset".into(),
+                            ))
+                        }
+                    }
                     "textureProj" => {
                         if !(2..=3).contains(&args.len()) {
                             return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
@@ -331,6 +481,7 @@ impl Program<'_> {
                                 Expression::ImageSample {
                                     image: args[0].0,
                                     sampler,
+                                    gather: None,
                                     coordinate: args[1].0,
                                     array_index: None, //TODO
                                     offset: None,      //TODO
@@ -366,55 +517,8 @@ impl Program<'_> {
                             return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
                         }
                         if ctx.samplers.get(&args[0].0).is_some() {
-                            let (arrayed, dims) =
-                                match *self.resolve_type(ctx, args[0].0, args[0].1)? {
-                                    TypeInner::Image { arrayed, dim, .. } => (arrayed, dim),
-                                    _ => (false, crate::ImageDimension::D1),
-                                };
-
-                            let (coordinate, array_index) = if arrayed {
-                                (
-                                    match dims {
-                                        crate::ImageDimension::D1 => ctx.add_expression(
-                                            Expression::AccessIndex {
-                                                base: args[1].0,
-                                                index: 0,
-                                            },
-                                            body,
-                                        ),
-                                        crate::ImageDimension::D2 => ctx.add_expression(
-                                            Expression::Swizzle {
-                                                size: VectorSize::Bi,
-                                                vector: args[1].0,
-                                                pattern: SwizzleComponent::XYZW,
-                                            },
-                                            body,
-                                        ),
-                                        _ => ctx.add_expression(
-                                            Expression::Swizzle {
-                                                size: VectorSize::Tri,
-                                                vector: args[1].0,
-                                                pattern: SwizzleComponent::XYZW,
-                                            },
-                                            body,
-                                        ),
-                                    },
-                                    Some(ctx.add_expression(
-                                        Expression::AccessIndex {
-                                            base: args[1].0,
-                                            index: match dims {
-                                                crate::ImageDimension::D1 => 1,
-                                                crate::ImageDimension::D2 => 2,
-                                                crate::ImageDimension::D3 => 3,
-                                                crate::ImageDimension::Cube => 2,
-                                            },
-                                        },
-                                        body,
-                                    )),
-                                )
-                            } else {
-                                (args[1].0, None)
-                            };
+                            let (coordinate, array_index) =
+                                self.split_array_coordinate(ctx, body, args[0], args[1])?;
 
                             Ok(Some(ctx.add_expression(
                                 Expression::ImageLoad {
@@ -436,7 +540,9 @@ impl Program<'_> {
                     | "inversesqrt" | "exp" | "exp2" | "sign" | "transpose" | "inverse"
                     | "normalize" | "sinh" | "cos" | "cosh" | "tan" | "tanh" | "acos" | "asin"
                     | "log" | "log2" | "length" | "determinant" | "bitCount"
-                    | "bitfieldReverse" => {
+                    | "bitfieldReverse" | "packSnorm4x8" | "packUnorm4x8" | "packSnorm2x16"
+                    | "packUnorm2x16" | "packHalf2x16" | "unpackSnorm4x8" | "unpackUnorm4x8"
+                    | "unpackSnorm2x16" | "unpackUnorm2x16" | "unpackHalf2x16" => {
                         if args.len() != 1 {
                             return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
                         }
@@ -471,6 +577,16 @@ impl Program<'_> {
                                     "determinant" => MathFunction::Determinant,
                                     "bitCount" => MathFunction::CountOneBits,
                                     "bitfieldReverse" => MathFunction::ReverseBits,
+                                    "packSnorm4x8" => MathFunction::Pack4x8snorm,
+                                    "packUnorm4x8" => MathFunction::Pack4x8unorm,
+                                    "packSnorm2x16" => MathFunction::Pack2x16snorm,
+                                    "packUnorm2x16" => MathFunction::Pack2x16unorm,
+                                    "packHalf2x16" => MathFunction::Pack2x16float,
+                                    "unpackSnorm4x8" => MathFunction::Unpack4x8snorm,
+                                    "unpackUnorm4x8" => MathFunction::Unpack4x8unorm,
+                                    "unpackSnorm2x16" => MathFunction::Unpack2x16snorm,
+                                    "unpackUnorm2x16" => MathFunction::Unpack2x16unorm,
+                                    "unpackHalf2x16" => MathFunction::Unpack2x16float,
                                     _ => unreachable!(),
                                 },
                                 arg: args[0].0,
@@ -731,6 +847,58 @@ impl Program<'_> {
                             body,
                         )))
                     }
+                    "not" => {
+                        if args.len() != 1 {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+                        match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Vector {
+                                kind: ScalarKind::Bool,
+                                ..
+                            } => {}
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    meta,
+                                    "not only accepts boolean vectors".into(),
+                                ))
+                            }
+                        }
+                        Ok(Some(ctx.add_expression(
+                            Expression::Unary {
+                                op: UnaryOperator::Not,
+                                expr: args[0].0,
+                            },
+                            body,
+                        )))
+                    }
+                    "dFdx" | "dFdxFine" | "dFdxCoarse" | "dFdy" | "dFdyFine" | "dFdyCoarse"
+                    | "fwidth" | "fwidthFine" | "fwidthCoarse" => {
+                        if args.len() != 1 {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+                        let axis = if name.starts_with("dFdx") {
+                            DerivativeAxis::X
+                        } else if name.starts_with("dFdy") {
+                            DerivativeAxis::Y
+                        } else {
+                            DerivativeAxis::Width
+                        };
+                        let ctrl = if name.ends_with("Fine") {
+                            DerivativeControl::Fine
+                        } else if name.ends_with("Coarse") {
+                            DerivativeControl::Coarse
+                        } else {
+                            DerivativeControl::None
+                        };
+                        Ok(Some(ctx.add_expression(
+                            Expression::Derivative {
+                                axis,
+                                ctrl,
+                                expr: args[0].0,
+                            },
+                            body,
+                        )))
+                    }
                     "isinf" | "isnan" | "all" | "any" => {
                         let fun = match name.as_str() {
                             "isinf" => RelationalFunction::IsInf,
@@ -772,45 +940,55 @@ impl Program<'_> {
 
                                 exact = false;
 
-                                let (decl_kind, call_kind) = match (decl_inner, call_inner) {
+                                let (decl_scalar, call_scalar) = match (decl_inner, call_inner) {
                                     (
                                         &TypeInner::Scalar {
-                                            kind: decl_kind, ..
+                                            kind: decl_kind,
+                                            width: decl_width,
                                         },
                                         &TypeInner::Scalar {
-                                            kind: call_kind, ..
+                                            kind: call_kind,
+                                            width: call_width,
                                         },
-                                    ) => (decl_kind, call_kind),
+                                    ) => ((decl_kind, decl_width), (call_kind, call_width)),
                                     (
                                         &TypeInner::Vector {
                                             kind: decl_kind,
                                             size: decl_size,
-                                            ..
+                                            width: decl_width,
                                         },
                                         &TypeInner::Vector {
                                             kind: call_kind,
                                             size: call_size,
-                                            ..
+                                            width: call_width,
                                         },
-                                    ) if decl_size == call_size => (decl_kind, call_kind),
+                                    ) if decl_size == call_size => {
+                                        ((decl_kind, decl_width), (call_kind, call_width))
+                                    }
                                     (
                                         &TypeInner::Matrix {
                                             rows: decl_rows,
                                             columns: decl_columns,
-                                            ..
+                                            width: decl_width,
                                         },
                                         &TypeInner::Matrix {
                                             rows: call_rows,
                                             columns: call_columns,
-                                            ..
+                                            width: call_width,
                                         },
                                     ) if decl_columns == call_columns && decl_rows == call_rows => {
-                                        (ScalarKind::Float, ScalarKind::Float)
+                                        (
+                                            (ScalarKind::Float, decl_width),
+                                            (ScalarKind::Float, call_width),
+                                        )
                                     }
                                     _ => continue 'outer,
                                 };
 
-                                match (type_power(decl_kind), type_power(call_kind)) {
+                                match (
+                                    type_power(decl_scalar.0, decl_scalar.1),
+                                    type_power(call_scalar.0, call_scalar.1),
+                                ) {
                                     (Some(decl_power), Some(call_power))
                                         if decl_power > call_power => {}
                                     _ => continue 'outer,
@@ -865,7 +1043,7 @@ impl Program<'_> {
                                         Expression::Swizzle { .. }
                                     )
                                 {
-                                    let ty = self.module.types.fetch_or_append(Type {
+                                    let ty = self.module.types.insert(Type {
                                         name: None,
                                         inner: TypeInner::Vector { size, kind, width },
                                     });
@@ -974,9 +1152,17 @@ impl Program<'_> {
         meta: SourceMetadata,
     ) -> Result<Handle<Function>, ErrorKind> {
         ensure_block_returns(&mut function.body);
-        let stage = self.entry_points.get(&name);
+        let stage = self
+            .entry_points
+            .get(&name)
+            .or_else(|| self.pragma_entry_points.get(&name))
+            .cloned()
+            .or(match name.as_str() {
+                "main" => self.main_stage,
+                _ => None,
+            });
 
-        Ok(if let Some(&stage) = stage {
+        Ok(if let Some(stage) = stage {
             let handle = self.module.functions.append(function);
             self.entries.push((name, stage, handle));
             self.function_arg_use.push(Vec::new());
@@ -1144,7 +1330,76 @@ impl Program<'_> {
         }
     }
 
-    pub fn add_entry_points(&mut self) {
+    /// Walks the type of an entry point argument, splitting arrays into one
+    /// argument per element, each element taking the next free locations.
+    #[allow(clippy::too_many_arguments)]
+    fn arg_type_walker(
+        &self,
+        binding: Binding,
+        meta: SourceMetadata,
+        pointer: Handle<Expression>,
+        ty: Handle<Type>,
+        expressions: &mut Arena<Expression>,
+        body: &mut Block,
+        f: &mut impl FnMut(
+            Handle<Expression>,
+            Handle<Type>,
+            Binding,
+            &mut Arena<Expression>,
+            &mut Block,
+        ),
+    ) -> Result<(), ErrorKind> {
+        match (&self.module.types[ty].inner, binding) {
+            (
+                &TypeInner::Array {
+                    base,
+                    size: ArraySize::Constant(size),
+                    ..
+                },
+                Binding::Location {
+                    location,
+                    interpolation,
+                    sampling,
+                },
+            ) => {
+                let size = &self.module.constants[size];
+                // The locations of the elements can't depend on a specialization
+                let size = match size.specialization {
+                    None => size.to_array_length(),
+                    Some(_) => None,
+                }
+                .ok_or_else(|| {
+                    ErrorKind::SemanticError(
+                        meta,
+                        "Arrays of inputs and outputs must have a constant length".into(),
+                    )
+                })?;
+                let stride = self.location_count(base);
+
+                for index in 0..size {
+                    let len = expressions.len();
+                    let member_pointer = expressions.append(Expression::AccessIndex {
+                        base: pointer,
+                        index,
+                    });
+                    body.push(Statement::Emit(expressions.range_from(len)));
+
+                    let binding = Binding::Location {
+                        location: location + index * stride,
+                        interpolation,
+                        sampling,
+                    };
+
+                    self.arg_type_walker(binding, meta, member_pointer, base, expressions, body, f)?
+                }
+            }
+            (_, binding) => f(pointer, ty, binding, expressions, body),
+        }
+
+        Ok(())
+    }
+
+    pub fn add_entry_points(&mut self) -> Result<(), ErrorKind> {
         let mut function_arg_use = Vec::new();
         std::mem::swap(&mut self.function_arg_use, &mut function_arg_use);
 
@@ -1182,18 +1437,28 @@ impl Program<'_> {
                 }
 
                 let ty = self.module.global_variables[arg.handle].ty;
-                let idx = arguments.len() as u32;
+                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
 
-                arguments.push(FunctionArgument {
-                    name: arg.name.clone(),
+                self.arg_type_walker(
+                    arg.binding.clone(),
+                    arg.meta,
+                    pointer,
                     ty,
-                    binding: Some(arg.binding.clone()),
-                });
-
-                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
-                let value = expressions.append(Expression::FunctionArgument(idx));
+                    &mut expressions,
+                    &mut body,
+                    &mut |pointer, ty, binding, expressions, body| {
+                        let idx = arguments.len() as u32;
+
+                        arguments.push(FunctionArgument {
+                            name: arg.name.clone(),
+                            ty,
+                            binding: Some(binding),
+                        });
 
-                body.push(Statement::Store { pointer, value });
+                        let value = expressions.append(Expression::FunctionArgument(idx));
+                        body.push(Statement::Store { pointer, value });
+                    },
+                )?;
             }
 
             body.push(Statement::Call {
@@ -1220,25 +1485,35 @@ impl Program<'_> {
                 }
 
                 let ty = self.module.global_variables[arg.handle].ty;
+                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
 
-                members.push(StructMember {
-                    name: arg.name.clone(),
+                self.arg_type_walker(
+                    arg.binding.clone(),
+                    arg.meta,
+                    pointer,
                     ty,
-                    binding: Some(arg.binding.clone()),
-                    offset: span,
-                });
+                    &mut expressions,
+                    &mut body,
+                    &mut |pointer, ty, binding, expressions, body| {
+                        members.push(StructMember {
+                            name: arg.name.clone(),
+                            ty,
+                            binding: Some(binding),
+                            offset: span,
+                        });
 
-                span += self.module.types[ty].inner.span(&self.module.constants);
+                        span += self.module.types[ty].inner.span(&self.module.constants);
 
-                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
-                let len = expressions.len();
-                let load = expressions.append(Expression::Load { pointer });
-                body.push(Statement::Emit(expressions.range_from(len)));
-                components.push(load)
+                        let len = expressions.len();
+                        let load = expressions.append(Expression::Load { pointer });
+                        body.push(Statement::Emit(expressions.range_from(len)));
+                        components.push(load)
+                    },
+                )?;
             }
 
             let (ty, value) = if !components.is_empty() {
-                let ty = self.module.types.append(Type {
+                let ty = self.module.types.insert(Type {
                     name: None,
                     inner: TypeInner::Struct {
                         top_level: false,
@@ -1277,5 +1552,10 @@ impl Program<'_> {
                 },
             });
         }
+
+        // Qualifiers that weren't specified by the user get the glsl defaults
+        self.module.apply_common_default_interpolation();
+
+        Ok(())
     }
 }
@@ -1,16 +1,17 @@
 use super::{
     ast::Precision,
     token::{SourceMetadata, Token, TokenValue},
-    types::parse_type,
+    types::{is_integer_matrix, parse_type},
 };
 use crate::FastHashMap;
 use pp_rs::{
     pp::Preprocessor,
-    token::{Punct, Token as PPToken, TokenValue as PPTokenValue},
+    token::{PreprocessorError, Punct, Token as PPToken, TokenValue as PPTokenValue},
 };
 use std::collections::VecDeque;
 
 pub struct Lexer<'a> {
+    input: &'a str,
     pp: Preprocessor<'a>,
     tokens: VecDeque<PPToken>,
 }
@@ -18,14 +19,65 @@ pub struct Lexer<'a> {
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str, defines: &'a FastHashMap<String, String>) -> Self {
         let mut pp = Preprocessor::new(input);
+        // `#line` can't change the source string number, so it's always 0,
+        // unless the options define `__FILE__`, which replaces it.
+        // `__VERSION__` is defined once the `#version` directive is seen.
+        let _ = pp.add_define("__FILE__", "0");
         for (define, value) in defines {
             pp.add_define(define, value).unwrap(); //TODO: handle error
         }
         Lexer {
+            input,
             pp,
             tokens: Default::default(),
         }
     }
+
+    /// Returns the message of an `#error` directive, which is the rest of
+    /// the line following it (including any line continuations), and the
+    /// offset where it ends.
+    fn error_message(&self, start: usize) -> (String, usize) {
+        let rest = self.input.get(start..).unwrap_or_default();
+        let mut message = String::new();
+        let mut end = start;
+        for raw in rest.split('\n') {
+            let line = raw.trim_end_matches('\r');
+            if line.ends_with('\\') {
+                message.push_str(&line[..line.len() - 1]);
+                end += raw.len() + 1;
+            } else {
+                message.push_str(line);
+                end += line.len();
+                break;
+            }
+        }
+        (message.trim().to_string(), end.min(self.input.len()))
+    }
+
+    /// Returns the value of the floating point literal ending at `end`, and
+    /// the offset where it starts.
+    fn double_literal(&self, end: usize) -> Option<(f64, usize)> {
+        let text = self.input.get(..end)?;
+        let mut start = text.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let exponent = &text[..start];
+        let exponent = if exponent.ends_with('+') || exponent.ends_with('-') {
+            &exponent[..exponent.len() - 1]
+        } else {
+            exponent
+        };
+        if start != end && (exponent.ends_with('e') || exponent.ends_with('E')) {
+            start = exponent.len() - 1;
+        }
+        start = text[..start]
+            .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+            .len();
+
+        let literal = &text[start..];
+        if !literal.contains(&['.', 'e', 'E'][..]) {
+            return None;
+        }
+        literal.parse().ok().map(|value| (value, start))
+    }
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -36,6 +88,45 @@ impl<'a> Iterator for Lexer<'a> {
             Some(t) => t,
             None => match self.pp.next()? {
                 Ok(t) => t,
+                Err((PreprocessorError::ErrorDirective, loc)) => {
+                    // The preprocessor stops right after the directive name,
+                    // so the message is recovered from the source.
+                    let (message, end) = self.error_message(loc.end as usize);
+                    meta.start = loc.start as usize;
+                    meta.end = end;
+                    return Some(Token {
+                        value: TokenValue::ErrorDirective(message),
+                        meta,
+                    });
+                }
+                Err((PreprocessorError::NotSupported64BitLiteral, _)) => {
+                    // The preprocessor stops before the `lf` suffix of double
+                    // literals, which then follows as an identifier, so the
+                    // literal is recovered from the source.
+                    match self.pp.next() {
+                        Some(Ok(PPToken {
+                            value: PPTokenValue::Ident(ref suffix),
+                            ref location,
+                        })) if suffix == "lf" || suffix == "LF" => {
+                            if let Some((value, start)) =
+                                self.double_literal(location.start as usize)
+                            {
+                                meta.start = start;
+                                meta.end = location.end as usize;
+                                return Some(Token {
+                                    value: TokenValue::DoubleConstant(value),
+                                    meta,
+                                });
+                            }
+                        }
+                        Some(Ok(token)) => self.tokens.push_front(token),
+                        _ => {}
+                    }
+                    return Some(Token {
+                        value: TokenValue::Unknown(PreprocessorError::NotSupported64BitLiteral),
+                        meta,
+                    });
+                }
                 Err((err, loc)) => {
                     meta.start = loc.start as usize;
                     meta.end = loc.end as usize;
@@ -76,6 +167,8 @@ impl<'a> Iterator for Lexer<'a> {
                     "highp" => TokenValue::PrecisionQualifier(Precision::High),
                     "mediump" => TokenValue::PrecisionQualifier(Precision::Medium),
                     "lowp" => TokenValue::PrecisionQualifier(Precision::Low),
+                    "readonly" => TokenValue::MemoryQualifier(crate::StorageAccess::LOAD),
+                    "writeonly" => TokenValue::MemoryQualifier(crate::StorageAccess::STORE),
                     // values
                     "true" => TokenValue::BoolConstant(true),
                     "false" => TokenValue::BoolConstant(false),
@@ -97,6 +190,9 @@ impl<'a> Iterator for Lexer<'a> {
                     // types
                     "void" => TokenValue::Void,
                     "struct" => TokenValue::Struct,
+                    word if is_integer_matrix(word) => {
+                        TokenValue::IntegerMatrix(String::from(word))
+                    }
                     word => match parse_type(word) {
                         Some(t) => TokenValue::TypeName(t),
                         None => TokenValue::Identifier(String::from(word)),
@@ -159,12 +255,31 @@ impl<'a> Iterator for Lexer<'a> {
                 Punct::Question => TokenValue::Question,
             },
             PPTokenValue::Pragma(pragma) => {
+                // Only `#pragma entry` is understood, the others are ignored
+                match pragma.tokens.first() {
+                    Some(&PPToken {
+                        value: PPTokenValue::Ident(ref name),
+                        ..
+                    }) if name == "entry" => {}
+                    _ => return self.next(),
+                }
                 for t in pragma.tokens {
                     self.tokens.push_back(t);
                 }
                 TokenValue::Pragma
             }
             PPTokenValue::Version(version) => {
+                if let Some(&PPToken {
+                    value: PPTokenValue::Integer(ref version),
+                    ..
+                }) = version.tokens.first()
+                {
+                    // Can only fail if `__VERSION__` was already defined by a
+                    // previous (invalid) `#version` which the parser rejects
+                    let _ = self
+                        .pp
+                        .add_define("__VERSION__", &version.value.to_string());
+                }
                 for t in version.tokens {
                     self.tokens.push_back(t);
                 }
@@ -19,15 +19,39 @@ mod token;
 mod types;
 mod variables;
 
+/// Options for parsing GLSL.
+///
+/// A function becomes an entry point if it's listed in `entry_points`, named
+/// with a `#pragma entry(name, stage)` directive before its definition, or if
+/// it's `main` and `stage` is set.
 #[derive(Default)]
 pub struct Options {
+    /// Names of the entry points and their stages.
+    ///
+    /// This is superseded by `stage` and `#pragma entry`, and only kept for
+    /// compatibility with the existing callers.
+    #[deprecated(note = "set `stage` for `main`, or use `#pragma entry(name, stage)`")]
     pub entry_points: FastHashMap<String, ShaderStage>,
+    /// Stage of the `main` function.
+    pub stage: Option<ShaderStage>,
     pub defines: FastHashMap<String, String>,
     pub strip_unused_linkages: bool,
 }
 
+impl Options {
+    /// Options for a shader of the given stage, with `main` as its entry point.
+    pub fn from_stage(stage: ShaderStage) -> Self {
+        Options {
+            stage: Some(stage),
+            ..Default::default()
+        }
+    }
+}
+
 pub fn parse_str(source: &str, options: &Options) -> Result<Module, ParseError> {
+    #[allow(deprecated)]
     let mut program = Program::new(&options.entry_points, options.strip_unused_linkages);
+    program.main_stage = options.stage;
 
     let lex = lex::Lexer::new(source, &options.defines);
     let mut parser = parser::Parser::new(&mut program, lex);
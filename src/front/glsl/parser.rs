@@ -13,11 +13,11 @@ use crate::{
     arena::Handle,
     front::glsl::{ast::Precision, error::ExpectedToken},
     Arena, ArraySize, BinaryOperator, Block, Constant, ConstantInner, Expression, Function,
-    FunctionResult, ResourceBinding, ScalarKind, ScalarValue, Statement, StorageClass,
+    FunctionResult, ResourceBinding, ScalarKind, ScalarValue, ShaderStage, Statement, StorageClass,
     StructMember, SwitchCase, Type, TypeInner, UnaryOperator,
 };
 use core::convert::TryFrom;
-use std::{iter::Peekable, mem};
+use std::iter::Peekable;
 
 type Result<T> = std::result::Result<T, ErrorKind>;
 
@@ -57,7 +57,18 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
     }
 
     fn bump(&mut self) -> Result<Token> {
-        self.lexer.next().ok_or(ErrorKind::EndOfFile)
+        match self.lexer.next() {
+            Some(Token {
+                value: TokenValue::ErrorDirective(message),
+                meta,
+            }) => Err(ErrorKind::ErrorDirective(meta, message)),
+            Some(Token {
+                value: TokenValue::IntegerMatrix(name),
+                meta,
+            }) => Err(ErrorKind::IntegerMatrix(meta, name)),
+            Some(token) => Ok(token),
+            None => Err(ErrorKind::EndOfFile),
+        }
     }
 
     /// Returns None on the end of the file rather than an error like other methods
@@ -77,10 +88,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         self.parse_version()?;
 
         while self.lexer.peek().is_some() {
-            self.parse_external_declaration()?;
+            if self.bump_if(TokenValue::Pragma).is_some() {
+                self.parse_entry_pragma()?;
+            } else {
+                self.parse_external_declaration()?;
+            }
         }
 
-        self.program.add_entry_points();
+        self.program.add_entry_points()?;
 
         Ok(())
     }
@@ -121,6 +136,31 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         Ok(())
     }
 
+    /// Parses the `entry(name, stage)` arguments of an entry point pragma
+    fn parse_entry_pragma(&mut self) -> Result<()> {
+        // The lexer only forwards `entry` pragmas
+        self.expect_ident()?;
+        self.expect(TokenValue::LeftParen)?;
+        let (name, _) = self.expect_ident()?;
+        self.expect(TokenValue::Comma)?;
+        let (stage, meta) = self.expect_ident()?;
+        let stage = match stage.as_str() {
+            "vertex" => ShaderStage::Vertex,
+            "fragment" => ShaderStage::Fragment,
+            "compute" => ShaderStage::Compute,
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    format!("Unknown shader stage: {}", stage).into(),
+                ))
+            }
+        };
+        self.expect(TokenValue::RightParen)?;
+
+        self.program.pragma_entry_points.insert(name, stage);
+        Ok(())
+    }
+
     /// Parses an optional array_specifier returning `Ok(None)` if there is no
     /// LeftBracket
     fn parse_array_specifier(&mut self) -> Result<Option<ArraySize>> {
@@ -141,7 +181,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         let token = self.bump()?;
         let handle = match token.value {
             TokenValue::Void => None,
-            TokenValue::TypeName(ty) => Some(self.program.module.types.fetch_or_append(ty)),
+            TokenValue::TypeName(ty) => Some(self.program.module.types.insert(ty)),
             TokenValue::Struct => {
                 let ty_name = self.expect_ident()?.0;
                 self.expect(TokenValue::LeftBrace)?;
@@ -149,7 +189,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 let span = self.parse_struct_declaration_list(&mut members)?;
                 self.expect(TokenValue::RightBrace)?;
 
-                let ty = self.program.module.types.append(Type {
+                let ty = self.program.module.types.insert(Type {
                     name: Some(ty_name.clone()),
                     inner: TypeInner::Struct {
                         top_level: false,
@@ -190,7 +230,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
     fn maybe_array(&mut self, base: Handle<Type>, size: Option<ArraySize>) -> Handle<Type> {
         size.map(|size| {
-            self.program.module.types.fetch_or_append(Type {
+            self.program.module.types.insert(Type {
                 name: None,
                 inner: TypeInner::Array {
                     base,
@@ -209,6 +249,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Interpolation(_)
             | TokenValue::Sampling(_)
             | TokenValue::PrecisionQualifier(_)
+            | TokenValue::MemoryQualifier(_)
             | TokenValue::Const
             | TokenValue::In
             | TokenValue::Out
@@ -245,6 +286,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                     ),
                     TokenValue::Sampling(s) => TypeQualifier::Sampling(s),
                     TokenValue::PrecisionQualifier(p) => TypeQualifier::Precision(p),
+                    TokenValue::MemoryQualifier(access) => TypeQualifier::StorageAccess(access),
                     _ => unreachable!(),
                 },
                 token.meta,
@@ -352,6 +394,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                             "local_size_x" => TypeQualifier::WorkGroupSize(0, value),
                             "local_size_y" => TypeQualifier::WorkGroupSize(1, value),
                             "local_size_z" => TypeQualifier::WorkGroupSize(2, value),
+                            "constant_id" => TypeQualifier::SpecializationConstant(value),
                             _ => return Err(ErrorKind::UnknownLayoutQualifier(token.meta, name)),
                         },
                         token.meta,
@@ -897,7 +940,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         let span = self.parse_struct_declaration_list(&mut members)?;
         self.expect(TokenValue::RightBrace)?;
 
-        let mut ty = self.program.module.types.append(Type {
+        let mut ty = self.program.module.types.insert(Type {
             name: Some(ty_name),
             inner: TypeInner::Struct {
                 top_level: true,
@@ -911,7 +954,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Semicolon => None,
             TokenValue::Identifier(name) => {
                 if let Some(size) = self.parse_array_specifier()? {
-                    ty = self.program.module.types.fetch_or_append(Type {
+                    ty = self.program.module.types.insert(Type {
                         name: None,
                         inner: TypeInner::Array {
                             base: ty,
@@ -1017,6 +1060,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 (float.width / 8) as u8,
                 ScalarValue::Float(float.value as f64),
             ),
+            TokenValue::DoubleConstant(value) => (8, ScalarValue::Float(value)),
             TokenValue::BoolConstant(value) => (1, ScalarValue::Bool(value)),
             TokenValue::LeftParen => {
                 let expr = self.parse_expression(ctx, body)?;
@@ -1102,7 +1146,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                         meta,
                     }
                 } else {
-                    let var = match self.program.lookup_variable(ctx, body, &name)? {
+                    let var = match self.program.lookup_variable(ctx, body, &name, meta)? {
                         Some(var) => var,
                         None => return Err(ErrorKind::UnknownVariable(meta, name)),
                     };
@@ -1119,7 +1163,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 let Token { value, mut meta } = self.bump()?;
 
                 let mut handle = if let TokenValue::TypeName(ty) = value {
-                    self.program.module.types.fetch_or_append(ty)
+                    self.program.module.types.insert(ty)
                 } else {
                     unreachable!()
                 };
@@ -1151,7 +1195,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                         }
                     };
 
-                    handle = self.program.module.types.fetch_or_append(Type {
+                    handle = self.program.module.types.insert(Type {
                         name: None,
                         inner: TypeInner::Array {
                             base: handle,
@@ -1420,6 +1464,9 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
         while let TokenValue::Comma = self.expect_peek()?.value {
             self.bump()?;
+            // Only the value of the last expression is used, but the others
+            // still have their side effects
+            ctx.lower(self.program, expr, false, body)?;
             expr = self.parse_assignment(ctx, body)?;
         }
 
@@ -1561,9 +1608,12 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                                 }
                             }
 
-                            let fall_through = body.iter().any(|s| {
-                                mem::discriminant(s) == mem::discriminant(&Statement::Break)
-                            });
+                            // The case falls through to the next one unless it jumps away,
+                            // possibly at the end of a nested block like `case 0: { break; }`
+                            let fall_through = match body.last() {
+                                Some(last) => !crate::proc::terminates(last),
+                                None => true,
+                            };
 
                             cases.push(SwitchCase {
                                 value,
@@ -1656,29 +1706,79 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
                 self.expect(TokenValue::While)?;
                 self.expect(TokenValue::LeftParen)?;
-                let root = self.parse_expression(ctx, &mut loop_body)?;
+                let mut check = Block::new();
+                let root = self.parse_expression(ctx, &mut check)?;
                 self.expect(TokenValue::RightParen)?;
 
-                let expr = ctx
-                    .lower_expect(self.program, root, false, &mut loop_body)?
-                    .0;
+                let expr = ctx.lower_expect(self.program, root, false, &mut check)?.0;
                 let condition = ctx.add_expression(
                     Expression::Unary {
                         op: UnaryOperator::Not,
                         expr,
                     },
-                    &mut loop_body,
+                    &mut check,
                 );
 
-                ctx.emit_flush(&mut loop_body);
+                ctx.emit_flush(&mut check);
                 ctx.emit_start();
 
-                loop_body.push(Statement::If {
+                check.push(Statement::If {
                     condition,
                     accept: vec![Statement::Break],
                     reject: Block::new(),
                 });
 
+                if continues(&loop_body) {
+                    // The `continuing` block can't break out of the loop, so the
+                    // condition is checked at the start of every iteration but the first
+                    let ty = self.program.module.types.insert(Type {
+                        name: None,
+                        inner: TypeInner::Scalar {
+                            kind: ScalarKind::Bool,
+                            width: crate::BOOL_WIDTH,
+                        },
+                    });
+                    let first = ctx.locals.append(crate::LocalVariable {
+                        name: None,
+                        ty,
+                        init: None,
+                    });
+                    // The flag is set on every entry, as the loop may be nested in another one
+                    let value = self.program.module.constants.fetch_or_append(Constant {
+                        name: None,
+                        specialization: None,
+                        inner: ConstantInner::boolean(true),
+                    });
+                    let pointer = ctx.add_expression(Expression::LocalVariable(first), body);
+                    let value = ctx.add_expression(Expression::Constant(value), body);
+                    body.push(Statement::Store { pointer, value });
+
+                    let mut guarded = Block::new();
+                    let pointer =
+                        ctx.add_expression(Expression::LocalVariable(first), &mut guarded);
+                    let condition = ctx.add_expression(Expression::Load { pointer }, &mut guarded);
+                    ctx.emit_flush(&mut guarded);
+                    ctx.emit_start();
+                    guarded.push(Statement::If {
+                        condition,
+                        accept: Block::new(),
+                        reject: check,
+                    });
+
+                    let value = self.program.module.constants.fetch_or_append(Constant {
+                        name: None,
+                        specialization: None,
+                        inner: ConstantInner::boolean(false),
+                    });
+                    let value = ctx.add_expression(Expression::Constant(value), &mut guarded);
+                    guarded.push(Statement::Store { pointer, value });
+
+                    guarded.extend(loop_body);
+                    loop_body = guarded;
+                } else {
+                    loop_body.extend(check);
+                }
+
                 body.push(Statement::Loop {
                     body: loop_body,
                     continuing: Block::new(),
@@ -1694,7 +1794,8 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                     if self.peek_type_name() || self.peek_type_qualifier() {
                         self.parse_declaration(ctx, body, false)?;
                     } else {
-                        self.parse_expression(ctx, body)?;
+                        let expr = self.parse_expression(ctx, body)?;
+                        ctx.lower(self.program, expr, false, body)?;
                         self.expect(TokenValue::Semicolon)?;
                     }
                 }
@@ -1794,7 +1895,8 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             | TokenValue::TypeName(_)
             | TokenValue::IntConstant(_)
             | TokenValue::BoolConstant(_)
-            | TokenValue::FloatConstant(_) => {
+            | TokenValue::FloatConstant(_)
+            | TokenValue::DoubleConstant(_) => {
                 let expr = self.parse_expression(ctx, body)?;
                 ctx.lower(self.program, expr, false, body)?;
                 self.expect(TokenValue::Semicolon)?;
@@ -1802,7 +1904,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Semicolon => {
                 self.bump()?;
             }
-            _ => {}
+            _ => {
+                // Bumping also reports the tokens that carry an error
+                let token = self.bump()?;
+                return Err(ErrorKind::InvalidToken(
+                    token,
+                    vec![ExpectedToken::Statement],
+                ));
+            }
         }
 
         Ok(())
@@ -1922,6 +2031,25 @@ impl<'ctx, 'fun> DeclarationContext<'ctx, 'fun> {
     }
 }
 
+/// Return true if `block` contains a `Continue` of the loop around it.
+fn continues(block: &Block) -> bool {
+    block.iter().any(|statement| match *statement {
+        Statement::Continue => true,
+        Statement::Block(ref inner) => continues(inner),
+        Statement::If {
+            ref accept,
+            ref reject,
+            ..
+        } => continues(accept) || continues(reject),
+        Statement::Switch {
+            ref cases,
+            ref default,
+            ..
+        } => cases.iter().any(|case| continues(&case.body)) || continues(default),
+        _ => false,
+    })
+}
+
 fn binding_power(value: &TokenValue) -> Option<(u8, u8)> {
     Some(match *value {
         TokenValue::LogicalOr => (1, 2),
@@ -159,6 +159,49 @@ fn control_flow() {
     .unwrap();
 }
 
+#[test]
+fn switch_fall_through() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            int x;
+            switch (5) {
+                case 1: {
+                    x = 1;
+                    break;
+                }
+                case 2:
+                    x = 2;
+                case 3: {
+                    x = 3;
+                }
+                case 4:
+                    x = 4;
+                    return;
+            }
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let function = &program.module.functions.iter().next().unwrap().1;
+    let cases = function
+        .body
+        .iter()
+        .find_map(|statement| match *statement {
+            crate::Statement::Switch { ref cases, .. } => Some(cases),
+            _ => None,
+        })
+        .unwrap();
+    let fall_through: Vec<_> = cases.iter().map(|case| case.fall_through).collect();
+    assert_eq!(fall_through, [false, true, true, false]);
+}
+
 #[test]
 fn declarations() {
     let mut entry_points = crate::FastHashMap::default();
@@ -178,6 +221,30 @@ fn declarations() {
     )
     .unwrap();
 
+    let _program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in vec4 colors[3];
+        layout(location = 3) in vec2 v_uv;
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) in vec4 colors[3];
+            layout(location = 2) in vec2 v_uv;
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(..)
+    ));
+
     let _program = parse_program(
         r#"
         #version 450
@@ -279,6 +346,153 @@ fn textures() {
     .unwrap();
 }
 
+#[test]
+fn arrayed_textures() {
+    use crate::{ImageClass, ImageDimension, TypeInner};
+
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform texture1DArray t1;
+        layout(set = 0, binding = 1) uniform texture2DArray t2;
+        layout(set = 0, binding = 2) uniform texture2DMS t3;
+        layout(set = 0, binding = 3) uniform itexture2DMSArray t4;
+        layout(set = 0, binding = 4) uniform textureCubeArray t5;
+        void main() {}
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    let images: Vec<_> = module
+        .global_variables
+        .iter()
+        .filter_map(|(_, var)| match module.types[var.ty].inner {
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class: ImageClass::Sampled { multi, .. },
+            } => Some((dim, arrayed, multi)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        images,
+        [
+            (ImageDimension::D1, true, false),
+            (ImageDimension::D2, true, false),
+            (ImageDimension::D2, false, true),
+            (ImageDimension::D2, true, true),
+            (ImageDimension::Cube, true, false),
+        ]
+    );
+}
+
+#[test]
+fn multisampled_texel_fetch() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform texture2DMS tex;
+        layout(set = 0, binding = 1) uniform sampler tex_sampler;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = texelFetch(sampler2DMS(tex, tex_sampler), ivec2(1, 2), 3);
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    // The body of `main` is written to a regular function called by the entry point
+    let (function, index) = module
+        .functions
+        .iter()
+        .find_map(|(_, function)| {
+            function
+                .expressions
+                .iter()
+                .find_map(|(_, expr)| match *expr {
+                    crate::Expression::ImageLoad { index, .. } => index,
+                    _ => None,
+                })
+                .map(|index| (function, index))
+        })
+        .unwrap();
+    match function.expressions[index] {
+        crate::Expression::Constant(constant) => match module.constants[constant].inner {
+            crate::ConstantInner::Scalar {
+                value: crate::ScalarValue::Sint(3),
+                ..
+            } => {}
+            ref other => panic!("Unexpected sample index {:?}", other),
+        },
+        ref other => panic!("Unexpected sample index {:?}", other),
+    }
+}
+
+#[test]
+fn cube_array_sampling() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform textureCubeArray tex;
+        layout(set = 0, binding = 1) uniform sampler tex_sampler;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = texture(samplerCubeArray(tex, tex_sampler), vec4(1.0, 0.0, 0.0, 2.0));
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    let function = module
+        .functions
+        .iter()
+        .map(|(_, function)| function)
+        .find(|function| {
+            function
+                .expressions
+                .iter()
+                .any(|(_, expr)| matches!(*expr, crate::Expression::ImageSample { .. }))
+        })
+        .unwrap();
+    let (coordinate, array_index) = function
+        .expressions
+        .iter()
+        .find_map(|(_, expr)| match *expr {
+            crate::Expression::ImageSample {
+                coordinate,
+                array_index,
+                ..
+            } => Some((coordinate, array_index)),
+            _ => None,
+        })
+        .unwrap();
+    match function.expressions[coordinate] {
+        crate::Expression::Swizzle {
+            size: crate::VectorSize::Tri,
+            ..
+        } => {}
+        ref other => panic!("Unexpected coordinate {:?}", other),
+    }
+    match function.expressions[array_index.unwrap()] {
+        crate::Expression::As {
+            kind: crate::ScalarKind::Sint,
+            ..
+        } => {}
+        ref other => panic!("Unexpected array index {:?}", other),
+    }
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .unwrap();
+}
+
 #[test]
 fn functions() {
     let mut entry_points = crate::FastHashMap::default();
@@ -464,6 +678,52 @@ fn constants() {
     assert!(constants.next().is_none());
 }
 
+#[test]
+fn double_literals() {
+    use crate::{ConstantInner, ScalarValue};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        const double a = 0.1lf;
+        const double b = 2.lf+1.5e-3LF;
+        const float c = 1e4;
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let values: Vec<_> = program
+        .module
+        .constants
+        .iter()
+        .map(|(_, constant)| match constant.inner {
+            ConstantInner::Scalar {
+                width,
+                value: ScalarValue::Float(value),
+            } => (width, value),
+            ref other => panic!("Unexpected constant {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [(8, 0.1), (8, 2.0), (8, 1.5e-3), (8, 2.0015), (4, 1e4)]
+    );
+
+    parse_program(
+        r#"
+        #  version 450
+        const double a = 1.0Lf;
+        "#,
+        &entry_points,
+    )
+    .err()
+    .unwrap();
+}
+
 #[test]
 fn function_overloading() {
     let mut entry_points = crate::FastHashMap::default();
@@ -630,6 +890,51 @@ fn structs() {
     .unwrap_err();
 }
 
+#[test]
+fn matrices() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #  version 450
+        void main() {
+            mat2x3 a = mat2x3(1.0);
+            dmat4 b = dmat4(1.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    // glsl doesn't have integer or boolean matrices
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                imat2 a;
+            }
+            "#,
+            &entry_points,
+        )
+        .unwrap_err(),
+        ErrorKind::IntegerMatrix(SourceMetadata { start: 70, end: 75 }, "imat2".into())
+    );
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            uniform umat3x4 m;
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .unwrap_err(),
+        ErrorKind::IntegerMatrix(SourceMetadata { start: 48, end: 55 }, "umat3x4".into())
+    );
+}
+
 #[test]
 fn swizzles() {
     let mut entry_points = crate::FastHashMap::default();
@@ -691,3 +996,608 @@ fn vector_indexing() {
     )
     .unwrap();
 }
+
+#[test]
+fn interpolation() {
+    use crate::{Binding, Interpolation, Sampling};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        layout(location = 0) in vec4 smooth_input;
+        layout(location = 1) flat in vec2 flat_input;
+        layout(location = 2) noperspective centroid in float linear_input;
+        layout(location = 3) in int int_input;
+        layout(location = 0) out vec4 color;
+        void main() {
+            color = smooth_input + vec4(flat_input, linear_input, float(int_input));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let function = &program.module.entry_points[0].function;
+    let bindings = function
+        .arguments
+        .iter()
+        .map(|arg| match arg.binding {
+            Some(Binding::Location {
+                interpolation,
+                sampling,
+                ..
+            }) => (interpolation, sampling),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bindings,
+        [
+            (Some(Interpolation::Perspective), Some(Sampling::Center)),
+            (Some(Interpolation::Flat), None),
+            (Some(Interpolation::Linear), Some(Sampling::Centroid)),
+            (Some(Interpolation::Flat), None),
+        ]
+    );
+
+    let result = function.result.as_ref().unwrap();
+    match program.module.types[result.ty].inner {
+        crate::TypeInner::Struct { ref members, .. } => assert_eq!(
+            members[0].binding,
+            Some(Binding::Location {
+                location: 0,
+                interpolation: None,
+                sampling: None,
+            })
+        ),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn sample_builtins() {
+    use crate::{Binding, BuiltIn};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        layout(location = 0) out vec4 color;
+        void main() {
+            if (!gl_HelperInvocation) {
+                color = vec4(gl_SamplePosition, 0.0, 1.0);
+            }
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let function = &program.module.entry_points[0].function;
+    let bindings = function
+        .arguments
+        .iter()
+        .map(|arg| arg.binding.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bindings,
+        [
+            Some(Binding::BuiltIn(BuiltIn::HelperInvocation)),
+            Some(Binding::BuiltIn(BuiltIn::SamplePosition)),
+        ]
+    );
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&program.module)
+    .unwrap();
+}
+
+#[test]
+fn view_index() {
+    use crate::valid::{Capabilities, ValidationFlags, Validator};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        layout(location = 0) out vec4 color;
+        void main() {
+            color = vec4(float(gl_ViewIndex));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert!(
+        Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&program.module)
+            .is_err()
+    );
+    Validator::new(ValidationFlags::all(), Capabilities::MULTIVIEW)
+        .validate(&program.module)
+        .unwrap();
+}
+
+#[test]
+fn entry_point_discovery() {
+    let source = r#"
+        #  version 450
+        #pragma entry(vs_main, vertex)
+        #pragma optimize(on)
+        void vs_main() {
+            gl_Position = vec4(1.0);
+        }
+        void main() {}
+        "#;
+
+    let module =
+        super::parse_str(source, &super::Options::from_stage(ShaderStage::Fragment)).unwrap();
+    let entry_points = module
+        .entry_points
+        .iter()
+        .map(|ep| (ep.name.as_str(), ep.stage))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entry_points,
+        [
+            ("vs_main", ShaderStage::Vertex),
+            ("main", ShaderStage::Fragment)
+        ]
+    );
+
+    // without a stage, `main` is a regular function
+    let module = super::parse_str(source, &super::Options::default()).unwrap();
+    assert_eq!(module.entry_points.len(), 1);
+
+    let error = super::parse_str(
+        "#version 450\n#pragma entry(main, geometry)\nvoid main() {}",
+        &super::Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 33, end: 41 },
+            "Unknown shader stage: geometry".into()
+        )
+    );
+}
+
+#[test]
+fn global_initializers() {
+    let error = super::parse_str(
+        "#version 450\nlayout(set = 0, binding = 0) uniform float scale = 1.0;\nvoid main() {}",
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap_err();
+    match error.kind {
+        ErrorKind::SemanticError(_, ref message) => {
+            assert!(message.contains("initializer"), "{}", message)
+        }
+        ref other => panic!("Unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn preprocessor_directives() {
+    let source = r#"#version 450
+        #pragma optimize(off)
+        #pragma debug(on)
+        #pragma STDGL invariant(all)
+        #if __VERSION__ != 450 || __FILE__ != 0 || __LINE__ != 5
+        #error unexpected predefined macros
+        #endif
+        #define SATURATE(x) \
+            clamp(x, \
+                  0.0, 1.0)
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = vec4(SATURATE(2.0));
+        }
+        "#;
+    let module =
+        super::parse_str(source, &super::Options::from_stage(ShaderStage::Fragment)).unwrap();
+    assert_eq!(module.entry_points.len(), 1);
+
+    let source = "#version 450\n#ifndef USE_FOG\n#error USE_FOG must be \\\n  defined\n#endif\n";
+    let error =
+        super::parse_str(source, &super::Options::from_stage(ShaderStage::Fragment)).unwrap_err();
+    assert_eq!(
+        error.kind,
+        ErrorKind::ErrorDirective(
+            SourceMetadata { start: 30, end: 63 },
+            "USE_FOG must be   defined".into()
+        )
+    );
+    assert_eq!(error.to_string(), "#error USE_FOG must be   defined");
+
+    // Predefined macros of the options win over the built-in ones
+    let mut options = super::Options::from_stage(ShaderStage::Fragment);
+    options.defines.insert("__FILE__".into(), "3".into());
+    let source =
+        "#version 450\n#if __FILE__ != 3\n#error unexpected file\n#endif\nvoid main() {}\n";
+    let module = super::parse_str(source, &options).unwrap();
+    assert_eq!(module.entry_points.len(), 1);
+}
+
+#[test]
+fn memory_qualifiers() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) readonly buffer Data { uint values[]; };
+        layout(set = 0, binding = 1) writeonly buffer Output { uint total; };
+        layout(set = 0, binding = 2) readonly writeonly buffer Query { uint unused[]; };
+        layout(set = 0, binding = 3) buffer Counter { uint count; };
+        layout(local_size_x = 1) in;
+        void main() {
+            total = values[0];
+            count += 1u;
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Compute),
+    )
+    .unwrap();
+    let accesses: Vec<_> = module
+        .global_variables
+        .iter()
+        .map(|(_, var)| var.storage_access)
+        .collect();
+    assert_eq!(
+        accesses,
+        [
+            crate::StorageAccess::LOAD,
+            crate::StorageAccess::STORE,
+            crate::StorageAccess::empty(),
+            crate::StorageAccess::all(),
+        ]
+    );
+
+    let error = super::parse_str(
+        "#version 450\nlayout(set = 0, binding = 0) readonly uniform sampler s;\nvoid main() {}",
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap_err();
+    match error.kind {
+        ErrorKind::SemanticError(_, ref message) => {
+            assert!(message.contains("Memory qualifiers"), "{}", message)
+        }
+        ref other => panic!("Unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn texture_offsets() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(location = 0) in vec2 v_uv;
+        layout(location = 0) out vec4 o_color;
+        layout(set = 1, binding = 1) uniform texture2D tex;
+        layout(set = 1, binding = 2) uniform sampler tex_sampler;
+        void main() {
+            o_color = textureOffset(sampler2D(tex, tex_sampler), v_uv, ivec2(1, -1));
+            o_color += textureLodOffset(sampler2D(tex, tex_sampler), v_uv, 0.0, ivec2(2));
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    let offsets: Vec<_> = module
+        .functions
+        .iter()
+        .flat_map(|(_, function)| function.expressions.iter())
+        .filter_map(|(_, expr)| match *expr {
+            crate::Expression::ImageSample { offset, .. } => Some(offset.unwrap()),
+            _ => None,
+        })
+        .map(|offset| match module.constants[offset].inner {
+            crate::ConstantInner::Composite { ref components, .. } => components
+                .iter()
+                .map(|&component| match module.constants[component].inner {
+                    crate::ConstantInner::Scalar {
+                        value: crate::ScalarValue::Sint(value),
+                        ..
+                    } => value,
+                    ref other => panic!("Unexpected offset component {:?}", other),
+                })
+                .collect::<Vec<_>>(),
+            ref other => panic!("Unexpected offset {:?}", other),
+        })
+        .collect();
+    assert_eq!(offsets, [vec![1, -1], vec![2, 2]]);
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
+
+#[test]
+fn vector_scalar_operands() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(location = 0) flat in ivec3 v_index;
+        layout(location = 0) out ivec3 o_index;
+        void main() {
+            ivec3 index = v_index % 4 + (v_index & 1) - (2 ^ v_index);
+            o_index = (1 | index) / 2 - (index << 1);
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
+
+#[test]
+fn relational_functions() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(location = 0) in vec4 a;
+        layout(location = 1) in vec4 b;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            bvec4 less = lessThan(a, b);
+            bvec4 other = not(equal(a, b));
+            if (any(not(less)) && all(other)) {
+                o_color = a;
+            } else {
+                o_color = b;
+            }
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // the body of `main` is in a function called by the entry point
+    let function = module.functions.iter().next().unwrap().1;
+    let nots = function
+        .expressions
+        .iter()
+        .filter(|&(_, expression)| match *expression {
+            crate::Expression::Unary {
+                op: crate::UnaryOperator::Not,
+                ..
+            } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(nots, 2);
+}
+
+#[test]
+fn not_of_non_boolean_vectors() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Fragment);
+
+    for &arg in ["true", "ivec2(1)", "vec4(1.0)"].iter() {
+        let source = format!(
+            r#"
+            #version 450
+            void main() {{
+                bool b = any(not({}));
+            }}
+            "#,
+            arg
+        );
+        match parse_program(&source, &entry_points).err().unwrap() {
+            ErrorKind::SemanticError(_, message) => {
+                assert_eq!(message, "not only accepts boolean vectors")
+            }
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn derivative_functions() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(location = 0) in vec2 uv;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            vec2 coarse = dFdxCoarse(uv) + dFdyCoarse(uv);
+            vec2 fine = dFdxFine(uv) + fwidthFine(uv);
+            o_color = vec4(coarse, fine) * fwidth(uv.x);
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let function = module.functions.iter().next().unwrap().1;
+    let mut derivatives = function
+        .expressions
+        .iter()
+        .filter_map(|(_, expression)| match *expression {
+            crate::Expression::Derivative { axis, ctrl, .. } => Some((axis, ctrl)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    derivatives.sort();
+    assert_eq!(
+        derivatives,
+        [
+            (crate::DerivativeAxis::X, crate::DerivativeControl::Coarse),
+            (crate::DerivativeAxis::X, crate::DerivativeControl::Fine),
+            (crate::DerivativeAxis::Y, crate::DerivativeControl::Coarse),
+            (crate::DerivativeAxis::Width, crate::DerivativeControl::Fine),
+            (crate::DerivativeAxis::Width, crate::DerivativeControl::None),
+        ]
+    );
+}
+
+#[test]
+fn nested_do_while() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(location = 0) out int o_count;
+        void main() {
+            int c = 0;
+            for (int i = 0; i < 2; i++) {
+                do {
+                    c += i;
+                    if (c == 3) {
+                        continue;
+                    }
+                } while (c < 0);
+            }
+            o_count = c;
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Fragment),
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // the do-while is the loop nested in the `for` loop
+    fn find_inner_loop(
+        block: &[crate::Statement],
+        depth: usize,
+    ) -> Option<(&[crate::Statement], usize)> {
+        for (index, statement) in block.iter().enumerate() {
+            match *statement {
+                crate::Statement::Loop { .. } if depth == 1 => return Some((block, index)),
+                crate::Statement::Loop { ref body, .. } => return find_inner_loop(body, depth + 1),
+                crate::Statement::Block(ref inner) => {
+                    if let Some(found) = find_inner_loop(inner, depth) {
+                        return Some(found);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+    let function = module.functions.iter().next().unwrap().1;
+    let (block, index) = find_inner_loop(&function.body, 0).unwrap();
+    // the first iteration flag is set right before every entry into the loop
+    match block[index - 1] {
+        crate::Statement::Store { value, .. } => match function.expressions[value] {
+            crate::Expression::Constant(constant) => assert_eq!(
+                module.constants[constant].inner,
+                crate::ConstantInner::boolean(true)
+            ),
+            ref other => panic!("Unexpected value {:?}", other),
+        },
+        ref other => panic!("Unexpected statement {:?}", other),
+    }
+}
+
+#[test]
+fn varying_array_lengths() {
+    let source = r#"
+        #version 450
+        layout(location = 0) in vec4 colors[-2];
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = colors[0];
+        }
+    "#;
+    let options = super::Options::from_stage(ShaderStage::Fragment);
+    let check = |source| match super::parse_str(source, &options).err().unwrap().kind {
+        ErrorKind::SemanticError(_, message) => {
+            assert_eq!(
+                message,
+                "Arrays of inputs and outputs must have a constant length"
+            )
+        }
+        other => panic!("Unexpected error {:?}", other),
+    };
+    check(source);
+
+    // The locations of the elements can't depend on a specialization
+    check(
+        r#"
+        #version 450
+        layout(constant_id = 0) const int COUNT = 2;
+        layout(location = 0) in vec4 colors[COUNT];
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = colors[0];
+        }
+    "#,
+    );
+}
+
+#[test]
+fn empty_composite_equality() {
+    let module = super::parse_str(
+        r#"
+        #version 450
+        layout(location = 0) out vec4 o;
+        void main() {
+            float x[0];
+            float y[0];
+            o = vec4(float(x == y), float(x != y), 0.0, 0.0);
+        }
+        "#,
+        &super::Options::from_stage(ShaderStage::Vertex),
+    )
+    .unwrap();
+
+    // There is nothing to compare, so the results are constants
+    let bools: Vec<_> = module
+        .constants
+        .iter()
+        .filter_map(|(_, constant)| match constant.inner {
+            crate::ConstantInner::Scalar {
+                value: crate::ScalarValue::Bool(value),
+                ..
+            } => Some(value),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(bools, [true, false]);
+    let function = &module.functions.iter().next().unwrap().1;
+    assert!(!function.expressions.iter().any(|(_, expr)| match *expr {
+        crate::Expression::Binary { .. } => true,
+        _ => false,
+    }));
+}
@@ -1,7 +1,7 @@
 pub use pp_rs::token::{Float, Integer, PreprocessorError};
 
 use super::ast::Precision;
-use crate::{Interpolation, Sampling, Type};
+use crate::{Interpolation, Sampling, StorageAccess, Type};
 use std::{fmt, ops::Range};
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -39,6 +39,10 @@ pub struct Token {
 #[derive(Debug, PartialEq)]
 pub enum TokenValue {
     Unknown(PreprocessorError),
+    /// An `#error` directive with the rest of its line as the message
+    ErrorDirective(String),
+    /// The name of a matrix of integers, like `imat2`, which GLSL doesn't have
+    IntegerMatrix(String),
     Identifier(String),
 
     Extension,
@@ -46,6 +50,7 @@ pub enum TokenValue {
     Pragma,
 
     FloatConstant(Float),
+    DoubleConstant(f64),
     IntConstant(Integer),
     BoolConstant(bool),
 
@@ -60,6 +65,7 @@ pub enum TokenValue {
     Sampling(Sampling),
     Precision,
     PrecisionQualifier(Precision),
+    MemoryQualifier(StorageAccess),
 
     Continue,
     Break,
@@ -1,5 +1,12 @@
 use crate::{ImageClass, ImageDimension, ScalarKind, Type, TypeInner, VectorSize};
 
+/// Returns whether `type_name` would name a matrix of signed or unsigned integers,
+/// like `imat2` or `umat3x4`, so that it can be reported as unsupported.
+pub fn is_integer_matrix(type_name: &str) -> bool {
+    (type_name.starts_with("imat") || type_name.starts_with("umat"))
+        && parse_type(&type_name[1..]).is_some()
+}
+
 pub fn parse_type(type_name: &str) -> Option<Type> {
     match type_name {
         "bool" => Some(Type {
@@ -83,7 +90,11 @@ pub fn parse_type(type_name: &str) -> Option<Type> {
 
                 let kind = iter.next()?;
                 let size = iter.next()?;
-                let (_, width) = kind_width_parse(kind)?;
+                let (kind, width) = kind_width_parse(kind)?;
+                // glsl only has floating point matrices, `imat` and friends aren't types
+                if kind != ScalarKind::Float {
+                    return None;
+                }
 
                 let (columns, rows) = if let Some(size) = size_parse(size) {
                     (size, size)
@@ -125,14 +136,14 @@ pub fn parse_type(type_name: &str) -> Option<Type> {
 
                 let (dim, arrayed, class) = match size {
                     "1D" => (ImageDimension::D1, false, sampled(false)),
-                    "1DArray" => (ImageDimension::D1, false, sampled(false)),
+                    "1DArray" => (ImageDimension::D1, true, sampled(false)),
                     "2D" => (ImageDimension::D2, false, sampled(false)),
-                    "2DArray" => (ImageDimension::D2, false, sampled(false)),
-                    "2DMS" => (ImageDimension::D2, true, sampled(true)),
+                    "2DArray" => (ImageDimension::D2, true, sampled(false)),
+                    "2DMS" => (ImageDimension::D2, false, sampled(true)),
                     "2DMSArray" => (ImageDimension::D2, true, sampled(true)),
                     "3D" => (ImageDimension::D3, false, sampled(false)),
                     "Cube" => (ImageDimension::Cube, false, sampled(false)),
-                    "CubeArray" => (ImageDimension::D2, false, sampled(false)),
+                    "CubeArray" => (ImageDimension::Cube, true, sampled(false)),
                     _ => return None,
                 };
 
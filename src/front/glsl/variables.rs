@@ -1,7 +1,7 @@
 use crate::{
-    Binding, Block, BuiltIn, Constant, Expression, GlobalVariable, Handle, ImageClass,
-    Interpolation, LocalVariable, ScalarKind, StorageAccess, StorageClass, SwizzleComponent, Type,
-    TypeInner, VectorSize,
+    ArraySize, Binding, Block, BuiltIn, Constant, Expression, GlobalVariable, Handle, ImageClass,
+    LocalVariable, ScalarKind, StorageAccess, StorageClass, SwizzleComponent, Type, TypeInner,
+    VectorSize,
 };
 
 use super::ast::*;
@@ -37,6 +37,7 @@ impl Program<'_> {
         ctx: &mut Context,
         body: &mut Block,
         name: &str,
+        meta: SourceMetadata,
     ) -> Result<Option<VariableReference>, ErrorKind> {
         if let Some(local_var) = ctx.lookup_local_var(name) {
             return Ok(Some(local_var));
@@ -46,10 +47,7 @@ impl Program<'_> {
         }
 
         let mut add_builtin = |inner, builtin, mutable, prologue, storage| {
-            let ty = self
-                .module
-                .types
-                .fetch_or_append(Type { name: None, inner });
+            let ty = self.module.types.insert(Type { name: None, inner });
 
             let handle = self.module.global_variables.append(GlobalVariable {
                 name: Some(name.into()),
@@ -67,6 +65,7 @@ impl Program<'_> {
                 handle,
                 prologue,
                 storage,
+                meta,
             });
 
             self.global_variables.push((
@@ -145,6 +144,27 @@ impl Program<'_> {
                 PrologueStage::VERTEX,
                 StorageQualifier::Input,
             ),
+            "gl_ViewIndex" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::ViewIndex,
+                false,
+                PrologueStage::VERTEX | PrologueStage::FRAGMENT,
+                StorageQualifier::Input,
+            ),
+            "gl_NumWorkGroups" => add_builtin(
+                TypeInner::Vector {
+                    size: VectorSize::Tri,
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::NumWorkGroups,
+                false,
+                PrologueStage::COMPUTE,
+                StorageQualifier::Input,
+            ),
             "gl_GlobalInvocationID" => add_builtin(
                 TypeInner::Vector {
                     size: VectorSize::Tri,
@@ -176,6 +196,27 @@ impl Program<'_> {
                 PrologueStage::FRAGMENT,
                 StorageQualifier::Input,
             ),
+            "gl_SamplePosition" => add_builtin(
+                TypeInner::Vector {
+                    size: VectorSize::Bi,
+                    kind: ScalarKind::Float,
+                    width: 4,
+                },
+                BuiltIn::SamplePosition,
+                false,
+                PrologueStage::FRAGMENT,
+                StorageQualifier::Input,
+            ),
+            "gl_HelperInvocation" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Bool,
+                    width: crate::BOOL_WIDTH,
+                },
+                BuiltIn::HelperInvocation,
+                false,
+                PrologueStage::FRAGMENT,
+                StorageQualifier::Input,
+            ),
             _ => Ok(None),
         }
     }
@@ -323,6 +364,23 @@ impl Program<'_> {
         }
     }
 
+    /// Returns the number of consecutive locations taken by a varying of the
+    /// given type.
+    pub fn location_count(&self, ty: Handle<Type>) -> u32 {
+        match self.module.types[ty].inner {
+            TypeInner::Array {
+                base,
+                size: ArraySize::Constant(size),
+                ..
+            } => {
+                let size = self.module.constants[size].to_array_length().unwrap_or(1);
+                size * self.location_count(base)
+            }
+            TypeInner::Matrix { columns, .. } => columns as u32,
+            _ => 1,
+        }
+    }
+
     pub fn add_global_var(
         &mut self,
         VarDeclaration {
@@ -340,6 +398,8 @@ impl Program<'_> {
         let mut sampling = None;
         let mut layout = None;
         let mut precision = None;
+        let mut access = None;
+        let mut specialization = None;
 
         for &(ref qualifier, meta) in qualifiers {
             match *qualifier {
@@ -394,6 +454,16 @@ impl Program<'_> {
                     meta,
                     "Cannot use more than one precision qualifier per declaration"
                 ),
+                TypeQualifier::SpecializationConstant(id) => qualifier_arm!(
+                    id,
+                    specialization,
+                    meta,
+                    "Cannot use more than one constant_id per declaration"
+                ),
+                // `readonly writeonly` only allows queries
+                TypeQualifier::StorageAccess(a) => {
+                    access = Some(access.unwrap_or(StorageAccess::all()) & a)
+                }
                 _ => {
                     return Err(ErrorKind::SemanticError(
                         meta,
@@ -424,6 +494,13 @@ impl Program<'_> {
             ));
         }
 
+        if specialization.is_some() && storage != StorageQualifier::Const {
+            return Err(ErrorKind::SemanticError(
+                meta,
+                "constant_id can only be used on constants".into(),
+            ));
+        }
+
         if let Some(location) = location {
             let input = storage == StorageQualifier::Input;
             let prologue = if input {
@@ -431,13 +508,30 @@ impl Program<'_> {
             } else {
                 PrologueStage::empty()
             };
-            let interpolation = self.module.types[ty].inner.scalar_kind().map(|kind| {
-                if let ScalarKind::Float = kind {
-                    Interpolation::Perspective
-                } else {
-                    Interpolation::Flat
+            let count = self.location_count(ty);
+            for other in self.entry_args.iter() {
+                let other_location = match other.binding {
+                    Binding::Location { location, .. } if other.storage == storage => location,
+                    _ => continue,
+                };
+                let other_count =
+                    self.location_count(self.module.global_variables[other.handle].ty);
+
+                if location < other_location + other_count && other_location < location + count {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!(
+                            "Locations {}..{} overlap with the locations {}..{} of \"{}\"",
+                            location,
+                            location + count,
+                            other_location,
+                            other_location + other_count,
+                            other.name.as_deref().unwrap_or_default(),
+                        )
+                        .into(),
+                    ));
                 }
-            });
+            }
 
             let handle = self.module.global_variables.append(GlobalVariable {
                 name: name.clone(),
@@ -459,6 +553,7 @@ impl Program<'_> {
                 handle,
                 prologue,
                 storage,
+                meta,
             });
 
             if let Some(name) = name {
@@ -477,6 +572,25 @@ impl Program<'_> {
             let init = init.ok_or_else(|| {
                 ErrorKind::SemanticError(meta, "const values must have an initializer".into())
             })?;
+            let init = match specialization {
+                // The initializer may be shared with other constants,
+                // so the specialization constant gets its own copy
+                Some(id) => {
+                    let inner = self.module.constants[init].inner.clone();
+                    if let crate::ConstantInner::Composite { .. } = inner {
+                        return Err(ErrorKind::SemanticError(
+                            meta,
+                            "constant_id can only be used on scalar constants".into(),
+                        ));
+                    }
+                    self.module.constants.append(Constant {
+                        name: name.clone(),
+                        specialization: Some(id),
+                        inner,
+                    })
+                }
+                None => init,
+            };
             if let Some(name) = name {
                 self.global_variables.push((
                     name,
@@ -491,32 +605,39 @@ impl Program<'_> {
         }
 
         let (class, storage_access) = match self.module.types[ty].inner {
-            TypeInner::Image { class, .. } => (
-                StorageClass::Handle,
-                if let ImageClass::Storage(_) = class {
-                    // TODO: Add support for qualifiers such as readonly,
-                    // writeonly and readwrite
-                    StorageAccess::all()
-                } else {
-                    StorageAccess::empty()
-                },
+            TypeInner::Image {
+                class: ImageClass::Storage(_),
+                ..
+            } => (StorageClass::Handle, access.unwrap_or(StorageAccess::all())),
+            _ if storage == StorageQualifier::StorageClass(StorageClass::Storage) => (
+                StorageClass::Storage,
+                access.unwrap_or(StorageAccess::all()),
             ),
-            TypeInner::Sampler { .. } => (StorageClass::Handle, StorageAccess::empty()),
-            _ => {
-                if let StorageQualifier::StorageClass(StorageClass::Storage) = storage {
-                    (StorageClass::Storage, StorageAccess::all())
-                } else {
-                    (
-                        match storage {
-                            StorageQualifier::StorageClass(class) => class,
-                            _ => StorageClass::Private,
-                        },
-                        StorageAccess::empty(),
-                    )
-                }
+            _ if access.is_some() => {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    "Memory qualifiers can only be used in storage images and buffers".into(),
+                ))
+            }
+            TypeInner::Image { .. } | TypeInner::Sampler { .. } => {
+                (StorageClass::Handle, StorageAccess::empty())
             }
+            _ => (
+                match storage {
+                    StorageQualifier::StorageClass(class) => class,
+                    _ => StorageClass::Private,
+                },
+                StorageAccess::empty(),
+            ),
         };
 
+        if init.is_some() && class != StorageClass::Private {
+            return Err(ErrorKind::SemanticError(
+                meta,
+                "Only global variables without a storage qualifier can have an initializer".into(),
+            ));
+        }
+
         let handle = self.module.global_variables.append(GlobalVariable {
             name: name.clone(),
             class,
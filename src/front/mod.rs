@@ -1,5 +1,6 @@
 //! Parsers which load shaders into memory.
 
+pub mod builder;
 #[cfg(feature = "glsl-in")]
 pub mod glsl;
 #[cfg(feature = "spv-in")]
@@ -8,7 +9,7 @@ pub mod spv;
 pub mod wgsl;
 
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
     proc::{ResolveContext, ResolveError, TypeResolution},
 };
 use std::ops;
@@ -50,6 +51,13 @@ impl super::ConstantInner {
 }
 
 /// Helper processor that derives the types of all expressions.
+///
+/// Resolutions are cached per expression handle, so front ends can call
+/// [`grow`](Typifier::grow) after appending expressions and only the new
+/// ones get resolved. When the expressions are rolled back with
+/// [`Arena::truncate`], [`truncate`](Typifier::truncate) forgets their
+/// resolutions, so that re-parsing a function body only resolves the
+/// expressions appended again.
 #[derive(Debug)]
 pub struct Typifier {
     resolutions: Vec<TypeResolution>,
@@ -65,11 +73,13 @@ impl Typifier {
     pub fn get<'a>(
         &'a self,
         expr_handle: Handle<crate::Expression>,
-        types: &'a Arena<crate::Type>,
+        types: &'a UniqueArena<crate::Type>,
     ) -> &'a crate::TypeInner {
         self.resolutions[expr_handle.index()].inner_with(types)
     }
 
+    /// Resolve the types of all the expressions up to `expr_handle`
+    /// that haven't been resolved yet.
     pub fn grow(
         &mut self,
         expr_handle: Handle<crate::Expression>,
@@ -85,6 +95,14 @@ impl Typifier {
         }
         Ok(())
     }
+
+    /// Forget the resolutions of the expressions past the first `length` ones.
+    ///
+    /// The remaining resolutions may refer to types of the module, so the
+    /// type arena must not be rolled back past the types they use.
+    pub fn truncate(&mut self, length: usize) {
+        self.resolutions.truncate(length);
+    }
 }
 
 impl ops::Index<Handle<crate::Expression>> for Typifier {
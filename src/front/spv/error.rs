@@ -109,5 +109,20 @@ pub enum Error {
     InvalidBarrierScope(spirv::Word),
     #[error("invalid barrier memory semantics %{0}")]
     InvalidBarrierMemorySemantics(spirv::Word),
+    #[error("invalid gather component %{0}")]
+    InvalidGatherComponent(spirv::Word),
+    #[error("forward pointer %{0} completes to an already declared type")]
+    DuplicateForwardPointer(spirv::Word),
+    #[error("forward pointer %{0} is never completed")]
+    IncompleteForwardPointer(spirv::Word),
     // incomplete implementation errors
 }
+
+impl Error {
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic).
+    ///
+    /// SPIR-V has no source text, so the diagnostic has no labels.
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        crate::diagnostic::Diagnostic::from_error("front::spv", self)
+    }
+}
@@ -359,7 +359,7 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
                     });
                 }
                 _ => {
-                    let ty = module.types.append(crate::Type {
+                    let ty = module.types.insert(crate::Type {
                         name: None,
                         inner: crate::TypeInner::Struct {
                             top_level: false,
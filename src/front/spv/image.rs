@@ -1,5 +1,5 @@
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
     FunctionArgument,
 };
 
@@ -44,6 +44,8 @@ pub struct SamplingOptions {
     pub project: bool,
     /// Depth comparison sampling with a reference value.
     pub compare: bool,
+    /// Gathering a component of the four texels instead of filtering them.
+    pub gather: bool,
 }
 
 enum ExtraCoordinate {
@@ -62,7 +64,7 @@ fn extract_image_coordinates(
     extra_coordinate: ExtraCoordinate,
     base: Handle<crate::Expression>,
     coordinate_ty: Handle<crate::Type>,
-    type_arena: &Arena<crate::Type>,
+    type_arena: &UniqueArena<crate::Type>,
     expressions: &mut Arena<crate::Expression>,
 ) -> (Handle<crate::Expression>, Option<Handle<crate::Expression>>) {
     let (given_size, kind) = match type_arena[coordinate_ty].inner {
@@ -165,7 +167,7 @@ fn extract_image_coordinates(
 pub(super) fn patch_comparison_type(
     flags: SamplingFlags,
     var: &mut crate::GlobalVariable,
-    arena: &mut Arena<crate::Type>,
+    arena: &mut UniqueArena<crate::Type>,
 ) -> bool {
     if !flags.contains(SamplingFlags::COMPARISON) {
         return true;
@@ -178,11 +180,16 @@ pub(super) fn patch_comparison_type(
     let original_ty = &arena[var.ty];
     let ty_inner = match original_ty.inner {
         crate::TypeInner::Image {
-            class: _,
+            class,
             dim,
             arrayed,
         } => crate::TypeInner::Image {
-            class: crate::ImageClass::Depth,
+            class: crate::ImageClass::Depth {
+                multi: match class {
+                    crate::ImageClass::Sampled { multi, .. } => multi,
+                    _ => false,
+                },
+            },
             dim,
             arrayed,
         },
@@ -191,7 +198,7 @@ pub(super) fn patch_comparison_type(
     };
 
     let name = original_ty.name.clone();
-    var.ty = arena.append(crate::Type {
+    var.ty = arena.insert(crate::Type {
         name,
         inner: ty_inner,
     });
@@ -233,7 +240,7 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
     pub(super) fn parse_image_write(
         &mut self,
         words_left: u16,
-        type_arena: &Arena<crate::Type>,
+        type_arena: &UniqueArena<crate::Type>,
         global_arena: &Arena<crate::GlobalVariable>,
         arguments: &[FunctionArgument],
         expressions: &mut Arena<crate::Expression>,
@@ -290,7 +297,7 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
     pub(super) fn parse_image_load(
         &mut self,
         mut words_left: u16,
-        type_arena: &Arena<crate::Type>,
+        type_arena: &UniqueArena<crate::Type>,
         global_arena: &Arena<crate::GlobalVariable>,
         arguments: &[FunctionArgument],
         expressions: &mut Arena<crate::Expression>,
@@ -380,7 +387,8 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
         &mut self,
         mut words_left: u16,
         options: SamplingOptions,
-        type_arena: &Arena<crate::Type>,
+        type_arena: &UniqueArena<crate::Type>,
+        const_arena: &Arena<crate::Constant>,
         global_arena: &Arena<crate::GlobalVariable>,
         arguments: &[FunctionArgument],
         expressions: &mut Arena<crate::Expression>,
@@ -395,6 +403,34 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
         } else {
             None
         };
+        // depth gathers have no component
+        let gather = match (options.gather, dref_id) {
+            (false, _) => None,
+            (true, Some(_)) => Some(crate::SwizzleComponent::X),
+            (true, None) => {
+                use crate::SwizzleComponent as Sc;
+                let component_id = self.next()?;
+                let component_handle = self.lookup_constant.lookup(component_id)?.handle;
+                let index = match const_arena[component_handle].inner {
+                    crate::ConstantInner::Scalar {
+                        value: crate::ScalarValue::Uint(index),
+                        ..
+                    } => index,
+                    crate::ConstantInner::Scalar {
+                        value: crate::ScalarValue::Sint(index),
+                        ..
+                    } => index as u64,
+                    _ => return Err(Error::InvalidGatherComponent(component_id)),
+                };
+                Some(match index {
+                    0 => Sc::X,
+                    1 => Sc::Y,
+                    2 => Sc::Z,
+                    3 => Sc::W,
+                    _ => return Err(Error::InvalidGatherComponent(component_id)),
+                })
+            }
+        };
 
         let mut image_ops = if words_left != 0 {
             words_left -= 1;
@@ -403,7 +439,10 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
             0
         };
 
-        let mut level = crate::SampleLevel::Auto;
+        let mut level = match gather {
+            Some(_) => crate::SampleLevel::Zero,
+            None => crate::SampleLevel::Auto,
+        };
         let mut offset = None;
         while image_ops != 0 {
             let bit = 1 << image_ops.trailing_zeros();
@@ -420,6 +459,17 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
                     level = crate::SampleLevel::Exact(lod_handle);
                     words_left -= 1;
                 }
+                spirv::ImageOperands::GRAD => {
+                    let grad_x_expr = self.next()?;
+                    let grad_x_handle = self.lookup_expression.lookup(grad_x_expr)?.handle;
+                    let grad_y_expr = self.next()?;
+                    let grad_y_handle = self.lookup_expression.lookup(grad_y_expr)?.handle;
+                    level = crate::SampleLevel::Gradient {
+                        x: grad_x_handle,
+                        y: grad_y_handle,
+                    };
+                    words_left -= 2;
+                }
                 spirv::ImageOperands::CONST_OFFSET => {
                     let offset_constant = self.next()?;
                     let offset_handle = self.lookup_constant.lookup(offset_constant)?.handle;
@@ -474,39 +524,61 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
             ref other => return Err(Error::InvalidGlobalVar(other.clone())),
         }
 
-        let (coordinate, array_index) = match type_arena[image_ty].inner {
+        let (dim, coordinate, array_index) = match type_arena[image_ty].inner {
             crate::TypeInner::Image {
                 dim,
                 arrayed,
                 class: _,
-            } => extract_image_coordinates(
-                dim,
+            } => {
+                let (coordinate, array_index) = extract_image_coordinates(
+                    dim,
+                    if options.project {
+                        ExtraCoordinate::Projection
+                    } else if arrayed {
+                        ExtraCoordinate::ArrayLayer
+                    } else {
+                        ExtraCoordinate::Garbage
+                    },
+                    coord_lexp.handle,
+                    coord_type_handle,
+                    type_arena,
+                    expressions,
+                );
+                (dim, coordinate, array_index)
+            }
+            _ => return Err(Error::InvalidImage(image_ty)),
+        };
+
+        let depth_ref = match dref_id {
+            Some(id) => {
+                let dref = self.lookup_expression.lookup(id)?.handle;
                 if options.project {
-                    ExtraCoordinate::Projection
-                } else if arrayed {
-                    ExtraCoordinate::ArrayLayer
+                    // The reference is divided by the projection term as well.
+                    let projection = expressions.append(crate::Expression::AccessIndex {
+                        base: coord_lexp.handle,
+                        index: dim.required_coordinate_size().map_or(1, |size| size as u32),
+                    });
+                    Some(expressions.append(crate::Expression::Binary {
+                        op: crate::BinaryOperator::Divide,
+                        left: dref,
+                        right: projection,
+                    }))
                 } else {
-                    ExtraCoordinate::Garbage
-                },
-                coord_lexp.handle,
-                coord_type_handle,
-                type_arena,
-                expressions,
-            ),
-            _ => return Err(Error::InvalidImage(image_ty)),
+                    Some(dref)
+                }
+            }
+            None => None,
         };
 
         let expr = crate::Expression::ImageSample {
             image: si_lexp.image,
             sampler: si_lexp.sampler,
+            gather,
             coordinate,
             array_index,
             offset,
             level,
-            depth_ref: match dref_id {
-                Some(id) => Some(self.lookup_expression.lookup(id)?.handle),
-                None => None,
-            },
+            depth_ref,
         };
         self.lookup_expression.insert(
             result_id,
@@ -551,6 +623,53 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
         Ok(())
     }
 
+    pub(super) fn parse_image_query_lod(
+        &mut self,
+        type_arena: &UniqueArena<crate::Type>,
+        global_arena: &Arena<crate::GlobalVariable>,
+        arguments: &[FunctionArgument],
+        expressions: &mut Arena<crate::Expression>,
+    ) -> Result<(), Error> {
+        let result_type_id = self.next()?;
+        let result_id = self.next()?;
+        let sampled_image_id = self.next()?;
+        let coordinate_id = self.next()?;
+
+        let si_lexp = self.lookup_sampled_image.lookup(sampled_image_id)?.clone();
+        let coord_lexp = self.lookup_expression.lookup(coordinate_id)?;
+        let coord_type_handle = self.lookup_type.lookup(coord_lexp.type_id)?.handle;
+
+        let image_ty = expressions.get_image_expr_ty(si_lexp.image, global_arena, arguments)?;
+        // The array layer doesn't affect the level of detail.
+        let (coordinate, _) = match type_arena[image_ty].inner {
+            crate::TypeInner::Image { dim, .. } => extract_image_coordinates(
+                dim,
+                ExtraCoordinate::Garbage,
+                coord_lexp.handle,
+                coord_type_handle,
+                type_arena,
+                expressions,
+            ),
+            _ => return Err(Error::InvalidImage(image_ty)),
+        };
+
+        let expr = crate::Expression::ImageQuery {
+            image: si_lexp.image,
+            query: crate::ImageQuery::Lod {
+                sampler: si_lexp.sampler,
+                coordinate,
+            },
+        };
+        self.lookup_expression.insert(
+            result_id,
+            LookupExpression {
+                handle: expressions.append(expr),
+                type_id: result_type_id,
+            },
+        );
+        Ok(())
+    }
+
     pub(super) fn parse_image_query_other(
         &mut self,
         query: crate::ImageQuery,
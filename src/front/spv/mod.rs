@@ -39,7 +39,7 @@ use flow::*;
 use function::*;
 
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
     proc::{Alignment, Layouter},
     FastHashMap,
 };
@@ -70,6 +70,7 @@ pub const SUPPORTED_CAPABILITIES: &[spirv::Capability] = &[
     spirv::Capability::Int16,
     spirv::Capability::Int64,
     spirv::Capability::Geometry,
+    spirv::Capability::PhysicalStorageBufferAddresses,
     // tricky ones
     spirv::Capability::UniformBufferArrayDynamicIndexing,
     spirv::Capability::StorageBufferArrayDynamicIndexing,
@@ -77,6 +78,7 @@ pub const SUPPORTED_CAPABILITIES: &[spirv::Capability] = &[
 pub const SUPPORTED_EXTENSIONS: &[&str] = &[
     "SPV_KHR_storage_buffer_storage_class",
     "SPV_KHR_vulkan_memory_model",
+    "SPV_KHR_physical_storage_buffer",
 ];
 pub const SUPPORTED_EXT_SETS: &[&str] = &["GLSL.std.450"];
 
@@ -381,6 +383,12 @@ pub struct Parser<I> {
     state: ModuleState,
     layouter: Layouter,
     temp_bytes: Vec<u8>,
+    /// Scratch space for operands that are only needed while parsing an instruction.
+    temp_words: Vec<spirv::Word>,
+    /// Operands of the function body instruction being parsed, read ahead
+    /// so that the constants and globals they refer to can be registered.
+    operands: Vec<spirv::Word>,
+    operand_index: usize,
     ext_glsl_id: Option<spirv::Word>,
     future_decor: FastHashMap<spirv::Word, Decoration>,
     future_member_decor: FastHashMap<(spirv::Word, MemberIndex), Decoration>,
@@ -420,6 +428,9 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             state: ModuleState::Empty,
             layouter: Layouter::default(),
             temp_bytes: Vec::new(),
+            temp_words: Vec::new(),
+            operands: Vec::new(),
+            operand_index: 0,
             ext_glsl_id: None,
             future_decor: FastHashMap::default(),
             future_member_decor: FastHashMap::default(),
@@ -447,9 +458,58 @@ impl<I: Iterator<Item = u32>> Parser<I> {
     }
 
     fn next(&mut self) -> Result<u32, Error> {
+        if let Some(&word) = self.operands.get(self.operand_index) {
+            self.operand_index += 1;
+            return Ok(word);
+        }
         self.data.next().ok_or(Error::IncompleteData)
     }
 
+    /// Read the operands of `inst` ahead, and register an expression for every
+    /// constant and global variable among them that this function hasn't used yet.
+    ///
+    /// Doing this on first use, instead of registering everything up front in
+    /// `make_expression_storage`, keeps the cost proportional to the function size.
+    fn prefetch_operands(
+        &mut self,
+        inst: Instruction,
+        expressions: &mut Arena<crate::Expression>,
+        block: &mut crate::Block,
+        emitter: &mut super::Emitter,
+    ) -> Result<(), Error> {
+        self.operands.clear();
+        self.operand_index = 0;
+        for _ in 1..inst.wc {
+            let word = self.data.next().ok_or(Error::IncompleteData)?;
+            self.operands.push(word);
+        }
+
+        let mut flushed = false;
+        for &id in self.operands.iter() {
+            if self.lookup_expression.contains_key(&id) {
+                continue;
+            }
+            let (expression, type_id) = if let Some(var) = self.lookup_variable.get(&id) {
+                (crate::Expression::GlobalVariable(var.handle), var.type_id)
+            } else if let Some(con) = self.lookup_constant.get(&id) {
+                (crate::Expression::Constant(con.handle), con.type_id)
+            } else {
+                continue;
+            };
+            if !flushed {
+                block.extend(emitter.finish(expressions));
+                flushed = true;
+            }
+            let handle = expressions.append(expression);
+            self.lookup_expression
+                .insert(id, LookupExpression { handle, type_id });
+        }
+        if flushed {
+            emitter.start(expressions);
+        }
+        Ok(())
+    }
+
     fn next_inst(&mut self) -> Result<Instruction, Error> {
         let word = self.next()?;
         let (wc, opcode) = ((word >> 16) as u16, (word & 0xffff) as u16);
@@ -621,7 +681,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         &mut self,
         expressions: &mut Arena<crate::Expression>,
         op: crate::BinaryOperator,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
     ) -> Result<(), Error> {
         let result_type_id = self.next()?;
         let result_id = self.next()?;
@@ -702,7 +762,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
     fn parse_expr_derivative(
         &mut self,
         expressions: &mut Arena<crate::Expression>,
-        axis: crate::DerivativeAxis,
+        (axis, ctrl): (crate::DerivativeAxis, crate::DerivativeControl),
     ) -> Result<(), Error> {
         let result_type_id = self.next()?;
         let result_id = self.next()?;
@@ -712,6 +772,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
 
         let expr = crate::Expression::Derivative {
             axis,
+            ctrl,
             expr: arg_lexp.handle,
         };
         self.lookup_expression.insert(
@@ -730,7 +791,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         root_type_id: spirv::Word,
         object_expr: Handle<crate::Expression>,
         selections: &[spirv::Word],
-        type_arena: &Arena<crate::Type>,
+        type_arena: &UniqueArena<crate::Type>,
         expressions: &mut Arena<crate::Expression>,
     ) -> Result<Handle<crate::Expression>, Error> {
         let selection = match selections.first() {
@@ -788,7 +849,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         expressions: &mut Arena<crate::Expression>,
         local_arena: &mut Arena<crate::LocalVariable>,
         const_arena: &mut Arena<crate::Constant>,
-        type_arena: &Arena<crate::Type>,
+        type_arena: &UniqueArena<crate::Type>,
         global_arena: &Arena<crate::GlobalVariable>,
         arguments: &[crate::FunctionArgument],
         function_info: &mut FunctionInfo,
@@ -802,6 +863,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             use spirv::Op;
             let inst = self.next_inst()?;
             log::debug!("\t\t{:?} [{}]", inst.op, inst.wc);
+            self.prefetch_operands(inst, expressions, &mut block, &mut emitter)?;
 
             match inst.op {
                 Op::Line => {
@@ -1221,9 +1283,10 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     let id = self.next()?;
                     let object_id = self.next()?;
                     let composite_id = self.next()?;
-                    let mut selections = Vec::with_capacity(inst.wc as usize - 5);
+                    self.temp_words.clear();
                     for _ in 5..inst.wc {
-                        selections.push(self.next()?);
+                        let selection = self.next()?;
+                        self.temp_words.push(selection);
                     }
 
                     let object_lexp = self.lookup_expression.lookup(object_id)?.clone();
@@ -1232,7 +1295,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         root_lexp.handle,
                         result_type_id,
                         object_lexp.handle,
-                        &selections,
+                        &self.temp_words,
                         type_arena,
                         expressions,
                     )?;
@@ -1283,9 +1346,10 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     let result_type_id = self.next()?;
                     let result_id = self.next()?;
                     let pointer_id = self.next()?;
-                    if inst.wc != 4 {
-                        inst.expect(5)?;
-                        let _memory_access = self.next()?;
+                    // Memory operands, such as the alignment of physical
+                    // pointer accesses, don't affect the IR.
+                    for _ in 4..inst.wc {
+                        let _memory_operand = self.next()?;
                     }
 
                     let base_lexp = self.lookup_expression.lookup(pointer_id)?;
@@ -1317,9 +1381,8 @@ impl<I: Iterator<Item = u32>> Parser<I> {
 
                     let pointer_id = self.next()?;
                     let value_id = self.next()?;
-                    if inst.wc != 3 {
-                        inst.expect(4)?;
-                        let _memory_access = self.next()?;
+                    for _ in 3..inst.wc {
+                        let _memory_operand = self.next()?;
                     }
                     let base_expr = self.lookup_expression.lookup(pointer_id)?;
                     let value_expr = self.lookup_expression.lookup(value_id)?;
@@ -1507,11 +1570,13 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     let options = image::SamplingOptions {
                         compare: false,
                         project: false,
+                        gather: false,
                     };
                     self.parse_image_sample(
                         extra,
                         options,
                         type_arena,
+                        const_arena,
                         global_arena,
                         arguments,
                         expressions,
@@ -1523,11 +1588,13 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     let options = image::SamplingOptions {
                         compare: false,
                         project: true,
+                        gather: false,
                     };
                     self.parse_image_sample(
                         extra,
                         options,
                         type_arena,
+                        const_arena,
                         global_arena,
                         arguments,
                         expressions,
@@ -1539,11 +1606,13 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     let options = image::SamplingOptions {
                         compare: true,
                         project: false,
+                        gather: false,
                     };
                     self.parse_image_sample(
                         extra,
                         options,
                         type_arena,
+                        const_arena,
                         global_arena,
                         arguments,
                         expressions,
@@ -1555,11 +1624,31 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     let options = image::SamplingOptions {
                         compare: true,
                         project: true,
+                        gather: false,
                     };
                     self.parse_image_sample(
                         extra,
                         options,
                         type_arena,
+                        const_arena,
+                        global_arena,
+                        arguments,
+                        expressions,
+                        function_info,
+                    )?;
+                }
+                Op::ImageGather | Op::ImageDrefGather => {
+                    let extra = inst.expect_at_least(6)?;
+                    let options = image::SamplingOptions {
+                        compare: inst.op == Op::ImageDrefGather,
+                        project: false,
+                        gather: true,
+                    };
+                    self.parse_image_sample(
+                        extra,
+                        options,
+                        type_arena,
+                        const_arena,
                         global_arena,
                         arguments,
                         expressions,
@@ -1582,6 +1671,10 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     inst.expect(4)?;
                     self.parse_image_query_other(crate::ImageQuery::NumSamples, expressions)?;
                 }
+                Op::ImageQueryLod => {
+                    inst.expect(5)?;
+                    self.parse_image_query_lod(type_arena, global_arena, arguments, expressions)?;
+                }
                 // other ops
                 Op::Select => {
                     inst.expect(6)?;
@@ -1712,21 +1805,63 @@ impl<I: Iterator<Item = u32>> Parser<I> {
 
                     let value_lexp = self.lookup_expression.lookup(value_id)?;
                     let ty_lookup = self.lookup_type.lookup(result_type_id)?;
-                    let (kind, width) = match type_arena[ty_lookup.handle].inner {
-                        crate::TypeInner::Scalar { kind, width }
-                        | crate::TypeInner::Vector { kind, width, .. } => (kind, width),
-                        crate::TypeInner::Matrix { width, .. } => (crate::ScalarKind::Float, width),
+                    let value_ty_handle = self.lookup_type.lookup(value_lexp.type_id)?.handle;
+                    let expr = match (
+                        &type_arena[ty_lookup.handle].inner,
+                        &type_arena[value_ty_handle].inner,
+                    ) {
+                        // Bitcasts between physical pointers and `uvec2` addresses.
+                        (&crate::TypeInner::Pointer { .. }, _)
+                        | (_, &crate::TypeInner::Pointer { .. })
+                            if inst.op == Op::Bitcast =>
+                        {
+                            crate::Expression::PointerCast {
+                                expr: value_lexp.handle,
+                                ty: ty_lookup.handle,
+                            }
+                        }
+                        (&crate::TypeInner::Scalar { kind, width }, _)
+                        | (&crate::TypeInner::Vector { kind, width, .. }, _) => {
+                            crate::Expression::As {
+                                expr: value_lexp.handle,
+                                kind,
+                                convert: if inst.op == Op::Bitcast {
+                                    None
+                                } else {
+                                    Some(width)
+                                },
+                            }
+                        }
+                        (&crate::TypeInner::Matrix { width, .. }, _) => crate::Expression::As {
+                            expr: value_lexp.handle,
+                            kind: crate::ScalarKind::Float,
+                            convert: if inst.op == Op::Bitcast {
+                                None
+                            } else {
+                                Some(width)
+                            },
+                        },
                         _ => return Err(Error::InvalidAsType(ty_lookup.handle)),
                     };
+                    self.lookup_expression.insert(
+                        result_id,
+                        LookupExpression {
+                            handle: expressions.append(expr),
+                            type_id: result_type_id,
+                        },
+                    );
+                }
+                Op::ConvertUToPtr | Op::ConvertPtrToU => {
+                    inst.expect(4)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let value_id = self.next()?;
 
-                    let expr = crate::Expression::As {
+                    let value_lexp = self.lookup_expression.lookup(value_id)?;
+                    let ty_lookup = self.lookup_type.lookup(result_type_id)?;
+                    let expr = crate::Expression::PointerCast {
                         expr: value_lexp.handle,
-                        kind,
-                        convert: if inst.op == Op::Bitcast {
-                            None
-                        } else {
-                            Some(width)
-                        },
+                        ty: ty_lookup.handle,
                     };
                     self.lookup_expression.insert(
                         result_id,
@@ -1868,6 +2003,16 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                             Glo::FaceForward => Mf::FaceForward,
                             Glo::Reflect => Mf::Reflect,
                             Glo::Refract => Mf::Refract,
+                            Glo::PackSnorm4x8 => Mf::Pack4x8snorm,
+                            Glo::PackUnorm4x8 => Mf::Pack4x8unorm,
+                            Glo::PackSnorm2x16 => Mf::Pack2x16snorm,
+                            Glo::PackUnorm2x16 => Mf::Pack2x16unorm,
+                            Glo::PackHalf2x16 => Mf::Pack2x16float,
+                            Glo::UnpackSnorm4x8 => Mf::Unpack4x8snorm,
+                            Glo::UnpackUnorm4x8 => Mf::Unpack4x8unorm,
+                            Glo::UnpackSnorm2x16 => Mf::Unpack2x16snorm,
+                            Glo::UnpackUnorm2x16 => Mf::Unpack2x16unorm,
+                            Glo::UnpackHalf2x16 => Mf::Unpack2x16float,
                             _ => return Err(Error::UnsupportedExtInst(inst_id)),
                         };
 
@@ -2071,14 +2216,62 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         continue_block_id,
                     });
                 }
-                Op::DPdx | Op::DPdxFine | Op::DPdxCoarse => {
-                    self.parse_expr_derivative(expressions, crate::DerivativeAxis::X)?;
+                Op::DPdx => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::X, crate::DerivativeControl::None),
+                    )?;
+                }
+                Op::DPdxFine => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::X, crate::DerivativeControl::Fine),
+                    )?;
+                }
+                Op::DPdxCoarse => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::X, crate::DerivativeControl::Coarse),
+                    )?;
+                }
+                Op::DPdy => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::Y, crate::DerivativeControl::None),
+                    )?;
+                }
+                Op::DPdyFine => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::Y, crate::DerivativeControl::Fine),
+                    )?;
+                }
+                Op::DPdyCoarse => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::Y, crate::DerivativeControl::Coarse),
+                    )?;
                 }
-                Op::DPdy | Op::DPdyFine | Op::DPdyCoarse => {
-                    self.parse_expr_derivative(expressions, crate::DerivativeAxis::Y)?;
+                Op::Fwidth => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::Width, crate::DerivativeControl::None),
+                    )?;
                 }
-                Op::Fwidth | Op::FwidthFine | Op::FwidthCoarse => {
-                    self.parse_expr_derivative(expressions, crate::DerivativeAxis::Width)?;
+                Op::FwidthFine => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (crate::DerivativeAxis::Width, crate::DerivativeControl::Fine),
+                    )?;
+                }
+                Op::FwidthCoarse => {
+                    self.parse_expr_derivative(
+                        expressions,
+                        (
+                            crate::DerivativeAxis::Width,
+                            crate::DerivativeControl::Coarse,
+                        ),
+                    )?;
                 }
                 Op::ArrayLength => {
                     inst.expect(5)?;
@@ -2200,34 +2393,13 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         {
             assert!(self.lookup_expression.is_empty());
         }
-        // register global variables
-        for (&id, var) in self.lookup_variable.iter() {
-            let handle = expressions.append(crate::Expression::GlobalVariable(var.handle));
-            self.lookup_expression.insert(
-                id,
-                LookupExpression {
-                    type_id: var.type_id,
-                    handle,
-                },
-            );
-        }
         // register special constants
         self.index_constant_expressions.clear();
         for &con_handle in self.index_constants.iter() {
             let handle = expressions.append(crate::Expression::Constant(con_handle));
             self.index_constant_expressions.push(handle);
         }
-        // register constants
-        for (&id, con) in self.lookup_constant.iter() {
-            let handle = expressions.append(crate::Expression::Constant(con.handle));
-            self.lookup_expression.insert(
-                id,
-                LookupExpression {
-                    type_id: con.type_id,
-                    handle,
-                },
-            );
-        }
+        // constants and global variables are registered on first use
         // done
         expressions
     }
@@ -2428,6 +2600,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 Op::TypeVector => self.parse_type_vector(inst, &mut module),
                 Op::TypeMatrix => self.parse_type_matrix(inst, &mut module),
                 Op::TypeFunction => self.parse_type_function(inst),
+                Op::TypeForwardPointer => self.parse_type_forward_pointer(inst, &mut module),
                 Op::TypePointer => self.parse_type_pointer(inst, &mut module),
                 Op::TypeArray => self.parse_type_array(inst, &mut module),
                 Op::TypeRuntimeArray => self.parse_type_runtime_array(inst, &mut module),
@@ -2450,6 +2623,20 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             }?;
         }
 
+        // Forward pointers keep pointing to themselves until completed.
+        let incomplete = self
+            .lookup_type
+            .iter()
+            .filter(|&(_, lookup)| match module.types[lookup.handle].inner {
+                crate::TypeInner::Pointer { base, .. } => base == lookup.handle,
+                _ => false,
+            })
+            .map(|(&id, _)| id)
+            .min();
+        if let Some(id) = incomplete {
+            return Err(Error::IncompleteForwardPointer(id));
+        }
+
         log::info!("Patching...");
         {
             let mut nodes = petgraph::algo::toposort(&self.function_call_graph, None)
@@ -2584,7 +2771,10 @@ impl<I: Iterator<Item = u32>> Parser<I> {
 
         let ep_id = self.next()?;
         let mode_id = self.next()?;
-        let args: Vec<spirv::Word> = self.data.by_ref().take(inst.wc as usize - 3).collect();
+        self.temp_words.clear();
+        self.temp_words
+            .extend(self.data.by_ref().take(inst.wc as usize - 3));
+        let args = &self.temp_words;
 
         let ep = self
             .lookup_entry_point
@@ -2742,7 +2932,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: self.future_decor.remove(&id).and_then(|dec| dec.name),
                     inner,
                 }),
@@ -2773,7 +2963,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: self.future_decor.remove(&id).and_then(|dec| dec.name),
                     inner,
                 }),
@@ -2799,7 +2989,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: self.future_decor.remove(&id).and_then(|dec| dec.name),
                     inner,
                 }),
@@ -2832,7 +3022,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: self.future_decor.remove(&id).and_then(|dec| dec.name),
                     inner,
                 }),
@@ -2867,7 +3057,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: decor.and_then(|dec| dec.name),
                     inner,
                 }),
@@ -2906,7 +3096,48 @@ impl<I: Iterator<Item = u32>> Parser<I> {
 
         let decor = self.future_decor.remove(&id);
         let base_lookup_ty = self.lookup_type.lookup(type_id)?;
+
+        // Complete a forward declaration, keeping its handle.
+        if let Some(forward) = self.lookup_type.get(&id) {
+            let handle = forward.handle;
+            let base = base_lookup_ty.handle;
+            let class = match module.types[handle].inner {
+                crate::TypeInner::Pointer { class, .. } => class,
+                _ => return Err(Error::InvalidId(id)),
+            };
+            let name = match decor.and_then(|dec| dec.name) {
+                Some(name) => Some(name),
+                None => module.types[handle].name.clone(),
+            };
+            module
+                .types
+                .replace(
+                    handle,
+                    crate::Type {
+                        name,
+                        inner: crate::TypeInner::Pointer { base, class },
+                    },
+                )
+                .map_err(|_| Error::DuplicateForwardPointer(id))?;
+            self.lookup_type.insert(
+                id,
+                LookupType {
+                    handle,
+                    base_id: Some(type_id),
+                },
+            );
+            return Ok(());
+        }
+
         let class = match module.types[base_lookup_ty.handle].inner {
+            // Physical pointers are values, so pointers to them are regular pointers.
+            crate::TypeInner::Pointer {
+                class: crate::StorageClass::PhysicalStorageBuffer,
+                ..
+            } => match map_storage_class(storage_class)? {
+                ExtendedClass::Global(class) => class,
+                ExtendedClass::Input | ExtendedClass::Output => crate::StorageClass::Private,
+            },
             crate::TypeInner::Pointer { class, .. }
             | crate::TypeInner::ValuePointer { class, .. } => class,
             _ if self
@@ -2926,7 +3157,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             base_lookup_ty.clone()
         } else {
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: decor.and_then(|dec| dec.name),
                     inner: crate::TypeInner::Pointer {
                         base: base_lookup_ty.handle,
@@ -2940,6 +3171,41 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    /// Declares a physical pointer type ahead of its base type.
+    ///
+    /// The pointer type is added right away, pointing to itself, and gets
+    /// its real base when the matching `OpTypePointer` is parsed.
+    fn parse_type_forward_pointer(
+        &mut self,
+        inst: Instruction,
+        module: &mut crate::Module,
+    ) -> Result<(), Error> {
+        self.switch(ModuleState::Type, inst.op)?;
+        inst.expect(3)?;
+        let id = self.next()?;
+        let storage_class = self.next()?;
+
+        let class = match map_storage_class(storage_class)? {
+            ExtendedClass::Global(class @ crate::StorageClass::PhysicalStorageBuffer) => class,
+            _ => return Err(Error::UnsupportedStorageClass(storage_class)),
+        };
+        let handle = module.types.insert(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Pointer {
+                base: Handle::from_usize(module.types.len()),
+                class,
+            },
+        });
+        self.lookup_type.insert(
+            id,
+            LookupType {
+                handle,
+                base_id: None,
+            },
+        );
+        Ok(())
+    }
+
     fn parse_type_array(
         &mut self,
         inst: Instruction,
@@ -2965,7 +3231,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: decor.name,
                     inner,
                 }),
@@ -2998,7 +3264,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         self.lookup_type.insert(
             id,
             LookupType {
-                handle: module.types.append(crate::Type {
+                handle: module.types.insert(crate::Type {
                     name: decor.name,
                     inner,
                 }),
@@ -3098,7 +3364,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             },
             members,
         };
-        let ty_handle = module.types.append(crate::Type {
+        let ty_handle = module.types.insert(crate::Type {
             name: parent_decor.and_then(|dec| dec.name),
             inner,
         });
@@ -3142,7 +3408,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         let decor = self.future_decor.remove(&id).unwrap_or_default();
 
         // ensure there is a type for texture coordinate without extra components
-        module.types.fetch_or_append(crate::Type {
+        module.types.insert(crate::Type {
             name: None,
             inner: {
                 let kind = crate::ScalarKind::Float;
@@ -3173,7 +3439,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             arrayed: is_array,
         };
 
-        let handle = module.types.append(crate::Type {
+        let handle = module.types.insert(crate::Type {
             name: decor.name,
             inner,
         });
@@ -3212,7 +3478,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         inst.expect(2)?;
         let id = self.next()?;
         let decor = self.future_decor.remove(&id).unwrap_or_default();
-        let handle = module.types.append(crate::Type {
+        let handle = module.types.insert(crate::Type {
             name: decor.name,
             inner: crate::TypeInner::Sampler { comparison: false },
         });
@@ -3483,6 +3749,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         | crate::BuiltIn::InstanceIndex
                         | crate::BuiltIn::SampleIndex
                         | crate::BuiltIn::VertexIndex
+                        | crate::BuiltIn::ViewIndex
                         | crate::BuiltIn::PrimitiveIndex
                         | crate::BuiltIn::LocalInvocationIndex => Some(crate::TypeInner::Scalar {
                             kind: crate::ScalarKind::Uint,
@@ -3490,6 +3757,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         }),
                         crate::BuiltIn::GlobalInvocationId
                         | crate::BuiltIn::LocalInvocationId
+                        | crate::BuiltIn::NumWorkGroups
                         | crate::BuiltIn::WorkGroupId
                         | crate::BuiltIn::WorkGroupSize => Some(crate::TypeInner::Vector {
                             size: crate::VectorSize::Tri,
@@ -3502,9 +3770,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         needs_inner_uint,
                         module.types[effective_ty].inner.scalar_kind(),
                     ) {
-                        unsigned_ty = module
-                            .types
-                            .fetch_or_append(crate::Type { name: None, inner });
+                        unsigned_ty = module.types.insert(crate::Type { name: None, inner });
                     }
                 }
 
@@ -3625,6 +3891,18 @@ pub fn parse_u8_slice(data: &[u8], options: &Options) -> Result<crate::Module, E
     Parser::new(words, options).parse()
 }
 
+/// Parse a module that was loaded or built with `rspirv`.
+///
+/// This is a convenience wrapper: the module is assembled into SPIR-V words,
+/// which are then parsed like the ones of [`parse_u8_slice`]. It costs a copy
+/// of the whole binary on top of parsing it.
+#[cfg(feature = "rspirv")]
+pub fn from_rspirv(module: &rspirv::dr::Module, options: &Options) -> Result<crate::Module, Error> {
+    use rspirv::binary::Assemble as _;
+
+    Parser::new(module.assemble().into_iter(), options).parse()
+}
+
 #[cfg(test)]
 mod test {
     #[test]
@@ -3640,4 +3918,191 @@ mod test {
         ];
         let _ = super::parse_u8_slice(&bin, &Default::default()).unwrap();
     }
+
+    #[test]
+    fn incomplete_forward_pointer() {
+        let words = vec![
+            // Magic number, version 1.5, generator, bound, reserved.
+            0x0723_0203,
+            0x0001_0500,
+            0,
+            2,
+            0,
+            // OpCapability Shader
+            0x0002_0011,
+            1,
+            // OpCapability PhysicalStorageBufferAddresses
+            0x0002_0011,
+            5347,
+            // OpMemoryModel PhysicalStorageBuffer64 GLSL450
+            0x0003_000e,
+            5348,
+            1,
+            // OpTypeForwardPointer %1 PhysicalStorageBuffer
+            0x0003_0027,
+            1,
+            5349,
+        ];
+        match super::Parser::new(words.into_iter(), &Default::default()).parse() {
+            Err(super::Error::IncompleteForwardPointer(1)) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(all(feature = "wgsl-in", feature = "spv-out"))]
+    #[test]
+    fn image_operations() {
+        let module = crate::front::wgsl::parse_str(
+            "
+            [[group(0), binding(0)]] var tex: texture_2d<f32>;
+            [[group(0), binding(1)]] var samp: sampler;
+            [[group(0), binding(2)]] var depth: texture_depth_2d;
+            [[group(0), binding(3)]] var samp_cmp: sampler_comparison;
+            [[stage(fragment)]]
+            fn main() -> [[location(0)]] vec4<f32> {
+                let tc = vec2<f32>(0.5);
+                return textureSampleGrad(tex, samp, tc, vec2<f32>(1.0), vec2<f32>(2.0))
+                    + textureGather(2, tex, samp, tc)
+                    + textureGatherCompare(depth, samp_cmp, tc, 0.5, vec2<i32>(1, 0));
+            }
+            ",
+        )
+        .unwrap();
+        let validate = |module: &crate::Module| {
+            crate::valid::Validator::new(
+                crate::valid::ValidationFlags::all(),
+                crate::valid::Capabilities::empty(),
+            )
+            .validate(module)
+            .unwrap()
+        };
+        let info = validate(&module);
+        let words =
+            crate::back::spv::write_vec(&module, &info, &crate::back::spv::Options::default())
+                .unwrap();
+
+        let module = super::Parser::new(words.into_iter(), &Default::default())
+            .parse()
+            .unwrap();
+        validate(&module);
+        // the entry point calls a function with the body
+        let samples: Vec<_> = module
+            .functions
+            .iter()
+            .flat_map(|(_, function)| function.expressions.iter())
+            .filter_map(|(_, expr)| match *expr {
+                crate::Expression::ImageSample {
+                    gather,
+                    level,
+                    offset,
+                    depth_ref,
+                    ..
+                } => Some((gather, level, offset.is_some(), depth_ref.is_some())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(samples.len(), 3);
+        match samples[0] {
+            (None, crate::SampleLevel::Gradient { .. }, false, false) => {}
+            ref other => panic!("Unexpected gradient sample {:?}", other),
+        }
+        match samples[1] {
+            (Some(crate::SwizzleComponent::Z), crate::SampleLevel::Zero, false, false) => {}
+            ref other => panic!("Unexpected gather {:?}", other),
+        }
+        match samples[2] {
+            (Some(crate::SwizzleComponent::X), crate::SampleLevel::Zero, true, true) => {}
+            ref other => panic!("Unexpected depth gather {:?}", other),
+        }
+    }
+
+    #[cfg(all(feature = "glsl-in", feature = "spv-out"))]
+    #[test]
+    fn function_calls() {
+        let module = crate::front::glsl::parse_str(
+            "
+            #version 450
+            layout(location = 0) in float v_x;
+            layout(location = 0) out vec4 o_color;
+            void split(float x, out float a, inout float b) {
+                a = x * 2.0;
+                b = b - x;
+            }
+            float unused(float x) {
+                return x * 3.0;
+            }
+            void main() {
+                float a;
+                float b = 1.0;
+                split(v_x, a, b);
+                o_color = vec4(a + b);
+            }
+            ",
+            &crate::front::glsl::Options::from_stage(crate::ShaderStage::Fragment),
+        )
+        .unwrap();
+        let validate = |module: &crate::Module| {
+            crate::valid::Validator::new(
+                crate::valid::ValidationFlags::all(),
+                crate::valid::Capabilities::empty(),
+            )
+            .validate(module)
+            .unwrap()
+        };
+        let info = validate(&module);
+        let words =
+            crate::back::spv::write_vec(&module, &info, &crate::back::spv::Options::default())
+                .unwrap();
+
+        let module = super::Parser::new(words.into_iter(), &Default::default())
+            .parse()
+            .unwrap();
+        validate(&module);
+
+        // the functions that aren't called are kept as well
+        let (split, function) = module
+            .functions
+            .iter()
+            .find(|&(_, function)| function.arguments.len() == 3)
+            .unwrap();
+        assert!(module
+            .functions
+            .iter()
+            .any(|(_, function)| function.arguments.len() == 1 && function.result.is_some()));
+
+        // the output parameters are passed as pointers to local variables
+        for argument in function.arguments[1..].iter() {
+            match module.types[argument.ty].inner {
+                crate::TypeInner::Pointer {
+                    class: crate::StorageClass::Function,
+                    ..
+                } => {}
+                ref other => panic!("Unexpected output parameter type {:?}", other),
+            }
+        }
+        let (caller, arguments) = module
+            .functions
+            .iter()
+            .flat_map(|(_, caller)| {
+                caller
+                    .body
+                    .iter()
+                    .filter_map(move |statement| match *statement {
+                        crate::Statement::Call {
+                            function,
+                            ref arguments,
+                            ..
+                        } if function == split => Some((caller, arguments)),
+                        _ => None,
+                    })
+            })
+            .next()
+            .unwrap();
+        for &argument in arguments[1..].iter() {
+            match caller.expressions[argument] {
+                crate::Expression::LocalVariable(_) => {}
+                ref other => panic!("Unexpected output argument {:?}", other),
+            }
+        }
+    }
 }
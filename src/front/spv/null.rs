@@ -1,5 +1,5 @@
 use super::Error;
-use crate::arena::{Arena, Handle};
+use crate::arena::{Arena, Handle, UniqueArena};
 
 fn make_scalar_inner(kind: crate::ScalarKind, width: crate::Bytes) -> crate::ConstantInner {
     crate::ConstantInner::Scalar {
@@ -15,7 +15,7 @@ fn make_scalar_inner(kind: crate::ScalarKind, width: crate::Bytes) -> crate::Con
 
 pub fn generate_null_constant(
     ty: Handle<crate::Type>,
-    type_arena: &mut Arena<crate::Type>,
+    type_arena: &mut UniqueArena<crate::Type>,
     constant_arena: &mut Arena<crate::Constant>,
 ) -> Result<crate::ConstantInner, Error> {
     let inner = match type_arena[ty].inner {
@@ -36,7 +36,7 @@ pub fn generate_null_constant(
             rows,
             width,
         } => {
-            let vector_ty = type_arena.fetch_or_append(crate::Type {
+            let vector_ty = type_arena.insert(crate::Type {
                 name: None,
                 inner: crate::TypeInner::Vector {
                     kind: crate::ScalarKind::Float,
@@ -100,7 +100,7 @@ pub fn generate_null_constant(
 pub fn generate_default_built_in(
     built_in: Option<crate::BuiltIn>,
     ty: Handle<crate::Type>,
-    type_arena: &mut Arena<crate::Type>,
+    type_arena: &mut UniqueArena<crate::Type>,
     constant_arena: &mut Arena<crate::Constant>,
 ) -> Result<Handle<crate::Constant>, Error> {
     let inner = match built_in {
@@ -17,6 +17,7 @@ pub fn map_built_in(word: &str, span: Span) -> Result<crate::BuiltIn, Error<'_>>
         // vertex
         "vertex_index" => crate::BuiltIn::VertexIndex,
         "instance_index" => crate::BuiltIn::InstanceIndex,
+        "view_index" => crate::BuiltIn::ViewIndex,
         // fragment
         "front_facing" => crate::BuiltIn::FrontFacing,
         "frag_depth" => crate::BuiltIn::FragDepth,
@@ -29,6 +30,7 @@ pub fn map_built_in(word: &str, span: Span) -> Result<crate::BuiltIn, Error<'_>>
         "local_invocation_index" => crate::BuiltIn::LocalInvocationIndex,
         "workgroup_id" => crate::BuiltIn::WorkGroupId,
         "workgroup_size" => crate::BuiltIn::WorkGroupSize,
+        "num_workgroups" => crate::BuiltIn::NumWorkGroups,
         _ => return Err(Error::UnknownBuiltin(span)),
     })
 }
@@ -117,11 +119,34 @@ pub fn get_scalar_type(word: &str) -> Option<(crate::ScalarKind, crate::Bytes)>
     }
 }
 
-pub fn map_derivative_axis(word: &str) -> Option<crate::DerivativeAxis> {
+pub fn get_matrix_size(word: &str) -> Option<(crate::VectorSize, crate::VectorSize)> {
+    use crate::VectorSize as Vs;
     match word {
-        "dpdx" => Some(crate::DerivativeAxis::X),
-        "dpdy" => Some(crate::DerivativeAxis::Y),
-        "fwidth" => Some(crate::DerivativeAxis::Width),
+        "mat2x2" => Some((Vs::Bi, Vs::Bi)),
+        "mat2x3" => Some((Vs::Bi, Vs::Tri)),
+        "mat2x4" => Some((Vs::Bi, Vs::Quad)),
+        "mat3x2" => Some((Vs::Tri, Vs::Bi)),
+        "mat3x3" => Some((Vs::Tri, Vs::Tri)),
+        "mat3x4" => Some((Vs::Tri, Vs::Quad)),
+        "mat4x2" => Some((Vs::Quad, Vs::Bi)),
+        "mat4x3" => Some((Vs::Quad, Vs::Tri)),
+        "mat4x4" => Some((Vs::Quad, Vs::Quad)),
+        _ => None,
+    }
+}
+
+pub fn map_derivative(word: &str) -> Option<(crate::DerivativeAxis, crate::DerivativeControl)> {
+    use crate::{DerivativeAxis as Axis, DerivativeControl as Ctrl};
+    match word {
+        "dpdx" => Some((Axis::X, Ctrl::None)),
+        "dpdxCoarse" => Some((Axis::X, Ctrl::Coarse)),
+        "dpdxFine" => Some((Axis::X, Ctrl::Fine)),
+        "dpdy" => Some((Axis::Y, Ctrl::None)),
+        "dpdyCoarse" => Some((Axis::Y, Ctrl::Coarse)),
+        "dpdyFine" => Some((Axis::Y, Ctrl::Fine)),
+        "fwidth" => Some((Axis::Width, Ctrl::None)),
+        "fwidthCoarse" => Some((Axis::Width, Ctrl::Coarse)),
+        "fwidthFine" => Some((Axis::Width, Ctrl::Fine)),
         _ => None,
     }
 }
@@ -181,6 +206,7 @@ pub fn map_standard_fun(word: &str) -> Option<crate::MathFunction> {
         "normalize" => Mf::Normalize,
         "faceForward" => Mf::FaceForward,
         "reflect" => Mf::Reflect,
+        "refract" => Mf::Refract,
         // computational
         "sign" => Mf::Sign,
         "fma" => Mf::Fma,
@@ -194,6 +220,18 @@ pub fn map_standard_fun(word: &str) -> Option<crate::MathFunction> {
         // bits
         "countOneBits" => Mf::CountOneBits,
         "reverseBits" => Mf::ReverseBits,
+        // data packing
+        "pack4x8snorm" => Mf::Pack4x8snorm,
+        "pack4x8unorm" => Mf::Pack4x8unorm,
+        "pack2x16snorm" => Mf::Pack2x16snorm,
+        "pack2x16unorm" => Mf::Pack2x16unorm,
+        "pack2x16float" => Mf::Pack2x16float,
+        // data unpacking
+        "unpack4x8snorm" => Mf::Unpack4x8snorm,
+        "unpack4x8unorm" => Mf::Unpack4x8unorm,
+        "unpack2x16snorm" => Mf::Unpack2x16snorm,
+        "unpack2x16unorm" => Mf::Unpack2x16unorm,
+        "unpack2x16float" => Mf::Unpack2x16float,
         _ => return None,
     })
 }
@@ -1,4 +1,32 @@
-use super::{conv, Error, ExpectedToken, Span, Token, TokenSpan};
+use super::{conv, Error, ExpectedToken};
+use std::ops;
+
+pub(super) type Span = ops::Range<usize>;
+pub(super) type TokenSpan<'a> = (Token<'a>, Span);
+
+/// A lexical token of the WGSL source.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Token<'a> {
+    Separator(char),
+    DoubleColon,
+    Paren(char),
+    DoubleParen(char),
+    Number {
+        value: &'a str,
+        ty: char,
+        width: &'a str,
+    },
+    String(&'a str),
+    Word(&'a str),
+    Operation(char),
+    LogicalOperation(char),
+    ShiftOperation(char),
+    Arrow,
+    Unknown(char),
+    UnterminatedString,
+    Trivia,
+    End,
+}
 
 fn _consume_str<'a>(input: &'a str, what: &str) -> Option<&'a str> {
     if input.starts_with(what) {
@@ -153,10 +181,43 @@ fn consume_token(mut input: &str, generic: bool) -> (Token<'_>, &str) {
     }
 }
 
+/// Iterator over the tokens of a WGSL source, paired with their byte spans.
+///
+/// Whitespace and comments are skipped, and the iteration stops at the end
+/// of the source, so `Token::Trivia` and `Token::End` are never produced.
+/// Generic angle brackets can't be told apart from comparison and shift
+/// operators without parsing, so `<` and `>` are always lexed as operators
+/// when followed by `=` or doubled.
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Token<'a>, ops::Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lexer.next() {
+            (Token::End, _) => None,
+            token => Some(token),
+        }
+    }
+}
+
+/// Split a WGSL source into tokens, for tools like formatters and syntax
+/// highlighters.
+pub fn tokenize(source: &str) -> Tokens<'_> {
+    Tokens {
+        lexer: Lexer::new(source),
+    }
+}
+
 #[derive(Clone)]
 pub(super) struct Lexer<'a> {
     input: &'a str,
     pub(super) source: &'a str,
+    /// Whether half precision floats were allowed by `enable f16;`.
+    pub(super) f16_enabled: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -164,6 +225,23 @@ impl<'a> Lexer<'a> {
         Lexer {
             input,
             source: input,
+            f16_enabled: false,
+        }
+    }
+
+    /// Checks that scalars of `kind` and `width`, written at `span`, don't
+    /// need an extension that isn't enabled.
+    pub(super) fn check_scalar(
+        &self,
+        kind: crate::ScalarKind,
+        width: crate::Bytes,
+        span: Span,
+    ) -> Result<(), Error<'a>> {
+        match (kind, width) {
+            (crate::ScalarKind::Float, 2) if !self.f16_enabled => {
+                Err(Error::ExtensionNotEnabled(span, "f16"))
+            }
+            _ => Ok(()),
         }
     }
 
@@ -233,10 +311,7 @@ impl<'a> Lexer<'a> {
         token
     }
 
-    pub(super) fn expect_span(
-        &mut self,
-        expected: Token<'a>,
-    ) -> Result<std::ops::Range<usize>, Error<'a>> {
+    pub(super) fn expect_span(&mut self, expected: Token<'a>) -> Result<Span, Error<'a>> {
         let next = self.next();
         if next.0 == expected {
             Ok(next.1)
@@ -320,14 +395,17 @@ impl<'a> Lexer<'a> {
         &mut self,
     ) -> Result<(crate::ScalarKind, crate::Bytes), Error<'a>> {
         self.expect_generic_paren('<')?;
-        let pair = match self.next() {
+        let (kind, width) = match self.next() {
             (Token::Word(word), span) => {
-                conv::get_scalar_type(word).ok_or(Error::UnknownScalarType(span))
+                let (kind, width) = conv::get_scalar_type(word)
+                    .ok_or_else(|| Error::UnknownScalarType(span.clone()))?;
+                self.check_scalar(kind, width, span)?;
+                (kind, width)
             }
-            (_, span) => Err(Error::UnknownScalarType(span)),
-        }?;
+            (_, span) => return Err(Error::UnknownScalarType(span)),
+        };
         self.expect_generic_paren('>')?;
-        Ok(pair)
+        Ok((kind, width))
     }
 
     /// Parses a generic scalar type, for example `<f32>`.
@@ -457,3 +535,26 @@ fn test_variable_decl() {
         ],
     )
 }
+
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize("let a = 1; // comment\nb").collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            (Token::Word("let"), 0..3),
+            (Token::Word("a"), 4..5),
+            (Token::Operation('='), 6..7),
+            (
+                Token::Number {
+                    value: "1",
+                    ty: 'i',
+                    width: "",
+                },
+                8..9
+            ),
+            (Token::Separator(';'), 9..10),
+            (Token::Word("b"), 22..23),
+        ]
+    );
+}
@@ -8,14 +8,15 @@ mod lexer;
 mod tests;
 
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
+    diagnostic::ErrorCode,
     proc::{
         ensure_block_returns, Alignment, Layouter, ResolveContext, ResolveError, TypeResolution,
     },
     ConstantInner, FastHashMap, ScalarValue,
 };
 
-use self::lexer::Lexer;
+use self::lexer::{Lexer, Span, TokenSpan};
 use codespan_reporting::{
     diagnostic::{Diagnostic, Label},
     files::{Files, SimpleFile},
@@ -28,37 +29,12 @@ use std::{
     borrow::Cow,
     convert::TryFrom,
     io::{self, Write},
-    iter,
+    iter, mem,
     num::{NonZeroU32, ParseFloatError, ParseIntError},
-    ops,
 };
 use thiserror::Error;
 
-type Span = ops::Range<usize>;
-type TokenSpan<'a> = (Token<'a>, Span);
-
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum Token<'a> {
-    Separator(char),
-    DoubleColon,
-    Paren(char),
-    DoubleParen(char),
-    Number {
-        value: &'a str,
-        ty: char,
-        width: &'a str,
-    },
-    String(&'a str),
-    Word(&'a str),
-    Operation(char),
-    LogicalOperation(char),
-    ShiftOperation(char),
-    Arrow,
-    Unknown(char),
-    UnterminatedString,
-    Trivia,
-    End,
-}
+pub use self::lexer::{tokenize, Token, Tokens};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ExpectedToken<'a> {
@@ -98,6 +74,7 @@ pub enum Error<'a> {
     BadScalarWidth(Span, &'a str),
     BadAccessor(Span),
     BadTexture(Span),
+    BadGatherComponent(Span),
     BadTypeCast {
         span: Span,
         from_type: String,
@@ -108,6 +85,11 @@ pub enum Error<'a> {
         kind: crate::ScalarKind,
         width: u8,
     },
+    BadMatrixScalarType {
+        span: Span,
+        kind: crate::ScalarKind,
+        width: u8,
+    },
     InvalidResolve(ResolveError),
     InvalidForInitializer(Span),
     UnknownStorageClass(Span),
@@ -120,17 +102,22 @@ pub enum Error<'a> {
     UnknownType(Span),
     UnknownStorageFormat(Span),
     UnknownConservativeDepth(Span),
+    UnknownExtension(Span),
+    ExtensionNotEnabled(Span, &'static str),
     ZeroStride(Span),
     ZeroSizeOrAlign(Span),
     InconsistentBinding(Span),
     UnknownLocalFunction(Span),
     InitializationTypeMismatch(Span, Handle<crate::Type>),
     MissingType(Span),
+    ChainedComparison(Span, Span),
+    AssignmentInExpression(Span),
     Other,
 }
 
 impl<'a> Error<'a> {
     fn as_parse_error(&self, source: &'a str) -> ParseError {
+        let code = format!("front::wgsl::{}", self.code());
         match *self {
             Error::Unexpected((_, ref unexpected_span), expected) => {
                 let expected_str = match expected {
@@ -181,6 +168,7 @@ impl<'a> Error<'a> {
                         format!("expected {}", expected_str).into(),
                     )],
                     notes: vec![],
+                    code,
                 }
             },
             Error::BadU32(ref bad_span, ref err) => ParseError {
@@ -190,6 +178,7 @@ impl<'a> Error<'a> {
                 ),
                 labels: vec![(bad_span.clone(), "expected positive integer".into())],
                 notes: vec![err.to_string()],
+                code,
             },
             Error::BadI32(ref bad_span, ref err) => ParseError {
                 message: format!(
@@ -198,6 +187,7 @@ impl<'a> Error<'a> {
                 ),
                 labels: vec![(bad_span.clone(), "expected integer".into())],
                 notes: vec![err.to_string()],
+                code,
             },
             Error::BadFloat(ref bad_span, ref err) => ParseError {
                 message: format!(
@@ -206,6 +196,7 @@ impl<'a> Error<'a> {
                 ),
                 labels: vec![(bad_span.clone(), "expected floating-point literal".into())],
                 notes: vec![err.to_string()],
+                code,
             },
             Error::BadU32Constant(ref bad_span) => ParseError {
                 message: format!(
@@ -214,12 +205,14 @@ impl<'a> Error<'a> {
                 ),
                 labels: vec![(bad_span.clone(), "expected non-negative integer".into())],
                 notes: vec![],
+                code,
             },
 
             Error::BadScalarWidth(ref bad_span, width) => ParseError {
                 message: format!("invalid width of `{}` for literal", width,),
                 labels: vec![(bad_span.clone(), "invalid width".into())],
                 notes: vec!["valid widths are 8, 16, 32, 64".to_string()],
+                code,
             },
             Error::BadAccessor(ref accessor_span) => ParseError {
                 message: format!(
@@ -228,26 +221,43 @@ impl<'a> Error<'a> {
                 ),
                 labels: vec![(accessor_span.clone(), "invalid accessor".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownIdent(ref ident_span, ident) => ParseError {
                 message: format!("no definition in scope for identifier: '{}'", ident),
                 labels: vec![(ident_span.clone(), "unknown identifier".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownScalarType(ref bad_span) => ParseError {
                 message: format!("unknown scalar type: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown scalar type".into())],
                 notes: vec!["Valid scalar types are f16, f32, f64, i8, i16, i32, i64, u8, u16, u32, u64, bool".into()],
+                code,
             },
             Error::BadTextureSampleType { ref span, kind, width } => ParseError {
                 message: format!("texture sample type must be one of f32, i32 or u32, but found {}", kind.to_wgsl(width)),
                 labels: vec![(span.clone(), "must be one of f32, i32 or u32".into())],
                 notes: vec![],
+                code,
+            },
+            Error::BadMatrixScalarType { ref span, kind, width } => ParseError {
+                message: format!("matrix scalar type must be a floating-point type, but found {}", kind.to_wgsl(width)),
+                labels: vec![(span.clone(), "must be a floating-point type".into())],
+                notes: vec![],
+                code,
             },
             Error::BadTexture(ref bad_span) => ParseError {
                 message: format!("expected an image, but found '{}' which is not an image", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "not an image".into())],
                 notes: vec![],
+                code,
+            },
+            Error::BadGatherComponent(ref bad_span) => ParseError {
+                message: format!("gather component must be 0, 1, 2 or 3, but found '{}'", &source[bad_span.clone()]),
+                labels: vec![(bad_span.clone(), "not a component index".into())],
+                notes: vec![],
+                code,
             },
             Error::BadTypeCast { ref span, ref from_type, ref to_type } => {
                 let msg = format!("cannot cast a {} to a {}", from_type, to_type);
@@ -255,92 +265,141 @@ impl<'a> Error<'a> {
                     message: msg.clone(),
                     labels: vec![(span.clone(), msg.into())],
                     notes: vec![],
+                    code,
                 }
             },
             Error::InvalidResolve(ref resolve_error) => ParseError {
                 message: resolve_error.to_string(),
                 labels: vec![],
                 notes: vec![],
+                code,
             },
             Error::InvalidForInitializer(ref bad_span) => ParseError {
                 message: format!("for(;;) initializer is not an assignment or a function call: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "not an assignment or function call".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownStorageClass(ref bad_span) => ParseError {
                 message: format!("unknown storage class: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown storage class".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownAttribute(ref bad_span) => ParseError {
                 message: format!("unknown attribute: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown attribute".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownBuiltin(ref bad_span) => ParseError {
                 message: format!("unknown builtin: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown builtin".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownAccess(ref bad_span) => ParseError {
                 message: format!("unknown access: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown access".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownShaderStage(ref bad_span) => ParseError {
                 message: format!("unknown shader stage: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown shader stage".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownStorageFormat(ref bad_span) => ParseError {
                 message: format!("unknown storage format: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown storage format".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownConservativeDepth(ref bad_span) => ParseError {
                 message: format!("unknown conservative depth: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown conservative depth".into())],
                 notes: vec![],
+                code,
+            },
+            Error::UnknownExtension(ref bad_span) => ParseError {
+                message: format!("unknown extension: '{}'", &source[bad_span.clone()]),
+                labels: vec![(bad_span.clone(), "unknown extension".into())],
+                notes: vec![],
+                code,
+            },
+            Error::ExtensionNotEnabled(ref bad_span, extension) => ParseError {
+                message: format!("'{}' requires the {} extension, which is not enabled", &source[bad_span.clone()], extension),
+                labels: vec![(bad_span.clone(), format!("requires 'enable {};'", extension).into())],
+                notes: vec![],
+                code,
             },
             Error::UnknownType(ref bad_span) => ParseError {
                 message: format!("unknown type: '{}'", &source[bad_span.clone()]),
                 labels: vec![(bad_span.clone(), "unknown type".into())],
                 notes: vec![],
+                code,
             },
             Error::ZeroStride(ref bad_span) => ParseError {
                 message: "array stride must not be zero".to_string(),
                 labels: vec![(bad_span.clone(), "array stride must not be zero".into())],
                 notes: vec![],
+                code,
             },
             Error::ZeroSizeOrAlign(ref bad_span) => ParseError {
                 message: "struct member size or alignment must not be 0".to_string(),
                 labels: vec![(bad_span.clone(), "struct member size or alignment must not be 0".into())],
                 notes: vec![],
+                code,
             },
             Error::InconsistentBinding(ref span) => ParseError {
                 message: "input/output binding is not consistent".to_string(),
                 labels: vec![(span.clone(), "input/output binding is not consistent".into())],
                 notes: vec![],
+                code,
             },
             Error::UnknownLocalFunction(ref span) => ParseError {
                 message: format!("unknown local function `{}`", &source[span.clone()]),
                 labels: vec![(span.clone(), "unknown local function".into())],
                 notes: vec![],
+                code,
             },
             Error::InitializationTypeMismatch(ref name_span, ref expected_ty) => ParseError {
                 message: format!("the type of `{}` is expected to be {:?}", &source[name_span.clone()], expected_ty),
                 labels: vec![(name_span.clone(), format!("definition of `{}`", &source[name_span.clone()]).into())],
                 notes: vec![],
+                code,
             },
             Error::MissingType(ref name_span) => ParseError {
                 message: format!("variable `{}` needs a type", &source[name_span.clone()]),
                 labels: vec![(name_span.clone(), format!("definition of `{}`", &source[name_span.clone()]).into())],
                 notes: vec![],
+                code,
+            },
+            Error::ChainedComparison(ref first_span, ref second_span) => ParseError {
+                message: "comparison operators can't be chained".to_string(),
+                labels: vec![
+                    (first_span.clone(), "first comparison".into()),
+                    (second_span.clone(), "second comparison".into()),
+                ],
+                notes: vec![format!(
+                    "`a {0} b {1} c` would compare the boolean result of `a {0} b` with `c`, split it into `a {0} b && b {1} c` instead",
+                    &source[first_span.clone()],
+                    &source[second_span.clone()],
+                )],
+                code,
+            },
+            Error::AssignmentInExpression(ref span) => ParseError {
+                message: "assignment can't be used as an expression".to_string(),
+                labels: vec![(span.clone(), "unexpected assignment".into())],
+                notes: vec!["in WGSL, assignment is a statement and has no value, so `a = b = c;` has to be written as `b = c; a = b;`".to_string()],
+                code,
             },
             Error::Other => ParseError {
                 message: "other error".to_string(),
                 labels: vec![],
                 notes: vec![],
+                code,
             },
         }
     }
@@ -394,7 +453,7 @@ impl crate::TypeInner {
     /// Note: The names of a `TypeInner::Struct` is not known. Therefore this method will simply return "struct" for them.
     pub fn to_wgsl(
         &self,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
         constants: &Arena<crate::Constant>,
     ) -> String {
         match *self {
@@ -452,7 +511,8 @@ impl crate::TypeInner {
 
                 let class_suffix = match class {
                     crate::ImageClass::Sampled { multi: true, .. } => "_multisampled",
-                    crate::ImageClass::Depth => "_depth",
+                    crate::ImageClass::Depth { multi: false } => "_depth",
+                    crate::ImageClass::Depth { multi: true } => "_depth_multisampled",
                     _ => "",
                 };
 
@@ -464,7 +524,7 @@ impl crate::TypeInner {
                         let element_type = kind.to_wgsl(4);
                         format!("<{}>", element_type)
                     }
-                    crate::ImageClass::Depth => String::new(),
+                    crate::ImageClass::Depth { .. } => String::new(),
                     crate::ImageClass::Storage(format) => {
                         format!("<{}>", format.to_wgsl())
                     }
@@ -476,6 +536,17 @@ impl crate::TypeInner {
                 )
             }
             crate::TypeInner::Sampler { .. } => "sampler".to_string(),
+            crate::TypeInner::BindingArray { base, size } => {
+                let member_type = &types[base];
+                let base = member_type.name.as_deref().unwrap_or("unknown");
+                match size {
+                    crate::ArraySize::Constant(size) => {
+                        let size = constants[size].name.as_deref().unwrap_or("unknown");
+                        format!("binding_array<{}, {}>", base, size)
+                    }
+                    crate::ArraySize::Dynamic => format!("binding_array<{}>", base),
+                }
+            }
         }
     }
 }
@@ -483,7 +554,7 @@ impl crate::TypeInner {
 mod type_inner_tests {
     #[test]
     fn to_wgsl() {
-        let mut types = crate::Arena::new();
+        let mut types = crate::UniqueArena::new();
         let mut constants = crate::Arena::new();
         let c = constants.append(crate::Constant {
             name: Some("C".to_string()),
@@ -494,7 +565,7 @@ mod type_inner_tests {
             },
         });
 
-        let mytype1 = types.append(crate::Type {
+        let mytype1 = types.insert(crate::Type {
             name: Some("MyType1".to_string()),
             inner: crate::TypeInner::Struct {
                 top_level: true,
@@ -502,7 +573,7 @@ mod type_inner_tests {
                 span: 0,
             },
         });
-        let mytype2 = types.append(crate::Type {
+        let mytype2 = types.insert(crate::Type {
             name: Some("MyType2".to_string()),
             inner: crate::TypeInner::Struct {
                 top_level: true,
@@ -547,9 +618,19 @@ mod type_inner_tests {
         let img2 = crate::TypeInner::Image {
             dim: crate::ImageDimension::Cube,
             arrayed: true,
-            class: crate::ImageClass::Depth,
+            class: crate::ImageClass::Depth { multi: false },
         };
         assert_eq!(img2.to_wgsl(&types, &constants), "texture_depth_cube_array");
+
+        let img3 = crate::TypeInner::Image {
+            dim: crate::ImageDimension::D2,
+            arrayed: false,
+            class: crate::ImageClass::Depth { multi: true },
+        };
+        assert_eq!(
+            img3.to_wgsl(&types, &constants),
+            "texture_depth_multisampled_2d"
+        );
     }
 }
 
@@ -585,7 +666,7 @@ struct StatementContext<'input, 'temp, 'out> {
     variables: &'out mut Arena<crate::LocalVariable>,
     expressions: &'out mut Arena<crate::Expression>,
     named_expressions: &'out mut FastHashMap<Handle<crate::Expression>, String>,
-    types: &'out mut Arena<crate::Type>,
+    types: &'out mut UniqueArena<crate::Type>,
     constants: &'out mut Arena<crate::Constant>,
     global_vars: &'out Arena<crate::GlobalVariable>,
     functions: &'out Arena<crate::Function>,
@@ -641,10 +722,10 @@ struct ExpressionContext<'input, 'temp, 'out> {
     lookup_ident: &'temp FastHashMap<&'input str, Handle<crate::Expression>>,
     typifier: &'temp mut super::Typifier,
     expressions: &'out mut Arena<crate::Expression>,
-    types: &'out mut Arena<crate::Type>,
+    types: &'out mut UniqueArena<crate::Type>,
     constants: &'out mut Arena<crate::Constant>,
     global_vars: &'out Arena<crate::GlobalVariable>,
-    local_vars: &'out Arena<crate::LocalVariable>,
+    local_vars: &'out mut Arena<crate::LocalVariable>,
     arguments: &'out [crate::FunctionArgument],
     functions: &'out Arena<crate::Function>,
     block: &'temp mut crate::Block,
@@ -686,12 +767,75 @@ impl<'a> ExpressionContext<'a, '_, '_> {
         }
     }
 
+    /// Append an expression that needs no `Emit`, like a constant, pausing
+    /// the emitter around it.
+    fn append_pre_emitted(&mut self, expression: crate::Expression) -> Handle<crate::Expression> {
+        self.block.extend(self.emitter.finish(self.expressions));
+        let handle = self.expressions.append(expression);
+        self.emitter.start(self.expressions);
+        handle
+    }
+
+    /// Returns the component `index` of `base`, if `base` is a constant
+    /// composite, so that constant lookups are folded at parse time.
+    fn fold_constant_access(
+        &mut self,
+        base: Handle<crate::Expression>,
+        index: u32,
+    ) -> Option<Handle<crate::Expression>> {
+        let component = match self.expressions[base] {
+            crate::Expression::Constant(constant) => match self.constants[constant].inner {
+                ConstantInner::Composite { ref components, .. } => {
+                    *components.get(index as usize)?
+                }
+                ConstantInner::Scalar { .. } => return None,
+            },
+            _ => return None,
+        };
+        Some(self.append_pre_emitted(crate::Expression::Constant(component)))
+    }
+
+    /// Returns a pointer to a local copy of `base`, if it's a constant array.
+    ///
+    /// Arrays can only be indexed dynamically behind a pointer, so lookup
+    /// tables are copied to a local variable initialized with them.
+    fn spill_constant_array(
+        &mut self,
+        base: Handle<crate::Expression>,
+    ) -> Option<Handle<crate::Expression>> {
+        let constant = match self.expressions[base] {
+            crate::Expression::Constant(constant) => constant,
+            _ => return None,
+        };
+        let ty = match self.constants[constant].inner {
+            ConstantInner::Composite { ty, .. } => match self.types[ty].inner {
+                crate::TypeInner::Array { .. } => ty,
+                _ => return None,
+            },
+            ConstantInner::Scalar { .. } => return None,
+        };
+        // reuse the copy made by a previous lookup in this function
+        let existing = self
+            .local_vars
+            .iter()
+            .find(|&(_, var)| var.name.is_none() && var.init == Some(constant))
+            .map(|(handle, _)| handle);
+        let variable = match existing {
+            Some(handle) => handle,
+            None => self.local_vars.append(crate::LocalVariable {
+                name: None,
+                ty,
+                init: Some(constant),
+            }),
+        };
+        Some(self.append_pre_emitted(crate::Expression::LocalVariable(variable)))
+    }
+
     fn prepare_sampling(
         &mut self,
-        image_name: &'a str,
+        image: Handle<crate::Expression>,
         span: Span,
     ) -> Result<SamplingContext, Error<'a>> {
-        let image = self.lookup_ident.lookup(image_name, span.clone())?;
         Ok(SamplingContext {
             image,
             arrayed: match *self.resolve_type(image)? {
@@ -721,6 +865,32 @@ impl<'a> ExpressionContext<'a, '_, '_> {
         Ok(left)
     }
 
+    /// Parses a binary operator that can't be chained, like the comparisons.
+    fn parse_non_associative_binary_op(
+        &mut self,
+        lexer: &mut Lexer<'a>,
+        classifier: impl Fn(Token<'a>) -> Option<crate::BinaryOperator>,
+        mut parser: impl FnMut(
+            &mut Lexer<'a>,
+            ExpressionContext<'a, '_, '_>,
+        ) -> Result<Handle<crate::Expression>, Error<'a>>,
+    ) -> Result<Handle<crate::Expression>, Error<'a>> {
+        let left = parser(lexer, self.reborrow())?;
+        let op = match classifier(lexer.peek().0) {
+            Some(op) => op,
+            None => return Ok(left),
+        };
+        let (_, op_span) = lexer.next();
+        let right = parser(lexer, self.reborrow())?;
+        let (next, next_span) = lexer.peek();
+        if classifier(next).is_some() {
+            return Err(Error::ChainedComparison(op_span, next_span));
+        }
+        Ok(self
+            .expressions
+            .append(crate::Expression::Binary { op, left, right }))
+    }
+
     fn parse_binary_splat_op(
         &mut self,
         lexer: &mut Lexer<'a>,
@@ -780,7 +950,7 @@ impl Composition {
         }
     }
 
-    fn extract_impl(name: &str, name_span: Span) -> Result<u32, Error> {
+    fn extract_impl(name: &str, name_span: Span) -> Result<u32, Error<'_>> {
         let ch = name
             .chars()
             .next()
@@ -795,12 +965,16 @@ impl Composition {
         base: Handle<crate::Expression>,
         name: &str,
         name_span: Span,
-    ) -> Result<crate::Expression, Error> {
+    ) -> Result<crate::Expression, Error<'_>> {
+        // only a single component of a reference can be accessed
+        if name.len() > 1 {
+            return Err(Error::BadAccessor(name_span));
+        }
         Self::extract_impl(name, name_span)
             .map(|index| crate::Expression::AccessIndex { base, index })
     }
 
-    fn make(name: &str, name_span: Span) -> Result<Self, Error> {
+    fn make(name: &str, name_span: Span) -> Result<Self, Error<'_>> {
         if name.len() > 1 {
             let mut components = [crate::SwizzleComponent::X; 4];
             for (comp, ch) in components.iter_mut().zip(name.chars()) {
@@ -924,6 +1098,7 @@ pub struct ParseError {
     message: String,
     labels: Vec<(Span, Cow<'static, str>)>,
     notes: Vec<String>,
+    code: String,
 }
 
 impl ParseError {
@@ -965,6 +1140,27 @@ impl ParseError {
         writer.into_string()
     }
 
+    /// Describe the error as a [`Diagnostic`](crate::diagnostic::Diagnostic).
+    ///
+    /// The first label of the message is the primary one.
+    pub fn to_diagnostic(&self) -> crate::diagnostic::Diagnostic {
+        use crate::diagnostic::{Diagnostic, Label, Severity};
+
+        let mut labels = self.labels.iter().map(|label| Label {
+            span: label.0.clone(),
+            message: label.1.to_string(),
+        });
+        Diagnostic {
+            severity: Severity::Error,
+            code: Some(self.code.clone()),
+            message: self.message.clone(),
+            primary: labels.next(),
+            secondary: labels.collect(),
+            notes: self.notes.clone(),
+            location: None,
+        }
+    }
+
     /// Returns the 1-based line number and column of the first label in the
     /// error message.
     pub fn location(&self, source: &str) -> (usize, usize) {
@@ -997,6 +1193,8 @@ pub struct Parser {
     scopes: Vec<Scope>,
     lookup_type: FastHashMap<String, Handle<crate::Type>>,
     layouter: Layouter,
+    /// Errors of the statements that were skipped.
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
@@ -1005,10 +1203,12 @@ impl Parser {
             scopes: Vec::new(),
             lookup_type: FastHashMap::default(),
             layouter: Default::default(),
+            errors: Vec::new(),
         }
     }
 
     fn get_constant_inner<'a>(
+        lexer: &Lexer<'a>,
         word: &'a str,
         ty: char,
         width: &'a str,
@@ -1030,17 +1230,22 @@ impl Parser {
                 .map_err(|e| Error::BadFloat(span.clone(), e))?,
             _ => unreachable!(),
         };
-        Ok(crate::ConstantInner::Scalar {
-            value,
-            width: if width.is_empty() {
-                4
-            } else {
-                match width.parse::<crate::Bytes>() {
-                    Ok(bits) if (bits % 8) == 0 => Ok(bits / 8),
-                    _ => Err(Error::BadScalarWidth(span, width)),
-                }?
-            },
-        })
+        let width = if width.is_empty() {
+            4
+        } else {
+            match width.parse::<crate::Bytes>() {
+                Ok(bits) if (bits % 8) == 0 => Ok(bits / 8),
+                _ => Err(Error::BadScalarWidth(span.clone(), width)),
+            }?
+        };
+        let kind = match value {
+            crate::ScalarValue::Sint(_) => crate::ScalarKind::Sint,
+            crate::ScalarValue::Uint(_) => crate::ScalarKind::Uint,
+            crate::ScalarValue::Float(_) => crate::ScalarKind::Float,
+            crate::ScalarValue::Bool(_) => crate::ScalarKind::Bool,
+        };
+        lexer.check_scalar(kind, width, span)?;
+        Ok(crate::ConstantInner::Scalar { value, width })
     }
 
     fn parse_local_function_call<'a>(
@@ -1082,11 +1287,11 @@ impl Parser {
             let argument = self.parse_general_expression(lexer, ctx.reborrow())?;
             lexer.close_arguments()?;
             crate::Expression::Relational { fun, argument }
-        } else if let Some(axis) = conv::map_derivative_axis(name) {
+        } else if let Some((axis, ctrl)) = conv::map_derivative(name) {
             lexer.open_arguments()?;
             let expr = self.parse_general_expression(lexer, ctx.reborrow())?;
             lexer.close_arguments()?;
-            crate::Expression::Derivative { axis, expr }
+            crate::Expression::Derivative { axis, ctrl, expr }
         } else if let Some(fun) = conv::map_standard_fun(name) {
             lexer.open_arguments()?;
             let arg_count = fun.argument_count();
@@ -1133,12 +1338,13 @@ impl Parser {
             match name {
                 "textureSample" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
-                    let (sampler_name, sampler_span) = lexer.next_ident_with_span()?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
-                    let sc = ctx.prepare_sampling(image_name, image_span)?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
                     let array_index = if sc.arrayed {
                         lexer.expect(Token::Separator(','))?;
                         Some(self.parse_general_expression(lexer, ctx.reborrow())?)
@@ -1153,7 +1359,8 @@ impl Parser {
                     lexer.close_arguments()?;
                     crate::Expression::ImageSample {
                         image: sc.image,
-                        sampler: ctx.lookup_ident.lookup(sampler_name, sampler_span)?,
+                        sampler,
+                        gather: None,
                         coordinate,
                         array_index,
                         offset,
@@ -1163,12 +1370,13 @@ impl Parser {
                 }
                 "textureSampleLevel" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
-                    let (sampler_name, sampler_span) = lexer.next_ident_with_span()?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
-                    let sc = ctx.prepare_sampling(image_name, image_span)?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
                     let array_index = if sc.arrayed {
                         lexer.expect(Token::Separator(','))?;
                         Some(self.parse_general_expression(lexer, ctx.reborrow())?)
@@ -1185,7 +1393,8 @@ impl Parser {
                     lexer.close_arguments()?;
                     crate::Expression::ImageSample {
                         image: sc.image,
-                        sampler: ctx.lookup_ident.lookup(sampler_name, sampler_span)?,
+                        sampler,
+                        gather: None,
                         coordinate,
                         array_index,
                         offset,
@@ -1195,12 +1404,13 @@ impl Parser {
                 }
                 "textureSampleBias" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
-                    let (sampler_name, sampler_span) = lexer.next_ident_with_span()?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
-                    let sc = ctx.prepare_sampling(image_name, image_span)?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
                     let array_index = if sc.arrayed {
                         lexer.expect(Token::Separator(','))?;
                         Some(self.parse_general_expression(lexer, ctx.reborrow())?)
@@ -1217,7 +1427,8 @@ impl Parser {
                     lexer.close_arguments()?;
                     crate::Expression::ImageSample {
                         image: sc.image,
-                        sampler: ctx.lookup_ident.lookup(sampler_name, sampler_span)?,
+                        sampler,
+                        gather: None,
                         coordinate,
                         array_index,
                         offset,
@@ -1227,12 +1438,13 @@ impl Parser {
                 }
                 "textureSampleGrad" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
-                    let (sampler_name, sampler_span) = lexer.next_ident_with_span()?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
-                    let sc = ctx.prepare_sampling(image_name, image_span)?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
                     let array_index = if sc.arrayed {
                         lexer.expect(Token::Separator(','))?;
                         Some(self.parse_general_expression(lexer, ctx.reborrow())?)
@@ -1251,7 +1463,8 @@ impl Parser {
                     lexer.close_arguments()?;
                     crate::Expression::ImageSample {
                         image: sc.image,
-                        sampler: ctx.lookup_ident.lookup(sampler_name, sampler_span)?,
+                        sampler,
+                        gather: None,
                         coordinate,
                         array_index,
                         offset,
@@ -1261,12 +1474,13 @@ impl Parser {
                 }
                 "textureSampleCompare" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
-                    let (sampler_name, sampler_span) = lexer.next_ident_with_span()?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
-                    let sc = ctx.prepare_sampling(image_name, image_span)?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
                     let array_index = if sc.arrayed {
                         lexer.expect(Token::Separator(','))?;
                         Some(self.parse_general_expression(lexer, ctx.reborrow())?)
@@ -1283,7 +1497,8 @@ impl Parser {
                     lexer.close_arguments()?;
                     crate::Expression::ImageSample {
                         image: sc.image,
-                        sampler: ctx.lookup_ident.lookup(sampler_name, sampler_span)?,
+                        sampler,
+                        gather: None,
                         coordinate,
                         array_index,
                         offset,
@@ -1293,12 +1508,95 @@ impl Parser {
                 }
                 "textureSampleCompareLevel" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
+                    lexer.expect(Token::Separator(','))?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
+                    lexer.expect(Token::Separator(','))?;
+                    let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
+                    let array_index = if sc.arrayed {
+                        lexer.expect(Token::Separator(','))?;
+                        Some(self.parse_general_expression(lexer, ctx.reborrow())?)
+                    } else {
+                        None
+                    };
+                    lexer.expect(Token::Separator(','))?;
+                    let reference = self.parse_general_expression(lexer, ctx.reborrow())?;
+                    let offset = if lexer.skip(Token::Separator(',')) {
+                        Some(self.parse_const_expression(lexer, ctx.types, ctx.constants)?)
+                    } else {
+                        None
+                    };
+                    lexer.close_arguments()?;
+                    crate::Expression::ImageSample {
+                        image: sc.image,
+                        sampler,
+                        gather: None,
+                        coordinate,
+                        array_index,
+                        offset,
+                        level: crate::SampleLevel::Zero,
+                        depth_ref: Some(reference),
+                    }
+                }
+                "textureGather" => {
+                    lexer.open_arguments()?;
+                    // Depth images have no component argument
+                    let component = match lexer.peek() {
+                        (Token::Number { .. }, span) => {
+                            use crate::SwizzleComponent as Sc;
+                            let component = match lexer.next_uint_literal()? {
+                                0 => Sc::X,
+                                1 => Sc::Y,
+                                2 => Sc::Z,
+                                3 => Sc::W,
+                                _ => return Err(Error::BadGatherComponent(span)),
+                            };
+                            lexer.expect(Token::Separator(','))?;
+                            component
+                        }
+                        _ => crate::SwizzleComponent::X,
+                    };
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
+                    lexer.expect(Token::Separator(','))?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
+                    lexer.expect(Token::Separator(','))?;
+                    let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
+                    let array_index = if sc.arrayed {
+                        lexer.expect(Token::Separator(','))?;
+                        Some(self.parse_general_expression(lexer, ctx.reborrow())?)
+                    } else {
+                        None
+                    };
+                    let offset = if lexer.skip(Token::Separator(',')) {
+                        Some(self.parse_const_expression(lexer, ctx.types, ctx.constants)?)
+                    } else {
+                        None
+                    };
+                    lexer.close_arguments()?;
+                    crate::Expression::ImageSample {
+                        image: sc.image,
+                        sampler,
+                        gather: Some(component),
+                        coordinate,
+                        array_index,
+                        offset,
+                        level: crate::SampleLevel::Zero,
+                        depth_ref: None,
+                    }
+                }
+                "textureGatherCompare" => {
+                    lexer.open_arguments()?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
-                    let (sampler_name, sampler_span) = lexer.next_ident_with_span()?;
+                    let (sampler, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
-                    let sc = ctx.prepare_sampling(image_name, image_span)?;
+                    let sc = ctx.prepare_sampling(image, image_span)?;
                     let array_index = if sc.arrayed {
                         lexer.expect(Token::Separator(','))?;
                         Some(self.parse_general_expression(lexer, ctx.reborrow())?)
@@ -1315,7 +1613,8 @@ impl Parser {
                     lexer.close_arguments()?;
                     crate::Expression::ImageSample {
                         image: sc.image,
-                        sampler: ctx.lookup_ident.lookup(sampler_name, sampler_span)?,
+                        sampler,
+                        gather: Some(crate::SwizzleComponent::X),
                         coordinate,
                         array_index,
                         offset,
@@ -1325,8 +1624,8 @@ impl Parser {
                 }
                 "textureLoad" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
-                    let image = ctx.lookup_ident.lookup(image_name, image_span.clone())?;
+                    let (image, image_span) =
+                        self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.expect(Token::Separator(','))?;
                     let coordinate = self.parse_general_expression(lexer, ctx.reborrow())?;
                     let (class, arrayed) = match *ctx.resolve_type(image)? {
@@ -1342,7 +1641,7 @@ impl Parser {
                     let index = match class {
                         crate::ImageClass::Storage(_) => None,
                         // it's the MSAA index for multi-sampled, and LOD for the others
-                        crate::ImageClass::Sampled { .. } | crate::ImageClass::Depth => {
+                        crate::ImageClass::Sampled { .. } | crate::ImageClass::Depth { .. } => {
                             lexer.expect(Token::Separator(','))?;
                             Some(self.parse_general_expression(lexer, ctx.reborrow())?)
                         }
@@ -1357,8 +1656,7 @@ impl Parser {
                 }
                 "textureDimensions" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
-                    let image = ctx.lookup_ident.lookup(image_name, image_span)?;
+                    let (image, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     let level = if lexer.skip(Token::Separator(',')) {
                         let expr = self.parse_general_expression(lexer, ctx.reborrow())?;
                         Some(expr)
@@ -1373,8 +1671,7 @@ impl Parser {
                 }
                 "textureNumLevels" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
-                    let image = ctx.lookup_ident.lookup(image_name, image_span)?;
+                    let (image, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.close_arguments()?;
                     crate::Expression::ImageQuery {
                         image,
@@ -1383,8 +1680,7 @@ impl Parser {
                 }
                 "textureNumLayers" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
-                    let image = ctx.lookup_ident.lookup(image_name, image_span)?;
+                    let (image, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.close_arguments()?;
                     crate::Expression::ImageQuery {
                         image,
@@ -1393,8 +1689,7 @@ impl Parser {
                 }
                 "textureNumSamples" => {
                     lexer.open_arguments()?;
-                    let (image_name, image_span) = lexer.next_ident_with_span()?;
-                    let image = ctx.lookup_ident.lookup(image_name, image_span)?;
+                    let (image, _) = self.parse_handle_expression(lexer, ctx.reborrow())?;
                     lexer.close_arguments()?;
                     crate::Expression::ImageQuery {
                         image,
@@ -1431,6 +1726,7 @@ impl Parser {
         &mut self,
         lexer: &mut Lexer<'a>,
         type_name: &'a str,
+        type_span: Span,
         mut ctx: ExpressionContext<'a, '_, '_>,
     ) -> Result<Option<Handle<crate::Expression>>, Error<'a>> {
         let ty_resolution = match self.lookup_type.get(type_name) {
@@ -1439,6 +1735,7 @@ impl Parser {
                 lexer,
                 TypeAttributes::default(),
                 type_name,
+                type_span,
                 ctx.types,
                 ctx.constants,
             )? {
@@ -1509,9 +1806,7 @@ impl Parser {
         } else {
             let ty = match ty_resolution {
                 TypeResolution::Handle(handle) => handle,
-                TypeResolution::Value(inner) => {
-                    ctx.types.fetch_or_append(crate::Type { name: None, inner })
-                }
+                TypeResolution::Value(inner) => ctx.types.insert(crate::Type { name: None, inner }),
             };
             components.push(last_component);
             crate::Expression::Compose { ty, components }
@@ -1525,7 +1820,7 @@ impl Parser {
         first_token_span: TokenSpan<'a>,
         lexer: &mut Lexer<'a>,
         register_name: Option<&'a str>,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<Handle<crate::Constant>, Error<'a>> {
         self.scopes.push(Scope::ConstantExpr);
@@ -1533,7 +1828,7 @@ impl Parser {
             (Token::Word("true"), _) => crate::ConstantInner::boolean(true),
             (Token::Word("false"), _) => crate::ConstantInner::boolean(false),
             (Token::Number { value, ty, width }, _) => {
-                Self::get_constant_inner(value, ty, width, first_token_span)?
+                Self::get_constant_inner(lexer, value, ty, width, first_token_span)?
             }
             (Token::Word(name), name_span) => {
                 // look for an existing constant first
@@ -1592,7 +1887,7 @@ impl Parser {
     fn parse_const_expression<'a>(
         &mut self,
         lexer: &mut Lexer<'a>,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<Handle<crate::Constant>, Error<'a>> {
         self.parse_const_expression_impl(lexer.next(), lexer, None, type_arena, const_arena)
@@ -1615,13 +1910,7 @@ impl Parser {
             | token @ (Token::Number { .. }, _) => {
                 let const_handle =
                     self.parse_const_expression_impl(token, lexer, None, ctx.types, ctx.constants)?;
-                // pause the emitter while generating this expression, since it's pre-emitted
-                ctx.block.extend(ctx.emitter.finish(ctx.expressions));
-                let expr = ctx
-                    .expressions
-                    .append(crate::Expression::Constant(const_handle));
-                ctx.emitter.start(ctx.expressions);
-                expr
+                ctx.append_pre_emitted(crate::Expression::Constant(const_handle))
             }
             (Token::Word(word), span) => {
                 if let Some(&expr) = ctx.lookup_ident.get(word) {
@@ -1631,7 +1920,9 @@ impl Parser {
                 {
                     //TODO: resolve the duplicate call in `parse_singular_expression`
                     expr
-                } else if let Some(expr) = self.parse_construction(lexer, word, ctx.reborrow())? {
+                } else if let Some(expr) =
+                    self.parse_construction(lexer, word, span.clone(), ctx.reborrow())?
+                {
                     expr
                 } else {
                     return Err(Error::UnknownIdent(span, word));
@@ -1752,6 +2043,10 @@ impl Parser {
                             index,
                         }
                     } else {
+                        if let Some(pointer) = ctx.spill_constant_array(handle) {
+                            handle = pointer;
+                            needs_deref = true;
+                        }
                         crate::Expression::Access {
                             base: handle,
                             index,
@@ -1769,10 +2064,31 @@ impl Parser {
                 }
             };
 
+            if let crate::Expression::AccessIndex { base, index } = expression {
+                if let Some(component) = ctx.fold_constant_access(base, index) {
+                    handle = component;
+                    continue;
+                }
+            }
             handle = ctx.expressions.append(expression);
         }
     }
 
+    /// Parse the image or sampler operand of a texture builtin.
+    ///
+    /// This is the name of a handle variable, optionally indexed when the
+    /// variable is a binding array.
+    fn parse_handle_expression<'a>(
+        &mut self,
+        lexer: &mut Lexer<'a>,
+        mut ctx: ExpressionContext<'a, '_, '_>,
+    ) -> Result<(Handle<crate::Expression>, Span), Error<'a>> {
+        let (name, span) = lexer.next_ident_with_span()?;
+        let handle = ctx.lookup_ident.lookup(name, span.clone())?;
+        let handle = self.parse_postfix(lexer, ctx.reborrow(), handle, false)?;
+        Ok((handle, span))
+    }
+
     fn parse_singular_expression<'a>(
         &mut self,
         lexer: &mut Lexer<'a>,
@@ -1800,6 +2116,12 @@ impl Parser {
                 let handle = self.parse_primary_expression(lexer, ctx.reborrow())?;
                 (false, handle)
             }
+            Token::Operation('*') => {
+                let expr = crate::Expression::Load {
+                    pointer: self.parse_singular_expression(lexer, ctx.reborrow())?,
+                };
+                (false, ctx.expressions.append(expr))
+            }
             Token::Word(word) => {
                 let handle = match self.parse_function_call_inner(lexer, word, ctx.reborrow())? {
                     Some(handle) => handle,
@@ -1837,7 +2159,7 @@ impl Parser {
             },
             // relational_expression
             |lexer, mut context| {
-                context.parse_binary_op(
+                context.parse_non_associative_binary_op(
                     lexer,
                     |token| match token {
                         Token::Paren('<') => Some(crate::BinaryOperator::Less),
@@ -1961,6 +2283,9 @@ impl Parser {
                 )
             },
         )?;
+        if let (Token::Operation('='), span) = lexer.peek() {
+            return Err(Error::AssignmentInExpression(span));
+        }
         self.scopes.pop();
         Ok(handle)
     }
@@ -1968,7 +2293,7 @@ impl Parser {
     fn parse_variable_ident_decl<'a>(
         &mut self,
         lexer: &mut Lexer<'a>,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<(&'a str, Span, Handle<crate::Type>, crate::StorageAccess), Error<'a>> {
         let (name, name_span) = lexer.next_ident_with_span()?;
@@ -1980,7 +2305,7 @@ impl Parser {
     fn parse_variable_decl<'a>(
         &mut self,
         lexer: &mut Lexer<'a>,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<ParsedVariable<'a>, Error<'a>> {
         self.scopes.push(Scope::VariableDecl);
@@ -2014,7 +2339,7 @@ impl Parser {
     fn parse_struct_body<'a>(
         &mut self,
         lexer: &mut Lexer<'a>,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<(Vec<crate::StructMember>, u32), Error<'a>> {
         let mut offset = 0;
@@ -2108,12 +2433,23 @@ impl Parser {
         lexer: &mut Lexer<'a>,
         attribute: TypeAttributes,
         word: &'a str,
-        type_arena: &mut Arena<crate::Type>,
+        word_span: Span,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<Option<crate::TypeInner>, Error<'a>> {
         if let Some((kind, width)) = conv::get_scalar_type(word) {
+            lexer.check_scalar(kind, width, word_span)?;
             return Ok(Some(crate::TypeInner::Scalar { kind, width }));
         }
+        if let Some((columns, rows)) = conv::get_matrix_size(word) {
+            let (kind, width, span) = lexer.next_scalar_generic_with_span()?;
+            Self::check_matrix_scalar_type(lexer, kind, width, span)?;
+            return Ok(Some(crate::TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            }));
+        }
         Ok(Some(match word {
             "vec2" => {
                 let (kind, width) = lexer.next_scalar_generic()?;
@@ -2139,78 +2475,6 @@ impl Parser {
                     width,
                 }
             }
-            "mat2x2" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Bi,
-                    rows: crate::VectorSize::Bi,
-                    width,
-                }
-            }
-            "mat2x3" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Bi,
-                    rows: crate::VectorSize::Tri,
-                    width,
-                }
-            }
-            "mat2x4" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Bi,
-                    rows: crate::VectorSize::Quad,
-                    width,
-                }
-            }
-            "mat3x2" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Tri,
-                    rows: crate::VectorSize::Bi,
-                    width,
-                }
-            }
-            "mat3x3" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Tri,
-                    rows: crate::VectorSize::Tri,
-                    width,
-                }
-            }
-            "mat3x4" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Tri,
-                    rows: crate::VectorSize::Quad,
-                    width,
-                }
-            }
-            "mat4x2" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Quad,
-                    rows: crate::VectorSize::Bi,
-                    width,
-                }
-            }
-            "mat4x3" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Quad,
-                    rows: crate::VectorSize::Tri,
-                    width,
-                }
-            }
-            "mat4x4" => {
-                let (_, width) = lexer.next_scalar_generic()?;
-                crate::TypeInner::Matrix {
-                    columns: crate::VectorSize::Quad,
-                    rows: crate::VectorSize::Quad,
-                    width,
-                }
-            }
             "ptr" => {
                 lexer.expect_generic_paren('<')?;
                 let (ident, span) = lexer.next_ident_with_span()?;
@@ -2242,6 +2506,20 @@ impl Parser {
 
                 crate::TypeInner::Array { base, size, stride }
             }
+            "binding_array" => {
+                lexer.expect_generic_paren('<')?;
+                let (base, _access) = self.parse_type_decl(lexer, None, type_arena, const_arena)?;
+                let size = if lexer.skip(Token::Separator(',')) {
+                    let const_handle =
+                        self.parse_const_expression(lexer, type_arena, const_arena)?;
+                    crate::ArraySize::Constant(const_handle)
+                } else {
+                    crate::ArraySize::Dynamic
+                };
+                lexer.expect_generic_paren('>')?;
+
+                crate::TypeInner::BindingArray { base, size }
+            }
             "sampler" => crate::TypeInner::Sampler { comparison: false },
             "sampler_comparison" => crate::TypeInner::Sampler { comparison: true },
             "texture_1d" => {
@@ -2328,22 +2606,27 @@ impl Parser {
             "texture_depth_2d" => crate::TypeInner::Image {
                 dim: crate::ImageDimension::D2,
                 arrayed: false,
-                class: crate::ImageClass::Depth,
+                class: crate::ImageClass::Depth { multi: false },
             },
             "texture_depth_2d_array" => crate::TypeInner::Image {
                 dim: crate::ImageDimension::D2,
                 arrayed: true,
-                class: crate::ImageClass::Depth,
+                class: crate::ImageClass::Depth { multi: false },
             },
             "texture_depth_cube" => crate::TypeInner::Image {
                 dim: crate::ImageDimension::Cube,
                 arrayed: false,
-                class: crate::ImageClass::Depth,
+                class: crate::ImageClass::Depth { multi: false },
             },
             "texture_depth_cube_array" => crate::TypeInner::Image {
                 dim: crate::ImageDimension::Cube,
                 arrayed: true,
-                class: crate::ImageClass::Depth,
+                class: crate::ImageClass::Depth { multi: false },
+            },
+            "texture_depth_multisampled_2d" => crate::TypeInner::Image {
+                dim: crate::ImageDimension::D2,
+                arrayed: false,
+                class: crate::ImageClass::Depth { multi: true },
             },
             "texture_storage_1d" => {
                 let format = lexer.next_format_generic()?;
@@ -2402,6 +2685,19 @@ impl Parser {
         }
     }
 
+    fn check_matrix_scalar_type<'a>(
+        lexer: &Lexer<'a>,
+        kind: crate::ScalarKind,
+        width: u8,
+        span: Span,
+    ) -> Result<(), Error<'a>> {
+        // Matrices of integers or booleans can't be represented in the IR
+        match kind {
+            crate::ScalarKind::Float => lexer.check_scalar(kind, width, span),
+            _ => Err(Error::BadMatrixScalarType { span, kind, width }),
+        }
+    }
+
     /// Parse type declaration of a given name and attribute.
     #[allow(clippy::too_many_arguments)]
     fn parse_type_decl_name<'a>(
@@ -2411,14 +2707,21 @@ impl Parser {
         name_span: Span,
         debug_name: Option<&'a str>,
         attribute: TypeAttributes,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<Handle<crate::Type>, Error<'a>> {
         Ok(match self.lookup_type.get(name) {
             Some(&handle) => handle,
             None => {
-                match self.parse_type_decl_impl(lexer, attribute, name, type_arena, const_arena)? {
-                    Some(inner) => type_arena.fetch_or_append(crate::Type {
+                match self.parse_type_decl_impl(
+                    lexer,
+                    attribute,
+                    name,
+                    name_span.clone(),
+                    type_arena,
+                    const_arena,
+                )? {
+                    Some(inner) => type_arena.insert(crate::Type {
                         name: debug_name.map(|s| s.to_string()),
                         inner,
                     }),
@@ -2432,7 +2735,7 @@ impl Parser {
         &mut self,
         lexer: &mut Lexer<'a>,
         debug_name: Option<&'a str>,
-        type_arena: &mut Arena<crate::Type>,
+        type_arena: &mut UniqueArena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
     ) -> Result<(Handle<crate::Type>, crate::StorageAccess), Error<'a>> {
         self.scopes.push(Scope::TypeDecl);
@@ -2532,7 +2835,7 @@ impl Parser {
                 self.scopes.push(Scope::Block);
                 let mut statements = Vec::new();
                 while !lexer.skip(Token::Paren('}')) {
-                    self.parse_statement(
+                    self.parse_block_statement(
                         lexer,
                         context.reborrow(),
                         &mut statements,
@@ -2634,9 +2937,9 @@ impl Parser {
                             // register the type, if needed
                             match context.typifier[value].clone() {
                                 TypeResolution::Handle(ty) => ty,
-                                TypeResolution::Value(inner) => context
-                                    .types
-                                    .fetch_or_append(crate::Type { name: None, inner }),
+                                TypeResolution::Value(inner) => {
+                                    context.types.insert(crate::Type { name: None, inner })
+                                }
                             }
                         }
                     };
@@ -2787,7 +3090,12 @@ impl Parser {
                                 if lexer.skip(Token::Paren('}')) {
                                     break false;
                                 }
-                                self.parse_statement(lexer, context.reborrow(), &mut body, false)?;
+                                self.parse_block_statement(
+                                    lexer,
+                                    context.reborrow(),
+                                    &mut body,
+                                    false,
+                                )?;
                             };
 
                             cases.push(crate::SwitchCase {
@@ -2825,7 +3133,7 @@ impl Parser {
                     if lexer.skip(Token::Paren('}')) {
                         break;
                     }
-                    self.parse_statement(lexer, context.reborrow(), &mut body, false)?;
+                    self.parse_block_statement(lexer, context.reborrow(), &mut body, false)?;
                 }
 
                 block.push(crate::Statement::Loop { body, continuing });
@@ -2883,7 +3191,7 @@ impl Parser {
                 lexer.expect(Token::Paren('{'))?;
 
                 while !lexer.skip(Token::Paren('}')) {
-                    self.parse_statement(lexer, context.reborrow(), &mut body, false)?;
+                    self.parse_block_statement(lexer, context.reborrow(), &mut body, false)?;
                 }
 
                 block.push(crate::Statement::Loop { body, continuing });
@@ -2904,10 +3212,8 @@ impl Parser {
             "textureStore" => {
                 emitter.start(context.expressions);
                 lexer.open_arguments()?;
-                let (image_name, image_span) = lexer.next_ident_with_span()?;
-                let image = context
-                    .lookup_ident
-                    .lookup(image_name, image_span.clone())?;
+                let (image, image_span) = self
+                    .parse_handle_expression(lexer, context.as_expression(block, &mut emitter))?;
                 lexer.expect(Token::Separator(','))?;
                 let mut expr_context = context.as_expression(block, &mut emitter);
                 let arrayed = match *expr_context.resolve_type(image)? {
@@ -2951,6 +3257,73 @@ impl Parser {
         Ok(())
     }
 
+    /// Parse a statement of a block, recovering from its errors.
+    ///
+    /// On error, the statement is skipped up to the next `;` or the end of
+    /// the block, and the error is recorded, so that parsing carries on with
+    /// the next statement. Errors at the end of the source are returned.
+    fn parse_block_statement<'a, 'out>(
+        &mut self,
+        lexer: &mut Lexer<'a>,
+        context: StatementContext<'a, '_, 'out>,
+        block: &'out mut crate::Block,
+        is_uniform_control_flow: bool,
+    ) -> Result<(), Error<'a>> {
+        let start = lexer.clone();
+        let scope_depth = self.scopes.len();
+        match self.parse_statement(lexer, context, block, is_uniform_control_flow) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                *lexer = start;
+                if !Self::skip_statement(lexer) {
+                    return Err(error);
+                }
+                self.scopes.truncate(scope_depth);
+                self.errors.push(error.as_parse_error(lexer.source));
+                Ok(())
+            }
+        }
+    }
+
+    /// Skip the statement starting at the current token. It ends after a `;`
+    /// that isn't nested in parentheses, brackets or braces of the statement,
+    /// or after a `}` matching a `{` of the statement, or before the `}`
+    /// closing the block. Returns false if the end of the source is reached
+    /// instead.
+    fn skip_statement(lexer: &mut Lexer) -> bool {
+        let mut depth = 0usize;
+        // parentheses and brackets, which can't contain the end of the block
+        let mut paren_depth = 0usize;
+        loop {
+            match lexer.peek().0 {
+                Token::End => return false,
+                Token::Separator(';') if depth == 0 && paren_depth == 0 => {
+                    let _ = lexer.next();
+                    return true;
+                }
+                Token::Paren('(') | Token::Paren('[') => paren_depth += 1,
+                Token::DoubleParen('[') => paren_depth += 2,
+                Token::Paren(')') | Token::Paren(']') => {
+                    paren_depth = paren_depth.saturating_sub(1)
+                }
+                Token::DoubleParen(']') => paren_depth = paren_depth.saturating_sub(2),
+                Token::Paren('{') => depth += 1,
+                Token::Paren('}') => {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        let _ = lexer.next();
+                        return true;
+                    }
+                }
+                _ => {}
+            }
+            let _ = lexer.next();
+        }
+    }
+
     fn parse_block<'a>(
         &mut self,
         lexer: &mut Lexer<'a>,
@@ -2961,7 +3334,7 @@ impl Parser {
         lexer.expect(Token::Paren('{'))?;
         let mut block = Vec::new();
         while !lexer.skip(Token::Paren('}')) {
-            self.parse_statement(
+            self.parse_block_statement(
                 lexer,
                 context.reborrow(),
                 &mut block,
@@ -3187,11 +3560,27 @@ impl Parser {
         // read items
         match lexer.next() {
             (Token::Separator(';'), _) => {}
+            (Token::Word("enable"), _) => loop {
+                match lexer.next_ident_with_span()? {
+                    ("f16", _) => lexer.f16_enabled = true,
+                    (_, span) => return Err(Error::UnknownExtension(span)),
+                }
+                match lexer.next() {
+                    (Token::Separator(';'), _) => break,
+                    (Token::Separator(','), _) => {}
+                    other => {
+                        return Err(Error::Unexpected(
+                            other,
+                            ExpectedToken::Token(Token::Separator(';')),
+                        ))
+                    }
+                }
+            },
             (Token::Word("struct"), _) => {
                 let name = lexer.next_ident()?;
                 let (members, span) =
                     self.parse_struct_body(lexer, &mut module.types, &mut module.constants)?;
-                let ty = module.types.fetch_or_append(crate::Type {
+                let ty = module.types.insert(crate::Type {
                     name: Some(name.to_string()),
                     inner: crate::TypeInner::Struct {
                         top_level: is_block,
@@ -3214,7 +3603,7 @@ impl Parser {
                 self.lookup_type.insert(name.to_owned(), ty);
                 lexer.expect(Token::Separator(';'))?;
             }
-            (Token::Word("let"), _) => {
+            (Token::Word("let"), _) | (Token::Word("const"), _) => {
                 let (name, name_span, explicit_ty, _access) = self.parse_variable_ident_decl(
                     lexer,
                     &mut module.types,
@@ -3263,9 +3652,9 @@ impl Parser {
                         crate::TypeInner::Array { .. } if binding.is_some() => {
                             crate::StorageClass::Storage
                         }
-                        crate::TypeInner::Image { .. } | crate::TypeInner::Sampler { .. } => {
-                            crate::StorageClass::Handle
-                        }
+                        crate::TypeInner::Image { .. }
+                        | crate::TypeInner::Sampler { .. }
+                        | crate::TypeInner::BindingArray { .. } => crate::StorageClass::Handle,
                         _ => crate::StorageClass::Private,
                     },
                 };
@@ -3308,27 +3697,47 @@ impl Parser {
     }
 
     pub fn parse(&mut self, source: &str) -> Result<crate::Module, ParseError> {
+        self.parse_recovering(source)
+            .map_err(|mut errors| errors.swap_remove(0))
+    }
+
+    /// Parse `source`, returning all the errors found instead of the first one.
+    ///
+    /// A statement that fails to parse is skipped, up to the next `;` or the
+    /// end of its block, and parsing goes on with the next statement. Other
+    /// errors stop the parsing. The errors are in the order of the source.
+    pub fn parse_recovering(&mut self, source: &str) -> Result<crate::Module, Vec<ParseError>> {
         self.scopes.clear();
         self.lookup_type.clear();
         self.layouter.clear();
+        self.errors.clear();
 
         let mut module = crate::Module::default();
         let mut lexer = Lexer::new(source);
         let mut lookup_global_expression = FastHashMap::default();
         loop {
             match self.parse_global_decl(&mut lexer, &mut module, &mut lookup_global_expression) {
-                Err(error) => return Err(error.as_parse_error(lexer.source)),
+                Err(error) => {
+                    self.errors.push(error.as_parse_error(lexer.source));
+                    break;
+                }
                 Ok(true) => {}
                 Ok(false) => {
                     if !self.scopes.is_empty() {
                         log::error!("Reached the end of file, but scopes are not closed");
-                        return Err(Error::Other.as_parse_error(lexer.source));
+                        self.errors.push(Error::Other.as_parse_error(lexer.source));
                     };
-                    module.apply_common_default_interpolation();
-                    return Ok(module);
+                    break;
                 }
             }
         }
+
+        if self.errors.is_empty() {
+            module.apply_common_default_interpolation();
+            Ok(module)
+        } else {
+            Err(mem::take(&mut self.errors))
+        }
     }
 }
 
@@ -3336,6 +3745,13 @@ pub fn parse_str(source: &str) -> Result<crate::Module, ParseError> {
     Parser::new().parse(source)
 }
 
+/// Parse `source`, returning all the errors found.
+///
+/// See [`Parser::parse_recovering`].
+pub fn parse_str_recovering(source: &str) -> Result<crate::Module, Vec<ParseError>> {
+    Parser::new().parse_recovering(source)
+}
+
 pub struct StringErrorBuffer {
     buf: Vec<u8>,
 }
@@ -268,6 +268,51 @@ fn parse_postfix() {
     .unwrap();
 }
 
+#[test]
+fn parse_constant_arrays() {
+    let module = parse_str(
+        "
+        struct Light {
+            color: vec3<f32>;
+            intensity: f32;
+        };
+        const positions: array<vec2<f32>, 3> = array<vec2<f32>, 3>(
+            vec2<f32>(-1.0, -1.0),
+            vec2<f32>(3.0, -1.0),
+            vec2<f32>(-1.0, 3.0),
+        );
+        const light: Light = Light(vec3<f32>(1.0, 0.5, 0.0), 2.0);
+
+        [[stage(vertex)]]
+        fn main([[builtin(vertex_index)]] vertex_idx: u32) -> [[builtin(position)]] vec4<f32> {
+            let w = positions[2].y * light.intensity;
+            return vec4<f32>(positions[vertex_idx], 0.0, w);
+        }
+        ",
+    )
+    .unwrap();
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let function = &module.entry_points[0].function;
+    // constant lookups are folded
+    let access_count = function
+        .expressions
+        .iter()
+        .filter(|&(_, expr)| match *expr {
+            crate::Expression::AccessIndex { .. } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(access_count, 0);
+    // the table is copied to a local variable to be indexed dynamically
+    assert_eq!(function.local_variables.len(), 1);
+}
+
 #[test]
 fn parse_expressions() {
     parse_str("fn foo() {
@@ -330,3 +375,67 @@ fn parse_array_length() {
     )
     .unwrap();
 }
+
+#[test]
+fn parse_enable() {
+    parse_str("enable f16; let a: f16 = 1.5f16;").unwrap();
+    parse_str("enable f16, f16;").unwrap();
+    assert!(parse_str("enable f128;").is_err());
+}
+
+#[test]
+fn parse_f16_requires_enable() {
+    assert!(parse_str("let a: f16 = 1.5;").is_err());
+    assert!(parse_str("let a: f32 = f32(1.5f16);").is_err());
+    assert!(parse_str("var<private> a: vec2<f16>;").is_err());
+    assert!(parse_str("var<private> a: mat2x2<f16>;").is_err());
+    assert!(parse_str("fn f() { let a = f16(1.0); }").is_err());
+    parse_str(
+        "
+        enable f16;
+        var<private> a: mat2x2<f16>;
+        fn f() -> vec2<f16> {
+            return vec2<f16>(f16(1.0), 0.5f16);
+        }
+        ",
+    )
+    .unwrap();
+}
+
+#[test]
+fn validate_scalar_widths() {
+    let module = parse_str("enable f16; let a: f16 = 1.5f16; let b: i64 = 1i64;").unwrap();
+    let validate = |capabilities| {
+        crate::valid::Validator::new(crate::valid::ValidationFlags::all(), capabilities)
+            .validate(&module)
+    };
+    assert!(validate(crate::valid::Capabilities::INT64).is_err());
+    assert!(validate(crate::valid::Capabilities::FLOAT16).is_err());
+    validate(crate::valid::Capabilities::INT64 | crate::valid::Capabilities::FLOAT16).unwrap();
+}
+
+#[test]
+fn validate_global_initializers() {
+    use crate::valid::{Capabilities, GlobalVariableError, ValidationError, ValidationFlags};
+
+    let validate = |source| {
+        let module = parse_str(source).unwrap();
+        crate::valid::Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+    };
+    validate("var<private> a: f32 = 1.0;").unwrap();
+    match validate("var<workgroup> a: f32 = 1.0;") {
+        Err(ValidationError::GlobalVariable {
+            error: GlobalVariableError::InitializerNotAllowed(crate::StorageClass::WorkGroup),
+            ..
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match validate("var<private> a: f32 = 1;") {
+        Err(ValidationError::GlobalVariable {
+            error: GlobalVariableError::InitializerType,
+            ..
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
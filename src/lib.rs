@@ -151,11 +151,40 @@ tree.
 
 mod arena;
 pub mod back;
+#[cfg(feature = "compress")]
+mod compress;
+pub mod diagnostic;
 pub mod front;
 pub mod proc;
+#[cfg(all(
+    any(feature = "wgsl-in", feature = "glsl-in", feature = "spv-in"),
+    any(
+        feature = "spv-out",
+        feature = "msl-out",
+        feature = "glsl-out",
+        feature = "hlsl-out",
+        feature = "wgsl-out"
+    )
+))]
+pub mod translate;
 pub mod valid;
-
-pub use crate::arena::{Arena, Handle, Range};
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use crate::arena::{Arena, Handle, Range, UniqueArena};
+#[cfg(feature = "compress")]
+pub use crate::compress::{CompressionError, COMPRESSED_FORMAT_VERSION, OLDEST_FORMAT_VERSION};
+#[cfg(all(
+    any(feature = "wgsl-in", feature = "glsl-in", feature = "spv-in"),
+    any(
+        feature = "spv-out",
+        feature = "msl-out",
+        feature = "glsl-out",
+        feature = "hlsl-out",
+        feature = "wgsl-out"
+    )
+))]
+pub use crate::translate::translate;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -178,6 +207,19 @@ pub type FastHashSet<K> = HashSet<K, BuildHasherDefault<fxhash::FxHasher>>;
 /// Map of expressions that have associated variable names
 pub(crate) type NamedExpressions = FastHashMap<Handle<Expression>, String>;
 
+/// Serialize the named expressions sorted by handle, so that the output
+/// doesn't depend on the iteration order of the map.
+#[cfg(feature = "serialize")]
+fn serialize_named_expressions<S: serde::Serializer>(
+    named_expressions: &NamedExpressions,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted = named_expressions
+        .iter()
+        .collect::<std::collections::BTreeMap<_, _>>();
+    sorted.serialize(serializer)
+}
+
 /// Early fragment tests. In a standard situation if a driver determines that it is possible to
 /// switch on early depth test it will. Typical situations when early depth test is switched off:
 ///   - Calling ```discard``` in a shader.
@@ -250,6 +292,15 @@ pub enum StorageClass {
     Handle,
     /// Push constants.
     PushConstant,
+    /// Buffer data addressed by a 64-bit physical device address.
+    ///
+    /// Pointers in this class are plain values: they can be stored in
+    /// buffers and variables, and are obtained with [`Expression::PointerCast`].
+    /// They can't be used as the class of a global variable.
+    ///
+    /// Unlike other pointer types, the base type of these may come later in
+    /// the type arena, which allows recursive structures like linked lists.
+    PhysicalStorageBuffer,
 }
 
 /// Built-in inputs and outputs.
@@ -266,16 +317,20 @@ pub enum BuiltIn {
     InstanceIndex,
     PointSize,
     VertexIndex,
+    ViewIndex,
     // fragment
     FragDepth,
     FrontFacing,
+    HelperInvocation,
     PrimitiveIndex,
     SampleIndex,
     SampleMask,
+    SamplePosition,
     // compute
     GlobalInvocationId,
     LocalInvocationId,
     LocalInvocationIndex,
+    NumWorkGroups,
     WorkGroupId,
     WorkGroupSize,
 }
@@ -361,7 +416,7 @@ pub enum Sampling {
 
 /// Member of a user-defined structure.
 // Clone is used only for error reporting and is not intended for end users
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct StructMember {
@@ -468,13 +523,19 @@ pub enum ImageClass {
         multi: bool,
     },
     /// Depth comparison image.
-    Depth,
+    Depth {
+        /// Multi-sampled depth image.
+        ///
+        /// Like multi-sampled [`Sampled`](ImageClass::Sampled) images, these can't
+        /// have mipmaps, and can only be loaded from rather than sampled.
+        multi: bool,
+    },
     /// Storage image.
     Storage(StorageFormat),
 }
 
 /// A data type declared in the module.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct Type {
@@ -485,7 +546,7 @@ pub struct Type {
 }
 
 /// Enum with additional information, depending on the kind of type.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum TypeInner {
@@ -599,10 +660,29 @@ pub enum TypeInner {
     },
     /// Can be used to sample values from images.
     Sampler { comparison: bool },
+
+    /// Array of bindings.
+    ///
+    /// A `BindingArray` represents an array where each element draws its
+    /// value from a separate bound resource. The `base` type must be an
+    /// [`Image`] or a [`Sampler`]. A `BindingArray` may only be the type of a
+    /// global variable in the [`Handle`] storage class, and its elements are
+    /// accessed with [`Access`] and [`AccessIndex`] expressions.
+    ///
+    /// If the `size` is [`Dynamic`], the number of bound resources is decided
+    /// by the pipeline layout.
+    ///
+    /// [`Image`]: TypeInner::Image
+    /// [`Sampler`]: TypeInner::Sampler
+    /// [`Handle`]: StorageClass::Handle
+    /// [`Access`]: Expression::Access
+    /// [`AccessIndex`]: Expression::AccessIndex
+    /// [`Dynamic`]: ArraySize::Dynamic
+    BindingArray { base: Handle<Type>, size: ArraySize },
 }
 
 /// Constant value.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct Constant {
@@ -623,7 +703,7 @@ pub enum ScalarValue {
 }
 
 /// Additional information, dependent on the kind of constant.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum ConstantInner {
@@ -638,7 +718,7 @@ pub enum ConstantInner {
 }
 
 /// Describes how an input/output variable is to be bound.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum Binding {
@@ -664,7 +744,7 @@ pub struct ResourceBinding {
 }
 
 /// Variable defined at module level.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Hash, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct GlobalVariable {
@@ -673,17 +753,25 @@ pub struct GlobalVariable {
     /// How this variable is to be stored.
     pub class: StorageClass,
     /// For resources, defines the binding point.
+    ///
+    /// This is required for the `Uniform`, `Storage` and `Handle` classes,
+    /// and must be `None` for the others.
     pub binding: Option<ResourceBinding>,
     /// The type of this variable.
     pub ty: Handle<Type>,
     /// Initial value for this variable.
+    ///
+    /// Only variables in the `Private` class can have one.
     pub init: Option<Handle<Constant>>,
     /// Access bit for storage types of images and buffers.
+    ///
+    /// This must be empty for the classes other than `Storage` and `Handle`,
+    /// except for loads of `PushConstant`.
     pub storage_access: StorageAccess,
 }
 
 /// Variable defined at function level.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct LocalVariable {
@@ -740,6 +828,19 @@ pub enum DerivativeAxis {
     Width,
 }
 
+/// Hint at which precision to compute a derivative.
+#[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub enum DerivativeControl {
+    /// Computed from fewer invocations, like the same value for a whole quad.
+    Coarse,
+    /// Computed from the invocations next to the current one.
+    Fine,
+    /// Left to the implementation.
+    None,
+}
+
 /// Built-in shader function for testing relation between values.
 #[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
@@ -813,10 +914,22 @@ pub enum MathFunction {
     // bits
     CountOneBits,
     ReverseBits,
+    // data packing
+    Pack4x8snorm,
+    Pack4x8unorm,
+    Pack2x16snorm,
+    Pack2x16unorm,
+    Pack2x16float,
+    // data unpacking
+    Unpack4x8snorm,
+    Unpack4x8unorm,
+    Unpack2x16snorm,
+    Unpack2x16unorm,
+    Unpack2x16float,
 }
 
 /// Sampling modifier to control the level of detail.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum SampleLevel {
@@ -831,7 +944,7 @@ pub enum SampleLevel {
 }
 
 /// Type of an image query.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum ImageQuery {
@@ -846,11 +959,21 @@ pub enum ImageQuery {
     NumLayers,
     /// Get the number of samples.
     NumSamples,
+    /// Get the level of detail that would be used for sampling the image
+    /// with an implicit level at the given coordinate.
+    ///
+    /// The result is a 2-component float vector of the mipmap level that
+    /// would be accessed, and of the computed level relative to the base
+    /// level before clamping.
+    Lod {
+        sampler: Handle<Expression>,
+        coordinate: Handle<Expression>,
+    },
 }
 
 /// Component selection for a vector swizzle.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum SwizzleComponent {
@@ -880,8 +1003,7 @@ bitflags::bitflags! {
 /// An expression that can be evaluated to obtain a value.
 ///
 /// This is a Single Static Assignment (SSA) scheme similar to SPIR-V.
-#[derive(Clone, Debug)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum Expression {
@@ -1001,9 +1123,17 @@ pub enum Expression {
     /// Load a value indirectly.
     Load { pointer: Handle<Expression> },
     /// Sample a point from a sampled or a depth image.
+    ///
+    /// If `gather` is set, the selected component of the four texels that
+    /// bilinear filtering would use is gathered into a vector instead. Only
+    /// 2D and cube images can be gathered, at level [`SampleLevel::Zero`],
+    /// and only the `X` component of depth images, which are compared with
+    /// `depth_ref` if it's given.
     ImageSample {
         image: Handle<Expression>,
         sampler: Handle<Expression>,
+        /// If `Some`, this is a gather of the selected component.
+        gather: Option<SwizzleComponent>,
         coordinate: Handle<Expression>,
         array_index: Option<Handle<Expression>>,
         offset: Option<Handle<Constant>>,
@@ -1051,11 +1181,8 @@ pub enum Expression {
         /// -   [`Storage`] images hold exactly one sample per texel, so `index` must
         ///     be `None`.
         ///
-        /// -   [`Depth`] images may have mipmaps, so `index` must be `Some(level)`,
-        ///     where `level` identifies the level of detail.
-        ///
-        /// -   [`Sampled`] images may be multisampled or have mipmaps, but not both.
-        ///     Which one is indicated by the `Sampled` variant's [`multi`] field:
+        /// -   [`Sampled`] and [`Depth`] images may be multisampled or have mipmaps,
+        ///     but not both. Which one is indicated by the variant's [`multi`] field:
         ///
         ///     - If `multi` is `true`, then the image has multiple samples per
         ///       texel, and `index` must be `Some(sample)`, where `sample` is
@@ -1107,7 +1234,11 @@ pub enum Expression {
     /// Compute the derivative on an axis.
     Derivative {
         axis: DerivativeAxis,
-        //modifier,
+        #[cfg_attr(
+            feature = "compress",
+            serde(deserialize_with = "compress::deserialize_derivative_control")
+        )]
+        ctrl: DerivativeControl,
         expr: Handle<Expression>,
     },
     /// Call a relational function.
@@ -1132,6 +1263,22 @@ pub enum Expression {
         /// Otherwise, bitcast.
         convert: Option<Bytes>,
     },
+    /// Cast between a [`PhysicalStorageBuffer`] pointer and its address.
+    ///
+    /// Either `expr` is an address and `ty` is a pointer type in the
+    /// [`PhysicalStorageBuffer`] class, or `expr` is such a pointer and `ty`
+    /// is an address type. Addresses are either a 64-bit unsigned integer,
+    /// or a 2-component vector of 32-bit unsigned integers holding the low
+    /// and high halves of the address.
+    ///
+    /// There is no pointer arithmetic in the IR. Instead, the pointer is cast
+    /// to an address, offset, and cast back.
+    ///
+    /// [`PhysicalStorageBuffer`]: StorageClass::PhysicalStorageBuffer
+    PointerCast {
+        expr: Handle<Expression>,
+        ty: Handle<Type>,
+    },
     /// Result of calling another function.
     Call(Handle<Function>),
     /// Get the length of an array.
@@ -1147,7 +1294,7 @@ pub type Block = Vec<Statement>;
 
 /// A case for a switch statement.
 // Clone is used only for error reporting and is not intended for end users
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct SwitchCase {
@@ -1163,7 +1310,7 @@ pub struct SwitchCase {
 //TODO: consider removing `Clone`. It's not valid to clone `Statement::Emit` anyway.
 /// Instructions which make up an executable block.
 // Clone is used only for error reporting and is not intended for end users
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum Statement {
@@ -1290,7 +1437,7 @@ pub enum Statement {
 }
 
 /// A function argument.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct FunctionArgument {
@@ -1303,7 +1450,7 @@ pub struct FunctionArgument {
     pub binding: Option<Binding>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct FunctionResult {
@@ -1330,13 +1477,17 @@ pub struct Function {
     /// Expressions used inside this function.
     pub expressions: Arena<Expression>,
     /// Map of expressions that have associated variable names
+    #[cfg_attr(
+        feature = "serialize",
+        serde(serialize_with = "serialize_named_expressions")
+    )]
     pub named_expressions: NamedExpressions,
     /// Block of instructions comprising the body of the function.
     pub body: Block,
 }
 
 /// Exported function, to be run at a certain stage in the pipeline.
-#[derive(Debug)]
+#[derive(Debug, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct EntryPoint {
@@ -1363,12 +1514,15 @@ pub struct EntryPoint {
 /// Alternatively, you can load an existing shader using one of the [available front ends][front].
 ///
 /// When finished, you can export modules using one of the [available backends][back].
-#[derive(Debug, Default)]
+///
+/// Modules are `Send` and `Sync`, so they can be translated on several
+/// threads at once.
+#[derive(Debug, Default, Hash)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct Module {
     /// Storage for the types defined in this module.
-    pub types: Arena<Type>,
+    pub types: UniqueArena<Type>,
     /// Storage for the constants defined in this module.
     pub constants: Arena<Constant>,
     /// Storage for the global variables defined in this module.
@@ -1378,3 +1532,10 @@ pub struct Module {
     /// Entry points.
     pub entry_points: Vec<EntryPoint>,
 }
+
+// Fail to compile if the IR or its analysis stops being shareable across threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Module>();
+    assert_send_sync::<valid::ModuleInfo>();
+};
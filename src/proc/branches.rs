@@ -0,0 +1,372 @@
+use std::cmp::Ordering;
+
+use crate::{
+    arena::{Arena, Handle},
+    BinaryOperator, Block, Bytes, Constant, ConstantInner, Expression, LocalVariable, ScalarValue,
+    Statement, UnaryOperator,
+};
+
+impl crate::Module {
+    /// Replace the branches on constant values by the code they select.
+    ///
+    /// An `If` whose condition is constant becomes a `Block` with the
+    /// statements of the branch it takes, and so does a `Switch` whose selector
+    /// is constant, unless the selected cases `Break` out of it: then it keeps
+    /// them as its default, with no other case. The statements following a
+    /// `Return`, `Kill`, `Break` or `Continue` in a block are removed.
+    ///
+    /// A value is constant if it's a scalar constant without specialization,
+    /// or a comparison, integer arithmetic that doesn't overflow, logical operation, negation or
+    /// logical negation of constant values. Specialization constants can be set beforehand to
+    /// generate the variants of a shader, removing the unused features.
+    pub fn eliminate_dead_branches(&mut self) {
+        let constants = &self.constants;
+        for (_, fun) in self.functions.iter_mut() {
+            eliminate_block(&mut fun.body, &fun.expressions, constants);
+        }
+        for ep in self.entry_points.iter_mut() {
+            let fun = &mut ep.function;
+            eliminate_block(&mut fun.body, &fun.expressions, constants);
+        }
+    }
+}
+
+fn eliminate_block(
+    block: &mut Block,
+    expressions: &Arena<Expression>,
+    constants: &Arena<Constant>,
+) {
+    for statement in block.iter_mut() {
+        let replacement = match *statement {
+            Statement::Block(ref mut inner) => {
+                eliminate_block(inner, expressions, constants);
+                None
+            }
+            Statement::If {
+                condition,
+                ref mut accept,
+                ref mut reject,
+            } => match evaluate(condition, expressions, constants, None) {
+                Some(ScalarValue::Bool(true)) => Some(Statement::Block(std::mem::take(accept))),
+                Some(ScalarValue::Bool(false)) => Some(Statement::Block(std::mem::take(reject))),
+                _ => {
+                    eliminate_block(accept, expressions, constants);
+                    eliminate_block(reject, expressions, constants);
+                    None
+                }
+            },
+            Statement::Switch {
+                selector,
+                ref mut cases,
+                ref mut default,
+            } => {
+                let value = match evaluate(selector, expressions, constants, None) {
+                    Some(ScalarValue::Sint(value)) => Some(value as i32),
+                    Some(ScalarValue::Uint(value)) => Some(value as i32),
+                    _ => None,
+                };
+                match value {
+                    Some(value) => {
+                        // Take the selected case, and the ones it falls through to
+                        let first = cases
+                            .iter()
+                            .position(|case| case.value == value)
+                            .unwrap_or(cases.len());
+                        let mut selected = Block::new();
+                        let mut falls_through = true;
+                        for case in cases.drain(..).skip(first) {
+                            if falls_through {
+                                selected.extend(case.body);
+                                falls_through = case.fall_through;
+                            }
+                        }
+                        if falls_through {
+                            selected.append(default);
+                        }
+                        eliminate_block(&mut selected, expressions, constants);
+                        if breaks(&selected) {
+                            *default = selected;
+                            None
+                        } else {
+                            Some(Statement::Block(selected))
+                        }
+                    }
+                    None => {
+                        for case in cases.iter_mut() {
+                            eliminate_block(&mut case.body, expressions, constants);
+                        }
+                        eliminate_block(default, expressions, constants);
+                        None
+                    }
+                }
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                eliminate_block(body, expressions, constants);
+                eliminate_block(continuing, expressions, constants);
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(mut replacement) = replacement {
+            // The taken branch may have constant branches of its own
+            if let Statement::Block(ref mut inner) = replacement {
+                eliminate_block(inner, expressions, constants);
+            }
+            *statement = replacement;
+        }
+    }
+
+    if let Some(end) = block.iter().position(terminates) {
+        block.truncate(end + 1);
+    }
+    block.retain(|statement| match *statement {
+        Statement::Block(ref inner) => !inner.is_empty(),
+        _ => true,
+    });
+}
+
+/// Return true if nothing executes after `statement` in its block.
+pub(crate) fn terminates(statement: &Statement) -> bool {
+    match *statement {
+        Statement::Return { .. } | Statement::Kill | Statement::Break | Statement::Continue => true,
+        Statement::Block(ref inner) => match inner.last() {
+            Some(last) => terminates(last),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Return true if `block` contains a `Break` out of the switch or loop around it.
+fn breaks(block: &Block) -> bool {
+    block.iter().any(|statement| match *statement {
+        Statement::Break => true,
+        Statement::Block(ref inner) => breaks(inner),
+        Statement::If {
+            ref accept,
+            ref reject,
+            ..
+        } => breaks(accept) || breaks(reject),
+        _ => false,
+    })
+}
+
+/// Return the value of `handle` if it's known at translation time.
+///
+/// If `counter` is given, the loads of its local variable have its value,
+/// which is an integer of the given width if it's not a float.
+/// Integer arithmetic that overflows the width of its operands isn't evaluated.
+pub(super) fn evaluate(
+    handle: Handle<Expression>,
+    expressions: &Arena<Expression>,
+    constants: &Arena<Constant>,
+    counter: Option<(Handle<LocalVariable>, ScalarValue, Bytes)>,
+) -> Option<ScalarValue> {
+    evaluate_sized(handle, expressions, constants, counter).map(|(value, _)| value)
+}
+
+/// Return the value of `handle` and its width, like [`evaluate`].
+fn evaluate_sized(
+    handle: Handle<Expression>,
+    expressions: &Arena<Expression>,
+    constants: &Arena<Constant>,
+    counter: Option<(Handle<LocalVariable>, ScalarValue, Bytes)>,
+) -> Option<(ScalarValue, Bytes)> {
+    match expressions[handle] {
+        Expression::Constant(constant) => match constants[constant] {
+            Constant {
+                specialization: None,
+                inner: ConstantInner::Scalar { value, width },
+                ..
+            } => Some((value, width)),
+            _ => None,
+        },
+        Expression::Load { pointer } => match (counter, &expressions[pointer]) {
+            (Some((var, value, width)), &Expression::LocalVariable(local)) if local == var => {
+                Some((value, width))
+            }
+            _ => None,
+        },
+        Expression::Unary { op, expr } => {
+            let (value, width) = evaluate_sized(expr, expressions, constants, counter)?;
+            let value = match (op, value) {
+                (UnaryOperator::Not, ScalarValue::Bool(value)) => ScalarValue::Bool(!value),
+                (UnaryOperator::Negate, ScalarValue::Sint(value)) => {
+                    ScalarValue::Sint(value.checked_neg()?)
+                }
+                (UnaryOperator::Negate, ScalarValue::Float(value)) => ScalarValue::Float(-value),
+                _ => return None,
+            };
+            Some((fit(value, width)?, width))
+        }
+        Expression::Binary { op, left, right } => {
+            let (left, width) = evaluate_sized(left, expressions, constants, counter)?;
+            let (right, right_width) = evaluate_sized(right, expressions, constants, counter)?;
+            if left.scalar_kind() != right.scalar_kind() || width != right_width {
+                return None;
+            }
+            let ordering = left.partial_cmp(&right)?;
+            let value = match (op, left, right) {
+                (BinaryOperator::Add, ScalarValue::Sint(left), ScalarValue::Sint(right)) => {
+                    ScalarValue::Sint(left.checked_add(right)?)
+                }
+                (BinaryOperator::Add, ScalarValue::Uint(left), ScalarValue::Uint(right)) => {
+                    ScalarValue::Uint(left.checked_add(right)?)
+                }
+                (BinaryOperator::Subtract, ScalarValue::Sint(left), ScalarValue::Sint(right)) => {
+                    ScalarValue::Sint(left.checked_sub(right)?)
+                }
+                (BinaryOperator::Subtract, ScalarValue::Uint(left), ScalarValue::Uint(right)) => {
+                    ScalarValue::Uint(left.checked_sub(right)?)
+                }
+                (BinaryOperator::Multiply, ScalarValue::Sint(left), ScalarValue::Sint(right)) => {
+                    ScalarValue::Sint(left.checked_mul(right)?)
+                }
+                (BinaryOperator::Multiply, ScalarValue::Uint(left), ScalarValue::Uint(right)) => {
+                    ScalarValue::Uint(left.checked_mul(right)?)
+                }
+                (BinaryOperator::Equal, _, _) => ScalarValue::Bool(ordering == Ordering::Equal),
+                (BinaryOperator::NotEqual, _, _) => ScalarValue::Bool(ordering != Ordering::Equal),
+                (BinaryOperator::Less, _, _) => ScalarValue::Bool(ordering == Ordering::Less),
+                (BinaryOperator::LessEqual, _, _) => {
+                    ScalarValue::Bool(ordering != Ordering::Greater)
+                }
+                (BinaryOperator::Greater, _, _) => ScalarValue::Bool(ordering == Ordering::Greater),
+                (BinaryOperator::GreaterEqual, _, _) => {
+                    ScalarValue::Bool(ordering != Ordering::Less)
+                }
+                (BinaryOperator::LogicalAnd, ScalarValue::Bool(left), ScalarValue::Bool(right))
+                | (BinaryOperator::And, ScalarValue::Bool(left), ScalarValue::Bool(right)) => {
+                    ScalarValue::Bool(left && right)
+                }
+                (BinaryOperator::LogicalOr, ScalarValue::Bool(left), ScalarValue::Bool(right))
+                | (
+                    BinaryOperator::InclusiveOr,
+                    ScalarValue::Bool(left),
+                    ScalarValue::Bool(right),
+                ) => ScalarValue::Bool(left || right),
+                _ => return None,
+            };
+            match value {
+                ScalarValue::Bool(_) => Some((value, crate::BOOL_WIDTH)),
+                _ => Some((fit(value, width)?, width)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Return `value` if it's representable by an integer of `width` bytes, or isn't an integer.
+fn fit(value: ScalarValue, width: Bytes) -> Option<ScalarValue> {
+    let bits = u32::from(width) * 8;
+    let fits = match value {
+        ScalarValue::Sint(value) if bits < 64 => {
+            let high = value >> (bits - 1);
+            high == 0 || high == -1
+        }
+        ScalarValue::Uint(value) if bits < 64 => value >> bits == 0,
+        _ => true,
+    };
+    if fits {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn eliminate_dead_branches() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[stage(fragment)]]
+        fn main([[location(0)]] value: f32) -> [[location(0)]] vec4<f32> {
+            var color: vec4<f32> = vec4<f32>(value);
+            if (!(1 < 2)) {
+                color = vec4<f32>(0.0);
+            } else {
+                color = color * 2.0;
+            }
+            switch (1) {
+                case 0: {
+                    color = vec4<f32>(1.0);
+                }
+                case 1: {
+                    color = color + vec4<f32>(1.0);
+                    fallthrough;
+                }
+                default: {
+                    return color;
+                }
+            }
+            return vec4<f32>(value);
+        }
+        ",
+    )
+    .unwrap();
+    module.eliminate_dead_branches();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // Only the taken branches remain, and nothing after the `return` of the switch
+    fn count(block: &Block) -> (usize, usize) {
+        let mut stores = 0;
+        let mut returns = 0;
+        for statement in block {
+            let (inner_stores, inner_returns) = match *statement {
+                Statement::Store { .. } => (1, 0),
+                Statement::Return { .. } => (0, 1),
+                Statement::Block(ref inner) => count(inner),
+                Statement::If { .. } | Statement::Switch { .. } => (100, 100),
+                _ => (0, 0),
+            };
+            stores += inner_stores;
+            returns += inner_returns;
+        }
+        (stores, returns)
+    }
+    let body = &module.entry_points[0].function.body;
+    assert_eq!(count(body), (3, 1));
+    assert!(terminates(body.last().unwrap()));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn eliminate_overflowing_branches() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[stage(fragment)]]
+        fn main([[location(0)]] value: f32) -> [[location(0)]] vec4<f32> {
+            if (4294967295u + 1u > 4294967295u) {
+                return vec4<f32>(0.0);
+            }
+            if (-2147483647 - 1 < 0) {
+                return vec4<f32>(1.0);
+            }
+            return vec4<f32>(value);
+        }
+        ",
+    )
+    .unwrap();
+    module.eliminate_dead_branches();
+
+    // The overflowing addition isn't evaluated, unlike the subtraction that fits in 32 bits
+    let body = &module.entry_points[0].function.body;
+    let ifs = body
+        .iter()
+        .filter(|statement| match **statement {
+            Statement::If { .. } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(ifs, 1);
+}
@@ -0,0 +1,228 @@
+use super::interface::rewrite_returns;
+use crate::{
+    arena::{Arena, Handle},
+    BinaryOperator, Binding, BuiltIn, Constant, ConstantInner, Expression, ScalarValue,
+    ShaderStage, TypeInner, UnaryOperator,
+};
+
+bitflags::bitflags! {
+    /// Adjustments made by [`Module::adjust_coordinate_space`].
+    ///
+    /// [`Module::adjust_coordinate_space`]: crate::Module::adjust_coordinate_space
+    #[derive(Default)]
+    #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+    #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+    pub struct CoordinateSpaceAdjustment: u32 {
+        /// Negate the Y coordinate of the position.
+        const FLIP_Y = 0x1;
+        /// Remap the depth from (0,1) to (-1,1), as `z * 2 - w`.
+        const REMAP_DEPTH = 0x2;
+    }
+}
+
+impl crate::Module {
+    /// Adjust the clip space position written by the vertex entry points.
+    ///
+    /// The IR follows the clip space of WebGPU, with the Y axis pointing up
+    /// and depths between 0 and 1. Vulkan points the Y axis down, and OpenGL
+    /// uses depths between -1 and 1, so translating for those may require
+    /// [`FLIP_Y`] or [`REMAP_DEPTH`].
+    ///
+    /// The math is injected in front of every `return` of the vertex entry
+    /// points, so the result is the same whichever backend writes the module,
+    /// unlike the coordinate space flags of the individual backends.
+    /// Those flags have to be turned off, or the position is adjusted twice:
+    /// [`translate`] leaves them off when it runs the adjustment, and rejects
+    /// the options that turn them on explicitly.
+    ///
+    /// [`translate`]: crate::translate::translate
+    /// [`FLIP_Y`]: CoordinateSpaceAdjustment::FLIP_Y
+    /// [`REMAP_DEPTH`]: CoordinateSpaceAdjustment::REMAP_DEPTH
+    pub fn adjust_coordinate_space(&mut self, adjustment: CoordinateSpaceAdjustment) {
+        if adjustment.is_empty() {
+            return;
+        }
+
+        let two = if adjustment.contains(CoordinateSpaceAdjustment::REMAP_DEPTH) {
+            Some(self.constants.fetch_or_append(Constant {
+                name: None,
+                specialization: None,
+                inner: ConstantInner::Scalar {
+                    width: 4,
+                    value: ScalarValue::Float(2.0),
+                },
+            }))
+        } else {
+            None
+        };
+
+        for ep in self.entry_points.iter_mut() {
+            if ep.stage != ShaderStage::Vertex {
+                continue;
+            }
+            let function = &mut ep.function;
+            let result = match function.result {
+                Some(ref result) => result,
+                None => continue,
+            };
+
+            // The position is either the result itself, or a member of the result struct
+            let position = Some(Binding::BuiltIn(BuiltIn::Position));
+            let (member, position_ty) = match self.types[result.ty].inner {
+                _ if result.binding == position => (None, result.ty),
+                TypeInner::Struct { ref members, .. } => {
+                    match members.iter().position(|member| member.binding == position) {
+                        Some(index) => (Some((index, members.len())), members[index].ty),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            let result_ty = result.ty;
+
+            let two = two.map(|two| function.expressions.append(Expression::Constant(two)));
+            let adjust = |position, expressions: &mut Arena<Expression>| {
+                let mut components: Vec<_> = (0..4)
+                    .map(|index| {
+                        expressions.append(Expression::AccessIndex {
+                            base: position,
+                            index,
+                        })
+                    })
+                    .collect();
+                if adjustment.contains(CoordinateSpaceAdjustment::FLIP_Y) {
+                    components[1] = expressions.append(Expression::Unary {
+                        op: UnaryOperator::Negate,
+                        expr: components[1],
+                    });
+                }
+                if let Some(two) = two {
+                    let scaled = expressions.append(Expression::Binary {
+                        op: BinaryOperator::Multiply,
+                        left: components[2],
+                        right: two,
+                    });
+                    components[2] = expressions.append(Expression::Binary {
+                        op: BinaryOperator::Subtract,
+                        left: scaled,
+                        right: components[3],
+                    });
+                }
+                expressions.append(Expression::Compose {
+                    ty: position_ty,
+                    components,
+                })
+            };
+
+            rewrite_returns(
+                &mut function.body,
+                &mut function.expressions,
+                &mut |value: Handle<Expression>, expressions: &mut Arena<Expression>| {
+                    Some(match member {
+                        None => adjust(value, expressions),
+                        Some((position_index, count)) => {
+                            let components = (0..count as u32)
+                                .map(|index| {
+                                    let member = expressions
+                                        .append(Expression::AccessIndex { base: value, index });
+                                    if index as usize == position_index {
+                                        adjust(member, expressions)
+                                    } else {
+                                        member
+                                    }
+                                })
+                                .collect();
+                            expressions.append(Expression::Compose {
+                                ty: result_ty,
+                                components,
+                            })
+                        }
+                    })
+                },
+            );
+        }
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn adjust_vertex_positions() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        struct VertexOutput {
+            [[location(0)]] uv: vec2<f32>;
+            [[builtin(position)]] position: vec4<f32>;
+        };
+
+        [[stage(vertex)]]
+        fn vs_struct([[location(0)]] pos: vec4<f32>) -> VertexOutput {
+            if (pos.x > 0.0) {
+                return VertexOutput(pos.xy, pos);
+            }
+            return VertexOutput(vec2<f32>(0.0), pos);
+        }
+
+        [[stage(vertex)]]
+        fn vs_plain([[location(0)]] pos: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return pos;
+        }
+        ",
+    )
+    .unwrap();
+    module.adjust_coordinate_space(
+        CoordinateSpaceAdjustment::FLIP_Y | CoordinateSpaceAdjustment::REMAP_DEPTH,
+    );
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // Find the position written by the first return of each entry point
+    fn returned_position(function: &crate::Function, member: Option<usize>) -> &Expression {
+        let value = function
+            .body
+            .iter()
+            .find_map(|statement| match *statement {
+                crate::Statement::Return { value } => value,
+                crate::Statement::If { ref accept, .. } => {
+                    accept.iter().find_map(|statement| match *statement {
+                        crate::Statement::Return { value } => value,
+                        _ => None,
+                    })
+                }
+                _ => None,
+            })
+            .unwrap();
+        let position = match (member, &function.expressions[value]) {
+            (None, _) => value,
+            (Some(index), &Expression::Compose { ref components, .. }) => components[index],
+            (_, other) => panic!("Unexpected result {:?}", other),
+        };
+        &function.expressions[position]
+    }
+
+    for (ep, member) in module.entry_points.iter().zip(&[Some(1), None]) {
+        let components = match *returned_position(&ep.function, *member) {
+            Expression::Compose { ref components, .. } => components,
+            ref other => panic!("Unexpected position {:?}", other),
+        };
+        match ep.function.expressions[components[1]] {
+            Expression::Unary {
+                op: UnaryOperator::Negate,
+                ..
+            } => {}
+            ref other => panic!("Unexpected Y coordinate {:?}", other),
+        }
+        match ep.function.expressions[components[2]] {
+            Expression::Binary {
+                op: BinaryOperator::Subtract,
+                right,
+                ..
+            } if right == components[3] => {}
+            ref other => panic!("Unexpected depth {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::hash::{Hash, Hasher};
+
+/// A hasher whose output only depends on the data written to it.
+///
+/// This is 64-bit FNV-1a, with integers written in little-endian order
+/// and `usize` written as `u64`, so it gives the same result on every run
+/// and platform, unlike the hasher of `std` or `fxhash`.
+struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    fn new() -> Self {
+        StableHasher(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes())
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64)
+    }
+}
+
+impl Hash for crate::Function {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.name.hash(hasher);
+        self.arguments.hash(hasher);
+        self.result.hash(hasher);
+        self.local_variables.hash(hasher);
+        self.expressions.hash(hasher);
+        // The map iterates in an arbitrary order, so hash it sorted by handle
+        let mut named_expressions = self.named_expressions.iter().collect::<Vec<_>>();
+        named_expressions.sort_by_key(|&(&handle, _)| handle);
+        named_expressions.hash(hasher);
+        self.body.hash(hasher);
+    }
+}
+
+impl crate::Module {
+    /// Return a hash of the contents of the module.
+    ///
+    /// The hash is the same across runs and platforms, so it can be used as
+    /// the key of a persistent cache of translated shaders. Two modules that
+    /// are equal, with the same handles and names, have the same hash.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn content_hash() {
+    let source = "
+        [[stage(fragment)]]
+        fn main([[location(0)]] value: f32) -> [[location(0)]] vec4<f32> {
+            let color = vec4<f32>(value, 0.0, 1.0, 1.0);
+            return color * 2.0;
+        }
+    ";
+    let module = crate::front::wgsl::parse_str(source).unwrap();
+    let again = crate::front::wgsl::parse_str(source).unwrap();
+    assert_eq!(module.content_hash(), again.content_hash());
+
+    let changed = crate::front::wgsl::parse_str(&source.replace("2.0", "3.0")).unwrap();
+    assert_ne!(module.content_hash(), changed.content_hash());
+    let renamed = crate::front::wgsl::parse_str(&source.replace("color", "tint")).unwrap();
+    assert_ne!(module.content_hash(), renamed.content_hash());
+}
@@ -0,0 +1,239 @@
+use crate::{
+    arena::{Arena, Handle, Range},
+    Block, Expression, FastHashSet, Statement,
+};
+
+impl crate::Module {
+    /// Hoist loop-invariant address computations out of loops.
+    ///
+    /// An [`Access`] or [`AccessIndex`] expression emitted at the top level of a
+    /// loop's body is moved in front of the loop if its operands don't depend on
+    /// anything evaluated within the loop. Loads, calls and all the other
+    /// expressions stay where they are, so this never changes the behavior of
+    /// the program, only the places where access chains are computed.
+    ///
+    /// Nested loops are processed first, so an access chain that is invariant
+    /// in several enclosing loops ends up in front of the outermost of them.
+    ///
+    /// [`Access`]: crate::Expression::Access
+    /// [`AccessIndex`]: crate::Expression::AccessIndex
+    pub fn hoist_loop_invariants(&mut self) {
+        for (_, fun) in self.functions.iter_mut() {
+            hoist_block(&mut fun.body, &fun.expressions);
+        }
+        for ep in self.entry_points.iter_mut() {
+            hoist_block(&mut ep.function.body, &ep.function.expressions);
+        }
+    }
+}
+
+fn hoist_block(block: &mut Block, expressions: &Arena<Expression>) {
+    let mut index = 0;
+    while index < block.len() {
+        let hoisted = match block[index] {
+            Statement::Block(ref mut inner) => {
+                hoist_block(inner, expressions);
+                Vec::new()
+            }
+            Statement::If {
+                ref mut accept,
+                ref mut reject,
+                ..
+            } => {
+                hoist_block(accept, expressions);
+                hoist_block(reject, expressions);
+                Vec::new()
+            }
+            Statement::Switch {
+                ref mut cases,
+                ref mut default,
+                ..
+            } => {
+                for case in cases.iter_mut() {
+                    hoist_block(&mut case.body, expressions);
+                }
+                hoist_block(default, expressions);
+                Vec::new()
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                hoist_block(body, expressions);
+                hoist_block(continuing, expressions);
+                hoist_loop(body, continuing, expressions)
+            }
+            _ => Vec::new(),
+        };
+
+        let count = hoisted.len();
+        block.splice(index..index, hoisted);
+        index += count + 1;
+    }
+}
+
+/// Remove the invariant expressions from the loop body, returning the
+/// statements that emit them in front of the loop.
+fn hoist_loop(
+    body: &mut Block,
+    continuing: &Block,
+    expressions: &Arena<Expression>,
+) -> Vec<Statement> {
+    let mut variant = FastHashSet::default();
+    collect_evaluated(body, &mut variant);
+    collect_evaluated(continuing, &mut variant);
+
+    let mut hoisted = Vec::new();
+    for statement in body.iter() {
+        if let Statement::Emit(ref range) = *statement {
+            for handle in range.clone() {
+                let invariant = |operand| !variant.contains(&operand);
+                let hoist = match expressions[handle] {
+                    Expression::Access { base, index } => invariant(base) && invariant(index),
+                    Expression::AccessIndex { base, .. } => invariant(base),
+                    _ => false,
+                };
+                if hoist {
+                    variant.remove(&handle);
+                    hoisted.push(handle);
+                }
+            }
+        }
+    }
+
+    if hoisted.is_empty() {
+        return Vec::new();
+    }
+
+    let old_body = std::mem::replace(body, Vec::with_capacity(hoisted.len()));
+    for statement in old_body {
+        match statement {
+            Statement::Emit(range) => {
+                let kept = range.filter(|handle| variant.contains(handle));
+                body.extend(emit_statements(kept));
+            }
+            other => body.push(other),
+        }
+    }
+
+    emit_statements(hoisted.into_iter())
+}
+
+/// Collect the expressions evaluated by the statements of `block`.
+fn collect_evaluated(block: &Block, set: &mut FastHashSet<Handle<Expression>>) {
+    for statement in block {
+        match *statement {
+            Statement::Emit(ref range) => set.extend(range.clone()),
+            Statement::Block(ref inner) => collect_evaluated(inner, set),
+            Statement::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                collect_evaluated(accept, set);
+                collect_evaluated(reject, set);
+            }
+            Statement::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases {
+                    collect_evaluated(&case.body, set);
+                }
+                collect_evaluated(default, set);
+            }
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                collect_evaluated(body, set);
+                collect_evaluated(continuing, set);
+            }
+            Statement::Call {
+                result: Some(result),
+                ..
+            } => {
+                set.insert(result);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Build `Emit` statements covering the given increasing handles.
+fn emit_statements(handles: impl Iterator<Item = Handle<Expression>>) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut current: Option<(Handle<Expression>, Handle<Expression>)> = None;
+    for handle in handles {
+        current = match current {
+            Some((first, last)) if last.index() + 1 == handle.index() => Some((first, handle)),
+            Some((first, last)) => {
+                statements.push(Statement::Emit(Range::new_from_bounds(first, last)));
+                Some((handle, handle))
+            }
+            None => Some((handle, handle)),
+        };
+    }
+    if let Some((first, last)) = current {
+        statements.push(Statement::Emit(Range::new_from_bounds(first, last)));
+    }
+    statements
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn hoist_access_chains() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        struct Item { value: f32; };
+        var<private> items: array<Item, 4>;
+        fn sum(index: i32) -> f32 {
+            var total: f32 = 0.0;
+            loop {
+                if (total > 10.0) {
+                    break;
+                }
+                total = total + items[index].value;
+            }
+            return total;
+        }
+        ",
+    )
+    .unwrap();
+    module.hoist_loop_invariants();
+
+    let fun = &module.functions[module.functions.find_by_name("sum").unwrap()];
+    let position = fun
+        .body
+        .iter()
+        .position(|statement| match *statement {
+            Statement::Loop { .. } => true,
+            _ => false,
+        })
+        .unwrap();
+    let hoisted = match fun.body[position - 1] {
+        Statement::Emit(ref range) => range.clone().collect::<Vec<_>>(),
+        ref other => panic!("Unexpected statement {:?}", other),
+    };
+    match hoisted[..] {
+        [access, access_index] => {
+            match fun.expressions[access] {
+                Expression::Access { .. } => {}
+                ref other => panic!("Unexpected expression {:?}", other),
+            }
+            match fun.expressions[access_index] {
+                Expression::AccessIndex { base, index: 0 } if base == access => {}
+                ref other => panic!("Unexpected expression {:?}", other),
+            }
+        }
+        _ => panic!("Unexpected hoisted expressions {:?}", hoisted),
+    }
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
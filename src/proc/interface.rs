@@ -0,0 +1,917 @@
+use crate::{
+    arena::{Arena, Handle, Range, UniqueArena},
+    Binding, Block, Expression, FastHashMap, FastHashSet, Function, ImageQuery, SampleLevel,
+    Statement, Type, TypeInner,
+};
+
+/// Configuration of [`Module::prune_interface`].
+///
+/// [`Module::prune_interface`]: crate::Module::prune_interface
+#[derive(Clone, Debug, Default)]
+pub struct InterfacePruning {
+    /// Locations of inputs and outputs that are kept even if they are unused.
+    pub keep_locations: Vec<u32>,
+    /// Locations read by the next stage of the pipeline.
+    ///
+    /// Outputs at any other location are removed. If `None`, all outputs are kept.
+    pub consumed_outputs: Option<Vec<u32>>,
+}
+
+impl InterfacePruning {
+    fn prunable_input(&self, binding: Option<&Binding>) -> bool {
+        match binding {
+            Some(&Binding::Location { location, .. }) => !self.keep_locations.contains(&location),
+            _ => false,
+        }
+    }
+
+    fn prunable_output(&self, binding: Option<&Binding>) -> bool {
+        match (binding, self.consumed_outputs.as_ref()) {
+            (Some(&Binding::Location { location, .. }), Some(consumed)) => {
+                !consumed.contains(&location) && !self.keep_locations.contains(&location)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl crate::Module {
+    /// Remove the user-defined inputs and outputs of an entry point that are unused.
+    ///
+    /// An input with a `Location` binding is removed if the entry point never
+    /// reads it, either as an argument or as a member of a struct argument.
+    /// Outputs are always written by the entry point, so an output is only
+    /// removed if [`InterfacePruning::consumed_outputs`] doesn't list its location.
+    ///
+    /// Built-in inputs and outputs are never removed, since even unused ones
+    /// may affect the pipeline, for example by forcing per-sample shading.
+    ///
+    /// Struct arguments and results that lose members get new struct types,
+    /// and the expressions that only existed to access removed inputs are
+    /// dropped from the entry point.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entry_point` isn't a valid index into the module's entry points.
+    pub fn prune_interface(&mut self, entry_point: usize, options: &InterfacePruning) {
+        let function = &mut self.entry_points[entry_point].function;
+        prune_inputs(function, &mut self.types, options);
+        prune_outputs(function, &mut self.types, options);
+    }
+}
+
+fn prune_inputs(
+    function: &mut Function,
+    types: &mut UniqueArena<Type>,
+    options: &InterfacePruning,
+) {
+    let expressions = &function.expressions;
+    let used = collect_used(&function.body, expressions);
+
+    // Find out which arguments are read, and which of them are only read member by member
+    let mut read = FastHashSet::default();
+    let mut read_whole = FastHashSet::default();
+    let mut read_members = FastHashSet::default();
+    let argument_index = |handle: Handle<Expression>| match expressions[handle] {
+        Expression::FunctionArgument(index) => Some(index),
+        _ => None,
+    };
+    for &handle in used.iter() {
+        match expressions[handle] {
+            Expression::FunctionArgument(index) => {
+                read.insert(index);
+            }
+            Expression::AccessIndex { base, index } if argument_index(base).is_some() => {
+                read_members.insert((argument_index(base).unwrap(), index));
+            }
+            ref expression => visit_operands(expression, &mut |operand| {
+                read_whole.extend(argument_index(operand));
+            }),
+        }
+    }
+    visit_statement_operands(&function.body, &mut |operand| {
+        read_whole.extend(argument_index(operand));
+    });
+
+    // Decide what to remove: whole arguments, or some members of struct arguments
+    let mut argument_map = Vec::with_capacity(function.arguments.len());
+    let mut member_maps = FastHashMap::default();
+    let mut kept_arguments = Vec::with_capacity(function.arguments.len());
+    for (index, mut argument) in function.arguments.drain(..).enumerate() {
+        let index = index as u32;
+        let mut removed =
+            options.prunable_input(argument.binding.as_ref()) && !read.contains(&index);
+        if argument.binding.is_none() && !read_whole.contains(&index) {
+            if let Type {
+                ref name,
+                inner:
+                    TypeInner::Struct {
+                        top_level,
+                        ref members,
+                        span,
+                    },
+            } = types[argument.ty]
+            {
+                let mut member_map = Vec::with_capacity(members.len());
+                let mut kept_members = Vec::with_capacity(members.len());
+                for (member_index, member) in members.iter().enumerate() {
+                    if options.prunable_input(member.binding.as_ref())
+                        && !read_members.contains(&(index, member_index as u32))
+                    {
+                        member_map.push(None);
+                    } else {
+                        member_map.push(Some(kept_members.len() as u32));
+                        kept_members.push(member.clone());
+                    }
+                }
+
+                if kept_members.is_empty() {
+                    removed = true;
+                } else if kept_members.len() < members.len() {
+                    let ty = Type {
+                        name: name.clone(),
+                        inner: TypeInner::Struct {
+                            top_level,
+                            members: kept_members,
+                            span,
+                        },
+                    };
+                    argument.ty = types.insert(ty);
+                    member_maps.insert(index, member_map);
+                }
+            }
+        }
+
+        if removed {
+            argument_map.push(None);
+        } else {
+            argument_map.push(Some(kept_arguments.len() as u32));
+            kept_arguments.push(argument);
+        }
+    }
+    function.arguments = kept_arguments;
+
+    if argument_map.iter().all(Option::is_some) && member_maps.is_empty() {
+        return;
+    }
+
+    // Expressions that depend on removed inputs can't be used, so they are removed as well
+    let mut removed = FastHashSet::default();
+    let mut renumbered = Vec::new();
+    for (handle, expression) in function.expressions.iter() {
+        let mut remove = false;
+        match *expression {
+            Expression::FunctionArgument(index) => {
+                remove = argument_map[index as usize].is_none();
+            }
+            Expression::AccessIndex { base, index } => {
+                let map = argument_index(base).and_then(|argument| member_maps.get(&argument));
+                match map.and_then(|map| map.get(index as usize)) {
+                    Some(&Some(new_index)) => renumbered.push((handle, new_index)),
+                    Some(&None) => remove = true,
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+        visit_operands(expression, &mut |operand| {
+            remove |= removed.contains(&operand);
+        });
+        if remove {
+            removed.insert(handle);
+        }
+    }
+
+    for (handle, new_index) in renumbered {
+        if let Expression::AccessIndex { ref mut index, .. } = *function.expressions.get_mut(handle)
+        {
+            *index = new_index;
+        }
+    }
+    for (_, expression) in function.expressions.iter_mut() {
+        if let Expression::FunctionArgument(ref mut index) = *expression {
+            if let Some(new_index) = argument_map[*index as usize] {
+                *index = new_index;
+            }
+        }
+    }
+
+    if !removed.is_empty() {
+        compact_expressions(function, &removed);
+    }
+}
+
+fn prune_outputs(
+    function: &mut Function,
+    types: &mut UniqueArena<Type>,
+    options: &InterfacePruning,
+) {
+    let result = match function.result {
+        Some(ref result) => result,
+        None => return,
+    };
+
+    let mut remove_result = options.prunable_output(result.binding.as_ref());
+    let mut new_struct = None;
+    if let Type {
+        ref name,
+        inner:
+            TypeInner::Struct {
+                top_level,
+                ref members,
+                span,
+            },
+    } = types[result.ty]
+    {
+        let kept: Vec<_> = (0..members.len() as u32)
+            .filter(|&index| !options.prunable_output(members[index as usize].binding.as_ref()))
+            .collect();
+        if kept.is_empty() {
+            remove_result = true;
+        } else if kept.len() < members.len() {
+            let ty = Type {
+                name: name.clone(),
+                inner: TypeInner::Struct {
+                    top_level,
+                    members: kept
+                        .iter()
+                        .map(|&index| members[index as usize].clone())
+                        .collect(),
+                    span,
+                },
+            };
+            new_struct = Some((types.insert(ty), kept));
+        }
+    }
+
+    if remove_result {
+        function.result = None;
+        rewrite_returns(
+            &mut function.body,
+            &mut function.expressions,
+            &mut |_, _| None,
+        );
+    } else if let Some((ty, kept)) = new_struct {
+        function.result.as_mut().unwrap().ty = ty;
+        // Build the new result out of the members of the old one
+        rewrite_returns(
+            &mut function.body,
+            &mut function.expressions,
+            &mut |value, expressions| {
+                let components = kept
+                    .iter()
+                    .map(|&index| {
+                        expressions.append(Expression::AccessIndex { base: value, index })
+                    })
+                    .collect();
+                Some(expressions.append(Expression::Compose { ty, components }))
+            },
+        );
+    }
+}
+
+/// Collect the expressions that the statements of `block` depend on.
+pub(super) fn collect_used(
+    block: &Block,
+    expressions: &Arena<Expression>,
+) -> FastHashSet<Handle<Expression>> {
+    let mut used = FastHashSet::default();
+    let mut pending = Vec::new();
+    visit_statement_operands(block, &mut |operand| pending.push(operand));
+    while let Some(handle) = pending.pop() {
+        if used.insert(handle) {
+            visit_operands(&expressions[handle], &mut |operand| pending.push(operand));
+        }
+    }
+    used
+}
+
+/// Remove the `removed` expressions from `function`, which nothing else may depend on.
+pub(super) fn compact_expressions(
+    function: &mut Function,
+    removed: &FastHashSet<Handle<Expression>>,
+) {
+    let old_expressions = std::mem::take(&mut function.expressions);
+    let mut handle_map = Vec::with_capacity(old_expressions.len());
+    for (handle, mut expression) in old_expressions
+        .into_inner()
+        .into_iter()
+        .enumerate()
+        .map(|(index, expression)| (Handle::from_usize(index), expression))
+    {
+        if removed.contains(&handle) {
+            handle_map.push(None);
+        } else {
+            visit_operands_mut(&mut expression, &mut |operand| {
+                *operand = handle_map[operand.index()].unwrap();
+            });
+            handle_map.push(Some(function.expressions.append(expression)));
+        }
+    }
+
+    compact_block(&mut function.body, &handle_map);
+    function.named_expressions = function
+        .named_expressions
+        .drain()
+        .filter_map(|(handle, name)| Some((handle_map[handle.index()]?, name)))
+        .collect();
+}
+
+fn compact_block(block: &mut Block, handle_map: &[Option<Handle<Expression>>]) {
+    remap_block(
+        block,
+        &|handle| *handle = handle_map[handle.index()].unwrap(),
+        &|range| {
+            let mut kept = range
+                .clone()
+                .filter_map(|handle| handle_map[handle.index()]);
+            let first = kept.next()?;
+            let last = kept.last().unwrap_or(first);
+            Some(Range::new_from_bounds(first, last))
+        },
+    );
+}
+
+/// Rewrite the expression handles used by the statements of `block`.
+///
+/// `map_range` returns the new range of each `Emit`, or `None` to drop it.
+pub(super) fn remap_block(
+    block: &mut Block,
+    map: &dyn Fn(&mut Handle<Expression>),
+    map_range: &dyn Fn(&Range<Expression>) -> Option<Range<Expression>>,
+) {
+    block.retain(|statement| match *statement {
+        Statement::Emit(ref range) => map_range(range).is_some(),
+        _ => true,
+    });
+    for statement in block.iter_mut() {
+        match *statement {
+            Statement::Emit(ref mut range) => *range = map_range(range).unwrap(),
+            Statement::Block(ref mut inner) => remap_block(inner, map, map_range),
+            Statement::If {
+                ref mut condition,
+                ref mut accept,
+                ref mut reject,
+            } => {
+                map(condition);
+                remap_block(accept, map, map_range);
+                remap_block(reject, map, map_range);
+            }
+            Statement::Switch {
+                ref mut selector,
+                ref mut cases,
+                ref mut default,
+            } => {
+                map(selector);
+                for case in cases.iter_mut() {
+                    remap_block(&mut case.body, map, map_range);
+                }
+                remap_block(default, map, map_range);
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                remap_block(body, map, map_range);
+                remap_block(continuing, map, map_range);
+            }
+            Statement::Return {
+                value: Some(ref mut value),
+            } => map(value),
+            Statement::Store {
+                ref mut pointer,
+                ref mut value,
+            } => {
+                map(pointer);
+                map(value);
+            }
+            Statement::ImageStore {
+                ref mut image,
+                ref mut coordinate,
+                ref mut array_index,
+                ref mut value,
+            } => {
+                map(image);
+                map(coordinate);
+                array_index.as_mut().map(map);
+                map(value);
+            }
+            Statement::Call {
+                ref mut arguments,
+                ref mut result,
+                ..
+            } => {
+                arguments.iter_mut().for_each(map);
+                result.as_mut().map(map);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replace the values returned by the statements of `block`.
+///
+/// The expressions that `rewrite` appends are emitted right before the return.
+pub(super) fn rewrite_returns<F>(
+    block: &mut Block,
+    expressions: &mut Arena<Expression>,
+    rewrite: &mut F,
+) where
+    F: FnMut(Handle<Expression>, &mut Arena<Expression>) -> Option<Handle<Expression>>,
+{
+    let mut index = 0;
+    while index < block.len() {
+        match block[index] {
+            Statement::Block(ref mut inner) => rewrite_returns(inner, expressions, rewrite),
+            Statement::If {
+                ref mut accept,
+                ref mut reject,
+                ..
+            } => {
+                rewrite_returns(accept, expressions, rewrite);
+                rewrite_returns(reject, expressions, rewrite);
+            }
+            Statement::Switch {
+                ref mut cases,
+                ref mut default,
+                ..
+            } => {
+                for case in cases.iter_mut() {
+                    rewrite_returns(&mut case.body, expressions, rewrite);
+                }
+                rewrite_returns(default, expressions, rewrite);
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                rewrite_returns(body, expressions, rewrite);
+                rewrite_returns(continuing, expressions, rewrite);
+            }
+            Statement::Return { value: Some(value) } => {
+                let start = expressions.len();
+                block[index] = Statement::Return {
+                    value: rewrite(value, expressions),
+                };
+                if expressions.len() > start {
+                    block.insert(index, Statement::Emit(expressions.range_from(start)));
+                    index += 1;
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+}
+
+pub(super) fn visit_statement_operands(block: &Block, visit: &mut dyn FnMut(Handle<Expression>)) {
+    for statement in block {
+        match *statement {
+            Statement::Block(ref inner) => visit_statement_operands(inner, visit),
+            Statement::If {
+                condition,
+                ref accept,
+                ref reject,
+            } => {
+                visit(condition);
+                visit_statement_operands(accept, visit);
+                visit_statement_operands(reject, visit);
+            }
+            Statement::Switch {
+                selector,
+                ref cases,
+                ref default,
+            } => {
+                visit(selector);
+                for case in cases {
+                    visit_statement_operands(&case.body, visit);
+                }
+                visit_statement_operands(default, visit);
+            }
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                visit_statement_operands(body, visit);
+                visit_statement_operands(continuing, visit);
+            }
+            Statement::Return { value: Some(value) } => visit(value),
+            Statement::Store { pointer, value } => {
+                visit(pointer);
+                visit(value);
+            }
+            Statement::ImageStore {
+                image,
+                coordinate,
+                array_index,
+                value,
+            } => {
+                visit(image);
+                visit(coordinate);
+                array_index.map(&mut *visit);
+                visit(value);
+            }
+            Statement::Call { ref arguments, .. } => {
+                arguments.iter().cloned().for_each(&mut *visit)
+            }
+            Statement::Emit(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Return { value: None }
+            | Statement::Kill
+            | Statement::Barrier(_) => {}
+        }
+    }
+}
+
+pub(super) fn visit_operands(expression: &Expression, visit: &mut dyn FnMut(Handle<Expression>)) {
+    match *expression {
+        Expression::Access { base, index } => {
+            visit(base);
+            visit(index);
+        }
+        Expression::AccessIndex { base, .. } => visit(base),
+        Expression::Splat { value, .. } => visit(value),
+        Expression::Swizzle { vector, .. } => visit(vector),
+        Expression::Compose { ref components, .. } => {
+            components.iter().cloned().for_each(&mut *visit)
+        }
+        Expression::Load { pointer } => visit(pointer),
+        Expression::ImageSample {
+            image,
+            sampler,
+            coordinate,
+            array_index,
+            level,
+            depth_ref,
+            ..
+        } => {
+            visit(image);
+            visit(sampler);
+            visit(coordinate);
+            array_index.map(&mut *visit);
+            match level {
+                SampleLevel::Exact(expr) | SampleLevel::Bias(expr) => visit(expr),
+                SampleLevel::Gradient { x, y } => {
+                    visit(x);
+                    visit(y);
+                }
+                SampleLevel::Auto | SampleLevel::Zero => {}
+            }
+            depth_ref.map(&mut *visit);
+        }
+        Expression::ImageLoad {
+            image,
+            coordinate,
+            array_index,
+            index,
+        } => {
+            visit(image);
+            visit(coordinate);
+            array_index.map(&mut *visit);
+            index.map(&mut *visit);
+        }
+        Expression::ImageQuery { image, query } => {
+            visit(image);
+            match query {
+                ImageQuery::Size { level } => {
+                    if let Some(level) = level {
+                        visit(level);
+                    }
+                }
+                ImageQuery::Lod {
+                    sampler,
+                    coordinate,
+                } => {
+                    visit(sampler);
+                    visit(coordinate);
+                }
+                ImageQuery::NumLevels | ImageQuery::NumLayers | ImageQuery::NumSamples => {}
+            }
+        }
+        Expression::Unary { expr, .. }
+        | Expression::Derivative { expr, .. }
+        | Expression::As { expr, .. }
+        | Expression::PointerCast { expr, .. }
+        | Expression::ArrayLength(expr) => visit(expr),
+        Expression::Binary { left, right, .. } => {
+            visit(left);
+            visit(right);
+        }
+        Expression::Select {
+            condition,
+            accept,
+            reject,
+        } => {
+            visit(condition);
+            visit(accept);
+            visit(reject);
+        }
+        Expression::Relational { argument, .. } => visit(argument),
+        Expression::Math {
+            arg, arg1, arg2, ..
+        } => {
+            visit(arg);
+            arg1.map(&mut *visit);
+            arg2.map(&mut *visit);
+        }
+        Expression::Constant(_)
+        | Expression::FunctionArgument(_)
+        | Expression::GlobalVariable(_)
+        | Expression::LocalVariable(_)
+        | Expression::Call(_) => {}
+    }
+}
+
+pub(super) fn visit_operands_mut(
+    expression: &mut Expression,
+    visit: &mut dyn FnMut(&mut Handle<Expression>),
+) {
+    match *expression {
+        Expression::Access {
+            ref mut base,
+            ref mut index,
+        } => {
+            visit(base);
+            visit(index);
+        }
+        Expression::AccessIndex { ref mut base, .. } => visit(base),
+        Expression::Splat { ref mut value, .. } => visit(value),
+        Expression::Swizzle { ref mut vector, .. } => visit(vector),
+        Expression::Compose {
+            ref mut components, ..
+        } => components.iter_mut().for_each(&mut *visit),
+        Expression::Load { ref mut pointer } => visit(pointer),
+        Expression::ImageSample {
+            ref mut image,
+            ref mut sampler,
+            ref mut coordinate,
+            ref mut array_index,
+            ref mut level,
+            ref mut depth_ref,
+            ..
+        } => {
+            visit(image);
+            visit(sampler);
+            visit(coordinate);
+            array_index.as_mut().map(&mut *visit);
+            match *level {
+                SampleLevel::Exact(ref mut expr) | SampleLevel::Bias(ref mut expr) => visit(expr),
+                SampleLevel::Gradient {
+                    ref mut x,
+                    ref mut y,
+                } => {
+                    visit(x);
+                    visit(y);
+                }
+                SampleLevel::Auto | SampleLevel::Zero => {}
+            }
+            depth_ref.as_mut().map(&mut *visit);
+        }
+        Expression::ImageLoad {
+            ref mut image,
+            ref mut coordinate,
+            ref mut array_index,
+            ref mut index,
+        } => {
+            visit(image);
+            visit(coordinate);
+            array_index.as_mut().map(&mut *visit);
+            index.as_mut().map(&mut *visit);
+        }
+        Expression::ImageQuery {
+            ref mut image,
+            ref mut query,
+        } => {
+            visit(image);
+            match *query {
+                ImageQuery::Size { ref mut level } => {
+                    if let Some(ref mut level) = *level {
+                        visit(level);
+                    }
+                }
+                ImageQuery::Lod {
+                    ref mut sampler,
+                    ref mut coordinate,
+                } => {
+                    visit(sampler);
+                    visit(coordinate);
+                }
+                ImageQuery::NumLevels | ImageQuery::NumLayers | ImageQuery::NumSamples => {}
+            }
+        }
+        Expression::Unary { ref mut expr, .. }
+        | Expression::Derivative { ref mut expr, .. }
+        | Expression::As { ref mut expr, .. }
+        | Expression::PointerCast { ref mut expr, .. }
+        | Expression::ArrayLength(ref mut expr) => visit(expr),
+        Expression::Binary {
+            ref mut left,
+            ref mut right,
+            ..
+        } => {
+            visit(left);
+            visit(right);
+        }
+        Expression::Select {
+            ref mut condition,
+            ref mut accept,
+            ref mut reject,
+        } => {
+            visit(condition);
+            visit(accept);
+            visit(reject);
+        }
+        Expression::Relational {
+            ref mut argument, ..
+        } => visit(argument),
+        Expression::Math {
+            ref mut arg,
+            ref mut arg1,
+            ref mut arg2,
+            ..
+        } => {
+            visit(arg);
+            arg1.as_mut().map(&mut *visit);
+            arg2.as_mut().map(&mut *visit);
+        }
+        Expression::Constant(_)
+        | Expression::FunctionArgument(_)
+        | Expression::GlobalVariable(_)
+        | Expression::LocalVariable(_)
+        | Expression::Call(_) => {}
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn prune_varyings() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        struct VertexOutput {
+            [[builtin(position)]] position: vec4<f32>;
+            [[location(0)]] color: vec4<f32>;
+            [[location(1)]] normal: vec3<f32>;
+            [[location(2)]] depth: f32;
+        };
+
+        [[stage(vertex)]]
+        fn vs_main([[location(0)]] position: vec4<f32>, [[location(1)]] unused: f32) -> VertexOutput {
+            return VertexOutput(position, vec4<f32>(1.0), vec3<f32>(0.0), 0.5);
+        }
+
+        [[stage(fragment)]]
+        fn fs_main(in: VertexOutput, [[location(3)]] unused: f32) -> [[location(0)]] vec4<f32> {
+            let scaled = in.normal * 2.0;
+            return in.color;
+        }
+        ",
+    )
+    .unwrap();
+
+    let vs = module
+        .entry_points
+        .iter()
+        .position(|ep| ep.name == "vs_main")
+        .unwrap();
+    let fs = module
+        .entry_points
+        .iter()
+        .position(|ep| ep.name == "fs_main")
+        .unwrap();
+    module.prune_interface(
+        vs,
+        &InterfacePruning {
+            keep_locations: vec![2],
+            consumed_outputs: Some(vec![0]),
+        },
+    );
+    module.prune_interface(fs, &InterfacePruning::default());
+
+    let locations = |ty: Handle<Type>| match module.types[ty].inner {
+        TypeInner::Struct { ref members, .. } => members
+            .iter()
+            .map(|member| match member.binding {
+                Some(Binding::Location { location, .. }) => Some(location),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        ref other => panic!("Unexpected type {:?}", other),
+    };
+
+    let vs_function = &module.entry_points[vs].function;
+    assert_eq!(vs_function.arguments.len(), 1);
+    assert_eq!(
+        locations(vs_function.result.as_ref().unwrap().ty),
+        [None, Some(0), Some(2)]
+    );
+
+    let fs_function = &module.entry_points[fs].function;
+    assert_eq!(fs_function.arguments.len(), 1);
+    assert_eq!(locations(fs_function.arguments[0].ty), [None, Some(0)]);
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
+
+/// Turn the samples of `function` that are only used through a two
+/// component swizzle into level of detail queries, which WGSL can't express.
+#[cfg(all(test, feature = "wgsl-in"))]
+pub(super) fn sample_to_lod_queries(function: &mut Function) {
+    let samples: Vec<_> = function
+        .expressions
+        .iter()
+        .filter_map(|(_, expression)| match *expression {
+            Expression::Swizzle {
+                size: crate::VectorSize::Bi,
+                vector,
+                ..
+            } => Some(vector),
+            _ => None,
+        })
+        .collect();
+    for handle in samples {
+        let expression = function.expressions.get_mut(handle);
+        if let Expression::ImageSample {
+            image,
+            sampler,
+            coordinate,
+            ..
+        } = *expression
+        {
+            *expression = Expression::ImageQuery {
+                image,
+                query: ImageQuery::Lod {
+                    sampler,
+                    coordinate,
+                },
+            };
+        }
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn prune_lod_query_inputs() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]] var t: texture_2d<f32>;
+        [[group(0), binding(1)]] var s: sampler;
+
+        [[stage(fragment)]]
+        fn main(
+            [[location(0)]] unused: vec4<f32>,
+            [[location(1)]] uv: vec2<f32>,
+        ) -> [[location(0)]] vec4<f32> {
+            let scaled = unused * 2.0;
+            let lod = textureSample(t, s, uv * 0.5).xy;
+            return vec4<f32>(lod, 0.0, 1.0);
+        }
+        ",
+    )
+    .unwrap();
+    sample_to_lod_queries(&mut module.entry_points[0].function);
+    module.prune_interface(0, &InterfacePruning::default());
+
+    let function = &module.entry_points[0].function;
+    assert_eq!(function.arguments.len(), 1);
+    // the operands of the query follow the expressions that moved
+    let query = function
+        .expressions
+        .iter()
+        .find_map(|(_, expression)| match *expression {
+            Expression::ImageQuery {
+                query:
+                    ImageQuery::Lod {
+                        sampler,
+                        coordinate,
+                    },
+                ..
+            } => Some((sampler, coordinate)),
+            _ => None,
+        });
+    match query {
+        Some((sampler, coordinate)) => {
+            assert!(matches!(
+                function.expressions[sampler],
+                Expression::GlobalVariable(_)
+            ));
+            assert!(matches!(
+                function.expressions[coordinate],
+                Expression::Binary { .. }
+            ));
+        }
+        None => panic!("The level of detail query is gone"),
+    }
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
@@ -1,4 +1,4 @@
-pub use crate::{Arena, Handle};
+pub use crate::{Handle, UniqueArena};
 
 impl crate::Module {
     /// Apply the usual default interpolation for vertex shader outputs and fragment shader inputs.
@@ -36,42 +36,46 @@ impl crate::Module {
         /// `binding` refers to the `Binding` whose type is `ty`. If `ty` is a struct, then it's the
         /// bindings of the struct's members that we care about, and the binding of the struct
         /// itself is meaningless, so `binding` should be `None`.
+        ///
+        /// If the updated struct turns out to be identical to one already in `types`, `ty` is
+        /// changed to refer to that one instead.
         fn default_binding_or_struct(
             binding: &mut Option<Binding>,
-            ty: Handle<Type>,
-            types: &mut Arena<Type>,
+            ty: &mut Handle<Type>,
+            types: &mut UniqueArena<Type>,
         ) {
-            let inner = &mut types.get_mut(ty).inner;
-            if let TypeInner::Struct {
-                members: ref mut m, ..
-            } = *inner
-            {
+            if let TypeInner::Struct { .. } = types[*ty].inner {
                 // A struct. It's the individual members we care about, so recurse.
 
-                // To choose the right interpolations for `members`, we must consult other
-                // elements of `types`. But both `members` and the types it refers to are stored
-                // in `types`, and Rust won't let us mutate one element of the `Arena`'s `Vec`
-                // while reading others.
-                //
-                // So, temporarily swap the member list out its type, assign appropriate
-                // interpolations to its members, and then swap the list back in.
-                use std::mem;
-                let mut members = mem::take(m);
+                // To choose the right interpolations for the members, we must consult other
+                // elements of `types`, and types can't be mutated in place anyway. So, copy
+                // the member list out of its type, assign appropriate interpolations to its
+                // members, and then replace the type with the updated one.
+                let (name, top_level, mut members, span) = match types[*ty] {
+                    Type {
+                        ref name,
+                        inner:
+                            TypeInner::Struct {
+                                top_level,
+                                ref members,
+                                span,
+                            },
+                    } => (name.clone(), top_level, members.clone(), span),
+                    _ => unreachable!("ty must be a struct"),
+                };
 
                 for member in &mut members {
-                    default_binding_or_struct(&mut member.binding, member.ty, types);
+                    default_binding_or_struct(&mut member.binding, &mut member.ty, types);
                 }
 
-                // Swap the member list back in. It's essential that we call `types.get_mut`
-                // afresh here, rather than just using `m`: it's only because `m` was dead that
-                // we were able to pass `types` to the recursive call.
-                match types.get_mut(ty).inner {
-                    TypeInner::Struct {
-                        members: ref mut m, ..
-                    } => mem::replace(m, members),
-                    _ => unreachable!("ty must be a struct"),
+                let inner = TypeInner::Struct {
+                    top_level,
+                    members,
+                    span,
                 };
-
+                if let Err(existing) = types.replace(*ty, Type { name, inner }) {
+                    *ty = existing;
+                }
                 return;
             }
 
@@ -83,7 +87,7 @@ impl crate::Module {
                 Some(binding) => binding,
             };
 
-            match *inner {
+            match types[*ty].inner {
                 // Some interpolatable type.
                 //
                 // GLSL has 64-bit floats, but it won't interpolate them. WGSL and MSL only have
@@ -134,12 +138,16 @@ impl crate::Module {
             match ep.stage {
                 crate::ShaderStage::Fragment => {
                     for arg in &mut function.arguments {
-                        default_binding_or_struct(&mut arg.binding, arg.ty, &mut self.types);
+                        default_binding_or_struct(&mut arg.binding, &mut arg.ty, &mut self.types);
                     }
                 }
                 crate::ShaderStage::Vertex => {
                     if let Some(result) = function.result.as_mut() {
-                        default_binding_or_struct(&mut result.binding, result.ty, &mut self.types);
+                        default_binding_or_struct(
+                            &mut result.binding,
+                            &mut result.ty,
+                            &mut self.types,
+                        );
                     }
                 }
                 _ => (),
@@ -147,3 +155,56 @@ impl crate::Module {
         }
     }
 }
+
+#[test]
+fn default_interpolation_of_duplicate_struct() {
+    use crate::{Binding, Interpolation, Sampling, ScalarKind, StructMember, Type, TypeInner};
+
+    let mut module = crate::Module::default();
+    let float = module.types.insert(Type {
+        name: None,
+        inner: TypeInner::Scalar {
+            kind: ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let io_struct = |interpolation, sampling| Type {
+        name: None,
+        inner: TypeInner::Struct {
+            top_level: false,
+            members: vec![StructMember {
+                name: None,
+                ty: float,
+                binding: Some(Binding::Location {
+                    location: 0,
+                    interpolation,
+                    sampling,
+                }),
+                offset: 0,
+            }],
+            span: 4,
+        },
+    };
+    let complete = module.types.insert(io_struct(
+        Some(Interpolation::Perspective),
+        Some(Sampling::Center),
+    ));
+    let incomplete = module.types.insert(io_struct(None, None));
+    module.entry_points.push(crate::EntryPoint {
+        name: "main".to_string(),
+        stage: crate::ShaderStage::Fragment,
+        early_depth_test: None,
+        workgroup_size: [0; 3],
+        function: crate::Function {
+            arguments: vec![crate::FunctionArgument {
+                name: None,
+                ty: incomplete,
+                binding: None,
+            }],
+            ..Default::default()
+        },
+    });
+
+    module.apply_common_default_interpolation();
+    assert_eq!(module.entry_points[0].function.arguments[0].ty, complete);
+}
@@ -1,4 +1,4 @@
-use crate::arena::{Arena, Handle};
+use crate::arena::{Arena, Handle, UniqueArena};
 use std::{num::NonZeroU32, ops};
 
 pub type Alignment = NonZeroU32;
@@ -64,7 +64,7 @@ impl Layouter {
 
     pub fn update(
         &mut self,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
         constants: &Arena<crate::Constant>,
     ) -> Result<(), InvalidBaseType> {
         use crate::TypeInner as Ti;
@@ -102,6 +102,13 @@ impl Layouter {
                         Alignment::new((count * width) as u32).unwrap()
                     },
                 },
+                Ti::Pointer {
+                    class: crate::StorageClass::PhysicalStorageBuffer,
+                    ..
+                } => TypeLayout {
+                    size,
+                    alignment: Alignment::new(super::PHYSICAL_POINTER_SPAN).unwrap(),
+                },
                 Ti::Pointer { .. } | Ti::ValuePointer { .. } => TypeLayout {
                     size,
                     alignment: Alignment::new(1).unwrap(),
@@ -136,7 +143,7 @@ impl Layouter {
                         alignment,
                     }
                 }
-                Ti::Image { .. } | Ti::Sampler { .. } => TypeLayout {
+                Ti::Image { .. } | Ti::Sampler { .. } | Ti::BindingArray { .. } => TypeLayout {
                     size,
                     alignment: Alignment::new(1).unwrap(),
                 },
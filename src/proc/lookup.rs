@@ -0,0 +1,63 @@
+//! Lookup helpers over the module arenas, for reflection and tooling.
+
+use crate::{Arena, Handle, UniqueArena};
+
+macro_rules! impl_named_lookup {
+    ($arena:ident: $($ty:ident),*) => {$(
+        impl $arena<crate::$ty> {
+            /// Returns an iterator over the named items of this arena,
+            /// in the order of their handles.
+            pub fn iter_named(
+                &self,
+            ) -> impl DoubleEndedIterator<Item = (Handle<crate::$ty>, &str, &crate::$ty)> {
+                self.iter()
+                    .filter_map(|(handle, item)| Some((handle, item.name.as_deref()?, item)))
+            }
+
+            /// Returns the handle of the first item with the given name.
+            pub fn find_by_name(&self, name: &str) -> Option<Handle<crate::$ty>> {
+                self.iter_named()
+                    .find(|&(_, item_name, _)| item_name == name)
+                    .map(|(handle, _, _)| handle)
+            }
+        }
+    )*};
+}
+
+impl_named_lookup!(UniqueArena: Type);
+impl_named_lookup!(Arena: Constant, GlobalVariable, Function);
+
+impl Arena<crate::GlobalVariable> {
+    /// Returns the handle of the global variable bound to the given resource slot.
+    pub fn find_binding(&self, group: u32, binding: u32) -> Option<Handle<crate::GlobalVariable>> {
+        let key = crate::ResourceBinding { group, binding };
+        self.fetch_if(|var| var.binding.as_ref() == Some(&key))
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn lookup() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        [[block]]
+        struct Data { value: f32; };
+        [[group(1), binding(2)]]
+        var<uniform> data: Data;
+        fn helper() -> f32 { return data.value; }
+        ",
+    )
+    .unwrap();
+
+    let ty = module.types.find_by_name("Data").unwrap();
+    assert_eq!(module.types[ty].name.as_deref(), Some("Data"));
+    let var = module.global_variables.find_binding(1, 2).unwrap();
+    assert_eq!(module.global_variables.find_by_name("data"), Some(var));
+    assert_eq!(module.global_variables.find_binding(2, 1), None);
+    let names = module
+        .functions
+        .iter_named()
+        .map(|(_, name, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["helper"]);
+}
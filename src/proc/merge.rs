@@ -0,0 +1,678 @@
+use crate::{
+    arena::Handle, ArraySize, Block, Constant, ConstantInner, Expression, Function, GlobalVariable,
+    ResourceBinding, ShaderStage, Statement, Type, TypeInner,
+};
+
+/// Errors returned by [`Module::merge`].
+///
+/// [`Module::merge`]: crate::Module::merge
+#[derive(Clone, Debug, thiserror::Error, PartialEq)]
+pub enum MergeError {
+    #[error("the {stage:?} entry point {name:?} is defined by both modules")]
+    EntryPointCollision { name: String, stage: ShaderStage },
+    #[error("global variable {existing:?} has the resource binding {binding:?} with a different definition")]
+    BindingCollision {
+        existing: Handle<GlobalVariable>,
+        binding: ResourceBinding,
+    },
+    #[error("a forward declared pointer completes to the existing type {existing:?}")]
+    DuplicateType { existing: Handle<Type> },
+    #[error("no function matches the declaration of {name:?}")]
+    UnresolvedFunction { name: String },
+    #[error("several functions match the declaration of {name:?}")]
+    AmbiguousFunction { name: String },
+}
+
+/// Where the contents of a module went when it was merged into another one.
+///
+/// Returned by [`Module::merge`], this maps the handles of the merged module
+/// to the handles of the same items in the resulting module.
+///
+/// [`Module::merge`]: crate::Module::merge
+#[derive(Debug)]
+pub struct HandleRemap {
+    types: Vec<Handle<Type>>,
+    constants: Vec<Handle<Constant>>,
+    global_variables: Vec<Handle<GlobalVariable>>,
+    functions: Vec<Handle<Function>>,
+    existing_functions: Vec<Handle<Function>>,
+    entry_point_offset: usize,
+}
+
+impl HandleRemap {
+    /// Returns the handle that the type `handle` of the merged module has in
+    /// the resulting module, which may be an identical type it already had.
+    pub fn ty(&self, handle: Handle<Type>) -> Handle<Type> {
+        self.types[handle.index()]
+    }
+
+    /// Returns the handle of the constant `handle` of the merged module in the
+    /// resulting module, which may be an identical constant it already had.
+    pub fn constant(&self, handle: Handle<Constant>) -> Handle<Constant> {
+        self.constants[handle.index()]
+    }
+
+    /// Returns the handle of the global variable `handle` of the merged module
+    /// in the resulting module, which is the existing variable if it had the
+    /// same resource binding.
+    pub fn global_variable(&self, handle: Handle<GlobalVariable>) -> Handle<GlobalVariable> {
+        self.global_variables[handle.index()]
+    }
+
+    /// Returns the handle of the function `handle` of the merged module in the
+    /// resulting module, which is its definition if it was a declaration.
+    pub fn function(&self, handle: Handle<Function>) -> Handle<Function> {
+        self.functions[handle.index()]
+    }
+
+    /// Returns the handle that the function `handle` of the resulting module
+    /// had before the merge has now.
+    ///
+    /// The functions are reordered when declarations are resolved, so the
+    /// handles of the existing functions can change too.
+    pub fn existing_function(&self, handle: Handle<Function>) -> Handle<Function> {
+        self.existing_functions[handle.index()]
+    }
+
+    /// Returns the index of the entry point `index` of the merged module in
+    /// the entry points of the resulting module.
+    pub fn entry_point(&self, index: usize) -> usize {
+        self.entry_point_offset + index
+    }
+}
+
+impl crate::Module {
+    /// Import the types, constants, global variables, functions and entry
+    /// points of `other`.
+    ///
+    /// This links a library module, shared by several shaders, with the
+    /// module of one of them. Types are deduplicated, and so are constants.
+    /// A global variable with the same resource binding as one of this
+    /// module is considered the same resource, and is deduplicated too,
+    /// provided both variables match apart from their names.
+    ///
+    /// A function with an empty body, like a GLSL prototype that is never
+    /// defined, is a declaration of a function defined by the other module.
+    /// Declarations of both modules are resolved to the function with the same
+    /// name, arguments, and result, and are then removed. It is an error if
+    /// there is no such function, or more than one.
+    ///
+    /// If an error is returned, no global variable, function, or entry point
+    /// has been imported, but some types and constants may have been.
+    pub fn merge(&mut self, other: crate::Module) -> Result<HandleRemap, MergeError> {
+        for ep in other.entry_points.iter() {
+            if self
+                .entry_points
+                .iter()
+                .any(|existing| existing.name == ep.name && existing.stage == ep.stage)
+            {
+                return Err(MergeError::EntryPointCollision {
+                    name: ep.name.clone(),
+                    stage: ep.stage,
+                });
+            }
+        }
+
+        let mut importer = Importer {
+            module: self,
+            constants: other.constants.into_inner().into_iter().map(Some).collect(),
+            constant_map: Vec::new(),
+            type_map: Vec::with_capacity(other.types.len()),
+        };
+        importer.constant_map = vec![None; importer.constants.len()];
+
+        // Types only refer to the types before them, apart from physical
+        // pointers, whose base is completed once it's imported.
+        let mut forward_pointers = Vec::new();
+        for (index, ty) in other.types.into_inner().into_iter().enumerate() {
+            let inner = match ty.inner {
+                TypeInner::Pointer { base, class } if base.index() >= index => {
+                    forward_pointers.push((importer.module.types.len(), base, class));
+                    // A pointer to itself, so that the placeholder isn't deduplicated
+                    TypeInner::Pointer {
+                        base: Handle::from_usize(importer.module.types.len()),
+                        class,
+                    }
+                }
+                TypeInner::Pointer { base, class } => TypeInner::Pointer {
+                    base: importer.type_map[base.index()],
+                    class,
+                },
+                TypeInner::Array { base, size, stride } => TypeInner::Array {
+                    base: importer.type_map[base.index()],
+                    size: importer.import_array_size(size),
+                    stride,
+                },
+                TypeInner::Struct {
+                    top_level,
+                    mut members,
+                    span,
+                } => {
+                    for member in members.iter_mut() {
+                        member.ty = importer.type_map[member.ty.index()];
+                    }
+                    TypeInner::Struct {
+                        top_level,
+                        members,
+                        span,
+                    }
+                }
+                TypeInner::BindingArray { base, size } => TypeInner::BindingArray {
+                    base: importer.type_map[base.index()],
+                    size: importer.import_array_size(size),
+                },
+                inner => inner,
+            };
+            let handle = importer.module.types.insert(Type {
+                name: ty.name,
+                inner,
+            });
+            importer.type_map.push(handle);
+        }
+        for (index, base, class) in forward_pointers {
+            let handle = Handle::from_usize(index);
+            let name = importer.module.types[handle].name.clone();
+            let base = importer.type_map[base.index()];
+            importer
+                .module
+                .types
+                .replace(
+                    handle,
+                    Type {
+                        name,
+                        inner: TypeInner::Pointer { base, class },
+                    },
+                )
+                .map_err(|existing| MergeError::DuplicateType { existing })?;
+        }
+        for index in 0..importer.constants.len() {
+            importer.import_constant(Handle::from_usize(index));
+        }
+
+        let Importer {
+            module,
+            constant_map,
+            type_map,
+            ..
+        } = importer;
+        let constants: Vec<_> = constant_map.into_iter().map(Option::unwrap).collect();
+        let import_init = |init: Option<Handle<Constant>>| init.map(|c| constants[c.index()]);
+
+        // Find the resources of `other` that this module already has
+        let mut globals = Vec::with_capacity(other.global_variables.len());
+        for (_, var) in other.global_variables.iter() {
+            let var = GlobalVariable {
+                ty: type_map[var.ty.index()],
+                init: import_init(var.init),
+                ..var.clone()
+            };
+            let existing = var.binding.as_ref().and_then(|binding| {
+                module
+                    .global_variables
+                    .fetch_if(|existing| existing.binding.as_ref() == Some(binding))
+            });
+            if let Some(existing) = existing {
+                let existing_var = &module.global_variables[existing];
+                if existing_var.class != var.class
+                    || existing_var.ty != var.ty
+                    || existing_var.init != var.init
+                    || existing_var.storage_access != var.storage_access
+                {
+                    return Err(MergeError::BindingCollision {
+                        existing,
+                        binding: var.binding.unwrap(),
+                    });
+                }
+            }
+            globals.push((var, existing));
+        }
+
+        let functions = std::mem::take(&mut module.functions).into_inner();
+        let existing_count = functions.len();
+        let mut functions: Vec<_> = functions
+            .into_iter()
+            .chain(other.functions.into_inner())
+            .collect();
+        let link = link_functions(&functions, existing_count, &type_map)?;
+
+        let global_variables: Vec<_> = globals
+            .into_iter()
+            .map(|(var, existing)| existing.unwrap_or_else(|| module.global_variables.append(var)))
+            .collect();
+        let remap = HandleRemap {
+            types: type_map,
+            constants,
+            global_variables,
+            functions: link.map[existing_count..].to_vec(),
+            existing_functions: link.map[..existing_count].to_vec(),
+            entry_point_offset: module.entry_points.len(),
+        };
+
+        for (index, function) in functions.iter_mut().enumerate() {
+            if index < existing_count {
+                remap_calls(function, &|handle| remap.existing_function(handle));
+            } else {
+                remap.apply(function);
+            }
+        }
+        let mut functions: Vec<_> = functions.into_iter().map(Some).collect();
+        for index in link.order {
+            module.functions.append(functions[index].take().unwrap());
+        }
+        for ep in module.entry_points.iter_mut() {
+            remap_calls(&mut ep.function, &|handle| remap.existing_function(handle));
+        }
+        for mut ep in other.entry_points {
+            remap.apply(&mut ep.function);
+            module.entry_points.push(ep);
+        }
+        Ok(remap)
+    }
+}
+
+/// The state of [`Module::merge`] while importing types and constants.
+///
+/// [`Module::merge`]: crate::Module::merge
+struct Importer<'a> {
+    module: &'a mut crate::Module,
+    /// The constants of the other module, until they are imported.
+    constants: Vec<Option<Constant>>,
+    constant_map: Vec<Option<Handle<Constant>>>,
+    type_map: Vec<Handle<Type>>,
+}
+
+impl Importer<'_> {
+    fn import_array_size(&mut self, size: ArraySize) -> ArraySize {
+        match size {
+            ArraySize::Constant(constant) => ArraySize::Constant(self.import_constant(constant)),
+            ArraySize::Dynamic => ArraySize::Dynamic,
+        }
+    }
+
+    /// Import a constant of the other module, along with its components.
+    ///
+    /// The constants used by array types are imported along with the types,
+    /// so the ones they depend on must be scalars.
+    fn import_constant(&mut self, handle: Handle<Constant>) -> Handle<Constant> {
+        if let Some(imported) = self.constant_map[handle.index()] {
+            return imported;
+        }
+        let mut constant = self.constants[handle.index()].take().unwrap();
+        if let ConstantInner::Composite {
+            ref mut ty,
+            ref mut components,
+        } = constant.inner
+        {
+            *ty = self.type_map[ty.index()];
+            for component in components.iter_mut() {
+                *component = self.import_constant(*component);
+            }
+        }
+        let imported = self.module.constants.fetch_or_append(constant);
+        self.constant_map[handle.index()] = Some(imported);
+        imported
+    }
+}
+
+/// How the functions of both modules are laid out in the resulting module.
+struct Link {
+    /// The definitions, in an order where callees come before their callers.
+    order: Vec<usize>,
+    /// The handle every function ends up with, which is its definition for
+    /// a declaration.
+    map: Vec<Handle<Function>>,
+}
+
+/// Resolve the declarations among `functions`, which are the functions of
+/// this module followed by the ones of the other module.
+fn link_functions(
+    functions: &[Function],
+    existing_count: usize,
+    type_map: &[Handle<Type>],
+) -> Result<Link, MergeError> {
+    let import_ty = |index: usize, ty: Handle<Type>| {
+        if index < existing_count {
+            ty
+        } else {
+            type_map[ty.index()]
+        }
+    };
+    let signature = |index: usize| {
+        let function = &functions[index];
+        let arguments: Vec<_> = function
+            .arguments
+            .iter()
+            .map(|argument| import_ty(index, argument.ty))
+            .collect();
+        let result = function
+            .result
+            .as_ref()
+            .map(|result| import_ty(index, result.ty));
+        (function.name.as_deref(), arguments, result)
+    };
+    let is_declaration = |index: usize| functions[index].body.is_empty();
+
+    // the definition of every function
+    let definitions = (0..functions.len())
+        .map(|index| {
+            if !is_declaration(index) {
+                return Ok(index);
+            }
+            let declared = signature(index);
+            let mut candidates = (0..functions.len()).filter(|&candidate| {
+                !is_declaration(candidate) && signature(candidate) == declared
+            });
+            let name = || declared.0.unwrap_or_default().to_string();
+            match (candidates.next(), candidates.next()) {
+                (Some(definition), None) => Ok(definition),
+                (None, _) => Err(MergeError::UnresolvedFunction { name: name() }),
+                (Some(_), Some(_)) => Err(MergeError::AmbiguousFunction { name: name() }),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // functions can only call the ones before them
+    let callees: Vec<Vec<usize>> = functions
+        .iter()
+        .enumerate()
+        .map(|(index, function)| {
+            let offset = if index < existing_count {
+                0
+            } else {
+                existing_count
+            };
+            let mut callees = Vec::new();
+            for_each_call(&function.body, &mut |handle| {
+                callees.push(definitions[offset + handle.index()])
+            });
+            callees
+        })
+        .collect();
+    let mut visited = vec![false; functions.len()];
+    let mut order = Vec::with_capacity(functions.len());
+    fn visit(index: usize, callees: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+        // recursion is left for the validator to report
+        visited[index] = true;
+        for &callee in callees[index].iter() {
+            visit(callee, callees, visited, order);
+        }
+        order.push(index);
+    }
+    for index in 0..functions.len() {
+        if !is_declaration(index) {
+            visit(index, &callees, &mut visited, &mut order);
+        }
+    }
+
+    let mut handles = vec![None; functions.len()];
+    for (position, &index) in order.iter().enumerate() {
+        handles[index] = Some(Handle::from_usize(position));
+    }
+    let map = definitions
+        .iter()
+        .map(|&definition| handles[definition].unwrap())
+        .collect();
+    Ok(Link { order, map })
+}
+
+fn for_each_call(block: &Block, f: &mut impl FnMut(Handle<Function>)) {
+    for statement in block.iter() {
+        match *statement {
+            Statement::Block(ref inner) => for_each_call(inner, f),
+            Statement::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                for_each_call(accept, f);
+                for_each_call(reject, f);
+            }
+            Statement::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases.iter() {
+                    for_each_call(&case.body, f);
+                }
+                for_each_call(default, f);
+            }
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                for_each_call(body, f);
+                for_each_call(continuing, f);
+            }
+            Statement::Call { function, .. } => f(function),
+            _ => {}
+        }
+    }
+}
+
+/// Make the calls of `function` refer to the functions `map` returns.
+fn remap_calls(function: &mut Function, map: &dyn Fn(Handle<Function>) -> Handle<Function>) {
+    for (_, expression) in function.expressions.iter_mut() {
+        if let Expression::Call(ref mut function) = *expression {
+            *function = map(*function);
+        }
+    }
+    remap_block_calls(&mut function.body, map);
+}
+
+fn remap_block_calls(block: &mut Block, map: &dyn Fn(Handle<Function>) -> Handle<Function>) {
+    for statement in block.iter_mut() {
+        match *statement {
+            Statement::Block(ref mut inner) => remap_block_calls(inner, map),
+            Statement::If {
+                ref mut accept,
+                ref mut reject,
+                ..
+            } => {
+                remap_block_calls(accept, map);
+                remap_block_calls(reject, map);
+            }
+            Statement::Switch {
+                ref mut cases,
+                ref mut default,
+                ..
+            } => {
+                for case in cases.iter_mut() {
+                    remap_block_calls(&mut case.body, map);
+                }
+                remap_block_calls(default, map);
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                remap_block_calls(body, map);
+                remap_block_calls(continuing, map);
+            }
+            Statement::Call {
+                ref mut function, ..
+            } => *function = map(*function),
+            _ => {}
+        }
+    }
+}
+
+impl HandleRemap {
+    /// Make a function of the merged module refer to the items of the resulting one.
+    fn apply(&self, function: &mut Function) {
+        for argument in function.arguments.iter_mut() {
+            argument.ty = self.ty(argument.ty);
+        }
+        if let Some(ref mut result) = function.result {
+            result.ty = self.ty(result.ty);
+        }
+        for (_, local) in function.local_variables.iter_mut() {
+            local.ty = self.ty(local.ty);
+            local.init = local.init.map(|init| self.constant(init));
+        }
+        for (_, expression) in function.expressions.iter_mut() {
+            match *expression {
+                Expression::Constant(ref mut constant) => *constant = self.constant(*constant),
+                Expression::Compose { ref mut ty, .. }
+                | Expression::PointerCast { ref mut ty, .. } => *ty = self.ty(*ty),
+                Expression::ImageSample {
+                    offset: Some(ref mut offset),
+                    ..
+                } => *offset = self.constant(*offset),
+                Expression::GlobalVariable(ref mut var) => *var = self.global_variable(*var),
+                _ => {}
+            }
+        }
+        remap_calls(function, &|handle| self.function(handle));
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn merge_modules() {
+    let library = "
+        [[block]]
+        struct Globals {
+            light: vec4<f32>;
+        };
+
+        [[group(0), binding(0)]] var<uniform> globals: Globals;
+
+        fn shade(normal: vec4<f32>) -> vec4<f32> {
+            return globals.light * max(dot(normal, globals.light), 0.0);
+        }
+    ";
+    let material = "
+        [[block]]
+        struct Globals {
+            light: vec4<f32>;
+        };
+
+        [[group(0), binding(0)]] var<uniform> u_globals: Globals;
+        [[group(1), binding(0)]] var<uniform> u_tint: Globals;
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] normal: vec4<f32>) -> [[location(0)]] vec4<f32> {
+            return normal * u_globals.light * u_tint.light;
+        }
+    ";
+    let mut module = crate::front::wgsl::parse_str(library).unwrap();
+    let type_count = module.types.len();
+    let other = crate::front::wgsl::parse_str(material).unwrap();
+    let tint = other
+        .global_variables
+        .fetch_if(|var| var.name.as_deref() == Some("u_tint"))
+        .unwrap();
+    let remap = module.merge(other).unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // The shared resource and its type are only there once
+    assert_eq!(module.types.len(), type_count);
+    let names: Vec<_> = module
+        .global_variables
+        .iter()
+        .map(|(_, var)| var.name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("globals"), Some("u_tint")]);
+    assert_eq!(
+        module.global_variables[remap.global_variable(tint)]
+            .name
+            .as_deref(),
+        Some("u_tint")
+    );
+    assert_eq!(module.entry_points[remap.entry_point(0)].name, "main");
+
+    let other = crate::front::wgsl::parse_str(material).unwrap();
+    assert_eq!(
+        module.merge(other).unwrap_err(),
+        MergeError::EntryPointCollision {
+            name: "main".to_string(),
+            stage: ShaderStage::Fragment,
+        }
+    );
+    let mut other = crate::front::wgsl::parse_str(library).unwrap();
+    other.global_variables.get_mut(Handle::from_usize(0)).class = crate::StorageClass::Storage;
+    match module.merge(other) {
+        Err(MergeError::BindingCollision { existing, .. }) => assert_eq!(existing.index(), 0),
+        other => panic!("Unexpected merge result {:?}", other.map(|_| ())),
+    }
+}
+
+#[cfg(all(feature = "glsl-in", feature = "wgsl-in"))]
+#[test]
+fn merge_declarations() {
+    let library = "
+        fn shade(normal: vec4<f32>) -> vec4<f32> {
+            return normal * 0.5;
+        }
+    ";
+    let shader = "
+        #version 450
+        layout(location = 0) in vec4 normal;
+        layout(location = 0) out vec4 color;
+
+        vec4 shade(vec4 normal);
+
+        void main() {
+            color = shade(normal);
+        }
+    ";
+    let parse_shader = || {
+        let options = crate::front::glsl::Options::from_stage(ShaderStage::Fragment);
+        crate::front::glsl::parse_str(shader, &options).unwrap()
+    };
+
+    let mut module = parse_shader();
+    let main = module
+        .functions
+        .fetch_if(|function| function.name.as_deref() == Some("main"))
+        .unwrap();
+    let remap = module
+        .merge(crate::front::wgsl::parse_str(library).unwrap())
+        .unwrap();
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // The declaration is gone, and the definition comes before its caller
+    let names: Vec<_> = module
+        .functions
+        .iter()
+        .map(|(_, function)| function.name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("shade"), Some("main")]);
+    assert_eq!(remap.function(Handle::from_usize(0)).index(), 0);
+    assert_eq!(remap.existing_function(main).index(), 1);
+
+    let mut module = parse_shader();
+    let other = crate::front::wgsl::parse_str("fn shade(normal: vec4<f32>) {}").unwrap();
+    assert_eq!(
+        module.merge(other).unwrap_err(),
+        MergeError::UnresolvedFunction {
+            name: "shade".to_string()
+        }
+    );
+
+    let mut module = crate::front::wgsl::parse_str(library).unwrap();
+    module
+        .merge(crate::front::wgsl::parse_str(library).unwrap())
+        .unwrap();
+    assert_eq!(
+        module.merge(parse_shader()).unwrap_err(),
+        MergeError::AmbiguousFunction {
+            name: "shade".to_string()
+        }
+    );
+}
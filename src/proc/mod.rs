@@ -1,15 +1,33 @@
 //! Module processing functionality.
 
+mod branches;
+mod coordinates;
+mod hash;
+mod hoister;
 mod index;
+mod interface;
 mod interpolator;
 mod layouter;
+mod lookup;
+mod merge;
 mod namer;
+mod promotion;
+mod simplify;
+mod srgb;
 mod terminator;
 mod typifier;
+mod unroll;
+mod zero;
 
+pub(crate) use branches::terminates;
+pub use coordinates::CoordinateSpaceAdjustment;
 pub use index::IndexableLength;
+pub use interface::InterfacePruning;
 pub use layouter::{Alignment, InvalidBaseType, Layouter, TypeLayout};
-pub use namer::{EntryPointIndex, NameKey, Namer};
+pub use merge::{HandleRemap, MergeError};
+pub use namer::{renamed_items, EntryPointIndex, NameKey, Namer};
+pub use promotion::PromotionError;
+pub use srgb::SrgbConversion;
 pub use terminator::ensure_block_returns;
 pub use typifier::{ResolveContext, ResolveError, TypeResolution};
 
@@ -73,6 +91,8 @@ impl super::ScalarValue {
 }
 
 pub const POINTER_SPAN: u32 = 4;
+/// Size of a pointer in the `PhysicalStorageBuffer` class, which is a 64-bit address.
+pub const PHYSICAL_POINTER_SPAN: u32 = 8;
 
 impl super::TypeInner {
     pub fn scalar_kind(&self) -> Option<super::ScalarKind> {
@@ -98,6 +118,14 @@ impl super::TypeInner {
                 rows,
                 width,
             } => (columns as u8 * rows as u8 * width) as u32,
+            Self::Pointer {
+                class: super::StorageClass::PhysicalStorageBuffer,
+                ..
+            }
+            | Self::ValuePointer {
+                class: super::StorageClass::PhysicalStorageBuffer,
+                ..
+            } => PHYSICAL_POINTER_SPAN,
             Self::Pointer { .. } | Self::ValuePointer { .. } => POINTER_SPAN,
             Self::Array {
                 base: _,
@@ -115,7 +143,7 @@ impl super::TypeInner {
                 count * stride
             }
             Self::Struct { span, .. } => span,
-            Self::Image { .. } | Self::Sampler { .. } => 0,
+            Self::Image { .. } | Self::Sampler { .. } | Self::BindingArray { .. } => 0,
         }
     }
 }
@@ -178,6 +206,18 @@ impl super::MathFunction {
             // bits
             Self::CountOneBits => 1,
             Self::ReverseBits => 1,
+            // data packing
+            Self::Pack4x8snorm => 1,
+            Self::Pack4x8unorm => 1,
+            Self::Pack2x16snorm => 1,
+            Self::Pack2x16unorm => 1,
+            Self::Pack2x16float => 1,
+            // data unpacking
+            Self::Unpack4x8snorm => 1,
+            Self::Unpack4x8unorm => 1,
+            Self::Unpack2x16snorm => 1,
+            Self::Unpack2x16unorm => 1,
+            Self::Unpack2x16float => 1,
         }
     }
 }
@@ -3,7 +3,7 @@ use std::collections::hash_map::Entry;
 
 pub type EntryPointIndex = u16;
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum NameKey {
     Constant(Handle<crate::Constant>),
     GlobalVariable(Handle<crate::GlobalVariable>),
@@ -17,6 +17,36 @@ pub enum NameKey {
     EntryPointArgument(EntryPointIndex, u32),
 }
 
+impl NameKey {
+    /// Returns the name given to this item in the IR, if any.
+    pub fn ir_name<'a>(&self, module: &'a crate::Module) -> Option<&'a str> {
+        let name = match *self {
+            NameKey::Constant(handle) => &module.constants[handle].name,
+            NameKey::GlobalVariable(handle) => &module.global_variables[handle].name,
+            NameKey::Type(handle) => &module.types[handle].name,
+            NameKey::StructMember(handle, index) => match module.types[handle].inner {
+                crate::TypeInner::Struct { ref members, .. } => &members[index as usize].name,
+                _ => return None,
+            },
+            NameKey::Function(handle) => &module.functions[handle].name,
+            NameKey::FunctionArgument(handle, index) => {
+                &module.functions[handle].arguments[index as usize].name
+            }
+            NameKey::FunctionLocal(fun, handle) => {
+                &module.functions[fun].local_variables[handle].name
+            }
+            NameKey::EntryPoint(index) => return Some(&module.entry_points[index as usize].name),
+            NameKey::EntryPointLocal(index, handle) => {
+                &module.entry_points[index as usize].function.local_variables[handle].name
+            }
+            NameKey::EntryPointArgument(index, arg) => {
+                &module.entry_points[index as usize].function.arguments[arg as usize].name
+            }
+        };
+        name.as_deref()
+    }
+}
+
 /// This processor assigns names to all the things in a module
 /// that may need identifiers in a textual backend.
 #[derive(Default)]
@@ -34,7 +64,14 @@ impl Namer {
             .chars()
             .skip_while(|c| c.is_numeric())
             .filter(|&c| c.is_ascii_alphanumeric() || c == '_')
-            .collect::<String>();
+            .fold(String::new(), |mut base, c| {
+                // identifiers containing double underscores are reserved
+                // in most of the shading languages, so collapse them
+                if c != '_' || !base.ends_with('_') {
+                    base.push(c);
+                }
+                base
+            });
         // close the name by '_' if the re is a number, so that
         // we can have our own number!
         match base.chars().next_back() {
@@ -95,6 +132,20 @@ impl Namer {
         }
     }
 
+    /// Returns `true` if `name` can be emitted as is: it's a plain identifier
+    /// that isn't reserved and doesn't contain double underscores.
+    fn is_verbatim(&self, name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_numeric())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !name.contains("__")
+            && !self.keywords.contains(name)
+            && !self
+                .reserved_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+
     fn call_or(&mut self, label: &Option<String>, fallback: &str) -> String {
         self.call(match *label {
             Some(ref name) => name,
@@ -134,8 +185,25 @@ impl Namer {
             if let crate::TypeInner::Struct { ref members, .. } = ty.inner {
                 // struct members have their own namespace, because access is always prefixed
                 self.namespace(|namer| {
+                    // member names are visible to reflection, so keep them
+                    // verbatim whenever they don't collide
+                    let mut used = FastHashSet::default();
                     for (index, member) in members.iter().enumerate() {
-                        let name = namer.call_or(&member.name, "member");
+                        let name = match member.name {
+                            Some(ref name) if namer.is_verbatim(name) && !used.contains(name) => {
+                                namer
+                                    .unique
+                                    .insert((name.clone(), namer.namespace_index), 0);
+                                name.clone()
+                            }
+                            ref label => loop {
+                                let name = namer.call_or(label, "member");
+                                if !used.contains(&name) {
+                                    break name;
+                                }
+                            },
+                        };
+                        used.insert(name.clone());
                         output.insert(NameKey::StructMember(ty_handle, index as u32), name);
                     }
                 })
@@ -228,3 +296,72 @@ impl Namer {
         }
     }
 }
+
+/// Collects the items whose IR name couldn't be used verbatim, mapped to the
+/// name they were emitted with.
+pub fn renamed_items(
+    module: &crate::Module,
+    names: &FastHashMap<NameKey, String>,
+) -> FastHashMap<NameKey, String> {
+    names
+        .iter()
+        .filter(|&(key, name)| match key.ir_name(module) {
+            Some(ir_name) => ir_name != name,
+            None => false,
+        })
+        .map(|(&key, name)| (key, name.clone()))
+        .collect()
+}
+
+#[test]
+fn sanitize_and_number() {
+    let mut namer = Namer::default();
+    let mut output = FastHashMap::default();
+    namer.reset(
+        &crate::Module::default(),
+        &["sample"],
+        &["gl_"],
+        &mut output,
+    );
+
+    assert_eq!(namer.call("x"), "x");
+    assert_eq!(namer.call("x"), "x1");
+    // names ending with a digit can't collide with the numbered ones
+    assert_eq!(namer.call("x1"), "x1_");
+    assert_eq!(namer.call("sample"), "sample1");
+    assert_eq!(namer.call("sample"), "sample2");
+    assert_eq!(namer.call("a__b"), "a_b");
+    assert_eq!(namer.call("2d-offset"), "doffset");
+    assert_eq!(namer.call("gl_Position"), "gen_gl_Position");
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn stable_names() {
+    let module = crate::front::wgsl::parse_str(
+        "
+        struct Data { value: f32; other: f32; };
+        var<private> data: Data;
+        fn helper(value: f32) -> f32 { var data: f32 = value; return data; }
+        ",
+    )
+    .unwrap();
+
+    let names = || {
+        let mut output = FastHashMap::default();
+        Namer::default().reset(&module, &[], &[], &mut output);
+        output
+    };
+    let first = names();
+    assert_eq!(first, names());
+
+    let fun = module.functions.find_by_name("helper").unwrap();
+    let global = module.global_variables.find_by_name("data").unwrap();
+    // struct members have their own namespace, the rest share one
+    let ty = module.types.find_by_name("Data").unwrap();
+    assert_eq!(first[&NameKey::StructMember(ty, 0)], "value");
+    assert_eq!(first[&NameKey::FunctionArgument(fun, 0)], "value");
+    let (local, _) = module.functions[fun].local_variables.iter().next().unwrap();
+    assert_eq!(first[&NameKey::FunctionLocal(fun, local)], "data");
+    assert_eq!(first[&NameKey::GlobalVariable(global)], "data1");
+}
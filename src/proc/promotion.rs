@@ -0,0 +1,416 @@
+use super::interface::{remap_block, visit_operands, visit_operands_mut, visit_statement_operands};
+use crate::{
+    arena::{Handle, Range, UniqueArena},
+    Constant, ConstantInner, Expression, FastHashMap, FastHashSet, Function, GlobalVariable,
+    StorageClass, Type, TypeInner,
+};
+
+/// Errors returned by [`Module::promote_uniforms`].
+///
+/// [`Module::promote_uniforms`]: crate::Module::promote_uniforms
+#[derive(Clone, Debug, thiserror::Error, PartialEq)]
+pub enum PromotionError {
+    #[error("global variable {0:?} is not a uniform buffer")]
+    NotUniform(Handle<GlobalVariable>),
+    #[error("constant {constant:?} doesn't match the type of global variable {global:?}")]
+    TypeMismatch {
+        global: Handle<GlobalVariable>,
+        constant: Handle<Constant>,
+    },
+    #[error("an array or matrix of global variable {0:?} is indexed dynamically")]
+    DynamicIndex(Handle<GlobalVariable>),
+    #[error("a pointer into global variable {0:?} is used for something else than a load")]
+    PointerUse(Handle<GlobalVariable>),
+}
+
+impl crate::Module {
+    /// Replace uniform buffers by constants holding their contents.
+    ///
+    /// `values` maps each uniform buffer to a constant of the same type, known
+    /// at translation time. This suits GL targets where the parameters of a
+    /// material are baked when building its shaders: every load from a buffer
+    /// becomes a use of its constant, and the buffer is removed from the
+    /// module, along with its binding. The global variables that come after a
+    /// removed one get new handles.
+    ///
+    /// Constants can't be indexed dynamically the way buffers can, so an error
+    /// is returned if an array or matrix of a buffer is, or if a pointer into a
+    /// buffer is passed to a function. The module is left untouched in that case.
+    pub fn promote_uniforms(
+        &mut self,
+        values: &FastHashMap<Handle<GlobalVariable>, Handle<Constant>>,
+    ) -> Result<(), PromotionError> {
+        for (&global, &constant) in values.iter() {
+            let var = &self.global_variables[global];
+            if var.class != StorageClass::Uniform {
+                return Err(PromotionError::NotUniform(global));
+            }
+            let matches = match self.constants[constant].inner {
+                ConstantInner::Scalar { width, ref value } => {
+                    self.types[var.ty].inner
+                        == TypeInner::Scalar {
+                            kind: value.scalar_kind(),
+                            width,
+                        }
+                }
+                ConstantInner::Composite { ty, .. } => ty == var.ty,
+            };
+            if !matches {
+                return Err(PromotionError::TypeMismatch { global, constant });
+            }
+        }
+
+        // Check every function before changing any of them
+        let loads = self
+            .functions
+            .iter()
+            .map(|(_, function)| function)
+            .chain(self.entry_points.iter().map(|ep| &ep.function))
+            .map(|function| find_loads(function, self, values))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let old_globals = std::mem::take(&mut self.global_variables);
+        let mut global_map = Vec::with_capacity(old_globals.len());
+        for (index, var) in old_globals.into_inner().into_iter().enumerate() {
+            global_map.push(if values.contains_key(&Handle::from_usize(index)) {
+                None
+            } else {
+                Some(self.global_variables.append(var))
+            });
+        }
+
+        let functions = self
+            .functions
+            .iter_mut()
+            .map(|(_, function)| function)
+            .chain(self.entry_points.iter_mut().map(|ep| &mut ep.function));
+        for (function, loads) in functions.zip(loads) {
+            promote_function(function, &loads, values, &global_map);
+        }
+        Ok(())
+    }
+}
+
+/// A pointer into a promoted buffer, and the type it points to, unless that
+/// type is a vector or scalar inside a matrix or vector.
+type BufferPointer = (Handle<GlobalVariable>, Option<Handle<Type>>);
+
+/// Find the loads from the uniform buffers of `values` in `function`, and
+/// check that they are the only uses of pointers into those buffers.
+fn find_loads(
+    function: &Function,
+    module: &crate::Module,
+    values: &FastHashMap<Handle<GlobalVariable>, Handle<Constant>>,
+) -> Result<FastHashSet<Handle<Expression>>, PromotionError> {
+    let expressions = &function.expressions;
+    // The buffer each expression points into, if any
+    let mut pointers: Vec<Option<BufferPointer>> = Vec::with_capacity(expressions.len());
+    let mut loads = FastHashSet::default();
+    for (handle, expression) in expressions.iter() {
+        let pointer = match *expression {
+            Expression::GlobalVariable(global) if values.contains_key(&global) => {
+                Some((global, Some(module.global_variables[global].ty)))
+            }
+            Expression::Access { base, index } => match pointers[base.index()] {
+                Some((global, ty)) => {
+                    if let Some(ty) = ty {
+                        match module.types[ty].inner {
+                            TypeInner::Array { .. } | TypeInner::Matrix { .. }
+                                if expressions[index].is_dynamic_index(module) =>
+                            {
+                                return Err(PromotionError::DynamicIndex(global));
+                            }
+                            _ => {}
+                        }
+                    }
+                    Some((global, element_type(&module.types, ty, None)))
+                }
+                None => None,
+            },
+            Expression::AccessIndex { base, index } => pointers[base.index()]
+                .map(|(global, ty)| (global, element_type(&module.types, ty, Some(index)))),
+            Expression::Load { pointer } if pointers[pointer.index()].is_some() => {
+                loads.insert(handle);
+                None
+            }
+            ref other => {
+                let mut used = None;
+                visit_operands(other, &mut |operand| {
+                    used = used.or(pointers[operand.index()]);
+                });
+                if let Some((global, _)) = used {
+                    return Err(PromotionError::PointerUse(global));
+                }
+                None
+            }
+        };
+        pointers.push(pointer);
+    }
+
+    let mut used = None;
+    visit_statement_operands(&function.body, &mut |operand| {
+        used = used.or(pointers[operand.index()]);
+    });
+    match used {
+        Some((global, _)) => Err(PromotionError::PointerUse(global)),
+        None => Ok(loads),
+    }
+}
+
+/// Return the type of the member `index` of `ty`, or of its elements.
+fn element_type(
+    types: &UniqueArena<Type>,
+    ty: Option<Handle<Type>>,
+    index: Option<u32>,
+) -> Option<Handle<Type>> {
+    match types[ty?].inner {
+        TypeInner::Struct { ref members, .. } => Some(members.get(index? as usize)?.ty),
+        TypeInner::Array { base, .. } => Some(base),
+        _ => None,
+    }
+}
+
+/// Replace the promoted buffers of `function` by their constants, and
+/// the `loads` from them by the values they point to.
+///
+/// The pointers into the buffers are now values, so the loads are removed.
+fn promote_function(
+    function: &mut Function,
+    loads: &FastHashSet<Handle<Expression>>,
+    values: &FastHashMap<Handle<GlobalVariable>, Handle<Constant>>,
+    global_map: &[Option<Handle<GlobalVariable>>],
+) {
+    let old_expressions = std::mem::take(&mut function.expressions);
+    let mut handle_map = Vec::with_capacity(old_expressions.len());
+    let mut kept = Vec::with_capacity(old_expressions.len());
+    for (index, mut expression) in old_expressions.into_inner().into_iter().enumerate() {
+        match expression {
+            Expression::Load { pointer } if loads.contains(&Handle::from_usize(index)) => {
+                handle_map.push(handle_map[pointer.index()]);
+                kept.push(false);
+                continue;
+            }
+            Expression::GlobalVariable(global) => {
+                expression = match global_map[global.index()] {
+                    Some(new) => Expression::GlobalVariable(new),
+                    None => Expression::Constant(values[&global]),
+                };
+            }
+            _ => visit_operands_mut(&mut expression, &mut |operand| {
+                *operand = handle_map[operand.index()];
+            }),
+        }
+        handle_map.push(function.expressions.append(expression));
+        kept.push(true);
+    }
+
+    remap_block(
+        &mut function.body,
+        &|handle| *handle = handle_map[handle.index()],
+        &|range| {
+            let mut kept = range
+                .clone()
+                .filter(|handle| kept[handle.index()])
+                .map(|handle| handle_map[handle.index()]);
+            let first = kept.next()?;
+            let last = kept.last().unwrap_or(first);
+            Some(Range::new_from_bounds(first, last))
+        },
+    );
+    // A load of a whole buffer is now its constant, which can't be named
+    let expressions = &function.expressions;
+    function.named_expressions = function
+        .named_expressions
+        .drain()
+        .map(|(handle, name)| (handle_map[handle.index()], name))
+        .filter(|&(handle, _)| !expressions[handle].needs_pre_emit())
+        .collect();
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn promote_uniforms() {
+    const GLOBALS: &str = "
+        [[block]]
+        struct Material {
+            tint: vec4<f32>;
+            weights: array<f32, 2>;
+        };
+
+        [[block]]
+        struct Globals {
+            offset: f32;
+        };
+
+        [[group(0), binding(0)]] var<uniform> material: Material;
+        [[group(0), binding(1)]] var<uniform> globals: Globals;
+    ";
+    const WEIGHT: &str = "
+        fn weight(index: i32) -> f32 {
+            return material.weights[index];
+        }
+    ";
+    const MAIN: &str = "
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+            let m = material;
+            return m.tint * material.tint.x * material.weights[1] + vec4<f32>(globals.offset);
+        }
+    ";
+
+    /// Returns the material buffer, a constant of its tint, and a constant
+    /// of the whole material.
+    fn material_values(
+        module: &mut crate::Module,
+    ) -> (Handle<GlobalVariable>, Handle<Constant>, Handle<Constant>) {
+        let material = module
+            .global_variables
+            .fetch_if(|var| var.name.as_deref() == Some("material"))
+            .unwrap();
+        let material_ty = module.global_variables[material].ty;
+
+        let mut scalar = |value| {
+            module.constants.append(Constant {
+                name: None,
+                specialization: None,
+                inner: ConstantInner::Scalar {
+                    width: 4,
+                    value: crate::ScalarValue::Float(value),
+                },
+            })
+        };
+        let (one, half) = (scalar(1.0), scalar(0.5));
+        let (tint_ty, weights_ty) = match module.types[material_ty].inner {
+            TypeInner::Struct { ref members, .. } => (members[0].ty, members[1].ty),
+            ref other => panic!("Unexpected material type {:?}", other),
+        };
+        let tint = module.constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: tint_ty,
+                components: vec![one, half, half, one],
+            },
+        });
+        let weights = module.constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: weights_ty,
+                components: vec![half, one],
+            },
+        });
+        let material_value = module.constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: material_ty,
+                components: vec![tint, weights],
+            },
+        });
+        (material, tint, material_value)
+    }
+
+    let mut module = crate::front::wgsl::parse_str(&[GLOBALS, WEIGHT, MAIN].concat()).unwrap();
+    let (material, tint, material_value) = material_values(&mut module);
+    let mut values = FastHashMap::default();
+    values.insert(material, tint);
+    assert_eq!(
+        module.promote_uniforms(&values),
+        Err(PromotionError::TypeMismatch {
+            global: material,
+            constant: tint,
+        })
+    );
+    values.insert(material, material_value);
+    // The weights are indexed dynamically in `weight`
+    assert_eq!(
+        module.promote_uniforms(&values),
+        Err(PromotionError::DynamicIndex(material))
+    );
+
+    let mut module = crate::front::wgsl::parse_str(&[GLOBALS, MAIN].concat()).unwrap();
+    let (material, _, material_value) = material_values(&mut module);
+    let values = std::iter::once((material, material_value)).collect();
+    module.promote_uniforms(&values).unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let names: Vec<_> = module
+        .global_variables
+        .iter()
+        .map(|(_, var)| var.name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("globals")]);
+    let function = &module.entry_points[0].function;
+    let loads = function
+        .expressions
+        .iter()
+        .filter(|&(_, expression)| match *expression {
+            Expression::Load { .. } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(loads, 1);
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn promote_uniforms_with_lod_query() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[block]]
+        struct Globals {
+            scale: f32;
+        };
+
+        [[group(0), binding(0)]] var<uniform> globals: Globals;
+        [[group(0), binding(1)]] var t: texture_2d<f32>;
+        [[group(0), binding(2)]] var s: sampler;
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] uv: vec2<f32>) -> [[location(0)]] vec4<f32> {
+            let lod = textureSample(t, s, uv * globals.scale).xy;
+            return vec4<f32>(lod, 0.0, 1.0);
+        }
+        ",
+    )
+    .unwrap();
+    super::interface::sample_to_lod_queries(&mut module.entry_points[0].function);
+    let globals = module
+        .global_variables
+        .fetch_if(|var| var.name.as_deref() == Some("globals"))
+        .unwrap();
+    let globals_ty = module.global_variables[globals].ty;
+    let scale = module.constants.append(Constant {
+        name: None,
+        specialization: None,
+        inner: ConstantInner::Scalar {
+            width: 4,
+            value: crate::ScalarValue::Float(2.0),
+        },
+    });
+    let value = module.constants.append(Constant {
+        name: None,
+        specialization: None,
+        inner: ConstantInner::Composite {
+            ty: globals_ty,
+            components: vec![scale],
+        },
+    });
+    let values = std::iter::once((globals, value)).collect();
+    module.promote_uniforms(&values).unwrap();
+
+    // The operands of the query follow the removed load and global
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
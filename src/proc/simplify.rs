@@ -0,0 +1,429 @@
+use super::interface::{
+    compact_expressions, remap_block, visit_operands, visit_operands_mut, visit_statement_operands,
+};
+use crate::{
+    arena::{Arena, Handle},
+    BinaryOperator, Block, Constant, ConstantInner, Expression, FastHashMap, FastHashSet, Function,
+    ScalarValue, Statement,
+};
+
+impl crate::Module {
+    /// Simplify the expressions of every function, to clean up what the
+    /// front ends produce before it reaches the back ends.
+    ///
+    /// The following expressions are replaced by an equivalent one:
+    /// - the identities `x * 1`, `1 * x`, `x / 1`, `x + 0`, `0 + x`, `x - 0`,
+    ///   where the constant is a scalar or a splat of one, replaced by `x`. This doesn't keep
+    ///   the sign of `-0.0 + 0.0`, like the fast math of shader compilers.
+    /// - double negations and double logical or bitwise `Not`s.
+    /// - a `Compose` of all the components of a vector of the same type, in
+    ///   order, which is replaced by the vector.
+    /// - the pure expressions identical to one that's already evaluated in
+    ///   the same block or an enclosing one, like repeated access chains.
+    ///
+    /// The replaced expressions are removed, along with the pure expressions
+    /// that are no longer used. Their names are given to their replacements
+    /// if they have none.
+    pub fn simplify_expressions(&mut self) {
+        for index in 0..self.functions.len() {
+            let handle = Handle::from_usize(index);
+            // take the function out, so that the others can be used to resolve calls
+            let mut function = std::mem::take(self.functions.get_mut(handle));
+            simplify_function(&mut function, self);
+            *self.functions.get_mut(handle) = function;
+        }
+        for index in 0..self.entry_points.len() {
+            let mut function = std::mem::take(&mut self.entry_points[index].function);
+            simplify_function(&mut function, self);
+            self.entry_points[index].function = function;
+        }
+    }
+}
+
+fn simplify_function(function: &mut Function, module: &crate::Module) {
+    let mut typifier = crate::front::Typifier::new();
+    let resolved = match function.expressions.iter().last() {
+        Some((last, _)) => {
+            let resolve_ctx = super::ResolveContext {
+                constants: &module.constants,
+                types: &module.types,
+                global_vars: &module.global_variables,
+                local_vars: &function.local_variables,
+                functions: &module.functions,
+                arguments: &function.arguments,
+            };
+            typifier
+                .grow(last, &function.expressions, &resolve_ctx)
+                .is_ok()
+        }
+        None => return,
+    };
+    if !resolved {
+        log::warn!("Unable to resolve the expressions of {:?}", function.name);
+        return;
+    }
+
+    let mut simplifier = Simplifier {
+        module,
+        typifier: &typifier,
+        expressions: &mut function.expressions,
+        map: Vec::new(),
+        scopes: vec![FastHashMap::default()],
+    };
+    simplifier.map = simplifier.expressions.iter().map(|(h, _)| h).collect();
+    // the expressions that aren't emitted are usable everywhere
+    for index in 0..simplifier.map.len() {
+        let handle = Handle::from_usize(index);
+        if simplifier.expressions[handle].needs_pre_emit() {
+            simplifier.deduplicate(handle);
+        }
+    }
+    simplifier.visit_block(&function.body);
+
+    // rewrite the expressions that were never emitted, like call results
+    let map = simplifier.map;
+    for (_, expression) in function.expressions.iter_mut() {
+        visit_operands_mut(expression, &mut |operand| *operand = map[operand.index()]);
+    }
+    remap_block(
+        &mut function.body,
+        &|handle| *handle = map[handle.index()],
+        &|range| Some(range.clone()),
+    );
+
+    let names = function.named_expressions.drain().collect::<Vec<_>>();
+    for (handle, name) in names {
+        let target = map[handle.index()];
+        if !function.expressions[target].needs_pre_emit() {
+            function.named_expressions.entry(target).or_insert(name);
+        }
+    }
+
+    // Remove the replaced expressions, and the pure ones left unused
+    let mut live = FastHashSet::default();
+    let mut pending = Vec::new();
+    visit_statement_operands(&function.body, &mut |operand| pending.push(operand));
+    for (handle, expression) in function.expressions.iter() {
+        let is_root = !is_pure(expression)
+            || expression.needs_pre_emit()
+            || function.named_expressions.contains_key(&handle);
+        if is_root && map[handle.index()] == handle {
+            pending.push(handle);
+        }
+    }
+    while let Some(handle) = pending.pop() {
+        if live.insert(handle) {
+            visit_operands(&function.expressions[handle], &mut |operand| {
+                pending.push(operand)
+            });
+        }
+    }
+    let removed = function
+        .expressions
+        .iter()
+        .map(|(handle, _)| handle)
+        .filter(|handle| !live.contains(handle))
+        .collect::<FastHashSet<_>>();
+    if !removed.is_empty() {
+        compact_expressions(function, &removed);
+    }
+}
+
+struct Simplifier<'a> {
+    module: &'a crate::Module,
+    typifier: &'a crate::front::Typifier,
+    expressions: &'a mut Arena<Expression>,
+    /// The expression replacing each one, which is itself if it's kept.
+    map: Vec<Handle<Expression>>,
+    /// The pure expressions evaluated in each enclosing block.
+    scopes: Vec<FastHashMap<Expression, Handle<Expression>>>,
+}
+
+impl Simplifier<'_> {
+    fn visit_block(&mut self, block: &Block) {
+        for statement in block.iter() {
+            match *statement {
+                Statement::Emit(ref range) => {
+                    for handle in range.clone() {
+                        self.simplify(handle);
+                    }
+                }
+                Statement::Block(ref inner) => self.visit_nested(inner),
+                Statement::If {
+                    ref accept,
+                    ref reject,
+                    ..
+                } => {
+                    self.visit_nested(accept);
+                    self.visit_nested(reject);
+                }
+                Statement::Switch {
+                    ref cases,
+                    ref default,
+                    ..
+                } => {
+                    for case in cases.iter() {
+                        self.visit_nested(&case.body);
+                    }
+                    self.visit_nested(default);
+                }
+                Statement::Loop {
+                    ref body,
+                    ref continuing,
+                } => {
+                    self.visit_nested(body);
+                    self.visit_nested(continuing);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_nested(&mut self, block: &Block) {
+        self.scopes.push(FastHashMap::default());
+        self.visit_block(block);
+        self.scopes.pop();
+    }
+
+    fn simplify(&mut self, handle: Handle<Expression>) {
+        let map = &self.map;
+        visit_operands_mut(self.expressions.get_mut(handle), &mut |operand| {
+            *operand = map[operand.index()]
+        });
+        match self.identity(handle) {
+            Some(replacement) => self.map[handle.index()] = replacement,
+            None => self.deduplicate(handle),
+        }
+    }
+
+    /// Return the operand that `handle` evaluates to, if it's an identity.
+    fn identity(&self, handle: Handle<Expression>) -> Option<Handle<Expression>> {
+        match self.expressions[handle] {
+            Expression::Binary { op, left, right } => {
+                let (left_value, right_value) = (self.scalar(left), self.scalar(right));
+                let operand = match op {
+                    BinaryOperator::Multiply if is_one(right_value) => left,
+                    BinaryOperator::Multiply if is_one(left_value) => right,
+                    BinaryOperator::Divide if is_one(right_value) => left,
+                    BinaryOperator::Add if is_zero(right_value) => left,
+                    BinaryOperator::Add if is_zero(left_value) => right,
+                    BinaryOperator::Subtract if is_zero(right_value) => left,
+                    _ => return None,
+                };
+                // a scalar times a vector of ones is a vector
+                let types = &self.module.types;
+                if self.typifier.get(operand, types) == self.typifier.get(handle, types) {
+                    Some(operand)
+                } else {
+                    None
+                }
+            }
+            Expression::Unary { op, expr } => match self.expressions[expr] {
+                Expression::Unary {
+                    op: inner_op,
+                    expr: inner,
+                } if inner_op == op => Some(inner),
+                _ => None,
+            },
+            Expression::Compose { ty, ref components } => {
+                let mut vector = None;
+                for (index, &component) in components.iter().enumerate() {
+                    match self.expressions[component] {
+                        Expression::AccessIndex { base, index: i }
+                            if i as usize == index && vector.unwrap_or(base) == base =>
+                        {
+                            vector = Some(base)
+                        }
+                        _ => return None,
+                    }
+                }
+                let vector = vector?;
+                let ty_inner = &self.module.types[ty].inner;
+                match *ty_inner {
+                    crate::TypeInner::Vector { .. }
+                        if self.typifier.get(vector, &self.module.types) == ty_inner =>
+                    {
+                        Some(vector)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace `handle` by an identical expression in scope, or make it
+    /// available to the following ones.
+    fn deduplicate(&mut self, handle: Handle<Expression>) {
+        let expression = &self.expressions[handle];
+        if !is_pure(expression) {
+            return;
+        }
+        let existing = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(expression).cloned());
+        match existing {
+            Some(existing) => self.map[handle.index()] = existing,
+            None => {
+                let expression = expression.clone();
+                self.scopes.last_mut().unwrap().insert(expression, handle);
+            }
+        }
+    }
+
+    /// Return the value of `handle` if it's a scalar constant, or a vector
+    /// of that constant.
+    fn scalar(&self, handle: Handle<Expression>) -> Option<ScalarValue> {
+        match self.expressions[handle] {
+            Expression::Splat { value, .. } => self.scalar(value),
+            Expression::Constant(constant) => match self.module.constants[constant] {
+                Constant {
+                    specialization: None,
+                    inner: ConstantInner::Scalar { value, .. },
+                    ..
+                } => Some(value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn is_one(value: Option<ScalarValue>) -> bool {
+    match value {
+        Some(ScalarValue::Sint(value)) => value == 1,
+        Some(ScalarValue::Uint(value)) => value == 1,
+        Some(ScalarValue::Float(value)) => value == 1.0,
+        _ => false,
+    }
+}
+
+fn is_zero(value: Option<ScalarValue>) -> bool {
+    match value {
+        Some(ScalarValue::Sint(value)) => value == 0,
+        Some(ScalarValue::Uint(value)) => value == 0,
+        Some(ScalarValue::Float(value)) => value == 0.0,
+        _ => false,
+    }
+}
+
+/// Return true if `expression` only depends on its operands, and not on the
+/// memory or the place where it's evaluated.
+fn is_pure(expression: &Expression) -> bool {
+    match *expression {
+        Expression::Access { .. }
+        | Expression::AccessIndex { .. }
+        | Expression::Constant(_)
+        | Expression::Splat { .. }
+        | Expression::Swizzle { .. }
+        | Expression::Compose { .. }
+        | Expression::FunctionArgument(_)
+        | Expression::GlobalVariable(_)
+        | Expression::LocalVariable(_)
+        | Expression::Unary { .. }
+        | Expression::Binary { .. }
+        | Expression::Select { .. }
+        | Expression::Relational { .. }
+        | Expression::Math { .. }
+        | Expression::As { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn simplify_expressions() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        fn f(v: vec4<f32>, i: i32) -> vec4<f32> {
+            let a = v * 1.0 + 0.0;
+            let b = vec4<f32>(a.x, a.y, a.z, a.w);
+            let c = -(-i);
+            let d = b * f32(c);
+            let e = b * f32(c);
+            return d + e;
+        }
+        ",
+    )
+    .unwrap();
+    module.simplify_expressions();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let function = module.functions.iter().next().unwrap().1;
+    let emitted = function
+        .expressions
+        .iter()
+        .filter(|&(_, expression)| !expression.needs_pre_emit())
+        .map(|(_, expression)| expression)
+        .collect::<Vec<_>>();
+    // only `f32(i)`, `v * f32(i)` and the sum are left
+    match emitted[..] {
+        [&Expression::As { .. }, &Expression::Binary {
+            op: BinaryOperator::Multiply,
+            ..
+        }, &Expression::Binary {
+            op: BinaryOperator::Add,
+            left,
+            right,
+        }] => assert_eq!(left, right),
+        ref other => panic!("Unexpected expressions {:?}", other),
+    }
+    // the names of the replaced expressions move to their replacements
+    let mut names = function.named_expressions.values().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["d"]);
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn simplify_lod_query() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]] var t: texture_2d<f32>;
+        [[group(0), binding(1)]] var s: sampler;
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] uv: vec2<f32>) -> [[location(0)]] vec4<f32> {
+            let scaled = uv * 1.0 + 0.0;
+            let lod = textureSample(t, s, scaled).xy;
+            return vec4<f32>(lod, 0.0, 1.0);
+        }
+        ",
+    )
+    .unwrap();
+    super::interface::sample_to_lod_queries(&mut module.entry_points[0].function);
+    module.simplify_expressions();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // the coordinate is replaced by the argument it's equal to
+    let function = &module.entry_points[0].function;
+    let coordinate = function
+        .expressions
+        .iter()
+        .find_map(|(_, expression)| match *expression {
+            Expression::ImageQuery {
+                query: crate::ImageQuery::Lod { coordinate, .. },
+                ..
+            } => Some(coordinate),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        function.expressions[coordinate],
+        Expression::FunctionArgument(0)
+    );
+}
@@ -0,0 +1,457 @@
+use super::interface::{remap_block, rewrite_returns, visit_operands_mut};
+use crate::{
+    arena::{Arena, Handle, Range},
+    BinaryOperator, Binding, Constant, ConstantInner, Expression, FastHashSet, Function,
+    GlobalVariable, ImageClass, MathFunction, ScalarKind, ScalarValue, ShaderStage,
+    SwizzleComponent, Type, TypeInner, VectorSize,
+};
+
+/// Direction of the conversions injected by [`Module::convert_srgb_images`]
+/// and [`Module::convert_srgb_outputs`].
+///
+/// [`Module::convert_srgb_images`]: crate::Module::convert_srgb_images
+/// [`Module::convert_srgb_outputs`]: crate::Module::convert_srgb_outputs
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum SrgbConversion {
+    /// Convert sRGB encoded colors to linear ones.
+    Decode,
+    /// Convert linear colors to sRGB encoded ones.
+    Encode,
+}
+
+/// Constants of the conversion curve, in the order they are used.
+const DECODE_CONSTANTS: [f32; 5] = [0.04045, 12.92, 0.055, 1.055, 2.4];
+const ENCODE_CONSTANTS: [f32; 5] = [0.003_130_8, 12.92, 0.055, 1.055, 1.0 / 2.4];
+
+/// The module level handles shared by the conversions of every function.
+struct Conversion {
+    direction: SrgbConversion,
+    constants: [Handle<Constant>; 5],
+    vec4: Handle<Type>,
+}
+
+impl Conversion {
+    fn new(module: &mut crate::Module, direction: SrgbConversion) -> Self {
+        let values = match direction {
+            SrgbConversion::Decode => DECODE_CONSTANTS,
+            SrgbConversion::Encode => ENCODE_CONSTANTS,
+        };
+        let mut constants = [Handle::from_usize(0); 5];
+        for (handle, &value) in constants.iter_mut().zip(values.iter()) {
+            *handle = module.constants.fetch_or_append(Constant {
+                name: None,
+                specialization: None,
+                inner: ConstantInner::Scalar {
+                    width: 4,
+                    value: ScalarValue::Float(value as f64),
+                },
+            });
+        }
+        let vec4 = module.types.insert(Type {
+            name: None,
+            inner: TypeInner::Vector {
+                size: VectorSize::Quad,
+                kind: ScalarKind::Float,
+                width: 4,
+            },
+        });
+        Conversion {
+            direction,
+            constants,
+            vec4,
+        }
+    }
+
+    /// Append the constant expressions used by [`Conversion::append`].
+    ///
+    /// These must not be covered by an `Emit`.
+    fn append_constants(&self, expressions: &mut Arena<Expression>) -> [Handle<Expression>; 5] {
+        let mut handles = [Handle::from_usize(0); 5];
+        for (handle, &constant) in handles.iter_mut().zip(self.constants.iter()) {
+            *handle = expressions.append(Expression::Constant(constant));
+        }
+        handles
+    }
+
+    /// Append the expressions converting the color channels of `value`,
+    /// which is a `vec4<f32>`, and return the converted color.
+    ///
+    /// The alpha channel is left untouched.
+    fn append(
+        &self,
+        value: Handle<Expression>,
+        constants: &[Handle<Expression>; 5],
+        expressions: &mut Arena<Expression>,
+    ) -> Handle<Expression> {
+        let rgb = expressions.append(Expression::Swizzle {
+            size: VectorSize::Tri,
+            vector: value,
+            pattern: [
+                SwizzleComponent::X,
+                SwizzleComponent::Y,
+                SwizzleComponent::Z,
+                SwizzleComponent::X,
+            ],
+        });
+        let alpha = expressions.append(Expression::AccessIndex {
+            base: value,
+            index: 3,
+        });
+        let mut splats = [rgb; 5];
+        for (splat, &constant) in splats.iter_mut().zip(constants.iter()) {
+            *splat = expressions.append(Expression::Splat {
+                size: VectorSize::Tri,
+                value: constant,
+            });
+        }
+        let [threshold, linear_scale, offset, scale, exponent] = splats;
+
+        let condition = binary(expressions, BinaryOperator::LessEqual, rgb, threshold);
+        let (linear, curved) = match self.direction {
+            // c / 12.92, or ((c + 0.055) / 1.055) ^ 2.4
+            SrgbConversion::Decode => {
+                let linear = binary(expressions, BinaryOperator::Divide, rgb, linear_scale);
+                let shifted = binary(expressions, BinaryOperator::Add, rgb, offset);
+                let scaled = binary(expressions, BinaryOperator::Divide, shifted, scale);
+                (linear, pow(expressions, scaled, exponent))
+            }
+            // c * 12.92, or 1.055 * c ^ (1 / 2.4) - 0.055
+            SrgbConversion::Encode => {
+                let linear = binary(expressions, BinaryOperator::Multiply, rgb, linear_scale);
+                let raised = pow(expressions, rgb, exponent);
+                let scaled = binary(expressions, BinaryOperator::Multiply, raised, scale);
+                let curved = binary(expressions, BinaryOperator::Subtract, scaled, offset);
+                (linear, curved)
+            }
+        };
+        let converted = expressions.append(Expression::Select {
+            condition,
+            accept: linear,
+            reject: curved,
+        });
+        expressions.append(Expression::Compose {
+            ty: self.vec4,
+            components: vec![converted, alpha],
+        })
+    }
+}
+
+fn binary(
+    expressions: &mut Arena<Expression>,
+    op: BinaryOperator,
+    left: Handle<Expression>,
+    right: Handle<Expression>,
+) -> Handle<Expression> {
+    expressions.append(Expression::Binary { op, left, right })
+}
+
+fn pow(
+    expressions: &mut Arena<Expression>,
+    base: Handle<Expression>,
+    exponent: Handle<Expression>,
+) -> Handle<Expression> {
+    expressions.append(Expression::Math {
+        fun: MathFunction::Pow,
+        arg: base,
+        arg1: Some(exponent),
+        arg2: None,
+    })
+}
+
+const VEC4: TypeInner = TypeInner::Vector {
+    size: VectorSize::Quad,
+    kind: ScalarKind::Float,
+    width: 4,
+};
+
+impl crate::Module {
+    /// Convert the texels read from the given images between sRGB and linear.
+    ///
+    /// This emulates sRGB textures on targets that lack them, like GL ES 2:
+    /// the texture is bound with a linear format, and the result of every
+    /// `ImageSample` and `ImageLoad` of it goes through the conversion math,
+    /// usually [`Decode`].
+    ///
+    /// Only float sampled images, read directly through their global, are
+    /// converted. The other handles of `images` are ignored.
+    ///
+    /// [`Decode`]: SrgbConversion::Decode
+    pub fn convert_srgb_images(
+        &mut self,
+        images: &FastHashSet<Handle<GlobalVariable>>,
+        direction: SrgbConversion,
+    ) {
+        let types = &self.types;
+        let global_variables = &self.global_variables;
+        let images: FastHashSet<_> = images
+            .iter()
+            .cloned()
+            .filter(|&handle| match types[global_variables[handle].ty].inner {
+                TypeInner::Image {
+                    class:
+                        ImageClass::Sampled {
+                            kind: ScalarKind::Float,
+                            ..
+                        },
+                    ..
+                } => true,
+                _ => false,
+            })
+            .collect();
+        if images.is_empty() {
+            return;
+        }
+
+        let conversion = Conversion::new(self, direction);
+        for (_, function) in self.functions.iter_mut() {
+            convert_reads(function, &images, &conversion);
+        }
+        for ep in self.entry_points.iter_mut() {
+            convert_reads(&mut ep.function, &images, &conversion);
+        }
+    }
+
+    /// Convert the colors written to the given locations by the fragment
+    /// entry points between linear and sRGB.
+    ///
+    /// This emulates sRGB render targets on targets that lack them: the
+    /// conversion math, usually [`Encode`], is injected in front of every
+    /// `return`. Only the
+    /// `vec4<f32>` outputs are converted.
+    ///
+    /// [`Encode`]: SrgbConversion::Encode
+    pub fn convert_srgb_outputs(&mut self, locations: &[u32], direction: SrgbConversion) {
+        let mut conversion = None;
+        for index in 0..self.entry_points.len() {
+            let ep = &self.entry_points[index];
+            if ep.stage != ShaderStage::Fragment {
+                continue;
+            }
+            let result = match ep.function.result {
+                Some(ref result) => result,
+                None => continue,
+            };
+
+            let is_converted = |binding: &Option<Binding>, ty: Handle<Type>| match *binding {
+                Some(Binding::Location { location, .. }) => {
+                    locations.contains(&location) && self.types[ty].inner == VEC4
+                }
+                _ => false,
+            };
+            // Either the result itself is converted, or some members of the result struct
+            let members = match self.types[result.ty].inner {
+                _ if is_converted(&result.binding, result.ty) => None,
+                TypeInner::Struct { ref members, .. } => {
+                    let converted: Vec<_> = members
+                        .iter()
+                        .map(|member| is_converted(&member.binding, member.ty))
+                        .collect();
+                    if !converted.contains(&true) {
+                        continue;
+                    }
+                    Some(converted)
+                }
+                _ => continue,
+            };
+            let result_ty = result.ty;
+
+            if conversion.is_none() {
+                conversion = Some(Conversion::new(self, direction));
+            }
+            let conversion = conversion.as_ref().unwrap();
+            let function = &mut self.entry_points[index].function;
+            let constants = conversion.append_constants(&mut function.expressions);
+            rewrite_returns(
+                &mut function.body,
+                &mut function.expressions,
+                &mut |value: Handle<Expression>, expressions: &mut Arena<Expression>| {
+                    Some(match members {
+                        None => conversion.append(value, &constants, expressions),
+                        Some(ref members) => {
+                            let components = (0..members.len() as u32)
+                                .map(|index| {
+                                    let member = expressions
+                                        .append(Expression::AccessIndex { base: value, index });
+                                    if members[index as usize] {
+                                        conversion.append(member, &constants, expressions)
+                                    } else {
+                                        member
+                                    }
+                                })
+                                .collect();
+                            expressions.append(Expression::Compose {
+                                ty: result_ty,
+                                components,
+                            })
+                        }
+                    })
+                },
+            );
+        }
+    }
+}
+
+/// Convert the results of the image reads of `function` from `images`.
+///
+/// The uses of a read must come after its conversion in the arena, so the
+/// conversion is inserted right after the read, rebuilding the arena.
+fn convert_reads(
+    function: &mut Function,
+    images: &FastHashSet<Handle<GlobalVariable>>,
+    conversion: &Conversion,
+) {
+    let expressions = &function.expressions;
+    let reads: FastHashSet<_> = expressions
+        .iter()
+        .filter_map(|(handle, expression)| match *expression {
+            Expression::ImageSample { image, .. } | Expression::ImageLoad { image, .. } => {
+                match expressions[image] {
+                    Expression::GlobalVariable(var) if images.contains(&var) => Some(handle),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    if reads.is_empty() {
+        return;
+    }
+
+    let old_expressions = std::mem::take(&mut function.expressions);
+    // The constants go first, outside of any `Emit`
+    let constants = conversion.append_constants(&mut function.expressions);
+    // The first and last new handles of each old expression; the users of a
+    // read refer to the last one, which is the converted color.
+    let mut bounds: Vec<(Handle<Expression>, Handle<Expression>)> =
+        Vec::with_capacity(old_expressions.len());
+    for (index, mut expression) in old_expressions.into_inner().into_iter().enumerate() {
+        visit_operands_mut(&mut expression, &mut |operand| {
+            *operand = bounds[operand.index()].1;
+        });
+        let first = function.expressions.append(expression);
+        let last = if reads.contains(&Handle::from_usize(index)) {
+            conversion.append(first, &constants, &mut function.expressions)
+        } else {
+            first
+        };
+        bounds.push((first, last));
+    }
+
+    remap_block(
+        &mut function.body,
+        &|handle| *handle = bounds[handle.index()].1,
+        &|range| {
+            let first = range.clone().next()?;
+            let last = range.clone().last().unwrap_or(first);
+            Some(Range::new_from_bounds(
+                bounds[first.index()].0,
+                bounds[last.index()].1,
+            ))
+        },
+    );
+    function.named_expressions = function
+        .named_expressions
+        .drain()
+        .map(|(handle, name)| (bounds[handle.index()].1, name))
+        .collect();
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn convert_srgb() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]] var color_texture: texture_2d<f32>;
+        [[group(0), binding(1)]] var linear_texture: texture_2d<f32>;
+        [[group(0), binding(2)]] var texture_sampler: sampler;
+
+        struct FragmentOutput {
+            [[location(0)]] color: vec4<f32>;
+            [[location(1)]] normal: vec4<f32>;
+        };
+
+        fn fetch(uv: vec2<i32>) -> vec4<f32> {
+            return textureLoad(color_texture, uv, 0);
+        }
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] uv: vec2<f32>) -> FragmentOutput {
+            let color = textureSample(color_texture, texture_sampler, uv);
+            let normal = textureSample(linear_texture, texture_sampler, uv);
+            return FragmentOutput(color * fetch(vec2<i32>(uv)), normal);
+        }
+        ",
+    )
+    .unwrap();
+    let color_texture = module
+        .global_variables
+        .fetch_if(|var| var.name.as_deref() == Some("color_texture"))
+        .unwrap();
+    let images = std::iter::once(color_texture).collect();
+    module.convert_srgb_images(&images, SrgbConversion::Decode);
+    module.convert_srgb_outputs(&[0], SrgbConversion::Encode);
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // Count the reads whose results are converted, and the converted colors
+    fn count_conversions(function: &Function) -> (usize, usize) {
+        let mut reads = 0;
+        let mut selects = 0;
+        for (_, expression) in function.expressions.iter() {
+            match *expression {
+                Expression::Swizzle { vector, .. } => match function.expressions[vector] {
+                    Expression::ImageSample { .. } | Expression::ImageLoad { .. } => reads += 1,
+                    _ => {}
+                },
+                Expression::Select { .. } => selects += 1,
+                _ => {}
+            }
+        }
+        (reads, selects)
+    }
+    let (_, fetch) = module.functions.iter().next().unwrap();
+    assert_eq!(count_conversions(fetch), (1, 1));
+    // One sampled color is decoded, and one output is encoded
+    assert_eq!(count_conversions(&module.entry_points[0].function), (1, 2));
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn convert_srgb_with_lod_query() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        [[group(0), binding(0)]] var color_texture: texture_2d<f32>;
+        [[group(0), binding(1)]] var texture_sampler: sampler;
+
+        [[stage(fragment)]]
+        fn main([[location(0)]] uv: vec2<f32>) -> [[location(0)]] vec4<f32> {
+            let color = textureSample(color_texture, texture_sampler, uv);
+            let lod = textureSample(color_texture, texture_sampler, uv * 2.0).xy;
+            return color * lod.x;
+        }
+        ",
+    )
+    .unwrap();
+    super::interface::sample_to_lod_queries(&mut module.entry_points[0].function);
+    let color_texture = module
+        .global_variables
+        .fetch_if(|var| var.name.as_deref() == Some("color_texture"))
+        .unwrap();
+    let images = std::iter::once(color_texture).collect();
+    module.convert_srgb_images(&images, SrgbConversion::Decode);
+
+    // The query isn't a read, but its operands move with the arena
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
@@ -1,4 +1,4 @@
-use crate::arena::{Arena, Handle};
+use crate::arena::{Arena, Handle, UniqueArena};
 
 use thiserror::Error;
 
@@ -18,7 +18,7 @@ impl TypeResolution {
         }
     }
 
-    pub fn inner_with<'a>(&'a self, arena: &'a Arena<crate::Type>) -> &'a crate::TypeInner {
+    pub fn inner_with<'a>(&'a self, arena: &'a UniqueArena<crate::Type>) -> &'a crate::TypeInner {
         match *self {
             Self::Handle(handle) => &arena[handle].inner,
             Self::Value(ref inner) => inner,
@@ -111,7 +111,7 @@ pub enum ResolveError {
 
 pub struct ResolveContext<'a> {
     pub constants: &'a Arena<crate::Constant>,
-    pub types: &'a Arena<crate::Type>,
+    pub types: &'a UniqueArena<crate::Type>,
     pub global_vars: &'a Arena<crate::GlobalVariable>,
     pub local_vars: &'a Arena<crate::LocalVariable>,
     pub functions: &'a Arena<crate::Function>,
@@ -131,7 +131,9 @@ impl<'a> ResolveContext<'a> {
                 // Arrays and matrices can only be indexed dynamically behind a
                 // pointer, but that's a validation error, not a type error, so
                 // go ahead provide a type here.
-                Ti::Array { base, .. } => TypeResolution::Handle(base),
+                Ti::Array { base, .. } | Ti::BindingArray { base, .. } => {
+                    TypeResolution::Handle(base)
+                }
                 Ti::Matrix { rows, width, .. } => TypeResolution::Value(Ti::Vector {
                     size: rows,
                     kind: crate::ScalarKind::Float,
@@ -215,7 +217,9 @@ impl<'a> ResolveContext<'a> {
                         width,
                     })
                 }
-                Ti::Array { base, .. } => TypeResolution::Handle(base),
+                Ti::Array { base, .. } | Ti::BindingArray { base, .. } => {
+                    TypeResolution::Handle(base)
+                }
                 Ti::Struct { ref members, .. } => {
                     let member = members
                         .get(index as usize)
@@ -365,10 +369,28 @@ impl<'a> ResolveContext<'a> {
                     return Err(ResolveError::InvalidPointer(pointer));
                 }
             },
+            crate::Expression::ImageSample {
+                image,
+                gather: Some(_),
+                ..
+            } => match *past(image).inner_with(types) {
+                Ti::Image { class, .. } => TypeResolution::Value(Ti::Vector {
+                    kind: match class {
+                        crate::ImageClass::Sampled { kind, multi: _ } => kind,
+                        _ => crate::ScalarKind::Float,
+                    },
+                    width: 4,
+                    size: crate::VectorSize::Quad,
+                }),
+                ref other => {
+                    log::error!("Image type {:?}", other);
+                    return Err(ResolveError::InvalidImage(image));
+                }
+            },
             crate::Expression::ImageSample { image, .. }
             | crate::Expression::ImageLoad { image, .. } => match *past(image).inner_with(types) {
                 Ti::Image { class, .. } => TypeResolution::Value(match class {
-                    crate::ImageClass::Depth => Ti::Scalar {
+                    crate::ImageClass::Depth { multi: _ } => Ti::Scalar {
                         kind: crate::ScalarKind::Float,
                         width: 4,
                     },
@@ -417,6 +439,11 @@ impl<'a> ResolveContext<'a> {
                     kind: crate::ScalarKind::Sint,
                     width: 4,
                 },
+                crate::ImageQuery::Lod { .. } => Ti::Vector {
+                    size: crate::VectorSize::Bi,
+                    kind: crate::ScalarKind::Float,
+                    width: 4,
+                },
             }),
             crate::Expression::Unary { expr, .. } => past(expr).clone(),
             crate::Expression::Binary { op, left, right } => match op {
@@ -503,7 +530,7 @@ impl<'a> ResolveContext<'a> {
                 | crate::BinaryOperator::ShiftRight => past(left).clone(),
             },
             crate::Expression::Select { accept, .. } => past(accept).clone(),
-            crate::Expression::Derivative { axis: _, expr } => past(expr).clone(),
+            crate::Expression::Derivative { expr, .. } => past(expr).clone(),
             crate::Expression::Relational { .. } => TypeResolution::Value(Ti::Scalar {
                 kind: crate::ScalarKind::Bool,
                 width: crate::BOOL_WIDTH,
@@ -633,6 +660,18 @@ impl<'a> ResolveContext<'a> {
                     // bits
                     Mf::CountOneBits |
                     Mf::ReverseBits => res_arg.clone(),
+                    // data packing
+                    Mf::Pack4x8snorm |
+                    Mf::Pack4x8unorm |
+                    Mf::Pack2x16snorm |
+                    Mf::Pack2x16unorm |
+                    Mf::Pack2x16float => TypeResolution::Value(Ti::Scalar { kind: crate::ScalarKind::Uint, width: 4 }),
+                    // data unpacking
+                    Mf::Unpack4x8snorm |
+                    Mf::Unpack4x8unorm => TypeResolution::Value(Ti::Vector { size: crate::VectorSize::Quad, kind: crate::ScalarKind::Float, width: 4 }),
+                    Mf::Unpack2x16snorm |
+                    Mf::Unpack2x16unorm |
+                    Mf::Unpack2x16float => TypeResolution::Value(Ti::Vector { size: crate::VectorSize::Bi, kind: crate::ScalarKind::Float, width: 4 }),
                 }
             }
             crate::Expression::As {
@@ -660,6 +699,7 @@ impl<'a> ResolveContext<'a> {
                     )))
                 }
             },
+            crate::Expression::PointerCast { ty, .. } => TypeResolution::Handle(ty),
             crate::Expression::Call(function) => {
                 let result = self.functions[function]
                     .result
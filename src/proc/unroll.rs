@@ -0,0 +1,642 @@
+use super::{
+    branches::{evaluate, terminates},
+    interface::visit_operands_mut,
+};
+use crate::{
+    arena::{Arena, Handle, Range, UniqueArena},
+    Block, Constant, ConstantInner, Expression, FastHashMap, FastHashSet, Function, LocalVariable,
+    ScalarValue, Statement, Type, TypeInner,
+};
+
+impl crate::Module {
+    /// Unroll the loops that run a constant number of times, at most `max_iterations`.
+    ///
+    /// This is meant for targets like GLSL ES 2, where arrays of samplers can
+    /// only be indexed by constants, and so can't be indexed by the counters
+    /// of dynamic loops.
+    ///
+    /// A loop is unrolled if it starts by checking a condition to `Break`, its
+    /// `continuing` block only stores into a local variable, its counter, and
+    /// its body doesn't store into the counter nor leave the loop otherwise.
+    /// The counter must be initialized by a store right in front of the loop,
+    /// or by the initializer of the variable if the loop is the only place it's
+    /// stored to. The condition and the stored value are evaluated for each
+    /// iteration like [`Module::eliminate_dead_branches`] evaluates its branches.
+    ///
+    /// Each iteration gets a copy of the body, in which the counter is loaded
+    /// as a constant. The final value of the counter is stored after them.
+    ///
+    /// [`Module::eliminate_dead_branches`]: crate::Module::eliminate_dead_branches
+    pub fn unroll_loops(&mut self, max_iterations: u32) {
+        let mut unroller = Unroller {
+            constants: &mut self.constants,
+            types: &self.types,
+            max_iterations,
+        };
+        for (_, fun) in self.functions.iter_mut() {
+            unroller.unroll_function(fun);
+        }
+        for ep in self.entry_points.iter_mut() {
+            unroller.unroll_function(&mut ep.function);
+        }
+    }
+}
+
+struct Unroller<'a> {
+    constants: &'a mut Arena<Constant>,
+    types: &'a UniqueArena<Type>,
+    max_iterations: u32,
+}
+
+/// What [`Unroller::plan`] needs to know about the surroundings of a loop.
+struct PlanContext<'a> {
+    /// The pointer and value of the store in front of the loop, if any.
+    initial: Option<(Handle<Expression>, Handle<Expression>)>,
+    /// Whether the loop is nested in another one.
+    in_loop: bool,
+    stores: &'a FastHashMap<Handle<LocalVariable>, usize>,
+    escaping: &'a FastHashSet<Handle<LocalVariable>>,
+}
+
+/// A loop that can be unrolled.
+struct Unrolling {
+    /// The pointer to the counter, its width, and its values for each iteration.
+    counter: Handle<Expression>,
+    width: crate::Bytes,
+    values: Vec<ScalarValue>,
+    /// The value of the counter once the loop is over.
+    last: ScalarValue,
+}
+
+impl Unroller<'_> {
+    fn unroll_function(&mut self, fun: &mut Function) {
+        // Find the counters that can only change through the `Store` statements
+        let mut escaping = FastHashSet::default();
+        for (_, expression) in fun.expressions.iter() {
+            if let Expression::Load { .. } = *expression {
+                continue;
+            }
+            super::interface::visit_operands(expression, &mut |operand| {
+                if let Expression::LocalVariable(var) = fun.expressions[operand] {
+                    escaping.insert(var);
+                }
+            });
+        }
+        let mut stores = FastHashMap::default();
+        count_stores(&fun.body, &fun.expressions, &mut stores, &mut escaping);
+
+        let mut body = std::mem::take(&mut fun.body);
+        self.unroll_block(&mut body, fun, &stores, &escaping, false);
+        fun.body = body;
+    }
+
+    fn unroll_block(
+        &mut self,
+        block: &mut Block,
+        fun: &mut Function,
+        stores: &FastHashMap<Handle<LocalVariable>, usize>,
+        escaping: &FastHashSet<Handle<LocalVariable>>,
+        in_loop: bool,
+    ) {
+        for index in 0..block.len() {
+            let initial = match index.checked_sub(1).map(|index| &block[index]) {
+                Some(&Statement::Store { pointer, value }) => Some((pointer, value)),
+                _ => None,
+            };
+            let unrolling = match block[index] {
+                Statement::Block(ref mut inner) => {
+                    self.unroll_block(inner, fun, stores, escaping, in_loop);
+                    None
+                }
+                Statement::If {
+                    ref mut accept,
+                    ref mut reject,
+                    ..
+                } => {
+                    self.unroll_block(accept, fun, stores, escaping, in_loop);
+                    self.unroll_block(reject, fun, stores, escaping, in_loop);
+                    None
+                }
+                Statement::Switch {
+                    ref mut cases,
+                    ref mut default,
+                    ..
+                } => {
+                    for case in cases.iter_mut() {
+                        self.unroll_block(&mut case.body, fun, stores, escaping, in_loop);
+                    }
+                    self.unroll_block(default, fun, stores, escaping, in_loop);
+                    None
+                }
+                Statement::Loop {
+                    ref mut body,
+                    ref mut continuing,
+                } => {
+                    // Inner loops first, so that the outer ones copy unrolled bodies
+                    self.unroll_block(body, fun, stores, escaping, true);
+                    self.unroll_block(continuing, fun, stores, escaping, true);
+                    let context = PlanContext {
+                        initial,
+                        in_loop,
+                        stores,
+                        escaping,
+                    };
+                    self.plan(body, continuing, fun, &context)
+                }
+                _ => None,
+            };
+
+            if let Some(unrolling) = unrolling {
+                let body = match block[index] {
+                    Statement::Loop { ref mut body, .. } => std::mem::take(body),
+                    _ => unreachable!(),
+                };
+                block[index] = Statement::Block(self.unroll(&body, unrolling, fun));
+            }
+        }
+    }
+
+    /// Check if the loop can be unrolled, and find the values of its counter.
+    fn plan(
+        &self,
+        body: &Block,
+        continuing: &Block,
+        fun: &Function,
+        context: &PlanContext,
+    ) -> Option<Unrolling> {
+        let expressions = &fun.expressions;
+        let local = |pointer: Handle<Expression>| match expressions[pointer] {
+            Expression::LocalVariable(var) if !context.escaping.contains(&var) => Some(var),
+            _ => None,
+        };
+
+        // The continuing block updates the counter, and nothing else
+        let (counter, next) = match continuing.split_last()? {
+            (&Statement::Store { pointer, value }, rest)
+                if rest.iter().all(|statement| match *statement {
+                    Statement::Emit(_) => true,
+                    _ => false,
+                }) =>
+            {
+                (pointer, value)
+            }
+            _ => return None,
+        };
+        let var = local(counter)?;
+        let width = match self.types[fun.local_variables[var].ty].inner {
+            TypeInner::Scalar { width, .. } => width,
+            _ => return None,
+        };
+
+        // The body starts by checking whether to leave the loop
+        let check = body.iter().position(|statement| match *statement {
+            Statement::Emit(_) => false,
+            _ => true,
+        })?;
+        let (condition, continue_if) = match body[check] {
+            Statement::If {
+                condition,
+                ref accept,
+                ref reject,
+            } => match (accept.as_slice(), reject.as_slice()) {
+                (&[], &[Statement::Break]) => (condition, true),
+                (&[Statement::Break], &[]) => (condition, false),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let rest = &body[check + 1..];
+        if rest.iter().any(terminates)
+            || leaves_loop(rest)
+            || count_stores(
+                rest,
+                expressions,
+                &mut FastHashMap::default(),
+                &mut FastHashSet::default(),
+            )
+            .contains_key(&var)
+        {
+            return None;
+        }
+
+        let first = match context.initial {
+            Some((pointer, value)) if local(pointer) == Some(var) => {
+                evaluate(value, expressions, self.constants, None)?
+            }
+            // The initializer only runs once, so it can't start nested loops
+            _ if !context.in_loop && context.stores.get(&var) == Some(&1) => {
+                match self.constants[fun.local_variables[var].init?] {
+                    Constant {
+                        specialization: None,
+                        inner: ConstantInner::Scalar { value, .. },
+                        ..
+                    } => value,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        let mut values = Vec::new();
+        let mut value = first;
+        loop {
+            // The counter's arithmetic can't overflow its width, so a loop
+            // whose counter would wrap around isn't unrolled
+            let counter = Some((var, value, width));
+            match evaluate(condition, expressions, self.constants, counter)? {
+                ScalarValue::Bool(go_on) if go_on == continue_if => {}
+                ScalarValue::Bool(_) => break,
+                _ => return None,
+            }
+            if values.len() as u32 == self.max_iterations {
+                return None;
+            }
+            values.push(value);
+            value = evaluate(next, expressions, self.constants, counter)?;
+        }
+        Some(Unrolling {
+            counter,
+            width,
+            values,
+            last: value,
+        })
+    }
+
+    /// Copy the body of the loop for each iteration, without the exit check.
+    fn unroll(&mut self, body: &Block, unrolling: Unrolling, fun: &mut Function) -> Block {
+        let var = match fun.expressions[unrolling.counter] {
+            Expression::LocalVariable(var) => var,
+            _ => unreachable!(),
+        };
+        let width = unrolling.width;
+        let check = body
+            .iter()
+            .position(|statement| match *statement {
+                Statement::Emit(_) => false,
+                _ => true,
+            })
+            .unwrap_or(0);
+
+        let mut unrolled = Vec::with_capacity(unrolling.values.len() + 1);
+        for value in unrolling.values {
+            let constant = self.constant(value, width);
+            let mut copier = Copier {
+                expressions: &mut fun.expressions,
+                var,
+                constant,
+                map: FastHashMap::default(),
+            };
+            let mut copy = copier.copy_block(&body[..check]);
+            copy.extend(copier.copy_block(&body[check + 1..]));
+            unrolled.push(Statement::Block(copy));
+        }
+        let last = self.constant(unrolling.last, width);
+        unrolled.push(Statement::Store {
+            pointer: unrolling.counter,
+            value: fun.expressions.append(Expression::Constant(last)),
+        });
+        unrolled
+    }
+
+    fn constant(&mut self, value: ScalarValue, width: crate::Bytes) -> Handle<Constant> {
+        self.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar { width, value },
+        })
+    }
+}
+
+/// Copies the statements of a loop body, and the expressions they emit, for
+/// one iteration.
+struct Copier<'a> {
+    expressions: &'a mut Arena<Expression>,
+    /// The counter of the loop, and its value in this iteration.
+    var: Handle<LocalVariable>,
+    constant: Handle<Constant>,
+    /// The copies of the expressions emitted so far.
+    map: FastHashMap<Handle<Expression>, Handle<Expression>>,
+}
+
+impl Copier<'_> {
+    fn map(&self, handle: Handle<Expression>) -> Handle<Expression> {
+        self.map.get(&handle).cloned().unwrap_or(handle)
+    }
+
+    fn copy_block(&mut self, block: &[Statement]) -> Block {
+        let mut copy = Vec::with_capacity(block.len());
+        for statement in block {
+            let statement = match *statement {
+                Statement::Emit(ref range) => {
+                    self.copy_emit(range, &mut copy);
+                    continue;
+                }
+                Statement::Block(ref inner) => Statement::Block(self.copy_block(inner)),
+                Statement::If {
+                    condition,
+                    ref accept,
+                    ref reject,
+                } => Statement::If {
+                    condition: self.map(condition),
+                    accept: self.copy_block(accept),
+                    reject: self.copy_block(reject),
+                },
+                Statement::Switch {
+                    selector,
+                    ref cases,
+                    ref default,
+                } => Statement::Switch {
+                    selector: self.map(selector),
+                    cases: cases
+                        .iter()
+                        .map(|case| crate::SwitchCase {
+                            value: case.value,
+                            body: self.copy_block(&case.body),
+                            fall_through: case.fall_through,
+                        })
+                        .collect(),
+                    default: self.copy_block(default),
+                },
+                Statement::Loop {
+                    ref body,
+                    ref continuing,
+                } => Statement::Loop {
+                    body: self.copy_block(body),
+                    continuing: self.copy_block(continuing),
+                },
+                Statement::Return { value } => Statement::Return {
+                    value: value.map(|value| self.map(value)),
+                },
+                Statement::Store { pointer, value } => Statement::Store {
+                    pointer: self.map(pointer),
+                    value: self.map(value),
+                },
+                Statement::ImageStore {
+                    image,
+                    coordinate,
+                    array_index,
+                    value,
+                } => Statement::ImageStore {
+                    image: self.map(image),
+                    coordinate: self.map(coordinate),
+                    array_index: array_index.map(|index| self.map(index)),
+                    value: self.map(value),
+                },
+                Statement::Call {
+                    function,
+                    ref arguments,
+                    result,
+                } => Statement::Call {
+                    function,
+                    arguments: arguments
+                        .iter()
+                        .map(|&argument| self.map(argument))
+                        .collect(),
+                    result: result.map(|result| {
+                        let copy = self.expressions.append(Expression::Call(function));
+                        self.map.insert(result, copy);
+                        copy
+                    }),
+                },
+                ref other => other.clone(),
+            };
+            copy.push(statement);
+        }
+        copy
+    }
+
+    /// Copy the expressions of `range`, emitting the copies into `block`.
+    ///
+    /// The loads of the counter become constants, which aren't emitted.
+    fn copy_emit(&mut self, range: &Range<Expression>, block: &mut Block) {
+        let mut emitted: Option<(Handle<Expression>, Handle<Expression>)> = None;
+        for handle in range.clone() {
+            let is_counter = match self.expressions[handle] {
+                Expression::Load { pointer } => match self.expressions[pointer] {
+                    Expression::LocalVariable(var) => var == self.var,
+                    _ => false,
+                },
+                _ => false,
+            };
+            let copy = if is_counter {
+                if let Some((first, last)) = emitted.take() {
+                    block.push(Statement::Emit(Range::new_from_bounds(first, last)));
+                }
+                self.expressions.append(Expression::Constant(self.constant))
+            } else {
+                let mut expression = self.expressions[handle].clone();
+                visit_operands_mut(&mut expression, &mut |operand| {
+                    *operand = self.map(*operand)
+                });
+                let copy = self.expressions.append(expression);
+                emitted = Some((emitted.map_or(copy, |(first, _)| first), copy));
+                copy
+            };
+            self.map.insert(handle, copy);
+        }
+        if let Some((first, last)) = emitted {
+            block.push(Statement::Emit(Range::new_from_bounds(first, last)));
+        }
+    }
+}
+
+/// Count the stores into each local variable, and find the ones that escape
+/// as pointer arguments of calls.
+fn count_stores<'a>(
+    block: &[Statement],
+    expressions: &Arena<Expression>,
+    stores: &'a mut FastHashMap<Handle<LocalVariable>, usize>,
+    escaping: &mut FastHashSet<Handle<LocalVariable>>,
+) -> &'a FastHashMap<Handle<LocalVariable>, usize> {
+    for statement in block {
+        match *statement {
+            Statement::Block(ref inner) => {
+                count_stores(inner, expressions, stores, escaping);
+            }
+            Statement::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                count_stores(accept, expressions, stores, escaping);
+                count_stores(reject, expressions, stores, escaping);
+            }
+            Statement::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases {
+                    count_stores(&case.body, expressions, stores, escaping);
+                }
+                count_stores(default, expressions, stores, escaping);
+            }
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                count_stores(body, expressions, stores, escaping);
+                count_stores(continuing, expressions, stores, escaping);
+            }
+            Statement::Store { pointer, .. } => {
+                if let Expression::LocalVariable(var) = expressions[pointer] {
+                    *stores.entry(var).or_insert(0) += 1;
+                }
+            }
+            Statement::Call { ref arguments, .. } => {
+                for &argument in arguments {
+                    if let Expression::LocalVariable(var) = expressions[argument] {
+                        escaping.insert(var);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stores
+}
+
+/// Return true if `block` may `Break` out of the loop around it, or `Continue` it.
+fn leaves_loop(block: &[Statement]) -> bool {
+    block.iter().any(|statement| match *statement {
+        Statement::Break | Statement::Continue => true,
+        Statement::Block(ref inner) => leaves_loop(inner),
+        Statement::If {
+            ref accept,
+            ref reject,
+            ..
+        } => leaves_loop(accept) || leaves_loop(reject),
+        // A `Break` in a switch leaves the switch, but a `Continue` continues the loop
+        Statement::Switch {
+            ref cases,
+            ref default,
+            ..
+        } => cases
+            .iter()
+            .map(|case| &case.body)
+            .chain(Some(default))
+            .any(|body| continues(body)),
+        _ => false,
+    })
+}
+
+fn continues(block: &[Statement]) -> bool {
+    block.iter().any(|statement| match *statement {
+        Statement::Continue => true,
+        Statement::Block(ref inner) => continues(inner),
+        Statement::If {
+            ref accept,
+            ref reject,
+            ..
+        } => continues(accept) || continues(reject),
+        Statement::Switch {
+            ref cases,
+            ref default,
+            ..
+        } => cases
+            .iter()
+            .map(|case| &case.body)
+            .chain(Some(default))
+            .any(|body| continues(body)),
+        _ => false,
+    })
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn unroll_loops() {
+    let source = "
+        [[stage(fragment)]]
+        fn main([[location(0)]] value: f32) -> [[location(0)]] vec4<f32> {
+            var color: vec4<f32> = vec4<f32>(0.0);
+            for (var i: i32 = 0; i < 4; i = i + 1) {
+                var j: i32 = 2;
+                loop {
+                    if (j >= 4) {
+                        break;
+                    }
+                    color[j] = color[j] + f32(i) * value;
+                    continuing {
+                        j = j + 1;
+                    }
+                }
+            }
+            var k: u32 = 0u;
+            loop {
+                if (k > 100u) {
+                    break;
+                }
+                color.x = color.x + value;
+                continuing {
+                    k = k + 1u;
+                }
+            }
+            return color;
+        }
+    ";
+    let mut module = crate::front::wgsl::parse_str(source).unwrap();
+    module.unroll_loops(10);
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // Both loops nested in the `for` are unrolled, but not the last one
+    fn count_loops(block: &Block) -> usize {
+        block
+            .iter()
+            .map(|statement| match *statement {
+                Statement::Block(ref inner) => count_loops(inner),
+                Statement::Loop { ref body, .. } => 1 + count_loops(body),
+                _ => 0,
+            })
+            .sum()
+    }
+    let function = &module.entry_points[0].function;
+    assert_eq!(count_loops(&function.body), 1);
+    // The four iterations of the outer loop, and the store of the final counter
+    let iterations = function
+        .body
+        .iter()
+        .find_map(|statement| match *statement {
+            Statement::Block(ref iterations) => Some(iterations),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(iterations.len(), 5);
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn unroll_loops_overflowing_counters() {
+    let source = "
+        [[stage(fragment)]]
+        fn main([[location(0)]] value: f32) -> [[location(0)]] vec4<f32> {
+            var color: vec4<f32> = vec4<f32>(0.0);
+            for (var i: u32 = 4294967294u; i <= 4294967295u; i = i + 1u) {
+                color.x = color.x + value;
+            }
+            for (var j: i32 = 2147483646; j >= 2147483646; j = j + 1) {
+                color.y = color.y + value;
+            }
+            return color;
+        }
+    ";
+    let mut module = crate::front::wgsl::parse_str(source).unwrap();
+    module.unroll_loops(10);
+
+    // Both counters wrap around instead of leaving the loops, which are kept
+    let loops = module.entry_points[0]
+        .function
+        .body
+        .iter()
+        .filter(|statement| match **statement {
+            Statement::Loop { .. } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(loops, 2);
+}
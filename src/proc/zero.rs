@@ -0,0 +1,261 @@
+use super::interface::collect_used;
+use crate::{
+    arena::{Arena, Handle, UniqueArena},
+    Block, Constant, ConstantInner, Expression, FastHashSet, Function, GlobalVariable, ScalarKind,
+    ScalarValue, ShaderStage, Statement, StorageClass, Type, TypeInner,
+};
+
+impl crate::Module {
+    /// Guarantee that the private and workgroup variables start out zeroed.
+    ///
+    /// Private variables without an initializer get a zero one. Workgroup
+    /// variables can't have initializers, so every compute entry point stores
+    /// zero into the ones it uses, directly or through the functions it calls,
+    /// and then waits on a workgroup barrier before running its own code.
+    ///
+    /// WGSL requires this, while the memory is left uninitialized by default
+    /// in the other languages, and threadgroup memory in particular contains
+    /// whatever the previous workgroup left there on Metal and GL.
+    pub fn zero_initialize_globals(&mut self) {
+        let mut workgroup_zeros = Vec::new();
+        for (handle, var) in self.global_variables.iter_mut() {
+            let needs_zero = match var.class {
+                StorageClass::Private => var.init.is_none(),
+                StorageClass::WorkGroup => true,
+                _ => false,
+            };
+            if !needs_zero {
+                continue;
+            }
+            match zero_constant(var.ty, &mut self.types, &mut self.constants) {
+                Some(zero) if var.class == StorageClass::WorkGroup => {
+                    workgroup_zeros.push((handle, zero))
+                }
+                Some(zero) => var.init = Some(zero),
+                None => log::warn!("Unable to zero-initialize {:?}", var.name),
+            }
+        }
+        if workgroup_zeros.is_empty() {
+            return;
+        }
+
+        for ep in self.entry_points.iter_mut() {
+            if ep.stage != ShaderStage::Compute {
+                continue;
+            }
+            let used = used_globals(&ep.function, &self.functions);
+            let mut prologue = Block::new();
+            for &(var, zero) in workgroup_zeros.iter() {
+                if !used.contains(&var) {
+                    continue;
+                }
+                // both expressions are pre-emitted, so no `Emit` is needed
+                let expressions = &mut ep.function.expressions;
+                let pointer = expressions.append(Expression::GlobalVariable(var));
+                let value = expressions.append(Expression::Constant(zero));
+                prologue.push(Statement::Store { pointer, value });
+            }
+            if prologue.is_empty() {
+                continue;
+            }
+            prologue.push(Statement::Barrier(crate::Barrier::WORK_GROUP));
+            prologue.append(&mut ep.function.body);
+            ep.function.body = prologue;
+        }
+    }
+}
+
+/// Return the zero value of `ty`, if it has one.
+fn zero_constant(
+    ty: Handle<Type>,
+    types: &mut UniqueArena<Type>,
+    constants: &mut Arena<Constant>,
+) -> Option<Handle<Constant>> {
+    let zero_scalar = |kind, width, constants: &mut Arena<Constant>| {
+        let value = match kind {
+            ScalarKind::Sint => ScalarValue::Sint(0),
+            ScalarKind::Uint => ScalarValue::Uint(0),
+            ScalarKind::Float => ScalarValue::Float(0.0),
+            ScalarKind::Bool => ScalarValue::Bool(false),
+        };
+        constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar { width, value },
+        })
+    };
+
+    let inner = match types[ty].inner {
+        TypeInner::Scalar { kind, width } => return Some(zero_scalar(kind, width, constants)),
+        TypeInner::Vector { size, kind, width } => ConstantInner::Composite {
+            ty,
+            components: vec![zero_scalar(kind, width, constants); size as usize],
+        },
+        TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => {
+            let column_ty = types.insert(Type {
+                name: None,
+                inner: TypeInner::Vector {
+                    size: rows,
+                    kind: ScalarKind::Float,
+                    width,
+                },
+            });
+            ConstantInner::Composite {
+                ty,
+                components: vec![zero_constant(column_ty, types, constants)?; columns as usize],
+            }
+        }
+        TypeInner::Array {
+            base,
+            size: crate::ArraySize::Constant(size),
+            ..
+        } => {
+            let length = constants[size].to_array_length()?;
+            ConstantInner::Composite {
+                ty,
+                components: vec![zero_constant(base, types, constants)?; length as usize],
+            }
+        }
+        TypeInner::Struct { ref members, .. } => {
+            let member_types = members.iter().map(|member| member.ty).collect::<Vec<_>>();
+            let components = member_types
+                .into_iter()
+                .map(|member_ty| zero_constant(member_ty, types, constants))
+                .collect::<Option<_>>()?;
+            ConstantInner::Composite { ty, components }
+        }
+        _ => return None,
+    };
+    Some(constants.fetch_or_append(Constant {
+        name: None,
+        specialization: None,
+        inner,
+    }))
+}
+
+/// Return the global variables used by `function` and the functions it calls.
+fn used_globals(
+    function: &Function,
+    functions: &Arena<Function>,
+) -> FastHashSet<Handle<GlobalVariable>> {
+    let mut used = FastHashSet::default();
+    let mut visited = FastHashSet::default();
+    let mut stack = vec![function];
+    while let Some(function) = stack.pop() {
+        // front ends may append expressions for globals that are never used
+        for handle in collect_used(&function.body, &function.expressions) {
+            if let Expression::GlobalVariable(var) = function.expressions[handle] {
+                used.insert(var);
+            }
+        }
+        let mut callees = Vec::new();
+        collect_calls(&function.body, &mut callees);
+        for callee in callees {
+            if visited.insert(callee) {
+                stack.push(&functions[callee]);
+            }
+        }
+    }
+    used
+}
+
+fn collect_calls(block: &Block, callees: &mut Vec<Handle<Function>>) {
+    for statement in block.iter() {
+        match *statement {
+            Statement::Block(ref inner) => collect_calls(inner, callees),
+            Statement::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                collect_calls(accept, callees);
+                collect_calls(reject, callees);
+            }
+            Statement::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases.iter() {
+                    collect_calls(&case.body, callees);
+                }
+                collect_calls(default, callees);
+            }
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                collect_calls(body, callees);
+                collect_calls(continuing, callees);
+            }
+            Statement::Call { function, .. } => callees.push(function),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "wgsl-in")]
+#[test]
+fn zero_initialize_globals() {
+    let mut module = crate::front::wgsl::parse_str(
+        "
+        var<private> counter: u32;
+        var<private> scale: f32 = 2.0;
+        var<workgroup> shared_data: array<vec2<f32>, 4>;
+        var<workgroup> unused: mat2x2<f32>;
+
+        fn accumulate(index: u32) {
+            shared_data[index] = shared_data[index] + vec2<f32>(scale);
+        }
+
+        [[stage(compute), workgroup_size(4)]]
+        fn main([[builtin(local_invocation_index)]] index: u32) {
+            counter = counter + 1u;
+            accumulate(index);
+        }
+        ",
+    )
+    .unwrap();
+    module.zero_initialize_globals();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let counter = module.global_variables.find_by_name("counter").unwrap();
+    let scale = module.global_variables.find_by_name("scale").unwrap();
+    match module.global_variables[counter].init {
+        Some(init) => assert_eq!(
+            module.constants[init].inner,
+            ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Uint(0),
+            }
+        ),
+        None => panic!("counter isn't initialized"),
+    }
+    match module.global_variables[scale].init {
+        Some(init) => assert_eq!(
+            module.constants[init].inner,
+            ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(2.0),
+            }
+        ),
+        None => panic!("scale lost its initializer"),
+    }
+
+    // only the workgroup variable used through `accumulate` is stored to
+    let body = &module.entry_points[0].function.body;
+    match body[..2] {
+        [Statement::Store { .. }, Statement::Barrier(crate::Barrier::WORK_GROUP)] => {}
+        ref other => panic!("Unexpected prologue {:?}", other),
+    }
+}
@@ -0,0 +1,682 @@
+//! One-call translation between shading languages.
+//!
+//! [`translate`] runs the whole pipeline: it parses the [`Source`], validates
+//! and analyzes the module, and writes it out for the [`Target`]. Everything
+//! that isn't specified in [`TranslateOptions`] gets a default picked for the
+//! target, taking into account the features the module actually uses.
+//!
+//! ```ignore
+//! use naga::translate::{Source, Target, TranslateOptions};
+//!
+//! let options = TranslateOptions::default();
+//! let artifacts = naga::translate(Source::Wgsl(source), Target::Msl, &options)?;
+//! let msl = artifacts.output.as_text().unwrap();
+//! ```
+//!
+//! With the `rayon` feature, [`translate_many`] and [`translate_entry_points`]
+//! spread the work over the global thread pool of `rayon`. Modules and their
+//! validation info are `Send` and `Sync`, so applications can also share
+//! them between their own threads.
+
+use crate::valid::{Capabilities, ValidationError, ValidationFlags, Validator};
+
+/// Shader source to translate.
+#[derive(Clone, Copy, Debug)]
+pub enum Source<'a> {
+    /// WGSL source text.
+    #[cfg(feature = "wgsl-in")]
+    Wgsl(&'a str),
+    /// GLSL source text, whose `main` function is an entry point of `stage`.
+    #[cfg(feature = "glsl-in")]
+    Glsl {
+        source: &'a str,
+        stage: crate::ShaderStage,
+    },
+    /// SPIR-V binary, as little endian bytes.
+    #[cfg(feature = "spv-in")]
+    Spv(&'a [u8]),
+}
+
+/// Language to translate to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    #[cfg(feature = "spv-out")]
+    Spv,
+    #[cfg(feature = "msl-out")]
+    Msl,
+    /// GLSL only holds a single entry point, see [`TranslateOptions::entry_point`].
+    #[cfg(feature = "glsl-out")]
+    Glsl,
+    #[cfg(feature = "hlsl-out")]
+    Hlsl,
+    #[cfg(feature = "wgsl-out")]
+    Wgsl,
+}
+
+impl Target {
+    /// IR capabilities that the back end is able to express.
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            #[cfg(feature = "spv-out")]
+            Target::Spv => Capabilities::all(),
+            #[cfg(feature = "msl-out")]
+            Target::Msl => Capabilities::all() - Capabilities::FLOAT64,
+            #[cfg(feature = "glsl-out")]
+            Target::Glsl => {
+                Capabilities::all()
+                    - Capabilities::PUSH_CONSTANT
+                    - Capabilities::PHYSICAL_STORAGE_BUFFER
+            }
+            #[cfg(feature = "hlsl-out")]
+            Target::Hlsl => {
+                Capabilities::all()
+                    - Capabilities::PUSH_CONSTANT
+                    - Capabilities::PHYSICAL_STORAGE_BUFFER
+            }
+            #[cfg(feature = "wgsl-out")]
+            Target::Wgsl => Capabilities::all() - Capabilities::PHYSICAL_STORAGE_BUFFER,
+        }
+    }
+}
+
+/// Configuration of [`translate`].
+///
+/// The per-target options are used verbatim when provided. Otherwise the
+/// defaults of the back end are used, with the language version raised as
+/// needed by the module.
+#[derive(Clone, Debug, Default)]
+pub struct TranslateOptions {
+    pub validation_flags: ValidationFlags,
+    /// Capabilities allowed during validation. Defaults to everything the
+    /// target can express, see [`Target::capabilities`].
+    pub capabilities: Option<Capabilities>,
+    /// Name of the entry point to write, for targets that only hold one.
+    /// Defaults to the first entry point of the module.
+    pub entry_point: Option<String>,
+    /// Zero the private and workgroup variables that have no initializer,
+    /// as WGSL requires, see [`Module::zero_initialize_globals`].
+    ///
+    /// [`Module::zero_initialize_globals`]: crate::Module::zero_initialize_globals
+    pub zero_initialize_globals: bool,
+    /// Adjust the position written by the vertex entry points to the clip
+    /// space of the target, see [`Module::adjust_coordinate_space`].
+    ///
+    /// This replaces the coordinate space flags of the SPIR-V and GLSL back
+    /// ends, which are then left out of their default options, and must not
+    /// be set in the given ones.
+    ///
+    /// [`Module::adjust_coordinate_space`]: crate::Module::adjust_coordinate_space
+    pub coordinate_space_adjustment: crate::proc::CoordinateSpaceAdjustment,
+    #[cfg(feature = "spv-out")]
+    pub spv: Option<crate::back::spv::Options>,
+    #[cfg(feature = "msl-out")]
+    pub msl: Option<crate::back::msl::Options>,
+    #[cfg(feature = "glsl-out")]
+    pub glsl: Option<crate::back::glsl::Options>,
+    #[cfg(feature = "hlsl-out")]
+    pub hlsl: Option<crate::back::hlsl::Options>,
+}
+
+/// Error produced by [`translate`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[cfg(feature = "wgsl-in")]
+    #[error(transparent)]
+    WgslIn(#[from] crate::front::wgsl::ParseError),
+    #[cfg(feature = "glsl-in")]
+    #[error(transparent)]
+    GlslIn(#[from] crate::front::glsl::ParseError),
+    #[cfg(feature = "spv-in")]
+    #[error(transparent)]
+    SpvIn(#[from] crate::front::spv::Error),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[cfg(feature = "spv-out")]
+    #[error(transparent)]
+    SpvOut(#[from] crate::back::spv::Error),
+    #[cfg(feature = "msl-out")]
+    #[error(transparent)]
+    MslOut(#[from] crate::back::msl::Error),
+    #[cfg(feature = "glsl-out")]
+    #[error(transparent)]
+    GlslOut(#[from] crate::back::glsl::Error),
+    #[cfg(feature = "hlsl-out")]
+    #[error(transparent)]
+    HlslOut(#[from] crate::back::hlsl::Error),
+    #[cfg(feature = "wgsl-out")]
+    #[error(transparent)]
+    WgslOut(#[from] crate::back::wgsl::Error),
+    #[error("the module has no entry points")]
+    NoEntryPoints,
+    #[error("entry point {0:?} is not found")]
+    UnknownEntryPoint(String),
+    #[error("the coordinate space is adjusted by both the module and the {0:?} writer")]
+    CoordinateSpaceAdjustedTwice(Target),
+}
+
+/// Translated shader.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    Text(String),
+    Binary(Vec<u32>),
+}
+
+impl Output {
+    /// Returns the source text, for textual targets.
+    pub fn as_text(&self) -> Option<&str> {
+        match *self {
+            Output::Text(ref text) => Some(text),
+            Output::Binary(_) => None,
+        }
+    }
+
+    /// Returns the words, for binary targets.
+    pub fn as_binary(&self) -> Option<&[u32]> {
+        match *self {
+            Output::Text(_) => None,
+            Output::Binary(ref words) => Some(words),
+        }
+    }
+}
+
+/// Entry point of the translated module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryPointReflection {
+    /// Name of the entry point in the IR.
+    pub name: String,
+    pub stage: crate::ShaderStage,
+    pub workgroup_size: [u32; 3],
+    /// Name of the entry point in the output, or `None` if it wasn't written.
+    pub output_name: Option<String>,
+}
+
+/// Information about the translated module that is required for its use.
+#[derive(Default)]
+pub struct Reflection {
+    /// Entry points of the module, in the order of the IR.
+    pub entry_points: Vec<EntryPointReflection>,
+    /// Texture and uniform mapping of the GLSL output.
+    #[cfg(feature = "glsl-out")]
+    pub glsl: Option<crate::back::glsl::ReflectionInfo>,
+}
+
+/// Result of a successful [`translate`].
+pub struct Artifacts {
+    pub output: Output,
+    pub reflection: Reflection,
+    /// Things that didn't prevent the translation, but may surprise the
+    /// user, like entry points that were left out.
+    pub warnings: Vec<String>,
+}
+
+/// Translate `input` to `target`.
+///
+/// See the [module documentation](self) for details.
+pub fn translate(
+    input: Source,
+    target: Target,
+    options: &TranslateOptions,
+) -> Result<Artifacts, Error> {
+    let module = parse(input, options)?;
+    let info = validate(&module, target, options)?;
+    write(&module, &info, target, options)
+}
+
+/// Translate each of `inputs` to `target`, in parallel.
+///
+/// The results are in the order of `inputs`.
+#[cfg(feature = "rayon")]
+pub fn translate_many(
+    inputs: &[Source],
+    target: Target,
+    options: &TranslateOptions,
+) -> Vec<Result<Artifacts, Error>> {
+    use rayon::prelude::*;
+
+    inputs
+        .par_iter()
+        .map(|&input| translate(input, target, options))
+        .collect()
+}
+
+/// Translate `input` to `target` once per entry point, in parallel.
+///
+/// This is meant for targets that only hold a single entry point, like GLSL.
+/// The module is parsed and validated once, then written with
+/// [`TranslateOptions::entry_point`] set to each of its entry points, in order.
+#[cfg(feature = "rayon")]
+pub fn translate_entry_points(
+    input: Source,
+    target: Target,
+    options: &TranslateOptions,
+) -> Result<Vec<Artifacts>, Error> {
+    use rayon::prelude::*;
+
+    let module = parse(input, options)?;
+    let info = validate(&module, target, options)?;
+    module
+        .entry_points
+        .par_iter()
+        .map(|ep| {
+            let ep_options = TranslateOptions {
+                entry_point: Some(ep.name.clone()),
+                ..options.clone()
+            };
+            write(&module, &info, target, &ep_options)
+        })
+        .collect()
+}
+
+fn validate(
+    module: &crate::Module,
+    target: Target,
+    options: &TranslateOptions,
+) -> Result<crate::valid::ModuleInfo, Error> {
+    let capabilities = options
+        .capabilities
+        .unwrap_or_else(|| target.capabilities());
+    Ok(Validator::new(options.validation_flags, capabilities).validate(module)?)
+}
+
+fn write(
+    module: &crate::Module,
+    info: &crate::valid::ModuleInfo,
+    target: Target,
+    options: &TranslateOptions,
+) -> Result<Artifacts, Error> {
+    // Only some of the targets amend these
+    #[allow(unused_mut)]
+    let mut reflection = Reflection {
+        entry_points: module
+            .entry_points
+            .iter()
+            .map(|ep| EntryPointReflection {
+                name: ep.name.clone(),
+                stage: ep.stage,
+                workgroup_size: ep.workgroup_size,
+                output_name: Some(ep.name.clone()),
+            })
+            .collect(),
+        ..Reflection::default()
+    };
+    #[allow(unused_mut)]
+    let mut warnings = Vec::new();
+
+    let output = match target {
+        #[cfg(feature = "spv-out")]
+        Target::Spv => {
+            use crate::back::spv::WriterFlags;
+
+            let adjusted = !options.coordinate_space_adjustment.is_empty();
+            let spv_options = match options.spv {
+                Some(ref spv_options) => {
+                    if adjusted
+                        && spv_options
+                            .flags
+                            .contains(WriterFlags::ADJUST_COORDINATE_SPACE)
+                    {
+                        return Err(Error::CoordinateSpaceAdjustedTwice(target));
+                    }
+                    spv_options.clone()
+                }
+                None => {
+                    let mut spv_options = crate::back::spv::Options::default();
+                    if adjusted {
+                        spv_options.flags -= WriterFlags::ADJUST_COORDINATE_SPACE;
+                    }
+                    spv_options
+                }
+            };
+            let words = crate::back::spv::write_vec(module, info, &spv_options)?;
+            let names = crate::back::spv::entry_point_names(module, &spv_options);
+            for (ep_reflection, name) in reflection.entry_points.iter_mut().zip(names) {
+                ep_reflection.output_name = name;
+            }
+            Output::Binary(words)
+        }
+        #[cfg(feature = "msl-out")]
+        Target::Msl => {
+            use crate::back::msl;
+
+            let msl_options = match options.msl {
+                Some(ref msl_options) => msl_options.clone(),
+                None => {
+                    let mut msl_options = msl::Options::default();
+                    if scalar_capabilities(module).contains(Capabilities::INT64) {
+                        msl_options.lang_version = (2, 2);
+                    }
+                    msl_options
+                }
+            };
+            let (string, translation_info) =
+                msl::write_string(module, info, &msl_options, &msl::PipelineOptions::default())?;
+            reflect_names(
+                &mut reflection,
+                &mut warnings,
+                translation_info.entry_point_names,
+            );
+            Output::Text(string)
+        }
+        #[cfg(feature = "glsl-out")]
+        Target::Glsl => {
+            use crate::back::glsl;
+
+            let ep_index = match options.entry_point {
+                Some(ref name) => module
+                    .entry_points
+                    .iter()
+                    .position(|ep| ep.name == *name)
+                    .ok_or_else(|| Error::UnknownEntryPoint(name.clone()))?,
+                None if module.entry_points.is_empty() => return Err(Error::NoEntryPoints),
+                None => 0,
+            };
+            let ep = &module.entry_points[ep_index];
+
+            let adjusted = !options.coordinate_space_adjustment.is_empty();
+            let glsl_options = match options.glsl {
+                Some(ref glsl_options) => {
+                    if adjusted
+                        && glsl_options
+                            .writer_flags
+                            .contains(glsl::WriterFlags::ADJUST_COORDINATE_SPACE)
+                    {
+                        return Err(Error::CoordinateSpaceAdjustedTwice(target));
+                    }
+                    glsl_options.clone()
+                }
+                None => {
+                    let mut glsl_options = glsl::Options::default();
+                    if adjusted {
+                        glsl_options.writer_flags -= glsl::WriterFlags::ADJUST_COORDINATE_SPACE;
+                    }
+                    // Wide and narrow scalars are only available on desktop
+                    let wide = Capabilities::FLOAT16 | Capabilities::FLOAT64 | Capabilities::INT64;
+                    if scalar_capabilities(module).intersects(wide) {
+                        glsl_options.version = glsl::Version::Desktop(450);
+                    }
+                    glsl_options
+                }
+            };
+            let pipeline_options = glsl::PipelineOptions {
+                shader_stage: ep.stage,
+                entry_point: ep.name.clone(),
+                multiview: None,
+            };
+
+            let mut buffer = String::new();
+            let mut writer =
+                glsl::Writer::new(&mut buffer, module, info, &glsl_options, &pipeline_options)?;
+            let glsl_reflection = writer.write()?;
+
+            for (index, ep_reflection) in reflection.entry_points.iter_mut().enumerate() {
+                if index == ep_index {
+                    ep_reflection.output_name = Some(glsl_reflection.entry_point.clone());
+                } else {
+                    ep_reflection.output_name = None;
+                    warnings.push(format!(
+                        "entry point {:?} is not written, GLSL only holds {:?}",
+                        ep_reflection.name, ep.name
+                    ));
+                }
+            }
+            reflection.glsl = Some(glsl_reflection);
+            Output::Text(buffer)
+        }
+        #[cfg(feature = "hlsl-out")]
+        Target::Hlsl => {
+            use crate::back::hlsl;
+
+            let hlsl_options = options.hlsl.clone().unwrap_or_default();
+            let mut buffer = String::new();
+            let mut writer = hlsl::Writer::new(&mut buffer, &hlsl_options);
+            let hlsl_reflection = writer.write(module, info)?;
+            reflect_names(
+                &mut reflection,
+                &mut warnings,
+                hlsl_reflection.entry_point_names,
+            );
+            Output::Text(buffer)
+        }
+        #[cfg(feature = "wgsl-out")]
+        Target::Wgsl => Output::Text(crate::back::wgsl::write_string(module, info)?),
+    };
+
+    Ok(Artifacts {
+        output,
+        reflection,
+        warnings,
+    })
+}
+
+fn parse(input: Source, options: &TranslateOptions) -> Result<crate::Module, Error> {
+    let mut module = match input {
+        #[cfg(feature = "wgsl-in")]
+        Source::Wgsl(source) => crate::front::wgsl::parse_str(source)?,
+        #[cfg(feature = "glsl-in")]
+        Source::Glsl { source, stage } => {
+            let options = crate::front::glsl::Options::from_stage(stage);
+            crate::front::glsl::parse_str(source, &options)?
+        }
+        #[cfg(feature = "spv-in")]
+        Source::Spv(data) => {
+            crate::front::spv::parse_u8_slice(data, &crate::front::spv::Options::default())?
+        }
+    };
+    if options.zero_initialize_globals {
+        module.zero_initialize_globals();
+    }
+    module.adjust_coordinate_space(options.coordinate_space_adjustment);
+    Ok(module)
+}
+
+/// Returns the scalar width capabilities used by the types of `module`.
+#[cfg(any(feature = "msl-out", feature = "glsl-out"))]
+fn scalar_capabilities(module: &crate::Module) -> Capabilities {
+    use crate::{ScalarKind as Sk, TypeInner as Ti};
+
+    let mut capabilities = Capabilities::empty();
+    for (_, ty) in module.types.iter() {
+        let (kind, width) = match ty.inner {
+            Ti::Scalar { kind, width }
+            | Ti::Vector { kind, width, .. }
+            | Ti::ValuePointer { kind, width, .. } => (kind, width),
+            Ti::Matrix { width, .. } => (Sk::Float, width),
+            _ => continue,
+        };
+        capabilities |= match (kind, width) {
+            (Sk::Float, 2) => Capabilities::FLOAT16,
+            (Sk::Float, 8) => Capabilities::FLOAT64,
+            (Sk::Sint, 8) | (Sk::Uint, 8) => Capabilities::INT64,
+            _ => Capabilities::empty(),
+        };
+    }
+    capabilities
+}
+
+/// Fill in the output names of the entry points, turning the ones that
+/// failed to translate into warnings.
+#[cfg(any(feature = "msl-out", feature = "hlsl-out"))]
+fn reflect_names<E: std::fmt::Display>(
+    reflection: &mut Reflection,
+    warnings: &mut Vec<String>,
+    names: Vec<Result<String, E>>,
+) {
+    for (ep_reflection, name) in reflection.entry_points.iter_mut().zip(names) {
+        ep_reflection.output_name = match name {
+            Ok(name) => Some(name),
+            Err(error) => {
+                warnings.push(format!(
+                    "entry point {:?} is not written: {}",
+                    ep_reflection.name, error
+                ));
+                None
+            }
+        };
+    }
+}
+
+#[cfg(all(feature = "wgsl-in", feature = "spv-out", feature = "glsl-out"))]
+#[test]
+fn translate_wgsl() {
+    let source = "
+        [[stage(vertex)]]
+        fn vs_main([[location(0)]] pos: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return pos;
+        }
+        [[stage(fragment)]]
+        fn fs_main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+    let options = TranslateOptions::default();
+
+    let artifacts = translate(Source::Wgsl(source), Target::Spv, &options).unwrap();
+    assert_eq!(
+        artifacts.output.as_binary().unwrap()[0],
+        spirv::MAGIC_NUMBER
+    );
+    assert!(artifacts.warnings.is_empty());
+
+    let mut spv_options = crate::back::spv::Options::default();
+    spv_options.entry_point_names.insert(
+        (crate::ShaderStage::Fragment, "fs_main".to_string()),
+        "frag".to_string(),
+    );
+    spv_options.entry_point = Some((crate::ShaderStage::Fragment, "fs_main".to_string()));
+    let spv_translate_options = TranslateOptions {
+        spv: Some(spv_options),
+        ..TranslateOptions::default()
+    };
+    let artifacts = translate(Source::Wgsl(source), Target::Spv, &spv_translate_options).unwrap();
+    let names: Vec<_> = artifacts
+        .reflection
+        .entry_points
+        .iter()
+        .map(|ep| ep.output_name.as_deref())
+        .collect();
+    assert_eq!(names, [None, Some("frag")]);
+
+    let artifacts = translate(Source::Wgsl(source), Target::Glsl, &options).unwrap();
+    assert!(artifacts
+        .output
+        .as_text()
+        .unwrap()
+        .starts_with("#version 310 es"));
+    let names: Vec<_> = artifacts
+        .reflection
+        .entry_points
+        .iter()
+        .map(|ep| ep.output_name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("main"), None]);
+    assert_eq!(artifacts.warnings.len(), 1);
+
+    let options = TranslateOptions {
+        entry_point: Some("missing".to_string()),
+        ..TranslateOptions::default()
+    };
+    match translate(Source::Wgsl(source), Target::Glsl, &options) {
+        Err(Error::UnknownEntryPoint(ref name)) if name == "missing" => {}
+        other => panic!("unexpected result: {:?}", other.map(|a| a.output)),
+    }
+}
+
+#[cfg(all(feature = "wgsl-in", feature = "glsl-out", feature = "msl-out"))]
+#[test]
+fn translate_detects_features() {
+    let source = "
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            var x: f64;
+        }
+    ";
+    let options = TranslateOptions::default();
+
+    let artifacts = translate(Source::Wgsl(source), Target::Glsl, &options).unwrap();
+    assert!(artifacts
+        .output
+        .as_text()
+        .unwrap()
+        .starts_with("#version 450"));
+
+    match translate(Source::Wgsl(source), Target::Msl, &options) {
+        Err(Error::Validation(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|a| a.output)),
+    }
+}
+
+#[cfg(all(feature = "rayon", feature = "wgsl-in", feature = "glsl-out"))]
+#[test]
+fn translate_in_parallel() {
+    let source = "
+        [[stage(vertex)]]
+        fn vs_main([[location(0)]] pos: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return pos;
+        }
+        [[stage(fragment)]]
+        fn fs_main() -> [[location(0)]] vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+    let options = TranslateOptions::default();
+
+    let artifacts = translate_entry_points(Source::Wgsl(source), Target::Glsl, &options).unwrap();
+    let written: Vec<_> = artifacts
+        .iter()
+        .map(|artifacts| {
+            artifacts
+                .reflection
+                .entry_points
+                .iter()
+                .position(|ep| ep.output_name.is_some())
+        })
+        .collect();
+    assert_eq!(written, [Some(0), Some(1)]);
+
+    let inputs = [
+        Source::Wgsl(source),
+        Source::Wgsl("fn"),
+        Source::Wgsl(source),
+    ];
+    let results = translate_many(&inputs, Target::Glsl, &options);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().output, artifacts[0].output);
+    assert!(results[1].is_err());
+    assert!(results[2].is_ok());
+}
+
+#[cfg(all(feature = "wgsl-in", feature = "spv-out", feature = "glsl-out"))]
+#[test]
+fn translate_adjusted_coordinate_space() {
+    use crate::proc::CoordinateSpaceAdjustment;
+
+    let source = Source::Wgsl(
+        "
+        [[stage(vertex)]]
+        fn main([[location(0)]] pos: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+            return pos;
+        }
+    ",
+    );
+    let mut options = TranslateOptions {
+        coordinate_space_adjustment: CoordinateSpaceAdjustment::all(),
+        ..TranslateOptions::default()
+    };
+
+    let artifacts = translate(source, Target::Glsl, &options).unwrap();
+    assert!(!artifacts
+        .output
+        .as_text()
+        .unwrap()
+        .contains("gl_Position.yz"));
+    assert!(translate(source, Target::Spv, &options).is_ok());
+
+    options.spv = Some(crate::back::spv::Options::default());
+    match translate(source, Target::Spv, &options) {
+        Err(Error::CoordinateSpaceAdjustedTwice(Target::Spv)) => {}
+        other => panic!(
+            "unexpected result {:?}",
+            other.map(|artifacts| artifacts.output)
+        ),
+    }
+}
@@ -123,6 +123,8 @@ bitflags::bitflags! {
         const WRITE = 0x2;
         /// The information about the data is queried.
         const QUERY = 0x4;
+        /// The image or sampler is used for sampling.
+        const SAMPLE = 0x8;
     }
 }
 
@@ -141,6 +143,11 @@ pub struct ExpressionInfo {
     pub uniformity: Uniformity,
     pub ref_count: usize,
     assignable_global: Option<Handle<crate::GlobalVariable>>,
+    /// How the global variable this expression points into is used through it.
+    ///
+    /// The usage of a `Load` or `Store` is recorded on its pointer, and the
+    /// usage of an image operation on its image.
+    pub global_use: GlobalUse,
     pub ty: TypeResolution,
 }
 
@@ -150,6 +157,7 @@ impl ExpressionInfo {
             uniformity: Uniformity::new(),
             ref_count: 0,
             assignable_global: None,
+            global_use: GlobalUse::empty(),
             // this doesn't matter at this point, will be overwritten
             ty: TypeResolution::Value(crate::TypeInner::Scalar {
                 kind: crate::ScalarKind::Bool,
@@ -168,10 +176,19 @@ enum GlobalOrArgument {
 }
 
 impl crate::Expression {
-    fn to_global_or_argument(&self) -> Result<GlobalOrArgument, ExpressionError> {
+    fn to_global_or_argument(
+        &self,
+        expression_arena: &Arena<crate::Expression>,
+    ) -> Result<GlobalOrArgument, ExpressionError> {
         Ok(match *self {
             crate::Expression::GlobalVariable(var) => GlobalOrArgument::Global(var),
             crate::Expression::FunctionArgument(i) => GlobalOrArgument::Argument(i),
+            // elements of binding arrays are tracked as the whole array
+            crate::Expression::Access { base, .. }
+            | crate::Expression::AccessIndex { base, .. } => match expression_arena[base] {
+                crate::Expression::GlobalVariable(var) => GlobalOrArgument::Global(var),
+                _ => return Err(ExpressionError::ExpectedGlobalOrArgument),
+            },
             _ => return Err(ExpressionError::ExpectedGlobalOrArgument),
         })
     }
@@ -218,6 +235,21 @@ impl FunctionInfo {
     pub fn expression_count(&self) -> usize {
         self.expressions.len()
     }
+    /// Returns an iterator over the global variables used by this function,
+    /// together with the kind of usage.
+    ///
+    /// The usage includes all the functions called by this one, directly or
+    /// indirectly, so for an entry point this describes the complete set of
+    /// globals it can touch.
+    pub fn global_uses(
+        &self,
+    ) -> impl Iterator<Item = (Handle<crate::GlobalVariable>, GlobalUse)> + '_ {
+        self.global_uses
+            .iter()
+            .enumerate()
+            .filter(|&(_, usage)| !usage.is_empty())
+            .map(|(index, &usage)| (Handle::from_usize(index), usage))
+    }
     pub fn dominates_global_use(&self, other: &Self) -> bool {
         for (self_global_uses, other_global_uses) in
             self.global_uses.iter().zip(other.global_uses.iter())
@@ -268,6 +300,7 @@ impl FunctionInfo {
         info.ref_count += 1;
         // mark the used global as read
         if let Some(global) = info.assignable_global {
+            info.global_use |= global_use;
             self.global_uses[global.index()] |= global_use;
         }
         info.uniformity.non_uniform_result
@@ -300,6 +333,30 @@ impl FunctionInfo {
         info.uniformity.non_uniform_result
     }
 
+    /// Record the image and the sampler used together.
+    fn add_sampling(
+        &mut self,
+        image: Handle<crate::Expression>,
+        sampler: Handle<crate::Expression>,
+        expression_arena: &Arena<crate::Expression>,
+    ) -> Result<(), ExpressionError> {
+        let image_storage = expression_arena[image].to_global_or_argument(expression_arena)?;
+        let sampler_storage = expression_arena[sampler].to_global_or_argument(expression_arena)?;
+
+        match (image_storage, sampler_storage) {
+            (GlobalOrArgument::Global(image), GlobalOrArgument::Global(sampler)) => {
+                self.sampling_set.insert(SamplingKey { image, sampler });
+            }
+            _ => {
+                self.sampling.insert(Sampling {
+                    image: image_storage,
+                    sampler: sampler_storage,
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Inherit information from a called function.
     fn process_call(
         &mut self,
@@ -316,7 +373,7 @@ impl FunctionInfo {
                 GlobalOrArgument::Argument(i) => {
                     let handle = arguments[i as usize];
                     expression_arena[handle]
-                        .to_global_or_argument()
+                        .to_global_or_argument(expression_arena)
                         .map_err(|error| FunctionError::Expression { handle, error })?
                 }
             };
@@ -326,7 +383,7 @@ impl FunctionInfo {
                 GlobalOrArgument::Argument(i) => {
                     let handle = arguments[i as usize];
                     expression_arena[handle]
-                        .to_global_or_argument()
+                        .to_global_or_argument(expression_arena)
                         .map_err(|error| FunctionError::Expression { handle, error })?
                 }
             };
@@ -404,7 +461,10 @@ impl FunctionInfo {
                     Some(crate::Binding::BuiltIn(built_in)) => match built_in {
                         // per-polygon built-ins are uniform
                         crate::BuiltIn::FrontFacing
+                        // per-view built-ins are uniform
+                        | crate::BuiltIn::ViewIndex
                         // per-work-group built-ins are uniform
+                        | crate::BuiltIn::NumWorkGroups
                         | crate::BuiltIn::WorkGroupId
                         | crate::BuiltIn::WorkGroupSize => true,
                         _ => false,
@@ -427,8 +487,9 @@ impl FunctionInfo {
                 assignable_global = Some(gh);
                 let var = &resolve_context.global_vars[gh];
                 let uniform = match var.class {
-                    // local data is non-uniform
-                    Sc::Function | Sc::Private => false,
+                    // local data is non-uniform, and physical buffers
+                    // aren't accessible through global variables
+                    Sc::Function | Sc::Private | Sc::PhysicalStorageBuffer => false,
                     // workgroup memory is exclusively accessed by the group
                     Sc::WorkGroup => true,
                     // uniform data
@@ -454,26 +515,14 @@ impl FunctionInfo {
             E::ImageSample {
                 image,
                 sampler,
+                gather: _,
                 coordinate,
                 array_index,
                 offset: _,
                 level,
                 depth_ref,
             } => {
-                let image_storage = expression_arena[image].to_global_or_argument()?;
-                let sampler_storage = expression_arena[sampler].to_global_or_argument()?;
-
-                match (image_storage, sampler_storage) {
-                    (GlobalOrArgument::Global(image), GlobalOrArgument::Global(sampler)) => {
-                        self.sampling_set.insert(SamplingKey { image, sampler });
-                    }
-                    _ => {
-                        self.sampling.insert(Sampling {
-                            image: image_storage,
-                            sampler: sampler_storage,
-                        });
-                    }
-                }
+                self.add_sampling(image, sampler, expression_arena)?;
 
                 // "nur" == "Non-Uniform Result"
                 let array_nur = array_index.and_then(|h| self.add_ref(h));
@@ -485,8 +534,8 @@ impl FunctionInfo {
                 let dref_nur = depth_ref.and_then(|h| self.add_ref(h));
                 Uniformity {
                     non_uniform_result: self
-                        .add_ref(image)
-                        .or(self.add_ref(sampler))
+                        .add_ref_impl(image, GlobalUse::SAMPLE)
+                        .or(self.add_ref_impl(sampler, GlobalUse::SAMPLE))
                         .or(self.add_ref(coordinate))
                         .or(array_nur)
                         .or(level_nur)
@@ -516,13 +565,26 @@ impl FunctionInfo {
                 }
             }
             E::ImageQuery { image, query } => {
-                let query_nur = match query {
-                    crate::ImageQuery::Size { level: Some(h) } => self.add_ref(h),
-                    _ => None,
+                let (query_nur, requirements) = match query {
+                    crate::ImageQuery::Size { level: Some(h) } => {
+                        (self.add_ref(h), UniformityRequirements::empty())
+                    }
+                    crate::ImageQuery::Lod {
+                        sampler,
+                        coordinate,
+                    } => {
+                        self.add_sampling(image, sampler, expression_arena)?;
+                        let nur = self
+                            .add_ref_impl(sampler, GlobalUse::SAMPLE)
+                            .or(self.add_ref(coordinate));
+                        // the level is computed from the implicit derivatives
+                        (nur, UniformityRequirements::IMPLICIT_LEVEL)
+                    }
+                    _ => (None, UniformityRequirements::empty()),
                 };
                 Uniformity {
                     non_uniform_result: self.add_ref_impl(image, GlobalUse::QUERY).or(query_nur),
-                    requirements: UniformityRequirements::empty(),
+                    requirements,
                 }
             }
             E::Unary { expr, .. } => Uniformity {
@@ -568,6 +630,10 @@ impl FunctionInfo {
                 non_uniform_result: self.add_ref(expr),
                 requirements: UniformityRequirements::empty(),
             },
+            E::PointerCast { expr, .. } => Uniformity {
+                non_uniform_result: self.add_ref(expr),
+                requirements: UniformityRequirements::empty(),
+            },
             E::Call(function) => {
                 let info = other_functions
                     .get(function.index())
@@ -586,6 +652,7 @@ impl FunctionInfo {
             uniformity,
             ref_count: 0,
             assignable_global,
+            global_use: GlobalUse::empty(),
             ty,
         };
         Ok(())
@@ -834,6 +901,32 @@ impl ModuleInfo {
     pub fn get_entry_point(&self, index: usize) -> &FunctionInfo {
         &self.entry_points[index]
     }
+
+    /// Returns the usage of a global variable by the entry point at `index`,
+    /// accumulated over the whole call graph of that entry point.
+    pub fn get_entry_point_info(
+        &self,
+        index: usize,
+        global: Handle<crate::GlobalVariable>,
+    ) -> GlobalUse {
+        self.entry_points[index][global]
+    }
+
+    /// Returns the set of shader stages, whose entry points use the global variable.
+    pub fn global_variable_stages(
+        &self,
+        module: &crate::Module,
+        global: Handle<crate::GlobalVariable>,
+    ) -> ShaderStages {
+        module
+            .entry_points
+            .iter()
+            .zip(self.entry_points.iter())
+            .filter(|&(_, info)| !info[global].is_empty())
+            .fold(ShaderStages::empty(), |stages, (ep, _)| {
+                stages | ShaderStages::from(ep.stage)
+            })
+    }
 }
 
 #[test]
@@ -849,8 +942,8 @@ fn uniform_control_flow() {
             value: crate::ScalarValue::Uint(0),
         },
     });
-    let mut type_arena = Arena::new();
-    let ty = type_arena.append(crate::Type {
+    let mut type_arena = crate::UniqueArena::new();
+    let ty = type_arena.insert(crate::Type {
         name: None,
         inner: crate::TypeInner::Vector {
             size: crate::VectorSize::Bi,
@@ -882,6 +975,7 @@ fn uniform_control_flow() {
     // checks the non-uniform control flow
     let derivative_expr = expressions.append(E::Derivative {
         axis: crate::DerivativeAxis::X,
+        ctrl: crate::DerivativeControl::None,
         expr: constant_expr,
     });
     let emit_range_constant_derivative = expressions.range_from(0);
@@ -1022,4 +1116,8 @@ fn uniform_control_flow() {
         }),
     );
     assert_eq!(info[non_uniform_global], GlobalUse::READ | GlobalUse::WRITE);
+    assert_eq!(
+        info[access_expr].global_use,
+        GlobalUse::READ | GlobalUse::WRITE
+    );
 }
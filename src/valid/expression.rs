@@ -1,6 +1,6 @@
 use super::{compose::validate_compose, ComposeError, FunctionInfo, ShaderStages, TypeFlags};
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Handle, UniqueArena},
     proc::{ProcError, ResolveError},
 };
 
@@ -43,6 +43,8 @@ pub enum ExpressionError {
     Compose(#[from] ComposeError),
     #[error(transparent)]
     Proc(#[from] ProcError),
+    #[error("Operand {0:?} is a pointer, it has to be loaded first")]
+    PointerOperand(Handle<crate::Expression>),
     #[error("Operation {0:?} can't work with {1:?}")]
     InvalidUnaryOperandType(crate::UnaryOperator, Handle<crate::Expression>),
     #[error("Operation {0:?} can't work with {1:?} and {2:?}")]
@@ -95,6 +97,12 @@ pub enum ExpressionError {
     InvalidDepthReference(Handle<crate::Expression>),
     #[error("Sample level is not compatible with the image dimension {0:?}")]
     InvalidSampleLevel(crate::ImageDimension),
+    #[error("Gather is not possible on images of dimension {0:?}")]
+    InvalidGatherDimension(crate::ImageDimension),
+    #[error("Gather of component {0:?} is not possible on images of class {1:?}")]
+    InvalidGatherComponent(crate::SwizzleComponent, crate::ImageClass),
+    #[error("Gather has to sample level zero")]
+    InvalidGatherLevel,
     #[error("Sample level (exact) type {0:?} is not a scalar float")]
     InvalidSampleLevelExactType(Handle<crate::Expression>),
     #[error("Sample level (bias) type {0:?} is not a scalar float")]
@@ -103,6 +111,8 @@ pub enum ExpressionError {
     InvalidSampleLevelGradientType(crate::ImageDimension, Handle<crate::Expression>),
     #[error("Unable to cast")]
     InvalidCastArgument,
+    #[error("Unable to cast {0:?} between a physical storage buffer pointer and an address of type {1:?}")]
+    InvalidPointerCast(Handle<crate::Expression>, Handle<crate::Type>),
     #[error("Invalid argument count for {0:?}")]
     WrongArgumentCount(crate::MathFunction),
     #[error("Argument [{1}] to {0:?} as expression {2:?} has an invalid type.")]
@@ -111,7 +121,7 @@ pub enum ExpressionError {
 
 struct ExpressionTypeResolver<'a> {
     root: Handle<crate::Expression>,
-    types: &'a Arena<crate::Type>,
+    types: &'a UniqueArena<crate::Type>,
     info: &'a FunctionInfo,
 }
 
@@ -126,6 +136,44 @@ impl<'a> ExpressionTypeResolver<'a> {
             Err(ExpressionError::ForwardDependency(handle))
         }
     }
+
+    /// Resolve the type of an operand, which has to be a value and not a pointer.
+    fn resolve_value(
+        &self,
+        handle: Handle<crate::Expression>,
+    ) -> Result<&'a crate::TypeInner, ExpressionError> {
+        match *self.resolve(handle)? {
+            crate::TypeInner::Pointer { .. } | crate::TypeInner::ValuePointer { .. } => {
+                Err(ExpressionError::PointerOperand(handle))
+            }
+            ref inner => Ok(inner),
+        }
+    }
+}
+
+/// Returns the type of an image or sampler operand, which has to be a global
+/// variable, a function argument, or an element of a binding array.
+fn resolve_handle_type(
+    operand: Handle<crate::Expression>,
+    function: &crate::Function,
+    module: &crate::Module,
+) -> Result<Handle<crate::Type>, ExpressionError> {
+    match function.expressions[operand] {
+        crate::Expression::GlobalVariable(var_handle) => Ok(module.global_variables[var_handle].ty),
+        crate::Expression::FunctionArgument(i) => Ok(function.arguments[i as usize].ty),
+        crate::Expression::Access { base, .. } | crate::Expression::AccessIndex { base, .. } => {
+            match function.expressions[base] {
+                crate::Expression::GlobalVariable(var_handle) => {
+                    match module.types[module.global_variables[var_handle].ty].inner {
+                        crate::TypeInner::BindingArray { base, .. } => Ok(base),
+                        _ => Err(ExpressionError::ExpectedGlobalVariable),
+                    }
+                }
+                _ => Err(ExpressionError::ExpectedGlobalVariable),
+            }
+        }
+        _ => Err(ExpressionError::ExpectedGlobalVariable),
+    }
 }
 
 impl super::Validator {
@@ -151,7 +199,7 @@ impl super::Validator {
                 let base_type = resolver.resolve(base)?;
                 // See the documentation for `Expression::Access`.
                 let dynamic_indexing_restricted = match *base_type {
-                    Ti::Vector { .. } => false,
+                    Ti::Vector { .. } | Ti::BindingArray { .. } => false,
                     Ti::Matrix { .. } | Ti::Array { .. } => true,
                     Ti::Pointer { .. } | Ti::ValuePointer { size: Some(_), .. } => false,
                     ref other => {
@@ -227,8 +275,13 @@ impl super::Validator {
                         Ti::Array {
                             size: crate::ArraySize::Constant(handle),
                             ..
+                        }
+                        | Ti::BindingArray {
+                            size: crate::ArraySize::Constant(handle),
+                            ..
                         } => module.constants[handle].to_array_length().unwrap(),
-                        Ti::Array { .. } => !0, // can't statically know, but need run-time checks
+                        // can't statically know, but need run-time checks
+                        Ti::Array { .. } | Ti::BindingArray { .. } => !0,
                         Ti::Pointer { base, .. } if top_level => {
                             resolve_index_limit(module, top, &module.types[base].inner, false)?
                         }
@@ -334,6 +387,7 @@ impl super::Validator {
             E::ImageSample {
                 image,
                 sampler,
+                gather,
                 coordinate,
                 array_index,
                 offset,
@@ -341,20 +395,8 @@ impl super::Validator {
                 depth_ref,
             } => {
                 // check the validity of expressions
-                let image_ty = match function.expressions[image] {
-                    crate::Expression::GlobalVariable(var_handle) => {
-                        module.global_variables[var_handle].ty
-                    }
-                    crate::Expression::FunctionArgument(i) => function.arguments[i as usize].ty,
-                    _ => return Err(ExpressionError::ExpectedGlobalVariable),
-                };
-                let sampler_ty = match function.expressions[sampler] {
-                    crate::Expression::GlobalVariable(var_handle) => {
-                        module.global_variables[var_handle].ty
-                    }
-                    crate::Expression::FunctionArgument(i) => function.arguments[i as usize].ty,
-                    _ => return Err(ExpressionError::ExpectedGlobalVariable),
-                };
+                let image_ty = resolve_handle_type(image, function, module)?;
+                let sampler_ty = resolve_handle_type(sampler, function, module)?;
                 let comparison = match module.types[sampler_ty].inner {
                     Ti::Sampler { comparison } => comparison,
                     _ => return Err(ExpressionError::ExpectedSamplerType(sampler_ty)),
@@ -390,7 +432,16 @@ impl super::Validator {
                         kind: crate::ScalarKind::Float,
                         multi: false,
                     } => false,
-                    crate::ImageClass::Depth => true,
+                    // integer texels can be gathered, but not filtered
+                    crate::ImageClass::Sampled {
+                        kind: crate::ScalarKind::Sint,
+                        multi: false,
+                    }
+                    | crate::ImageClass::Sampled {
+                        kind: crate::ScalarKind::Uint,
+                        multi: false,
+                    } if gather.is_some() => false,
+                    crate::ImageClass::Depth { multi: false } => true,
                     _ => return Err(ExpressionError::InvalidImageClass(class)),
                 };
                 if comparison != depth_ref.is_some() || (comparison && !image_depth) {
@@ -401,6 +452,22 @@ impl super::Validator {
                     });
                 }
 
+                // check gather properties
+                if let Some(component) = gather {
+                    match dim {
+                        crate::ImageDimension::D2 | crate::ImageDimension::Cube => {}
+                        crate::ImageDimension::D1 | crate::ImageDimension::D3 => {
+                            return Err(ExpressionError::InvalidGatherDimension(dim))
+                        }
+                    }
+                    if image_depth && component != crate::SwizzleComponent::X {
+                        return Err(ExpressionError::InvalidGatherComponent(component, class));
+                    }
+                    if level != crate::SampleLevel::Zero {
+                        return Err(ExpressionError::InvalidGatherLevel);
+                    }
+                }
+
                 // check texture coordinates type
                 let num_components = match dim {
                     crate::ImageDimension::D1 => 1,
@@ -457,7 +524,7 @@ impl super::Validator {
                 let can_level = match class {
                     crate::ImageClass::Sampled { multi, .. } => !multi,
                     crate::ImageClass::Storage { .. } => unreachable!(),
-                    crate::ImageClass::Depth { .. } => true,
+                    crate::ImageClass::Depth { multi } => !multi,
                 };
                 match level {
                     // require `can_level` here?
@@ -519,13 +586,7 @@ impl super::Validator {
                 array_index,
                 index,
             } => {
-                let ty = match function.expressions[image] {
-                    crate::Expression::GlobalVariable(var_handle) => {
-                        module.global_variables[var_handle].ty
-                    }
-                    crate::Expression::FunctionArgument(i) => function.arguments[i as usize].ty,
-                    _ => return Err(ExpressionError::ExpectedGlobalVariable),
-                };
+                let ty = resolve_handle_type(image, function, module)?;
                 match module.types[ty].inner {
                     Ti::Image {
                         class,
@@ -574,38 +635,72 @@ impl super::Validator {
                 ShaderStages::all()
             }
             E::ImageQuery { image, query } => {
-                let ty = match function.expressions[image] {
-                    crate::Expression::GlobalVariable(var_handle) => {
-                        module.global_variables[var_handle].ty
-                    }
-                    crate::Expression::FunctionArgument(i) => function.arguments[i as usize].ty,
-                    _ => return Err(ExpressionError::ExpectedGlobalVariable),
-                };
-                match module.types[ty].inner {
-                    Ti::Image { class, arrayed, .. } => {
+                let ty = resolve_handle_type(image, function, module)?;
+                let dim = match module.types[ty].inner {
+                    Ti::Image {
+                        class,
+                        arrayed,
+                        dim,
+                    } => {
                         let can_level = match class {
                             crate::ImageClass::Sampled { multi, .. } => !multi,
                             crate::ImageClass::Storage { .. } => false,
-                            crate::ImageClass::Depth { .. } => true,
+                            crate::ImageClass::Depth { multi } => !multi,
                         };
                         let good = match query {
                             crate::ImageQuery::NumLayers => arrayed,
                             crate::ImageQuery::Size { level: None } => true,
                             crate::ImageQuery::Size { level: Some(_) }
-                            | crate::ImageQuery::NumLevels => can_level,
+                            | crate::ImageQuery::NumLevels
+                            | crate::ImageQuery::Lod { .. } => can_level,
                             crate::ImageQuery::NumSamples => !can_level,
                         };
                         if !good {
                             return Err(ExpressionError::InvalidImageClass(class));
                         }
+                        dim
                     }
                     _ => return Err(ExpressionError::ExpectedImageType(ty)),
+                };
+                match query {
+                    crate::ImageQuery::Lod {
+                        sampler,
+                        coordinate,
+                    } => {
+                        let sampler_ty = resolve_handle_type(sampler, function, module)?;
+                        match module.types[sampler_ty].inner {
+                            Ti::Sampler { .. } => {}
+                            _ => return Err(ExpressionError::ExpectedSamplerType(sampler_ty)),
+                        }
+                        let num_components = match dim {
+                            crate::ImageDimension::D1 => 1,
+                            crate::ImageDimension::D2 => 2,
+                            crate::ImageDimension::D3 | crate::ImageDimension::Cube => 3,
+                        };
+                        match *resolver.resolve(coordinate)? {
+                            Ti::Scalar {
+                                kind: Sk::Float, ..
+                            } if num_components == 1 => {}
+                            Ti::Vector {
+                                size,
+                                kind: Sk::Float,
+                                ..
+                            } if size as u32 == num_components => {}
+                            _ => {
+                                return Err(ExpressionError::InvalidImageCoordinateType(
+                                    dim, coordinate,
+                                ))
+                            }
+                        }
+                        // the level is computed from the implicit derivatives
+                        ShaderStages::FRAGMENT
+                    }
+                    _ => ShaderStages::all(),
                 }
-                ShaderStages::all()
             }
             E::Unary { op, expr } => {
                 use crate::UnaryOperator as Uo;
-                let inner = resolver.resolve(expr)?;
+                let inner = resolver.resolve_value(expr)?;
                 match (op, inner.scalar_kind()) {
                     (_, Some(Sk::Sint))
                     | (_, Some(Sk::Bool))
@@ -621,8 +716,8 @@ impl super::Validator {
             }
             E::Binary { op, left, right } => {
                 use crate::BinaryOperator as Bo;
-                let left_inner = resolver.resolve(left)?;
-                let right_inner = resolver.resolve(right)?;
+                let left_inner = resolver.resolve_value(left)?;
+                let right_inner = resolver.resolve_value(right)?;
                 let good = match op {
                     Bo::Add | Bo::Subtract => match *left_inner {
                         Ti::Scalar { kind, .. } | Ti::Vector { kind, .. } => match kind {
@@ -813,7 +908,7 @@ impl super::Validator {
                 }
                 ShaderStages::all()
             }
-            E::Derivative { axis: _, expr } => {
+            E::Derivative { expr, .. } => {
                 match *resolver.resolve(expr)? {
                     Ti::Scalar {
                         kind: Sk::Float, ..
@@ -859,9 +954,9 @@ impl super::Validator {
             } => {
                 use crate::MathFunction as Mf;
 
-                let arg_ty = resolver.resolve(arg)?;
-                let arg1_ty = arg1.map(|expr| resolver.resolve(expr)).transpose()?;
-                let arg2_ty = arg2.map(|expr| resolver.resolve(expr)).transpose()?;
+                let arg_ty = resolver.resolve_value(arg)?;
+                let arg1_ty = arg1.map(|expr| resolver.resolve_value(expr)).transpose()?;
+                let arg2_ty = arg2.map(|expr| resolver.resolve_value(expr)).transpose()?;
                 match fun {
                     Mf::Abs => {
                         if arg1_ty.is_some() | arg2_ty.is_some() {
@@ -1147,6 +1242,48 @@ impl super::Validator {
                             _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
                         }
                     }
+                    Mf::Pack4x8snorm | Mf::Pack4x8unorm => {
+                        if arg1_ty.is_some() | arg2_ty.is_some() {
+                            return Err(ExpressionError::WrongArgumentCount(fun));
+                        }
+                        match *arg_ty {
+                            Ti::Vector {
+                                size: crate::VectorSize::Quad,
+                                kind: Sk::Float,
+                                width: 4,
+                            } => {}
+                            _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
+                        }
+                    }
+                    Mf::Pack2x16snorm | Mf::Pack2x16unorm | Mf::Pack2x16float => {
+                        if arg1_ty.is_some() | arg2_ty.is_some() {
+                            return Err(ExpressionError::WrongArgumentCount(fun));
+                        }
+                        match *arg_ty {
+                            Ti::Vector {
+                                size: crate::VectorSize::Bi,
+                                kind: Sk::Float,
+                                width: 4,
+                            } => {}
+                            _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
+                        }
+                    }
+                    Mf::Unpack4x8snorm
+                    | Mf::Unpack4x8unorm
+                    | Mf::Unpack2x16snorm
+                    | Mf::Unpack2x16unorm
+                    | Mf::Unpack2x16float => {
+                        if arg1_ty.is_some() | arg2_ty.is_some() {
+                            return Err(ExpressionError::WrongArgumentCount(fun));
+                        }
+                        match *arg_ty {
+                            Ti::Scalar {
+                                kind: Sk::Uint,
+                                width: 4,
+                            } => {}
+                            _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
+                        }
+                    }
                 }
                 ShaderStages::all()
             }
@@ -1172,6 +1309,39 @@ impl super::Validator {
                 }
                 ShaderStages::all()
             }
+            E::PointerCast { expr, ty } => {
+                let is_address = |inner: &crate::TypeInner| match *inner {
+                    Ti::Scalar {
+                        kind: Sk::Uint,
+                        width: 8,
+                    }
+                    | Ti::Vector {
+                        size: crate::VectorSize::Bi,
+                        kind: Sk::Uint,
+                        width: 4,
+                    } => true,
+                    _ => false,
+                };
+                let is_physical_pointer = |inner: &crate::TypeInner| match *inner {
+                    Ti::Pointer {
+                        class: crate::StorageClass::PhysicalStorageBuffer,
+                        ..
+                    } => true,
+                    _ => false,
+                };
+                let source = resolver.resolve(expr)?;
+                let target = &resolver
+                    .types
+                    .try_get(ty)
+                    .ok_or(ExpressionError::InvalidCastArgument)?
+                    .inner;
+                if !(is_address(source) && is_physical_pointer(target)
+                    || is_physical_pointer(source) && is_address(target))
+                {
+                    return Err(ExpressionError::InvalidPointerCast(expr, ty));
+                }
+                ShaderStages::all()
+            }
             E::Call(function) => other_infos[function.index()].available_stages,
             E::ArrayLength(expr) => match *resolver.resolve(expr)? {
                 Ti::Pointer { base, .. } => {
@@ -2,7 +2,7 @@ use super::{
     analyzer::{UniformityDisruptor, UniformityRequirements},
     ExpressionError, FunctionInfo, ModuleInfo, ShaderStages, TypeFlags, ValidationFlags,
 };
-use crate::arena::{Arena, Handle};
+use crate::arena::{Arena, Handle, UniqueArena};
 use bit_set::BitSet;
 
 #[derive(Clone, Debug, thiserror::Error)]
@@ -63,6 +63,12 @@ pub enum FunctionError {
     },
     #[error("Argument '{name}' at index {index} has a type that can't be passed into functions.")]
     InvalidArgumentType { index: usize, name: String },
+    #[error(
+        "Argument '{name}' at index {index} has a binding, but the function isn't an entry point"
+    )]
+    UnexpectedArgumentBinding { index: usize, name: String },
+    #[error("The result has a binding, but the function isn't an entry point")]
+    UnexpectedResultBinding,
     #[error("There are instructions after `return`/`break`/`continue`")]
     InstructionsAfterReturn,
     #[error("The `break` is used outside of a `loop` or `switch` context")]
@@ -98,6 +104,8 @@ pub enum FunctionError {
         #[source]
         error: CallError,
     },
+    #[error("The function is recursive, following the call cycle {0:?}")]
+    Recursion(Vec<Handle<crate::Function>>),
     #[error(
         "Required uniformity of control flow for {0:?} in {1:?} is not fulfilled because of {2:?}"
     )]
@@ -124,7 +132,7 @@ struct BlockContext<'a> {
     abilities: ControlFlowAbility,
     info: &'a FunctionInfo,
     expressions: &'a Arena<crate::Expression>,
-    types: &'a Arena<crate::Type>,
+    types: &'a UniqueArena<crate::Type>,
     global_vars: &'a Arena<crate::GlobalVariable>,
     functions: &'a Arena<crate::Function>,
     prev_infos: &'a [FunctionInfo],
@@ -201,7 +209,112 @@ impl<'a> BlockContext<'a> {
     }
 }
 
+/// Collect the functions called by a block, including its nested blocks.
+fn collect_callees(statements: &[crate::Statement], callees: &mut Vec<Handle<crate::Function>>) {
+    use crate::Statement as S;
+    for statement in statements {
+        match *statement {
+            S::Block(ref block) => collect_callees(block, callees),
+            S::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                collect_callees(accept, callees);
+                collect_callees(reject, callees);
+            }
+            S::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases {
+                    collect_callees(&case.body, callees);
+                }
+                collect_callees(default, callees);
+            }
+            S::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                collect_callees(body, callees);
+                collect_callees(continuing, callees);
+            }
+            S::Call { function, .. } => callees.push(function),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+/// Depth-first walk over the call graph. Returns the cycle, if one is found.
+fn find_call_cycle(
+    function: Handle<crate::Function>,
+    callees: &[Vec<Handle<crate::Function>>],
+    states: &mut [VisitState],
+    path: &mut Vec<Handle<crate::Function>>,
+) -> Option<Vec<Handle<crate::Function>>> {
+    states[function.index()] = VisitState::InProgress;
+    path.push(function);
+    for &callee in callees[function.index()].iter() {
+        match states.get(callee.index()) {
+            Some(&VisitState::Unvisited) => {
+                if let Some(cycle) = find_call_cycle(callee, callees, states, path) {
+                    return Some(cycle);
+                }
+            }
+            Some(&VisitState::InProgress) => {
+                let start = path.iter().position(|&fun| fun == callee).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(callee);
+                return Some(cycle);
+            }
+            // invalid handles are reported by the call validation
+            Some(&VisitState::Done) | None => {}
+        }
+    }
+    path.pop();
+    states[function.index()] = VisitState::Done;
+    None
+}
+
 impl super::Validator {
+    /// Check that no function can end up calling itself, directly or indirectly.
+    ///
+    /// Returns the function where the cycle has been detected, together
+    /// with the cycle itself.
+    pub(super) fn validate_call_graph(
+        module: &crate::Module,
+    ) -> Result<(), (Handle<crate::Function>, FunctionError)> {
+        let callees = module
+            .functions
+            .iter()
+            .map(|(_, fun)| {
+                let mut callees = Vec::new();
+                collect_callees(&fun.body, &mut callees);
+                callees
+            })
+            .collect::<Vec<_>>();
+
+        let mut states = vec![VisitState::Unvisited; callees.len()];
+        let mut path = Vec::new();
+        for (handle, _) in module.functions.iter() {
+            if states[handle.index()] != VisitState::Unvisited {
+                continue;
+            }
+            if let Some(cycle) = find_call_cycle(handle, &callees, &mut states, &mut path) {
+                return Err((cycle[0], FunctionError::Recursion(cycle)));
+            }
+        }
+        Ok(())
+    }
+
     fn validate_call(
         &mut self,
         function: Handle<crate::Function>,
@@ -387,7 +500,14 @@ impl super::Validator {
                             crate::Expression::LocalVariable(_)
                             | crate::Expression::GlobalVariable(_)
                             | crate::Expression::FunctionArgument(_) => break,
-                            _ => return Err(FunctionError::InvalidStorePointer(current)),
+                            // physical pointers are values, and can come from anywhere
+                            _ => match *context.resolve_pointer_type(current)? {
+                                Ti::Pointer {
+                                    class: crate::StorageClass::PhysicalStorageBuffer,
+                                    ..
+                                } => break,
+                                _ => return Err(FunctionError::InvalidStorePointer(current)),
+                            },
                         }
                     }
 
@@ -426,18 +546,30 @@ impl super::Validator {
                 } => {
                     //Note: this code uses a lot of `FunctionError::InvalidImageStore`,
                     // and could probably be refactored.
-                    let var = match *context.get_expression(image)? {
+                    let image_ty = match *context.get_expression(image)? {
                         crate::Expression::GlobalVariable(var_handle) => {
-                            &context.global_vars[var_handle]
+                            Some(context.global_vars[var_handle].ty)
                         }
-                        _ => {
-                            return Err(FunctionError::InvalidImageStore(
-                                ExpressionError::ExpectedGlobalVariable,
-                            ))
+                        // elements of binding arrays
+                        crate::Expression::Access { base, .. }
+                        | crate::Expression::AccessIndex { base, .. } => {
+                            match *context.get_expression(base)? {
+                                crate::Expression::GlobalVariable(var_handle) => {
+                                    match context.types[context.global_vars[var_handle].ty].inner {
+                                        Ti::BindingArray { base, .. } => Some(base),
+                                        _ => None,
+                                    }
+                                }
+                                _ => None,
+                            }
                         }
-                    };
+                        _ => None,
+                    }
+                    .ok_or(FunctionError::InvalidImageStore(
+                        ExpressionError::ExpectedGlobalVariable,
+                    ))?;
 
-                    let value_ty = match context.types[var.ty].inner {
+                    let value_ty = match context.types[image_ty].inner {
                         Ti::Image {
                             class,
                             arrayed,
@@ -489,7 +621,7 @@ impl super::Validator {
                         }
                         _ => {
                             return Err(FunctionError::InvalidImageStore(
-                                ExpressionError::ExpectedImageType(var.ty),
+                                ExpressionError::ExpectedImageType(image_ty),
                             ))
                         }
                     };
@@ -527,7 +659,7 @@ impl super::Validator {
     fn validate_local_var(
         &self,
         var: &crate::LocalVariable,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
         constants: &Arena<crate::Constant>,
     ) -> Result<(), LocalVariableError> {
         log::debug!("var {:?}", var);
@@ -558,7 +690,34 @@ impl super::Validator {
         Ok(())
     }
 
-    pub(super) fn validate_function(
+    /// Validate a function that isn't an entry point.
+    ///
+    /// Only entry points have inputs and outputs, so the arguments and the
+    /// result of other functions can't be bound to built-ins or locations.
+    pub(super) fn validate_plain_function(
+        &mut self,
+        fun: &crate::Function,
+        module: &crate::Module,
+        mod_info: &ModuleInfo,
+    ) -> Result<FunctionInfo, FunctionError> {
+        for (index, argument) in fun.arguments.iter().enumerate() {
+            if argument.binding.is_some() {
+                return Err(FunctionError::UnexpectedArgumentBinding {
+                    index,
+                    name: argument.name.clone().unwrap_or_default(),
+                });
+            }
+        }
+        if let Some(crate::FunctionResult {
+            binding: Some(_), ..
+        }) = fun.result
+        {
+            return Err(FunctionError::UnexpectedResultBinding);
+        }
+        self.validate_function_impl(fun, module, mod_info)
+    }
+
+    pub(super) fn validate_function_impl(
         &mut self,
         fun: &crate::Function,
         module: &crate::Module,
@@ -618,3 +777,121 @@ impl super::Validator {
         Ok(info)
     }
 }
+
+#[test]
+fn recursive_call_cycle() {
+    let mut module = crate::Module::default();
+    let fun_a = module.functions.append(crate::Function {
+        name: Some("a".to_string()),
+        ..Default::default()
+    });
+    let fun_b = module.functions.append(crate::Function {
+        name: Some("b".to_string()),
+        ..Default::default()
+    });
+    module
+        .functions
+        .get_mut(fun_a)
+        .body
+        .push(crate::Statement::Call {
+            function: fun_b,
+            arguments: Vec::new(),
+            result: None,
+        });
+    module
+        .functions
+        .get_mut(fun_b)
+        .body
+        .push(crate::Statement::Loop {
+            body: vec![crate::Statement::Call {
+                function: fun_a,
+                arguments: Vec::new(),
+                result: None,
+            }],
+            continuing: Vec::new(),
+        });
+
+    match super::Validator::new(ValidationFlags::all(), super::Capabilities::empty())
+        .validate(&module)
+    {
+        Err(super::ValidationError::Function {
+            handle,
+            error: FunctionError::Recursion(cycle),
+            ..
+        }) => {
+            assert_eq!(handle, fun_a);
+            assert_eq!(cycle, vec![fun_a, fun_b, fun_a]);
+        }
+        other => panic!("unexpected validation result: {:?}", other),
+    }
+}
+
+#[test]
+fn append_function() {
+    let mut module = crate::Module::default();
+    let fun_a = module.functions.append(crate::Function {
+        name: Some("a".to_string()),
+        ..Default::default()
+    });
+    let mut validator = super::Validator::new(ValidationFlags::all(), super::Capabilities::empty());
+    let mut info = validator.validate(&module).unwrap();
+
+    // the new function uses a new type, and calls the existing function
+    let ty = module.types.insert(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let fun_b = module.functions.append(crate::Function {
+        name: Some("b".to_string()),
+        arguments: vec![crate::FunctionArgument {
+            name: Some("x".to_string()),
+            ty,
+            binding: None,
+        }],
+        body: vec![crate::Statement::Call {
+            function: fun_a,
+            arguments: Vec::new(),
+            result: None,
+        }],
+        ..Default::default()
+    });
+    validator
+        .validate_function(&module, fun_b, &mut info)
+        .unwrap();
+    assert_eq!(info[fun_b].available_stages, super::ShaderStages::all());
+
+    match validator.validate_function(&module, fun_a, &mut info) {
+        Err(super::ValidationError::UnexpectedFunction(handle)) => assert_eq!(handle, fun_a),
+        other => panic!("unexpected validation result: {:?}", other),
+    }
+
+    // calling itself is caught like any forward call
+    let fun_c = module.functions.append(crate::Function {
+        name: Some("c".to_string()),
+        ..Default::default()
+    });
+    module
+        .functions
+        .get_mut(fun_c)
+        .body
+        .push(crate::Statement::Call {
+            function: fun_c,
+            arguments: Vec::new(),
+            result: None,
+        });
+    match validator.validate_function(&module, fun_c, &mut info) {
+        Err(super::ValidationError::Function {
+            handle,
+            error:
+                FunctionError::InvalidCall {
+                    error: CallError::ForwardDeclaredFunction,
+                    ..
+                },
+            ..
+        }) => assert_eq!(handle, fun_c),
+        other => panic!("unexpected validation result: {:?}", other),
+    }
+}
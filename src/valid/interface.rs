@@ -3,12 +3,10 @@ use super::{
     Capabilities, Disalignment, FunctionError, ModuleInfo, ShaderStages, TypeFlags,
     ValidationFlags,
 };
-use crate::arena::{Arena, Handle};
+use crate::arena::{Arena, Handle, UniqueArena};
 
 use bit_set::BitSet;
 
-const MAX_WORKGROUP_SIZE: u32 = 0x4000;
-
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum GlobalVariableError {
     #[error("Usage isn't compatible with the storage class")]
@@ -29,8 +27,16 @@ pub enum GlobalVariableError {
     UnsupportedCapability(Capabilities),
     #[error("Binding decoration is missing or not applicable")]
     InvalidBinding,
+    #[error("Variables of the {0:?} storage class can't have an initializer")]
+    InitializerNotAllowed(crate::StorageClass),
+    #[error("Initializer doesn't match the variable type")]
+    InitializerType,
     #[error("Alignment requirements for this storage class are not met by {0:?}")]
     Alignment(Handle<crate::Type>, #[source] Disalignment),
+    #[error("Bind group {group} exceeds the limit of {limit} groups")]
+    BindGroupOutOfRange { group: u32, limit: u32 },
+    #[error("Binding {binding} exceeds the limit of {limit} bindings per group")]
+    BindingOutOfRange { binding: u32, limit: u32 },
 }
 
 #[derive(Clone, Debug, thiserror::Error)]
@@ -41,16 +47,34 @@ pub enum VaryingError {
     InvalidInterpolation,
     #[error("Interpolation must be specified on vertex shader outputs and fragment shader inputs")]
     MissingInterpolation,
-    #[error("Built-in {0:?} is not available at this stage")]
-    InvalidBuiltInStage(crate::BuiltIn),
+    #[error("Interpolation and sampling can only be specified on vertex shader outputs and fragment shader inputs")]
+    UnexpectedInterpolation,
+    #[error(
+        "Built-in {built_in:?} is not available as {} of the {stage:?} stage",
+        if *.output { "an output" } else { "an input" }
+    )]
+    InvalidBuiltInStage {
+        built_in: crate::BuiltIn,
+        stage: crate::ShaderStage,
+        output: bool,
+    },
     #[error("Built-in type for {0:?} is invalid")]
     InvalidBuiltInType(crate::BuiltIn),
     #[error("Entry point arguments and return values must all have bindings")]
     MissingBinding,
     #[error("Struct member {0} is missing a binding")]
     MemberMissingBinding(u32),
+    #[error("Struct member {index} '{name}' varying error")]
+    Member {
+        index: u32,
+        name: String,
+        #[source]
+        error: Box<VaryingError>,
+    },
     #[error("Multiple bindings at location {location} are present")]
     BindingCollision { location: u32 },
+    #[error("Location {location} exceeds the limit of {limit} locations")]
+    LocationOutOfRange { location: u32, limit: u32 },
     #[error("Built-in {0:?} is present more than once")]
     DuplicateBuiltIn(crate::BuiltIn),
     #[error("Capability {0:?} is not supported")]
@@ -65,14 +89,17 @@ pub enum EntryPointError {
     UnexpectedEarlyDepthTest,
     #[error("Workgroup size is not applicable")]
     UnexpectedWorkgroupSize,
-    #[error("Workgroup size is out of range")]
-    OutOfRangeWorkgroupSize,
+    #[error("Workgroup size {size:?} exceeds the limit of {limit:?}")]
+    OutOfRangeWorkgroupSize { size: [u32; 3], limit: [u32; 3] },
     #[error("Uses operations forbidden at this stage")]
     ForbiddenStageOperations,
     #[error("Global variable {0:?} is used incorrectly as {1:?}")]
     InvalidGlobalUsage(Handle<crate::GlobalVariable>, GlobalUse),
-    #[error("Bindings for {0:?} conflict with other resource")]
-    BindingCollision(Handle<crate::GlobalVariable>),
+    #[error("Global variables {first:?} and {second:?} are bound to the same resource")]
+    BindingCollision {
+        first: Handle<crate::GlobalVariable>,
+        second: Handle<crate::GlobalVariable>,
+    },
     #[error("Argument {0} varying error")]
     Argument(u32, #[source] VaryingError),
     #[error("Result varying error")]
@@ -98,8 +125,9 @@ struct VaryingContext<'a> {
     ty: Handle<crate::Type>,
     stage: crate::ShaderStage,
     output: bool,
-    types: &'a Arena<crate::Type>,
+    types: &'a UniqueArena<crate::Type>,
     location_mask: &'a mut BitSet,
+    location_limit: u32,
     built_in_mask: u32,
     capabilities: Capabilities,
 }
@@ -179,6 +207,14 @@ impl VaryingContext<'_> {
                                 width: crate::BOOL_WIDTH,
                             },
                     ),
+                    Bi::HelperInvocation => (
+                        self.stage == St::Fragment && !self.output,
+                        *ty_inner
+                            == Ti::Scalar {
+                                kind: Sk::Bool,
+                                width: crate::BOOL_WIDTH,
+                            },
+                    ),
                     Bi::PrimitiveIndex => {
                         if !self.capabilities.contains(Capabilities::PRIMITIVE_INDEX) {
                             return Err(VaryingError::UnsupportedCapability(
@@ -194,6 +230,21 @@ impl VaryingContext<'_> {
                                 },
                         )
                     }
+                    Bi::ViewIndex => {
+                        if !self.capabilities.contains(Capabilities::MULTIVIEW) {
+                            return Err(VaryingError::UnsupportedCapability(
+                                Capabilities::MULTIVIEW,
+                            ));
+                        }
+                        (
+                            self.stage != St::Compute && !self.output,
+                            *ty_inner
+                                == Ti::Scalar {
+                                    kind: Sk::Uint,
+                                    width,
+                                },
+                        )
+                    }
                     Bi::SampleIndex => (
                         self.stage == St::Fragment && !self.output,
                         *ty_inner
@@ -210,6 +261,15 @@ impl VaryingContext<'_> {
                                 width,
                             },
                     ),
+                    Bi::SamplePosition => (
+                        self.stage == St::Fragment && !self.output,
+                        *ty_inner
+                            == Ti::Vector {
+                                size: Vs::Bi,
+                                kind: Sk::Float,
+                                width,
+                            },
+                    ),
                     Bi::LocalInvocationIndex => (
                         self.stage == St::Compute && !self.output,
                         *ty_inner
@@ -220,6 +280,7 @@ impl VaryingContext<'_> {
                     ),
                     Bi::GlobalInvocationId
                     | Bi::LocalInvocationId
+                    | Bi::NumWorkGroups
                     | Bi::WorkGroupId
                     | Bi::WorkGroupSize => (
                         self.stage == St::Compute && !self.output,
@@ -233,7 +294,11 @@ impl VaryingContext<'_> {
                 };
 
                 if !visible {
-                    return Err(VaryingError::InvalidBuiltInStage(built_in));
+                    return Err(VaryingError::InvalidBuiltInStage {
+                        built_in,
+                        stage: self.stage,
+                        output: self.output,
+                    });
                 }
                 if !type_good {
                     log::warn!("Wrong builtin type: {:?}", ty_inner);
@@ -245,6 +310,12 @@ impl VaryingContext<'_> {
                 interpolation,
                 sampling,
             } => {
+                if location >= self.location_limit {
+                    return Err(VaryingError::LocationOutOfRange {
+                        location,
+                        limit: self.location_limit,
+                    });
+                }
                 if !self.location_mask.insert(location as usize) {
                     return Err(VaryingError::BindingCollision { location });
                 }
@@ -259,10 +330,15 @@ impl VaryingContext<'_> {
                     _ => false,
                 };
 
+                // Vertex shader inputs and fragment shader outputs aren't interpolated,
+                // and the backends would produce invalid code for these qualifiers.
+                if !needs_interpolation && (interpolation.is_some() || sampling.is_some()) {
+                    return Err(VaryingError::UnexpectedInterpolation);
+                }
+
                 // It doesn't make sense to specify a sampling when `interpolation` is `Flat`, but
                 // SPIR-V and GLSL both explicitly tolerate such combinations of decorators /
                 // qualifiers, so we won't complain about that here.
-                let _ = sampling;
 
                 match ty_inner.scalar_kind() {
                     Some(crate::ScalarKind::Float) => {
@@ -301,7 +377,15 @@ impl VaryingContext<'_> {
                                 None => {
                                     return Err(VaryingError::MemberMissingBinding(index as u32))
                                 }
-                                Some(ref binding) => self.validate_impl(binding)?,
+                                Some(ref binding) => {
+                                    self.validate_impl(binding).map_err(|error| {
+                                        VaryingError::Member {
+                                            index: index as u32,
+                                            name: member.name.clone().unwrap_or_default(),
+                                            error: Box::new(error),
+                                        }
+                                    })?
+                                }
                             }
                         }
                     }
@@ -317,13 +401,16 @@ impl super::Validator {
     pub(super) fn validate_global_var(
         &self,
         var: &crate::GlobalVariable,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
+        constants: &Arena<crate::Constant>,
     ) -> Result<(), GlobalVariableError> {
         log::debug!("var {:?}", var);
         let type_info = &self.types[var.ty.index()];
 
         let (allowed_storage_access, required_type_flags, is_resource) = match var.class {
-            crate::StorageClass::Function => return Err(GlobalVariableError::InvalidUsage),
+            crate::StorageClass::Function | crate::StorageClass::PhysicalStorageBuffer => {
+                return Err(GlobalVariableError::InvalidUsage)
+            }
             crate::StorageClass::Storage => {
                 if let Err((ty_handle, disalignment)) = type_info.storage_layout {
                     if self.flags.contains(ValidationFlags::STRUCT_LAYOUTS) {
@@ -342,17 +429,19 @@ impl super::Validator {
                         return Err(GlobalVariableError::Alignment(ty_handle, disalignment));
                     }
                 }
-                (
-                    crate::StorageAccess::empty(),
-                    TypeFlags::DATA
-                        | TypeFlags::SIZED
-                        | TypeFlags::HOST_SHARED
-                        | TypeFlags::TOP_LEVEL,
-                    true,
-                )
+                let mut required_type_flags =
+                    TypeFlags::DATA | TypeFlags::SIZED | TypeFlags::HOST_SHARED;
+                if !self.capabilities.contains(Capabilities::LOOSE_UNIFORMS) {
+                    required_type_flags |= TypeFlags::TOP_LEVEL;
+                }
+                (crate::StorageAccess::empty(), required_type_flags, true)
             }
             crate::StorageClass::Handle => {
-                let access = match types[var.ty].inner {
+                let handle_ty = match types[var.ty].inner {
+                    crate::TypeInner::BindingArray { base, .. } => base,
+                    _ => var.ty,
+                };
+                let access = match types[handle_ty].inner {
                     crate::TypeInner::Image {
                         class: crate::ImageClass::Storage(_),
                         ..
@@ -400,6 +489,42 @@ impl super::Validator {
         if is_resource != var.binding.is_some() {
             return Err(GlobalVariableError::InvalidBinding);
         }
+        if let Some(ref bind) = var.binding {
+            if bind.group >= self.limits.max_bind_groups {
+                return Err(GlobalVariableError::BindGroupOutOfRange {
+                    group: bind.group,
+                    limit: self.limits.max_bind_groups,
+                });
+            }
+            if bind.binding >= self.limits.max_bindings_per_bind_group {
+                return Err(GlobalVariableError::BindingOutOfRange {
+                    binding: bind.binding,
+                    limit: self.limits.max_bindings_per_bind_group,
+                });
+            }
+        }
+
+        if let Some(const_handle) = var.init {
+            if var.class != crate::StorageClass::Private {
+                return Err(GlobalVariableError::InitializerNotAllowed(var.class));
+            }
+            match constants[const_handle].inner {
+                crate::ConstantInner::Scalar { width, ref value } => {
+                    let ty_inner = crate::TypeInner::Scalar {
+                        width,
+                        kind: value.scalar_kind(),
+                    };
+                    if types[var.ty].inner != ty_inner {
+                        return Err(GlobalVariableError::InitializerType);
+                    }
+                }
+                crate::ConstantInner::Composite { ty, components: _ } => {
+                    if ty != var.ty {
+                        return Err(GlobalVariableError::InitializerType);
+                    }
+                }
+            }
+        }
 
         Ok(())
     }
@@ -414,24 +539,25 @@ impl super::Validator {
             return Err(EntryPointError::UnexpectedEarlyDepthTest);
         }
         if ep.stage == crate::ShaderStage::Compute {
+            let limit = self.limits.max_compute_workgroup_size;
             if ep
                 .workgroup_size
                 .iter()
-                .any(|&s| s == 0 || s > MAX_WORKGROUP_SIZE)
+                .zip(limit.iter())
+                .any(|(&size, &max)| size == 0 || size > max)
             {
-                return Err(EntryPointError::OutOfRangeWorkgroupSize);
+                return Err(EntryPointError::OutOfRangeWorkgroupSize {
+                    size: ep.workgroup_size,
+                    limit,
+                });
             }
         } else if ep.workgroup_size != [0; 3] {
             return Err(EntryPointError::UnexpectedWorkgroupSize);
         }
 
-        let stage_bit = match ep.stage {
-            crate::ShaderStage::Vertex => ShaderStages::VERTEX,
-            crate::ShaderStage::Fragment => ShaderStages::FRAGMENT,
-            crate::ShaderStage::Compute => ShaderStages::COMPUTE,
-        };
+        let stage_bit = ShaderStages::from(ep.stage);
 
-        let info = self.validate_function(&ep.function, module, mod_info)?;
+        let info = self.validate_function_impl(&ep.function, module, mod_info)?;
 
         if !info.available_stages.contains(stage_bit) {
             return Err(EntryPointError::ForbiddenStageOperations);
@@ -446,6 +572,10 @@ impl super::Validator {
                 output: false,
                 types: &module.types,
                 location_mask: &mut self.location_mask,
+                location_limit: match ep.stage {
+                    crate::ShaderStage::Vertex => self.limits.max_vertex_attributes,
+                    _ => !0,
+                },
                 built_in_mask: argument_built_ins,
                 capabilities: self.capabilities,
             };
@@ -462,6 +592,10 @@ impl super::Validator {
                 output: true,
                 types: &module.types,
                 location_mask: &mut self.location_mask,
+                location_limit: match ep.stage {
+                    crate::ShaderStage::Fragment => self.limits.max_color_attachments,
+                    _ => !0,
+                },
                 built_in_mask: 0,
                 capabilities: self.capabilities,
             };
@@ -469,9 +603,7 @@ impl super::Validator {
                 .map_err(EntryPointError::Result)?;
         }
 
-        for bg in self.bind_group_masks.iter_mut() {
-            bg.clear();
-        }
+        self.resource_bindings.clear();
         for (var_handle, var) in module.global_variables.iter() {
             let usage = info[var_handle];
             if usage.is_empty() {
@@ -479,7 +611,9 @@ impl super::Validator {
             }
 
             let allowed_usage = match var.class {
-                crate::StorageClass::Function => unreachable!(),
+                crate::StorageClass::Function | crate::StorageClass::PhysicalStorageBuffer => {
+                    unreachable!()
+                }
                 crate::StorageClass::Uniform => GlobalUse::READ | GlobalUse::QUERY,
                 crate::StorageClass::Storage => storage_usage(var.storage_access),
                 crate::StorageClass::Handle => match module.types[var.ty].inner {
@@ -487,7 +621,7 @@ impl super::Validator {
                         class: crate::ImageClass::Storage(_),
                         ..
                     } => storage_usage(var.storage_access),
-                    _ => GlobalUse::READ | GlobalUse::QUERY,
+                    _ => GlobalUse::READ | GlobalUse::QUERY | GlobalUse::SAMPLE,
                 },
                 crate::StorageClass::Private | crate::StorageClass::WorkGroup => GlobalUse::all(),
                 crate::StorageClass::PushConstant => GlobalUse::READ,
@@ -502,13 +636,16 @@ impl super::Validator {
                 return Err(EntryPointError::InvalidGlobalUsage(var_handle, usage));
             }
 
+            // The back ends declare each global with its own binding, so even
+            // globals viewing a resource the same way can't alias it
             if let Some(ref bind) = var.binding {
-                while self.bind_group_masks.len() <= bind.group as usize {
-                    self.bind_group_masks.push(BitSet::new());
-                }
-                if !self.bind_group_masks[bind.group as usize].insert(bind.binding as usize) {
-                    return Err(EntryPointError::BindingCollision(var_handle));
+                if let Some(&first) = self.resource_bindings.get(bind) {
+                    return Err(EntryPointError::BindingCollision {
+                        first,
+                        second: var_handle,
+                    });
                 }
+                self.resource_bindings.insert(bind.clone(), var_handle);
             }
         }
 
@@ -6,9 +6,9 @@ mod interface;
 mod r#type;
 
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
     proc::{InvalidBaseType, Layouter},
-    FastHashSet,
+    FastHashMap, FastHashSet,
 };
 use bit_set::BitSet;
 use std::ops;
@@ -60,6 +60,49 @@ bitflags::bitflags! {
         const FLOAT64 = 0x2;
         /// Support for `Builtin:PrimitiveIndex`.
         const PRIMITIVE_INDEX = 0x4;
+        /// Integer values with width = 8.
+        const INT64 = 0x8;
+        /// Support for `StorageClass::PhysicalStorageBuffer` pointers.
+        const PHYSICAL_STORAGE_BUFFER = 0x10;
+        /// Support for `Builtin::ViewIndex`.
+        const MULTIVIEW = 0x20;
+        /// Float values with width = 2.
+        const FLOAT16 = 0x40;
+        /// Support for `StorageClass::Uniform` globals that aren't structs,
+        /// like the loose uniforms of older GLSL.
+        const LOOSE_UNIFORMS = 0x80;
+    }
+}
+
+/// Limits of the target the module is validated for.
+///
+/// The defaults only bound the workgroup size, so that a module can be
+/// validated before its target is known.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Limits {
+    /// Number of locations available to the inputs of a vertex shader.
+    pub max_vertex_attributes: u32,
+    /// Number of locations available to the outputs of a fragment shader.
+    pub max_color_attachments: u32,
+    /// Number of bind groups, the resources can be bound to groups below it.
+    pub max_bind_groups: u32,
+    /// Number of bindings in each bind group.
+    pub max_bindings_per_bind_group: u32,
+    /// Largest size of a compute workgroup, in each dimension.
+    pub max_compute_workgroup_size: [u32; 3],
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_vertex_attributes: !0,
+            max_color_attachments: !0,
+            max_bind_groups: !0,
+            max_bindings_per_bind_group: !0,
+            max_compute_workgroup_size: [0x4000; 3],
+        }
     }
 }
 
@@ -74,6 +117,16 @@ bitflags::bitflags! {
     }
 }
 
+impl From<crate::ShaderStage> for ShaderStages {
+    fn from(stage: crate::ShaderStage) -> Self {
+        match stage {
+            crate::ShaderStage::Vertex => Self::VERTEX,
+            crate::ShaderStage::Fragment => Self::FRAGMENT,
+            crate::ShaderStage::Compute => Self::COMPUTE,
+        }
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
@@ -93,10 +146,11 @@ impl ops::Index<Handle<crate::Function>> for ModuleInfo {
 pub struct Validator {
     flags: ValidationFlags,
     capabilities: Capabilities,
+    limits: Limits,
     types: Vec<r#type::TypeInfo>,
     layouter: Layouter,
     location_mask: BitSet,
-    bind_group_masks: Vec<BitSet>,
+    resource_bindings: FastHashMap<crate::ResourceBinding, Handle<crate::GlobalVariable>>,
     select_cases: FastHashSet<i32>,
     valid_expression_list: Vec<Handle<crate::Expression>>,
     valid_expression_set: BitSet,
@@ -153,6 +207,8 @@ pub enum ValidationError {
         #[source]
         error: EntryPointError,
     },
+    #[error("Function {0:?} isn't the first one missing from the module info")]
+    UnexpectedFunction(Handle<crate::Function>),
     #[error("Module is corrupted")]
     Corrupted,
 }
@@ -170,7 +226,10 @@ impl crate::TypeInner {
             | Self::Pointer { .. }
             | Self::ValuePointer { .. }
             | Self::Struct { .. } => true,
-            Self::Array { .. } | Self::Image { .. } | Self::Sampler { .. } => false,
+            Self::Array { .. }
+            | Self::Image { .. }
+            | Self::Sampler { .. }
+            | Self::BindingArray { .. } => false,
         }
     }
 
@@ -202,21 +261,28 @@ impl Validator {
         Validator {
             flags,
             capabilities,
+            limits: Limits::default(),
             types: Vec::new(),
             layouter: Layouter::default(),
             location_mask: BitSet::new(),
-            bind_group_masks: Vec::new(),
+            resource_bindings: FastHashMap::default(),
             select_cases: FastHashSet::default(),
             valid_expression_list: Vec::new(),
             valid_expression_set: BitSet::new(),
         }
     }
 
+    /// Check the module against the `limits` of its target.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     fn validate_constant(
         &self,
         handle: Handle<crate::Constant>,
         constants: &Arena<crate::Constant>,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
     ) -> Result<(), ConstantError> {
         let con = &constants[handle];
         match con.inner {
@@ -251,9 +317,15 @@ impl Validator {
         Ok(())
     }
 
-    /// Check the given module to be valid.
-    pub fn validate(&mut self, module: &crate::Module) -> Result<ModuleInfo, ValidationError> {
-        self.reset_types(module.types.len());
+    /// Check the constants and the types of the module.
+    ///
+    /// Types are only checked starting from `first_type`, the ones before it
+    /// are expected to be checked already.
+    fn validate_types_and_constants(
+        &mut self,
+        module: &crate::Module,
+        first_type: usize,
+    ) -> Result<(), ValidationError> {
         self.layouter.update(&module.types, &module.constants)?;
 
         if self.flags.contains(ValidationFlags::CONSTANTS) {
@@ -267,7 +339,7 @@ impl Validator {
             }
         }
 
-        for (handle, ty) in module.types.iter() {
+        for (handle, ty) in module.types.iter().skip(first_type) {
             let ty_info = self
                 .validate_type(handle, &module.types, &module.constants)
                 .map_err(|error| ValidationError::Type {
@@ -277,9 +349,16 @@ impl Validator {
                 })?;
             self.types[handle.index()] = ty_info;
         }
+        Ok(())
+    }
+
+    /// Check the given module to be valid.
+    pub fn validate(&mut self, module: &crate::Module) -> Result<ModuleInfo, ValidationError> {
+        self.reset_types(module.types.len());
+        self.validate_types_and_constants(module, 0)?;
 
         for (var_handle, var) in module.global_variables.iter() {
-            self.validate_global_var(var, &module.types)
+            self.validate_global_var(var, &module.types, &module.constants)
                 .map_err(|error| ValidationError::GlobalVariable {
                     handle: var_handle,
                     name: var.name.clone().unwrap_or_default(),
@@ -287,13 +366,21 @@ impl Validator {
                 })?;
         }
 
+        if let Err((handle, error)) = Self::validate_call_graph(module) {
+            return Err(ValidationError::Function {
+                handle,
+                name: module.functions[handle].name.clone().unwrap_or_default(),
+                error,
+            });
+        }
+
         let mut mod_info = ModuleInfo {
             functions: Vec::with_capacity(module.functions.len()),
             entry_points: Vec::with_capacity(module.entry_points.len()),
         };
 
         for (handle, fun) in module.functions.iter() {
-            match self.validate_function(fun, module, &mod_info) {
+            match self.validate_plain_function(fun, module, &mod_info) {
                 Ok(info) => mod_info.functions.push(info),
                 Err(error) => {
                     return Err(ValidationError::Function {
@@ -329,4 +416,56 @@ impl Validator {
 
         Ok(mod_info)
     }
+
+    /// Check a function appended to a module that has already been validated.
+    ///
+    /// This is meant for tools editing a module interactively, which can
+    /// avoid validating the whole module again after adding a function.
+    /// `mod_info` has to be produced by the last [`validate`] call on this
+    /// validator, and `handle` has to be the first function not covered by
+    /// it. On success, the info of the function is added to `mod_info`, so
+    /// that the functions appended next are able to call it.
+    ///
+    /// Types appended to the module since the last call are validated here,
+    /// and so are the constants. Everything else is assumed to be unchanged:
+    /// modifying the existing types, global variables, functions or entry
+    /// points, as well as adding global variables or entry points, requires
+    /// a full [`validate`] call again.
+    ///
+    /// The function can only call the functions that come before it, so it
+    /// can't introduce any recursion in the call graph.
+    ///
+    /// [`validate`]: Validator::validate
+    pub fn validate_function(
+        &mut self,
+        module: &crate::Module,
+        handle: Handle<crate::Function>,
+        mod_info: &mut ModuleInfo,
+    ) -> Result<(), ValidationError> {
+        if handle.index() != mod_info.functions.len() {
+            return Err(ValidationError::UnexpectedFunction(handle));
+        }
+        let fun = module
+            .functions
+            .try_get(handle)
+            .ok_or(ValidationError::UnexpectedFunction(handle))?;
+
+        let first_type = self.types.len();
+        if module.types.len() < first_type {
+            return Err(ValidationError::Corrupted);
+        }
+        self.types
+            .resize(module.types.len(), r#type::TypeInfo::dummy());
+        self.validate_types_and_constants(module, first_type)?;
+
+        let info = self
+            .validate_plain_function(fun, module, mod_info)
+            .map_err(|error| ValidationError::Function {
+                handle,
+                name: fun.name.clone().unwrap_or_default(),
+                error,
+            })?;
+        mod_info.functions.push(info);
+        Ok(())
+    }
 }
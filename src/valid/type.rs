@@ -1,6 +1,6 @@
 use super::Capabilities;
 use crate::{
-    arena::{Arena, Handle},
+    arena::{Arena, Handle, UniqueArena},
     proc::Alignment,
 };
 
@@ -82,6 +82,8 @@ pub enum TypeError {
     InvalidBlockType(Handle<crate::Type>),
     #[error("Base type {0:?} for the array is invalid")]
     InvalidArrayBaseType(Handle<crate::Type>),
+    #[error("Base type {0:?} for the binding array is not an image or a sampler")]
+    InvalidBindingArrayBaseType(Handle<crate::Type>),
     #[error("The constant {0:?} can not be used for an array size")]
     InvalidArraySizeConstant(Handle<crate::Constant>),
     #[error("Array type {0:?} must have a length of one or more")]
@@ -103,6 +105,16 @@ pub enum TypeError {
     },
     #[error("The composite type contains a top-level structure")]
     NestedTopLevel,
+    #[error("Capability {0:?} is not supported")]
+    UnsupportedCapability(Capabilities),
+    #[error(
+        "Image with dimension {dim:?}, arrayed: {arrayed} and class {class:?} is not supported"
+    )]
+    UnsupportedImageType {
+        dim: crate::ImageDimension,
+        arrayed: bool,
+        class: crate::ImageClass,
+    },
 }
 
 // Only makes sense if `flags.contains(HOST_SHARED)`
@@ -144,7 +156,7 @@ pub(super) struct TypeInfo {
 }
 
 impl TypeInfo {
-    fn dummy() -> Self {
+    pub(super) fn dummy() -> Self {
         TypeInfo {
             flags: TypeFlags::empty(),
             uniform_layout: Ok(None),
@@ -167,9 +179,13 @@ impl super::Validator {
         match kind {
             crate::ScalarKind::Bool => width == crate::BOOL_WIDTH,
             crate::ScalarKind::Float => {
-                width == 4 || (width == 8 && self.capabilities.contains(Capabilities::FLOAT64))
+                width == 4
+                    || (width == 8 && self.capabilities.contains(Capabilities::FLOAT64))
+                    || (width == 2 && self.capabilities.contains(Capabilities::FLOAT16))
+            }
+            crate::ScalarKind::Sint | crate::ScalarKind::Uint => {
+                width == 4 || (width == 8 && self.capabilities.contains(Capabilities::INT64))
             }
-            crate::ScalarKind::Sint | crate::ScalarKind::Uint => width == 4,
         }
     }
 
@@ -182,7 +198,7 @@ impl super::Validator {
     pub(super) fn validate_type(
         &self,
         handle: Handle<crate::Type>,
-        types: &Arena<crate::Type>,
+        types: &UniqueArena<crate::Type>,
         constants: &Arena<crate::Constant>,
     ) -> Result<TypeInfo, TypeError> {
         use crate::TypeInner as Ti;
@@ -232,6 +248,44 @@ impl super::Validator {
                     count * (width as u32),
                 )
             }
+            Ti::Pointer {
+                base,
+                class: crate::StorageClass::PhysicalStorageBuffer,
+            } => {
+                // The base may come later in the arena, since the layout of
+                // the pointer doesn't depend on it. This is what allows
+                // recursive structures.
+                if types.try_get(base).is_none() {
+                    return Err(TypeError::UnresolvedBase(base));
+                }
+                if !self
+                    .capabilities
+                    .contains(Capabilities::PHYSICAL_STORAGE_BUFFER)
+                {
+                    return Err(TypeError::UnsupportedCapability(
+                        Capabilities::PHYSICAL_STORAGE_BUFFER,
+                    ));
+                }
+                if base < handle
+                    && !self.types[base.index()]
+                        .flags
+                        .contains(TypeFlags::DATA | TypeFlags::HOST_SHARED)
+                {
+                    return Err(TypeError::InvalidPointerBase(base));
+                }
+
+                // Physical pointers are plain 64-bit addresses, so unlike
+                // the logical ones they can be shared with the host,
+                // including as members of buffer blocks.
+                TypeInfo::new(
+                    TypeFlags::DATA
+                        | TypeFlags::SIZED
+                        | TypeFlags::INTERFACE
+                        | TypeFlags::HOST_SHARED
+                        | TypeFlags::ARGUMENT,
+                    crate::proc::PHYSICAL_POINTER_SPAN,
+                )
+            }
             Ti::Pointer { base, class: _ } => {
                 if base >= handle {
                     return Err(TypeError::UnresolvedBase(base));
@@ -467,7 +521,86 @@ impl super::Validator {
 
                 ti
             }
-            Ti::Image { .. } | Ti::Sampler { .. } => TypeInfo::new(TypeFlags::ARGUMENT, 0),
+            Ti::Image {
+                dim,
+                arrayed,
+                class,
+            } => {
+                use crate::{ImageClass as Ic, ImageDimension as Id};
+                // None of the targets have 3D arrays, depth images are only 2D or cube,
+                // and only 2D images can be multisampled
+                let supported = match class {
+                    Ic::Sampled { multi: true, .. } | Ic::Depth { multi: true } => dim == Id::D2,
+                    Ic::Depth { multi: false } => dim == Id::D2 || dim == Id::Cube,
+                    Ic::Sampled { multi: false, .. } | Ic::Storage(_) => true,
+                } && !(arrayed && dim == Id::D3);
+                if !supported {
+                    return Err(TypeError::UnsupportedImageType {
+                        dim,
+                        arrayed,
+                        class,
+                    });
+                }
+                TypeInfo::new(TypeFlags::ARGUMENT, 0)
+            }
+            Ti::Sampler { .. } => TypeInfo::new(TypeFlags::ARGUMENT, 0),
+            Ti::BindingArray { base, size } => {
+                if base >= handle {
+                    return Err(TypeError::UnresolvedBase(base));
+                }
+                match types[base].inner {
+                    Ti::Image { .. } | Ti::Sampler { .. } => {}
+                    _ => return Err(TypeError::InvalidBindingArrayBaseType(base)),
+                }
+                if let crate::ArraySize::Constant(const_handle) = size {
+                    match constants
+                        .try_get(const_handle)
+                        .and_then(|constant| constant.to_array_length())
+                    {
+                        Some(0) => return Err(TypeError::NonPositiveArrayLength(const_handle)),
+                        Some(_) => {}
+                        None => return Err(TypeError::InvalidArraySizeConstant(const_handle)),
+                    }
+                }
+                TypeInfo::new(TypeFlags::empty(), 0)
+            }
         })
     }
 }
+
+#[test]
+fn unsupported_image_types() {
+    use crate::{ImageClass as Ic, ImageDimension as Id};
+
+    let validate = |dim, arrayed, class| {
+        let mut module = crate::Module::default();
+        module.types.insert(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            },
+        });
+        super::Validator::new(super::ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+    };
+
+    for &(dim, arrayed) in &[(Id::D2, false), (Id::D2, true), (Id::Cube, true)] {
+        validate(dim, arrayed, Ic::Depth { multi: false }).unwrap();
+    }
+    for &(dim, arrayed, class) in &[
+        (Id::D1, false, Ic::Depth { multi: false }),
+        (Id::D1, true, Ic::Depth { multi: false }),
+        (Id::D3, false, Ic::Depth { multi: false }),
+        (Id::Cube, false, Ic::Depth { multi: true }),
+    ] {
+        match validate(dim, arrayed, class) {
+            Err(super::ValidationError::Type {
+                error: TypeError::UnsupportedImageType { .. },
+                ..
+            }) => {}
+            other => panic!("unexpected validation result: {:?}", other),
+        }
+    }
+}
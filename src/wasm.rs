@@ -0,0 +1,120 @@
+//! JavaScript bindings, for translating shaders from web tooling.
+//!
+//! A [`Shader`] is parsed from WGSL and validated once, and can then be
+//! written out with any of the enabled back ends. Errors are reported to
+//! JavaScript as strings.
+
+use crate::valid::{Capabilities, ValidationFlags, Validator};
+use wasm_bindgen::prelude::*;
+
+fn js_error(error: impl std::fmt::Display) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+/// A parsed and validated shader module.
+#[wasm_bindgen]
+pub struct Shader {
+    module: crate::Module,
+    info: crate::valid::ModuleInfo,
+}
+
+#[wasm_bindgen]
+impl Shader {
+    /// Parse and validate a WGSL shader.
+    #[wasm_bindgen(js_name = fromWgsl)]
+    pub fn from_wgsl(source: &str) -> Result<Shader, JsValue> {
+        let module = crate::front::wgsl::parse_str(source)
+            .map_err(|error| JsValue::from_str(&error.emit_to_string(source)))?;
+        let info = Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .map_err(js_error)?;
+        Ok(Shader { module, info })
+    }
+
+    /// Write an entry point as GLSL.
+    ///
+    /// `stage` is one of "vertex", "fragment" or "compute", and `es` selects
+    /// GLSL ES for the given `version`, such as 300 or 310.
+    #[wasm_bindgen(js_name = toGlsl)]
+    pub fn to_glsl(
+        &self,
+        stage: &str,
+        entry_point: &str,
+        version: u16,
+        es: bool,
+    ) -> Result<String, JsValue> {
+        use crate::back::glsl;
+
+        let shader_stage = match stage {
+            "vertex" => crate::ShaderStage::Vertex,
+            "fragment" => crate::ShaderStage::Fragment,
+            "compute" => crate::ShaderStage::Compute,
+            other => return Err(js_error(format!("Unknown shader stage {:?}", other))),
+        };
+        let options = glsl::Options {
+            version: if es {
+                glsl::Version::Embedded(version)
+            } else {
+                glsl::Version::Desktop(version)
+            },
+            ..glsl::Options::default()
+        };
+        let pipeline_options = glsl::PipelineOptions {
+            shader_stage,
+            entry_point: entry_point.to_string(),
+            multiview: None,
+        };
+
+        let mut buffer = String::new();
+        let mut writer = glsl::Writer::new(
+            &mut buffer,
+            &self.module,
+            &self.info,
+            &options,
+            &pipeline_options,
+        )
+        .map_err(js_error)?;
+        writer.write().map_err(js_error)?;
+        Ok(buffer)
+    }
+}
+
+#[cfg(feature = "wgsl-out")]
+#[wasm_bindgen]
+impl Shader {
+    /// Write the module back as WGSL.
+    #[wasm_bindgen(js_name = toWgsl)]
+    pub fn to_wgsl(&self) -> Result<String, JsValue> {
+        crate::back::wgsl::write_string(&self.module, &self.info).map_err(js_error)
+    }
+}
+
+#[cfg(feature = "spv-out")]
+#[wasm_bindgen]
+impl Shader {
+    /// Write the module as SPIR-V words.
+    #[wasm_bindgen(js_name = toSpirv)]
+    pub fn to_spirv(&self) -> Result<Vec<u32>, JsValue> {
+        let options = crate::back::spv::Options::default();
+        crate::back::spv::write_vec(&self.module, &self.info, &options).map_err(js_error)
+    }
+}
+
+#[cfg(feature = "msl-out")]
+#[wasm_bindgen]
+impl Shader {
+    /// Write the module as Metal Shading Language.
+    #[wasm_bindgen(js_name = toMsl)]
+    pub fn to_msl(&self) -> Result<String, JsValue> {
+        use crate::back::msl;
+
+        let (string, _) = msl::write_string(
+            &self.module,
+            &self.info,
+            &msl::Options::default(),
+            &msl::PipelineOptions::default(),
+        )
+        .map_err(js_error)?;
+        Ok(string)
+    }
+}
@@ -21,6 +21,10 @@ fn foo([[builtin(vertex_index)]] vi: u32) -> [[builtin(position)]] vec4<f32> {
 
 	let a = bar.data[arrayLength(&bar.data) - 2u];
 
+	// A store through a chain of accesses should be a single access chain
+	bar.matrix[1].z = 1.0;
+	bar.data[index] = a;
+
 	var c = array<i32, 5>(a, i32(b), 3, 4, 5);
 	c[vi + 1u] = 42;
 	let value = c[vi];
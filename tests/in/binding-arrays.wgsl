@@ -0,0 +1,16 @@
+[[group(0), binding(0)]]
+var textures: binding_array<texture_2d<f32>, 4>;
+[[group(0), binding(1)]]
+var samplers: binding_array<sampler, 4>;
+
+[[stage(fragment)]]
+fn main(
+    [[location(0), interpolate(flat)]] index: u32,
+    [[location(1)]] uv: vec2<f32>,
+) -> [[location(0)]] vec4<f32> {
+    let non_uniform_index = u32(uv.x);
+    var v: vec4<f32> = textureSample(textures[0], samplers[0], uv);
+    v = v + textureSample(textures[index], samplers[index], uv);
+    v = v + textureLoad(textures[non_uniform_index], vec2<i32>(0), 0);
+    return v;
+}
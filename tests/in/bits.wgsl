@@ -0,0 +1,18 @@
+// Data packing and unpacking functions.
+
+[[stage(compute), workgroup_size(1)]]
+fn main() {
+    var u: u32 = 0u;
+    var f2: vec2<f32> = vec2<f32>(0.0);
+    var f4: vec4<f32> = vec4<f32>(0.0);
+    u = pack4x8snorm(f4);
+    u = pack4x8unorm(f4);
+    u = pack2x16snorm(f2);
+    u = pack2x16unorm(f2);
+    u = pack2x16float(f2);
+    f4 = unpack4x8snorm(u);
+    f4 = unpack4x8unorm(u);
+    f2 = unpack2x16snorm(u);
+    f2 = unpack2x16unorm(u);
+    f2 = unpack2x16float(u);
+}
@@ -1,6 +1,27 @@
+fn switch_cases(selector: i32) -> i32 {
+	var result: i32 = 0;
+	switch (selector) {
+		case 3, 1, 2: {
+			result = 1;
+			fallthrough;
+		}
+		case 7: {
+			result = result + 7;
+		}
+		case 4: {
+			result = 4;
+		}
+		default: {
+			result = -1;
+		}
+	}
+	return result;
+}
+
 [[stage(compute), workgroup_size(1)]]
 fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
 	//TODO: execution-only barrier?
 	storageBarrier();
 	workgroupBarrier();
+	let selected = switch_cases(i32(global_id.x));
 }
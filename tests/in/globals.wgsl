@@ -4,7 +4,10 @@ let Foo: bool = true;
 
 var<workgroup> wg : array<f32, 10u>;
 
+var<private> weights : array<f32, 2u> = array<f32, 2u>(0.25, 0.75);
+
 [[stage(compute), workgroup_size(1)]]
 fn main() {
 	wg[3] = 1.0;
+	wg[4] = weights[1];
 }
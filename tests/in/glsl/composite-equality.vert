@@ -0,0 +1,23 @@
+#version 450
+
+struct Material {
+    vec4 color;
+    float roughness;
+    float weights[2];
+};
+
+layout(location = 0) in vec4 a_color;
+layout(location = 0) out float v_same;
+
+void main() {
+    Material a = Material(a_color, 0.5, float[2](1.0, 2.0));
+    Material b = Material(vec4(1.0), 0.5, float[2](1.0, 2.0));
+    if (a == b) {
+        v_same = 1.0;
+    } else if (a.weights != b.weights) {
+        v_same = 0.5;
+    } else {
+        v_same = 0.0;
+    }
+    gl_Position = a_color;
+}
@@ -0,0 +1,13 @@
+#version 450
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+double scale(double x, dvec2 v) {
+    dvec2 r = v * x;
+    return r.x + double(uv.y) + 0.5lf;
+}
+
+void main() {
+    double d = scale(2.0, dvec2(uv));
+    color = vec4(float(d));
+}
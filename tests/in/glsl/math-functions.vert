@@ -47,6 +47,8 @@ void main() {
     mat4 outerProductOut = outerProduct(a, b);
     float distanceOut = distance(a, b);
     vec4 stepOut = step(a, b);
+    uint packOut = packUnorm4x8(a);
+    vec2 unpackOut = unpackHalf2x16(packOut);
     // TODO: support out params in wgsl output
     // vec4 modfOut = modf(a, b);
     // vec4 frexpOut = frexp(a, b);
@@ -0,0 +1,43 @@
+#version 450
+layout(location = 0) flat in int v_index;
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    int a = 0;
+    int b = (a += 1, a * 2);
+    int c = v_index > 0 ? 1 : 2;
+    int i;
+    for (i = 0; i < 3; i++) {
+        c ^= i;
+    }
+    do {
+        c += 1;
+        if (c == 2) {
+            continue;
+        }
+    } while (c < 4);
+    do {
+        c %= 5;
+    } while (c > 8);
+    for (int j = 0; j < 2; j++) {
+        do {
+            c += j;
+            if (c == 3) {
+                continue;
+            }
+        } while (c < 0);
+    }
+    switch (v_index) {
+        case 0:
+            c <<= 1;
+        case 1:
+            c -= 2;
+            break;
+        default:
+            c *= 3;
+    }
+    if (c == 7) {
+        discard;
+    }
+    o_color = vec4(float(a + b + c + i));
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec4 a_pos;
+layout(location = 1) in vec2 a_weights[2];
+
+layout(location = 0) out vec4 v_colors[3];
+layout(location = 3) out float v_weight;
+
+void main() {
+    v_colors[0] = a_pos;
+    v_colors[1] = a_pos * 0.5;
+    v_colors[2] = a_pos * 0.25;
+    v_weight = a_weights[0].x + a_weights[1].y;
+    gl_Position = a_pos;
+}
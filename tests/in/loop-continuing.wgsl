@@ -0,0 +1,35 @@
+fn loop_continuing(n: i32) -> i32 {
+    var i: i32 = 0;
+    var sum: i32 = 0;
+    loop {
+        if (i >= n) {
+            break;
+        }
+        if (i % 2 == 0) {
+            continue;
+        }
+        switch (i) {
+            case 3: {
+                break;
+            }
+            case 5: {
+                continue;
+            }
+            default: {
+                sum = sum + i;
+            }
+        }
+        continuing {
+            i = i + 1;
+            if (sum > 100) {
+                sum = 0;
+            }
+        }
+    }
+    return sum;
+}
+
+[[stage(compute), workgroup_size(1)]]
+fn main() {
+    let sum = loop_continuing(10);
+}
@@ -0,0 +1,26 @@
+enable f16;
+
+[[block]]
+struct Output {
+    value: u32;
+};
+
+[[group(0), binding(0)]]
+var<storage> output: [[access(read_write)]] Output;
+
+let big: i64 = -5000000000i64;
+
+fn wide(a: i64, b: u64) -> u64 {
+    return u64(a - big) + b * 2u64;
+}
+
+fn narrow(a: f16) -> vec2<f16> {
+    return vec2<f16>(a, a * 0.5f16);
+}
+
+[[stage(compute), workgroup_size(1)]]
+fn main() {
+    let w = wide(-1i64, 5u64);
+    let h = narrow(1.5f16);
+    output.value = u32(w) + u32(f32(h.y));
+}
@@ -5,5 +5,19 @@ fn derivatives([[builtin(position)]] foo: vec4<f32>) -> [[location(0)]] vec4<f32
     let x = dpdx(foo);
     let y = dpdy(foo);
     let z = fwidth(foo);
-    return (x + y) * z;
+    let coarse = dpdxCoarse(foo) + dpdyCoarse(foo) + fwidthCoarse(foo);
+    let fine = dpdxFine(foo) + dpdyFine(foo) + fwidthFine(foo);
+    return (x + y) * z + coarse * fine;
+}
+
+[[stage(fragment)]]
+fn geometry(
+    [[location(0)]] normal: vec3<f32>,
+    [[location(1)]] incident: vec3<f32>,
+) -> [[location(0)]] vec4<f32> {
+    let n = normalize(normal);
+    let reflected = reflect(incident, n);
+    let refracted = refract(incident, n, 0.75);
+    let facing = faceForward(n, incident, n);
+    return vec4<f32>(reflected + refracted + facing, distance(incident, n) * length(refracted));
 }
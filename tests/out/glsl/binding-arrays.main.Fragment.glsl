@@ -0,0 +1,29 @@
+#version 310 es
+
+precision highp float;
+precision highp int;
+
+uniform highp sampler2D _group_0_binding_0[4];
+
+layout(location = 0) flat in uint _vs2fs_location0;
+layout(location = 1) smooth in vec2 _vs2fs_location1;
+layout(location = 0) out vec4 _fs2p_location0;
+
+void main() {
+    uint index = _vs2fs_location0;
+    vec2 uv = _vs2fs_location1;
+    vec4 v;
+    uint non_uniform_index = uint(uv.x);
+    vec4 _expr10 = texture(_group_0_binding_0[0], vec2(uv));
+    v = _expr10;
+    vec4 _expr12 = v;
+    vec4 _expr15 = texture(_group_0_binding_0[index], vec2(uv));
+    v = (_expr12 + _expr15);
+    vec4 _expr17 = v;
+    vec4 _expr22 = texelFetch(_group_0_binding_0[non_uniform_index], ivec2(0), 0);
+    v = (_expr17 + _expr22);
+    vec4 _expr24 = v;
+    _fs2p_location0 = _expr24;
+    return;
+}
+
@@ -0,0 +1,37 @@
+#version 310 es
+
+precision highp float;
+precision highp int;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+
+void main() {
+    uint u = 0u;
+    vec2 f2_;
+    vec4 f4_;
+    f2_ = vec2(0.0);
+    f4_ = vec4(0.0);
+    vec4 _expr8 = f4_;
+    u = packSnorm4x8(_expr8);
+    vec4 _expr10 = f4_;
+    u = packUnorm4x8(_expr10);
+    vec2 _expr12 = f2_;
+    u = packSnorm2x16(_expr12);
+    vec2 _expr14 = f2_;
+    u = packUnorm2x16(_expr14);
+    vec2 _expr16 = f2_;
+    u = packHalf2x16(_expr16);
+    uint _expr18 = u;
+    f4_ = unpackSnorm4x8(_expr18);
+    uint _expr20 = u;
+    f4_ = unpackUnorm4x8(_expr20);
+    uint _expr22 = u;
+    f2_ = unpackSnorm2x16(_expr22);
+    uint _expr24 = u;
+    f2_ = unpackUnorm2x16(_expr24);
+    uint _expr26 = u;
+    f2_ = unpackHalf2x16(_expr26);
+    return;
+}
+
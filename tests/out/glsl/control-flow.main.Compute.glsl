@@ -6,10 +6,34 @@ precision highp int;
 layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
 
 
+int switch_cases(int selector) {
+    int result = 0;
+    switch(selector) {
+        case 3:
+        case 1:
+        case 2:
+            result = 1;
+        case 7:
+            int _expr4 = result;
+            result = (_expr4 + 7);
+            break;
+        case 4:
+            result = 4;
+            break;
+        default:
+            result = -1;
+    }
+    int _expr9 = result;
+    return _expr9;
+}
+
 void main() {
     uvec3 global_id = gl_GlobalInvocationID;
-    groupMemoryBarrier();
-    groupMemoryBarrier();
+    memoryBarrierBuffer();
+    barrier();
+    memoryBarrierShared();
+    barrier();
+    int _expr3 = switch_cases(int(global_id.x));
     return;
 }
 
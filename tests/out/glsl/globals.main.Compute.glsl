@@ -7,9 +7,13 @@ layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
 
 shared float wg[10];
 
+float weights[2] = float[2](0.25, 0.75);
+
 
 void main() {
     wg[3] = 1.0;
+    float _expr10 = weights[1];
+    wg[4] = _expr10;
     return;
 }
 
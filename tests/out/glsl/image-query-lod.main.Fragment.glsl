@@ -0,0 +1,29 @@
+#version 450 core
+uniform highp sampler2D _group_0_binding_0;
+
+uniform highp sampler2DShadow _group_0_binding_2;
+
+vec3 uv1 = vec3(0.0, 0.0, 0.0);
+
+vec4 color = vec4(0.0, 0.0, 0.0, 0.0);
+
+layout(location = 0) smooth in vec3 _vs2fs_location0;
+layout(location = 0) out vec4 _fs2p_location0;
+
+void main2() {
+    vec3 _expr5 = uv1;
+    vec2 _expr9 = textureQueryLod(_group_0_binding_0, _expr5.xy);
+    float _expr23 = texture(_group_0_binding_2, vec3(vec2((_expr5.x / _expr5.z), (_expr5.y / _expr5.z)), (_expr5.z / _expr5.z)));
+    color = vec4(_expr9.x, _expr9.y, vec2(textureSize(_group_0_binding_0,1).xy).x, _expr23);
+    return;
+}
+
+void main() {
+    vec3 uv = _vs2fs_location0;
+    uv1 = uv;
+    main2();
+    vec4 _expr3 = color;
+    _fs2p_location0 = _expr3;
+    return;
+}
+
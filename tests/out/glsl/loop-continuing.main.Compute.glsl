@@ -0,0 +1,53 @@
+#version 310 es
+
+precision highp float;
+precision highp int;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+
+int loop_continuing(int n) {
+    int i = 0;
+    int sum = 0;
+    bool loop_init = true;
+    while(true) {
+        if (!loop_init) {
+        int _expr16 = i;
+        i = (_expr16 + 1);
+        int _expr19 = sum;
+        if ((_expr19 > 100)) {
+            sum = 0;
+        }
+        }
+        loop_init = false;
+        int _expr5 = i;
+        if ((_expr5 >= n)) {
+            break;
+        }
+        int _expr7 = i;
+        if (((_expr7 % 2) == 0)) {
+            continue;
+        }
+        int _expr12 = i;
+        switch(_expr12) {
+            case 3:
+                break;
+                break;
+            case 5:
+                continue;
+                break;
+            default:
+                int _expr13 = sum;
+                int _expr14 = i;
+                sum = (_expr13 + _expr14);
+        }
+    }
+    int _expr23 = sum;
+    return _expr23;
+}
+
+void main() {
+    int _expr1 = loop_continuing(10);
+    return;
+}
+
@@ -3,7 +3,7 @@
 precision highp float;
 precision highp int;
 
-struct type10 {
+struct type9 {
     vec2 member;
     vec4 gen_gl_Position;
     float gen_gl_PointSize;
@@ -29,10 +29,10 @@ layout(location = 0) in vec2 _p2vs_location0;
 layout(location = 0) smooth out vec2 _vs2fs_location0;
 
 void main2() {
-    vec2 _expr12 = a_uv1;
-    v_uv = _expr12;
-    vec2 _expr13 = a_pos1;
-    perVertexStruct.gen_gl_Position = vec4(_expr13.x, _expr13.y, 0.0, 1.0);
+    vec2 _expr5 = a_uv1;
+    v_uv = _expr5;
+    vec2 _expr8 = a_pos1;
+    perVertexStruct.gen_gl_Position = vec4(_expr8.x, _expr8.y, 0.0, 1.0);
     return;
 }
 
@@ -47,7 +47,7 @@ void main() {
     float _expr12 = perVertexStruct.gen_gl_PointSize;
     float _expr13[] = perVertexStruct.gen_gl_ClipDistance;
     float _expr14[] = perVertexStruct.gen_gl_CullDistance;
-    type10 _tmp_return = type10(_expr10, _expr11, _expr12, _expr13, _expr14);
+    type9 _tmp_return = type9(_expr10, _expr11, _expr12, _expr13, _expr14);
     _vs2fs_location0 = _tmp_return.member;
     gl_Position = _tmp_return.gen_gl_Position;
     gl_Position.yz = vec2(-gl_Position.y, gl_Position.z * 2.0 - gl_Position.w);
@@ -0,0 +1,25 @@
+#version 450 core
+#extension GL_ARB_gpu_shader_int64 : require
+#extension GL_AMD_gpu_shader_half_float : require
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+buffer Output_block_0Cs {
+    uint value;
+} _group_0_binding_0;
+
+
+uint64_t wide(int64_t a, uint64_t b) {
+    return (uint64_t((a - -5000000000l)) + (b * 2ul));
+}
+
+f16vec2 narrow(float16_t a1) {
+    return f16vec2(a1, (a1 * 0.5hf));
+}
+
+void main() {
+    uint64_t _expr4 = wide(-1l, 5ul);
+    f16vec2 _expr6 = narrow(1.5hf);
+    _group_0_binding_0.value = (uint(_expr4) + uint(float(_expr6.y)));
+    return;
+}
+
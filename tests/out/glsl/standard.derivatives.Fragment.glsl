@@ -10,7 +10,15 @@ void main() {
     vec4 x = dFdx(foo);
     vec4 y = dFdy(foo);
     vec4 z = fwidth(foo);
-    _fs2p_location0 = ((x + y) * z);
+    vec4 _expr4 = dFdx(foo);
+    vec4 _expr5 = dFdy(foo);
+    vec4 _expr7 = fwidth(foo);
+    vec4 coarse = ((_expr4 + _expr5) + _expr7);
+    vec4 _expr9 = dFdx(foo);
+    vec4 _expr10 = dFdy(foo);
+    vec4 _expr12 = fwidth(foo);
+    vec4 fine = ((_expr9 + _expr10) + _expr12);
+    _fs2p_location0 = (((x + y) * z) + (coarse * fine));
     return;
 }
 
@@ -0,0 +1,20 @@
+#version 310 es
+
+precision highp float;
+precision highp int;
+
+layout(location = 0) smooth in vec3 _vs2fs_location0;
+layout(location = 1) smooth in vec3 _vs2fs_location1;
+layout(location = 0) out vec4 _fs2p_location0;
+
+void main() {
+    vec3 normal = _vs2fs_location0;
+    vec3 incident = _vs2fs_location1;
+    vec3 n = normalize(normal);
+    vec3 reflected = reflect(incident, n);
+    vec3 refracted = refract(incident, n, 0.75);
+    vec3 facing = faceforward(n, incident, n);
+    _fs2p_location0 = vec4(((reflected + refracted) + facing), (distance(incident, n) * length(refracted)));
+    return;
+}
+
@@ -0,0 +1,31 @@
+[numthreads(1, 1, 1)]
+void main()
+{
+    uint u = 0u;
+    float2 f2_ = (float2)0;
+    float4 f4_ = (float4)0;
+
+    f2_ = float2(0.0.xx);
+    f4_ = float4(0.0.xxxx);
+    float4 _expr8 = f4_;
+    u = ((uint(int(round(clamp(_expr8[0], -1.0, 1.0) * 127.0))) & 255) | ((uint(int(round(clamp(_expr8[1], -1.0, 1.0) * 127.0))) & 255) << 8) | ((uint(int(round(clamp(_expr8[2], -1.0, 1.0) * 127.0))) & 255) << 16) | ((uint(int(round(clamp(_expr8[3], -1.0, 1.0) * 127.0))) & 255) << 24));
+    float4 _expr10 = f4_;
+    u = (uint(round(clamp(_expr10[0], 0.0, 1.0) * 255.0)) | (uint(round(clamp(_expr10[1], 0.0, 1.0) * 255.0)) << 8) | (uint(round(clamp(_expr10[2], 0.0, 1.0) * 255.0)) << 16) | (uint(round(clamp(_expr10[3], 0.0, 1.0) * 255.0)) << 24));
+    float2 _expr12 = f2_;
+    u = ((uint(int(round(clamp(_expr12[0], -1.0, 1.0) * 32767.0))) & 65535) | ((uint(int(round(clamp(_expr12[1], -1.0, 1.0) * 32767.0))) & 65535) << 16));
+    float2 _expr14 = f2_;
+    u = (uint(round(clamp(_expr14[0], 0.0, 1.0) * 65535.0)) | (uint(round(clamp(_expr14[1], 0.0, 1.0) * 65535.0)) << 16));
+    float2 _expr16 = f2_;
+    u = (f32tof16(_expr16[0]) | (f32tof16(_expr16[1]) << 16));
+    uint _expr18 = u;
+    f4_ = max(float4(int4(_expr18 << 24, _expr18 << 16, _expr18 << 8, _expr18) >> 24) / 127.0, -1.0);
+    uint _expr20 = u;
+    f4_ = (float4(_expr20 & 255, _expr20 >> 8 & 255, _expr20 >> 16 & 255, _expr20 >> 24) / 255.0);
+    uint _expr22 = u;
+    f2_ = max(float2(int2(_expr22 << 16, _expr22) >> 16) / 32767.0, -1.0);
+    uint _expr24 = u;
+    f2_ = (float2(_expr24 & 65535, _expr24 >> 16) / 65535.0);
+    uint _expr26 = u;
+    f2_ = float2(f16tof32(_expr26), f16tof32(_expr26 >> 16));
+    return;
+}
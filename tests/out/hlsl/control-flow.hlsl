@@ -2,10 +2,41 @@ struct ComputeInput_main {
     uint3 global_id1 : SV_DispatchThreadID;
 };
 
+int switch_cases(int selector)
+{
+    int result = 0;
+
+    switch(selector) {
+        case 3:
+        case 1:
+        case 2: {
+            result = 1;
+            int _expr4 = result;
+            result = (_expr4 + 7);
+            break;
+        }
+        case 7: {
+            int _expr4 = result;
+            result = (_expr4 + 7);
+            break;
+        }
+        case 4: {
+            result = 4;
+            break;
+        }
+        default: {
+            result = -1;
+        }
+    }
+    int _expr9 = result;
+    return _expr9;
+}
+
 [numthreads(1, 1, 1)]
 void main(ComputeInput_main computeinput_main)
 {
     DeviceMemoryBarrierWithGroupSync();
     GroupMemoryBarrierWithGroupSync();
+    const int _e3 = switch_cases(int(computeinput_main.global_id1.x));
     return;
 }
@@ -1,10 +1,13 @@
 static const bool Foo = true;
 
 groupshared float wg[10];
+static float weights[2] = { 0.25, 0.75 };
 
 [numthreads(1, 1, 1)]
 void main()
 {
     wg[3] = 1.0;
+    float _expr10 = weights[1];
+    wg[4] = _expr10;
     return;
 }
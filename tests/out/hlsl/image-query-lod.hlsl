@@ -0,0 +1,34 @@
+Texture2D<float4> image : register(t0);
+SamplerState image_sampler : register(s1);
+Texture2D shadow : register(t2);
+SamplerComparisonState shadow_sampler : register(s3);
+static float3 uv1 = (float3)0;
+static float4 color = (float4)0;
+
+struct FragmentInput_main {
+    float3 uv2 : LOC0;
+};
+
+int2 NagaMipDimensions2D(Texture2D<float4>, uint MipLevel)
+{
+    uint4 ret;
+    image.GetDimensions(MipLevel, ret.x, ret.y, ret.z);
+    return ret.xy;
+}
+
+void main1()
+{
+    float3 _expr5 = uv1;
+    float2 _expr9 = float2(image.CalculateLevelOfDetail(image_sampler, _expr5.xy), image.CalculateLevelOfDetailUnclamped(image_sampler, _expr5.xy));
+    float _expr23 = shadow.SampleCmp(shadow_sampler, float2((_expr5.x / _expr5.z), (_expr5.y / _expr5.z)), (_expr5.z / _expr5.z));
+    color = float4(_expr9.x, _expr9.y, float2(NagaMipDimensions2D(image, 1)).x, _expr23);
+    return;
+}
+
+float4 main(FragmentInput_main fragmentinput_main) : SV_Target0
+{
+    uv1 = fragmentinput_main.uv2;
+    main1();
+    float4 _expr3 = color;
+    return _expr3;
+}
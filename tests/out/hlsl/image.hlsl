@@ -211,3 +211,13 @@ float sample_comparison() : SV_Target0
     float s2d_depth_level = image_2d_depth.SampleCmpLevelZero(sampler_cmp, tc, 0.5);
     return (s2d_depth + s2d_depth_level);
 }
+
+float4 gather() : SV_Target0
+{
+    float2 tc = float2(0.5.xx);
+    float4 s2d = image_2d.GatherGreen(sampler_reg, tc);
+    float4 s2d_offset = image_2d.GatherAlpha(sampler_reg, tc, int2(3, 1));
+    float4 s2d_depth = image_2d_depth.GatherCmpRed(sampler_cmp, tc, 0.5);
+    float4 s2d_depth_offset = image_2d_depth.GatherCmpRed(sampler_cmp, tc, 0.5, int2(3, 1));
+    return (((s2d + s2d_offset) + s2d_depth) + s2d_depth_offset);
+}
@@ -5,7 +5,7 @@ struct gl_PerVertex {
     float gl_CullDistance[1] : SV_CullDistance;
 };
 
-struct type10 {
+struct type9 {
     linear float2 member : LOC0;
     float4 gl_Position : SV_Position;
     float gl_PointSize : PSIZE;
@@ -15,7 +15,7 @@ struct type10 {
 
 static float2 v_uv = (float2)0;
 static float2 a_uv1 = (float2)0;
-static gl_PerVertex perVertexStruct = { float4(0.0, 0.0, 0.0, 1.0), 1.0, float(0.0), float(0.0) };
+static gl_PerVertex perVertexStruct = { float4(0.0, 0.0, 0.0, 1.0), 1.0, { 0.0 }, { 0.0 } };
 static float2 a_pos1 = (float2)0;
 
 struct VertexInput_main {
@@ -25,14 +25,14 @@ struct VertexInput_main {
 
 void main1()
 {
-    float2 _expr12 = a_uv1;
-    v_uv = _expr12;
-    float2 _expr13 = a_pos1;
-    perVertexStruct.gl_Position = float4(_expr13.x, _expr13.y, 0.0, 1.0);
+    float2 _expr5 = a_uv1;
+    v_uv = _expr5;
+    float2 _expr8 = a_pos1;
+    perVertexStruct.gl_Position = float4(_expr8.x, _expr8.y, 0.0, 1.0);
     return;
 }
 
-type10 main(VertexInput_main vertexinput_main)
+type9 main(VertexInput_main vertexinput_main)
 {
     a_uv1 = vertexinput_main.a_uv2;
     a_pos1 = vertexinput_main.a_pos2;
@@ -42,6 +42,6 @@ type10 main(VertexInput_main vertexinput_main)
     float _expr12 = perVertexStruct.gl_PointSize;
     float _expr13[1] = perVertexStruct.gl_ClipDistance;
     float _expr14[1] = perVertexStruct.gl_CullDistance;
-    const type10 type10_ = { _expr10, _expr11, _expr12, _expr13, _expr14 };
-    return type10_;
+    const type9 type9_ = { _expr10, _expr11, _expr12, _expr13, _expr14 };
+    return type9_;
 }
@@ -1,6 +1,6 @@
 static const float3 c_ambient = float3(0.05, 0.05, 0.05);
 
-static const uint c_max_lights = 10;
+static const uint c_max_lights = 10u;
 
 struct Globals {
     uint4 num_lights;
@@ -2,10 +2,32 @@ struct FragmentInput_derivatives {
     float4 foo1 : SV_Position;
 };
 
+struct FragmentInput_geometry {
+    float3 normal1 : LOC0;
+    float3 incident1 : LOC1;
+};
+
 float4 derivatives(FragmentInput_derivatives fragmentinput_derivatives) : SV_Target0
 {
     float4 x = ddx(fragmentinput_derivatives.foo1);
     float4 y = ddy(fragmentinput_derivatives.foo1);
     float4 z = fwidth(fragmentinput_derivatives.foo1);
-    return ((x + y) * z);
+    float4 _expr4 = ddx_coarse(fragmentinput_derivatives.foo1);
+    float4 _expr5 = ddy_coarse(fragmentinput_derivatives.foo1);
+    float4 _expr7 = (abs(ddx_coarse(fragmentinput_derivatives.foo1)) + abs(ddy_coarse(fragmentinput_derivatives.foo1)));
+    float4 coarse = ((_expr4 + _expr5) + _expr7);
+    float4 _expr9 = ddx_fine(fragmentinput_derivatives.foo1);
+    float4 _expr10 = ddy_fine(fragmentinput_derivatives.foo1);
+    float4 _expr12 = (abs(ddx_fine(fragmentinput_derivatives.foo1)) + abs(ddy_fine(fragmentinput_derivatives.foo1)));
+    float4 fine = ((_expr9 + _expr10) + _expr12);
+    return (((x + y) * z) + (coarse * fine));
+}
+
+float4 geometry(FragmentInput_geometry fragmentinput_geometry) : SV_Target0
+{
+    float3 n = normalize(fragmentinput_geometry.normal1);
+    float3 reflected = reflect(fragmentinput_geometry.incident1, n);
+    float3 refracted = refract(fragmentinput_geometry.incident1, n, 0.75);
+    float3 facing = faceforward(n, fragmentinput_geometry.incident1, n);
+    return float4(((reflected + refracted) + facing), (distance(fragmentinput_geometry.incident1, n) * length(refracted)));
 }
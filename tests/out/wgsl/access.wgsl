@@ -17,6 +17,8 @@ fn foo([[builtin(vertex_index)]] vi: u32) -> [[builtin(position)]] vec4<f32> {
     let _e9: vec4<f32> = bar.matrix[3];
     let b: f32 = _e9.x;
     let a: i32 = bar.data[(arrayLength(&bar.data) - 2u)];
+    bar.matrix[1][2] = 1.0;
+    bar.data[3] = a;
     c = array<i32,5>(a, i32(b), 3, 4, 5);
     c[(vi + 1u)] = 42;
     let value: i32 = c[vi];
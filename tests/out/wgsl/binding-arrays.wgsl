@@ -0,0 +1,21 @@
+[[group(0), binding(0)]]
+var textures: binding_array<texture_2d<f32>,4>;
+[[group(0), binding(1)]]
+var samplers: binding_array<sampler,4>;
+
+[[stage(fragment)]]
+fn main([[location(0)]] index: u32, [[location(1)]] uv: vec2<f32>) -> [[location(0)]] vec4<f32> {
+    var v: vec4<f32>;
+
+    let non_uniform_index: u32 = u32(uv.x);
+    let _e10: vec4<f32> = textureSample(textures[0], samplers[0], uv);
+    v = _e10;
+    let _e12: vec4<f32> = v;
+    let _e15: vec4<f32> = textureSample(textures[index], samplers[index], uv);
+    v = (_e12 + _e15);
+    let _e17: vec4<f32> = v;
+    let _e22: vec4<f32> = textureLoad(textures[non_uniform_index], vec2<i32>(0), 0);
+    v = (_e17 + _e22);
+    let _e24: vec4<f32> = v;
+    return _e24;
+}
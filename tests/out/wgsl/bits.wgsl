@@ -0,0 +1,30 @@
+[[stage(compute), workgroup_size(1, 1, 1)]]
+fn main() {
+    var u: u32 = 0u;
+    var f2_: vec2<f32>;
+    var f4_: vec4<f32>;
+
+    f2_ = vec2<f32>(0.0);
+    f4_ = vec4<f32>(0.0);
+    let _e8: vec4<f32> = f4_;
+    u = pack4x8snorm(_e8);
+    let _e10: vec4<f32> = f4_;
+    u = pack4x8unorm(_e10);
+    let _e12: vec2<f32> = f2_;
+    u = pack2x16snorm(_e12);
+    let _e14: vec2<f32> = f2_;
+    u = pack2x16unorm(_e14);
+    let _e16: vec2<f32> = f2_;
+    u = pack2x16float(_e16);
+    let _e18: u32 = u;
+    f4_ = unpack4x8snorm(_e18);
+    let _e20: u32 = u;
+    f4_ = unpack4x8unorm(_e20);
+    let _e22: u32 = u;
+    f2_ = unpack2x16snorm(_e22);
+    let _e24: u32 = u;
+    f2_ = unpack2x16unorm(_e24);
+    let _e26: u32 = u;
+    f2_ = unpack2x16float(_e26);
+    return;
+}
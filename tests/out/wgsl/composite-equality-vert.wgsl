@@ -0,0 +1,53 @@
+struct Material {
+    color: vec4<f32>;
+    roughness: f32;
+    weights: [[stride(4)]] array<f32,2>;
+};
+
+struct VertexOutput {
+    [[location(0)]] v_same: f32;
+    [[builtin(position)]] member: vec4<f32>;
+};
+
+var<private> a_color1: vec4<f32>;
+var<private> v_same: f32;
+var<private> gl_Position: vec4<f32>;
+
+fn main1() {
+    var a: Material;
+    var b: Material = Material(vec4<f32>(1.0, 1.0, 1.0, 1.0), 0.5, array<f32,2>(1.0, 2.0));
+
+    let _e2: vec4<f32> = a_color1;
+    a = Material(_e2, 0.5, array<f32,2>(1.0, 2.0));
+    let _e17: Material = a;
+    let _e18: Material = b;
+    if (((all((_e17.color == _e18.color)) && (_e17.roughness == _e18.roughness)) && ((_e17.weights[0] == _e18.weights[0]) && (_e17.weights[1] == _e18.weights[1])))) {
+        {
+            v_same = 1.0;
+        }
+    } else {
+        let _e38: Material = a;
+        let _e40: Material = b;
+        if (((_e38.weights[0] != _e40.weights[0]) || (_e38.weights[1] != _e40.weights[1]))) {
+            {
+                v_same = 0.5;
+            }
+        } else {
+            {
+                v_same = 0.0;
+            }
+        }
+    }
+    let _e52: vec4<f32> = a_color1;
+    gl_Position = _e52;
+    return;
+}
+
+[[stage(vertex)]]
+fn main([[location(0)]] a_color: vec4<f32>) -> VertexOutput {
+    a_color1 = a_color;
+    main1();
+    let _e3: f32 = v_same;
+    let _e5: vec4<f32> = gl_Position;
+    return VertexOutput(_e3, _e5);
+}
@@ -1,6 +1,30 @@
+fn switch_cases(selector: i32) -> i32 {
+    var result: i32 = 0;
+
+    switch(selector) {
+        case 3, 1, 2: {
+            result = 1;
+            fallthrough;
+        }
+        case 7: {
+            let _e4: i32 = result;
+            result = (_e4 + 7);
+        }
+        case 4: {
+            result = 4;
+        }
+        default: {
+            result = -1;
+        }
+    }
+    let _e9: i32 = result;
+    return _e9;
+}
+
 [[stage(compute), workgroup_size(1, 1, 1)]]
 fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
     storageBarrier();
     workgroupBarrier();
+    let _e3: i32 = switch_cases(i32(global_id.x));
     return;
 }
@@ -0,0 +1,41 @@
+struct FragmentOutput {
+    [[location(0)]] color: vec4<f32>;
+};
+
+var<private> uv1: vec2<f32>;
+var<private> color: vec4<f32>;
+
+fn scale(x: f64, v: vec2<f64>) -> f64 {
+    var x1: f64;
+    var v1: vec2<f64>;
+    var r: vec2<f64>;
+
+    x1 = x;
+    v1 = v;
+    let _e6: vec2<f64> = v1;
+    let _e7: f64 = x1;
+    r = (_e6 * _e7);
+    let _e10: vec2<f64> = r;
+    let _e12: vec2<f32> = uv1;
+    return ((_e10.x + f64(_e12.y)) + 0.5f64);
+}
+
+fn main1() {
+    var d: f64;
+
+    let _e3: vec2<f32> = uv1;
+    let _e8: vec2<f32> = uv1;
+    let _e11: f64 = scale(f64(2.0), vec2<f64>(_e8.xy));
+    d = _e11;
+    let _e13: f64 = d;
+    color = vec4<f32>(f32(_e13));
+    return;
+}
+
+[[stage(fragment)]]
+fn main([[location(0)]] uv: vec2<f32>) -> FragmentOutput {
+    uv1 = uv;
+    main1();
+    let _e3: vec4<f32> = color;
+    return FragmentOutput(_e3);
+}
@@ -1,7 +1,7 @@
 [[block]]
 struct PushConstants {
     index: u32;
-    double: vec2<f32>;
+    double: vec2<f64>;
 };
 
 struct FragmentIn {
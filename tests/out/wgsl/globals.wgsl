@@ -1,9 +1,12 @@
 let Foo: bool = true;
 
 var<workgroup> wg: array<f32,10u>;
+var<private> weights: array<f32,2u> = array<f32,2u>(0.25, 0.75);
 
 [[stage(compute), workgroup_size(1, 1, 1)]]
 fn main() {
     wg[3] = 1.0;
+    let _e10: f32 = weights[1];
+    wg[4] = _e10;
     return;
 }
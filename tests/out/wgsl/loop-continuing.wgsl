@@ -0,0 +1,45 @@
+fn loop_continuing(n: i32) -> i32 {
+    var i: i32 = 0;
+    var sum: i32 = 0;
+
+    loop {
+        let _e5: i32 = i;
+        if ((_e5 >= n)) {
+            break;
+        }
+        let _e7: i32 = i;
+        if (((_e7 % 2) == 0)) {
+            continue;
+        }
+        let _e12: i32 = i;
+        switch(_e12) {
+            case 3: {
+                break;
+            }
+            case 5: {
+                continue;
+            }
+            default: {
+                let _e13: i32 = sum;
+                let _e14: i32 = i;
+                sum = (_e13 + _e14);
+            }
+        }
+        continuing {
+            let _e16: i32 = i;
+            i = (_e16 + 1);
+            let _e19: i32 = sum;
+            if ((_e19 > 100)) {
+                sum = 0;
+            }
+        }
+    }
+    let _e23: i32 = sum;
+    return _e23;
+}
+
+[[stage(compute), workgroup_size(1, 1, 1)]]
+fn main() {
+    let _e1: i32 = loop_continuing(10);
+    return;
+}
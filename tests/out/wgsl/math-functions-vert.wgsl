@@ -42,6 +42,8 @@ fn main1() {
     var outerProductOut: mat4x4<f32>;
     var distanceOut: f32;
     var stepOut: vec4<f32>;
+    var packOut: u32;
+    var unpackOut: vec2<f32>;
 
     let _e6: vec4<f32> = a;
     let _e7: vec4<f32> = b;
@@ -137,6 +139,10 @@ fn main1() {
     let _e145: vec4<f32> = a;
     let _e146: vec4<f32> = b;
     stepOut = step(_e145, _e146);
+    let _e149: vec4<f32> = a;
+    packOut = pack4x8unorm(_e149);
+    let _e152: u32 = packOut;
+    unpackOut = unpack2x16float(_e152);
     return;
 }
 
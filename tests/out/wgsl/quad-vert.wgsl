@@ -14,10 +14,10 @@ var<private> perVertexStruct: gl_PerVertex = gl_PerVertex(vec4<f32>(0.0, 0.0, 0.
 var<private> a_pos1: vec2<f32>;
 
 fn main1() {
-    let _e12: vec2<f32> = a_uv1;
-    v_uv = _e12;
-    let _e13: vec2<f32> = a_pos1;
-    perVertexStruct.gl_Position = vec4<f32>(_e13.x, _e13.y, 0.0, 1.0);
+    let _e5: vec2<f32> = a_uv1;
+    v_uv = _e5;
+    let _e8: vec2<f32> = a_pos1;
+    perVertexStruct.gl_Position = vec4<f32>(_e8.x, _e8.y, 0.0, 1.0);
     return;
 }
 
@@ -0,0 +1,27 @@
+enable f16;
+
+[[block]]
+struct Output {
+    value: u32;
+};
+
+let big: i64 = -5000000000i64;
+
+[[group(0), binding(0)]]
+var<storage> output: [[access(read_write)]] Output;
+
+fn wide(a: i64, b: u64) -> u64 {
+    return (u64((a - big)) + (b * 2u64));
+}
+
+fn narrow(a1: f16) -> vec2<f16> {
+    return vec2<f16>(a1, (a1 * 0.5f16));
+}
+
+[[stage(compute), workgroup_size(1, 1, 1)]]
+fn main() {
+    let _e4: u64 = wide(-1i64, 5u64);
+    let _e6: vec2<f16> = narrow(1.5f16);
+    output.value = (u32(_e4) + u32(f32(_e6.y)));
+    return;
+}
@@ -3,5 +3,22 @@ fn derivatives([[builtin(position)]] foo: vec4<f32>) -> [[location(0)]] vec4<f32
     let x: vec4<f32> = dpdx(foo);
     let y: vec4<f32> = dpdy(foo);
     let z: vec4<f32> = fwidth(foo);
-    return ((x + y) * z);
+    let _e4: vec4<f32> = dpdxCoarse(foo);
+    let _e5: vec4<f32> = dpdyCoarse(foo);
+    let _e7: vec4<f32> = fwidthCoarse(foo);
+    let coarse: vec4<f32> = ((_e4 + _e5) + _e7);
+    let _e9: vec4<f32> = dpdxFine(foo);
+    let _e10: vec4<f32> = dpdyFine(foo);
+    let _e12: vec4<f32> = fwidthFine(foo);
+    let fine: vec4<f32> = ((_e9 + _e10) + _e12);
+    return (((x + y) * z) + (coarse * fine));
+}
+
+[[stage(fragment)]]
+fn geometry([[location(0)]] normal: vec3<f32>, [[location(1)]] incident: vec3<f32>) -> [[location(0)]] vec4<f32> {
+    let n: vec3<f32> = normalize(normal);
+    let reflected: vec3<f32> = reflect(incident, n);
+    let refracted: vec3<f32> = refract(incident, n, 0.75);
+    let facing: vec3<f32> = faceForward(n, incident, n);
+    return vec4<f32>(((reflected + refracted) + facing), (distance(incident, n) * length(refracted)));
 }
@@ -0,0 +1,147 @@
+struct FragmentOutput {
+    [[location(0)]] o_color: vec4<f32>;
+};
+
+var<private> v_index1: i32;
+var<private> o_color: vec4<f32>;
+
+fn main1() {
+    var a: i32 = 0;
+    var b: i32;
+    var c: i32;
+    var i: i32;
+    var local: i32;
+    var local1: bool;
+    var j: i32 = 0;
+    var local2: i32;
+    var local3: bool;
+
+    let _e4: i32 = a;
+    a = (_e4 + 1);
+    let _e7: i32 = a;
+    b = (_e7 * 2);
+    let _e11: i32 = v_index1;
+    c = select(2, 1, (_e11 > 0));
+    i = 0;
+    loop {
+        let _e20: i32 = i;
+        if (!((_e20 < 3))) {
+            break;
+        }
+        {
+            let _e29: i32 = c;
+            let _e30: i32 = i;
+            c = (_e29 ^ _e30);
+        }
+        continuing {
+            let _e24: i32 = i;
+            local = _e24;
+            i = (_e24 + 1);
+        }
+    }
+    local1 = true;
+    loop {
+        let _e45: bool = local1;
+        if (_e45) {
+        } else {
+            let _e38: i32 = c;
+            if (!((_e38 < 4))) {
+                break;
+            }
+        }
+        local1 = false;
+        {
+            let _e32: i32 = c;
+            c = (_e32 + 1);
+            let _e35: i32 = c;
+            if ((_e35 == 2)) {
+                {
+                    continue;
+                }
+            }
+        }
+    }
+    loop {
+        {
+            let _e47: i32 = c;
+            c = (_e47 % 5);
+        }
+        let _e50: i32 = c;
+        if (!((_e50 > 8))) {
+            break;
+        }
+    }
+    loop {
+        let _e56: i32 = j;
+        if (!((_e56 < 2))) {
+            break;
+        }
+        {
+            local3 = true;
+            loop {
+                let _e78: bool = local3;
+                if (_e78) {
+                } else {
+                    let _e71: i32 = c;
+                    if (!((_e71 < 0))) {
+                        break;
+                    }
+                }
+                local3 = false;
+                {
+                    let _e65: i32 = c;
+                    let _e66: i32 = j;
+                    c = (_e65 + _e66);
+                    let _e68: i32 = c;
+                    if ((_e68 == 3)) {
+                        {
+                            continue;
+                        }
+                    }
+                }
+            }
+        }
+        continuing {
+            let _e60: i32 = j;
+            local2 = _e60;
+            j = (_e60 + 1);
+        }
+    }
+    let _e80: i32 = v_index1;
+    switch(_e80) {
+        case 0: {
+            let _e82: i32 = c;
+            c = (_e82 << u32(1));
+            fallthrough;
+        }
+        case 1: {
+            let _e87: i32 = c;
+            c = (_e87 - 2);
+            break;
+        }
+        default: {
+            let _e90: i32 = c;
+            c = (_e90 * 3);
+        }
+    }
+    let _e93: i32 = c;
+    if ((_e93 == 7)) {
+        {
+            discard;
+        }
+    }
+    let _e96: i32 = a;
+    let _e97: i32 = b;
+    let _e99: i32 = c;
+    let _e101: i32 = i;
+    o_color = vec4<f32>(f32((((_e96 + _e97) + _e99) + _e101)));
+    return;
+}
+
+[[stage(fragment)]]
+fn main([[location(0)]] v_index: i32) -> FragmentOutput {
+    v_index1 = v_index;
+    main1();
+    let _e3: vec4<f32> = o_color;
+    return FragmentOutput(_e3);
+}
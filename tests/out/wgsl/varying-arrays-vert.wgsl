@@ -0,0 +1,42 @@
+struct VertexOutput {
+    [[location(0)]] v_colors: vec4<f32>;
+    [[location(1)]] v_colors1: vec4<f32>;
+    [[location(2)]] v_colors2: vec4<f32>;
+    [[location(3)]] v_weight: f32;
+    [[builtin(position)]] member: vec4<f32>;
+};
+
+var<private> a_pos1: vec4<f32>;
+var<private> a_weights2: array<vec2<f32>,2>;
+var<private> v_colors: array<vec4<f32>,3>;
+var<private> v_weight: f32;
+var<private> gl_Position: vec4<f32>;
+
+fn main1() {
+    let _e6: vec4<f32> = a_pos1;
+    v_colors[0] = _e6;
+    let _e9: vec4<f32> = a_pos1;
+    v_colors[1] = (_e9 * 0.5);
+    let _e14: vec4<f32> = a_pos1;
+    v_colors[2] = (_e14 * 0.25);
+    let _e19: vec2<f32> = a_weights2[0];
+    let _e23: vec2<f32> = a_weights2[1];
+    v_weight = (_e19.x + _e23.y);
+    let _e27: vec4<f32> = a_pos1;
+    gl_Position = _e27;
+    return;
+}
+
+[[stage(vertex)]]
+fn main([[location(0)]] a_pos: vec4<f32>, [[location(1)]] a_weights: vec2<f32>, [[location(2)]] a_weights1: vec2<f32>) -> VertexOutput {
+    a_pos1 = a_pos;
+    a_weights2[0] = a_weights;
+    a_weights2[1] = a_weights1;
+    main1();
+    let _e9: vec4<f32> = v_colors[0];
+    let _e11: vec4<f32> = v_colors[1];
+    let _e13: vec4<f32> = v_colors[2];
+    let _e15: f32 = v_weight;
+    let _e17: vec4<f32> = gl_Position;
+    return VertexOutput(_e9, _e11, _e13, _e15, _e17);
+}
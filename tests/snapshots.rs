@@ -98,12 +98,12 @@ fn check_targets(module: &naga::Module, name: &str, targets: Targets) {
         if targets.contains(Targets::IR) {
             let config = ron::ser::PrettyConfig::default().with_new_line("\n".to_string());
             let string = ron::ser::to_string_pretty(module, config).unwrap();
-            fs::write(dest.join(format!("ir/{}.ron", name)), string).unwrap();
+            write_snapshot(dest.join(format!("ir/{}.ron", name)), &string);
         }
         if targets.contains(Targets::ANALYSIS) {
             let config = ron::ser::PrettyConfig::default().with_new_line("\n".to_string());
             let string = ron::ser::to_string_pretty(&info, config).unwrap();
-            fs::write(dest.join(format!("analysis/{}.info.ron", name)), string).unwrap();
+            write_snapshot(dest.join(format!("analysis/{}.info.ron", name)), &string);
         }
     }
 
@@ -131,7 +131,7 @@ fn check_targets(module: &naga::Module, name: &str, targets: Targets) {
     {
         if targets.contains(Targets::DOT) {
             let string = naga::back::dot::write(module, Some(&info)).unwrap();
-            fs::write(dest.join(format!("dot/{}.dot", name)), string).unwrap();
+            write_snapshot(dest.join(format!("dot/{}.dot", name)), &string);
         }
     }
     #[cfg(feature = "hlsl-out")]
@@ -148,6 +148,31 @@ fn check_targets(module: &naga::Module, name: &str, targets: Targets) {
     }
 }
 
+/// Write the output of a backend into its snapshot file.
+///
+/// The file is overwritten, so changes show up in `git diff`. If the
+/// `NAGA_SNAPSHOT_CHECK` environment variable is set, the output is compared
+/// against the checked-in snapshot instead, failing on any difference.
+#[allow(dead_code)]
+fn write_snapshot(path: PathBuf, contents: &str) {
+    if std::env::var_os("NAGA_SNAPSHOT_CHECK").is_none() {
+        fs::write(path, contents).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    if expected != contents {
+        for diff in diff::lines(&expected, contents) {
+            match diff {
+                diff::Result::Left(l) => println!("-{}", l),
+                diff::Result::Both(l, _) => println!(" {}", l),
+                diff::Result::Right(r) => println!("+{}", r),
+            }
+        }
+        panic!("Snapshot {} is out of date", path.display());
+    }
+}
+
 #[cfg(feature = "spv-out")]
 fn write_output_spv(
     module: &naga::Module,
@@ -186,11 +211,76 @@ fn write_output_spv(
 
     let spv = spv::write_vec(module, info, &options).unwrap();
 
-    let dis = rspirv::dr::load_words(spv)
-        .expect("Produced invalid SPIR-V")
-        .disassemble();
+    #[cfg(feature = "spv-validate")]
+    validate_spv(&spv, params.spv_version, file_name);
+
+    let spv_module = rspirv::dr::load_words(spv).expect("Produced invalid SPIR-V");
+
+    // The inferred capabilities must be the ones the writer picked
+    if options.capabilities.is_none() {
+        let mut declared: Vec<_> = spv_module
+            .capabilities
+            .iter()
+            .map(|inst| match inst.operands[0] {
+                rspirv::dr::Operand::Capability(capability) => capability,
+                ref other => panic!("Unexpected capability operand {:?}", other),
+            })
+            .collect();
+        declared.sort_by_key(|&capability| capability as u32);
+        assert_eq!(
+            spv::required_capabilities(module, info, &options).unwrap(),
+            declared,
+            "Inferred capabilities of {}",
+            file_name
+        );
+    }
+
+    let dis = spv_module.disassemble();
+
+    write_snapshot(destination.join(format!("spv/{}.spvasm", file_name)), &dis);
+}
+
+/// Run the binary through `spirv-val`, panicking if it's rejected, or if
+/// `spirv-val` can't be found, since the `spv-validate` feature asks for it.
+#[cfg(feature = "spv-validate")]
+fn validate_spv(spv: &[u32], version: (u8, u8), file_name: &str) {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    let mut child = match Command::new("spirv-val")
+        .arg("--target-env")
+        .arg(format!("spv{}.{}", version.0, version.1))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => panic!(
+            "spirv-val not found, it's required by the spv-validate feature to validate {}",
+            file_name
+        ),
+        Err(e) => panic!("Unable to run spirv-val: {}", e),
+    };
 
-    fs::write(destination.join(format!("spv/{}.spvasm", file_name)), dis).unwrap();
+    let bytes = spv
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    child.stdin.take().unwrap().write_all(&bytes).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    if !output.status.success() {
+        panic!(
+            "spirv-val rejected {}:\n{}{}",
+            file_name,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
 }
 
 #[cfg(feature = "msl-out")]
@@ -217,6 +307,7 @@ fn write_output_msl(
 
     let pipeline_options = msl::PipelineOptions {
         allow_point_size: true,
+        entry_point: None,
     };
 
     let (string, tr_info) = msl::write_string(module, info, options, &pipeline_options).unwrap();
@@ -227,7 +318,7 @@ fn write_output_msl(
         }
     }
 
-    fs::write(destination.join(format!("msl/{}.msl", file_name)), string).unwrap();
+    write_snapshot(destination.join(format!("msl/{}.msl", file_name)), &string);
 }
 
 #[cfg(feature = "glsl-out")]
@@ -257,6 +348,7 @@ fn write_output_glsl(
     let pipeline_options = glsl::PipelineOptions {
         shader_stage: stage,
         entry_point: ep_name.to_string(),
+        multiview: None,
     };
 
     let mut buffer = String::new();
@@ -264,11 +356,10 @@ fn write_output_glsl(
         glsl::Writer::new(&mut buffer, module, info, &options, &pipeline_options).unwrap();
     writer.write().unwrap();
 
-    fs::write(
+    write_snapshot(
         destination.join(format!("glsl/{}.{}.{:?}.glsl", file_name, ep_name, stage)),
-        buffer,
-    )
-    .unwrap();
+        &buffer,
+    );
 }
 
 #[cfg(feature = "hlsl-out")]
@@ -298,7 +389,10 @@ fn write_output_hlsl(
     let mut writer = hlsl::Writer::new(&mut buffer, options);
     let reflection_info = writer.write(module, info).unwrap();
 
-    fs::write(destination.join(format!("hlsl/{}.hlsl", file_name)), buffer).unwrap();
+    write_snapshot(
+        destination.join(format!("hlsl/{}.hlsl", file_name)),
+        &buffer,
+    );
 
     // We need a config file for validation script
     // This file contains an info about profiles (shader stages) contains inside generated shader
@@ -353,11 +447,10 @@ fn write_output_hlsl(
     )
     .unwrap();
 
-    fs::write(
+    write_snapshot(
         destination.join(format!("hlsl/{}.hlsl.config", file_name)),
-        config_str,
-    )
-    .unwrap();
+        &config_str,
+    );
 }
 
 #[cfg(feature = "wgsl-out")]
@@ -371,7 +464,10 @@ fn write_output_wgsl(
 
     let string = wgsl::write_string(module, info).unwrap();
 
-    fs::write(destination.join(format!("wgsl/{}.wgsl", file_name)), string).unwrap();
+    write_snapshot(
+        destination.join(format!("wgsl/{}.wgsl", file_name)),
+        &string,
+    );
 }
 
 #[cfg(feature = "wgsl-in")]
@@ -414,7 +510,12 @@ fn convert_wgsl() {
             "image",
             Targets::SPIRV | Targets::METAL | Targets::HLSL | Targets::WGSL,
         ),
-        ("extra", Targets::SPIRV | Targets::METAL | Targets::WGSL),
+        ("extra", Targets::SPIRV | Targets::WGSL),
+        // rspirv can't disassemble 16-bit literals
+        (
+            "scalar-widths",
+            Targets::METAL | Targets::GLSL | Targets::WGSL,
+        ),
         (
             "operators",
             Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::HLSL | Targets::WGSL,
@@ -428,10 +529,18 @@ fn convert_wgsl() {
             "control-flow",
             Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::HLSL | Targets::WGSL,
         ),
+        (
+            "loop-continuing",
+            Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::WGSL,
+        ),
         (
             "standard",
             Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::HLSL | Targets::WGSL,
         ),
+        (
+            "bits",
+            Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::HLSL | Targets::WGSL,
+        ),
         //TODO: GLSL https://github.com/gfx-rs/naga/issues/874
         (
             "interface",
@@ -446,6 +555,10 @@ fn convert_wgsl() {
             "texture-arg",
             Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::WGSL,
         ),
+        (
+            "binding-arrays",
+            Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::WGSL,
+        ),
     ];
 
     for &(name, targets) in inputs.iter() {
@@ -476,12 +589,6 @@ fn convert_spv(name: &str, adjust_coordinate_space: bool, targets: Targets) {
     )
     .unwrap();
     check_targets(&module, name, targets);
-    naga::valid::Validator::new(
-        naga::valid::ValidationFlags::all(),
-        naga::valid::Capabilities::empty(),
-    )
-    .validate(&module)
-    .unwrap();
 }
 
 #[cfg(feature = "spv-in")]
@@ -506,6 +613,22 @@ fn convert_spv_pointer_access() {
     convert_spv("pointer-access", true, Targets::SPIRV);
 }
 
+#[cfg(feature = "spv-in")]
+#[test]
+fn convert_spv_image_query_lod() {
+    convert_spv(
+        "image-query-lod",
+        false,
+        Targets::IR | Targets::SPIRV | Targets::METAL | Targets::GLSL | Targets::HLSL,
+    );
+}
+
+#[cfg(all(feature = "spv-in", feature = "spv-out"))]
+#[test]
+fn convert_spv_physical_pointer() {
+    convert_spv("physical-pointer", false, Targets::SPIRV | Targets::IR);
+}
+
 #[cfg(feature = "glsl-in")]
 #[allow(unused_variables)]
 #[test]
@@ -531,13 +654,13 @@ fn convert_glsl_folder() {
         let is_params_used = PathBuf::from(&params_path).exists();
         println!("Processing {}", file_name);
 
-        let mut entry_points = naga::FastHashMap::default();
-        if is_params_used {
+        let options = if is_params_used {
             let params: Parameters = match fs::read_to_string(&params_path) {
                 Ok(string) => ron::de::from_str(&string).expect("Couldn't find param file"),
                 Err(_) => panic!("Can't parse glsl params ron file: {:?}", &params_path),
             };
 
+            let mut entry_points = naga::FastHashMap::default();
             if let Some(vert) = params.glsl_vert_ep_name {
                 entry_points.insert(vert, naga::ShaderStage::Vertex);
             };
@@ -549,6 +672,16 @@ fn convert_glsl_folder() {
             if let Some(comp) = params.glsl_comp_ep_name {
                 entry_points.insert(comp, naga::ShaderStage::Compute);
             };
+
+            let strip_unused_linkages = entry_points.len() > 1;
+            // Keep covering the entry point map until it's removed.
+            #[allow(deprecated)]
+            let options = naga::front::glsl::Options {
+                entry_points,
+                strip_unused_linkages,
+                ..Default::default()
+            };
+            options
         } else {
             let stage = match entry.path().extension().and_then(|s| s.to_str()).unwrap() {
                 "vert" => naga::ShaderStage::Vertex,
@@ -556,17 +689,12 @@ fn convert_glsl_folder() {
                 "comp" => naga::ShaderStage::Compute,
                 ext => panic!("Unknown extension for glsl file {}", ext),
             };
-            entry_points.insert("main".to_string(), stage);
-        }
+            naga::front::glsl::Options::from_stage(stage)
+        };
 
-        let strip_unused_linkages = entry_points.len() > 1;
         let module = naga::front::glsl::parse_str(
             &fs::read_to_string(entry.path()).expect("Couldn't find glsl file"),
-            &naga::front::glsl::Options {
-                entry_points,
-                defines: Default::default(),
-                strip_unused_linkages: strip_unused_linkages,
-            },
+            &options,
         )
         .unwrap();
 
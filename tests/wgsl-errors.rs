@@ -77,6 +77,20 @@ fn invalid_scalar_width() {
     );
 }
 
+#[test]
+fn f16_without_enable() {
+    check(
+        "let scale: f16 = 1.0;",
+        r###"error: 'f16' requires the f16 extension, which is not enabled
+  ┌─ wgsl:1:12
+  │
+1 │ let scale: f16 = 1.0;
+  │            ^^^ requires 'enable f16;'
+
+"###,
+    );
+}
+
 #[test]
 fn invalid_texture_sample_type() {
     check(
@@ -190,6 +204,22 @@ fn bad_texture_sample_type() {
     );
 }
 
+#[test]
+fn bad_matrix_scalar_type() {
+    check(
+        r#"
+            var<private> m : mat2x2<i32>;
+        "#,
+        r#"error: matrix scalar type must be a floating-point type, but found i32
+  ┌─ wgsl:2:37
+  │
+2 │             var<private> m : mat2x2<i32>;
+  │                                     ^^^ must be a floating-point type
+
+"#,
+    );
+}
+
 #[test]
 fn bad_for_initializer() {
     check(
@@ -608,6 +638,49 @@ fn invalid_arrays() {
     }
 }
 
+#[test]
+fn pointer_operands() {
+    check_validation_error! {
+        "
+        fn negate() -> f32 {
+            var a: f32;
+            return -&a;
+        }
+        ":
+        Err(naga::valid::ValidationError::Function {
+            error: naga::valid::FunctionError::Expression {
+                error: naga::valid::ExpressionError::PointerOperand(_),
+                ..
+            },
+            ..
+        })
+    }
+
+    // Pointer arguments have to be dereferenced explicitly
+    check_validation_error! {
+        "
+        fn double(p: ptr<private, f32>) -> f32 {
+            return p * 2.0;
+        }
+        ":
+        Err(naga::valid::ValidationError::Function {
+            error: naga::valid::FunctionError::Expression {
+                error: naga::valid::ExpressionError::PointerOperand(_),
+                ..
+            },
+            ..
+        })
+    }
+    check_validation_error! {
+        "
+        fn double(p: ptr<private, f32>) -> f32 {
+            return *p * 2.0;
+        }
+        ":
+        Ok(_)
+    }
+}
+
 #[test]
 fn invalid_structs() {
     check_validation_error! {
@@ -741,6 +814,222 @@ fn missing_bindings() {
     }
 }
 
+#[test]
+fn invalid_built_ins() {
+    check_validation_error! {
+        "
+        [[stage(vertex)]]
+        fn vertex() -> [[builtin(frag_depth)]] f32 {
+           return 0.0;
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Vertex,
+            error: naga::valid::EntryPointError::Result(
+                naga::valid::VaryingError::InvalidBuiltInStage {
+                    built_in: naga::BuiltIn::FragDepth,
+                    stage: naga::ShaderStage::Vertex,
+                    output: true,
+                },
+            ),
+            ..
+        })
+    }
+
+    check_validation_error! {
+        "
+        struct FragmentIn {
+          [[location(0)]] color: vec4<f32>;
+          [[builtin(vertex_index)]] index: u32;
+        };
+
+        [[stage(fragment)]]
+        fn fragment(input: FragmentIn) -> [[location(0)]] vec4<f32> {
+           return input.color;
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Fragment,
+            error: naga::valid::EntryPointError::Argument(
+                0,
+                naga::valid::VaryingError::Member {
+                    index: 1,
+                    ref name,
+                    ref error,
+                },
+            ),
+            ..
+        })
+        if name == "index" && matches!(**error, naga::valid::VaryingError::InvalidBuiltInStage {
+            built_in: naga::BuiltIn::VertexIndex,
+            output: false,
+            ..
+        })
+    }
+
+    check_validation_error! {
+        "
+        fn helper([[builtin(position)]] position: vec4<f32>) -> f32 {
+           return position.x;
+        }
+        ":
+        Err(naga::valid::ValidationError::Function {
+            error: naga::valid::FunctionError::UnexpectedArgumentBinding {
+                index: 0,
+                ..
+            },
+            ..
+        })
+    }
+}
+
+#[test]
+fn binding_collision() {
+    check_validation_error! {
+        "
+        [[group(0), binding(0)]] var t: texture_2d<f32>;
+        [[group(0), binding(0)]] var s: sampler;
+
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+           return textureSample(t, s, vec2<f32>(0.0));
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            error: naga::valid::EntryPointError::BindingCollision {
+                first,
+                second,
+            },
+            ..
+        })
+        if first.index() == 0 && second.index() == 1
+    }
+
+    // Globals viewing the same resource the same way can't alias it either
+    check_validation_error! {
+        "
+        [[group(0), binding(0)]] var t: texture_2d<f32>;
+        [[group(0), binding(0)]] var u: texture_2d<f32>;
+        [[group(0), binding(1)]] var s: sampler;
+
+        [[stage(fragment)]]
+        fn main() -> [[location(0)]] vec4<f32> {
+           return textureSample(t, s, vec2<f32>(0.0)) + textureSample(u, s, vec2<f32>(1.0));
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            error: naga::valid::EntryPointError::BindingCollision {
+                first,
+                second,
+            },
+            ..
+        })
+        if first.index() == 0 && second.index() == 1
+    }
+}
+
+#[test]
+fn exceeded_limits() {
+    let module = naga::front::wgsl::parse_str(
+        "
+        [[group(0), binding(2)]] var t: texture_2d<f32>;
+
+        [[stage(vertex)]]
+        fn vertex([[location(3)]] position: vec4<f32>) -> [[builtin(position)]] vec4<f32> {
+           return position;
+        }
+
+        [[stage(fragment)]]
+        fn fragment() -> [[location(1)]] vec4<f32> {
+           return textureLoad(t, vec2<i32>(0), 0);
+        }
+
+        [[stage(compute), workgroup_size(64, 4)]]
+        fn compute() {}
+        ",
+    )
+    .unwrap();
+    let validate = |limits| {
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .with_limits(limits)
+        .validate(&module)
+    };
+    let limits = naga::valid::Limits {
+        max_vertex_attributes: 4,
+        max_color_attachments: 2,
+        max_bind_groups: 1,
+        max_bindings_per_bind_group: 3,
+        max_compute_workgroup_size: [64, 4, 1],
+    };
+    validate(limits.clone()).unwrap();
+
+    match validate(naga::valid::Limits {
+        max_bindings_per_bind_group: 2,
+        ..limits.clone()
+    }) {
+        Err(naga::valid::ValidationError::GlobalVariable {
+            ref name,
+            error:
+                naga::valid::GlobalVariableError::BindingOutOfRange {
+                    binding: 2,
+                    limit: 2,
+                },
+            ..
+        }) if name == "t" => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match validate(naga::valid::Limits {
+        max_vertex_attributes: 3,
+        ..limits.clone()
+    }) {
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Vertex,
+            error:
+                naga::valid::EntryPointError::Argument(
+                    0,
+                    naga::valid::VaryingError::LocationOutOfRange {
+                        location: 3,
+                        limit: 3,
+                    },
+                ),
+            ..
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match validate(naga::valid::Limits {
+        max_color_attachments: 1,
+        ..limits.clone()
+    }) {
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Fragment,
+            error:
+                naga::valid::EntryPointError::Result(naga::valid::VaryingError::LocationOutOfRange {
+                    location: 1,
+                    limit: 1,
+                }),
+            ..
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match validate(naga::valid::Limits {
+        max_compute_workgroup_size: [32, 32, 32],
+        ..limits
+    }) {
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Compute,
+            error:
+                naga::valid::EntryPointError::OutOfRangeWorkgroupSize {
+                    size: [64, 4, 1], ..
+                },
+            ..
+        }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
 #[test]
 fn invalid_access() {
     check_validation_error! {
@@ -820,3 +1109,195 @@ fn invalid_local_vars() {
         if local_var_name == "not_okay"
     }
 }
+
+#[test]
+fn entry_point_global_uses() {
+    let module = naga::front::wgsl::parse_str(
+        "
+        [[block]] struct Data { value: f32; };
+        [[group(0), binding(0)]] var<storage> data: [[access(read_write)]] Data;
+        [[group(0), binding(1)]] var<uniform> scale: Data;
+
+        fn store(value: f32) {
+            data.value = value;
+        }
+
+        [[stage(compute), workgroup_size(1)]]
+        fn main() {
+            store(scale.value);
+        }
+        ",
+    )
+    .unwrap();
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    let (data, _) = module.global_variables.iter().next().unwrap();
+    let (scale, _) = module.global_variables.iter().nth(1).unwrap();
+    assert_eq!(
+        info.get_entry_point_info(0, data),
+        naga::valid::GlobalUse::WRITE
+    );
+    assert_eq!(
+        info.get_entry_point_info(0, scale),
+        naga::valid::GlobalUse::READ
+    );
+    assert_eq!(info.get_entry_point(0).global_uses().count(), 2);
+    assert_eq!(
+        info.global_variable_stages(&module, data),
+        naga::valid::ShaderStages::COMPUTE
+    );
+}
+
+#[test]
+fn chained_comparison() {
+    check(
+        r#"
+            fn f(x: f32, y: f32, z: f32) -> bool {
+                return x < y <= z;
+            }
+        "#,
+        r###"error: comparison operators can't be chained
+  ┌─ wgsl:3:26
+  │
+3 │                 return x < y <= z;
+  │                          ^   ^^ second comparison
+  │                          │    
+  │                          first comparison
+  │
+  = note: `a < b <= c` would compare the boolean result of `a < b` with `c`, split it into `a < b && b <= c` instead
+
+"###,
+    );
+}
+
+#[test]
+fn chained_assignment() {
+    check(
+        r#"
+            fn f() {
+                var a: f32;
+                var b: f32;
+                a = b = 1.0;
+            }
+        "#,
+        r###"error: assignment can't be used as an expression
+  ┌─ wgsl:5:23
+  │
+5 │                 a = b = 1.0;
+  │                       ^ unexpected assignment
+  │
+  = note: in WGSL, assignment is a statement and has no value, so `a = b = c;` has to be written as `b = c; a = b;`
+
+"###,
+    );
+}
+
+#[test]
+fn bad_gather_component() {
+    check(
+        r#"
+            [[group(0), binding(0)]] var t: texture_2d<f32>;
+            [[group(0), binding(1)]] var s: sampler;
+            fn f() -> vec4<f32> {
+                return textureGather(4, t, s, vec2<f32>(0.5));
+            }
+        "#,
+        r###"error: gather component must be 0, 1, 2 or 3, but found '4'
+  ┌─ wgsl:5:38
+  │
+5 │                 return textureGather(4, t, s, vec2<f32>(0.5));
+  │                                      ^ not a component index
+
+"###,
+    );
+}
+
+#[test]
+fn invalid_gathers() {
+    check_validation_error! {
+        "
+        [[group(0), binding(0)]] var t: texture_3d<f32>;
+        [[group(0), binding(1)]] var s: sampler;
+        fn f() -> vec4<f32> {
+            return textureGather(0, t, s, vec3<f32>(0.5));
+        }
+        ":
+        Err(naga::valid::ValidationError::Function {
+            error: naga::valid::FunctionError::Expression {
+                error: naga::valid::ExpressionError::InvalidGatherDimension(_),
+                ..
+            },
+            ..
+        })
+    }
+
+    check_validation_error! {
+        "
+        [[group(0), binding(0)]] var t: texture_depth_2d;
+        [[group(0), binding(1)]] var s: sampler;
+        fn f() -> vec4<f32> {
+            return textureGather(1, t, s, vec2<f32>(0.5));
+        }
+        ":
+        Err(naga::valid::ValidationError::Function {
+            error: naga::valid::FunctionError::Expression {
+                error: naga::valid::ExpressionError::InvalidGatherComponent(_, _),
+                ..
+            },
+            ..
+        })
+    }
+}
+
+#[test]
+fn swizzle_assignment() {
+    check(
+        r#"
+            fn f() {
+                var v: vec4<f32>;
+                v.xy = vec2<f32>(1.0);
+            }
+        "#,
+        r###"error: invalid field accessor `xy`
+  ┌─ wgsl:4:19
+  │
+4 │                 v.xy = vec2<f32>(1.0);
+  │                   ^^ invalid accessor
+
+"###,
+    );
+}
+
+#[test]
+fn recover_from_statement_errors() {
+    let input = r#"
+        fn f(x: f32) -> f32 {
+            let a = 1.0 +;
+            if (x > 0.0) {
+                let b = foo;
+            }
+            let c = ;
+            return x;
+        }
+        fn g() {
+            let d = bar;
+            for (var i = 0; i < ; i = i + 1) {}
+            let e = baz;
+        }
+    "#;
+    let errors = naga::front::wgsl::parse_str_recovering(input).expect_err("expected errors");
+    let locations: Vec<_> = errors.iter().map(|error| error.location(input)).collect();
+    assert_eq!(
+        locations,
+        [(3, 26), (5, 25), (7, 21), (11, 21), (12, 33), (13, 21)]
+    );
+
+    // The first error is the one reported without recovery
+    let error = naga::front::wgsl::parse_str(input).unwrap_err();
+    assert_eq!(error.location(input), (3, 26));
+}